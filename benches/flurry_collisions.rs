@@ -0,0 +1,102 @@
+/* Benchmarks for maps whose keys collide heavily.
+ *
+ * The keys are hashed so that they all land in a handful of bins, which the map then turns into
+ * trees. This measures how those degraded bins perform, independently of the rest of the table.
+ */
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use flurry::{epoch, HashMap};
+use std::hash::{BuildHasherDefault, Hasher};
+
+const SIZE: usize = 1000;
+
+/// Hashes each integer to its value modulo `BINS`, so that keys only spread over that many bins.
+#[derive(Default)]
+struct FewBinsHasher(u64);
+
+const BINS: u64 = 8;
+
+impl Hasher for FewBinsHasher {
+    fn finish(&self) -> u64 {
+        self.0 % BINS
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 = (self.0 << 8) | u64::from(b);
+        }
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.0 = i;
+    }
+}
+
+type FewBins = BuildHasherDefault<FewBinsHasher>;
+
+fn filled() -> HashMap<u64, u64, FewBins> {
+    let map = HashMap::with_capacity_and_hasher(SIZE, FewBins::default());
+    {
+        // keep the guard local so that garbage can be reclaimed during the benchmark
+        let guard = epoch::pin();
+        for i in 0..SIZE as u64 {
+            map.insert(i, i, &guard);
+        }
+    }
+    map
+}
+
+fn insert_colliding(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert_colliding");
+    group.throughput(Throughput::Elements(SIZE as u64));
+    group.bench_function(BenchmarkId::from_parameter(BINS), |b| {
+        let map = HashMap::with_capacity_and_hasher(SIZE, FewBins::default());
+        b.iter(|| {
+            let guard = epoch::pin();
+            map.clear(&guard);
+            for i in 0..SIZE as u64 {
+                map.insert(i, i, &guard);
+            }
+            black_box(&map);
+        });
+    });
+    group.finish();
+}
+
+fn get_colliding(c: &mut Criterion) {
+    let mut group = c.benchmark_group("get_colliding");
+    group.throughput(Throughput::Elements(SIZE as u64));
+    let map = filled();
+    group.bench_function(BenchmarkId::from_parameter(BINS), |b| {
+        b.iter(|| {
+            let guard = epoch::pin();
+            for i in 0..SIZE as u64 {
+                black_box(map.get(&i, &guard));
+            }
+        });
+    });
+    group.finish();
+}
+
+fn get_absent_colliding(c: &mut Criterion) {
+    let mut group = c.benchmark_group("get_absent_colliding");
+    group.throughput(Throughput::Elements(SIZE as u64));
+    let map = filled();
+    group.bench_function(BenchmarkId::from_parameter(BINS), |b| {
+        b.iter(|| {
+            let guard = epoch::pin();
+            for i in SIZE as u64..2 * SIZE as u64 {
+                black_box(map.get(&i, &guard));
+            }
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    insert_colliding,
+    get_colliding,
+    get_absent_colliding,
+);
+criterion_main!(benches);
@@ -0,0 +1,93 @@
+use std::fmt::{self, Debug, Formatter};
+
+/// A list of insertions and removals to apply to a [`HashMap`](crate::HashMap) in one go.
+///
+/// Applying a batch with [`HashMap::apply_batch`](crate::HashMap::apply_batch) is cheaper than
+/// performing each of its operations individually: operations are grouped by the bin they touch,
+/// and each bin is locked only once for all of them. This makes batches a good fit for threads that
+/// produce large amounts of changes, such as logging or ingest pipelines.
+///
+/// If a batch contains several operations for the same key, only the last one takes effect.
+///
+/// ```
+/// use flurry::{HashMap, WriteBatch};
+///
+/// let map = HashMap::new();
+/// map.pin().insert(0, "zero");
+///
+/// let mut batch = WriteBatch::new();
+/// batch.insert(1, "one");
+/// batch.insert(2, "two");
+/// batch.remove(0);
+/// batch.remove(2);
+///
+/// map.apply_batch(batch, &map.guard());
+/// assert_eq!(map.pin().get(&1), Some(&"one"));
+/// assert_eq!(map.len(), 1);
+/// ```
+pub struct WriteBatch<K, V> {
+    /// The operations in the order they were added, where `None` means removal.
+    pub(crate) ops: Vec<(K, Option<V>)>,
+}
+
+impl<K, V> WriteBatch<K, V> {
+    /// Creates an empty batch.
+    pub fn new() -> Self {
+        WriteBatch { ops: Vec::new() }
+    }
+
+    /// Creates an empty batch with room for `capacity` operations.
+    pub fn with_capacity(capacity: usize) -> Self {
+        WriteBatch {
+            ops: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Adds an insertion of `value` for `key` to the batch.
+    pub fn insert(&mut self, key: K, value: V) {
+        self.ops.push((key, Some(value)));
+    }
+
+    /// Adds a removal of `key` to the batch.
+    pub fn remove(&mut self, key: K) {
+        self.ops.push((key, None));
+    }
+
+    /// Returns the number of operations in the batch.
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// Returns `true` if the batch contains no operations.
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Removes all operations from the batch, keeping its allocation.
+    pub fn clear(&mut self) {
+        self.ops.clear();
+    }
+}
+
+impl<K, V> Default for WriteBatch<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> Debug for WriteBatch<K, V>
+where
+    K: Debug,
+    V: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(&self.ops).finish()
+    }
+}
+
+impl<K, V> Extend<(K, V)> for WriteBatch<K, V> {
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        self.ops
+            .extend(iter.into_iter().map(|(key, value)| (key, Some(value))));
+    }
+}
@@ -0,0 +1,332 @@
+//! Using a map as a caching layer in front of a backing store.
+//!
+//! A [`Cache`] wraps a [`HashMap`] together with a [`CacheLoader`], which is consulted when a
+//! lookup misses, and a [`CacheWriter`], which is told about every insertion and removal so that
+//! the backing store stays up to date. Writes can either be propagated synchronously, before the
+//! map itself is changed ([`Cache::with_writer`]), or be queued and applied by a background
+//! thread, in order, without blocking the caller ([`Cache::with_write_behind`]).
+//!
+//! The writer is not synchronized with the map's bin locks. If several threads change the same
+//! key at the same time, the writer may see those changes in a different order than the map
+//! does. Callers that need the store to end up exactly matching the cache should make sure that
+//! each key is only written by one thread at a time.
+//!
+//! ```
+//! use flurry::cache::{Cache, CacheLoader, CacheWriter};
+//! use flurry::HashMap;
+//! use std::collections::BTreeMap;
+//! use std::sync::Mutex;
+//!
+//! // a backing store that is slow to query
+//! #[derive(Default)]
+//! struct Store(Mutex<BTreeMap<u32, String>>);
+//!
+//! impl CacheLoader<u32, String> for Store {
+//!     fn load(&self, key: &u32) -> Option<String> {
+//!         self.0.lock().unwrap().get(key).cloned()
+//!     }
+//! }
+//!
+//! impl CacheWriter<u32, String> for Store {
+//!     fn write(&self, key: &u32, value: &String) {
+//!         self.0.lock().unwrap().insert(*key, value.clone());
+//!     }
+//!
+//!     fn delete(&self, key: &u32) {
+//!         self.0.lock().unwrap().remove(key);
+//!     }
+//! }
+//!
+//! let store = std::sync::Arc::new(Store::default());
+//! store.0.lock().unwrap().insert(1, String::from("one"));
+//!
+//! let cache = Cache::new(HashMap::new())
+//!     .with_loader(store.clone())
+//!     .with_writer(store.clone());
+//! let guard = cache.guard();
+//!
+//! // misses are loaded from the store
+//! assert_eq!(cache.get(&1, &guard).map(String::as_str), Some("one"));
+//! assert!(cache.map().contains_key(&1, &guard));
+//!
+//! // and changes are written back to it
+//! cache.insert(2, String::from("two"), &guard);
+//! cache.remove(&1, &guard);
+//! assert_eq!(store.load(&2).as_deref(), Some("two"));
+//! assert_eq!(store.load(&1), None);
+//! ```
+
+use crate::{HashMap, TrackedGuard, TreeKey};
+use crossbeam_epoch::Guard;
+use parking_lot::Mutex;
+use std::fmt::{self, Debug, Formatter};
+use std::hash::{BuildHasher, Hash};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+/// A source of values for keys that are not in a [`Cache`].
+pub trait CacheLoader<K, V>: Send + Sync {
+    /// Returns the value for `key` in the backing store, if it has one.
+    ///
+    /// This may be called concurrently for the same key if several threads miss on it at the same
+    /// time, in which case only one of the loaded values is kept.
+    fn load(&self, key: &K) -> Option<V>;
+}
+
+/// A destination for the changes made to a [`Cache`].
+pub trait CacheWriter<K, V>: Send + Sync {
+    /// Stores `value` for `key` in the backing store.
+    fn write(&self, key: &K, value: &V);
+
+    /// Removes `key` from the backing store.
+    fn delete(&self, key: &K);
+}
+
+impl<K, V, T> CacheLoader<K, V> for Arc<T>
+where
+    T: CacheLoader<K, V> + ?Sized,
+{
+    fn load(&self, key: &K) -> Option<V> {
+        (**self).load(key)
+    }
+}
+
+impl<K, V, T> CacheWriter<K, V> for Arc<T>
+where
+    T: CacheWriter<K, V> + ?Sized,
+{
+    fn write(&self, key: &K, value: &V) {
+        (**self).write(key, value)
+    }
+
+    fn delete(&self, key: &K) {
+        (**self).delete(key)
+    }
+}
+
+enum Write<K, V> {
+    Store(K, V),
+    Delete(K),
+    Flush(mpsc::Sender<()>),
+}
+
+/// A queue of writes that a background thread applies to a [`CacheWriter`].
+struct WriteBehind<K, V> {
+    queue: Mutex<mpsc::Sender<Write<K, V>>>,
+    clone_value: fn(&V) -> V,
+    worker: Option<thread::JoinHandle<()>>,
+}
+
+impl<K, V> WriteBehind<K, V>
+where
+    K: 'static + Send,
+    V: 'static + Send,
+{
+    fn spawn<W>(writer: W, clone_value: fn(&V) -> V) -> Self
+    where
+        W: 'static + CacheWriter<K, V>,
+    {
+        let (queue, writes) = mpsc::channel();
+        let worker = thread::Builder::new()
+            .name(String::from("flurry-write-behind"))
+            .spawn(move || {
+                for write in writes {
+                    match write {
+                        Write::Store(key, value) => writer.write(&key, &value),
+                        Write::Delete(key) => writer.delete(&key),
+                        Write::Flush(done) => {
+                            let _ = done.send(());
+                        }
+                    }
+                }
+            })
+            .expect("failed to spawn write-behind thread");
+        WriteBehind {
+            queue: Mutex::new(queue),
+            clone_value,
+            worker: Some(worker),
+        }
+    }
+}
+
+impl<K, V> WriteBehind<K, V> {
+    fn push(&self, write: Write<K, V>) {
+        // the worker only exits once the queue is dropped, so this cannot fail
+        let _ = self.queue.lock().send(write);
+    }
+}
+
+impl<K, V> Drop for WriteBehind<K, V> {
+    fn drop(&mut self) {
+        // disconnect the queue so that the worker exits once it has applied all queued writes
+        let (disconnected, _) = mpsc::channel();
+        drop(std::mem::replace(&mut *self.queue.lock(), disconnected));
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+enum Writer<K, V> {
+    Through(Box<dyn CacheWriter<K, V>>),
+    Behind(WriteBehind<K, V>),
+}
+
+/// A [`HashMap`] backed by a store that misses are loaded from and changes are written to.
+///
+/// See the [module-level documentation](self) for details.
+pub struct Cache<K, V, S = crate::DefaultHashBuilder> {
+    map: HashMap<K, V, S>,
+    loader: Option<Box<dyn CacheLoader<K, V>>>,
+    writer: Option<Writer<K, V>>,
+}
+
+impl<K, V, S> Debug for Cache<K, V, S>
+where
+    K: Debug,
+    V: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let writer = match self.writer {
+            None => "none",
+            Some(Writer::Through(_)) => "write-through",
+            Some(Writer::Behind(_)) => "write-behind",
+        };
+        f.debug_struct("Cache")
+            .field("map", &self.map)
+            .field("loader", &self.loader.is_some())
+            .field("writer", &writer)
+            .finish()
+    }
+}
+
+impl<K, V, S> Cache<K, V, S> {
+    /// Creates a cache around `map`, without a loader or writer.
+    pub fn new(map: HashMap<K, V, S>) -> Self {
+        Cache {
+            map,
+            loader: None,
+            writer: None,
+        }
+    }
+
+    /// Loads keys that are not in the cache from `loader`.
+    pub fn with_loader<L>(mut self, loader: L) -> Self
+    where
+        L: 'static + CacheLoader<K, V>,
+    {
+        self.loader = Some(Box::new(loader));
+        self
+    }
+
+    /// Writes every change to `writer` before it is applied to the cache.
+    ///
+    /// This replaces any previously configured writer.
+    pub fn with_writer<W>(mut self, writer: W) -> Self
+    where
+        W: 'static + CacheWriter<K, V>,
+    {
+        self.writer = Some(Writer::Through(Box::new(writer)));
+        self
+    }
+
+    /// Returns the underlying map.
+    ///
+    /// Changes made directly to the map bypass the cache's writer.
+    pub fn map(&self) -> &HashMap<K, V, S> {
+        &self.map
+    }
+
+    /// Pins the current thread, like [`HashMap::guard`].
+    pub fn guard(&self) -> TrackedGuard {
+        self.map.guard()
+    }
+
+    /// Blocks until all queued writes have been applied to the writer.
+    ///
+    /// Does nothing unless the cache was configured with [`Cache::with_write_behind`].
+    pub fn flush(&self) {
+        if let Some(Writer::Behind(ref queue)) = self.writer {
+            let (done, flushed) = mpsc::channel();
+            queue.push(Write::Flush(done));
+            let _ = flushed.recv();
+        }
+    }
+}
+
+impl<K, V, S> Cache<K, V, S>
+where
+    K: 'static + Send,
+    V: 'static + Send + Clone,
+{
+    /// Queues every change to be written to `writer` by a background thread.
+    ///
+    /// Changes are written in the order in which they were made to the cache, and the cache does
+    /// not wait for them to be written. Use [`Cache::flush`] to wait for all queued changes to be
+    /// written. Dropping the cache also waits for the queue to drain.
+    ///
+    /// This replaces any previously configured writer.
+    pub fn with_write_behind<W>(mut self, writer: W) -> Self
+    where
+        W: 'static + CacheWriter<K, V>,
+    {
+        self.writer = Some(Writer::Behind(WriteBehind::spawn(writer, V::clone)));
+        self
+    }
+}
+
+impl<K, V, S> Cache<K, V, S>
+where
+    K: 'static + Sync + Send + Clone + Hash + TreeKey,
+    V: 'static + Sync + Send,
+    S: BuildHasher,
+{
+    /// Returns the value for `key`, loading it into the cache if it is missing.
+    ///
+    /// If the key is neither in the cache nor in the loader (or no loader is configured), this
+    /// returns `None`. Values that are loaded are not written back to the writer.
+    pub fn get<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g V> {
+        if let Some(value) = self.map.get(key, guard) {
+            return Some(value);
+        }
+        let value = self.loader.as_ref()?.load(key)?;
+        match self.map.try_insert(key.clone(), value, guard) {
+            Ok(value) => Some(value),
+            // another thread loaded or inserted the key in the meantime
+            Err(e) => Some(e.current),
+        }
+    }
+
+    /// Inserts `value` for `key`, writing it to the writer first, and returns the value it
+    /// replaced in the cache, if any.
+    pub fn insert<'g>(&'g self, key: K, value: V, guard: &'g Guard) -> Option<&'g V> {
+        match self.writer {
+            Some(Writer::Through(ref writer)) => writer.write(&key, &value),
+            Some(Writer::Behind(ref queue)) => {
+                queue.push(Write::Store(key.clone(), (queue.clone_value)(&value)))
+            }
+            None => {}
+        }
+        self.map.insert(key, value, guard)
+    }
+
+    /// Removes `key`, deleting it from the writer first, and returns the value it had in the
+    /// cache, if any.
+    ///
+    /// The key is deleted from the writer even if it was not in the cache.
+    pub fn remove<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g V> {
+        match self.writer {
+            Some(Writer::Through(ref writer)) => writer.delete(key),
+            Some(Writer::Behind(ref queue)) => queue.push(Write::Delete(key.clone())),
+            None => {}
+        }
+        self.map.remove(key, guard)
+    }
+
+    /// Removes `key` from the cache without deleting it from the writer.
+    ///
+    /// The next lookup of `key` will load it from the loader again.
+    pub fn invalidate<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g V> {
+        self.map.remove(key, guard)
+    }
+}
@@ -0,0 +1,602 @@
+//! A facade with the API of [`dashmap`](https://docs.rs/dashmap)'s `DashMap`.
+//!
+//! [`DashMap`] wraps a [`HashMap`] and exposes methods with the same names and shapes as those
+//! of `dashmap::DashMap`, none of which take a guard. Lookups return a [`Ref`], which keeps the
+//! epoch pinned for as long as it lives, much like the read lock that `dashmap` holds for its
+//! `Ref`s.
+//!
+//! There are some differences that stem from how flurry works:
+//!
+//!  - Values in the map can never be mutated in place, since other threads may be reading them
+//!    concurrently. There is no `get_mut`, and [`DashMap::alter`] and [`Entry::and_modify`]
+//!    instead replace the value with an updated clone.
+//!  - Methods that return a value that was removed from the map return a clone of it, since the
+//!    original may still be read by other threads.
+//!  - Holding on to a [`Ref`] does not block writers, but it does hold up the reclamation of
+//!    garbage in the map (see the [crate-level documentation](crate)).
+//!
+//! ```
+//! use flurry::compat::dashmap::DashMap;
+//!
+//! let map = DashMap::new();
+//! map.insert("apples", 3);
+//! map.entry("pears").or_insert(0);
+//! map.alter("apples", |_, n| n + 1);
+//! assert_eq!(*map.get("apples").unwrap(), 4);
+//! assert_eq!(map.get("pears").map(|r| *r.value()), Some(0));
+//!
+//! let total: i32 = map.iter().map(|r| *r.value()).sum();
+//! assert_eq!(total, 4);
+//! ```
+
+use crate::iter::Iter as MapIter;
+use crate::{Decision, DefaultHashBuilder, HashMap, TrackedGuard, TreeKey};
+use crossbeam_epoch::Guard;
+use std::borrow::Borrow;
+use std::fmt::{self, Debug, Formatter};
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::rc::Rc;
+
+/// A concurrent map with the API of `dashmap::DashMap`.
+///
+/// See the [module-level documentation](self) for details.
+pub struct DashMap<K, V, S = DefaultHashBuilder> {
+    map: HashMap<K, V, S>,
+}
+
+impl<K, V, S> Debug for DashMap<K, V, S>
+where
+    K: Debug,
+    V: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.map.fmt(f)
+    }
+}
+
+impl<K, V> DashMap<K, V, DefaultHashBuilder> {
+    /// Creates an empty map.
+    pub fn new() -> Self {
+        Self::from(HashMap::new())
+    }
+
+    /// Creates an empty map that can hold at least `capacity` elements without resizing.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::from(HashMap::with_capacity(capacity))
+    }
+}
+
+impl<K, V, S> Default for DashMap<K, V, S>
+where
+    S: Default,
+{
+    fn default() -> Self {
+        Self::from(HashMap::default())
+    }
+}
+
+impl<K, V, S> From<HashMap<K, V, S>> for DashMap<K, V, S> {
+    fn from(map: HashMap<K, V, S>) -> Self {
+        DashMap { map }
+    }
+}
+
+impl<K, V, S> DashMap<K, V, S> {
+    /// Creates an empty map that uses `hasher` to hash keys.
+    pub fn with_hasher(hasher: S) -> Self {
+        Self::from(HashMap::with_hasher(hasher))
+    }
+
+    /// Creates an empty map that uses `hasher` to hash keys, and can hold at least `capacity`
+    /// elements without resizing.
+    pub fn with_capacity_and_hasher(capacity: usize, hasher: S) -> Self {
+        Self::from(HashMap::with_capacity_and_hasher(capacity, hasher))
+    }
+
+    /// Returns the underlying [`HashMap`].
+    ///
+    /// This lets call sites that have been migrated use flurry's own API.
+    pub fn as_flurry(&self) -> &HashMap<K, V, S> {
+        &self.map
+    }
+
+    /// Consumes the facade and returns the underlying [`HashMap`].
+    pub fn into_flurry(self) -> HashMap<K, V, S> {
+        self.map
+    }
+
+    /// Returns the number of entries in the map.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns `true` if the map contains no entries.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Returns an iterator over the entries of the map.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        let guard = self.pin();
+        // safety: the guard is kept alive (and at the same address) by the `Rc` for as long as
+        // the iterator and any of the `Ref`s it produces exist.
+        let pinned: &Guard = unsafe { &*(&**guard as *const Guard) };
+        let inner = self.map.iter(pinned);
+        Iter { inner, guard }
+    }
+
+    fn pin(&self) -> Rc<TrackedGuard> {
+        Rc::new(self.map.guard())
+    }
+}
+
+impl<K, V, S> DashMap<K, V, S>
+where
+    K: Hash + TreeKey,
+    S: BuildHasher,
+{
+    /// Returns a reference to the entry for `key`, if there is one.
+    pub fn get<Q>(&self, key: &Q) -> Option<Ref<'_, K, V>>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
+    {
+        let guard = self.pin();
+        let (key, value) = self.map.get_key_value(key, &guard)?;
+        Some(Ref::new(&guard, key, value))
+    }
+
+    /// Returns `true` if the map contains an entry for `key`.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
+    {
+        self.map.contains_key(key, &self.map.guard())
+    }
+}
+
+impl<K, V, S> DashMap<K, V, S>
+where
+    K: 'static + Sync + Send + Clone + Hash + TreeKey,
+    V: 'static + Sync + Send + Clone,
+    S: BuildHasher,
+{
+    /// Inserts `value` for `key`, and returns (a clone of) the value it replaced, if any.
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        self.map.insert(key, value, &self.map.guard()).cloned()
+    }
+
+    /// Removes the entry for `key`, and returns (a clone of) it, if there was one.
+    pub fn remove<Q>(&self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
+    {
+        let guard = self.map.guard();
+        let (k, v) = self.map.remove_entry(key, &guard)?;
+        Some((k.clone(), v.clone()))
+    }
+
+    /// Removes the entry for `key` if `f` returns `true` for it, and returns (a clone of) it.
+    ///
+    /// If `f` returns `false`, the entry is left untouched.
+    pub fn remove_if<Q, F>(&self, key: &Q, f: F) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
+        F: FnOnce(&K, &V) -> bool,
+    {
+        let guard = self.map.guard();
+        let mut removed = None;
+        let decide = |k: &K, v: &V| {
+            if f(k, v) {
+                removed = Some((k.clone(), v.clone()));
+                Decision::Remove
+            } else {
+                Decision::Keep
+            }
+        };
+        match self.map.compute_if_present_until(key, decide, None, &guard) {
+            Ok(_) => removed,
+            Err(_) => unreachable!("remove_if without a deadline cannot time out"),
+        }
+    }
+
+    /// Replaces the value for `key` with the result of calling `f` on (a clone of) it.
+    ///
+    /// Does nothing if there is no entry for `key`. Otherwise, the entry always gets a new value,
+    /// even if it is equal to the old one, since the old one may still be read by other threads.
+    pub fn alter<Q, F>(&self, key: &Q, f: F)
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
+        F: FnOnce(&K, V) -> V,
+    {
+        self.map
+            .compute_if_present(key, |k, v| Some(f(k, v.clone())), &self.map.guard());
+    }
+
+    /// Replaces every value in the map with the result of calling `f` on (a clone of) it.
+    ///
+    /// Every entry is replaced in a single pass over the map, with
+    /// [`HashMap::retain_mut`](crate::HashMap::retain_mut).
+    pub fn alter_all<F>(&self, mut f: F)
+    where
+        F: FnMut(&K, V) -> V,
+    {
+        self.map
+            .retain_mut(|k, v| Decision::Replace(f(k, v.clone())), &self.map.guard());
+    }
+
+    /// Removes every entry for which `f` returns `false`.
+    pub fn retain<F>(&self, f: F)
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        self.map.retain(f, &self.map.guard());
+    }
+
+    /// Removes all entries from the map.
+    pub fn clear(&self) {
+        self.map.clear(&self.map.guard());
+    }
+
+    /// Returns the entry for `key`, for in-place manipulation.
+    pub fn entry(&self, key: K) -> Entry<'_, K, V, S> {
+        let guard = self.pin();
+        match self.map.get(&key, &guard) {
+            Some(value) => {
+                let value = value as *const V;
+                Entry::Occupied(OccupiedEntry {
+                    map: self,
+                    guard,
+                    key,
+                    value,
+                })
+            }
+            None => Entry::Vacant(VacantEntry {
+                map: self,
+                guard,
+                key,
+            }),
+        }
+    }
+}
+
+impl<K, V, S> Clone for DashMap<K, V, S>
+where
+    K: 'static + Sync + Send + Clone + Hash + TreeKey,
+    V: 'static + Sync + Send + Clone,
+    S: BuildHasher + Clone,
+{
+    fn clone(&self) -> Self {
+        Self::from(self.map.clone())
+    }
+}
+
+/// A reference to an entry of a [`DashMap`].
+///
+/// The entry (but not necessarily its presence in the map) remains valid for as long as this
+/// reference lives.
+pub struct Ref<'a, K, V> {
+    // keeps the key and value from being reclaimed
+    _guard: Rc<TrackedGuard>,
+    key: RefKey<K>,
+    value: *const V,
+    map: PhantomData<&'a (K, V)>,
+}
+
+enum RefKey<K> {
+    InMap(*const K),
+    // the key of an entry that was just inserted, which is equal to the key in the map
+    Owned(K),
+}
+
+impl<'a, K, V> Ref<'a, K, V> {
+    fn new(guard: &Rc<TrackedGuard>, key: &K, value: &V) -> Self {
+        Ref {
+            _guard: Rc::clone(guard),
+            key: RefKey::InMap(key),
+            value,
+            map: PhantomData,
+        }
+    }
+
+    /// Returns the entry's key.
+    pub fn key(&self) -> &K {
+        match self.key {
+            // safety: we hold a guard that was pinned when the key was read from the map.
+            RefKey::InMap(key) => unsafe { &*key },
+            RefKey::Owned(ref key) => key,
+        }
+    }
+
+    /// Returns the entry's value.
+    pub fn value(&self) -> &V {
+        // safety: we hold a guard that was pinned when the value was read from the map.
+        unsafe { &*self.value }
+    }
+
+    /// Returns the entry's key and value.
+    pub fn pair(&self) -> (&K, &V) {
+        (self.key(), self.value())
+    }
+}
+
+impl<K, V> Deref for Ref<'_, K, V> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        self.value()
+    }
+}
+
+impl<K, V> Debug for Ref<'_, K, V>
+where
+    K: Debug,
+    V: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ref")
+            .field("key", self.key())
+            .field("value", self.value())
+            .finish()
+    }
+}
+
+/// An iterator over the entries of a [`DashMap`].
+///
+/// See [`DashMap::iter`].
+pub struct Iter<'a, K, V> {
+    // NOTE: declared before `guard`, so that it is dropped first
+    inner: MapIter<'a, K, V>,
+    guard: Rc<TrackedGuard>,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = Ref<'a, K, V>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (key, value) = self.inner.next()?;
+        Some(Ref::new(&self.guard, key, value))
+    }
+}
+
+impl<K, V> Debug for Iter<'_, K, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Iter").finish()
+    }
+}
+
+/// An entry of a [`DashMap`], which may or may not be present.
+///
+/// See [`DashMap::entry`].
+#[derive(Debug)]
+pub enum Entry<'a, K, V, S> {
+    /// The map had an entry for the key.
+    Occupied(OccupiedEntry<'a, K, V, S>),
+    /// The map had no entry for the key.
+    Vacant(VacantEntry<'a, K, V, S>),
+}
+
+impl<'a, K, V, S> Entry<'a, K, V, S> {
+    /// Returns the entry's key.
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+}
+
+impl<'a, K, V, S> Entry<'a, K, V, S>
+where
+    K: 'static + Sync + Send + Clone + Hash + TreeKey,
+    V: 'static + Sync + Send + Clone,
+    S: BuildHasher,
+{
+    /// Replaces the entry's value with the result of calling `f` on a clone of it, if there is
+    /// one.
+    pub fn and_modify<F>(self, f: F) -> Self
+    where
+        F: FnOnce(&mut V),
+    {
+        match self {
+            Entry::Occupied(entry) => {
+                let OccupiedEntry {
+                    map, guard, key, ..
+                } = entry;
+                let modified = map.map.compute_if_present(
+                    &key,
+                    |_, v| {
+                        let mut v = v.clone();
+                        f(&mut v);
+                        Some(v)
+                    },
+                    &guard,
+                );
+                match modified {
+                    Some(value) => Entry::Occupied(OccupiedEntry {
+                        map,
+                        key,
+                        value,
+                        guard,
+                    }),
+                    // the entry was removed concurrently
+                    None => Entry::Vacant(VacantEntry { map, guard, key }),
+                }
+            }
+            vacant => vacant,
+        }
+    }
+
+    /// Returns a reference to the entry's value, inserting `value` if there is none.
+    pub fn or_insert(self, value: V) -> Ref<'a, K, V> {
+        match self {
+            Entry::Occupied(entry) => entry.into_ref(),
+            Entry::Vacant(entry) => entry.insert(value),
+        }
+    }
+
+    /// Returns a reference to the entry's value, inserting the result of `f` if there is none.
+    ///
+    /// If another thread inserts a value for the key concurrently, `f` may be called even though
+    /// its result is not inserted.
+    pub fn or_insert_with<F>(self, f: F) -> Ref<'a, K, V>
+    where
+        F: FnOnce() -> V,
+    {
+        match self {
+            Entry::Occupied(entry) => entry.into_ref(),
+            Entry::Vacant(entry) => entry.insert(f()),
+        }
+    }
+
+    /// Returns a reference to the entry's value, inserting `V::default()` if there is none.
+    pub fn or_default(self) -> Ref<'a, K, V>
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+}
+
+/// An entry of a [`DashMap`] that was present.
+pub struct OccupiedEntry<'a, K, V, S> {
+    map: &'a DashMap<K, V, S>,
+    guard: Rc<TrackedGuard>,
+    key: K,
+    value: *const V,
+}
+
+impl<K, V, S> Debug for OccupiedEntry<'_, K, V, S>
+where
+    K: Debug,
+    V: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OccupiedEntry")
+            .field("key", self.key())
+            .field("value", self.get())
+            .finish()
+    }
+}
+
+impl<'a, K, V, S> OccupiedEntry<'a, K, V, S> {
+    /// Returns the entry's key.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Returns the entry's value, as of when the entry was looked up.
+    pub fn get(&self) -> &V {
+        // safety: we hold a guard that was pinned when the value was read from the map.
+        unsafe { &*self.value }
+    }
+
+    /// Converts the entry into a reference to its value.
+    pub fn into_ref(self) -> Ref<'a, K, V> {
+        Ref {
+            _guard: self.guard,
+            key: RefKey::Owned(self.key),
+            value: self.value,
+            map: PhantomData,
+        }
+    }
+}
+
+impl<'a, K, V, S> OccupiedEntry<'a, K, V, S>
+where
+    K: 'static + Sync + Send + Clone + Hash + TreeKey,
+    V: 'static + Sync + Send + Clone,
+    S: BuildHasher,
+{
+    /// Replaces the entry's value, and returns (a clone of) the value it replaced.
+    pub fn insert(self, value: V) -> V {
+        let seen = self.value;
+        match self.map.map.insert(self.key, value, &self.guard) {
+            Some(old) => old.clone(),
+            // the entry was removed concurrently, so the value we saw is what was replaced.
+            // safety: the guard that was pinned when it was read from the map is still alive.
+            None => unsafe { &*seen }.clone(),
+        }
+    }
+
+    /// Removes the entry from the map, and returns (a clone of) it.
+    pub fn remove_entry(self) -> (K, V) {
+        match self.map.map.remove_entry(&self.key, &self.guard) {
+            Some((k, v)) => (k.clone(), v.clone()),
+            // the entry was removed concurrently, so the value we saw is what was removed
+            None => {
+                let value = self.get().clone();
+                (self.key, value)
+            }
+        }
+    }
+
+    /// Removes the entry from the map, and returns (a clone of) its value.
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+}
+
+/// An entry of a [`DashMap`] that was not present.
+pub struct VacantEntry<'a, K, V, S> {
+    map: &'a DashMap<K, V, S>,
+    guard: Rc<TrackedGuard>,
+    key: K,
+}
+
+impl<K, V, S> Debug for VacantEntry<'_, K, V, S>
+where
+    K: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VacantEntry")
+            .field("key", self.key())
+            .finish()
+    }
+}
+
+impl<'a, K, V, S> VacantEntry<'a, K, V, S> {
+    /// Returns the entry's key.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Consumes the entry and returns its key.
+    pub fn into_key(self) -> K {
+        self.key
+    }
+}
+
+impl<'a, K, V, S> VacantEntry<'a, K, V, S>
+where
+    K: 'static + Sync + Send + Clone + Hash + TreeKey,
+    V: 'static + Sync + Send + Clone,
+    S: BuildHasher,
+{
+    /// Inserts `value` for the entry's key, and returns a reference to it.
+    ///
+    /// If another thread inserted a value for the key since the entry was looked up, that value
+    /// is kept, and a reference to it is returned instead.
+    pub fn insert(self, value: V) -> Ref<'a, K, V> {
+        let value = match self
+            .map
+            .map
+            .try_insert(self.key.clone(), value, &self.guard)
+        {
+            Ok(value) => value as *const V,
+            Err(e) => e.current as *const V,
+        };
+        Ref {
+            _guard: self.guard,
+            key: RefKey::Owned(self.key),
+            value,
+            map: PhantomData,
+        }
+    }
+}
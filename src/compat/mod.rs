@@ -0,0 +1,7 @@
+//! Facades that mimic the APIs of other concurrent maps.
+//!
+//! These make it possible to switch an existing code base over to flurry one call site at a time.
+//! They trade some efficiency for not having to deal with guards explicitly, so new code should
+//! generally use [`HashMap`](crate::HashMap) directly.
+
+pub mod dashmap;
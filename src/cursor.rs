@@ -0,0 +1,353 @@
+//! Scans over a map that can be paused and resumed later.
+//!
+//! An [`Iter`](crate::iter::Iter) has to hold on to its guard until it is done, and a scan that
+//! takes minutes keeps everything that is removed from the map in the meantime from being
+//! reclaimed. A [`Cursor`] instead visits the map one bin at a time, and can be turned into a
+//! [`ResumeToken`] at any point. The token does not borrow from the map or the guard, so the
+//! guard can be dropped, and a new cursor picks up from the token under a fresh guard:
+//!
+//! ```
+//! use flurry::HashMap;
+//!
+//! let map: HashMap<_, _> = (0..1024).map(|i| (i, i)).collect();
+//!
+//! let mut seen = std::collections::HashSet::new();
+//! let mut token = None;
+//! loop {
+//!     let guard = map.guard();
+//!     let mut cursor = match token {
+//!         Some(token) => map.cursor_from(token, &guard),
+//!         None => map.cursor(&guard),
+//!     };
+//!     // look at a page of entries under this guard
+//!     seen.extend(cursor.by_ref().take(100).map(|(k, _)| *k));
+//!     let next = cursor.into_token();
+//!     if next.is_finished() {
+//!         break;
+//!     }
+//!     token = Some(next);
+//! }
+//! assert_eq!(seen.len(), 1024);
+//! ```
+//!
+//! The table may resize while the scan is paused, or while the cursor is visiting it. Bins are
+//! visited in an order that does not depend on the size of the table, so that every entry that
+//! is in the map for the whole duration of the scan is yielded at least once. An entry may be
+//! yielded more than once though, if the table resized while the scan was paused halfway through
+//! a bin, or if the map shrunk. Entries that are inserted or removed during the scan may or may
+//! not be yielded.
+//!
+//! Within a bin, entries are yielded in the order of their hashes, and the token remembers the
+//! hash that the scan got to. Keys whose hashes collide are told apart only by how many of them
+//! were yielded, so if some of them are removed while the scan is paused, the resumed scan may
+//! skip others.
+
+use crate::node::{BinEntry, Node, TreeNode};
+use crate::raw::Table;
+use crossbeam_epoch::{Atomic, Guard, Shared};
+use std::cmp::Reverse;
+use std::fmt::{self, Debug, Formatter};
+use std::sync::atomic::Ordering;
+
+/// Where a paused scan picks up again, as returned by [`Cursor::into_token`].
+///
+/// The default token starts a scan at the beginning of the map.
+///
+/// See the [module-level documentation](self) for details.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ResumeToken {
+    position: u64,
+    finished: bool,
+    /// How far the scan got into the bin at `position`, if it stopped halfway through.
+    progress: Option<Progress>,
+}
+
+/// How far a scan got into a bin.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct Progress {
+    /// The number of bins in the table that the bin was in.
+    len: usize,
+    /// The hash of the last entry that was yielded.
+    hash: u64,
+    /// How many entries with that hash were yielded.
+    same: usize,
+}
+
+impl ResumeToken {
+    /// Returns `true` if the scan this token was taken from has visited the whole map.
+    ///
+    /// A cursor resumed from a finished token yields nothing.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// The token of a scan that has visited the whole map.
+    pub(crate) const FINISHED: Self = ResumeToken {
+        position: 0,
+        finished: true,
+        progress: None,
+    };
+
+    /// Returns the token for a scan that visits the bin at `position` next.
+    pub(crate) fn at(position: u64) -> Self {
+        ResumeToken {
+            position,
+            finished: false,
+            progress: None,
+        }
+    }
+
+    /// Returns the position of the bin that the scan visits next.
+    pub(crate) fn position(&self) -> u64 {
+        self.position
+    }
+}
+
+/// A scan over a map's entries that can be paused, as returned by
+/// [`HashMap::cursor`](crate::HashMap::cursor).
+///
+/// See the [module-level documentation](self) for details.
+pub struct Cursor<'g, K, V> {
+    pub(crate) table: &'g Atomic<Table<K, V>>,
+    pub(crate) guard: &'g Guard,
+    /// The position of the bin to visit next, with the bits of the bin index reversed.
+    pub(crate) position: u64,
+    pub(crate) finished: bool,
+    /// The position of the bin that the entries in `pending` came from.
+    pub(crate) visiting: u64,
+    /// The entries of that bin that are yet to be yielded, with the lowest hash last.
+    pub(crate) pending: Vec<&'g Node<K, V>>,
+    /// How far we got into that bin.
+    pub(crate) progress: Option<Progress>,
+    /// How far an earlier scan got into the first bin we visit.
+    pub(crate) resume: Option<Progress>,
+    /// The number of bins in the table that we visited the bin in.
+    pub(crate) len: usize,
+}
+
+impl<K, V> Debug for Cursor<'_, K, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cursor")
+            .field("token", &self.token())
+            .finish()
+    }
+}
+
+impl<'g, K, V> Cursor<'g, K, V> {
+    pub(crate) fn new(
+        table: &'g Atomic<Table<K, V>>,
+        token: ResumeToken,
+        guard: &'g Guard,
+    ) -> Self {
+        Cursor {
+            table,
+            guard,
+            position: token.position,
+            finished: token.finished,
+            visiting: token.position,
+            pending: Vec::new(),
+            progress: None,
+            resume: token.progress,
+            len: 0,
+        }
+    }
+
+    /// Returns a token to resume the scan from.
+    ///
+    /// If the cursor is halfway through the entries of a bin, the resumed scan continues with
+    /// the rest of them, unless the table has resized in the meantime. It then visits the whole
+    /// bin again.
+    pub fn token(&self) -> ResumeToken {
+        if self.pending.is_empty() {
+            ResumeToken {
+                position: self.position,
+                finished: self.finished,
+                progress: self.resume,
+            }
+        } else {
+            ResumeToken {
+                position: self.visiting,
+                finished: false,
+                progress: self.progress,
+            }
+        }
+    }
+
+    /// Pauses the scan, and returns a token to resume it from.
+    ///
+    /// See [`token`](Cursor::token) for details.
+    pub fn into_token(self) -> ResumeToken {
+        self.token()
+    }
+
+    /// Moves on to the next bin, and collects the entries that belong to it into `pending`.
+    ///
+    /// Returns `false` once every bin has been visited.
+    fn visit_next_bin(&mut self) -> bool {
+        if self.finished {
+            return false;
+        }
+        let table = self.table.load(Ordering::SeqCst, self.guard);
+        if table.is_null() {
+            self.finished = true;
+            return false;
+        }
+        // safety: flurry does not drop or move until after guard drop
+        let table = unsafe { table.deref() };
+        let n = table.len();
+        let mask = n as u64 - 1;
+        let i = (self.position & mask) as usize;
+        collect(table, i, (n, i), &mut self.pending, self.guard);
+        self.pending.sort_by_key(|node| Reverse(node.hash));
+        self.progress = None;
+
+        // skip what an earlier scan already yielded, as long as the bin still holds the same
+        // entries as it did back then
+        if let Some(resume) = self.resume.take() {
+            if resume.len == n {
+                let mut same = resume.same;
+                while let Some(node) = self.pending.last() {
+                    if node.hash < resume.hash || (node.hash == resume.hash && same > 0) {
+                        if node.hash == resume.hash {
+                            same -= 1;
+                        }
+                        self.pending.pop();
+                    } else {
+                        break;
+                    }
+                }
+                self.progress = Some(resume);
+            }
+        }
+        self.len = n;
+
+        self.visiting = self.position;
+        match next_position(self.position, n) {
+            Some(position) => self.position = position,
+            None => {
+                self.position = 0;
+                self.finished = true;
+            }
+        }
+        true
+    }
+}
+
+impl<'g, K, V> Iterator for Cursor<'g, K, V> {
+    type Item = (&'g K, &'g V);
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(node) = self.pending.pop() {
+                let same = match self.progress {
+                    Some(progress) if progress.hash == node.hash => progress.same + 1,
+                    _ => 1,
+                };
+                self.progress = Some(Progress {
+                    len: self.len,
+                    hash: node.hash,
+                    same,
+                });
+                let value = node.value.load(Ordering::SeqCst, self.guard);
+                // safety: flurry does not drop or move until after guard drop
+                let value = unsafe { value.deref() };
+                return Some((&node.key, value));
+            }
+            if !self.visit_next_bin() {
+                return None;
+            }
+        }
+    }
+}
+
+/// Collects the entries in bin `i` of `table` that belong to bin `bin.1` of a table with
+/// `bin.0` bins into `entries`, following forwarding nodes into the next table if the bin has
+/// moved.
+pub(crate) fn collect<'g, K, V>(
+    table: &'g Table<K, V>,
+    i: usize,
+    bin: (usize, usize),
+    entries: &mut Vec<&'g Node<K, V>>,
+    guard: &'g Guard,
+) {
+    let entry = table.bin(i, guard);
+    if entry.is_null() {
+        return;
+    }
+    // safety: flurry does not drop or move until after guard drop
+    let mut node = match unsafe { entry.deref() } {
+        BinEntry::Moved => {
+            // safety: same argument as for following Moved in Table::find
+            let next = unsafe { table.next_table(guard).deref() };
+            let (n, m) = (table.len(), next.len());
+            if m >= n {
+                for j in (i..m).step_by(n) {
+                    collect(next, j, bin, entries, guard);
+                }
+            } else {
+                collect(next, i & (m - 1), bin, entries, guard);
+            }
+            return;
+        }
+        BinEntry::Node(node) => node,
+        BinEntry::Tree(tree_bin) => {
+            // safety:`entry` was read under our guard, at which point the tree was valid.
+            // Since our guard pins the current epoch, the TreeNodes remain valid for at
+            // least as long as we hold onto the guard. Structurally, TreeNodes always point
+            // to TreeNodes, so this is sound.
+            &unsafe { TreeNode::get_tree_node(tree_bin.first.load(Ordering::SeqCst, guard)) }.node
+        }
+        // a reserved bin has no entries yet
+        BinEntry::Reserved(_) => return,
+        BinEntry::TreeNode(_) => {
+            unreachable!("The head of a bin cannot be a TreeNode directly without BinEntry::Tree")
+        }
+    };
+    let (n, index) = bin;
+    loop {
+        // when the map shrinks, several bins move into the same bin of the next table
+        if node.hash & (n as u64 - 1) == index as u64 {
+            entries.push(node);
+        }
+        let next: Shared<'g, _> = node.next.load(Ordering::SeqCst, guard);
+        if next.is_null() {
+            return;
+        }
+        // safety: flurry does not drop or move until after guard drop
+        node = match unsafe { next.deref() } {
+            BinEntry::Node(node) => node,
+            BinEntry::TreeNode(tree_node) => &tree_node.node,
+            BinEntry::Moved | BinEntry::Tree(_) | BinEntry::Reserved(_) => {
+                unreachable!("Nodes can only point to Nodes or TreeNodes")
+            }
+        };
+    }
+}
+
+/// The start of a scan.
+pub(crate) const START: ResumeToken = ResumeToken {
+    position: 0,
+    finished: false,
+    progress: None,
+};
+
+/// Returns the position of the bin to visit after the one at `position` in a table with `len`
+/// bins, or `None` if that was the last one.
+pub(crate) fn next_position(position: u64, len: usize) -> Option<u64> {
+    let mask = len as u64 - 1;
+    // reverse the bits, so that the bins that a bin splits into when the table grows are all
+    // visited after it, and we do not miss any entries that moved there in the meantime
+    match reverse(reverse(position | !mask).wrapping_add(1)) {
+        0 => None,
+        position => Some(position),
+    }
+}
+
+/// Reverses the bits of `v`.
+///
+/// `u64::reverse_bits` is newer than the oldest Rust version we support.
+fn reverse(mut v: u64) -> u64 {
+    v = ((v >> 1) & 0x5555_5555_5555_5555) | ((v & 0x5555_5555_5555_5555) << 1);
+    v = ((v >> 2) & 0x3333_3333_3333_3333) | ((v & 0x3333_3333_3333_3333) << 2);
+    v = ((v >> 4) & 0x0f0f_0f0f_0f0f_0f0f) | ((v & 0x0f0f_0f0f_0f0f_0f0f) << 4);
+    v.swap_bytes()
+}
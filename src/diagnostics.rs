@@ -0,0 +1,673 @@
+//! Insight into how a map's entries are laid out in its table.
+//!
+//! A map spreads its entries over the bins of its table according to their hashes. With a good
+//! hasher, bins hold very few entries each. When many keys end up in the same bin, the map turns
+//! that bin into a tree to keep lookups fast, which is a strong sign that the hasher is a poor fit
+//! for the keys, or that someone is deliberately choosing keys that collide (hash flooding).
+//! [`HashMap::tree_bins`] lists those bins, so that they can be monitored at runtime.
+//!
+//! For a quick overview of a map's state, [`HashMap::summary`] returns a [`Summary`] of its size
+//! and layout that is formatted as a single line, which is handy for logs and status pages.
+//! [`HashMap::table_stats`] goes into more detail, with histograms of how long the bins' lists
+//! are and how deep their trees are, and how the entries are spread over the strides of bins
+//! that threads claim when they help resize the table. [`HashMap::memory_usage`] estimates how
+//! much memory the map takes up. With the `garbage-stats` feature, `HashMap::garbage_stats`
+//! reports how much memory the map has retired, and how much of it is still waiting to be
+//! reclaimed.
+//!
+//! ```
+//! use flurry::HashMap;
+//! use std::hash::{BuildHasherDefault, Hasher};
+//!
+//! // a hasher that maps every key to the same hash
+//! #[derive(Default)]
+//! struct Flooded;
+//!
+//! impl Hasher for Flooded {
+//!     fn finish(&self) -> u64 {
+//!         7
+//!     }
+//!
+//!     fn write(&mut self, _: &[u8]) {}
+//! }
+//!
+//! # if cfg!(feature = "unordered-keys") { return; }
+//! let map = HashMap::with_capacity_and_hasher(1 << 8, BuildHasherDefault::<Flooded>::default());
+//! let guard = map.guard();
+//! for i in 0..16 {
+//!     map.insert(i, i, &guard);
+//! }
+//!
+//! let bins: Vec<_> = map.tree_bins(&guard).collect();
+//! assert_eq!(bins.len(), 1);
+//! assert_eq!(bins[0].entries(), 16);
+//! assert_eq!(bins[0].hashes(), &[(7, 16)]);
+//! ```
+//!
+//! [`HashMap::tree_bins`]: crate::HashMap::tree_bins
+//! [`HashMap::summary`]: crate::HashMap::summary
+//! [`HashMap::table_stats`]: crate::HashMap::table_stats
+//! [`HashMap::memory_usage`]: crate::HashMap::memory_usage
+
+use crate::node::BinEntry;
+use crate::raw::Table;
+use crossbeam_epoch::Guard;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::mem;
+use std::sync::atomic::Ordering;
+
+/// A bin of a map that has been turned into a tree, as reported by
+/// [`HashMap::tree_bins`](crate::HashMap::tree_bins).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeBinInfo {
+    index: usize,
+    entries: usize,
+    hashes: Vec<(u64, usize)>,
+}
+
+impl TreeBinInfo {
+    /// Returns the index of the bin in the map's table.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the number of entries in the bin.
+    pub fn entries(&self) -> usize {
+        self.entries
+    }
+
+    /// Returns the distinct hashes of the entries in the bin, together with how many entries
+    /// have each of them.
+    ///
+    /// The most common hashes come first. Many entries with the same hash mean that their keys
+    /// collide outright, rather than just in the bits that select the bin.
+    pub fn hashes(&self) -> &[(u64, usize)] {
+        &self.hashes
+    }
+}
+
+/// An iterator over the bins of a map that have been turned into trees.
+///
+/// See [`HashMap::tree_bins`](crate::HashMap::tree_bins) for details.
+pub struct TreeBins<'g, K, V> {
+    table: Option<&'g Table<K, V>>,
+    index: usize,
+    guard: &'g Guard,
+}
+
+impl<'g, K, V> TreeBins<'g, K, V> {
+    pub(crate) fn new(table: Option<&'g Table<K, V>>, guard: &'g Guard) -> Self {
+        TreeBins {
+            table,
+            index: 0,
+            guard,
+        }
+    }
+}
+
+impl<K, V> Debug for TreeBins<'_, K, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TreeBins")
+            .field("index", &self.index)
+            .finish()
+    }
+}
+
+impl<'g, K, V> Iterator for TreeBins<'g, K, V> {
+    type Item = TreeBinInfo;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let table = self.table?;
+        while self.index < table.len() {
+            let index = self.index;
+            self.index += 1;
+
+            let bin = table.bin(index, self.guard);
+            if bin.is_null() {
+                continue;
+            }
+            // safety: the table was loaded under our guard, and so was the bin, so neither can be
+            // dropped until the guard is.
+            let tree_bin = match unsafe { bin.deref() } {
+                BinEntry::Tree(ref tree_bin) => tree_bin,
+                _ => continue,
+            };
+
+            // readers may always walk the nodes of a tree bin as a list, so this does not need
+            // the tree's read lock.
+            let mut entries = 0;
+            let mut hashes: Vec<(u64, usize)> = Vec::new();
+            let mut p = tree_bin.first.load(Ordering::SeqCst, self.guard);
+            while !p.is_null() {
+                // safety: the nodes of a tree bin are only dropped after its bin has been
+                // replaced, which cannot happen until our guard is dropped.
+                let node = unsafe { p.deref() }
+                    .as_tree_node()
+                    .expect("a tree bin only contains tree nodes");
+                entries += 1;
+                match hashes.iter_mut().find(|(hash, _)| *hash == node.node.hash) {
+                    Some((_, count)) => *count += 1,
+                    None => hashes.push((node.node.hash, 1)),
+                }
+                p = node.node.next.load(Ordering::SeqCst, self.guard);
+            }
+            hashes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+            return Some(TreeBinInfo {
+                index,
+                entries,
+                hashes,
+            });
+        }
+        None
+    }
+}
+
+/// Returns the number of bins of `table` that hold at least one entry.
+pub(crate) fn occupied_bins<K, V>(table: Option<&Table<K, V>>, guard: &Guard) -> usize {
+    let table = match table {
+        Some(table) => table,
+        None => return 0,
+    };
+    (0..table.len())
+        .filter(|&i| {
+            let bin = table.bin(i, guard);
+            // safety: the table was loaded under our guard, and so was the bin, so neither can be
+            // dropped until the guard is.
+            !bin.is_null()
+                && match unsafe { bin.deref() } {
+                    BinEntry::Node(_) | BinEntry::Tree(_) => true,
+                    // bins that moved to the next table, or have no entries yet
+                    _ => false,
+                }
+        })
+        .count()
+}
+
+/// An overview of the state of a map, as returned by [`HashMap::summary`](crate::HashMap::summary).
+///
+/// Its `Display` implementation prints all of it on one line:
+///
+/// ```
+/// use flurry::HashMap;
+///
+/// let map = HashMap::new();
+/// for i in 0..8 {
+///     map.pin().insert(i, i);
+/// }
+/// let summary = map.summary();
+/// assert_eq!(summary.entries(), 8);
+/// assert_eq!(summary.bucket_count(), 16);
+/// assert_eq!(summary.capacity(), 11);
+/// assert!(summary.to_string().starts_with("8 entries in 16 buckets (load 0.50), 0 tree bins"));
+/// assert!(summary.to_string().contains("capacity 11"));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    pub(crate) entries: usize,
+    pub(crate) buckets: usize,
+    pub(crate) capacity: usize,
+    pub(crate) occupied_bins: usize,
+    pub(crate) tree_bins: usize,
+    pub(crate) garbage: Option<usize>,
+}
+
+impl Summary {
+    /// Returns the number of entries in the map.
+    pub fn entries(&self) -> usize {
+        self.entries
+    }
+
+    /// Returns the number of buckets in the map's table.
+    pub fn bucket_count(&self) -> usize {
+        self.buckets
+    }
+
+    /// Returns the number of entries the map can hold before it resizes its table.
+    ///
+    /// See [`HashMap::capacity`](crate::HashMap::capacity).
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of entries per bucket, or 0 if the map has no table yet.
+    pub fn load(&self) -> f64 {
+        match self.buckets {
+            0 => 0.0,
+            buckets => self.entries as f64 / buckets as f64,
+        }
+    }
+
+    /// Returns the number of buckets that hold at least one entry.
+    ///
+    /// With a good hasher, this is close to the number of entries while the load is low. Far
+    /// fewer occupied buckets than entries mean that many entries share their buckets.
+    pub fn occupied_bins(&self) -> usize {
+        self.occupied_bins
+    }
+
+    /// Returns the number of bins that have been turned into trees.
+    ///
+    /// See [`HashMap::tree_bins`](crate::HashMap::tree_bins).
+    pub fn tree_bins(&self) -> usize {
+        self.tree_bins
+    }
+
+    /// Returns the number of objects the map retired that have not been reclaimed yet.
+    ///
+    /// The map only keeps track of this with the `garbage-stats` feature (which the `leak-check`
+    /// feature enables), and returns `None` otherwise.
+    pub fn garbage(&self) -> Option<usize> {
+        self.garbage
+    }
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} entries in {} buckets (load {:.2}), {} tree bins, {} occupied buckets, capacity {}",
+            self.entries,
+            self.buckets,
+            self.load(),
+            self.tree_bins,
+            self.occupied_bins,
+            self.capacity
+        )?;
+        if let Some(garbage) = self.garbage {
+            write!(f, ", {} retired objects awaiting reclamation", garbage)?;
+        }
+        Ok(())
+    }
+}
+
+/// Statistics about how a map's entries are spread over its table, as returned by
+/// [`HashMap::table_stats`](crate::HashMap::table_stats).
+///
+/// ```
+/// use flurry::HashMap;
+///
+/// let map = HashMap::with_capacity(100);
+/// let guard = map.guard();
+/// for i in 0..100 {
+///     map.insert(i, i, &guard);
+/// }
+/// let stats = map.table_stats(&guard);
+/// assert_eq!(stats.bin_count(), 256);
+/// assert_eq!(stats.entries(), 100);
+/// // every bin holds a list of some length, or a tree
+/// let lists: usize = stats.chain_lengths().iter().sum();
+/// assert_eq!(lists + stats.tree_bins(), 256);
+/// assert_eq!(stats.stride_entries().iter().sum::<usize>(), 100);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableStats {
+    bins: usize,
+    entries: usize,
+    chain_lengths: Vec<usize>,
+    tree_depths: Vec<usize>,
+    moved_bins: usize,
+    stride: usize,
+    stride_entries: Vec<usize>,
+}
+
+impl TableStats {
+    /// Returns the number of bins in the map's table.
+    pub fn bin_count(&self) -> usize {
+        self.bins
+    }
+
+    /// Returns the number of entries in the bins of the table.
+    ///
+    /// Unlike [`HashMap::len`](crate::HashMap::len), this counts the entries one by one, and
+    /// does not include entries in bins that a concurrent resize has already moved.
+    pub fn entries(&self) -> usize {
+        self.entries
+    }
+
+    /// Returns how many bins hold a list of each length.
+    ///
+    /// The element at index `i` is the number of bins whose list has `i` entries, so the first
+    /// element counts the empty bins. Bins that were turned into trees are not included; see
+    /// [`tree_depths`](TableStats::tree_depths) for those.
+    pub fn chain_lengths(&self) -> &[usize] {
+        &self.chain_lengths
+    }
+
+    /// Returns how many tree bins have each depth.
+    ///
+    /// The element at index `d` is the number of bins whose tree is `d` nodes deep, counting its
+    /// root. A tree of `n` entries is never deeper than about `2 * log2(n)`.
+    pub fn tree_depths(&self) -> &[usize] {
+        &self.tree_depths
+    }
+
+    /// Returns the number of bins that have been turned into trees.
+    pub fn tree_bins(&self) -> usize {
+        self.tree_depths.iter().sum()
+    }
+
+    /// Returns the length of the longest list in any bin.
+    pub fn max_chain_length(&self) -> usize {
+        self.chain_lengths.len().saturating_sub(1)
+    }
+
+    /// Returns the number of bins that a concurrent resize has already moved to the next table.
+    pub fn moved_bins(&self) -> usize {
+        self.moved_bins
+    }
+
+    /// Returns the number of bins that a thread claims at a time when it helps resize the table.
+    ///
+    /// This depends on the size of the table and the number of CPUs, and is 0 if the map has no
+    /// table yet.
+    pub fn resize_stride(&self) -> usize {
+        self.stride
+    }
+
+    /// Returns the number of entries in each stride of bins that a resize hands out, in the
+    /// order of the bins they cover.
+    ///
+    /// Resizes hand out strides starting from the end of the table, so when the number of bins
+    /// is not a multiple of the [stride](TableStats::resize_stride), the first stride is the
+    /// short one. Strides with many more entries than the others take longer to move.
+    pub fn stride_entries(&self) -> &[usize] {
+        &self.stride_entries
+    }
+}
+
+/// Gathers the [`TableStats`] of `table`, whose resizes hand out `stride` bins at a time.
+pub(crate) fn table_stats<K, V>(
+    table: Option<&Table<K, V>>,
+    stride: usize,
+    guard: &Guard,
+) -> TableStats {
+    let mut stats = TableStats {
+        bins: 0,
+        entries: 0,
+        chain_lengths: Vec::new(),
+        tree_depths: Vec::new(),
+        moved_bins: 0,
+        stride: 0,
+        stride_entries: Vec::new(),
+    };
+    let table = match table {
+        Some(table) => table,
+        None => return stats,
+    };
+    stats.bins = table.len();
+    stats.stride = stride;
+
+    let mut bin_entries = Vec::with_capacity(table.len());
+    for i in 0..table.len() {
+        let bin = table.bin(i, guard);
+        let mut entries = 0;
+        // safety: the table was loaded under our guard, and so was the bin, so neither can be
+        // dropped until the guard is.
+        match unsafe { bin.as_ref() } {
+            Some(BinEntry::Moved) => stats.moved_bins += 1,
+            Some(BinEntry::Tree(tree_bin)) => {
+                // readers may always walk the nodes of a tree bin as a list.
+                let mut p = tree_bin.first.load(Ordering::SeqCst, guard);
+                while !p.is_null() {
+                    entries += 1;
+                    // safety: the nodes of a tree bin are only dropped after its bin has been
+                    // replaced, which cannot happen until our guard is dropped.
+                    p = unsafe { p.deref() }
+                        .as_tree_node()
+                        .expect("a tree bin only contains tree nodes")
+                        .node
+                        .next
+                        .load(Ordering::SeqCst, guard);
+                }
+                increment(&mut stats.tree_depths, tree_bin.depth(guard));
+            }
+            Some(BinEntry::Node(node)) => {
+                entries = 1;
+                let mut p = node.next.load(Ordering::SeqCst, guard);
+                while !p.is_null() {
+                    entries += 1;
+                    // safety: nodes are only dropped after they have been unlinked from their
+                    // bin, which cannot happen until our guard is dropped.
+                    p = unsafe { p.deref() }
+                        .as_node()
+                        .expect("a list bin only contains nodes")
+                        .next
+                        .load(Ordering::SeqCst, guard);
+                }
+                increment(&mut stats.chain_lengths, entries);
+            }
+            // bins with no entries yet
+            Some(BinEntry::Reserved(_)) | None => increment(&mut stats.chain_lengths, 0),
+            Some(BinEntry::TreeNode(_)) => unreachable!("tree nodes are never the head of a bin"),
+        }
+        stats.entries += entries;
+        bin_entries.push(entries);
+    }
+
+    // resizes claim strides from the end of the table, so the remainder ends up at the start.
+    let mut end = bin_entries.len();
+    while end > 0 {
+        let start = end.saturating_sub(stride);
+        stats
+            .stride_entries
+            .push(bin_entries[start..end].iter().sum());
+        end = start;
+    }
+    stats.stride_entries.reverse();
+    stats
+}
+
+/// Counts one more occurrence of `value` in `histogram`.
+fn increment(histogram: &mut Vec<usize>, value: usize) {
+    if histogram.len() <= value {
+        histogram.resize(value + 1, 0);
+    }
+    histogram[value] += 1;
+}
+
+/// An estimate of the memory a map takes up, as returned by
+/// [`HashMap::memory_usage`](crate::HashMap::memory_usage).
+///
+/// The estimate counts the bytes of the allocations the map makes itself. It does not know about
+/// memory that keys and values own, such as the contents of a `String`, unless it is reported
+/// through [`HashMap::memory_usage_with`](crate::HashMap::memory_usage_with). Nor does it include
+/// the allocator's own overhead.
+///
+/// ```
+/// use flurry::HashMap;
+///
+/// let map = HashMap::new();
+/// let guard = map.guard();
+/// for i in 0..100 {
+///     map.insert(i, i.to_string(), &guard);
+/// }
+/// let usage = map.memory_usage(&guard);
+/// assert!(usage.table() >= map.bucket_count(&guard) * std::mem::size_of::<usize>());
+/// assert!(usage.nodes() >= 100 * std::mem::size_of::<(i32, String)>());
+/// assert_eq!(usage.contents(), 0);
+///
+/// let with_strings = map.memory_usage_with(&guard, |_, v| v.capacity());
+/// assert_eq!(with_strings.contents(), map.values(&guard).map(String::capacity).sum());
+/// assert_eq!(with_strings.total(), usage.total() + with_strings.contents());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryUsage {
+    pub(crate) table: usize,
+    pub(crate) nodes: usize,
+    pub(crate) contents: usize,
+    pub(crate) garbage: Option<usize>,
+}
+
+impl MemoryUsage {
+    /// Returns the number of bytes taken up by the map's table, and by the table it is moving
+    /// its entries to if it is in the middle of a resize.
+    pub fn table(&self) -> usize {
+        self.table
+    }
+
+    /// Returns the number of bytes taken up by the nodes that hold the map's entries, including
+    /// the keys and values themselves.
+    pub fn nodes(&self) -> usize {
+        self.nodes
+    }
+
+    /// Returns the number of bytes that the keys and values own outside of the map's nodes, as
+    /// reported by the closure passed to
+    /// [`HashMap::memory_usage_with`](crate::HashMap::memory_usage_with), or 0 if there was no
+    /// such closure.
+    pub fn contents(&self) -> usize {
+        self.contents
+    }
+
+    /// Returns the number of bytes taken up by the nodes, values, and tables that the map retired
+    /// and that have not been reclaimed yet.
+    ///
+    /// The map only keeps track of this with the `garbage-stats` feature (which the `leak-check`
+    /// feature enables), and returns `None` otherwise.
+    pub fn garbage(&self) -> Option<usize> {
+        self.garbage
+    }
+
+    /// Returns the total number of bytes in the estimate, including the garbage if it is known.
+    pub fn total(&self) -> usize {
+        self.table + self.nodes + self.contents + self.garbage.unwrap_or(0)
+    }
+}
+
+/// Estimates the [`MemoryUsage`] of the entries in `tables`, passing each entry to `contents`.
+pub(crate) fn memory_usage<K, V, F>(
+    tables: &[Option<&Table<K, V>>],
+    garbage: Option<usize>,
+    mut contents: F,
+    guard: &Guard,
+) -> MemoryUsage
+where
+    F: FnMut(&K, &V) -> usize,
+{
+    let mut usage = MemoryUsage {
+        table: 0,
+        nodes: 0,
+        contents: 0,
+        garbage,
+    };
+    let entry = mem::size_of::<BinEntry<K, V>>() + mem::size_of::<V>();
+    // the bins a resize has moved are empty in the first table, and filled in the second.
+    for table in tables.iter().flatten() {
+        usage.table += table.footprint();
+        for i in 0..table.len() {
+            let bin = table.bin(i, guard);
+            // safety: the table was loaded under our guard, and so was the bin, so neither can be
+            // dropped until the guard is.
+            let mut p = match unsafe { bin.as_ref() } {
+                Some(BinEntry::Node(_)) => bin,
+                Some(BinEntry::Tree(tree_bin)) => {
+                    // readers may always walk the nodes of a tree bin as a list.
+                    usage.nodes += mem::size_of::<BinEntry<K, V>>();
+                    tree_bin.first.load(Ordering::SeqCst, guard)
+                }
+                Some(BinEntry::Reserved(_)) => {
+                    usage.nodes += mem::size_of::<BinEntry<K, V>>();
+                    continue;
+                }
+                Some(BinEntry::Moved) | None => continue,
+                Some(BinEntry::TreeNode(_)) => {
+                    unreachable!("tree nodes are never the head of a bin")
+                }
+            };
+            while !p.is_null() {
+                // safety: nodes are only dropped after they have been unlinked from their bin,
+                // which cannot happen until our guard is dropped.
+                let node = match unsafe { p.deref() } {
+                    BinEntry::Node(node) => node,
+                    BinEntry::TreeNode(tree_node) => &tree_node.node,
+                    _ => unreachable!("a bin only contains nodes after its head"),
+                };
+                usage.nodes += entry;
+                let value = node.value.load(Ordering::SeqCst, guard);
+                // safety: values are only dropped after they have been replaced, which cannot
+                // happen until our guard is dropped.
+                usage.contents += contents(&node.key, unsafe { value.deref() });
+                p = node.next.load(Ordering::SeqCst, guard);
+            }
+        }
+    }
+    usage
+}
+
+/// Counters of the garbage a map has retired, as returned by
+/// [`HashMap::garbage_stats`](crate::HashMap::garbage_stats).
+///
+/// Removed entries, replaced values, and old tables are not freed right away, but retired to the
+/// map's collector, which reclaims them once no thread can still be reading them. A thread that
+/// stays pinned for a long time holds up that reclamation, and makes garbage pile up. The
+/// counters only ever grow, so a steadily growing [`queued`](GarbageStats::queued) count is a
+/// sign of such a thread, and [`since`](GarbageStats::since) gives the rates between two
+/// snapshots.
+///
+/// ```
+/// use flurry::HashMap;
+///
+/// let map = HashMap::new();
+/// let guard = map.guard();
+/// map.insert(1, "a", &guard);
+/// let before = map.garbage_stats();
+/// map.insert(1, "b", &guard);
+/// let stats = map.garbage_stats().since(&before);
+/// // the replaced value is held up by our guard
+/// assert_eq!(stats.retired(), 1);
+/// assert_eq!(stats.reclaimed(), 0);
+/// assert!(map.garbage_stats().queued() >= 1);
+/// ```
+#[cfg(feature = "garbage-stats")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GarbageStats {
+    pub(crate) retired: usize,
+    pub(crate) reclaimed: usize,
+    pub(crate) retired_bytes: usize,
+    pub(crate) reclaimed_bytes: usize,
+}
+
+#[cfg(feature = "garbage-stats")]
+impl GarbageStats {
+    /// Returns the number of objects the map has retired.
+    pub fn retired(&self) -> usize {
+        self.retired
+    }
+
+    /// Returns the number of retired objects that have been reclaimed.
+    pub fn reclaimed(&self) -> usize {
+        self.reclaimed
+    }
+
+    /// Returns the number of retired objects that have not been reclaimed yet.
+    pub fn queued(&self) -> usize {
+        self.retired.saturating_sub(self.reclaimed)
+    }
+
+    /// Returns the number of bytes taken up by the objects the map has retired.
+    pub fn retired_bytes(&self) -> usize {
+        self.retired_bytes
+    }
+
+    /// Returns the number of bytes taken up by the retired objects that have been reclaimed.
+    pub fn reclaimed_bytes(&self) -> usize {
+        self.reclaimed_bytes
+    }
+
+    /// Returns the number of bytes taken up by the retired objects that have not been reclaimed
+    /// yet.
+    pub fn queued_bytes(&self) -> usize {
+        self.retired_bytes.saturating_sub(self.reclaimed_bytes)
+    }
+
+    /// Returns the counters for what happened between `earlier` and this snapshot.
+    pub fn since(&self, earlier: &GarbageStats) -> GarbageStats {
+        GarbageStats {
+            retired: self.retired.saturating_sub(earlier.retired),
+            reclaimed: self.reclaimed.saturating_sub(earlier.reclaimed),
+            retired_bytes: self.retired_bytes.saturating_sub(earlier.retired_bytes),
+            reclaimed_bytes: self.reclaimed_bytes.saturating_sub(earlier.reclaimed_bytes),
+        }
+    }
+}
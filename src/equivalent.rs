@@ -0,0 +1,111 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+
+/// Key equivalence, for looking up keys by types other than the ones they can be borrowed as.
+///
+/// Lookups such as [`HashMap::get`](crate::HashMap::get) accept any `Q` that implements this
+/// trait (and [`Comparable`]) for the map's key type `K`. Every `Q` that `K` can be
+/// [borrowed](Borrow) as implements it already, so this only needs to be implemented for lookup
+/// types that `K` cannot be borrowed as. For keys of type `(String, String)`, for example, a
+/// pair of `&str` cannot be borrowed from the key, but can be compared to it:
+///
+/// ```
+/// use flurry::{Comparable, Equivalent, HashMap};
+/// use std::cmp::Ordering;
+///
+/// #[derive(Hash)]
+/// struct Pair<'a>(&'a str, &'a str);
+///
+/// impl Equivalent<(String, String)> for Pair<'_> {
+///     fn equivalent(&self, key: &(String, String)) -> bool {
+///         self.0 == key.0 && self.1 == key.1
+///     }
+/// }
+///
+/// impl Comparable<(String, String)> for Pair<'_> {
+///     fn compare(&self, key: &(String, String)) -> Ordering {
+///         (self.0, self.1).cmp(&(key.0.as_str(), key.1.as_str()))
+///     }
+/// }
+///
+/// let map = HashMap::new();
+/// let guard = map.guard();
+/// map.insert((String::from("grace"), String::from("hopper")), 1906, &guard);
+/// assert_eq!(map.get(&Pair("grace", "hopper"), &guard), Some(&1906));
+/// assert_eq!(map.remove(&Pair("grace", "hopper"), &guard), Some(&1906));
+/// ```
+///
+/// A `Q` that is equivalent to a key must have the same hash as that key. `Pair` above derives
+/// `Hash`, which hashes its fields in order, just like a tuple does, and a `&str` hashes the same
+/// as the `String` it refers to.
+///
+/// This trait and [`Comparable`] mirror the traits of the same names in the `equivalent` crate.
+pub trait Equivalent<K: ?Sized> {
+    /// Returns `true` if `self` is equivalent to `key`.
+    fn equivalent(&self, key: &K) -> bool;
+}
+
+impl<Q: ?Sized, K: ?Sized> Equivalent<K> for Q
+where
+    Q: Eq,
+    K: Borrow<Q>,
+{
+    #[inline]
+    fn equivalent(&self, key: &K) -> bool {
+        *self == *key.borrow()
+    }
+}
+
+/// Key ordering, for looking up keys by types other than the ones they can be borrowed as.
+///
+/// Bins with many entries are kept as trees that are ordered by key, so lookups also need to
+/// know how a `Q` is ordered relative to the map's keys. The ordering must agree with `K`'s
+/// implementation of [`Ord`], and with [`Equivalent`]: `compare` returns [`Ordering::Equal`]
+/// exactly for the keys that `self` is equivalent to. With the `unordered-keys` feature, the
+/// ordering is never used (see [`TreeKey`]).
+///
+/// See [`Equivalent`] for an example.
+pub trait Comparable<K: ?Sized>: Equivalent<K> {
+    /// Compares `self` to `key`, and returns their ordering.
+    fn compare(&self, key: &K) -> Ordering;
+}
+
+impl<Q: ?Sized, K: ?Sized> Comparable<K> for Q
+where
+    Q: TreeKey,
+    K: Borrow<Q>,
+{
+    #[inline]
+    fn compare(&self, key: &K) -> Ordering {
+        self.tree_cmp(key.borrow())
+    }
+}
+
+/// The bound on keys that allows a map to turn bins with many entries into trees.
+///
+/// Trees are ordered by key, so by default, this is implemented for every type that implements
+/// [`Ord`]. With the `unordered-keys` feature, it is implemented for every type that implements
+/// [`Eq`] instead, and bins always stay lists. That allows keys that have no order, such as
+/// wrappers around floats or foreign types, at the cost of lookups taking time linear in the
+/// number of colliding keys when many keys share a bin.
+///
+/// This trait cannot be implemented outside of flurry.
+pub trait TreeKey: Eq {
+    #[doc(hidden)]
+    fn tree_cmp(&self, other: &Self) -> Ordering;
+}
+
+#[cfg(not(feature = "unordered-keys"))]
+impl<T: ?Sized + Ord> TreeKey for T {
+    #[inline]
+    fn tree_cmp(&self, other: &Self) -> Ordering {
+        self.cmp(other)
+    }
+}
+
+#[cfg(feature = "unordered-keys")]
+impl<T: ?Sized + Eq> TreeKey for T {
+    fn tree_cmp(&self, _: &Self) -> Ordering {
+        unreachable!("bins are never turned into trees with the `unordered-keys` feature")
+    }
+}
@@ -0,0 +1,244 @@
+//! A feed of the changes made to a map.
+//!
+//! An [`EventStream`] attached to a map with [`HashMap::with_events`] receives an [`Event`] for
+//! every insertion, update, and removal that the map performs, in the order in which changes to
+//! each key took effect. Events carry clones of the changed keys, and, if the stream was created
+//! with [`EventStream::with_values`], of the new values as well. This makes the stream a
+//! convenient source for invalidating downstream caches or replicating the map elsewhere.
+//!
+//! The stream buffers a bounded number of events. If the consumer falls behind and the buffer
+//! fills up, further events are dropped rather than blocking the map, and counted in
+//! [`EventStream::dropped`], so that the consumer can tell that it missed changes (and, for
+//! example, resynchronize from the map itself).
+//!
+//! ```
+//! use flurry::events::{Event, EventStream};
+//! use flurry::HashMap;
+//! use std::sync::Arc;
+//!
+//! let events = Arc::new(EventStream::with_values(16));
+//! let map = HashMap::new().with_events(Arc::clone(&events));
+//! map.pin().insert(1, "a");
+//! map.pin().insert(1, "b");
+//! map.pin().remove(&1);
+//!
+//! assert_eq!(
+//!     events.drain(),
+//!     vec![
+//!         Event::Inserted { key: 1, value: Some("a") },
+//!         Event::Updated { key: 1, value: Some("b") },
+//!         Event::Removed { key: 1 },
+//!     ]
+//! );
+//! assert_eq!(events.dropped(), 0);
+//! ```
+//!
+//! [`HashMap::with_events`]: crate::HashMap::with_events
+
+use crate::observe::{Change, Observer};
+use parking_lot::{Condvar, Mutex};
+use std::collections::VecDeque;
+use std::fmt::{self, Debug, Formatter};
+use std::time::{Duration, Instant};
+
+/// A change made to a map, as delivered by an [`EventStream`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event<K, V> {
+    /// `key` was inserted.
+    Inserted {
+        /// The inserted key.
+        key: K,
+        /// The inserted value, if the stream includes values.
+        value: Option<V>,
+    },
+    /// The value of `key` was replaced.
+    ///
+    /// This is also delivered when a mutable reference to the value is handed out, such as by
+    /// [`HashMap::get_mut`](crate::HashMap::get_mut), since the value may then be changed in
+    /// place.
+    Updated {
+        /// The key whose value was replaced.
+        key: K,
+        /// The new value, if the stream includes values.
+        ///
+        /// This is always `None` for a value that was changed in place, since the map does not
+        /// see what it was changed to.
+        value: Option<V>,
+    },
+    /// `key` was removed.
+    Removed {
+        /// The removed key.
+        key: K,
+    },
+}
+
+impl<K, V> Event<K, V> {
+    /// Returns the key this event is about.
+    pub fn key(&self) -> &K {
+        match self {
+            Event::Inserted { key, .. } | Event::Updated { key, .. } | Event::Removed { key } => {
+                key
+            }
+        }
+    }
+}
+
+struct Buffer<K, V> {
+    events: VecDeque<Event<K, V>>,
+    capacity: usize,
+    dropped: u64,
+}
+
+/// A bounded buffer of the changes made to one or more maps.
+///
+/// See the [module-level documentation](self) for details.
+pub struct EventStream<K, V> {
+    buffer: Mutex<Buffer<K, V>>,
+    available: Condvar,
+    clone_value: Option<fn(&V) -> V>,
+}
+
+impl<K, V> Debug for EventStream<K, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let buffer = self.buffer.lock();
+        f.debug_struct("EventStream")
+            .field("capacity", &buffer.capacity)
+            .field("len", &buffer.events.len())
+            .field("dropped", &buffer.dropped)
+            .field("values", &self.clone_value.is_some())
+            .finish()
+    }
+}
+
+impl<K, V> EventStream<K, V> {
+    /// Creates a stream that buffers up to `capacity` events, which carry only the changed keys.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is 0.
+    pub fn new(capacity: usize) -> Self {
+        Self::with_value_cloner(capacity, None)
+    }
+
+    fn with_value_cloner(capacity: usize, clone_value: Option<fn(&V) -> V>) -> Self {
+        assert!(
+            capacity > 0,
+            "an event stream must be able to buffer at least one event"
+        );
+        EventStream {
+            buffer: Mutex::new(Buffer {
+                events: VecDeque::with_capacity(capacity),
+                capacity,
+                dropped: 0,
+            }),
+            available: Condvar::new(),
+            clone_value,
+        }
+    }
+
+    /// Returns the number of events that were dropped because the buffer was full.
+    pub fn dropped(&self) -> u64 {
+        self.buffer.lock().dropped
+    }
+
+    /// Returns the number of buffered events.
+    pub fn len(&self) -> usize {
+        self.buffer.lock().events.len()
+    }
+
+    /// Returns `true` if no events are buffered.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes and returns the oldest buffered event, if there is one.
+    pub fn try_recv(&self) -> Option<Event<K, V>> {
+        self.buffer.lock().events.pop_front()
+    }
+
+    /// Removes and returns the oldest buffered event, blocking until there is one.
+    ///
+    /// Note that this blocks forever if no more changes are made to the observed maps.
+    pub fn recv(&self) -> Event<K, V> {
+        let mut buffer = self.buffer.lock();
+        loop {
+            if let Some(event) = buffer.events.pop_front() {
+                return event;
+            }
+            self.available.wait(&mut buffer);
+        }
+    }
+
+    /// Removes and returns the oldest buffered event, blocking for at most `timeout` until there
+    /// is one.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Event<K, V>> {
+        let deadline = Instant::now() + timeout;
+        let mut buffer = self.buffer.lock();
+        loop {
+            if let Some(event) = buffer.events.pop_front() {
+                return Some(event);
+            }
+            if self.available.wait_until(&mut buffer, deadline).timed_out() {
+                return buffer.events.pop_front();
+            }
+        }
+    }
+
+    /// Removes and returns all buffered events, oldest first.
+    pub fn drain(&self) -> Vec<Event<K, V>> {
+        self.buffer.lock().events.drain(..).collect()
+    }
+
+    fn push(&self, event: Event<K, V>) {
+        let mut buffer = self.buffer.lock();
+        if buffer.events.len() == buffer.capacity {
+            buffer.dropped += 1;
+            return;
+        }
+        buffer.events.push_back(event);
+        drop(buffer);
+        self.available.notify_one();
+    }
+}
+
+impl<K, V> EventStream<K, V>
+where
+    V: Clone,
+{
+    /// Creates a stream that buffers up to `capacity` events, which carry both the changed keys
+    /// and their new values.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is 0.
+    pub fn with_values(capacity: usize) -> Self {
+        Self::with_value_cloner(capacity, Some(V::clone))
+    }
+}
+
+impl<K, V> Observer<K, V> for EventStream<K, V>
+where
+    K: Clone + Send,
+    V: Send,
+{
+    fn observe(&self, change: Change<'_, K, V>) {
+        let value = |value: &V| self.clone_value.map(|clone| clone(value));
+        self.push(match change {
+            Change::Inserted { key, value: new } => Event::Inserted {
+                key: key.clone(),
+                value: value(new),
+            },
+            Change::Replaced {
+                key, value: new, ..
+            } => Event::Updated {
+                key: key.clone(),
+                value: value(new),
+            },
+            Change::Removed { key, .. } => Event::Removed { key: key.clone() },
+            Change::Lent { key, .. } => Event::Updated {
+                key: key.clone(),
+                value: None,
+            },
+        });
+    }
+}
@@ -0,0 +1,166 @@
+//! Fault injection at critical points of the map's concurrent algorithms.
+//!
+//! The map is designed so that any thread can stall at any point without compromising the
+//! correctness of the other threads. Fail points let you test that your own code holds up under
+//! such conditions: each named point below is checked right as the map reaches it, and if you
+//! have configured an [`Action`] for it, that action is run on the thread that hit the point.
+//!
+//! | name                | location                                                          |
+//! |---------------------|-------------------------------------------------------------------|
+//! | `bin-cas-before`    | right before a compare-and-swap on the head of a bin              |
+//! | `bin-cas-after`     | right after a _successful_ compare-and-swap on the head of a bin  |
+//! | `transfer-bin`      | during a resize, after the current thread has claimed a bin to move, but before moving it |
+//! | `transfer-finish`   | during a resize, before the thread that finishes it swaps in the new table |
+//! | `count-before`      | before the map's element count is updated                         |
+//! | `table-alloc`       | before allocating a new table, during initialization or resize    |
+//!
+//! Use [`Action::Panic`] at `table-alloc` to simulate allocation failure, after which the map
+//! stays usable, or [`Action::Fail`] to make [`HashMap::try_reserve`] report one. Use [`Action::Sleep`] or [`Action::Callback`] to
+//! stretch a window in which other threads can interleave.
+//!
+//! Fail points are only compiled in with the `failpoints` feature, and are process-global.
+//!
+//! ```
+//! use flurry::failpoints::{self, Action};
+//! use std::time::Duration;
+//!
+//! failpoints::set("transfer-bin", Action::Sleep(Duration::from_millis(1)));
+//! let map = flurry::HashMap::new();
+//! let guard = map.guard();
+//! for i in 0..64 {
+//!     // every bin moved by a resize now takes at least a millisecond
+//!     map.insert(i, i, &guard);
+//! }
+//! failpoints::remove("transfer-bin");
+//! assert!(failpoints::hits("transfer-bin") > 0);
+//! ```
+//!
+//! [`HashMap::try_reserve`]: crate::HashMap::try_reserve
+
+use parking_lot::{const_rwlock, RwLock};
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// What to do when a configured fail point is reached.
+#[derive(Clone)]
+pub enum Action {
+    /// Panic with a message naming the fail point.
+    Panic,
+    /// Yield the current thread's time slice to the operating system.
+    Yield,
+    /// Put the current thread to sleep for the given duration.
+    Sleep(Duration),
+    /// Run the given function.
+    Callback(Arc<dyn Fn() + Send + Sync>),
+    /// Make the operation fail gracefully, if it can.
+    ///
+    /// Only fallible operations react to this: at `table-alloc`,
+    /// [`HashMap::try_reserve`](crate::HashMap::try_reserve) reports that the allocation failed.
+    /// Everywhere else, this does nothing.
+    Fail,
+}
+
+impl fmt::Debug for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::Panic => f.write_str("Panic"),
+            Action::Yield => f.write_str("Yield"),
+            Action::Sleep(d) => f.debug_tuple("Sleep").field(d).finish(),
+            Action::Callback(_) => f.write_str("Callback(..)"),
+            Action::Fail => f.write_str("Fail"),
+        }
+    }
+}
+
+struct FailPoint {
+    name: String,
+    action: Option<Action>,
+    hits: usize,
+}
+
+static POINTS: RwLock<Vec<FailPoint>> = const_rwlock(Vec::new());
+
+// the number of fail points that currently have an action configured, so that the common case of
+// no configured fail points does not have to take the lock
+static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+
+/// Configures `action` to run whenever the fail point `name` is reached.
+///
+/// This replaces any action previously configured for `name`.
+pub fn set(name: &str, action: Action) {
+    let mut points = POINTS.write();
+    if let Some(point) = points.iter_mut().find(|p| p.name == name) {
+        if point.action.is_none() {
+            ACTIVE.fetch_add(1, Ordering::SeqCst);
+        }
+        point.action = Some(action);
+    } else {
+        ACTIVE.fetch_add(1, Ordering::SeqCst);
+        points.push(FailPoint {
+            name: name.to_string(),
+            action: Some(action),
+            hits: 0,
+        });
+    }
+}
+
+/// Removes the action configured for the fail point `name`, if any.
+///
+/// The hit count of the fail point is retained.
+pub fn remove(name: &str) {
+    let mut points = POINTS.write();
+    if let Some(point) = points.iter_mut().find(|p| p.name == name) {
+        if point.action.take().is_some() {
+            ACTIVE.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Removes all configured actions and resets all hit counts.
+pub fn clear() {
+    let mut points = POINTS.write();
+    points.clear();
+    ACTIVE.store(0, Ordering::SeqCst);
+}
+
+/// Returns how many times the fail point `name` was reached while it had an action configured.
+pub fn hits(name: &str) -> usize {
+    POINTS
+        .read()
+        .iter()
+        .find(|p| p.name == name)
+        .map_or(0, |p| p.hits)
+}
+
+/// Runs the action configured for `name`, and returns `true` if it is [`Action::Fail`].
+#[inline]
+pub(crate) fn eval(name: &'static str) -> bool {
+    if ACTIVE.load(Ordering::Relaxed) == 0 {
+        return false;
+    }
+
+    let action = {
+        let mut points = POINTS.write();
+        match points.iter_mut().find(|p| p.name == name) {
+            Some(point) if point.action.is_some() => {
+                point.hits += 1;
+                point.action.clone()
+            }
+            _ => None,
+        }
+    };
+
+    // NOTE: the lock is released before running the action, so that actions may reconfigure
+    // fail points and so that sleeping at one fail point does not block all others.
+    match action {
+        None => {}
+        Some(Action::Panic) => panic!("fail point `{}` triggered", name),
+        Some(Action::Yield) => std::thread::yield_now(),
+        Some(Action::Sleep(d)) => std::thread::sleep(d),
+        Some(Action::Callback(f)) => f(),
+        Some(Action::Fail) => return true,
+    }
+    false
+}
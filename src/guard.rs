@@ -0,0 +1,445 @@
+#[cfg(feature = "reclaim-trace")]
+use crate::trace::{CollectorId, Tracker};
+use crossbeam_epoch::{Guard, LocalHandle};
+#[cfg(feature = "guard-ages")]
+use parking_lot::{const_mutex, Mutex};
+use std::cell::RefCell;
+use std::fmt::{self, Debug, Formatter};
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+#[cfg(feature = "guard-ages")]
+use std::time::{Duration, Instant};
+
+/// A [`Guard`] obtained from [`HashMap::guard`](crate::HashMap::guard).
+///
+/// This behaves exactly like the `Guard` it dereferences to, and can be passed anywhere a `&Guard`
+/// is expected. In addition, the map counts how many such guards are currently alive, which
+/// [`HashMap::active_guards`](crate::HashMap::active_guards) reports. With the `guard-ages`
+/// feature, the map also records when each guard was obtained, so that threads that stay pinned
+/// for an unusually long time (and so hold up the reclamation of garbage) can be detected with
+/// `HashMap::oldest_guard_age`.
+pub struct TrackedGuard {
+    guard: Guard,
+    pins: PinsRef,
+    shard: usize,
+    #[cfg(feature = "guard-ages")]
+    slot: usize,
+}
+
+impl TrackedGuard {
+    pub(crate) fn new(guard: Guard, pins: PinsRef) -> Self {
+        let shard = SHARD.with(|shard| *shard);
+        pins.shards[shard].live.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "guard-ages")]
+        let slot = pins.shards[shard].pinned.lock().insert(Instant::now());
+        TrackedGuard {
+            guard,
+            pins,
+            shard,
+            #[cfg(feature = "guard-ages")]
+            slot,
+        }
+    }
+
+    /// Returns the underlying `Guard`, which is no longer tracked.
+    pub fn into_inner(self) -> Guard {
+        let this = std::mem::ManuallyDrop::new(self);
+        this.untrack();
+        // safety: `this` is never used again, and is not dropped.
+        unsafe { std::ptr::read(&this.guard) }
+    }
+
+    fn untrack(&self) {
+        let shard = &self.pins.shards[self.shard];
+        #[cfg(feature = "guard-ages")]
+        shard.pinned.lock().remove(self.slot);
+        shard.live.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Deref for TrackedGuard {
+    type Target = Guard;
+
+    #[inline]
+    fn deref(&self) -> &Guard {
+        &self.guard
+    }
+}
+
+impl DerefMut for TrackedGuard {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Guard {
+        &mut self.guard
+    }
+}
+
+impl Drop for TrackedGuard {
+    fn drop(&mut self) {
+        self.untrack();
+    }
+}
+
+impl Debug for TrackedGuard {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TrackedGuard").finish()
+    }
+}
+
+/// Something that keeps the current thread pinned, and can stand in for a [`Guard`].
+///
+/// This is implemented for [`Guard`] itself, for [`TrackedGuard`], and for the
+/// [`HashMapRef`](crate::HashMapRef), [`OwnedMapRef`](crate::OwnedMapRef), and
+/// [`HashSetRef`](crate::HashSetRef) that pin their collection. Code that works with several of them, or that wants to pass pins around without
+/// naming the guard type of the reclamation scheme flurry uses, can be generic over `AsGuard`.
+///
+/// This trait is sealed, and cannot be implemented outside of flurry.
+///
+/// ```
+/// use flurry::epoch::AsGuard;
+/// use flurry::HashMap;
+///
+/// fn total(map: &HashMap<u32, u64>, keys: &[u32], pin: &impl AsGuard) -> u64 {
+///     keys.iter().filter_map(|k| map.get(k, pin.as_guard())).sum()
+/// }
+///
+/// let map = HashMap::new();
+/// map.pin().insert(1, 10);
+/// map.pin().insert(2, 20);
+/// assert_eq!(total(&map, &[1, 2, 3], &map.guard()), 30);
+/// assert_eq!(total(&map, &[1], &map.pin()), 10);
+/// assert_eq!(total(&map, &[2], &flurry::epoch::pin()), 20);
+/// ```
+pub trait AsGuard: private::Sealed {
+    /// Returns the guard that keeps the current thread pinned.
+    fn as_guard(&self) -> &Guard;
+}
+
+pub(crate) mod private {
+    /// Keeps [`AsGuard`](super::AsGuard) from being implemented outside of flurry.
+    #[allow(unreachable_pub)]
+    pub trait Sealed {}
+}
+
+impl private::Sealed for Guard {}
+
+impl AsGuard for Guard {
+    #[inline]
+    fn as_guard(&self) -> &Guard {
+        self
+    }
+}
+
+impl private::Sealed for TrackedGuard {}
+
+impl AsGuard for TrackedGuard {
+    #[inline]
+    fn as_guard(&self) -> &Guard {
+        &self.guard
+    }
+}
+
+/// The number of times [`Collector::flush`] flushes the collector.
+const FLUSH_ROUNDS: usize = 128;
+
+/// A domain of memory reclamation that several maps and sets can share.
+///
+/// By default, every map and set uses the global collector that [`pin`](crate::epoch::pin) also
+/// uses, so that a guard from any of them can be used with all of them. Garbage in the global
+/// collector is held up by every thread that stays pinned anywhere in the program, though. Maps
+/// and sets that are created [`with_collector`](crate::HashMap::with_collector) instead retire
+/// their garbage to a separate collector, which only their own guards hold up.
+///
+/// All the maps and sets that share a `Collector` can be used with the same guard, which may come
+/// from [`Collector::guard`], or from any of them. Guards from other collectors are rejected with
+/// a panic.
+///
+/// ```
+/// use flurry::epoch::Collector;
+/// use flurry::{HashMap, HashSet};
+///
+/// let collector = Collector::new();
+/// let users = HashMap::new().with_collector(&collector);
+/// let admins = HashSet::new().with_collector(&collector);
+///
+/// let guard = collector.guard();
+/// users.insert(1, "root", &guard);
+/// admins.insert(1, &guard);
+/// let names: Vec<_> = admins
+///     .iter(&guard)
+///     .filter_map(|id| users.get(id, &guard))
+///     .collect();
+/// assert_eq!(names, [&"root"]);
+/// ```
+#[derive(Clone)]
+pub struct Collector {
+    collector: crossbeam_epoch::Collector,
+    pins: PinsRef,
+}
+
+impl Collector {
+    /// Creates a new collector, which is not shared with any map or set yet.
+    pub fn new() -> Self {
+        Collector {
+            collector: crossbeam_epoch::Collector::new(),
+            pins: PinsRef::Shared(Arc::new(Pins::new())),
+        }
+    }
+
+    /// Returns the global collector that maps and sets use by default.
+    pub(crate) fn global() -> Self {
+        Collector {
+            collector: crossbeam_epoch::default_collector().clone(),
+            pins: PinsRef::Global(&GLOBAL),
+        }
+    }
+
+    /// Pin a `Guard` for use with the maps and sets that share this collector.
+    ///
+    /// Keep in mind that for as long as you hold onto this `Guard`, you are preventing the
+    /// collection of garbage generated by all of them.
+    pub fn guard(&self) -> TrackedGuard {
+        TrackedGuard::new(self.collector.register().pin(), self.pins.clone())
+    }
+
+    /// Pin a `Guard` for use with the maps and sets that share this collector, reusing a handle
+    /// to the collector that the calling thread keeps around.
+    ///
+    /// See [`HashMap::pin_cached`](crate::HashMap::pin_cached) for when this is worthwhile.
+    /// Unlike the guards from [`Collector::guard`], the returned guard is not counted by
+    /// [`HashMap::active_guards`](crate::HashMap::active_guards).
+    pub fn guard_cached(&self) -> Guard {
+        pin_cached(&self.collector)
+    }
+
+    /// Hands the garbage of the calling thread to the collector, and tries to advance the epoch
+    /// and reclaim all the garbage that no thread is holding up anymore.
+    ///
+    /// The collector normally does this a little at a time, as threads pin and unpin. Flushing
+    /// it at a known quiescent point, such as after a large [`clear`](crate::HashMap::clear),
+    /// frees the memory of removed entries without waiting for later operations to do so.
+    ///
+    /// This is best-effort: the epoch cannot advance while some thread stays pinned, so garbage
+    /// that was retired while any guard of this collector that is still alive was pinned is not
+    /// reclaimed, and that includes the guards of the calling thread.
+    pub fn flush(&self) {
+        // every `flush` hands our garbage to the collector, and then tries to advance the global
+        // epoch and free a handful of bags of garbage from two epochs ago. we don't know how much
+        // garbage is queued up, so we just do this a bunch of times.
+        let handle = self.collector.register();
+        for _ in 0..FLUSH_ROUNDS {
+            handle.pin().flush();
+        }
+        if self.collector == *crossbeam_epoch::default_collector() {
+            // garbage may also have been retired through this thread's default handle
+            crossbeam_epoch::pin().flush();
+        }
+    }
+
+    /// Returns the id that the events of the [`trace`](crate::trace) module carry for this
+    /// collector.
+    ///
+    /// This is only available with the `reclaim-trace` feature.
+    #[cfg(feature = "reclaim-trace")]
+    pub fn trace_id(&self) -> CollectorId {
+        self.pins.tracker().id()
+    }
+
+    pub(crate) fn from_parts(collector: crossbeam_epoch::Collector, pins: PinsRef) -> Self {
+        Collector { collector, pins }
+    }
+
+    pub(crate) fn epoch_collector(&self) -> &crossbeam_epoch::Collector {
+        &self.collector
+    }
+
+    pub(crate) fn pins(&self) -> &PinsRef {
+        &self.pins
+    }
+}
+
+impl Default for Collector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PartialEq for Collector {
+    fn eq(&self, other: &Self) -> bool {
+        self.collector == other.collector
+    }
+}
+
+impl Eq for Collector {}
+
+impl Debug for Collector {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Collector").finish()
+    }
+}
+
+thread_local! {
+    /// This thread's handle to the last collector other than the global one that it pinned with
+    /// [`pin_cached`].
+    static CACHED: RefCell<Option<(crossbeam_epoch::Collector, LocalHandle)>> = RefCell::default();
+}
+
+/// Pins the current thread with a handle to `collector` that is kept for later calls, rather than
+/// registering a new handle with the collector every time.
+///
+/// The global collector has a handle per thread already. For other collectors, each thread keeps
+/// a handle to the one it pinned with most recently, which keeps that collector (and the garbage
+/// that the thread retired to it) alive until the thread pins with another collector or exits.
+pub(crate) fn pin_cached(collector: &crossbeam_epoch::Collector) -> Guard {
+    if collector == crossbeam_epoch::default_collector() {
+        return crossbeam_epoch::pin();
+    }
+    CACHED
+        .try_with(|cached| {
+            let mut cached = cached.borrow_mut();
+            if let Some((ref c, ref handle)) = *cached {
+                if c == collector {
+                    return handle.pin();
+                }
+            }
+            let handle = collector.register();
+            let guard = handle.pin();
+            *cached = Some((collector.clone(), handle));
+            guard
+        })
+        // the thread-local may already be gone while the thread exits
+        .unwrap_or_else(|_| collector.register().pin())
+}
+
+/// The number of independently locked parts of a `Pins` registry.
+///
+/// Threads are spread across shards so that obtaining a guard rarely contends with other threads.
+const SHARDS: usize = 16;
+
+static NEXT_SHARD: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static SHARD: usize = NEXT_SHARD.fetch_add(1, Ordering::Relaxed) % SHARDS;
+}
+
+/// The live [`TrackedGuard`]s of a single collector, and, with the `reclaim-trace` feature, the
+/// collector's reclamation bookkeeping.
+pub(crate) struct Pins {
+    shards: [Shard; SHARDS],
+    #[cfg(feature = "reclaim-trace")]
+    tracker: Tracker,
+}
+
+// keep shards on separate cache lines, so that threads using different shards do not contend
+#[repr(align(64))]
+struct Shard {
+    /// The number of live guards, which is all that is tracked without the `guard-ages` feature,
+    /// so that obtaining a guard only costs an uncontended atomic increment.
+    live: AtomicUsize,
+    #[cfg(feature = "guard-ages")]
+    pinned: Mutex<Slab>,
+}
+
+/// The times at which the guards in a shard were pinned.
+#[cfg(feature = "guard-ages")]
+struct Slab {
+    pinned: Vec<Option<Instant>>,
+    free: Vec<usize>,
+}
+
+#[cfg(feature = "guard-ages")]
+impl Slab {
+    fn insert(&mut self, at: Instant) -> usize {
+        match self.free.pop() {
+            Some(slot) => {
+                self.pinned[slot] = Some(at);
+                slot
+            }
+            None => {
+                self.pinned.push(Some(at));
+                self.pinned.len() - 1
+            }
+        }
+    }
+
+    fn remove(&mut self, slot: usize) {
+        self.pinned[slot] = None;
+        self.free.push(slot);
+    }
+}
+
+// only used to initialize the array of shards below, where each use creates a fresh shard
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY_SHARD: Shard = Shard {
+    live: AtomicUsize::new(0),
+    #[cfg(feature = "guard-ages")]
+    pinned: const_mutex(Slab {
+        pinned: Vec::new(),
+        free: Vec::new(),
+    }),
+};
+
+static GLOBAL: Pins = Pins::global();
+
+/// The registry of live guards of a collector, which is static for the global collector.
+#[derive(Clone)]
+pub(crate) enum PinsRef {
+    Global(&'static Pins),
+    Shared(Arc<Pins>),
+}
+
+impl Deref for PinsRef {
+    type Target = Pins;
+
+    #[inline]
+    fn deref(&self) -> &Pins {
+        match *self {
+            PinsRef::Global(pins) => pins,
+            PinsRef::Shared(ref pins) => pins,
+        }
+    }
+}
+
+impl Pins {
+    /// The registry of the global collector.
+    const fn global() -> Self {
+        Pins {
+            shards: [EMPTY_SHARD; SHARDS],
+            #[cfg(feature = "reclaim-trace")]
+            tracker: Tracker::new(CollectorId::DEFAULT),
+        }
+    }
+
+    /// The registry of a collector other than the global one.
+    fn new() -> Self {
+        Pins {
+            shards: [EMPTY_SHARD; SHARDS],
+            #[cfg(feature = "reclaim-trace")]
+            tracker: Tracker::new(CollectorId::next()),
+        }
+    }
+
+    #[cfg(feature = "reclaim-trace")]
+    pub(crate) fn tracker(&self) -> &Tracker {
+        &self.tracker
+    }
+
+    /// Returns the number of live guards.
+    pub(crate) fn active(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.live.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// Returns how long the oldest live guard has been alive.
+    #[cfg(feature = "guard-ages")]
+    pub(crate) fn oldest(&self) -> Option<Duration> {
+        let oldest = self
+            .shards
+            .iter()
+            .filter_map(|shard| shard.pinned.lock().pinned.iter().flatten().min().copied())
+            .min()?;
+        Some(oldest.elapsed())
+    }
+}
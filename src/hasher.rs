@@ -0,0 +1,150 @@
+use std::collections::hash_map::RandomState as StdRandomState;
+use std::fmt::{self, Debug, Formatter};
+use std::hash::{BuildHasher, Hasher};
+
+/// Default hasher for [`HashMap`](crate::HashMap).
+///
+/// This is [`ahash`](https://docs.rs/ahash) keyed with a pair of seeds. By default, the seeds
+/// are chosen randomly for every new builder, which makes the map resistant to attacks that
+/// cause many collisions. If you need keys to hash the same way across maps or runs, for
+/// example so that a test is reproducible, you can pick the seeds yourself with
+/// [`DefaultHashBuilder::with_seeds`].
+///
+/// Seeded hashes are only reproducible within one build of a program on one target: `ahash`
+/// picks its algorithm based on the CPU features it is compiled for, and does not promise stable
+/// output across versions. If several machines or builds need to agree on hashes, use a portable
+/// hasher with a fixed output instead, through [`HashMap::with_hasher`](crate::HashMap::with_hasher).
+///
+/// Note that choosing the seeds manually can expose a DoS attack vector if the seeds are known
+/// to whoever controls the keys.
+///
+/// ```
+/// use flurry::{DefaultHashBuilder, HashMap};
+///
+/// let a: HashMap<i32, i32> = HashMap::with_hasher(DefaultHashBuilder::with_seeds(1, 2));
+/// let b: HashMap<i32, i32> = HashMap::with_hasher(DefaultHashBuilder::with_seeds(1, 2));
+/// assert_eq!(a.hasher().seeds(), b.hasher().seeds());
+///
+/// // a randomly seeded builder can be recreated from its seeds
+/// let random = DefaultHashBuilder::new();
+/// let (k0, k1) = random.seeds();
+/// let copy = DefaultHashBuilder::with_seeds(k0, k1);
+/// assert_eq!(copy.seeds(), random.seeds());
+/// ```
+#[derive(Clone)]
+pub struct DefaultHashBuilder {
+    seeds: (u64, u64),
+    inner: ahash::RandomState,
+}
+
+impl DefaultHashBuilder {
+    /// Creates a builder with randomly chosen seeds.
+    pub fn new() -> Self {
+        // std's RandomState is randomly keyed once per thread, and then incremented for each new
+        // instance, so hashing with a fresh one gives us distinct, unpredictable seeds.
+        let random = StdRandomState::new();
+        let mut h = random.build_hasher();
+        h.write_u8(0);
+        let k0 = h.finish();
+        h.write_u8(1);
+        let k1 = h.finish();
+        Self::with_seeds(k0, k1)
+    }
+
+    /// Creates a builder that uses the given seeds.
+    ///
+    /// Builders created with the same seeds produce the same hashes for the same keys, within the
+    /// same build of a program.
+    pub fn with_seeds(k0: u64, k1: u64) -> Self {
+        DefaultHashBuilder {
+            seeds: (k0, k1),
+            inner: ahash::RandomState::with_seeds(k0, k1),
+        }
+    }
+
+    /// Returns the seeds used by this builder.
+    pub fn seeds(&self) -> (u64, u64) {
+        self.seeds
+    }
+}
+
+impl Default for DefaultHashBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debug for DefaultHashBuilder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        // the seeds are deliberately not printed, since knowing them makes collisions easy to find
+        f.debug_struct("DefaultHashBuilder").finish()
+    }
+}
+
+impl BuildHasher for DefaultHashBuilder {
+    type Hasher = ahash::AHasher;
+
+    #[inline]
+    fn build_hasher(&self) -> Self::Hasher {
+        self.inner.build_hasher()
+    }
+}
+
+/// A final step of hashing that the map applies to every hash before using it, as set with
+/// [`HashMap::with_hash_spreader`](crate::HashMap::with_hash_spreader).
+///
+/// The map picks the bin for a key from the low bits of its hash, and orders the entries in a
+/// bin by their full hashes. By default, it uses the hashes that its [`BuildHasher`] produces as
+/// they are, which costs nothing if the hasher already distributes its output well, like the
+/// [`DefaultHashBuilder`] does. A hasher whose low bits are weak, such as one that passes integer
+/// keys through unchanged, can instead be paired with a spreader that mixes the high bits into
+/// the low ones, like [`Fmix64`].
+///
+/// Any `Fn(u64) -> u64` that is `Send` and `Sync` can be used as a spreader. A spreader must
+/// always return the same output for the same input.
+///
+/// ```
+/// use flurry::{Fmix64, HashMap};
+///
+/// let map = HashMap::new().with_hash_spreader(Fmix64);
+/// map.pin().insert(1, "one");
+/// assert_eq!(map.pin().get(&1), Some(&"one"));
+///
+/// // or a closure
+/// let map = HashMap::new().with_hash_spreader(|hash: u64| hash ^ (hash >> 32));
+/// map.pin().insert(1, "one");
+/// assert_eq!(map.pin().get(&1), Some(&"one"));
+/// ```
+pub trait HashSpreader: Send + Sync {
+    /// Returns the hash that the map uses for a key that its hasher hashed to `hash`.
+    fn spread(&self, hash: u64) -> u64;
+}
+
+impl<F> HashSpreader for F
+where
+    F: Fn(u64) -> u64 + Send + Sync,
+{
+    #[inline]
+    fn spread(&self, hash: u64) -> u64 {
+        self(hash)
+    }
+}
+
+/// A [`HashSpreader`] that applies the finalizer of MurmurHash3 (`fmix64`).
+///
+/// Every bit of the input affects every bit of the output, so this gives well-distributed low
+/// bits even for hashes that only differ in their high bits, at the cost of two multiplications
+/// per hash.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Fmix64;
+
+impl HashSpreader for Fmix64 {
+    #[inline]
+    fn spread(&self, mut hash: u64) -> u64 {
+        hash ^= hash >> 33;
+        hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+        hash ^= hash >> 33;
+        hash = hash.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+        hash ^ (hash >> 33)
+    }
+}
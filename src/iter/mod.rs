@@ -1,9 +1,17 @@
 mod traverser;
 pub(crate) use traverser::NodeIter;
 
+use crate::node::Versions;
+use crate::observe::{Change, Observers};
+use crate::{HashMap, TrackedGuard, TreeKey};
 use crossbeam_epoch::Guard;
+use std::fmt::{self, Debug, Formatter};
+use std::hash::{BuildHasher, Hash};
 use std::sync::atomic::Ordering;
 
+#[cfg(feature = "rayon")]
+pub use crate::rayon_impls::{ParIter, ParKeys, ParValues};
+
 /// An iterator over a map's entries.
 ///
 /// See [`HashMap::iter`](crate::HashMap::iter) for details.
@@ -13,6 +21,19 @@ pub struct Iter<'g, K, V> {
     pub(crate) guard: &'g Guard,
 }
 
+impl<K, V> Iter<'_, K, V> {
+    /// Splits off an iterator over about half of the entries this iterator has yet to yield, if
+    /// it has not yielded any yet.
+    #[cfg(feature = "rayon")]
+    pub(crate) fn split(&mut self) -> Option<Self> {
+        let node_iter = self.node_iter.split()?;
+        Some(Iter {
+            node_iter,
+            guard: self.guard,
+        })
+    }
+}
+
 impl<'g, K, V> Iterator for Iter<'g, K, V> {
     type Item = (&'g K, &'g V);
     fn next(&mut self) -> Option<Self::Item> {
@@ -32,6 +53,15 @@ pub struct Keys<'g, K, V> {
     pub(crate) node_iter: NodeIter<'g, K, V>,
 }
 
+impl<K, V> Keys<'_, K, V> {
+    /// See [`Iter::split`].
+    #[cfg(feature = "rayon")]
+    pub(crate) fn split(&mut self) -> Option<Self> {
+        let node_iter = self.node_iter.split()?;
+        Some(Keys { node_iter })
+    }
+}
+
 impl<'g, K, V> Iterator for Keys<'g, K, V> {
     type Item = &'g K;
     fn next(&mut self) -> Option<Self::Item> {
@@ -49,6 +79,18 @@ pub struct Values<'g, K, V> {
     pub(crate) guard: &'g Guard,
 }
 
+impl<K, V> Values<'_, K, V> {
+    /// See [`Iter::split`].
+    #[cfg(feature = "rayon")]
+    pub(crate) fn split(&mut self) -> Option<Self> {
+        let node_iter = self.node_iter.split()?;
+        Some(Values {
+            node_iter,
+            guard: self.guard,
+        })
+    }
+}
+
 impl<'g, K, V> Iterator for Values<'g, K, V> {
     type Item = &'g V;
     fn next(&mut self) -> Option<Self::Item> {
@@ -60,6 +102,278 @@ impl<'g, K, V> Iterator for Values<'g, K, V> {
     }
 }
 
+/// A mutable iterator over a map's entries.
+///
+/// See [`HashMap::iter_mut`](crate::HashMap::iter_mut) for details.
+pub struct IterMut<'a, K, V> {
+    pub(crate) node_iter: NodeIter<'a, K, V>,
+    pub(crate) versions: &'a Versions,
+    pub(crate) observers: &'a Observers<K, V>,
+    pub(crate) guard: &'a Guard,
+}
+
+impl<K, V> Debug for IterMut<'_, K, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IterMut").finish()
+    }
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V>
+where
+    K: Eq,
+{
+    type Item = (&'a K, &'a mut V);
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.node_iter.next()?;
+        if self.observers.is_active() {
+            let value = node.value.load(Ordering::SeqCst, self.guard);
+            // safety: the iterator borrows the map mutably, so the value cannot have been dropped
+            let value = unsafe { value.deref() };
+            self.observers.notify(Change::Lent {
+                key: &node.key,
+                value,
+            });
+        }
+        // safety: the iterator borrows the map mutably, and visits every node only once
+        let value = unsafe { node.value_mut(self.versions.next(), self.guard) };
+        Some((&node.key, value))
+    }
+}
+
+/// A mutable iterator over a map's values.
+///
+/// See [`HashMap::values_mut`](crate::HashMap::values_mut) for details.
+#[derive(Debug)]
+pub struct ValuesMut<'a, K, V> {
+    pub(crate) iter: IterMut<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for ValuesMut<'a, K, V>
+where
+    K: Eq,
+{
+    type Item = &'a mut V;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|(_, value)| value)
+    }
+}
+
+/// An iterator over clones of a map's entries that holds a guard of its own.
+///
+/// See [`HashMap::owned_iter`](crate::HashMap::owned_iter) for details.
+pub struct OwnedIter<'m, K, V> {
+    // borrows from the guard, which lives on the heap so that it stays put when the iterator
+    // is moved
+    pub(crate) node_iter: NodeIter<'m, K, V>,
+    pub(crate) guard: *mut TrackedGuard,
+}
+
+impl<K, V> Debug for OwnedIter<'_, K, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OwnedIter").finish()
+    }
+}
+
+impl<K, V> Iterator for OwnedIter<'_, K, V>
+where
+    K: Clone,
+    V: Clone,
+{
+    type Item = (K, V);
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.node_iter.next()?;
+        // safety: the guard is only dropped along with the iterator
+        let guard = unsafe { &**self.guard };
+        let value = node.value.load(Ordering::SeqCst, guard);
+        // safety: flurry does not drop or move until after guard drop
+        let value = unsafe { value.deref() };
+        Some((node.key.clone(), value.clone()))
+    }
+}
+
+impl<K, V> Drop for OwnedIter<'_, K, V> {
+    fn drop(&mut self) {
+        // safety: the guard was leaked from a box when the iterator was created, and nothing
+        // that borrows from it is used after this
+        drop(unsafe { Box::from_raw(self.guard) });
+    }
+}
+
+/// An iterator over clones of a map's values that holds a guard of its own.
+///
+/// See [`HashMap::values_cloned`](crate::HashMap::values_cloned) for details.
+pub struct ValuesCloned<'m, K, V> {
+    // borrows from the guard, which lives on the heap so that it stays put when the iterator
+    // is moved
+    pub(crate) node_iter: NodeIter<'m, K, V>,
+    pub(crate) guard: *mut TrackedGuard,
+}
+
+impl<K, V> Debug for ValuesCloned<'_, K, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ValuesCloned").finish()
+    }
+}
+
+impl<K, V> Iterator for ValuesCloned<'_, K, V>
+where
+    V: Clone,
+{
+    type Item = V;
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.node_iter.next()?;
+        // safety: the guard is only dropped along with the iterator
+        let guard = unsafe { &**self.guard };
+        let value = node.value.load(Ordering::SeqCst, guard);
+        // safety: flurry does not drop or move until after guard drop
+        Some(unsafe { value.deref() }.clone())
+    }
+}
+
+impl<K, V> Drop for ValuesCloned<'_, K, V> {
+    fn drop(&mut self) {
+        // safety: the guard was leaked from a box when the iterator was created, and nothing
+        // that borrows from it is used after this
+        drop(unsafe { Box::from_raw(self.guard) });
+    }
+}
+
+/// An owning iterator over a map's entries.
+///
+/// Returned by the [`IntoIterator`] implementation of [`HashMap`](crate::HashMap).
+#[derive(Debug)]
+pub struct IntoIter<K, V> {
+    pub(crate) entries: std::vec::IntoIter<(K, V)>,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+
+/// An owning iterator over a map's keys.
+///
+/// See [`HashMap::into_keys`](crate::HashMap::into_keys) for details.
+#[derive(Debug)]
+pub struct IntoKeys<K, V> {
+    pub(crate) entries: std::vec::IntoIter<(K, V)>,
+}
+
+impl<K, V> Iterator for IntoKeys<K, V> {
+    type Item = K;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next().map(|(key, _)| key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoKeys<K, V> {}
+
+/// An owning iterator over a map's values.
+///
+/// See [`HashMap::into_values`](crate::HashMap::into_values) for details.
+#[derive(Debug)]
+pub struct IntoValues<K, V> {
+    pub(crate) entries: std::vec::IntoIter<(K, V)>,
+}
+
+impl<K, V> Iterator for IntoValues<K, V> {
+    type Item = V;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoValues<K, V> {}
+
+/// An iterator that removes a map's entries and yields them.
+///
+/// See [`HashMap::drain`](crate::HashMap::drain) for details.
+pub struct Drain<'g, K, V, S> {
+    pub(crate) map: &'g HashMap<K, V, S>,
+    pub(crate) node_iter: NodeIter<'g, K, V>,
+    pub(crate) guard: &'g Guard,
+}
+
+impl<K, V, S> Debug for Drain<'_, K, V, S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Drain").finish()
+    }
+}
+
+impl<'g, K, V, S> Iterator for Drain<'g, K, V, S>
+where
+    K: 'static + Sync + Send + Clone + Hash + TreeKey,
+    V: 'static + Sync + Send,
+    S: BuildHasher,
+{
+    type Item = (&'g K, &'g V);
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let node = self.node_iter.next()?;
+            // another thread may have removed the entry since we got to it
+            if let Some(entry) = self.map.remove_entry(&node.key, self.guard) {
+                return Some(entry);
+            }
+        }
+    }
+}
+
+/// An iterator that removes the entries of a map that match a predicate, and yields them.
+///
+/// See [`HashMap::extract_if`](crate::HashMap::extract_if) for details.
+pub struct ExtractIf<'g, K, V, S, F> {
+    pub(crate) map: &'g HashMap<K, V, S>,
+    pub(crate) node_iter: NodeIter<'g, K, V>,
+    pub(crate) pred: F,
+    pub(crate) guard: &'g Guard,
+}
+
+impl<K, V, S, F> Debug for ExtractIf<'_, K, V, S, F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExtractIf").finish()
+    }
+}
+
+impl<'g, K, V, S, F> Iterator for ExtractIf<'g, K, V, S, F>
+where
+    K: 'static + Sync + Send + Clone + Hash + TreeKey,
+    V: 'static + Sync + Send,
+    S: BuildHasher,
+    F: FnMut(&K, &V) -> bool,
+{
+    type Item = (&'g K, &'g V);
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let node = self.node_iter.next()?;
+            let key = &node.key;
+            let pred = &mut self.pred;
+            // the predicate is checked under the bin lock, against the entry's current value
+            if let Some(value) = self
+                .map
+                .remove_if(key, |value| pred(key, value), self.guard)
+            {
+                return Some((key, value));
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::HashMap;
@@ -26,6 +26,9 @@ pub(crate) struct NodeIter<'g, K, V> {
     /// Initial table size
     base_size: usize,
 
+    /// The length and index of each table on the stack when we read the bin we are iterating over
+    moved_from: Vec<(usize, usize)>,
+
     guard: &'g Guard,
 }
 
@@ -49,10 +52,43 @@ impl<'g, K, V> NodeIter<'g, K, V> {
             base_index: 0,
             index: 0,
             base_limit: len,
+            moved_from: Vec::new(),
             guard,
         }
     }
 
+    /// Splits off an iterator over the upper half of the top-level bins this iterator covers,
+    /// and keeps the lower half for itself.
+    ///
+    /// Returns `None` if the iterator has already yielded entries, or covers a single bin. The
+    /// two iterators together yield the same entries as this one would have on its own, since
+    /// the entries a top-level bin moves into during a resize are only ever visited through it.
+    #[cfg_attr(not(feature = "rayon"), allow(dead_code))]
+    pub(crate) fn split(&mut self) -> Option<Self> {
+        if self.prev.is_some() || self.stack.is_some() || self.index != self.base_index {
+            return None;
+        }
+        let mid = self.base_index + (self.base_limit - self.base_index) / 2;
+        if mid == self.base_index {
+            return None;
+        }
+
+        let upper = Self {
+            table: self.table,
+            stack: None,
+            spare: None,
+            prev: None,
+            base_size: self.base_size,
+            base_index: mid,
+            index: mid,
+            base_limit: self.base_limit,
+            moved_from: Vec::new(),
+            guard: self.guard,
+        };
+        self.base_limit = mid;
+        Some(upper)
+    }
+
     fn push_state(&mut self, t: &'g Table<K, V>, i: usize, n: usize) {
         let mut s = self.spare.take();
         if let Some(ref mut s) = s {
@@ -109,9 +145,42 @@ impl<'g, K, V> NodeIter<'g, K, V> {
     }
 }
 
-impl<'g, K, V> Iterator for NodeIter<'g, K, V> {
-    type Item = &'g Node<K, V>;
-    fn next(&mut self) -> Option<Self::Item> {
+impl<'g, K, V> NodeIter<'g, K, V> {
+    /// Returns `true` if `node`, which we reached through the forwarding nodes in `moved_from`,
+    /// belongs to the bins that those forwarding nodes replaced.
+    ///
+    /// This always holds when the map grows, since every bin then moves into bins of its own.
+    /// When the map shrinks, several bins move into the same bin of the next table, and we come
+    /// across that bin once for each of them, so we only yield the entries of one at a time.
+    fn moved_here(&self, node: &Node<K, V>) -> bool {
+        self.moved_from
+            .iter()
+            .all(|&(length, index)| node.hash & (length as u64 - 1) == index as u64)
+    }
+
+    /// Returns `true` if bin `i` of a table with `n` bins can hold entries that belong to the
+    /// bins we followed forwarding nodes from.
+    ///
+    /// Once the map has both shrunk and grown again, most of the bins we descend into hold
+    /// entries of other bins only, and visiting them anyway makes iteration take exponentially
+    /// long.
+    fn reachable(&self, i: usize, n: usize) -> bool {
+        let mut s = self.stack.as_ref();
+        while let Some(frame) = s {
+            let matches = if frame.length <= n {
+                i & (frame.length - 1) == frame.index
+            } else {
+                frame.index & (n - 1) == i
+            };
+            if !matches {
+                return false;
+            }
+            s = frame.next.as_ref();
+        }
+        true
+    }
+
+    fn next_node(&mut self) -> Option<&'g Node<K, V>> {
         let mut e = None;
         if let Some(prev) = self.prev {
             let next = prev.next.load(Ordering::SeqCst, self.guard);
@@ -128,8 +197,9 @@ impl<'g, K, V> Iterator for NodeIter<'g, K, V> {
                     BinEntry::TreeNode(tree_node) => {
                         e = Some(&tree_node.node);
                     }
-                    BinEntry::Moved => unreachable!("Nodes can only point to Nodes or TreeNodes"),
-                    BinEntry::Tree(_) => unreachable!("Nodes can only point to Nodes or TreeNodes"),
+                    BinEntry::Moved | BinEntry::Tree(_) | BinEntry::Reserved(_) => {
+                        unreachable!("Nodes can only point to Nodes or TreeNodes")
+                    }
                 }
             }
         }
@@ -152,7 +222,11 @@ impl<'g, K, V> Iterator for NodeIter<'g, K, V> {
             let t = self.table.expect("is_none in if above");
             let i = self.index;
             let n = t.len();
-            let bin = t.bin(i, self.guard);
+            let bin = if self.reachable(i, n) {
+                t.bin(i, self.guard)
+            } else {
+                Shared::null()
+            };
             if !bin.is_null() {
                 // safety: flurry does not drop or move until after guard drop
                 let bin = unsafe { bin.deref() };
@@ -160,10 +234,13 @@ impl<'g, K, V> Iterator for NodeIter<'g, K, V> {
                     BinEntry::Moved => {
                         // recurse down into the target table
                         // safety: same argument as for following Moved in Table::find
-                        self.table = Some(unsafe { t.next_table(self.guard).deref() });
+                        let next_table = unsafe { t.next_table(self.guard).deref() };
+                        self.table = Some(next_table);
                         self.prev = None;
                         // make sure we can get back "up" to where we're at
                         self.push_state(t, i, n);
+                        // if the map is shrinking, our bin moved into a bin with a lower index
+                        self.index = i & (next_table.len() - 1);
                         continue;
                     }
                     BinEntry::Node(node) => {
@@ -187,10 +264,20 @@ impl<'g, K, V> Iterator for NodeIter<'g, K, V> {
                             .node,
                         );
                     }
+                    // a reserved bin has no entries yet
+                    BinEntry::Reserved(_) => {}
                     BinEntry::TreeNode(_) => unreachable!(
                         "The head of a bin cannot be a TreeNode directly without BinEntry::Tree"
                     ),
                 }
+
+                // remember how we got here, since the stack changes below
+                self.moved_from.clear();
+                let mut s = self.stack.as_ref();
+                while let Some(frame) = s {
+                    self.moved_from.push((frame.length, frame.index));
+                    s = frame.next.as_ref();
+                }
             }
 
             if self.stack.is_some() {
@@ -206,6 +293,18 @@ impl<'g, K, V> Iterator for NodeIter<'g, K, V> {
     }
 }
 
+impl<'g, K, V> Iterator for NodeIter<'g, K, V> {
+    type Item = &'g Node<K, V>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let node = self.next_node()?;
+            if self.moved_here(node) {
+                return Some(node);
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 struct TableStack<'g, K, V> {
     length: usize,
@@ -217,9 +316,9 @@ struct TableStack<'g, K, V> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::primitives::{AtomicU64, Mutex};
     use crate::raw::Table;
     use crossbeam_epoch::{self as epoch, Atomic, Owned};
-    use parking_lot::Mutex;
 
     #[test]
     fn iter_new() {
@@ -250,6 +349,7 @@ mod tests {
             value: Atomic::new(0usize),
             next: Atomic::null(),
             lock: Mutex::new(()),
+            version: AtomicU64::new(0),
         }));
 
         let table = Owned::new(Table::from(bins));
@@ -272,17 +372,18 @@ mod tests {
         // construct the forwarded-to table
         let mut deep_bins = vec![Atomic::null(); 16];
         deep_bins[8] = Atomic::new(BinEntry::Node(Node {
-            hash: 0,
+            hash: 8,
             key: 0usize,
             value: Atomic::new(0usize),
             next: Atomic::null(),
             lock: Mutex::new(()),
+            version: AtomicU64::new(0),
         }));
         let guard = epoch::pin();
         let deep_table = Owned::new(Table::from(deep_bins)).into_shared(&guard);
 
         // construct the forwarded-from table
-        let mut bins = vec![Shared::null(); 16];
+        let mut bins = [Shared::null(); 16];
         let table = Table::<usize, usize>::new(bins.len());
         for bin in &mut bins[8..] {
             // this also sets table.next_table to deep_table
@@ -290,8 +391,8 @@ mod tests {
         }
         // this cannot use Table::from(bins), since we need the table to get
         // the Moved and set its next_table
-        for i in 0..bins.len() {
-            table.store_bin(i, bins[i]);
+        for (i, &bin) in bins.iter().enumerate() {
+            table.store_bin(i, bin);
         }
         let table = Owned::new(table).into_shared(&guard);
         {
@@ -307,4 +408,46 @@ mod tests {
         // no one besides this test case uses deep_table
         unsafe { deep_table.into_owned() }.drop_bins();
     }
+
+    #[test]
+    fn iter_split() {
+        let mut bins = vec![Atomic::null(); 16];
+        for (i, bin) in bins.iter_mut().enumerate().step_by(3) {
+            *bin = Atomic::new(BinEntry::Node(Node {
+                hash: i as u64,
+                key: i,
+                value: Atomic::new(i),
+                next: Atomic::null(),
+                lock: Mutex::new(()),
+                version: AtomicU64::new(0),
+            }));
+        }
+
+        let table = Owned::new(Table::from(bins));
+        let guard = epoch::pin();
+        let table = table.into_shared(&guard);
+        {
+            let mut lower = NodeIter::new(table, &guard);
+            let mut upper = lower.split().unwrap();
+            let mut quarter = upper.split().unwrap();
+            let mut keys: Vec<_> = lower.by_ref().map(|node| node.key).collect();
+            assert_eq!(keys, [0, 3, 6]);
+            assert!(lower.split().is_none());
+            keys.extend(upper.map(|node| node.key));
+            keys.extend(quarter.by_ref().map(|node| node.key));
+            assert_eq!(keys, [0, 3, 6, 9, 12, 15]);
+
+            // splitting stops at a single bin
+            let mut first = NodeIter::new(table, &guard);
+            let mut rest = 0;
+            while let Some(upper) = first.split() {
+                rest += upper.count();
+            }
+            assert_eq!((first.count(), rest), (1, 5));
+        }
+
+        // safety: nothing holds on to references into the table any more
+        let mut t = unsafe { table.into_owned() };
+        t.drop_bins();
+    }
 }
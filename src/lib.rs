@@ -26,6 +26,24 @@
 //! best judgement in deciding whether or not to re-use a `Guard`. This is also the reason why the
 //! map requires that `K: 'static` and `V: 'static`. If we did not, then your keys and values may
 //! get dropped far later, potentially after those lifetimes have passed, which would not be sound.
+//! The maps of a [`scope`](scope()) are the exception: their garbage is all dropped before the scope
+//! ends, so their keys and values may borrow from outside of it.
+//!
+//! If you would rather not deal with guards at all, the [`sync`] module has wrappers around the
+//! map and set that pin the epoch inside every call, and only ever hand out owned clones of the
+//! keys and values.
+//!
+//! To find `Guard`s that are held for too long, you can enable the `leak-check` feature. With it,
+//! dropping a `HashMap` waits briefly for all the garbage the map produced to be reclaimed, and
+//! panics if it is not. That happens if some thread remains pinned, such as through a `Guard`
+//! that was leaked, or that outlives the map. Since all maps share the same garbage collector by
+//! default, the `Guard` may belong to a different map (or to another user of [`crossbeam::epoch`]
+//! entirely). The check is skipped if the dropping thread is itself pinned through [`epoch::pin`].
+//! To watch garbage pile up in a running program instead, the `garbage-stats` feature (which
+//! `leak-check` enables) keeps counts of the memory each map retires and reclaims, which
+//! `HashMap::garbage_stats` reports. Counting the guards a map hands out is always cheap, but
+//! `HashMap::oldest_guard_age` also records when each guard was created, so it is only available
+//! with the `guard-ages` feature (which `leak-check` enables as well).
 //!
 //! # Consistency
 //!
@@ -51,17 +69,17 @@
 //! average effect of maintaining roughly two bins per mapping (corresponding to a 0.75 load factor
 //! threshold for resizing). There may be much variance around this average as mappings are added
 //! and removed, but overall, this maintains a commonly accepted time/space tradeoff for hash
-//! tables.  However, resizing this or any other kind of hash table may be a relatively slow
-//! operation. When possible, it is a good idea to provide a size estimate by using the
-//! [`with_capacity`](HashMap::with_capacity) constructor. Note that using many keys with
-//! exactly the same [`Hash`](std::hash::Hash) value is a sure way to slow down performance of any
+//! tables. Maps that need a different tradeoff can pick their own threshold with
+//! [`with_load_factor`](HashMap::with_load_factor). However, resizing this or any other kind of
+//! hash table may be a relatively slow operation. When possible, it is a good idea to provide a
+//! size estimate by using the [`with_capacity`](HashMap::with_capacity) constructor. Note that
+//! using many keys with exactly the same [`Hash`](std::hash::Hash) value is a sure way to slow down performance of any
 //! hash table. To ameliorate impact, keys are required to be [`Ord`](std::cmp::Ord). This is used
 //! by the map to more efficiently store bins that contain a large number of elements with
-//! colliding hashes using the comparison order on their keys.
+//! colliding hashes using the comparison order on their keys. Keys that cannot be ordered can be
+//! used with the `unordered-keys` feature, which only requires them to be [`Eq`], and keeps all
+//! bins as lists (see [`TreeKey`]).
 //!
-/*
-//! TODO: dynamic load factor
-//! */
 //! # Hash Sets
 //!
 //! Flurry also supports concurrent hash sets, which may be created through [`HashSet`]. Hash sets
@@ -92,13 +110,10 @@
 //! However, some other types of nodes exist: `BinEntry::TreeNode`s are arranged in balanced trees
 //! instead of linear lists. Bins of type `BinEntry::Tree` hold the roots of sets of `BinEntry::TreeNode`s.
 //! Some nodes are of type `BinEntry::Moved`; these "forwarding nodes" are placed at the
-//! heads of bins during resizing. The Java version also has other special node types, but these
-//! have not yet been implemented in this port. These special nodes are all either uncommon or
-//! transient.
+//! heads of bins during resizing. Finally, `BinEntry::Reserved` nodes hold the place of the first
+//! entry of an empty bin while `get_or_insert_with` computes its value. These special nodes are all
+//! either uncommon or transient.
 //!
-/*
-//! TODO: TreeNodes, ReservationNodes
-*/
 //! The table is lazily initialized to a power-of-two size upon the first insertion.  Each bin in
 //! the table normally contains a list of nodes (most often, the list has only zero or one
 //! `BinEntry`). Table accesses require atomic reads, writes, and CASes.
@@ -108,7 +123,12 @@
 //! Other update operations (insert, delete, and replace) require locks. We do not want to waste
 //! the space required to associate a distinct lock object with each bin, so we instead embed a
 //! lock inside each node, and use the lock in the the first node of a bin list as the lock for the
-//! bin.
+//! bin. By default, that lock is a [`parking_lot::Mutex`], which spins adaptively for a short
+//! while before parking a thread that is waiting for it, so contended bins whose critical sections
+//! are short rarely put a writer to sleep. With the `spin-bin-locks` feature, bin locks never park
+//! a waiting thread, and instead keep spinning with exponential backoff, yielding to the scheduler
+//! once the wait gets long. This can help write-heavy workloads that contend on a few bins, but
+//! wastes CPU time if there are more threads than cores, or if the locks are held for long.
 //!
 //! Using the first node of a list as a lock does not by itself suffice though: When a node is
 //! locked, any update must first validate that it is still the first node after locking it, and
@@ -223,6 +243,14 @@
 //! traversal path (via next-pointers) until the lock becomes available or the list is exhausted,
 //! whichever comes first. These cases are not fast, but maximize aggregate expected throughput.
 //!
+//! Walking a tree chases a pointer to a different node at every step, which makes for poor cache
+//! locality in bins with many colliding keys. So tree bins with up to a few hundred nodes also
+//! keep a flat index: the nodes in tree order, next to an array of their hashes. Readers
+//! binary-search that array and only look at the nodes whose hash matches, without touching the
+//! read lock. Writers, who hold the bin lock, publish a changed copy of the index with every node
+//! they add or remove, and retire the old one like any other garbage. A bin that grows past the
+//! limit drops its index for good, and goes back to the tree.
+//!
 //! ## Garbage collection
 //!
 //! The Java implementation can rely on Java's runtime garbage collection to safely deallocate
@@ -233,6 +261,13 @@
 //! requiring `Guard` arguments to many methods or wrapping the return values, but provides much
 //! more efficient operation than if everything had to be atomically reference-counted.
 //!
+//! The reclamation scheme is not pluggable: the map, its nodes, iterators and raw table are
+//! written directly against the `Atomic`, `Shared`, `Owned` and `Guard` types of
+//! `crossbeam-epoch`, and its `Guard` is part of the public API through [`epoch`]. Supporting
+//! other schemes such as `flize` or `seize` behind features would mean rewriting the core against
+//! an abstraction over all of them, so it is not offered. What can be configured is which
+//! [`epoch::Collector`] a map retires its garbage to, and when that garbage is flushed.
+//!
 //!  [`crossbeam::epoch`]: https://docs.rs/crossbeam/0.7/crossbeam/epoch/index.html
 #![deny(
     missing_docs,
@@ -245,12 +280,22 @@
 use crossbeam_epoch::Guard;
 use std::ops::Deref;
 
+#[macro_use]
+mod primitives;
+
+mod batch;
+mod equivalent;
+mod guard;
+mod hasher;
 mod map;
 mod map_ref;
+#[cfg_attr(not(feature = "raw"), allow(unreachable_pub))]
 mod node;
-mod raw;
+mod observe;
+mod reclaim;
 mod set;
 mod set_ref;
+mod stamp;
 
 #[cfg(feature = "rayon")]
 mod rayon_impls;
@@ -258,24 +303,81 @@ mod rayon_impls;
 #[cfg(feature = "serde")]
 mod serde_impls;
 
+#[cfg(feature = "schedule-hooks")]
+pub mod schedule;
+
+#[cfg(feature = "failpoints")]
+pub mod failpoints;
+
+#[cfg(feature = "shadow")]
+mod shadow;
+
+#[cfg(feature = "reclaim-trace")]
+pub mod trace;
+
+#[cfg(feature = "async")]
+pub mod stream;
+
+#[cfg(feature = "raw")]
+pub mod raw;
+#[cfg(not(feature = "raw"))]
+#[allow(unreachable_pub)]
+mod raw;
+
 /// Iterator types.
 pub mod iter;
 
-pub use map::{HashMap, TryInsertError};
-pub use map_ref::HashMapRef;
+pub mod record;
+
+pub mod events;
+
+pub mod cache;
+
+pub mod compat;
+
+pub mod cursor;
+
+pub mod diagnostics;
+
+pub mod raw_entry;
+
+pub mod scope;
+
+pub mod sync;
+
+pub mod watch;
+
+pub use batch::WriteBatch;
+pub use equivalent::{Comparable, Equivalent, TreeKey};
+pub use guard::TrackedGuard;
+pub use hasher::{DefaultHashBuilder, Fmix64, HashSpreader};
+pub use map::{
+    CompareExchangeError, Decision, HashMap, LockTimeoutError, TryInsertError, TryReserveError,
+};
+pub use map_ref::{HashMapRef, OwnedMapRef, UnpinnedMapRef};
+pub use scope::scope;
 pub use set::HashSet;
 pub use set_ref::HashSetRef;
-
-/// Default hasher for [`HashMap`].
-pub type DefaultHashBuilder = ahash::RandomState;
+pub use stamp::{StaleStampError, Stamp};
 
 /// Types needed to safely access shared data concurrently.
+///
+/// The names in this module are the supported way to refer to guards outside of flurry. They are
+/// part of flurry's public API, so code that uses them rather than naming the
+/// memory reclamation crate flurry depends on keeps compiling when that dependency changes.
+/// Functions that only need _some_ pin can be generic over [`AsGuard`](crate::epoch::AsGuard), which flurry may
+/// implement for other guard types in the future.
 pub mod epoch {
+    pub use crate::guard::{AsGuard, Collector, TrackedGuard};
     pub use crossbeam_epoch::{pin, Guard};
+
+    #[cfg(feature = "raw")]
+    pub use crossbeam_epoch::{Atomic, Owned, Shared};
 }
 
 pub(crate) enum GuardRef<'g> {
-    Owned(Guard),
+    Owned(TrackedGuard),
+    Cached(Guard),
     Ref(&'g Guard),
 }
 
@@ -285,7 +387,9 @@ impl Deref for GuardRef<'_> {
     #[inline]
     fn deref(&self) -> &Guard {
         match *self {
-            GuardRef::Owned(ref guard) | GuardRef::Ref(&ref guard) => guard,
+            GuardRef::Owned(ref guard) => guard,
+            GuardRef::Cached(ref guard) => guard,
+            GuardRef::Ref(guard) => guard,
         }
     }
 }
@@ -1,13 +1,34 @@
+use crate::batch::WriteBatch;
+use crate::cursor::{Cursor, ResumeToken};
+#[cfg(feature = "garbage-stats")]
+use crate::diagnostics::GarbageStats;
+use crate::diagnostics::{MemoryUsage, Summary, TableStats, TreeBins};
+use crate::equivalent::{Comparable, TreeKey};
+use crate::events::EventStream;
+use crate::guard::{Collector, PinsRef, TrackedGuard};
+use crate::hasher::HashSpreader;
 use crate::iter::*;
 use crate::node::*;
+use crate::observe::{Change, Observers};
+use crate::primitives::{AtomicIsize, Mutex, MutexGuard, Ordering};
 use crate::raw::*;
+use crate::raw_entry::{RawEntryBuilder, RawEntryBuilderMut};
+use crate::reclaim::Garbage;
+use crate::record::Recorder;
+#[cfg(feature = "shadow")]
+use crate::shadow::Shadow;
+use crate::stamp::{StaleStampError, Stamp, Stamps};
+use crate::watch::Watch;
 use crossbeam_epoch::{self as epoch, Atomic, Guard, Owned, Shared};
+use std::alloc::Layout;
 use std::borrow::Borrow;
 use std::error::Error;
 use std::fmt::{self, Debug, Display, Formatter};
 use std::hash::{BuildHasher, Hash, Hasher};
 use std::iter::FromIterator;
-use std::sync::atomic::{AtomicIsize, Ordering};
+use std::ops::Add;
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
 
 const ISIZE_BITS: usize = core::mem::size_of::<isize>() * 8;
 
@@ -40,6 +61,10 @@ const UNTREEIFY_THRESHOLD: usize = 6;
 /// thresholds.
 const MIN_TREEIFY_CAPACITY: usize = 64;
 
+/// Whether bins are ever converted to trees. Trees are ordered by key, and keys do not have to be
+/// ordered with the `unordered-keys` feature.
+const TREEIFY: bool = cfg!(not(feature = "unordered-keys"));
+
 /// Minimum number of rebinnings per transfer step. Ranges are
 /// subdivided to allow multiple resizer threads.  This value
 /// serves as a lower bound to avoid resizers encountering
@@ -59,6 +84,17 @@ const MAX_RESIZERS: isize = (1 << (ISIZE_BITS - RESIZE_STAMP_BITS)) - 1;
 /// The bit shift for recording size stamp in `size_ctl`.
 const RESIZE_STAMP_SHIFT: usize = ISIZE_BITS - RESIZE_STAMP_BITS;
 
+/// The most items [`HashMap::extend_from_channel`] inserts under a single guard.
+const CHANNEL_BATCH: usize = 1024;
+
+/// How many bins [`HashMap::sample`] probes for each entry it is asked for, before it gives up.
+#[cfg(feature = "rand")]
+const SAMPLE_PROBES: usize = 16;
+
+/// How long a dropped map waits for its garbage to be reclaimed before reporting a leak.
+#[cfg(feature = "leak-check")]
+const LEAK_CHECK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1);
+
 #[cfg(not(miri))]
 static NCPU_INITIALIZER: std::sync::Once = std::sync::Once::new();
 #[cfg(not(miri))]
@@ -148,11 +184,43 @@ pub struct HashMap<K, V, S = crate::DefaultHashBuilder> {
     /// https://github.com/crossbeam-rs/crossbeam/blob/ebecb82c740a1b3d9d10f235387848f7e3fa9c68/crossbeam-skiplist/src/base.rs#L308-L319
     collector: epoch::Collector,
 
+    /// The garbage this map has retired.
+    garbage: Garbage,
+
+    /// Called with every value that the map removed once it is reclaimed, if set.
+    reclaim_hook: Option<Arc<dyn Fn(V) + Send + Sync>>,
+
+    /// Applied to every hash that `build_hasher` produces, if set.
+    spreader: Option<Arc<dyn HashSpreader>>,
+
+    /// The live guards of the map's collector.
+    pins: PinsRef,
+
+    /// Anything that needs to be notified of changes to the map's entries.
+    observers: Observers<K, V>,
+
+    /// Counts the writes to the map for optimistic readers, if enabled.
+    stamps: Stamps,
+
+    /// Hands out entry versions, if enabled.
+    versions: Versions,
+
+    /// The most entries that the `Debug` implementation prints, if limited.
+    pub(crate) debug_limit: Option<usize>,
+
+    /// The fraction of the bins that may be filled before the table is resized, or `None` for the
+    /// default of ¾.
+    load_factor: Option<f32>,
+
+    /// The reference map this map is checked against, if any.
+    #[cfg(feature = "shadow")]
+    shadow: Option<Arc<Shadow<K, V>>>,
+
     build_hasher: S,
 }
 
 #[derive(Eq, PartialEq, Clone, Debug)]
-enum PutResult<'a, T> {
+pub(crate) enum PutResult<'a, T> {
     Inserted {
         new: &'a T,
     },
@@ -167,7 +235,7 @@ enum PutResult<'a, T> {
 }
 
 impl<'a, T> PutResult<'a, T> {
-    fn before(&self) -> Option<&'a T> {
+    pub(crate) fn before(&self) -> Option<&'a T> {
         match *self {
             PutResult::Inserted { .. } => None,
             PutResult::Replaced { old, .. } => Some(old),
@@ -175,8 +243,7 @@ impl<'a, T> PutResult<'a, T> {
         }
     }
 
-    #[allow(dead_code)]
-    fn after(&self) -> Option<&'a T> {
+    pub(crate) fn after(&self) -> Option<&'a T> {
         match *self {
             PutResult::Inserted { new } => Some(new),
             PutResult::Replaced { new, .. } => Some(new),
@@ -217,6 +284,207 @@ where
     }
 }
 
+/// The error type for the [`HashMap::compare_exchange`] method.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CompareExchangeError<'a, V> {
+    /// A reference to the current value mapped to the key, or `None` if the key is not in the map.
+    pub current: Option<&'a V>,
+    /// The value that [`HashMap::compare_exchange`] failed to store.
+    pub new: V,
+}
+
+impl<'a, V> Display for CompareExchangeError<'a, V>
+where
+    V: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.current {
+            Some(current) => write!(
+                f,
+                "Exchange for \"{:?}\" failed as the key was mapped to \"{:?}\"",
+                self.new, current
+            ),
+            None => write!(
+                f,
+                "Exchange for \"{:?}\" failed as the key was not present",
+                self.new
+            ),
+        }
+    }
+}
+
+impl<'a, V> Error for CompareExchangeError<'a, V>
+where
+    V: Debug,
+{
+    #[inline]
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        None
+    }
+}
+
+/// The error type for [`HashMap::try_insert_for`] and [`HashMap::try_compute_for`].
+///
+/// Returned when the lock on the key's bin could not be acquired within the given timeout. The map
+/// is left unchanged, and whatever the operation would have applied is handed back.
+#[derive(Clone, PartialEq, Eq)]
+pub struct LockTimeoutError<T> {
+    /// How long the operation waited for the lock.
+    pub timeout: Duration,
+    /// What the operation failed to apply: the key-value pair for [`HashMap::try_insert_for`],
+    /// and the remapping function for [`HashMap::try_compute_for`].
+    pub not_applied: T,
+}
+
+impl<T> LockTimeoutError<T> {
+    /// Returns what the operation failed to apply.
+    pub fn into_inner(self) -> T {
+        self.not_applied
+    }
+}
+
+impl<T> Debug for LockTimeoutError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        // `not_applied` is often a closure, so it is left out
+        f.debug_struct("LockTimeoutError")
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+impl<T> Display for LockTimeoutError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Operation gave up after waiting {:?} for a bin lock",
+            self.timeout
+        )
+    }
+}
+
+impl<T> Error for LockTimeoutError<T> {
+    #[inline]
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        None
+    }
+}
+
+/// The error type for [`HashMap::try_reserve`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TryReserveError {
+    /// The requested capacity does not fit in a `usize`.
+    CapacityOverflow,
+    /// The allocator could not provide the memory for the map's new table.
+    AllocError {
+        /// The layout of the allocation that failed.
+        layout: Layout,
+    },
+}
+
+impl Display for TryReserveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            TryReserveError::CapacityOverflow => {
+                write!(f, "Reserve failed because the capacity overflowed")
+            }
+            TryReserveError::AllocError { layout } => write!(
+                f,
+                "Reserve failed because {} bytes could not be allocated for the table",
+                layout.size()
+            ),
+        }
+    }
+}
+
+impl Error for TryReserveError {
+    #[inline]
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        None
+    }
+}
+
+/// What [`HashMap::retain_mut`] should do with an entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision<V> {
+    /// Leave the entry as it is.
+    Keep,
+    /// Remove the entry from the map.
+    Remove,
+    /// Replace the entry's value with the given one.
+    Replace(V),
+}
+
+/// The outcome of `HashMap::put_with`.
+enum PutWith<'g, K, V, F> {
+    /// The closure produced a value, which was inserted.
+    Inserted(&'g V),
+    /// The closure did not produce a value, so nothing was inserted.
+    Declined,
+    /// The key was already present, so the closure was not called.
+    Present { current: &'g V, key: K, f: F },
+}
+
+/// An empty bin that has been reserved by `HashMap::put_with`.
+///
+/// Dropping it empties the bin again unless the reservation has already been replaced, and then
+/// unlocks and retires the reservation. This also cleans up after a panicking closure.
+struct ReservedBin<'a, 'g, K, V> {
+    table: &'g Table<K, V>,
+    bini: usize,
+    reservation: Shared<'g, BinEntry<K, V>>,
+    garbage: &'a Garbage,
+    guard: &'g Guard,
+}
+
+impl<K, V> Drop for ReservedBin<'_, '_, K, V> {
+    fn drop(&mut self) {
+        if self.table.bin(self.bini, self.guard) == self.reservation {
+            // we hold the reservation's lock, so no other thread can have changed the bin
+            self.table.store_bin(self.bini, Shared::null());
+        }
+        // safety: the reservation was placed into the bin while we held the guard, and it is only
+        // retired below. we acquired its lock before it became visible and forgot its guard.
+        unsafe {
+            self.reservation
+                .deref()
+                .as_reservation()
+                .unwrap()
+                .lock
+                .force_unlock()
+        };
+        // safety: the reservation is no longer in the bin, so no thread that executes after this
+        // line can get a reference to it. threads that are still waiting for its lock read it
+        // under a guard that pins an epoch no later than ours.
+        unsafe { self.garbage.retire(self.guard, self.reservation) };
+    }
+}
+
+/// Sets `size_ctl` back to `value` when dropped, unless it is forgotten first.
+///
+/// A thread that holds `size_ctl` at a negative value to initialize or resize the table keeps one
+/// of these around while it allocates the new table. If the allocation panics, the map is left as
+/// it was, rather than with a `size_ctl` that makes every later operation wait for a table that
+/// never comes.
+struct RestoreSizeCtl<'a> {
+    size_ctl: &'a AtomicIsize,
+    value: isize,
+}
+
+impl Drop for RestoreSizeCtl<'_> {
+    fn drop(&mut self) {
+        self.size_ctl.store(self.value, Ordering::SeqCst);
+    }
+}
+
+/// Takes the lock of a bin, giving up if it is not acquired by `deadline` (if there is one).
+#[inline]
+fn lock_bin(lock: &Mutex<()>, deadline: Option<Instant>) -> Option<MutexGuard<'_, ()>> {
+    match deadline {
+        None => Some(lock.lock()),
+        Some(deadline) => lock.try_lock_until(deadline),
+    }
+}
+
 // ===
 // the following methods only see Ks and Vs if there have been inserts.
 // modifications to the map are all guarded by thread-safety bounds (Send + Sync + 'static).
@@ -284,6 +552,11 @@ impl<K, V, S> HashMap<K, V, S> {
     /// very poor performance. Setting it manually using this
     /// function can expose a DoS attack vector.
     ///
+    /// The map uses the hashes that `hash_builder` produces as they are, without spreading them
+    /// any further, and picks the bin for a key from the low bits of its hash. A hasher that
+    /// already distributes its output well pays nothing extra for this. For a hasher whose low
+    /// bits are weak, add a [`HashSpreader`] with [`HashMap::with_hash_spreader`].
+    ///
     /// # Examples
     ///
     /// ```
@@ -301,6 +574,17 @@ impl<K, V, S> HashMap<K, V, S> {
             size_ctl: AtomicIsize::new(0),
             build_hasher: hash_builder,
             collector: epoch::default_collector().clone(),
+            garbage: Garbage::new(),
+            reclaim_hook: None,
+            spreader: None,
+            pins: Collector::global().pins().clone(),
+            observers: Observers::new(),
+            stamps: Stamps::new(false),
+            versions: Versions::new(false),
+            debug_limit: None,
+            load_factor: None,
+            #[cfg(feature = "shadow")]
+            shadow: None,
         }
     }
 
@@ -334,155 +618,1436 @@ impl<K, V, S> HashMap<K, V, S> {
         map
     }
 
-    /*
-    NOTE: This method is intentionally left out atm as it is a potentially large foot-gun.
-          See https://github.com/jonhoo/flurry/pull/49#issuecomment-580514518.
-    */
-    /*
-    /// Associate a custom [`epoch::Collector`] with this map.
+    /// Resizes the table of this map once it holds `load_factor` times as many entries as it has
+    /// bins, rather than three quarters as many.
+    ///
+    /// A lower load factor keeps the bins shorter, which makes lookups cheaper, at the cost of a
+    /// larger table. A higher load factor saves memory, but makes the bins longer. The load factor
+    /// also decides how large a table [`reserve`](HashMap::reserve) and
+    /// [`shrink_to`](HashMap::shrink_to) pick for a number of elements. A table that has already
+    /// been allocated, for example by [`HashMap::with_capacity`], keeps its size until the map
+    /// resizes it.
+    ///
+    /// # Panics
     ///
-    /// By default, the global collector is used. With this method you can use a different
-    /// collector instead. This may be desireable if you want more control over when and how memory
-    /// reclamation happens.
+    /// Panics if `load_factor` is not positive and finite.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
     ///
-    /// Note that _all_ `Guard` references provided to access the returned map _must_ be
-    /// constructed using guards produced by `collector`. You can use [`HashMap::register`] to get
-    /// a thread-local handle to the collector that then lets you construct an [`epoch::Guard`].
-    pub fn with_collector(mut self, collector: epoch::Collector) -> Self {
-        self.collector = collector;
+    /// let map = HashMap::new().with_load_factor(0.5);
+    /// let guard = map.guard();
+    /// for i in 0..8 {
+    ///     map.insert(i, i, &guard);
+    /// }
+    /// // the default table of 16 bins is resized once it holds 8 entries
+    /// assert_eq!(map.bucket_count(&guard), 32);
+    /// ```
+    pub fn with_load_factor(mut self, load_factor: f32) -> Self {
+        assert!(
+            load_factor > 0.0 && load_factor.is_finite(),
+            "load factor must be positive and finite, but is {}",
+            load_factor
+        );
+        self.load_factor = Some(load_factor);
+
+        // safety: we own the map, so no other thread can access it
+        let guard = unsafe { epoch::unprotected() };
+        let table = self.table.load(Ordering::SeqCst, guard);
+        if !table.is_null() {
+            // safety: as above
+            let n = unsafe { table.deref() }.len();
+            let threshold = self.resize_threshold(n);
+            self.size_ctl.store(threshold, Ordering::SeqCst);
+        }
         self
     }
 
-    /// Allocate a thread-local handle to the [`epoch::Collector`] associated with this map.
+    /// Logs every subsequent change made to this map in `recorder`.
     ///
-    /// You can use the returned handle to produce [`epoch::Guard`] references.
-    pub fn register(&self) -> epoch::LocalHandle {
-        self.collector.register()
+    /// See the [`record`](crate::record) module for details.
+    pub fn with_recorder(mut self, recorder: Arc<Recorder<K, V>>) -> Self
+    where
+        K: 'static + Clone + Send,
+        V: 'static + Clone + Send,
+    {
+        self.observers.push(recorder);
+        self
     }
-    */
 
-    /// Pin a `Guard` for use with this map.
+    /// Delivers an event for every subsequent change made to this map to `events`.
     ///
-    /// Keep in mind that for as long as you hold onto this `Guard`, you are preventing the
-    /// collection of garbage generated by the map.
-    pub fn guard(&self) -> epoch::Guard {
-        self.collector.register().pin()
+    /// See the [`events`](crate::events) module for details.
+    pub fn with_events(mut self, events: Arc<EventStream<K, V>>) -> Self
+    where
+        K: 'static + Clone + Send,
+        V: 'static + Send,
+    {
+        self.observers.push(events);
+        self
     }
 
-    #[inline]
-    fn check_guard(&self, guard: &Guard) {
-        // guard.collector() may be `None` if it is unprotected
-        if let Some(c) = guard.collector() {
-            assert_eq!(c, &self.collector);
-        }
+    /// Counts the writes made to this map, so that it supports [`HashMap::read_stamp`].
+    ///
+    /// Every subsequent insertion, replacement, removal, and resize then has to update a counter
+    /// that is shared by all threads, which makes writes somewhat more expensive when there are
+    /// many concurrent writers.
+    pub fn with_read_stamps(mut self) -> Self {
+        self.stamps = Stamps::new(true);
+        self
     }
 
-    /// Returns the number of entries in the map.
+    /// Keeps a version for every entry of this map, so that it supports
+    /// [`HashMap::get_versioned`].
+    ///
+    /// Every subsequent insertion and replacement then has to update a counter that is shared by
+    /// all threads, which makes writes somewhat more expensive when there are many concurrent
+    /// writers.
+    pub fn with_entry_versions(mut self) -> Self {
+        self.versions = Versions::new(true);
+        self
+    }
+
+    /// Prints at most `limit` entries when this map is formatted with `Debug`.
+    ///
+    /// The remaining entries are summarized by their number, which keeps the output of very large
+    /// maps readable, for example when they are part of a struct that derives `Debug`.
     ///
     /// # Examples
     ///
     /// ```
     /// use flurry::HashMap;
     ///
-    /// let map = HashMap::new();
+    /// let map = HashMap::new().with_debug_limit(1);
+    /// map.pin().insert(1, "one");
+    /// assert_eq!(format!("{:?}", map), r#"{1: "one"}"#);
     ///
-    /// map.pin().insert(1, "a");
-    /// map.pin().insert(2, "b");
-    /// assert!(map.pin().len() == 2);
+    /// map.pin().insert(2, "two");
+    /// map.pin().insert(3, "three");
+    /// assert!(format!("{:?}", map).ends_with("} and 2 more"));
     /// ```
-    pub fn len(&self) -> usize {
-        let n = self.count.load(Ordering::Relaxed);
-        if n < 0 {
-            0
-        } else {
-            n as usize
-        }
+    pub fn with_debug_limit(mut self, limit: usize) -> Self {
+        self.debug_limit = Some(limit);
+        self
     }
 
-    /// Returns `true` if the map is empty. Otherwise returns `false`.
+    /// Calls `hook` with every value that is subsequently removed from this map or replaced in
+    /// it, once the value is reclaimed.
+    ///
+    /// A removed value is not dropped right away, since other threads may still be reading it
+    /// under guards they pinned before it was removed. `hook` is only called once all of those
+    /// guards have been dropped, so it is the first point at which it is guaranteed that no
+    /// reader can observe the value anymore. This makes it a good place to release resources
+    /// that the values hold, such as closing the file handles in a cache. Like other deferred
+    /// destructors, `hook` runs on whichever thread happens to reclaim the value; see
+    /// [`HashMap::flush_garbage`] to reclaim garbage at a point of your choosing.
+    ///
+    /// Values that are still in the map when it is dropped are dropped as usual, without being
+    /// passed to `hook`. Clones of the map call the same `hook`.
     ///
     /// # Examples
     ///
     /// ```
     /// use flurry::HashMap;
+    /// use std::sync::{Arc, Mutex};
     ///
-    /// let map = HashMap::new();
-    /// assert!(map.pin().is_empty());
-    /// map.pin().insert("a", 1);
-    /// assert!(!map.pin().is_empty());
+    /// let closed = Arc::new(Mutex::new(Vec::new()));
+    /// let map = HashMap::new().with_reclaim_hook({
+    ///     let closed = closed.clone();
+    ///     move |file: String| closed.lock().unwrap().push(file)
+    /// });
+    ///
+    /// let guard = map.guard();
+    /// map.insert(1, String::from("a.log"), &guard);
+    /// map.remove(&1, &guard);
+    /// // the guard may still be reading the value
+    /// assert!(closed.lock().unwrap().is_empty());
+    ///
+    /// drop(guard);
+    /// map.flush_garbage();
+    /// assert_eq!(*closed.lock().unwrap(), ["a.log"]);
     /// ```
-    pub fn is_empty(&self) -> bool {
-        self.len() == 0
+    pub fn with_reclaim_hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(V) + Send + Sync + 'static,
+    {
+        self.reclaim_hook = Some(Arc::new(hook));
+        self
     }
 
-    #[cfg(test)]
-    /// Returns the capacity of the map.
-    fn capacity(&self, guard: &Guard) -> usize {
-        self.check_guard(guard);
-        let table = self.table.load(Ordering::Relaxed, &guard);
+    /// Applies `spreader` to every hash that the map's hasher produces, before the map uses it to
+    /// pick a bin.
+    ///
+    /// See [`HashSpreader`] for details. Clones of the map use the same spreader. Hashes passed to
+    /// the [`raw_entry`](HashMap::raw_entry) API are still the ones that the map's hasher
+    /// produces, and the map applies `spreader` to them too.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the map already holds entries, since they were placed by their old hashes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::{Fmix64, HashMap};
+    /// use std::hash::{BuildHasherDefault, Hasher};
+    ///
+    /// // a hasher that passes integer keys through as they are
+    /// #[derive(Default)]
+    /// struct Identity(u64);
+    ///
+    /// impl Hasher for Identity {
+    ///     fn finish(&self) -> u64 {
+    ///         self.0
+    ///     }
+    ///     fn write(&mut self, _: &[u8]) {
+    ///         unimplemented!()
+    ///     }
+    ///     fn write_u64(&mut self, n: u64) {
+    ///         self.0 = n;
+    ///     }
+    /// }
+    ///
+    /// let map = HashMap::<u64, u64, BuildHasherDefault<Identity>>::default()
+    ///     .with_hash_spreader(Fmix64);
+    /// let guard = map.guard();
+    /// // keys that only differ in their high bits still end up in different bins
+    /// for i in 0..64 {
+    ///     map.insert(i << 32, i, &guard);
+    /// }
+    /// assert!(map.table_stats(&guard).max_chain_length() < 8);
+    /// ```
+    pub fn with_hash_spreader<H>(mut self, spreader: H) -> Self
+    where
+        H: HashSpreader + 'static,
+    {
+        assert!(
+            self.is_empty(),
+            "the hash spreader must be set before any entries are inserted"
+        );
+        self.spreader = Some(Arc::new(spreader));
+        self
+    }
 
-        if table.is_null() {
-            0
-        } else {
-            // Safety: we loaded `table` under the `guard`,
-            // so it must still be valid here
-            unsafe { table.deref() }.len()
+    /// Applies the map's [`HashSpreader`], if it has one, to `hash`.
+    #[inline]
+    pub(crate) fn spread(&self, hash: u64) -> u64 {
+        match self.spreader {
+            Some(ref spreader) => spreader.spread(hash),
+            None => hash,
         }
     }
 
-    /// Returns the stamp bits for resizing a table of size n.
-    /// Must be negative when shifted left by `RESIZE_STAMP_SHIFT`.
-    fn resize_stamp(n: usize) -> isize {
-        n.leading_zeros() as isize | (1_isize << (RESIZE_STAMP_BITS - 1))
+    /// Retires `value`, which is no longer reachable from the map, and passes it to the reclaim
+    /// hook once it is reclaimed.
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`Garbage::retire`].
+    #[inline]
+    unsafe fn retire_value(&self, guard: &Guard, value: Shared<'_, V>) {
+        match self.reclaim_hook {
+            Some(ref hook) => {
+                let hook = Arc::clone(hook);
+                self.garbage
+                    .retire_with(guard, std::mem::size_of::<V>(), move || {
+                        hook(*value.into_owned().into_box())
+                    });
+            }
+            None => self.garbage.retire(guard, value),
+        }
     }
 
-    /// An iterator visiting all key-value pairs in arbitrary order.
+    /// Checks every subsequent operation on this map against a `std::collections::HashMap`.
     ///
-    /// The iterator element type is `(&'g K, &'g V)`.
-    pub fn iter<'g>(&'g self, guard: &'g Guard) -> Iter<'g, K, V> {
-        self.check_guard(guard);
-        let table = self.table.load(Ordering::SeqCst, guard);
-        let node_iter = NodeIter::new(table, guard);
-        Iter { node_iter, guard }
+    /// Every change to this map is mirrored into the reference map, and every lookup is checked
+    /// against it. If the two ever disagree, for example because a lookup finds a value that was
+    /// removed, or a replacement replaces a different value than the reference map holds, the
+    /// operation panics with a message that describes the divergence.
+    ///
+    /// To make the comparison exact, all operations on the map are serialized through a single
+    /// lock, and every changed key and value is cloned. This is only meant for debugging, and is
+    /// only available with the `shadow` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new().with_shadow_checking();
+    /// let guard = map.guard();
+    /// map.insert(1, "a", &guard);
+    /// assert_eq!(map.get(&1, &guard), Some(&"a"));
+    /// ```
+    #[cfg(feature = "shadow")]
+    pub fn with_shadow_checking(mut self) -> Self
+    where
+        K: 'static + Clone + Debug + Hash + Eq + Send + Sync,
+        V: 'static + Clone + Debug + PartialEq + Send + Sync,
+    {
+        let shadow = Arc::new(Shadow::new());
+        shadow.mirror(self.iter(&self.guard()));
+        self.observers.push(shadow.clone());
+        self.shadow = Some(shadow);
+        self
     }
 
-    /// An iterator visiting all keys in arbitrary order.
+    /// Serializes the calling operation with all other operations if shadow checking is enabled.
+    #[cfg(feature = "shadow")]
+    fn enter_shadow(&self) -> Option<parking_lot::ReentrantMutexGuard<'_, ()>> {
+        self.shadow.as_ref().map(|shadow| shadow.enter())
+    }
+
+    /// Pin a `Guard` for use with this map.
     ///
-    /// The iterator element type is `&'g K`.
-    pub fn keys<'g>(&'g self, guard: &'g Guard) -> Keys<'g, K, V> {
-        self.check_guard(guard);
-        let table = self.table.load(Ordering::SeqCst, guard);
-        let node_iter = NodeIter::new(table, guard);
-        Keys { node_iter }
+    /// Keep in mind that for as long as you hold onto this `Guard`, you are preventing the
+    /// collection of garbage generated by the map.
+    pub fn guard(&self) -> TrackedGuard {
+        TrackedGuard::new(self.collector.register().pin(), self.pins.clone())
     }
 
-    /// An iterator visiting all values in arbitrary order.
+    /// Pin a `Guard` for use with this map, reusing a handle to the map's collector that the
+    /// calling thread keeps around.
     ///
-    /// The iterator element type is `&'g V`.
-    pub fn values<'g>(&'g self, guard: &'g Guard) -> Values<'g, K, V> {
-        self.check_guard(guard);
-        let table = self.table.load(Ordering::SeqCst, guard);
-        let node_iter = NodeIter::new(table, guard);
-        Values { node_iter, guard }
+    /// This is cheaper than [`HashMap::guard`], which registers a new handle with the collector
+    /// and keeps track of the guard, and so is meant for very short operations on small maps, where
+    /// obtaining the guard can take longer than the operation itself. The returned guard is not
+    /// counted by [`HashMap::active_guards`].
+    ///
+    /// A thread keeps a handle to the global collector, and to the collector it pinned with most
+    /// recently among the ones created with [`Collector::new`]. The latter keeps that collector,
+    /// and the garbage the thread retired to it, alive until the thread pins with another
+    /// collector or exits.
+    ///
+    /// See also [`HashMap::pin_cached`].
+    pub fn guard_cached(&self) -> Guard {
+        crate::guard::pin_cached(&self.collector)
     }
 
-    fn init_table<'g>(&'g self, guard: &'g Guard) -> Shared<'g, Table<K, V>> {
-        loop {
-            let table = self.table.load(Ordering::SeqCst, guard);
-            // safety: we loaded the table while epoch was pinned. table won't be deallocated until
-            // next epoch at the earliest.
-            if !table.is_null() && !unsafe { table.deref() }.is_empty() {
-                break table;
-            }
-            // try to allocate the table
-            let mut sc = self.size_ctl.load(Ordering::SeqCst);
-            if sc < 0 {
-                // we lost the initialization race; just spin
-                std::thread::yield_now();
-                continue;
-            }
+    /// Makes this map use `collector` to reclaim the memory of removed entries, rather than the
+    /// global collector.
+    ///
+    /// All the maps and sets that use the same [`Collector`] can be used with the same guard,
+    /// while guards from other collectors (including the global one) are rejected. See
+    /// [`Collector`] for an example.
+    pub fn with_collector(mut self, collector: &Collector) -> Self {
+        // the map is owned, so no guard of the previous collector can be reading from it, and
+        // garbage that was retired to the previous collector stays there until it is reclaimed.
+        self.collector = collector.epoch_collector().clone();
+        self.pins = collector.pins().clone();
+        #[cfg(feature = "reclaim-trace")]
+        self.garbage.set_collector(collector.pins());
+        self
+    }
 
-            if self.size_ctl.compare_and_swap(sc, -1, Ordering::SeqCst) == sc {
+    /// Returns the [`Collector`] this map uses to reclaim memory.
+    ///
+    /// Other maps and sets can be made to share it with [`with_collector`](HashMap::with_collector).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let users = HashMap::new();
+    /// let groups = HashMap::new().with_collector(&users.collector());
+    /// let guard = users.guard();
+    /// users.insert(1, "root", &guard);
+    /// groups.insert("wheel", 1, &guard);
+    /// ```
+    pub fn collector(&self) -> Collector {
+        Collector::from_parts(self.collector.clone(), self.pins.clone())
+    }
+
+    /// Returns the number of guards obtained through maps that share this map's collector, and
+    /// that are still alive.
+    ///
+    /// All guards from [`HashMap::guard`], [`HashMap::pin`], [`HashSet::guard`], and
+    /// [`HashSet::pin`] are counted. Guards obtained directly from [`epoch::pin`], and the cached
+    /// guards of [`HashMap::guard_cached`] and [`HashMap::pin_cached`], are not.
+    ///
+    /// [`HashSet::guard`]: crate::HashSet::guard
+    /// [`HashSet::pin`]: crate::HashSet::pin
+    /// [`epoch::pin`]: crate::epoch::pin
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::<i32, i32>::new();
+    /// let before = map.active_guards();
+    /// let guard = map.guard();
+    /// assert!(map.active_guards() > before);
+    /// ```
+    pub fn active_guards(&self) -> usize {
+        self.pins.active()
+    }
+
+    /// Returns how long the oldest of the guards counted by [`HashMap::active_guards`] has been
+    /// alive, or `None` if there are no such guards.
+    ///
+    /// A thread that stays pinned prevents all garbage in the collector from being reclaimed, so
+    /// an age well beyond the expected duration of an operation usually indicates that some
+    /// thread is holding on to a guard for too long.
+    ///
+    /// This is only available with the `guard-ages` feature, since recording when each guard was
+    /// obtained makes obtaining a guard more expensive.
+    #[cfg(feature = "guard-ages")]
+    pub fn oldest_guard_age(&self) -> Option<Duration> {
+        self.pins.oldest()
+    }
+
+    /// Tries to reclaim the memory of removed entries and replaced tables right away.
+    ///
+    /// Normally, memory the map no longer uses is only freed once no thread could still be
+    /// accessing it, which the epoch-based reclamation scheme determines lazily as threads pin and
+    /// unpin. This is useful at points where the application knows that few threads are pinned,
+    /// for example between batches of requests, or after a large [`clear`](HashMap::clear).
+    ///
+    /// This is best-effort: garbage cannot be freed as long as some thread holds a guard from the
+    /// map's collector, which by default is the global collector shared with other data
+    /// structures. That includes guards of the calling thread. Garbage that was retired through a
+    /// guard obtained from [`epoch::pin`](crate::epoch::pin) is also only freed once the thread
+    /// that retired it flushes its thread-local garbage. See [`Collector::flush`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// for i in 0..64 {
+    ///     map.pin().insert(i, vec![0u8; 1024]);
+    /// }
+    /// map.pin().clear();
+    /// map.flush_garbage();
+    /// ```
+    pub fn flush_garbage(&self) {
+        self.collector().flush();
+    }
+
+    /// Panics if garbage this map retired cannot be reclaimed.
+    ///
+    /// See [`HashMap::flush_garbage`] for why that may take a while, even if all guards have been
+    /// dropped.
+    #[cfg(feature = "leak-check")]
+    fn check_garbage(&self) {
+        if self.garbage.outstanding() == 0 || std::thread::panicking() {
+            return;
+        }
+        if &self.collector == epoch::default_collector() && epoch::is_pinned() {
+            // this thread holds a guard that outlives the map, so its garbage cannot be reclaimed
+            // yet. there is no way to know whether that guard will ever be dropped.
+            return;
+        }
+
+        let handle = self.collector.register();
+        let deadline = std::time::Instant::now() + LEAK_CHECK_TIMEOUT;
+        while self.garbage.outstanding() != 0 && std::time::Instant::now() < deadline {
+            handle.pin().flush();
+            if &self.collector == epoch::default_collector() {
+                // garbage may also have been retired through this thread's default handle
+                epoch::pin().flush();
+            }
+            std::thread::yield_now();
+        }
+
+        let outstanding = self.garbage.outstanding();
+        if outstanding != 0 {
+            let guards = match self.oldest_guard_age() {
+                Some(age) => format!(
+                    "{} guards obtained from flurry are alive, the oldest for {:?}",
+                    self.active_guards(),
+                    age
+                ),
+                None => "no guards obtained from flurry are alive".to_string(),
+            };
+            panic!(
+                "HashMap dropped with {} retired objects that could not be reclaimed within {:?}; \
+                 some thread is still pinned (for example by a leaked or long-lived `Guard`), \
+                 or has not yet flushed the garbage it retired ({})",
+                outstanding, LEAK_CHECK_TIMEOUT, guards
+            );
+        }
+    }
+
+    /// Returns a reference to the map's [`BuildHasher`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::{DefaultHashBuilder, HashMap};
+    ///
+    /// let map: HashMap<i32, i32> = HashMap::with_hasher(DefaultHashBuilder::with_seeds(1, 2));
+    /// assert_eq!(map.hasher().seeds(), (1, 2));
+    /// ```
+    pub fn hasher(&self) -> &S {
+        &self.build_hasher
+    }
+
+    #[inline]
+    pub(crate) fn check_guard(&self, guard: &Guard) {
+        // guard.collector() may be `None` if it is unprotected
+        if let Some(c) = guard.collector() {
+            if c != &self.collector {
+                guard_mismatch(c, &self.collector);
+            }
+        }
+    }
+
+    /// Returns the number of entries in the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    ///
+    /// map.pin().insert(1, "a");
+    /// map.pin().insert(2, "b");
+    /// assert!(map.pin().len() == 2);
+    /// ```
+    pub fn len(&self) -> usize {
+        let n = self.count.load(Ordering::Relaxed);
+        if n < 0 {
+            0
+        } else {
+            n as usize
+        }
+    }
+
+    /// Returns `true` if the map is empty. Otherwise returns `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// assert!(map.pin().is_empty());
+    /// map.pin().insert("a", 1);
+    /// assert!(!map.pin().is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns a [`Stamp`] for reading several entries of the map as of a single point in time.
+    ///
+    /// The stamp is invalidated by any subsequent change to the map, including resizes. Reads
+    /// made with [`get_validated`](HashMap::get_validated) fail once the stamp is invalid, so if
+    /// several such reads succeed with the same stamp, no change was made to the map between
+    /// them, and the values they returned are consistent with one another. If a change was in
+    /// progress when the stamp was taken, the stamp is invalid from the start.
+    ///
+    /// Like optimistic reads with a `StampedLock`, this never blocks writers. In return, readers
+    /// must be prepared to retry when their stamp turns out to be invalid.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the map was not created with [`with_read_stamps`](HashMap::with_read_stamps).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new().with_read_stamps();
+    /// let guard = map.guard();
+    /// map.insert("from", 70, &guard);
+    /// map.insert("to", 30, &guard);
+    ///
+    /// // read both balances as of the same point in time
+    /// let total = loop {
+    ///     let stamp = map.read_stamp();
+    ///     let from = map.get_validated("from", stamp, &guard);
+    ///     let to = map.get_validated("to", stamp, &guard);
+    ///     if let (Ok(Some(from)), Ok(Some(to))) = (from, to) {
+    ///         break from + to;
+    ///     }
+    /// };
+    /// assert_eq!(total, 100);
+    /// ```
+    pub fn read_stamp(&self) -> Stamp {
+        assert!(
+            self.stamps.is_enabled(),
+            "read stamps are only supported by maps created with `with_read_stamps`"
+        );
+        self.stamps.stamp()
+    }
+
+    /// Returns `true` if no change has been made to the map since `stamp` was taken.
+    ///
+    /// See [`read_stamp`](HashMap::read_stamp) for details.
+    pub fn validate_stamp(&self, stamp: Stamp) -> bool {
+        self.stamps.validate(stamp)
+    }
+
+    fn assert_entry_versions(&self) {
+        assert!(
+            self.versions.is_enabled(),
+            "entry versions are only kept by maps created with `with_entry_versions`"
+        );
+    }
+
+    /// Returns the number of buckets (bins) in the map's current table.
+    ///
+    /// This is 0 until the first entry is inserted. The number of buckets is always a power of
+    /// two, and changes whenever the map resizes, which may happen concurrently with this call. See
+    /// [`bucket_index`](HashMap::bucket_index) for how to use this to colocate state with the
+    /// map's buckets.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// let guard = map.guard();
+    /// assert_eq!(map.bucket_count(&guard), 0);
+    /// map.insert(1, "a", &guard);
+    /// assert!(map.bucket_count(&guard).is_power_of_two());
+    /// ```
+    pub fn bucket_count(&self, guard: &Guard) -> usize {
+        self.check_guard(guard);
+        let table = self.table.load(Ordering::SeqCst, guard);
+        if table.is_null() {
+            0
+        } else {
+            // safety: we loaded `table` under the `guard`, so it must still be valid here
+            unsafe { table.deref() }.len()
+        }
+    }
+
+    /// Returns the number of entries the map can hold before it resizes its table.
+    ///
+    /// The map grows its table once it holds a fixed fraction of the table's buckets in entries,
+    /// three quarters of them unless [`with_load_factor`](HashMap::with_load_factor) picked
+    /// another fraction. This is 0 until the table is allocated, and, like
+    /// [`bucket_count`](HashMap::bucket_count), changes whenever the map resizes. Note that a
+    /// small map may also grow early if many of its keys land in the same bucket.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map: HashMap<i32, i32> = HashMap::with_capacity(100);
+    /// let guard = map.guard();
+    /// assert!(map.capacity(&guard) >= 100);
+    /// assert_eq!(map.bucket_count(&guard), 256);
+    /// assert_eq!(map.capacity(&guard), 191);
+    /// ```
+    pub fn capacity(&self, guard: &Guard) -> usize {
+        match self.bucket_count(guard) {
+            0 => 0,
+            // the resize happens as soon as the count reaches the threshold
+            n => self.resize_threshold(n) as usize - 1,
+        }
+    }
+
+    /// An iterator over the bins of the map that have been turned into trees.
+    ///
+    /// The map turns a bin into a tree once it holds too many entries, which suggests that the
+    /// map's hasher distributes its keys poorly, or that the keys were chosen to collide. See the
+    /// [`diagnostics`](crate::diagnostics) module for an example.
+    ///
+    /// Bins that a concurrent resize has already moved to the new table are not reported.
+    pub fn tree_bins<'g>(&'g self, guard: &'g Guard) -> TreeBins<'g, K, V> {
+        self.check_guard(guard);
+        let table = self.table.load(Ordering::SeqCst, guard);
+        // safety: we loaded `table` under the `guard`, so it must still be valid for 'g
+        TreeBins::new(unsafe { table.as_ref() }, guard)
+    }
+
+    /// Returns an overview of the map's size and layout.
+    ///
+    /// See [`Summary`] for an example.
+    pub fn summary(&self) -> Summary {
+        let guard = self.collector.register().pin();
+        #[cfg(feature = "garbage-stats")]
+        let garbage = Some(self.garbage.outstanding());
+        #[cfg(not(feature = "garbage-stats"))]
+        let garbage = None;
+        let table = self.table.load(Ordering::SeqCst, &guard);
+        Summary {
+            entries: self.len(),
+            buckets: self.bucket_count(&guard),
+            capacity: self.capacity(&guard),
+            // safety: we loaded `table` under the `guard`, so it must still be valid here
+            occupied_bins: crate::diagnostics::occupied_bins(unsafe { table.as_ref() }, &guard),
+            tree_bins: self.tree_bins(&guard).count(),
+            garbage,
+        }
+    }
+
+    /// Returns statistics about how the map's entries are spread over its table.
+    ///
+    /// Unlike [`summary`](HashMap::summary), this walks every entry of the map, and waits for
+    /// concurrent changes to the trees it finds to measure their depth, so it is best used
+    /// sparingly on large maps. See [`TableStats`] for an example.
+    ///
+    /// Bins that a concurrent resize has already moved to the new table are only counted, and
+    /// their entries are not included.
+    pub fn table_stats(&self, guard: &Guard) -> TableStats {
+        self.check_guard(guard);
+        let table = self.table.load(Ordering::SeqCst, guard);
+        // safety: we loaded `table` under the `guard`, so it must still be valid here
+        let table = unsafe { table.as_ref() };
+        let stride = table.map_or(0, |table| transfer_stride(table.len()) as usize);
+        crate::diagnostics::table_stats(table, stride, guard)
+    }
+
+    /// Returns how much garbage the map has retired, and how much of it has been reclaimed.
+    ///
+    /// This is only available with the `garbage-stats` feature. See [`GarbageStats`] for an
+    /// example.
+    #[cfg(feature = "garbage-stats")]
+    pub fn garbage_stats(&self) -> GarbageStats {
+        self.garbage.stats()
+    }
+
+    /// Returns an estimate of how many bytes the map takes up.
+    ///
+    /// This counts the map's table and the nodes that hold its entries, which include the keys
+    /// and values themselves, but not memory that the keys and values own elsewhere; use
+    /// [`memory_usage_with`](HashMap::memory_usage_with) to include that. With the
+    /// `garbage-stats` feature, it also counts the garbage the map retired that has not been
+    /// reclaimed yet. See
+    /// [`MemoryUsage`] for an example.
+    ///
+    /// Like [`table_stats`](HashMap::table_stats), this walks every entry of the map.
+    pub fn memory_usage(&self, guard: &Guard) -> MemoryUsage {
+        self.memory_usage_with(guard, |_, _| 0)
+    }
+
+    /// Returns an estimate of how many bytes the map takes up, including the bytes that `f`
+    /// reports the keys and values own outside of the map.
+    ///
+    /// `f` is called once for each entry. See [`memory_usage`](HashMap::memory_usage) for what
+    /// else is counted.
+    pub fn memory_usage_with<F>(&self, guard: &Guard, f: F) -> MemoryUsage
+    where
+        F: FnMut(&K, &V) -> usize,
+    {
+        self.check_guard(guard);
+        #[cfg(feature = "garbage-stats")]
+        let garbage = Some(self.garbage.outstanding_bytes());
+        #[cfg(not(feature = "garbage-stats"))]
+        let garbage = None;
+        let table = self.table.load(Ordering::SeqCst, guard);
+        let next_table = self.next_table.load(Ordering::SeqCst, guard);
+        // safety: we loaded both tables under the `guard`, so they must still be valid here
+        let tables = unsafe { [table.as_ref(), next_table.as_ref()] };
+        crate::diagnostics::memory_usage(&tables, garbage, f, guard)
+    }
+
+    /// Returns the stamp bits for resizing a table of size n.
+    /// Must be negative when shifted left by `RESIZE_STAMP_SHIFT`.
+    fn resize_stamp(n: usize) -> isize {
+        n.leading_zeros() as isize | (1_isize << (RESIZE_STAMP_BITS - 1))
+    }
+
+    /// An iterator visiting all key-value pairs in arbitrary order.
+    ///
+    /// The iterator element type is `(&'g K, &'g V)`.
+    pub fn iter<'g>(&'g self, guard: &'g Guard) -> Iter<'g, K, V> {
+        self.check_guard(guard);
+        let table = self.table.load(Ordering::SeqCst, guard);
+        let node_iter = NodeIter::new(table, guard);
+        Iter { node_iter, guard }
+    }
+
+    /// An iterator visiting clones of all key-value pairs in arbitrary order, which pins the
+    /// map for as long as it is alive.
+    ///
+    /// Unlike [`iter`](HashMap::iter), the returned iterator is not tied to a guard the caller
+    /// holds, only to the map, so it can be returned from functions or kept in structs. Since
+    /// the iterator hands out clones of the entries rather than references into the map, nothing
+    /// it yields outlives its guard. The iterator element type is `(K, V)`.
+    ///
+    /// Like any other guard, the one kept by the iterator holds up the reclamation of memory
+    /// that is removed from the map, so the iterator should not be kept around for longer than
+    /// needed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// fn evens(map: &HashMap<usize, usize>) -> impl Iterator<Item = (usize, usize)> + '_ {
+    ///     map.owned_iter().filter(|(k, _)| k % 2 == 0)
+    /// }
+    ///
+    /// let map: HashMap<_, _> = (0..8).map(|i| (i, i * 10)).collect();
+    /// let mut evens: Vec<_> = evens(&map).collect();
+    /// evens.sort();
+    /// assert_eq!(evens, [(0, 0), (2, 20), (4, 40), (6, 60)]);
+    /// ```
+    pub fn owned_iter(&self) -> OwnedIter<'_, K, V>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let guard = Box::into_raw(Box::new(self.guard()));
+        // safety: the guard is only dropped along with the iterator, and everything the node
+        // iterator borrows from it stays inside the iterator
+        let node_guard: &Guard = unsafe { &*guard };
+        let table = self.table.load(Ordering::SeqCst, node_guard);
+        let node_iter = NodeIter::new(table, node_guard);
+        OwnedIter { node_iter, guard }
+    }
+
+    /// An iterator visiting clones of all values in arbitrary order, which pins the map for as
+    /// long as it is alive.
+    ///
+    /// Like [`owned_iter`](HashMap::owned_iter), the returned iterator holds a guard of its own,
+    /// and can be kept around without one, but it only clones the values. The iterator element
+    /// type is `V`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map: HashMap<_, _> = (0..4).map(|i| (i, i.to_string())).collect();
+    /// let mut values: Vec<String> = map.values_cloned().collect();
+    /// values.sort();
+    /// assert_eq!(values, ["0", "1", "2", "3"]);
+    /// ```
+    pub fn values_cloned(&self) -> ValuesCloned<'_, K, V>
+    where
+        V: Clone,
+    {
+        let guard = Box::into_raw(Box::new(self.guard()));
+        // safety: the guard is only dropped along with the iterator, and everything the node
+        // iterator borrows from it stays inside the iterator
+        let node_guard: &Guard = unsafe { &*guard };
+        let table = self.table.load(Ordering::SeqCst, node_guard);
+        let node_iter = NodeIter::new(table, node_guard);
+        ValuesCloned { node_iter, guard }
+    }
+
+    /// An iterator visiting all keys in arbitrary order.
+    ///
+    /// The iterator element type is `&'g K`.
+    pub fn keys<'g>(&'g self, guard: &'g Guard) -> Keys<'g, K, V> {
+        self.check_guard(guard);
+        let table = self.table.load(Ordering::SeqCst, guard);
+        let node_iter = NodeIter::new(table, guard);
+        Keys { node_iter }
+    }
+
+    /// An iterator visiting all values in arbitrary order.
+    ///
+    /// The iterator element type is `&'g V`.
+    pub fn values<'g>(&'g self, guard: &'g Guard) -> Values<'g, K, V> {
+        self.check_guard(guard);
+        let table = self.table.load(Ordering::SeqCst, guard);
+        let node_iter = NodeIter::new(table, guard);
+        Values { node_iter, guard }
+    }
+
+    /// A parallel iterator visiting all key-value pairs in arbitrary order.
+    ///
+    /// The work is split up by regions of the map's table, which [rayon] then hands to its worker
+    /// threads. All of them read the map under `guard`. The iterator element type is
+    /// `(&'g K, &'g V)`.
+    ///
+    /// This is only available with the `rayon` feature.
+    ///
+    /// [rayon]: https://docs.rs/rayon
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    /// use rayon::iter::ParallelIterator;
+    ///
+    /// let map: HashMap<_, _> = (0..10_000u64).map(|i| (i, i * 2)).collect();
+    /// let guard = map.guard();
+    /// let sum: u64 = map.par_iter(&guard).map(|(_, v)| v).sum();
+    /// assert_eq!(sum, 9_999 * 10_000);
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn par_iter<'g>(&'g self, guard: &'g Guard) -> ParIter<'g, K, V> {
+        ParIter {
+            iter: self.iter(guard),
+        }
+    }
+
+    /// A parallel iterator visiting all keys in arbitrary order.
+    ///
+    /// See [`par_iter`](HashMap::par_iter) for details. The iterator element type is `&'g K`.
+    #[cfg(feature = "rayon")]
+    pub fn par_keys<'g>(&'g self, guard: &'g Guard) -> ParKeys<'g, K, V> {
+        ParKeys {
+            iter: self.keys(guard),
+        }
+    }
+
+    /// A parallel iterator visiting all values in arbitrary order.
+    ///
+    /// See [`par_iter`](HashMap::par_iter) for details. The iterator element type is `&'g V`.
+    #[cfg(feature = "rayon")]
+    pub fn par_values<'g>(&'g self, guard: &'g Guard) -> ParValues<'g, K, V> {
+        ParValues {
+            iter: self.values(guard),
+        }
+    }
+
+    /// Calls `f` on every key-value pair of the map, in parallel.
+    ///
+    /// Like [`view`](HashMap::view), this manages the guard itself: the map is pinned once on the
+    /// calling thread for the whole traversal, and the rayon workers read the entries under that
+    /// pin instead of pinning for every entry. The work is split up by regions of the table, just
+    /// like for [`par_iter`](HashMap::par_iter).
+    ///
+    /// Since the map stays pinned until all entries have been visited, no memory retired in the
+    /// meantime can be reclaimed, so `f` should not take overly long.
+    ///
+    /// This is only available with the `rayon` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    /// use std::sync::atomic::{AtomicU64, Ordering};
+    ///
+    /// let map: HashMap<_, _> = (0..1_000u64).map(|i| (i, i * 2)).collect();
+    /// let sum = AtomicU64::new(0);
+    /// map.par_for_each(|_, &v| {
+    ///     sum.fetch_add(v, Ordering::Relaxed);
+    /// });
+    /// assert_eq!(sum.into_inner(), 999 * 1_000);
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn par_for_each<F>(&self, f: F)
+    where
+        K: Sync,
+        V: Sync,
+        F: Fn(&K, &V) + Sync,
+    {
+        use rayon::iter::ParallelIterator;
+
+        let guard = self.guard();
+        self.par_iter(&guard).for_each(|(k, v)| f(k, v))
+    }
+
+    /// Returns the result of calling `f` on some key-value pair for which it returns `Some`, or
+    /// `None` if there is no such pair.
+    ///
+    /// The regions of the table are searched in parallel, like with
+    /// [`par_iter`](HashMap::par_iter), and once some worker finds a result, the others stop
+    /// searching. If `f` returns `Some` for several entries, it is unspecified which of their
+    /// results is returned.
+    ///
+    /// This is only available with the `rayon` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map: HashMap<_, _> = (0..10_000).map(|i| (i, i * 3)).collect();
+    /// let guard = map.guard();
+    /// let found = map.par_search(|&k, &v| if v == 2_997 { Some(k) } else { None }, &guard);
+    /// assert_eq!(found, Some(999));
+    /// assert_eq!(map.par_search(|_, &v| Some(v).filter(|&v| v % 2 == 3), &guard), None);
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn par_search<R, F>(&self, f: F, guard: &Guard) -> Option<R>
+    where
+        K: Sync,
+        V: Sync,
+        R: Send,
+        F: Fn(&K, &V) -> Option<R> + Sync,
+    {
+        use rayon::iter::ParallelIterator;
+
+        self.par_iter(guard).find_map_any(|(k, v)| f(k, v))
+    }
+
+    /// Returns the result of accumulating the given transformation of all key-value pairs using
+    /// `reducer` to combine values, or `None` if the map is empty.
+    ///
+    /// Like Java's `ConcurrentHashMap.reduce`, this runs in parallel if the map has at least
+    /// `parallelism_threshold` entries, and on the calling thread otherwise. Pass `usize::MAX` to
+    /// always run sequentially, or 1 to always run in parallel. Just like for
+    /// [`par_iter`](HashMap::par_iter), the work is split up by regions of the table, and the
+    /// entries are combined in no particular order, so `reducer` should be associative and
+    /// commutative.
+    ///
+    /// This is only available with the `rayon` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map: HashMap<_, _> = (1..=100u64).map(|i| (i, i * i)).collect();
+    /// let guard = map.guard();
+    /// let largest = map.reduce_entries(1_000, |&k, &v| (v, k), std::cmp::max, &guard);
+    /// assert_eq!(largest, Some((10_000, 100)));
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn reduce_entries<R, T, F>(
+        &self,
+        parallelism_threshold: usize,
+        transformer: T,
+        reducer: F,
+        guard: &Guard,
+    ) -> Option<R>
+    where
+        K: Sync,
+        V: Sync,
+        R: Send,
+        T: Fn(&K, &V) -> R + Sync,
+        F: Fn(R, R) -> R + Sync,
+    {
+        use rayon::iter::ParallelIterator;
+
+        if self.len() < parallelism_threshold {
+            self.iter(guard)
+                .map(|(k, v)| transformer(k, v))
+                .reduce(reducer)
+        } else {
+            self.par_iter(guard)
+                .map(|(k, v)| transformer(k, v))
+                .reduce_with(&reducer)
+        }
+    }
+
+    /// Returns the result of accumulating the given transformation of all keys using `reducer`
+    /// to combine values, or `None` if the map is empty.
+    ///
+    /// See [`reduce_entries`](HashMap::reduce_entries) for details.
+    #[cfg(feature = "rayon")]
+    pub fn reduce_keys<R, T, F>(
+        &self,
+        parallelism_threshold: usize,
+        transformer: T,
+        reducer: F,
+        guard: &Guard,
+    ) -> Option<R>
+    where
+        K: Sync,
+        V: Sync,
+        R: Send,
+        T: Fn(&K) -> R + Sync,
+        F: Fn(R, R) -> R + Sync,
+    {
+        self.reduce_entries(parallelism_threshold, |k, _| transformer(k), reducer, guard)
+    }
+
+    /// Returns the result of accumulating the given transformation of all values using `reducer`
+    /// to combine values, or `None` if the map is empty.
+    ///
+    /// See [`reduce_entries`](HashMap::reduce_entries) for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map: HashMap<_, _> = (0..10_000u64).map(|i| (i, i)).collect();
+    /// let sum = map.reduce_values(1, |&v| v, |a, b| a + b, &map.guard());
+    /// assert_eq!(sum, Some(9_999 * 10_000 / 2));
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn reduce_values<R, T, F>(
+        &self,
+        parallelism_threshold: usize,
+        transformer: T,
+        reducer: F,
+        guard: &Guard,
+    ) -> Option<R>
+    where
+        K: Sync,
+        V: Sync,
+        R: Send,
+        T: Fn(&V) -> R + Sync,
+        F: Fn(R, R) -> R + Sync,
+    {
+        self.reduce_entries(parallelism_threshold, |_, v| transformer(v), reducer, guard)
+    }
+
+    /// Returns a cursor that scans the map's entries one bin at a time, and can be paused and
+    /// resumed under a different guard.
+    ///
+    /// See the [`cursor`](crate::cursor) module for details.
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map: HashMap<_, _> = (0..16).map(|i| (i, i)).collect();
+    /// let guard = map.guard();
+    /// let mut cursor = map.cursor(&guard);
+    /// let first = cursor.by_ref().take(4).count();
+    /// let token = cursor.into_token();
+    /// drop(guard);
+    ///
+    /// let guard = map.guard();
+    /// let rest = map.cursor_from(token, &guard).count();
+    /// assert!(first + rest >= 16);
+    /// ```
+    pub fn cursor<'g>(&'g self, guard: &'g Guard) -> Cursor<'g, K, V> {
+        self.cursor_from(crate::cursor::START, guard)
+    }
+
+    /// Returns a cursor that picks up a scan of the map where the cursor that `token` was taken
+    /// from stopped.
+    ///
+    /// See the [`cursor`](crate::cursor) module for details.
+    pub fn cursor_from<'g>(&'g self, token: ResumeToken, guard: &'g Guard) -> Cursor<'g, K, V> {
+        self.check_guard(guard);
+        Cursor::new(&self.table, token, guard)
+    }
+
+    /// Returns up to `n` distinct entries of the map, chosen approximately uniformly at random.
+    ///
+    /// Rather than walking the whole map, this probes random bins, and picks a random entry from
+    /// each bin that is not empty, so it takes time proportional to `n` rather than to the
+    /// number of entries. Entries that share their bin with fewer others are somewhat more
+    /// likely to be picked. Fewer than `n` entries are returned if the map holds fewer, or if so
+    /// many of the bins are empty that most probes come up short.
+    ///
+    /// This requires the `rand` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map: HashMap<_, _> = (0..1024).map(|i| (i, i)).collect();
+    /// let guard = map.guard();
+    /// let sample = map.sample(5, &mut rand::thread_rng(), &guard);
+    /// assert_eq!(sample.len(), 5);
+    /// assert!(sample.iter().all(|(k, v)| k == v));
+    /// ```
+    #[cfg(feature = "rand")]
+    pub fn sample<'g, R>(&'g self, n: usize, rng: &mut R, guard: &'g Guard) -> Vec<(&'g K, &'g V)>
+    where
+        R: rand::Rng + ?Sized,
+    {
+        self.check_guard(guard);
+        let wanted = n.min(self.len());
+        let mut sample = Vec::with_capacity(wanted);
+        let table = self.table.load(Ordering::SeqCst, guard);
+        if table.is_null() || wanted == 0 {
+            return sample;
+        }
+        // safety: flurry does not drop or move until after guard drop
+        let table = unsafe { table.deref() };
+        let bins = table.len();
+
+        let mut entries = Vec::new();
+        for _ in 0..wanted.saturating_mul(SAMPLE_PROBES) {
+            let i = rng.gen_range(0, bins);
+            entries.clear();
+            crate::cursor::collect(table, i, (bins, i), &mut entries, guard);
+            if entries.is_empty() {
+                continue;
+            }
+            let node = entries[rng.gen_range(0, entries.len())];
+            if sample.iter().any(|&(key, _)| std::ptr::eq(key, &node.key)) {
+                continue;
+            }
+            let value = node.value.load(Ordering::SeqCst, guard);
+            // safety: flurry does not drop or move until after guard drop
+            sample.push((&node.key, unsafe { value.deref() }));
+            if sample.len() == wanted {
+                break;
+            }
+        }
+        sample
+    }
+
+    /// An iterator visiting all key-value pairs in arbitrary order, with mutable references to
+    /// the values.
+    ///
+    /// Since the map is borrowed mutably, no other thread can access it while the iterator is
+    /// alive, so this does not need a guard. The iterator element type is `(&'a K, &'a mut V)`.
+    ///
+    /// Every entry that a mutable reference is handed out for is reported to the map's observers
+    /// in the same way as by [`get_mut`](HashMap::get_mut), and, if the map keeps
+    /// [entry versions](HashMap::with_entry_versions), moves to a new version.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let mut map: HashMap<_, _> = (0..4).map(|i| (i, i)).collect();
+    /// for (k, v) in map.iter_mut() {
+    ///     *v += k * 10;
+    /// }
+    /// assert_eq!(map.pin().get(&3), Some(&33));
+    /// ```
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        // safety: we have &mut self, and all references we have returned are bound to the
+        // lifetime of a borrow of self, so no other thread can be accessing the map.
+        let guard = unsafe { epoch::unprotected() };
+        let table = self.table.load(Ordering::SeqCst, guard);
+        let node_iter = NodeIter::new(table, guard);
+        IterMut {
+            node_iter,
+            versions: &self.versions,
+            observers: &self.observers,
+            guard,
+        }
+    }
+
+    /// An iterator visiting all values in arbitrary order, with mutable references to them.
+    ///
+    /// Like [`HashMap::iter_mut`], this does not need a guard. The iterator element type is
+    /// `&'a mut V`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let mut map: HashMap<_, _> = (0..4).map(|i| (i, vec![i])).collect();
+    /// for v in map.values_mut() {
+    ///     v.push(0);
+    /// }
+    /// assert_eq!(map.pin().get(&2), Some(&vec![2, 0]));
+    /// ```
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut {
+            iter: self.iter_mut(),
+        }
+    }
+
+    /// Consumes the map, and returns an iterator over its keys in arbitrary order.
+    ///
+    /// Since the map is owned, this does not need a guard, and the keys are moved out of the map
+    /// rather than cloned. The values are dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map: HashMap<_, _> = (0..4).map(|i| (i.to_string(), i)).collect();
+    /// let mut keys: Vec<String> = map.into_keys().collect();
+    /// keys.sort();
+    /// assert_eq!(keys, ["0", "1", "2", "3"]);
+    /// ```
+    pub fn into_keys(self) -> IntoKeys<K, V> {
+        IntoKeys {
+            entries: self.into_entries().into_iter(),
+        }
+    }
+
+    /// Consumes the map, and returns an iterator over its values in arbitrary order.
+    ///
+    /// Since the map is owned, this does not need a guard, and the values are moved out of the
+    /// map rather than cloned. The keys are dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map: HashMap<_, _> = (0..4).map(|i| (i, vec![i; i])).collect();
+    /// let total: usize = map.into_values().map(|v| v.len()).sum();
+    /// assert_eq!(total, 6);
+    /// ```
+    pub fn into_values(self) -> IntoValues<K, V> {
+        IntoValues {
+            entries: self.into_entries().into_iter(),
+        }
+    }
+
+    /// Consumes the map, and moves its entries into a [`std::collections::HashMap`] that uses a
+    /// clone of the map's hasher.
+    ///
+    /// Since the map is owned, this does not need a guard, and the keys and values are moved out
+    /// of the map rather than cloned. A map that is shared between threads through an [`Arc`] can
+    /// be converted once all other threads are done with it, using [`Arc::try_unwrap`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    /// use std::sync::Arc;
+    /// use std::thread;
+    ///
+    /// let map = Arc::new(HashMap::new());
+    /// let handles: Vec<_> = (0..4)
+    ///     .map(|i| {
+    ///         let map = Arc::clone(&map);
+    ///         thread::spawn(move || {
+    ///             map.pin().insert(i, i * 10);
+    ///         })
+    ///     })
+    ///     .collect();
+    /// for handle in handles {
+    ///     handle.join().unwrap();
+    /// }
+    ///
+    /// let map = Arc::try_unwrap(map).unwrap().into_std();
+    /// assert_eq!(map.len(), 4);
+    /// assert_eq!(map[&3], 30);
+    /// ```
+    pub fn into_std(self) -> std::collections::HashMap<K, V, S>
+    where
+        K: Hash + Eq,
+        S: BuildHasher + Clone,
+    {
+        let hash_builder = self.build_hasher.clone();
+        let entries = self.into_entries();
+        let mut map =
+            std::collections::HashMap::with_capacity_and_hasher(entries.len(), hash_builder);
+        map.extend(entries);
+        map
+    }
+
+    /// Moves all entries out of the map.
+    fn into_entries(self) -> Vec<(K, V)> {
+        // safety: we own the map, and all references we have returned are bound to the lifetime
+        // of a borrow of it, so no-one else can access its entries. see also `drop`.
+        let guard = unsafe { crossbeam_epoch::unprotected() };
+
+        assert!(self.next_table.load(Ordering::SeqCst, guard).is_null());
+        let table = self.table.swap(Shared::null(), Ordering::SeqCst, guard);
+        let mut entries = Vec::new();
+        // table may never have been allocated
+        if !table.is_null() {
+            // safety: same as above + we own the table
+            let mut table = unsafe { table.into_owned() }.into_box();
+            entries.reserve(self.len());
+            table.take_entries(&mut entries);
+        }
+        entries
+    }
+
+    /// Calls `f` on the entries of the map until it returns `Some`, and returns that result.
+    ///
+    /// Returns `None` if `f` returns `None` for every entry. The entries are visited in arbitrary
+    /// order, and as with [`HashMap::iter`], entries that are inserted or removed while the map is
+    /// searched may or may not be visited. This is a linear scan, but it walks the bins directly
+    /// and stops at the first match.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// let guard = map.guard();
+    /// map.insert("alice", 31, &guard);
+    /// map.insert("bob", 17, &guard);
+    ///
+    /// let minor = map.search(|name, &age| if age < 18 { Some(*name) } else { None }, &guard);
+    /// assert_eq!(minor, Some("bob"));
+    /// assert_eq!(map.search(|_, &age| if age > 99 { Some(age) } else { None }, &guard), None);
+    /// ```
+    pub fn search<'g, R, F>(&'g self, mut f: F, guard: &'g Guard) -> Option<R>
+    where
+        F: FnMut(&'g K, &'g V) -> Option<R>,
+    {
+        self.check_guard(guard);
+        let table = self.table.load(Ordering::SeqCst, guard);
+        for node in NodeIter::new(table, guard) {
+            let value = node.value.load(Ordering::SeqCst, guard);
+            // safety: flurry does not drop or move until after guard drop
+            if let Some(result) = f(&node.key, unsafe { value.deref() }) {
+                return Some(result);
+            }
+        }
+        None
+    }
+
+    /// Returns `true` if the map contains an entry with the given value.
+    ///
+    /// This is a linear scan over the map's entries, see [`HashMap::search`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// let guard = map.guard();
+    /// map.insert(1, "a", &guard);
+    /// assert!(map.contains_value(&"a", &guard));
+    /// assert!(!map.contains_value(&"b", &guard));
+    /// ```
+    pub fn contains_value(&self, value: &V, guard: &Guard) -> bool
+    where
+        V: PartialEq,
+    {
+        self.search(|_, v| if v == value { Some(()) } else { None }, guard)
+            .is_some()
+    }
+
+    /// Returns the `k` largest entries of the map according to `cmp`, largest first.
+    ///
+    /// This visits every entry once, and only keeps the `k` largest ones seen so far, so it is
+    /// much cheaper than collecting and sorting the whole map when `k` is small. Entries that are
+    /// inserted or removed while the map is traversed may or may not be taken into account.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let views = HashMap::new();
+    /// let guard = views.guard();
+    /// views.insert("/", 120, &guard);
+    /// views.insert("/about", 8, &guard);
+    /// views.insert("/blog", 45, &guard);
+    /// views.insert("/contact", 3, &guard);
+    ///
+    /// let top = views.top_k_by(2, |a, b| a.1.cmp(b.1), &guard);
+    /// assert_eq!(top, vec![(&"/", &120), (&"/blog", &45)]);
+    /// ```
+    pub fn top_k_by<'g, F>(&'g self, k: usize, mut cmp: F, guard: &'g Guard) -> Vec<(&'g K, &'g V)>
+    where
+        F: FnMut(&(&'g K, &'g V), &(&'g K, &'g V)) -> std::cmp::Ordering,
+    {
+        // a min-heap (according to `cmp`) of the largest entries seen so far
+        let mut heap = Vec::with_capacity(k.min(self.len()));
+        if k == 0 {
+            return heap;
+        }
+        for entry in self.iter(guard) {
+            if heap.len() < k {
+                heap.push(entry);
+                sift_up(&mut heap, &mut cmp);
+            } else if cmp(&entry, &heap[0]) == std::cmp::Ordering::Greater {
+                heap[0] = entry;
+                sift_down(&mut heap, &mut cmp);
+            }
+        }
+        heap.sort_by(|a, b| cmp(b, a));
+        heap
+    }
+
+    fn init_table<'g>(&'g self, guard: &'g Guard) -> Shared<'g, Table<K, V>> {
+        loop {
+            let table = self.table.load(Ordering::SeqCst, guard);
+            // safety: we loaded the table while epoch was pinned. table won't be deallocated until
+            // next epoch at the earliest.
+            if !table.is_null() && !unsafe { table.deref() }.is_empty() {
+                break table;
+            }
+            // try to allocate the table
+            let mut sc = self.size_ctl.load(Ordering::SeqCst);
+            if sc < 0 {
+                // we lost the initialization race; just spin
+                crate::primitives::yield_now();
+                continue;
+            }
+
+            if self.size_ctl.compare_and_swap(sc, -1, Ordering::SeqCst) == sc {
                 // we get to do it!
                 let mut table = self.table.load(Ordering::SeqCst, guard);
 
@@ -494,10 +2059,10 @@ impl<K, V, S> HashMap<K, V, S> {
                     } else {
                         DEFAULT_CAPACITY
                     };
-                    let new_table = Owned::new(Table::new(n));
+                    let new_table = Owned::new(self.alloc_table(n, sc));
                     table = new_table.into_shared(guard);
                     self.table.store(table, Ordering::SeqCst);
-                    sc = load_factor!(n as isize)
+                    sc = self.resize_threshold(n);
                 }
                 self.size_ctl.store(sc, Ordering::SeqCst);
                 break table;
@@ -505,6 +2070,69 @@ impl<K, V, S> HashMap<K, V, S> {
         }
     }
 
+    /// Allocates a table with `bins` bins while the calling thread holds `size_ctl` at a negative
+    /// value, and sets `size_ctl` back to `restore` if the allocation panics.
+    fn alloc_table(&self, bins: usize, restore: isize) -> Table<K, V> {
+        let restore = RestoreSizeCtl {
+            size_ctl: &self.size_ctl,
+            value: restore,
+        };
+        let table = Table::new(bins);
+        std::mem::forget(restore);
+        table
+    }
+
+    /// Like [`alloc_table`](HashMap::alloc_table), but allocates the table with
+    /// `Table::try_new` if `fallible` is set.
+    fn try_alloc_table(
+        &self,
+        bins: usize,
+        fallible: bool,
+        restore: isize,
+    ) -> Result<Table<K, V>, TryReserveError> {
+        let restore = RestoreSizeCtl {
+            size_ctl: &self.size_ctl,
+            value: restore,
+        };
+        let table = new_table(bins, fallible);
+        std::mem::forget(restore);
+        table
+    }
+
+    /// Returns the number of bins a table needs to hold `size` elements without resizing.
+    fn table_size_for(&self, size: usize) -> usize {
+        match self.load_factor {
+            None if size >= MAXIMUM_CAPACITY / 2 => MAXIMUM_CAPACITY,
+            None => {
+                // round the requested_capacity to the next power of to from 1.5 * size + 1
+                // TODO: find out if this is neccessary
+                let size = size + (size >> 1) + 1;
+
+                std::cmp::min(MAXIMUM_CAPACITY, size.next_power_of_two())
+            }
+            Some(load_factor) => {
+                let size = 1.0 + size as f64 / f64::from(load_factor);
+                if size >= MAXIMUM_CAPACITY as f64 {
+                    MAXIMUM_CAPACITY
+                } else {
+                    (size as usize).next_power_of_two()
+                }
+            }
+        }
+    }
+
+    /// Returns the number of elements at which a table with `n` bins should be resized.
+    fn resize_threshold(&self, n: usize) -> isize {
+        match self.load_factor {
+            None => load_factor!(n as isize),
+            Some(load_factor) => {
+                let threshold = (n as f64 * f64::from(load_factor)).ceil();
+                // the map never gets anywhere near this many elements
+                threshold.min((1u64 << 62) as f64) as isize
+            }
+        }
+    }
+
     /// Presize the table to accommodate the given number of elements.
     fn presize(&mut self, size: usize) {
         // NOTE: this is a stripped-down version of try_presize for use only when we _know_ that
@@ -515,15 +2143,7 @@ impl<K, V, S> HashMap<K, V, S> {
         // while we are initializing it.
         let guard = unsafe { epoch::unprotected() };
 
-        let requested_capacity = if size >= MAXIMUM_CAPACITY / 2 {
-            MAXIMUM_CAPACITY
-        } else {
-            // round the requested_capacity to the next power of to from 1.5 * size + 1
-            // TODO: find out if this is neccessary
-            let size = size + (size >> 1) + 1;
-
-            std::cmp::min(MAXIMUM_CAPACITY, size.next_power_of_two())
-        } as usize;
+        let requested_capacity = self.table_size_for(size);
 
         // sanity check that the map has indeed not been set up already
         assert_eq!(self.size_ctl.load(Ordering::SeqCst), 0);
@@ -539,7 +2159,7 @@ impl<K, V, S> HashMap<K, V, S> {
         self.table.store(new_table, Ordering::SeqCst);
 
         // resize the table once it is 75% full
-        let new_load_to_resize_at = load_factor!(requested_capacity as isize);
+        let new_load_to_resize_at = self.resize_threshold(requested_capacity);
 
         // store the next load at which the table should resize to it's size_ctl field
         // and thus release the initialization "lock"
@@ -557,19 +2177,23 @@ impl<K, V, S> HashMap<K, V, S> {
 
 impl<K, V, S> HashMap<K, V, S>
 where
-    K: Clone + Ord,
+    K: Clone + TreeKey,
 {
     /// Tries to presize table to accommodate the given number of elements.
     fn try_presize(&self, size: usize, guard: &Guard) {
-        let requested_capacity = if size >= MAXIMUM_CAPACITY / 2 {
-            MAXIMUM_CAPACITY
-        } else {
-            // round the requested_capacity to the next power of to from 1.5 * size + 1
-            // TODO: find out if this is neccessary
-            let size = size + (size >> 1) + 1;
+        let result = self.presize_table(size, false, guard);
+        debug_assert!(result.is_ok());
+    }
 
-            std::cmp::min(MAXIMUM_CAPACITY, size.next_power_of_two())
-        } as isize;
+    /// Like `try_presize`, but if `fallible` is set, allocates the new table with
+    /// `Table::try_new`, and gives up if that fails, leaving the map as it was.
+    fn presize_table(
+        &self,
+        size: usize,
+        fallible: bool,
+        guard: &Guard,
+    ) -> Result<(), TryReserveError> {
+        let requested_capacity = self.table_size_for(size) as isize;
 
         loop {
             let size_ctl = self.size_ctl.load(Ordering::SeqCst);
@@ -619,7 +2243,14 @@ where
                 }
 
                 // create a table with `new_capacity` empty bins
-                let new_table = Owned::new(Table::new(new_capacity)).into_shared(guard);
+                let new_table = match self.try_alloc_table(new_capacity, fallible, size_ctl) {
+                    Ok(new_table) => Owned::new(new_table).into_shared(guard),
+                    Err(e) => {
+                        // release the initialization "lock" again
+                        self.size_ctl.store(size_ctl, Ordering::SeqCst);
+                        return Err(e);
+                    }
+                };
 
                 // store the new table to `self.table`
                 let old_table = self.table.swap(new_table, Ordering::SeqCst, &guard);
@@ -635,7 +2266,7 @@ where
                 // }
 
                 // resize the table once it is 75% full
-                let new_load_to_resize_at = load_factor!(new_capacity as isize);
+                let new_load_to_resize_at = self.resize_threshold(new_capacity);
 
                 // store the next load at which the table should resize to it's size_ctl field
                 // and thus release the initialization "lock"
@@ -663,10 +2294,46 @@ where
                 {
                     // someone else already started to resize the table
                     // TODO: can we `self.help_transfer`?
-                    self.transfer(table, Shared::null(), &guard);
+                    if !fallible {
+                        self.transfer(table, Shared::null(), &guard);
+                    } else {
+                        // allocate the next table before the transfer starts, so that we can
+                        // still back out. no other thread joins a resize before `next_table` is
+                        // set.
+                        match self.try_alloc_table(current_capactity << 1, true, size_ctl) {
+                            Ok(next_table) => {
+                                let next_table =
+                                    self.start_transfer(current_capactity, next_table, guard);
+                                self.transfer(table, next_table, guard);
+                            }
+                            Err(e) => {
+                                self.size_ctl.store(size_ctl, Ordering::SeqCst);
+                                return Err(e);
+                            }
+                        }
+                    }
                 }
             }
         }
+        Ok(())
+    }
+
+    /// Publishes `next_table` as the table that a resize of a table with `n` bins moves into.
+    fn start_transfer<'g>(
+        &'g self,
+        n: usize,
+        next_table: Table<K, V>,
+        guard: &'g Guard,
+    ) -> Shared<'g, Table<K, V>> {
+        // when growing, every bin of the old table is claimed on its own, and when shrinking,
+        // every bin of the new table is claimed together with all the bins that move into it
+        let claims = std::cmp::min(n, next_table.len());
+        let now_garbage = self
+            .next_table
+            .swap(Owned::new(next_table), Ordering::SeqCst, guard);
+        assert!(now_garbage.is_null());
+        self.transfer_index.store(claims as isize, Ordering::SeqCst);
+        self.next_table.load(Ordering::Relaxed, guard)
     }
 
     // NOTE: transfer requires that K and V are Send + Sync if it will actually transfer anything.
@@ -683,22 +2350,18 @@ where
         // this references is still active (marked by the guard), so the target of the references
         // won't be dropped while the guard remains active.
         let n = unsafe { table.deref() }.len();
-        let ncpu = num_cpus();
-
-        let stride = if ncpu > 1 { (n >> 3) / ncpu } else { n };
-        let stride = std::cmp::max(stride as isize, MIN_TRANSFER_STRIDE);
+        let stride = transfer_stride(n);
 
         if next_table.is_null() {
             // we are initiating a resize
-            let table = Owned::new(Table::new(n << 1));
-            let now_garbage = self.next_table.swap(table, Ordering::SeqCst, guard);
-            assert!(now_garbage.is_null());
-            self.transfer_index.store(n as isize, Ordering::SeqCst);
-            next_table = self.next_table.load(Ordering::Relaxed, guard);
+            // whoever started the resize got here when the table reached its resize threshold
+            let next = self.alloc_table(n << 1, self.resize_threshold(n));
+            next_table = self.start_transfer(n, next, guard);
         }
 
         // safety: same argument as for table above
         let next_n = unsafe { next_table.deref() }.len();
+        let claims = std::cmp::min(n, next_n);
 
         let mut advance = true;
         let mut finishing = false;
@@ -737,11 +2400,14 @@ where
                 }
             }
 
-            if i < 0 || i as usize >= n || i as usize + n >= next_n {
+            if i < 0 || i as usize >= claims {
                 // the resize has finished
 
                 if finishing {
                     // this branch is only taken for one thread partaking in the resize!
+                    fail_point!("transfer-finish");
+                    // the map's contents do not change, but stamps are still invalidated by resizes
+                    drop(self.stamps.write());
                     self.next_table.store(Shared::null(), Ordering::SeqCst);
                     let now_garbage = self.table.swap(next_table, Ordering::SeqCst, guard);
                     // safety: need to guarantee that now_garbage is no longer reachable. more
@@ -770,9 +2436,13 @@ where
                     // guard (since our guard is pinning the epoch). since the garbage is placed in
                     // our epoch, it won't be freed until the _next_ epoch, at which point, that
                     // thread must have dropped its guard, and with it, any reference to the value.
-                    unsafe { guard.defer_destroy(now_garbage) };
+                    let bytes = unsafe { now_garbage.deref() }.footprint();
+                    unsafe {
+                        self.garbage
+                            .retire_with(guard, bytes, move || drop(now_garbage.into_owned()))
+                    };
                     self.size_ctl
-                        .store(((n as isize) << 1) - ((n as isize) >> 1), Ordering::SeqCst);
+                        .store(self.resize_threshold(next_n), Ordering::SeqCst);
                     return;
                 }
 
@@ -789,7 +2459,7 @@ where
                     advance = true;
 
                     // NOTE: the java code says "recheck before commit" here
-                    i = n as isize;
+                    i = claims as isize;
                 }
 
                 continue;
@@ -802,6 +2472,14 @@ where
             // the target of these references won't be dropped while the guard remains active.
             let table = unsafe { table.deref() };
 
+            fail_point!("transfer-bin");
+            if next_n < n {
+                // safety: as for table above
+                let next_table = unsafe { next_table.deref() };
+                advance = self.shrink_bin(table, next_table, i, guard);
+                continue;
+            }
+
             let bin = table.bin(i as usize, guard);
             if bin.is_null() {
                 advance = table
@@ -834,6 +2512,11 @@ where
                     // already processed
                     advance = true;
                 }
+                BinEntry::Reserved(ref reservation) => {
+                    // the reservation stays locked until it has been replaced, so once we get the
+                    // lock, the bin has changed and we can try it again
+                    drop(reservation.lock.lock());
+                }
                 BinEntry::Node(ref head) => {
                     // bin is non-empty, need to link into it, so we must take the lock
                     let head_lock = head.lock.lock();
@@ -907,13 +2590,14 @@ where
                             &mut high_bin
                         };
 
-                        *link = Owned::new(BinEntry::Node(Node::with_next(
+                        let copy = Node::with_next(
                             node.hash,
                             node.key.clone(),
                             node.value.clone(),
                             Atomic::from(*link),
-                        )))
-                        .into_shared(guard);
+                        );
+                        copy.copy_version(node);
+                        *link = Owned::new(BinEntry::Node(copy)).into_shared(guard);
 
                         p = node.next.load(Ordering::SeqCst, guard);
                     }
@@ -924,6 +2608,8 @@ where
                         i,
                         table.get_moved(Shared::from(next_table as *const _), guard),
                     );
+                    // the lock lives in the head of the old bin, which may be retired below
+                    drop(head_lock);
 
                     // everything up to last_run in the _old_ bin linked list is now garbage.
                     // those nodes have all been re-allocated in the new bin linked list.
@@ -945,13 +2631,11 @@ where
                             .unwrap()
                             .next
                             .load(Ordering::SeqCst, guard);
-                        unsafe { guard.defer_destroy(p) };
+                        unsafe { self.garbage.retire(guard, p) };
                         p = next;
                     }
 
                     advance = true;
-
-                    drop(head_lock);
                 }
                 BinEntry::Tree(ref tree_bin) => {
                     let bin_lock = tree_bin.lock.lock();
@@ -985,6 +2669,7 @@ where
                             Atomic::null(),
                             Atomic::null(),
                         );
+                        new_node.node.copy_version(&tree_node.node);
                         let run_bit = hash & n as u64;
                         if run_bit == 0 {
                             new_node.prev.store(low_tail, Ordering::Relaxed);
@@ -1088,6 +2773,8 @@ where
                         i,
                         table.get_moved(Shared::from(next_table as *const _), guard),
                     );
+                    // the lock lives in the old bin, which may be retired below
+                    drop(bin_lock);
 
                     // if we did not re-use the old bin, it is now garbage,
                     // since all of its nodes have been reallocated. However,
@@ -1099,11 +2786,10 @@ where
                         // dropping the old bin if it was not used in
                         // `next_table` so there is no other reference to it
                         // anyone could obtain.
-                        unsafe { TreeBin::defer_drop_without_values(bin, guard) };
+                        unsafe { TreeBin::defer_drop_without_values(bin, &self.garbage, guard) };
                     }
 
                     advance = true;
-                    drop(bin_lock);
                 }
                 BinEntry::TreeNode(_) => unreachable!(
                     "The head of a bin cannot be a TreeNode directly without BinEntry::Tree"
@@ -1112,6 +2798,203 @@ where
         }
     }
 
+    /// Moves bins `i`, `i + m`, `i + 2m`, ... of `table` into bin `i` of `next_table`, which has
+    /// `m` bins, as part of shrinking the map.
+    ///
+    /// Returns `false` if any of those bins changed before we got hold of all of them, in which
+    /// case the caller has to try again.
+    fn shrink_bin<'g>(
+        &'g self,
+        table: &'g Table<K, V>,
+        next_table: &'g Table<K, V>,
+        i: usize,
+        guard: &'g Guard,
+    ) -> bool {
+        let m = next_table.len();
+
+        // writers start to insert into the bin of next_table as soon as any of the bins that move
+        // into it is Moved, so we have to hold all of them until that bin is complete. empty bins
+        // are held by reserving them. to not deadlock with a thread that holds one of the bins and
+        // waits for another, we only wait for the lock of the first bin, and start over if any of
+        // the others is busy.
+        let mut locked = Vec::with_capacity(table.len() / m);
+        let mut reserved = Vec::new();
+        for j in (i..table.len()).step_by(m) {
+            let bin = table.bin(j, guard);
+            if bin.is_null() {
+                let reservation = Owned::new(BinEntry::Reserved(Reservation::new()));
+                std::mem::forget(reservation.as_reservation().unwrap().lock.lock());
+                match table.cas_bin(j, bin, reservation, guard) {
+                    Ok(reservation) => {
+                        // dropping this empties the bin again, unless we have moved it
+                        reserved.push(ReservedBin {
+                            table,
+                            bini: j,
+                            reservation,
+                            garbage: &self.garbage,
+                            guard,
+                        });
+                        continue;
+                    }
+                    Err(changed) => {
+                        // safety: we acquired the lock above and forgot its guard, and the
+                        // reservation never became visible to other threads
+                        unsafe { changed.new.as_reservation().unwrap().lock.force_unlock() };
+                        return false;
+                    }
+                }
+            }
+
+            // safety: see the safety comment on the bins in `transfer`
+            let lock = match *unsafe { bin.deref() } {
+                BinEntry::Moved => {
+                    // the bins are moved all at once, so another thread has moved them already
+                    return true;
+                }
+                BinEntry::Reserved(ref reservation) => {
+                    // let go of the bins we hold while we wait for the reservation
+                    drop(locked);
+                    drop(reserved);
+                    drop(reservation.lock.lock());
+                    return false;
+                }
+                BinEntry::Node(ref head) => &head.lock,
+                BinEntry::Tree(ref tree_bin) => &tree_bin.lock,
+                BinEntry::TreeNode(_) => unreachable!(
+                    "The head of a bin cannot be a TreeNode directly without BinEntry::Tree"
+                ),
+            };
+            let bin_lock = if locked.is_empty() && reserved.is_empty() {
+                lock.lock()
+            } else {
+                match lock.try_lock() {
+                    Some(bin_lock) => bin_lock,
+                    None => {
+                        drop(locked);
+                        drop(reserved);
+                        crate::primitives::yield_now();
+                        return false;
+                    }
+                }
+            };
+            if table.bin(j, guard) != bin {
+                // the bin changed before we got the lock
+                return false;
+            }
+            locked.push((bin, bin_lock));
+        }
+
+        let mut entries = Vec::new();
+        for &(bin, _) in &locked {
+            // safety: we hold the lock of the bin, and it is still in the table
+            match *unsafe { bin.deref() } {
+                BinEntry::Node(ref head) => {
+                    let mut node = head;
+                    loop {
+                        entries.push(node);
+                        let next = node.next.load(Ordering::SeqCst, guard);
+                        if next.is_null() {
+                            break;
+                        }
+                        // safety: the nodes of a bin are only dropped after it has been replaced
+                        node = unsafe { next.deref() }.as_node().unwrap();
+                    }
+                }
+                BinEntry::Tree(ref tree_bin) => {
+                    let mut e = tree_bin.first.load(Ordering::SeqCst, guard);
+                    while !e.is_null() {
+                        // safety: as above. Structurally, TreeNodes always point to TreeNodes,
+                        // so this is sound.
+                        let tree_node = unsafe { TreeNode::get_tree_node(e) };
+                        entries.push(&tree_node.node);
+                        e = tree_node.node.next.load(Ordering::SeqCst, guard);
+                    }
+                }
+                _ => unreachable!("only lists and trees are locked"),
+            }
+        }
+
+        // as in `transfer`, the entries are copied, but their values are reused
+        let new_bin = if TREEIFY && entries.len() >= TREEIFY_THRESHOLD && m >= MIN_TREEIFY_CAPACITY
+        {
+            let mut first = Shared::null();
+            let mut tail = Shared::null();
+            for node in &entries {
+                let new_node = TreeNode::new(
+                    node.hash,
+                    node.key.clone(),
+                    node.value.clone(),
+                    Atomic::null(),
+                    Atomic::null(),
+                );
+                new_node.node.copy_version(node);
+                new_node.prev.store(tail, Ordering::Relaxed);
+                let new_node = Owned::new(BinEntry::TreeNode(new_node)).into_shared(guard);
+                if tail.is_null() {
+                    first = new_node;
+                } else {
+                    // safety: `tail` was just created by us and not shared.
+                    // Structurally, TreeNodes always point to TreeNodes, so this is sound.
+                    unsafe { TreeNode::get_tree_node(tail) }
+                        .node
+                        .next
+                        .store(new_node, Ordering::Relaxed);
+                }
+                tail = new_node;
+            }
+            Owned::new(BinEntry::Tree(TreeBin::new(
+                // safety: we have just created `first` and its `next` nodes and have never
+                // shared them
+                unsafe { first.into_owned() },
+                guard,
+            )))
+            .into_shared(guard)
+        } else {
+            let mut head = Shared::null();
+            for node in entries.iter().rev() {
+                let copy = Node::with_next(
+                    node.hash,
+                    node.key.clone(),
+                    node.value.clone(),
+                    Atomic::from(head),
+                );
+                copy.copy_version(node);
+                head = Owned::new(BinEntry::Node(copy)).into_shared(guard);
+            }
+            head
+        };
+
+        next_table.store_bin(i, new_bin);
+        let moved = table.get_moved(Shared::from(next_table as *const _), guard);
+        for j in (i..table.len()).step_by(m) {
+            table.store_bin(j, moved);
+        }
+
+        // the old bins are now garbage, except for the values, which we reused
+        for &(bin, _) in &locked {
+            // safety: the bins were swapped for Moved entries, so no thread can obtain a new
+            // reference to them, and threads with existing references pin an epoch no later than
+            // ours. see the corresponding comments in `transfer`.
+            match *unsafe { bin.deref() } {
+                BinEntry::Node(_) => {
+                    let mut p = bin;
+                    while !p.is_null() {
+                        let next = unsafe { p.deref() }
+                            .as_node()
+                            .unwrap()
+                            .next
+                            .load(Ordering::SeqCst, guard);
+                        unsafe { self.garbage.retire(guard, p) };
+                        p = next;
+                    }
+                }
+                _ => unsafe { TreeBin::defer_drop_without_values(bin, &self.garbage, guard) },
+            }
+        }
+        // dropping `locked` and `reserved` releases the bins
+        true
+    }
+
     fn help_transfer<'g>(
         &'g self,
         table: Shared<'g, Table<K, V>>,
@@ -1157,6 +3040,7 @@ where
         // TODO: implement the Java CounterCell business here
 
         use std::cmp;
+        fail_point!("count-before");
         let mut count = match n.cmp(&0) {
             cmp::Ordering::Greater => self.count.fetch_add(n, Ordering::SeqCst) + n,
             cmp::Ordering::Less => self.count.fetch_sub(n.abs(), Ordering::SeqCst) - n,
@@ -1249,6 +3133,145 @@ where
         let absolute = self.len() + additional;
         self.try_presize(absolute, guard);
     }
+
+    /// Tries to reserve capacity for at least `additional` more elements to be inserted in the
+    /// `HashMap`, and returns an error instead of aborting if the memory for the larger table
+    /// cannot be allocated.
+    ///
+    /// If this returns an error, the map is left as it was, and remains fully usable. Note that
+    /// only the allocation of the table itself is fallible: the map may still allocate a new
+    /// table with the global allocator when a later insert makes it grow, and allocates every
+    /// entry individually as it is inserted. Reserving up front lets code that runs low on memory
+    /// find out before it commits to a large batch of inserts.
+    ///
+    /// As with [`reserve`](HashMap::reserve), the map may reserve more space than requested, and
+    /// does not reserve any if the table is being resized by another thread at the time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::{HashMap, TryReserveError};
+    ///
+    /// let map: HashMap<&str, i32> = HashMap::new();
+    /// let guard = map.guard();
+    ///
+    /// map.try_reserve(10, &guard).expect("out of memory");
+    /// assert!(map.bucket_count(&guard) >= 10);
+    ///
+    /// map.insert("a", 1, &guard);
+    /// assert_eq!(
+    ///     map.try_reserve(usize::max_value(), &guard),
+    ///     Err(TryReserveError::CapacityOverflow)
+    /// );
+    /// ```
+    pub fn try_reserve(&self, additional: usize, guard: &Guard) -> Result<(), TryReserveError> {
+        self.check_guard(guard);
+        let absolute = self
+            .len()
+            .checked_add(additional)
+            .ok_or(TryReserveError::CapacityOverflow)?;
+        self.presize_table(absolute, true, guard)
+    }
+
+    /// Shrinks the table of the map as much as possible, while keeping enough room for its
+    /// current entries.
+    ///
+    /// This is useful once a map that has grown large holds much fewer entries again, since a map
+    /// never shrinks its table on its own. Shrinking moves the entries into a smaller table the
+    /// same way growing moves them into a larger one, so other threads can keep using the map,
+    /// and will help move the entries if they need to modify one of the bins that is being moved.
+    ///
+    /// See [`shrink_to`](HashMap::shrink_to) for details, including how shrinking affects
+    /// [`bucket_index`](HashMap::bucket_index).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// let guard = map.guard();
+    /// for i in 0..1000 {
+    ///     map.insert(i, i, &guard);
+    /// }
+    /// for i in 10..1000 {
+    ///     map.remove(&i, &guard);
+    /// }
+    /// assert!(map.bucket_count(&guard) >= 1000);
+    ///
+    /// map.shrink_to_fit(&guard);
+    /// assert_eq!(map.bucket_count(&guard), 16);
+    /// assert_eq!(map.get(&7, &guard), Some(&7));
+    /// ```
+    pub fn shrink_to_fit(&self, guard: &Guard) {
+        self.shrink_to(0, guard);
+    }
+
+    /// Shrinks the table of the map so that it can still hold at least `min_capacity` elements,
+    /// and at least as many as the map currently contains, without resizing.
+    ///
+    /// The table is never shrunk below the default capacity of 16 bins. If the table is already
+    /// small enough, if it has not been allocated yet, or if another thread is resizing it at the
+    /// time, this does nothing.
+    ///
+    /// Shrinking the table to `n` bins changes the [`bucket_index`](HashMap::bucket_index) of
+    /// keys to their index modulo `n`. Bucket indices taken modulo a power of two that is larger
+    /// than `n` are no longer stable, so auxiliary state that is aligned with the map's buckets
+    /// has to be redistributed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::with_capacity(1000);
+    /// let guard = map.guard();
+    /// map.insert(1, 1, &guard);
+    ///
+    /// map.shrink_to(100, &guard);
+    /// assert_eq!(map.bucket_count(&guard), 256);
+    /// map.shrink_to(0, &guard);
+    /// assert_eq!(map.bucket_count(&guard), 16);
+    /// ```
+    pub fn shrink_to(&self, min_capacity: usize, guard: &Guard) {
+        self.check_guard(guard);
+        let size = std::cmp::max(self.len(), min_capacity);
+        let bins = std::cmp::max(DEFAULT_CAPACITY, self.table_size_for(size));
+
+        loop {
+            let size_ctl = self.size_ctl.load(Ordering::SeqCst);
+            if size_ctl < 0 {
+                // the table is being initialized or resized
+                return;
+            }
+
+            let table = self.table.load(Ordering::SeqCst, guard);
+            if table.is_null() {
+                return;
+            }
+            // safety: we loaded the table under our guard, so it will not be dropped until the
+            // guard is
+            let n = unsafe { table.deref() }.len();
+            if n <= bins {
+                return;
+            }
+
+            let rs = Self::resize_stamp(n) << RESIZE_STAMP_SHIFT;
+            if table == self.table.load(Ordering::SeqCst, guard)
+                && self
+                    .size_ctl
+                    .compare_and_swap(size_ctl, rs + 2, Ordering::SeqCst)
+                    == size_ctl
+            {
+                // we start the resize, just like when growing, except that the next table is
+                // smaller
+                let next = self.alloc_table(bins, size_ctl);
+                let next_table = self.start_transfer(n, next, guard);
+                self.transfer(table, next_table, guard);
+                return;
+            }
+        }
+    }
 }
 
 // ===
@@ -1256,22 +3279,80 @@ where
 // but they _do_ perform lookups, which require hashing and equality.
 // ===
 
-impl<K, V, S> HashMap<K, V, S>
-where
-    K: Hash + Ord,
-    S: BuildHasher,
-{
-    #[inline]
-    fn hash<Q: ?Sized + Hash>(&self, key: &Q) -> u64 {
-        let mut h = self.build_hasher.build_hasher();
-        key.hash(&mut h);
-        h.finish()
+impl<K, V, S> HashMap<K, V, S>
+where
+    K: Hash + TreeKey,
+    S: BuildHasher,
+{
+    #[inline]
+    fn hash<Q: ?Sized + Hash>(&self, key: &Q) -> u64 {
+        let mut h = self.build_hasher.build_hasher();
+        key.hash(&mut h);
+        self.spread(h.finish())
+    }
+
+    fn get_node<'g, Q>(&'g self, key: &Q, guard: &'g Guard) -> Option<&'g Node<K, V>>
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+    {
+        #[cfg(feature = "shadow")]
+        {
+            if let Some(ref shadow) = self.shadow {
+                let _shadow = shadow.enter();
+                let node = self.find_node(key, guard);
+                shadow.check_lookup(
+                    key,
+                    node.map(|node| {
+                        let v = node.value.load(Ordering::SeqCst, guard);
+                        // safety: the value is only dropped in an epoch after it is replaced or
+                        // removed, and we are holding up the epoch with our guard.
+                        (&node.key, unsafe { v.deref() })
+                    }),
+                );
+                return node;
+            }
+        }
+
+        self.find_node(key, guard)
+    }
+
+    fn find_node<'g, Q>(&'g self, key: &Q, guard: &'g Guard) -> Option<&'g Node<K, V>>
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+    {
+        self.find_node_hashed(self.hash(key), key, guard)
+    }
+
+    /// Like `find_node`, but with a hash for `key` that the caller has already computed.
+    pub(crate) fn find_node_hashed<'g, Q>(
+        &'g self,
+        h: u64,
+        key: &Q,
+        guard: &'g Guard,
+    ) -> Option<&'g Node<K, V>>
+    where
+        Q: ?Sized + Comparable<K>,
+    {
+        self.find_node_with(h, |t, bin| t.find(bin, h, key, guard), guard)
+    }
+
+    /// Returns the first node with hash `h` whose key `is_match` accepts.
+    pub(crate) fn find_node_by<'g, F>(
+        &'g self,
+        h: u64,
+        mut is_match: F,
+        guard: &'g Guard,
+    ) -> Option<&'g Node<K, V>>
+    where
+        F: FnMut(&K) -> bool,
+    {
+        self.find_node_with(h, |t, bin| t.find_by(bin, h, &mut is_match, guard), guard)
     }
 
-    fn get_node<'g, Q>(&'g self, key: &Q, guard: &'g Guard) -> Option<&'g Node<K, V>>
+    /// Looks up the bin for `h` in the current table, and uses `find` to search it.
+    fn find_node_with<'g, F>(&'g self, h: u64, find: F, guard: &'g Guard) -> Option<&'g Node<K, V>>
     where
-        K: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        F: FnOnce(&'g Table<K, V>, &'g BinEntry<K, V>) -> Shared<'g, BinEntry<K, V>>,
     {
         let table = self.table.load(Ordering::SeqCst, guard);
         if table.is_null() {
@@ -1285,7 +3366,6 @@ where
             return None;
         }
 
-        let h = self.hash(key);
         let bini = table.bini(h);
         let bin = table.bin(bini, guard);
         if bin.is_null() {
@@ -1309,7 +3389,7 @@ where
         // swap happened, it must have happened _after_ we read. since we did the read while
         // pinning the epoch, the drop must happen in the _next_ epoch (i.e., the one that we
         // are holding up by holding on to our guard).
-        let node = table.find(unsafe { bin.deref() }, h, key, guard);
+        let node = find(table, unsafe { bin.deref() });
         if node.is_null() {
             return None;
         }
@@ -1320,16 +3400,17 @@ where
         Some(match node {
             BinEntry::Node(ref n) => n,
             BinEntry::TreeNode(ref tn) => &tn.node,
-            _ => panic!("`Table::find` should always return a Node"),
+            _ => panic!("`Table::find` and `Table::find_by` should always return a Node"),
         })
     }
 
     /// Returns `true` if the map contains a value for the specified key.
     ///
-    /// The key may be any borrowed form of the map's key type, but
-    /// [`Hash`] and [`Ord`] on the borrowed form *must* match those for
-    /// the key type.
+    /// The key may be any borrowed form of the map's key type, or any other type that is
+    /// [`Comparable`] to it, but [`Hash`] and [`Ord`] on that type *must* match those for the
+    /// key type.
     ///
+    /// [`Comparable`]: crate::Comparable
     /// [`Ord`]: std::cmp::Ord
     /// [`Hash`]: std::hash::Hash
     ///
@@ -1346,8 +3427,7 @@ where
     /// ```
     pub fn contains_key<Q>(&self, key: &Q, guard: &Guard) -> bool
     where
-        K: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<K>,
     {
         self.check_guard(guard);
         self.get(key, &guard).is_some()
@@ -1355,10 +3435,11 @@ where
 
     /// Returns a reference to the value corresponding to the key.
     ///
-    /// The key may be any borrowed form of the map's key type, but
-    /// [`Hash`] and [`Ord`] on the borrowed form *must* match those for
-    /// the key type.
+    /// The key may be any borrowed form of the map's key type, or any other type that is
+    /// [`Comparable`] to it, but [`Hash`] and [`Ord`] on that type *must* match those for the
+    /// key type.
     ///
+    /// [`Comparable`]: crate::Comparable
     /// [`Ord`]: std::cmp::Ord
     /// [`Hash`]: std::hash::Hash
     ///
@@ -1378,8 +3459,7 @@ where
     #[inline]
     pub fn get<'g, Q>(&'g self, key: &Q, guard: &'g Guard) -> Option<&'g V>
     where
-        K: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<K>,
     {
         self.check_guard(guard);
         let node = self.get_node(key, guard)?;
@@ -1392,9 +3472,162 @@ where
         unsafe { v.as_ref() }
     }
 
-    /// Returns the key-value pair corresponding to `key`.
+    /// Returns a mutable reference to the value corresponding to the key.
     ///
-    /// Returns `None` if this map contains no mapping for `key`.
+    /// Since the map is borrowed mutably, no other thread can access it while the reference is
+    /// alive, so this does not need a guard, and the value can be changed in place.
+    ///
+    /// The key may be any borrowed form of the map's key type, or any other type that is
+    /// [`Comparable`] to it, but [`Hash`] and [`Ord`] on that type *must* match those for the
+    /// key type.
+    ///
+    /// [`Comparable`]: crate::Comparable
+    /// [`Ord`]: std::cmp::Ord
+    /// [`Hash`]: std::hash::Hash
+    ///
+    /// The map cannot see what is done through the returned reference, so it reports handing it
+    /// out as a change of its own: the map's [`Recorder`](crate::record::Recorder) logs an
+    /// [`Op::Lend`](crate::record::Op::Lend), its [`EventStream`](crate::events::EventStream)
+    /// receives an update without a value, and a [`Watch`] on the key wakes up. With
+    /// [shadow checking](HashMap::with_shadow_checking), any value is accepted for the key until
+    /// it is next replaced or removed. If the map keeps
+    /// [entry versions](HashMap::with_entry_versions), the entry moves to a new version.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// map.pin().insert(1, String::from("a"));
+    /// if let Some(v) = map.get_mut(&1) {
+    ///     v.push('b');
+    /// }
+    /// assert_eq!(map.pin().get(&1).map(String::as_str), Some("ab"));
+    /// assert_eq!(map.get_mut(&2), None);
+    /// ```
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+    {
+        // safety: we have &mut self, and all references we have returned are bound to the
+        // lifetime of a borrow of self, so no other thread can be accessing the map.
+        let guard = unsafe { epoch::unprotected() };
+        let node = self.get_node(key, guard)?;
+        if self.observers.is_active() {
+            // safety: as above, so the value cannot have been dropped
+            let value = unsafe { node.value.load(Ordering::SeqCst, guard).deref() };
+            self.observers.notify(Change::Lent {
+                key: &node.key,
+                value,
+            });
+        }
+        // safety: as above, and we only hand out this one reference to the value
+        Some(unsafe { node.value_mut(self.versions.next(), guard) })
+    }
+
+    /// Returns references to the values for each of `keys`, in the same order.
+    ///
+    /// This is the same as calling [`get`](HashMap::get) for each key with the same guard, which
+    /// is the main cost to avoid when looking up many keys at once. Each lookup is independent,
+    /// so the results do not necessarily reflect a single point in time if the map is modified
+    /// concurrently.
+    ///
+    /// The key may be any borrowed form of the map's key type, but
+    /// [`Hash`] and [`Ord`] on the borrowed form *must* match those for
+    /// the key type.
+    ///
+    /// [`Ord`]: std::cmp::Ord
+    /// [`Hash`]: std::hash::Hash
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// let guard = map.guard();
+    /// map.insert(1, "a", &guard);
+    /// map.insert(2, "b", &guard);
+    /// assert_eq!(map.get_many(&[&2, &3, &1], &guard), [Some(&"b"), None, Some(&"a")]);
+    /// ```
+    pub fn get_many<'g, Q>(&'g self, keys: &[&Q], guard: &'g Guard) -> Vec<Option<&'g V>>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
+    {
+        self.check_guard(guard);
+        keys.iter().map(|key| self.get(*key, guard)).collect()
+    }
+
+    /// Returns the index of the bucket (bin) that `key` falls into in the map's current table, or
+    /// `None` if no table has been allocated yet.
+    ///
+    /// This lets callers keep auxiliary per-bucket state, such as striped locks or statistics,
+    /// that is distributed across keys the same way the map's entries are. The index is always
+    /// less than the [`bucket_count`](HashMap::bucket_count) of the same table.
+    ///
+    /// Note that a resize, which may happen concurrently with this call, moves keys to new
+    /// buckets. However, the number of buckets is always a power of two, and the index is taken
+    /// from the low bits of the key's hash, so `bucket_index(key) % m` stays the same across
+    /// resizes for any power of two `m` that the number of buckets does not drop below. As the
+    /// map grows on its own, that holds for any `m` that is at most the number of buckets at the
+    /// time, and sizing auxiliary state to such an `m` keeps it aligned with the map's
+    /// distribution. Only [`shrink_to`](HashMap::shrink_to) and
+    /// [`shrink_to_fit`](HashMap::shrink_to_fit) reduce the number of buckets, so auxiliary state
+    /// must be resized along with the map if it is shrunk below `m`.
+    ///
+    /// The key may be any borrowed form of the map's key type, but
+    /// [`Hash`] and [`Ord`] on the borrowed form *must* match those for
+    /// the key type.
+    ///
+    /// [`Ord`]: std::cmp::Ord
+    /// [`Hash`]: std::hash::Hash
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// let guard = map.guard();
+    /// assert_eq!(map.bucket_index(&1, &guard), None);
+    ///
+    /// map.insert(1, "a", &guard);
+    /// let stripes = map.bucket_count(&guard);
+    /// let stripe = map.bucket_index(&1, &guard).unwrap();
+    /// // keep inserting, which grows the table
+    /// for i in 2..1000 {
+    ///     map.insert(i, "b", &guard);
+    /// }
+    /// assert_eq!(map.bucket_index(&1, &guard).unwrap() % stripes, stripe);
+    /// ```
+    pub fn bucket_index<Q>(&self, key: &Q, guard: &Guard) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
+    {
+        self.check_guard(guard);
+        let table = self.table.load(Ordering::SeqCst, guard);
+        if table.is_null() {
+            return None;
+        }
+        // safety: we loaded `table` under the `guard`, so it must still be valid here
+        let table = unsafe { table.deref() };
+        if table.is_empty() {
+            return None;
+        }
+        Some(table.bini(self.hash(key)))
+    }
+
+    /// Calls `f` with the value corresponding to the key, and returns its result.
+    ///
+    /// Returns `None` if the map contains no mapping for `key`.
+    ///
+    /// This pins the current thread only for as long as `f` runs, so unlike with
+    /// [`get`](HashMap::get), no guard needs to be managed, and the value does not need to be
+    /// cloned to be used after the lookup. `f` should be short, since the pin holds up the
+    /// reclamation of garbage across the map.
     ///
     /// The key may be any borrowed form of the map's key type, but
     /// [`Hash`] and [`Ord`] on the borrowed form *must* match those for
@@ -1402,11 +3635,91 @@ where
     ///
     /// [`Ord`]: std::cmp::Ord
     /// [`Hash`]: std::hash::Hash
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// map.pin().insert(1, String::from("hello"));
+    /// assert_eq!(map.view(&1, |s| s.len()), Some(5));
+    /// assert_eq!(map.view(&2, |s| s.len()), None);
+    /// ```
+    pub fn view<Q, F, R>(&self, key: &Q, f: F) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
+        F: FnOnce(&V) -> R,
+    {
+        let guard = self.guard();
+        self.get(key, &guard).map(f)
+    }
+
+    /// Returns a clone of the value corresponding to the key.
+    ///
+    /// Returns `None` if the map contains no mapping for `key`.
+    ///
+    /// This pins the current thread only for the lookup, so unlike with [`get`](HashMap::get), no
+    /// guard needs to be managed, at the cost of cloning the value.
+    ///
+    /// The key may be any borrowed form of the map's key type, or any other type that is
+    /// [`Comparable`] to it, but [`Hash`] and [`Ord`] on that type *must* match those for the
+    /// key type.
+    ///
+    /// [`Comparable`]: crate::Comparable
+    /// [`Ord`]: std::cmp::Ord
+    /// [`Hash`]: std::hash::Hash
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// map.pin().insert(1, String::from("hello"));
+    /// assert_eq!(map.get_cloned(&1), Some(String::from("hello")));
+    /// assert_eq!(map.get_cloned(&2), None);
+    /// ```
+    pub fn get_cloned<Q>(&self, key: &Q) -> Option<V>
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+        V: Clone,
+    {
+        let guard = self.guard();
+        self.get(key, &guard).cloned()
+    }
+
+    /// Returns the key-value pair corresponding to `key`.
+    ///
+    /// Returns `None` if this map contains no mapping for `key`.
+    ///
+    /// The key may be any borrowed form of the map's key type, or any other type that is
+    /// [`Comparable`] to it, but [`Hash`] and [`Ord`] on that type *must* match those for the
+    /// key type.
+    ///
+    /// This hands back the key that is stored in the map, which is useful when keys carry data
+    /// that `Hash` and `Ord` ignore:
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// let guard = map.guard();
+    /// map.insert(String::from("apples"), 3, &guard);
+    /// let (key, value) = map.get_key_value("apples", &guard).unwrap();
+    /// assert_eq!(key, &String::from("apples"));
+    /// assert_eq!(value, &3);
+    /// assert_eq!(map.get_key_value("pears", &guard), None);
+    /// ```
+    ///
+    /// [`Comparable`]: crate::Comparable
+    /// [`Ord`]: std::cmp::Ord
+    /// [`Hash`]: std::hash::Hash
     #[inline]
     pub fn get_key_value<'g, Q>(&'g self, key: &Q, guard: &'g Guard) -> Option<(&'g K, &'g V)>
     where
-        K: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<K>,
     {
         self.check_guard(guard);
         let node = self.get_node(key, guard)?;
@@ -1419,9 +3732,173 @@ where
         unsafe { v.as_ref() }.map(|v| (&node.key, v))
     }
 
-    pub(crate) fn guarded_eq(&self, other: &Self, our_guard: &Guard, their_guard: &Guard) -> bool
+    /// Returns a builder for looking up entries by hashes that the caller has already computed.
+    ///
+    /// See the [`raw_entry`](crate::raw_entry) module for details.
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    /// use std::hash::{BuildHasher, Hash, Hasher};
+    ///
+    /// let map = HashMap::new();
+    /// let guard = map.guard();
+    /// map.insert(37, "a", &guard);
+    ///
+    /// let mut hasher = map.hasher().build_hasher();
+    /// 37.hash(&mut hasher);
+    /// let hash = hasher.finish();
+    /// assert_eq!(
+    ///     map.raw_entry(&guard).from_key_hashed_nocheck(hash, &37),
+    ///     Some((&37, &"a"))
+    /// );
+    /// assert_eq!(map.raw_entry(&guard).from_hash(hash, |k| *k == 37), Some((&37, &"a")));
+    /// ```
+    pub fn raw_entry<'g>(&'g self, guard: &'g Guard) -> RawEntryBuilder<'g, K, V, S> {
+        self.check_guard(guard);
+        RawEntryBuilder { map: self, guard }
+    }
+
+    /// Returns a builder for inserting and removing entries by hashes that the caller has
+    /// already computed.
+    ///
+    /// See the [`raw_entry`](crate::raw_entry) module for details.
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    /// use std::hash::{BuildHasher, Hash, Hasher};
+    ///
+    /// let map = HashMap::new();
+    /// let guard = map.guard();
+    ///
+    /// let mut hasher = map.hasher().build_hasher();
+    /// 37.hash(&mut hasher);
+    /// let hash = hasher.finish();
+    /// assert_eq!(map.raw_entry_mut(&guard).insert_hashed_nocheck(hash, 37, "a"), None);
+    /// assert_eq!(map.get(&37, &guard), Some(&"a"));
+    /// assert_eq!(
+    ///     map.raw_entry_mut(&guard).remove_hashed_nocheck(hash, &37),
+    ///     Some((&37, &"a"))
+    /// );
+    /// assert!(map.is_empty());
+    /// ```
+    pub fn raw_entry_mut<'g>(&'g self, guard: &'g Guard) -> RawEntryBuilderMut<'g, K, V, S> {
+        self.check_guard(guard);
+        RawEntryBuilderMut { map: self, guard }
+    }
+
+    /// Returns a reference to the value corresponding to the key, along with the entry's version.
+    ///
+    /// An entry's version increases every time its value is inserted or replaced, so callers can
+    /// tell whether an entry was modified by comparing its versions rather than its values.
+    /// Versions are drawn from a counter that is shared by all entries of the map, so an entry
+    /// that is removed and inserted again also gets a higher version than it had before.
+    ///
+    /// The key may be any borrowed form of the map's key type, but
+    /// [`Hash`] and [`Ord`] on the borrowed form *must* match those for
+    /// the key type.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the map was not created with
+    /// [`with_entry_versions`](HashMap::with_entry_versions).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new().with_entry_versions();
+    /// let guard = map.guard();
+    /// map.insert(1, "a", &guard);
+    /// let (_, seen) = map.get_versioned(&1, &guard).unwrap();
+    ///
+    /// map.insert(1, "a", &guard);
+    /// let (value, now) = map.get_versioned(&1, &guard).unwrap();
+    /// assert_eq!(value, &"a");
+    /// // the value is the same, but it was written again
+    /// assert!(now > seen);
+    /// ```
+    ///
+    /// [`Ord`]: std::cmp::Ord
+    /// [`Hash`]: std::hash::Hash
+    pub fn get_versioned<'g, Q>(&'g self, key: &Q, guard: &'g Guard) -> Option<(&'g V, u64)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
+    {
+        self.assert_entry_versions();
+        self.check_guard(guard);
+        let node = self.get_node(key, guard)?;
+
+        let (v, version) = node.load_versioned(guard);
+        assert!(!v.is_null());
+        // safety: the lifetime of the reference is bound to the guard
+        // supplied which means that the memory will not be modified
+        // until at least after the guard goes out of scope
+        unsafe { v.as_ref() }.map(|v| (v, version))
+    }
+
+    /// Returns a [`Watch`] that resolves the next time `key` is inserted, has its value replaced,
+    /// or is removed.
+    ///
+    /// This is a cheaper alternative to repeatedly calling [`get`](HashMap::get) while waiting for
+    /// another thread to change an entry. See the [`watch`](crate::watch) module for details.
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// let watch = map.watch(1);
+    /// assert!(!watch.is_changed());
+    /// map.pin().insert(1, "a");
+    /// assert!(watch.is_changed());
+    /// ```
+    pub fn watch(&self, key: K) -> Watch {
+        self.observers.watch(key)
+    }
+
+    /// Returns a reference to the value corresponding to the key, provided that the map has not
+    /// changed since `stamp` was taken.
+    ///
+    /// If the map has changed, this returns [`StaleStampError`], and the caller should discard
+    /// everything it read with `stamp` and start over with a new one. See
+    /// [`read_stamp`](HashMap::read_stamp) for details.
+    ///
+    /// The key may be any borrowed form of the map's key type, but
+    /// [`Hash`] and [`Ord`] on the borrowed form *must* match those for
+    /// the key type.
+    ///
+    /// [`Ord`]: std::cmp::Ord
+    /// [`Hash`]: std::hash::Hash
+    pub fn get_validated<'g, Q>(
+        &'g self,
+        key: &Q,
+        stamp: Stamp,
+        guard: &'g Guard,
+    ) -> Result<Option<&'g V>, StaleStampError>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
+    {
+        let value = self.get(key, guard);
+        // the value must be read first: a write that changed it has started by the time we read
+        // it, and so is seen by the validation below
+        if self.stamps.validate(stamp) {
+            Ok(value)
+        } else {
+            Err(StaleStampError)
+        }
+    }
+
+    pub(crate) fn guarded_eq<S2>(
+        &self,
+        other: &HashMap<K, V, S2>,
+        our_guard: &Guard,
+        their_guard: &Guard,
+    ) -> bool
     where
         V: PartialEq,
+        S2: BuildHasher,
     {
         if self.len() != other.len() {
             return false;
@@ -1439,7 +3916,7 @@ where
 
 impl<K, V, S> HashMap<K, V, S>
 where
-    K: Clone + Ord,
+    K: Clone + TreeKey,
 {
     /// Clears the map, removing all key-value pairs.
     ///
@@ -1448,33 +3925,102 @@ where
     /// ```
     /// use flurry::HashMap;
     ///
-    /// let map = HashMap::new();
-    ///
-    /// map.pin().insert(1, "a");
-    /// map.pin().clear();
-    /// assert!(map.pin().is_empty());
+    /// let map = HashMap::new();
+    ///
+    /// map.pin().insert(1, "a");
+    /// map.pin().clear();
+    /// assert!(map.pin().is_empty());
+    /// ```
+    pub fn clear(&self, guard: &Guard) {
+        self.clear_or_yield(ResumeToken::default(), || false, guard);
+    }
+
+    /// Clears the map like [`HashMap::clear`], starting where the call that returned `from` left
+    /// off, and stops early if `should_yield` returns `true`.
+    ///
+    /// `should_yield` is called before each non-empty bin is cleared, except for the first one, so
+    /// that every call makes progress. If it returns `true`, the bins cleared so far stay empty,
+    /// the rest of the map is left as it is, and the returned token says where to continue.
+    /// Passing it to the next call picks up right there. Once the whole map has been visited,
+    /// the returned token [is finished](ResumeToken::is_finished). Start with
+    /// `ResumeToken::default()`.
+    ///
+    /// Bins are visited in the same order as by a [`Cursor`], so the table may resize between
+    /// calls. Entries that are inserted while the map is being cleared may or may not be removed.
+    ///
+    /// This lets a latency-sensitive thread clear a large map in several slices, for example by
+    /// giving each call a deadline.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::cursor::ResumeToken;
+    /// use flurry::HashMap;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let map: HashMap<_, _> = (0..1000).map(|i| (i, i)).collect();
+    /// let guard = map.guard();
+    /// let mut token = ResumeToken::default();
+    /// loop {
+    ///     let deadline = Instant::now() + Duration::from_micros(100);
+    ///     token = map.clear_or_yield(token, || Instant::now() > deadline, &guard);
+    ///     if token.is_finished() {
+    ///         break;
+    ///     }
+    ///     // do other work before clearing more of the map
+    /// }
+    /// assert!(map.is_empty());
     /// ```
-    pub fn clear(&self, guard: &Guard) {
+    pub fn clear_or_yield<F>(
+        &self,
+        from: ResumeToken,
+        mut should_yield: F,
+        guard: &Guard,
+    ) -> ResumeToken
+    where
+        F: FnMut() -> bool,
+    {
+        self.check_guard(guard);
+        #[cfg(feature = "shadow")]
+        let _shadow = self.enter_shadow();
+
         // Negative number of deletions
         let mut delta = 0;
-        let mut idx = 0usize;
+        let mut position = from.position();
+        let mut finished = from.is_finished();
+        let mut cleared = false;
 
         let mut table = self.table.load(Ordering::SeqCst, guard);
-        // Safety: self.table is a valid pointer because we checked it above.
-        while !table.is_null() && idx < unsafe { table.deref() }.len() {
+        while !finished {
+            // Safety: self.table is a valid pointer because we checked it is not null.
+            if table.is_null() || unsafe { table.deref() }.is_empty() {
+                finished = true;
+                break;
+            }
             let tab = unsafe { table.deref() };
+            let idx = (position & (tab.len() as u64 - 1)) as usize;
             let raw_node = tab.bin(idx, guard);
             if raw_node.is_null() {
-                idx += 1;
+                match crate::cursor::next_position(position, tab.len()) {
+                    Some(next) => position = next,
+                    None => finished = true,
+                }
                 continue;
             }
+            if cleared && should_yield() {
+                break;
+            }
             // Safety: node is a valid pointer because we checked
             // it in the above if stmt.
             match unsafe { raw_node.deref() } {
                 BinEntry::Moved => {
+                    // clear the same position in the new table. the bins that this bin moved
+                    // into are all visited after it, no matter how the table was resized.
                     table = self.help_transfer(table, guard);
-                    // start from the first bin again in the new table
-                    idx = 0;
+                }
+                BinEntry::Reserved(ref reservation) => {
+                    // wait for the reserved entry to be inserted, and then clear it too
+                    drop(reservation.lock.lock());
                 }
                 BinEntry::Node(ref node) => {
                     let head_lock = node.lock.lock();
@@ -1491,7 +4037,9 @@ where
                     // threads and threads waiting on the lock will read the new bin, so we can
                     // drop the lock early and do the counting and garbage collection outside the
                     // critical section.
+                    let _write = self.stamps.write();
                     tab.store_bin(idx, Shared::null());
+                    self.notify_bin_cleared(raw_node, guard);
                     drop(head_lock);
                     // next, walk the nodes of the bin and free the nodes and their values as we go
                     // note that we do not free the head node yet, since we're holding the lock it contains
@@ -1512,10 +4060,10 @@ where
                             // into it above. it must also have pinned the epoch before that time. therefore, the
                             // defer_destroy below won't be executed until that thread's guard is dropped, at which
                             // point it holds no outstanding references to the value anyway.
-                            unsafe { guard.defer_destroy(value) };
+                            unsafe { self.retire_value(guard, value) };
                             // free the bin entry itself
                             // safety: same argument as for value above.
-                            unsafe { guard.defer_destroy(p) };
+                            unsafe { self.garbage.retire(guard, p) };
                             next
                         };
                     }
@@ -1523,10 +4071,14 @@ where
                     let value = node.value.load(Ordering::SeqCst, guard);
                     // NOTE: do not use the reference in `node` after this point!
                     // safety: same as the argument for being allowed to free the nodes beyond the head above
-                    unsafe { guard.defer_destroy(value) };
-                    unsafe { guard.defer_destroy(raw_node) };
+                    unsafe { self.retire_value(guard, value) };
+                    unsafe { self.garbage.retire(guard, raw_node) };
                     delta -= 1;
-                    idx += 1;
+                    cleared = true;
+                    match crate::cursor::next_position(position, tab.len()) {
+                        Some(next) => position = next,
+                        None => finished = true,
+                    }
                 }
                 BinEntry::Tree(ref tree_bin) => {
                     let bin_lock = tree_bin.lock.lock();
@@ -1543,7 +4095,9 @@ where
                     // threads and threads waiting on the lock will read the new bin, so we can
                     // drop the lock early and do the counting and garbage collection outside the
                     // critical section.
+                    let _write = self.stamps.write();
                     tab.store_bin(idx, Shared::null());
+                    self.notify_bin_cleared(raw_node, guard);
                     drop(bin_lock);
                     // next, walk the nodes of the bin and count how many values we remove
                     let mut p = tree_bin.first.load(Ordering::SeqCst, guard);
@@ -1557,16 +4111,31 @@ where
                             // guard.
                             // Structurally, TreeNodes always point to TreeNodes, so this is sound.
                             let tree_node = unsafe { TreeNode::get_tree_node(p) };
-                            // NOTE: we do not drop the TreeNodes or their
-                            // values here, since they will be dropped together
-                            // with the containing TreeBin (`tree_bin`) in its
-                            // `drop`
+                            // NOTE: we do not drop the TreeNodes here, since they will be dropped
+                            // together with the containing TreeBin (`tree_bin`) in its `drop`.
+                            // the same goes for their values, unless they have to be passed to
+                            // the reclaim hook.
+                            if self.reclaim_hook.is_some() {
+                                let value = tree_node.node.value.load(Ordering::SeqCst, guard);
+                                // safety: same as in the BinEntry::Node case above
+                                unsafe { self.retire_value(guard, value) };
+                            }
                             tree_node.node.next.load(Ordering::SeqCst, guard)
                         };
                     }
                     // safety: same as in the BinEntry::Node case above
-                    unsafe { guard.defer_destroy(raw_node) };
-                    idx += 1;
+                    if self.reclaim_hook.is_some() {
+                        unsafe {
+                            TreeBin::defer_drop_without_values(raw_node, &self.garbage, guard)
+                        };
+                    } else {
+                        unsafe { self.garbage.retire(guard, raw_node) };
+                    }
+                    cleared = true;
+                    match crate::cursor::next_position(position, tab.len()) {
+                        Some(next) => position = next,
+                        None => finished = true,
+                    }
                 }
                 BinEntry::TreeNode(_) => unreachable!(
                     "The head of a bin cannot be a TreeNode directly without BinEntry::Tree"
@@ -1577,6 +4146,39 @@ where
         if delta != 0 {
             self.add_count(delta, None, guard);
         }
+        if finished {
+            ResumeToken::FINISHED
+        } else {
+            ResumeToken::at(position)
+        }
+    }
+
+    /// Notifies observers of the removal of every entry in `bin`, which must just have been
+    /// unlinked from the map by `clear` while holding its lock.
+    fn notify_bin_cleared<'g>(&'g self, bin: Shared<'g, BinEntry<K, V>>, guard: &'g Guard) {
+        if !self.observers.is_active() {
+            return;
+        }
+
+        // safety: the bin was read under our guard, and is only marked as garbage by `clear`
+        // after this call. the same goes for all the nodes (and their values) in the bin.
+        let mut p = match unsafe { bin.deref() } {
+            BinEntry::Node(_) => bin,
+            BinEntry::Tree(ref tree_bin) => tree_bin.first.load(Ordering::SeqCst, guard),
+            _ => unreachable!("only heads of non-empty bins are cleared"),
+        };
+        while !p.is_null() {
+            let node = match unsafe { p.deref() } {
+                BinEntry::Node(ref node) => node,
+                BinEntry::TreeNode(ref tree_node) => &tree_node.node,
+                _ => unreachable!("bins only contain Nodes or TreeNodes"),
+            };
+            self.observers.notify(Change::Removed {
+                key: &node.key,
+                old: unsafe { node.value.load(Ordering::SeqCst, guard).deref() },
+            });
+            p = node.next.load(Ordering::SeqCst, guard);
+        }
     }
 }
 
@@ -1587,7 +4189,7 @@ where
 
 impl<K, V, S> HashMap<K, V, S>
 where
-    K: 'static + Sync + Send + Clone + Hash + Ord,
+    K: 'static + Sync + Send + Clone + Hash + TreeKey,
     V: 'static + Sync + Send,
     S: BuildHasher,
 {
@@ -1623,13 +4225,74 @@ where
         self.put(key, value, false, guard).before()
     }
 
+    /// Inserts a key-value pair into the map, and returns the value it replaced, if any.
+    ///
+    /// Since the map is borrowed mutably, no other thread can be holding a reference to the
+    /// replaced value, so this does not need a guard, and the value is moved out of the map
+    /// rather than dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// assert_eq!(map.insert_mut(37, String::from("a")), None);
+    /// assert_eq!(map.insert_mut(37, String::from("b")), Some(String::from("a")));
+    /// assert_eq!(map.pin().get(&37).map(String::as_str), Some("b"));
+    /// ```
+    pub fn insert_mut(&mut self, key: K, value: V) -> Option<V> {
+        // safety: we have &mut self, and all references we have returned are bound to the
+        // lifetime of a borrow of self, so no other thread can be accessing the map. that also
+        // means that garbage can be dropped right away.
+        let guard = unsafe { epoch::unprotected() };
+        let hash = self.hash(&key);
+        if self.find_node_hashed(hash, &key, guard).is_none() {
+            self.put_hashed(hash, key, value, false, guard);
+            return None;
+        }
+        let (_, old) = self.swap_node_if(hash, &key, Some(value), None, None, guard)?;
+        // safety: the old value is no longer in the map, and no-one else can be holding a
+        // reference to it (see above)
+        Some(*unsafe { old.into_owned() }.into_box())
+    }
+
+    /// Inserts a key-value pair into the map, and returns a reference to the value that is now in
+    /// the map for `key`.
+    ///
+    /// Like [`insert`](HashMap::insert), this replaces the current value if the key is present.
+    /// Rather than the replaced value, it returns the inserted one, so that code that goes on to
+    /// use the value right away does not have to look it up again.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// let guard = map.guard();
+    /// let log = map.insert_and_get("log", Vec::with_capacity(16), &guard);
+    /// assert!(log.capacity() >= 16);
+    ///
+    /// assert_eq!(map.insert_and_get("log", vec![1], &guard), &vec![1]);
+    /// assert_eq!(map.get("log", &guard), Some(&vec![1]));
+    /// ```
+    pub fn insert_and_get<'g>(&'g self, key: K, value: V, guard: &'g Guard) -> &'g V {
+        self.check_guard(guard);
+        self.put(key, value, false, guard)
+            .after()
+            .expect("put always inserts or replaces unless no_replacement is set")
+    }
+
     /// Inserts a key-value pair into the map unless the key already exists.
     ///
     /// If the map does not contain the key, the key-value pair is inserted
     /// and this method returns `Ok`.
     ///
     /// If the map does contain the key, the map is left unchanged and this
-    /// method returns `Err`.
+    /// method returns `Err`. The [`TryInsertError`] holds a reference to the
+    /// current value, and hands `value` back to the caller, so that it is not
+    /// lost even if it is expensive to build or cannot be cloned.
     ///
     /// [std-collections documentation]: https://doc.rust-lang.org/std/collections/index.html#insert-and-complex-keys
     ///
@@ -1656,30 +4319,453 @@ where
         key: K,
         value: V,
         guard: &'g Guard,
-    ) -> Result<&'g V, TryInsertError<'g, V>> {
-        match self.put(key, value, true, guard) {
-            PutResult::Exists {
-                current,
-                not_inserted,
-            } => Err(TryInsertError {
-                current,
-                not_inserted: *not_inserted,
-            }),
-            PutResult::Inserted { new } => Ok(new),
-            PutResult::Replaced { .. } => {
-                unreachable!("no_replacement cannot result in PutResult::Replaced")
-            }
+    ) -> Result<&'g V, TryInsertError<'g, V>> {
+        match self.put(key, value, true, guard) {
+            PutResult::Exists {
+                current,
+                not_inserted,
+            } => Err(TryInsertError {
+                current,
+                not_inserted: *not_inserted,
+            }),
+            PutResult::Inserted { new } => Ok(new),
+            PutResult::Replaced { .. } => {
+                unreachable!("no_replacement cannot result in PutResult::Replaced")
+            }
+        }
+    }
+
+    /// Returns a reference to the value for `key`, first inserting `V::default()` if the key is
+    /// not in the map.
+    ///
+    /// The default value is only constructed if the key is missing. If several threads insert the
+    /// same missing key at the same time, all of them get a reference to the value that ends up in
+    /// the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// let guard = map.guard();
+    /// assert_eq!(map.get_or_default("tags", &guard), &Vec::<&str>::new());
+    ///
+    /// map.insert("tags", vec!["a"], &guard);
+    /// assert_eq!(map.get_or_default("tags", &guard), &vec!["a"]);
+    /// ```
+    pub fn get_or_default<'g>(&'g self, key: K, guard: &'g Guard) -> &'g V
+    where
+        V: Default,
+    {
+        self.check_guard(guard);
+        if let Some(value) = self.get(&key, guard) {
+            return value;
+        }
+        self.put_if_absent(key, V::default(), guard)
+    }
+
+    /// Returns a reference to the value for `key`, first inserting the value produced by `f` if
+    /// the key is not in the map, or returns the error produced by `f`.
+    ///
+    /// `f` is only called if the key is missing, and it is called without holding any of the
+    /// map's locks. If it fails, nothing is inserted, and the map is left exactly as it was, which
+    /// makes this a good fit for filling a map from fallible sources such as I/O. If several
+    /// threads insert the same missing key at the same time, `f` may be called by each of them,
+    /// but all of them get a reference to the single value that ends up in the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// let guard = map.guard();
+    ///
+    /// let parsed = map.get_or_try_insert_with("a", || "12".parse::<u32>(), &guard);
+    /// assert_eq!(parsed, Ok(&12));
+    ///
+    /// let failed = map.get_or_try_insert_with("b", || "x".parse::<u32>(), &guard);
+    /// assert!(failed.is_err());
+    /// assert!(!map.contains_key("b", &guard));
+    /// ```
+    pub fn get_or_try_insert_with<'g, F, E>(
+        &'g self,
+        key: K,
+        f: F,
+        guard: &'g Guard,
+    ) -> Result<&'g V, E>
+    where
+        F: FnOnce() -> Result<V, E>,
+    {
+        self.check_guard(guard);
+        if let Some(value) = self.get(&key, guard) {
+            return Ok(value);
+        }
+        let value = f()?;
+        Ok(self.put_if_absent(key, value, guard))
+    }
+
+    /// Returns a reference to the value for `key`, first inserting the value produced by `f` if
+    /// the key is not in the map.
+    ///
+    /// Unlike with [`HashMap::get_or_try_insert_with`], `f` is called while the key's bin is
+    /// locked, or reserved if the bin is empty. If several threads look up the same missing key at
+    /// the same time, only one of them calls `f`, and the others wait for it and then get a
+    /// reference to the value it produced. This makes it a good fit for values that are expensive
+    /// to construct, or that must only be constructed once.
+    ///
+    /// Other writes to the same bin have to wait for `f` as well, so it should be as quick as
+    /// possible, and it must not access the map. If `f` panics, nothing is inserted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// let guard = map.guard();
+    ///
+    /// let mut loads = 0;
+    /// for _ in 0..3 {
+    ///     let value = map.get_or_insert_with("config", || {
+    ///         loads += 1;
+    ///         String::from("loaded")
+    ///     }, &guard);
+    ///     assert_eq!(value, "loaded");
+    /// }
+    /// assert_eq!(loads, 1);
+    /// ```
+    pub fn get_or_insert_with<'g, F>(&'g self, key: K, f: F, guard: &'g Guard) -> &'g V
+    where
+        F: FnOnce() -> V,
+    {
+        self.check_guard(guard);
+        if let Some(value) = self.get(&key, guard) {
+            return value;
+        }
+        match self.put_with(key, |_, _| Some(f()), guard) {
+            PutWith::Inserted(value) | PutWith::Present { current: value, .. } => value,
+            PutWith::Declined => unreachable!("the closure always produces a value"),
+        }
+    }
+
+    /// Inserts the value produced by `f` for `key`, unless the key is already present.
+    ///
+    /// `f` is called with the key and `None` while the key's bin is locked (or reserved, if the
+    /// bin is empty), and may decline to produce a value. If the key is present, `f` is not
+    /// called, and is handed back together with the key.
+    fn put_with<'g, F>(&'g self, key: K, f: F, guard: &'g Guard) -> PutWith<'g, K, V, F>
+    where
+        F: FnOnce(&K, Option<&V>) -> Option<V>,
+    {
+        #[cfg(feature = "shadow")]
+        let _shadow = self.enter_shadow();
+
+        let hash = self.hash(&key);
+        let mut table = self.table.load(Ordering::SeqCst, guard);
+        let (value, bin_count) = loop {
+            // safety: see put_until
+            if table.is_null() || unsafe { table.deref() }.is_empty() {
+                table = self.init_table(guard);
+                continue;
+            }
+
+            // safety: table is a valid pointer, for the same reasons as in put_until
+            let t = unsafe { table.deref() };
+
+            let bini = t.bini(hash);
+            let bin = t.bin(bini, guard);
+            if bin.is_null() {
+                // reserve the bin, so that no other thread can insert into it while we call `f`.
+                // the reservation is locked before it becomes visible, and only unlocked once it
+                // has been replaced with the new entry (or removed again, if `f` does not produce
+                // one).
+                let reservation = Owned::new(BinEntry::Reserved(Reservation::new()));
+                std::mem::forget(reservation.as_reservation().unwrap().lock.lock());
+                let reservation = match t.cas_bin(bini, bin, reservation, guard) {
+                    Ok(reservation) => ReservedBin {
+                        table: t,
+                        bini,
+                        reservation,
+                        garbage: &self.garbage,
+                        guard,
+                    },
+                    Err(changed) => {
+                        // safety: we acquired the lock above and forgot its guard, and the
+                        // reservation never became visible to other threads
+                        unsafe { changed.new.as_reservation().unwrap().lock.force_unlock() };
+                        continue;
+                    }
+                };
+
+                let value = match f(&key, None) {
+                    Some(value) => Owned::new(value).into_shared(guard),
+                    None => return PutWith::Declined,
+                };
+                let _write = self.stamps.write();
+                let node = Node::new(hash, key, value).versioned(self.versions.next());
+                let node = Owned::new(BinEntry::Node(node));
+                // as in put_until, observers must learn of the insert before any other thread can
+                // change the new node
+                let observing = self.observers.is_active();
+                if observing {
+                    std::mem::forget(node.as_node().unwrap().lock.lock());
+                }
+                let node = node.into_shared(guard);
+                t.store_bin(bini, node);
+                if observing {
+                    // safety: we just inserted node into the map while holding the guard, so it
+                    // cannot be dropped until the guard is dropped.
+                    let n = unsafe { node.deref() }.as_node().unwrap();
+                    self.observers.notify(Change::Inserted {
+                        key: &n.key,
+                        // safety: as for the returned reference below
+                        value: unsafe { value.deref() },
+                    });
+                    // safety: we acquired the lock above and forgot its guard
+                    unsafe { n.lock.force_unlock() };
+                }
+                drop(reservation);
+                break (value, 0);
+            }
+
+            // safety: bin is a valid pointer, for the same reasons as in put_until
+            match *unsafe { bin.deref() } {
+                BinEntry::Moved => {
+                    table = self.help_transfer(table, guard);
+                    continue;
+                }
+                BinEntry::Reserved(ref reservation) => {
+                    // the reservation stays locked until it has been replaced, so once we get the
+                    // lock, the bin has changed and we can try again
+                    drop(reservation.lock.lock());
+                    continue;
+                }
+                BinEntry::Node(ref head) => {
+                    let head_lock = head.lock.lock();
+
+                    // need to check that this is _still_ the head
+                    if t.bin(bini, guard) != bin {
+                        continue;
+                    }
+
+                    let mut bin_count = 1;
+                    let mut p = bin;
+                    loop {
+                        // safety: we read the bin while pinning the epoch, and it is still in
+                        // the table, so it cannot be dropped until after we drop our guard.
+                        let n = unsafe { p.deref() }.as_node().unwrap();
+                        if n.hash == hash && n.key == key {
+                            // safety: the value is reachable under our guard
+                            let current = unsafe { n.value.load(Ordering::SeqCst, guard).deref() };
+                            return PutWith::Present { current, key, f };
+                        }
+
+                        let next = n.next.load(Ordering::SeqCst, guard);
+                        if next.is_null() {
+                            break;
+                        }
+                        p = next;
+                        bin_count += 1;
+                    }
+
+                    // the key is missing, and we hold the bin's lock until it is inserted
+                    let value = match f(&key, None) {
+                        Some(value) => Owned::new(value).into_shared(guard),
+                        None => return PutWith::Declined,
+                    };
+                    let _write = self.stamps.write();
+                    let node = Node::new(hash, key, value).versioned(self.versions.next());
+                    let node = Owned::new(BinEntry::Node(node)).into_shared(guard);
+                    // safety: p is the last node of the bin, which we have locked
+                    unsafe { p.deref() }
+                        .as_node()
+                        .unwrap()
+                        .next
+                        .store(node, Ordering::SeqCst);
+                    if self.observers.is_active() {
+                        self.observers.notify(Change::Inserted {
+                            // safety: we just inserted node while holding the guard
+                            key: &unsafe { node.deref() }.as_node().unwrap().key,
+                            // safety: as for the returned reference below
+                            value: unsafe { value.deref() },
+                        });
+                    }
+                    drop(head_lock);
+                    break (value, bin_count);
+                }
+                BinEntry::Tree(ref tree_bin) => {
+                    let bin_lock = tree_bin.lock.lock();
+
+                    // need to check that this is _still_ the correct bin
+                    if t.bin(bini, guard) != bin {
+                        continue;
+                    }
+
+                    let p = TreeBin::find(bin, hash, &key, guard);
+                    if !p.is_null() {
+                        // safety: the TreeBin was read under our guard, at which point the tree
+                        // structure was valid, and TreeNodes always point to TreeNodes.
+                        let tree_node = unsafe { TreeNode::get_tree_node(p) };
+                        // safety: the value is reachable under our guard
+                        let current =
+                            unsafe { tree_node.node.value.load(Ordering::SeqCst, guard).deref() };
+                        return PutWith::Present { current, key, f };
+                    }
+
+                    // the key is missing, and we hold the bin's lock until it is inserted
+                    let value = match f(&key, None) {
+                        Some(value) => Owned::new(value).into_shared(guard),
+                        None => return PutWith::Declined,
+                    };
+                    let _write = self.stamps.write();
+                    let version = self.versions.next();
+                    let p = tree_bin.find_or_put_tree_val(hash, key, value, version, guard);
+                    debug_assert!(p.is_null(), "the key is not in the bin");
+                    if self.observers.is_active() {
+                        // new tree nodes are always inserted at the front of the bin's list,
+                        // and we still hold the bin's lock.
+                        // safety: as for the tree node above
+                        let tree_node = unsafe {
+                            TreeNode::get_tree_node(tree_bin.first.load(Ordering::SeqCst, guard))
+                        };
+                        self.observers.notify(Change::Inserted {
+                            key: &tree_node.node.key,
+                            // safety: as for the returned reference below
+                            value: unsafe { value.deref() },
+                        });
+                    }
+                    drop(bin_lock);
+                    // we don't actually count tree bins, just set this low enough that we don't
+                    // try to treeify the bin below
+                    break (value, 2);
+                }
+                BinEntry::TreeNode(_) => unreachable!(
+                    "The head of a bin cannot be a TreeNode directly without BinEntry::Tree"
+                ),
+            }
+        };
+
+        if bin_count >= TREEIFY_THRESHOLD {
+            // safety: we read the table under our guard, and the bin we inserted into was in it
+            let t = unsafe { table.deref() };
+            self.treeify_bin(t, t.bini(hash), guard);
+        }
+        self.add_count(1, Some(bin_count), guard);
+        self.garbage.flush(guard);
+        // safety: the value was inserted into the map while we held the guard, so it cannot be
+        // dropped until after the guard is.
+        PutWith::Inserted(unsafe { value.deref() })
+    }
+
+    /// Inserts `value` unless `key` is already present, and returns the value that is in the map.
+    fn put_if_absent<'g>(&'g self, key: K, value: V, guard: &'g Guard) -> &'g V {
+        match self.put(key, value, true, guard) {
+            PutResult::Exists { current, .. } => current,
+            PutResult::Inserted { new } => new,
+            PutResult::Replaced { .. } => {
+                unreachable!("no_replacement cannot result in PutResult::Replaced")
+            }
+        }
+    }
+
+    /// Inserts a key-value pair into the map, unless the lock on the key's bin cannot be acquired
+    /// within `timeout`.
+    ///
+    /// This behaves like [`HashMap::insert`], except that it gives up if another thread holds on
+    /// to the bin for too long (for example, while it runs a slow closure passed to
+    /// [`HashMap::compute_if_present`] for a key in the same bin). In that case, the map is left
+    /// unchanged, and the key-value pair is returned in the `Err`.
+    ///
+    /// Only waiting for the bin's lock is bounded by `timeout`. If the insert succeeds, the map may
+    /// still briefly block afterwards, for example to help with an ongoing resize.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    /// use std::time::Duration;
+    ///
+    /// let map = HashMap::new();
+    /// let guard = map.guard();
+    /// let timeout = Duration::from_millis(10);
+    /// assert_eq!(map.try_insert_for(37, "a", timeout, &guard).unwrap(), None);
+    /// assert_eq!(map.try_insert_for(37, "b", timeout, &guard).unwrap(), Some(&"a"));
+    /// ```
+    pub fn try_insert_for<'g>(
+        &'g self,
+        key: K,
+        value: V,
+        timeout: Duration,
+        guard: &'g Guard,
+    ) -> Result<Option<&'g V>, LockTimeoutError<(K, V)>> {
+        self.check_guard(guard);
+        match self.put_until(
+            self.hash(&key),
+            key,
+            value,
+            false,
+            Some(Instant::now() + timeout),
+            guard,
+        ) {
+            Ok(result) => Ok(result.before()),
+            Err(not_applied) => Err(LockTimeoutError {
+                timeout,
+                not_applied,
+            }),
+        }
+    }
+}
+
+// the following methods do the actual work of introducing items into the map. they do not require
+// the keys and values to be `'static` themselves, since they are only called by the public methods
+// that do, and by the maps of a `scope`, which reclaims all of their garbage before it ends.
+impl<K, V, S> HashMap<K, V, S>
+where
+    K: Sync + Send + Clone + Hash + TreeKey,
+    V: Sync + Send,
+    S: BuildHasher,
+{
+    pub(crate) fn put<'g>(
+        &'g self,
+        key: K,
+        value: V,
+        no_replacement: bool,
+        guard: &'g Guard,
+    ) -> PutResult<'g, V> {
+        self.put_hashed(self.hash(&key), key, value, no_replacement, guard)
+    }
+
+    /// Like `put`, but with a hash for `key` that the caller has already computed.
+    pub(crate) fn put_hashed<'g>(
+        &'g self,
+        hash: u64,
+        key: K,
+        value: V,
+        no_replacement: bool,
+        guard: &'g Guard,
+    ) -> PutResult<'g, V> {
+        match self.put_until(hash, key, value, no_replacement, None, guard) {
+            Ok(result) => result,
+            Err(_) => unreachable!("put without a deadline cannot time out"),
         }
     }
 
-    fn put<'g>(
+    /// Like `put_hashed`, but gives up and returns the key and value if a bin lock cannot be
+    /// acquired before `deadline`.
+    fn put_until<'g>(
         &'g self,
+        hash: u64,
         mut key: K,
         value: V,
         no_replacement: bool,
+        deadline: Option<Instant>,
         guard: &'g Guard,
-    ) -> PutResult<'g, V> {
-        let hash = self.hash(&key);
+    ) -> Result<PutResult<'g, V>, (K, V)> {
+        #[cfg(feature = "shadow")]
+        let _shadow = self.enter_shadow();
+
         let mut table = self.table.load(Ordering::SeqCst, guard);
         let mut bin_count;
         let value = Owned::new(value).into_shared(guard);
@@ -1714,24 +4800,50 @@ where
             let mut bin = t.bin(bini, guard);
             if bin.is_null() {
                 // fast path -- bin is empty so stick us at the front
-                let node = Owned::new(BinEntry::Node(Node::new(hash, key, value)));
+                let _write = self.stamps.write();
+                let node = Node::new(hash, key, value).versioned(self.versions.next());
+                let node = Owned::new(BinEntry::Node(node));
+                // if there are observers, we take the new node's lock before it becomes visible,
+                // and only release it once they have been notified. that way, no other thread can
+                // change the bin (and notify observers about it) before they learn of our insert.
+                let observing = self.observers.is_active();
+                if observing {
+                    std::mem::forget(node.as_node().unwrap().lock.lock());
+                }
                 match t.cas_bin(bini, bin, node, guard) {
-                    Ok(_old_null_ptr) => {
+                    Ok(node) => {
+                        if observing {
+                            // safety: we just inserted node into the map while holding the guard,
+                            // so it cannot be dropped until the guard is dropped.
+                            let n = unsafe { node.deref() }.as_node().unwrap();
+                            self.observers.notify(Change::Inserted {
+                                key: &n.key,
+                                // safety: as for the returned reference below
+                                value: unsafe { value.deref() },
+                            });
+                            // safety: we acquired the lock above and forgot its guard
+                            unsafe { n.lock.force_unlock() };
+                        }
                         self.add_count(1, Some(0), guard);
-                        guard.flush();
+                        self.garbage.flush(guard);
                         // safety: we have not moved the node's value since we placed it into
                         // its `Atomic` in the very beginning of the method, so the ref is still
                         // valid. since the value is not currently marked as garbage, we know it
                         // will not collected until at least one epoch passes, and since `value`
                         // was produced under a guard the pins the current epoch, the returned
                         // reference will remain valid for the guard's lifetime.
-                        return PutResult::Inserted {
+                        return Ok(PutResult::Inserted {
                             new: unsafe { value.deref() },
-                        };
+                        });
                     }
                     Err(changed) => {
                         assert!(!changed.current.is_null());
                         bin = changed.current;
+                        if observing {
+                            // safety: we acquired the lock above and forgot its guard, and the
+                            // node never became visible to other threads
+                            unsafe { changed.new.as_node().unwrap().lock.force_unlock() };
+                        }
                         if let BinEntry::Node(node) = *changed.new.into_box() {
                             key = node.key;
                         } else {
@@ -1765,6 +4877,16 @@ where
                     table = self.help_transfer(table, guard);
                     continue;
                 }
+                BinEntry::Reserved(ref reservation) => {
+                    // the reservation stays locked until it has been replaced, so once we get the
+                    // lock, the bin has changed and we can try again
+                    if lock_bin(&reservation.lock, deadline).is_none() {
+                        // safety: we own value and did not share it
+                        let value = *unsafe { value.into_owned().into_box() };
+                        return Err((key, value));
+                    }
+                    continue;
+                }
                 BinEntry::Node(ref head)
                     if no_replacement && head.hash == hash && head.key == key =>
                 {
@@ -1775,14 +4897,21 @@ where
                     // epoch, which won't arrive until after we drop our guard.
                     // safety (for value): since we never inserted the value in the tree, `value`
                     // is the last remaining pointer to the initial value.
-                    return PutResult::Exists {
+                    return Ok(PutResult::Exists {
                         current: unsafe { v.deref() },
                         not_inserted: unsafe { value.into_owned().into_box() },
-                    };
+                    });
                 }
                 BinEntry::Node(ref head) => {
                     // bin is non-empty, need to link into it, so we must take the lock
-                    let head_lock = head.lock.lock();
+                    let head_lock = match lock_bin(&head.lock, deadline) {
+                        Some(lock) => lock,
+                        None => {
+                            // safety: we own value and did not share it
+                            let value = *unsafe { value.into_owned().into_box() };
+                            return Err((key, value));
+                        }
+                    };
 
                     // need to check that this is _still_ the head
                     let current_head = t.bin(bini, guard);
@@ -1791,6 +4920,8 @@ where
                         continue;
                     }
 
+                    let _write = self.stamps.write();
+
                     // yes, it is still the head, so we can now "own" the bin
                     // note that there can still be readers in the bin!
 
@@ -1819,14 +4950,22 @@ where
                                 // `no_replacement`, we don't use the new value, so we need to clean
                                 // it up and return it back to the caller
                                 // safety: we own value and did not share it
-                                return PutResult::Exists {
+                                return Ok(PutResult::Exists {
                                     current: current_value,
                                     not_inserted: unsafe { value.into_owned().into_box() },
-                                };
+                                });
                             } else {
                                 // update the value in the existing node
-                                let now_garbage = n.value.swap(value, Ordering::SeqCst, guard);
+                                let now_garbage = n.swap_value(value, self.versions.next(), guard);
                                 // NOTE: now_garbage == current_value
+                                if self.observers.is_active() {
+                                    self.observers.notify(Change::Replaced {
+                                        key: &n.key,
+                                        old: current_value,
+                                        // safety: as for the returned reference below
+                                        value: unsafe { value.deref() },
+                                    });
+                                }
 
                                 // safety: need to guarantee that now_garbage is no longer
                                 // reachable. more specifically, no thread that executes _after_
@@ -1847,7 +4986,7 @@ where
                                 //    no other ways to get to a value except through its Node's
                                 //    `value` field (which is what we swapped), so freeing
                                 //    now_garbage is fine.
-                                unsafe { guard.defer_destroy(now_garbage) };
+                                unsafe { self.retire_value(guard, now_garbage) };
                             }
                             break Some(current_value);
                         }
@@ -1856,8 +4995,17 @@ where
                         let next = n.next.load(Ordering::SeqCst, guard);
                         if next.is_null() {
                             // we're at the end of the bin -- stick the node here!
-                            let node = Owned::new(BinEntry::Node(Node::new(hash, key, value)));
+                            let node = Node::new(hash, key, value).versioned(self.versions.next());
+                            let node = Owned::new(BinEntry::Node(node)).into_shared(guard);
                             n.next.store(node, Ordering::SeqCst);
+                            if self.observers.is_active() {
+                                self.observers.notify(Change::Inserted {
+                                    // safety: we just inserted node while holding the guard
+                                    key: &unsafe { node.deref() }.as_node().unwrap().key,
+                                    // safety: as for the returned reference below
+                                    value: unsafe { value.deref() },
+                                });
+                            }
                             break None;
                         }
                         p = next;
@@ -1870,7 +5018,14 @@ where
                 // cannot occur as in the Java code, TreeBins have a special, indicator hash value
                 BinEntry::Tree(ref tree_bin) => {
                     // bin is non-empty, need to link into it, so we must take the lock
-                    let head_lock = tree_bin.lock.lock();
+                    let head_lock = match lock_bin(&tree_bin.lock, deadline) {
+                        Some(lock) => lock,
+                        None => {
+                            // safety: we own value and did not share it
+                            let value = *unsafe { value.into_owned().into_box() };
+                            return Err((key, value));
+                        }
+                    };
 
                     // need to check that this is _still_ the correct bin
                     let current_head = t.bin(bini, guard);
@@ -1879,17 +5034,35 @@ where
                         continue;
                     }
 
+                    let _write = self.stamps.write();
+
                     // yes, it is still the head, so we can now "own" the bin
                     // note that there can still be readers in the bin!
 
                     // we don't actually count bins, just set this low enough
                     // that we don't try to treeify the bin later
                     bin_count = 2;
-                    let p = tree_bin.find_or_put_tree_val(hash, key, value, guard);
+                    let version = self.versions.next();
+                    let p = tree_bin.find_or_put_tree_val(hash, key, value, version, guard);
                     if p.is_null() {
                         // no TreeNode was returned, so the key did not previously exist in the
                         // TreeBin. This means it was successfully put there by the call above
                         // and we are done.
+                        if self.observers.is_active() {
+                            // new tree nodes are always inserted at the front of the bin's list,
+                            // and we still hold the bin's lock.
+                            // safety: as for the tree node below
+                            let tree_node = unsafe {
+                                TreeNode::get_tree_node(
+                                    tree_bin.first.load(Ordering::SeqCst, guard),
+                                )
+                            };
+                            self.observers.notify(Change::Inserted {
+                                key: &tree_node.node.key,
+                                // safety: as for the returned reference below
+                                value: unsafe { value.deref() },
+                            });
+                        }
                         break;
                     }
                     // safety: the TreeBin was read under our guard, at
@@ -1909,14 +5082,21 @@ where
                             // `no_replacement`, we don't use the new value, so we need to clean
                             // it up and return it back to the caller
                             // safety: we own value and did not share it
-                            return PutResult::Exists {
+                            return Ok(PutResult::Exists {
                                 current: current_value,
                                 not_inserted: unsafe { value.into_owned().into_box() },
-                            };
+                            });
                         } else {
-                            let now_garbage =
-                                tree_node.node.value.swap(value, Ordering::SeqCst, guard);
+                            let now_garbage = tree_node.node.swap_value(value, version, guard);
                             // NOTE: now_garbage == current_value
+                            if self.observers.is_active() {
+                                self.observers.notify(Change::Replaced {
+                                    key: &tree_node.node.key,
+                                    old: current_value,
+                                    // safety: as for the returned reference below
+                                    value: unsafe { value.deref() },
+                                });
+                            }
 
                             // safety: need to guarantee that now_garbage is no longer
                             // reachable. more specifically, no thread that executes _after_
@@ -1937,7 +5117,7 @@ where
                             //    no other ways to get to a value except through its Node's
                             //    `value` field (which is what we swapped), so freeing
                             //    now_garbage is fine.
-                            unsafe { guard.defer_destroy(now_garbage) };
+                            unsafe { self.retire_value(guard, now_garbage) };
                         }
                         Some(current_value)
                     };
@@ -1956,7 +5136,7 @@ where
                 self.treeify_bin(t, bini, guard);
             }
             if let Some(old_val) = old_val {
-                return PutResult::Replaced {
+                return Ok(PutResult::Replaced {
                     old: old_val,
                     // safety: we have not moved the node's value since we placed it into its
                     // `Atomic` in the very beginning of the method, so the ref is still valid.
@@ -1965,15 +5145,15 @@ where
                     // under a guard the pins the current epoch, the returned reference will remain
                     // valid for the guard's lifetime.
                     new: unsafe { value.deref() },
-                };
+                });
             }
             break;
         }
         // increment count, since we only get here if we did not return an old (updated) value
         debug_assert!(old_val.is_none());
         self.add_count(1, Some(bin_count), guard);
-        guard.flush();
-        PutResult::Inserted {
+        self.garbage.flush(guard);
+        Ok(PutResult::Inserted {
             // safety: we have not moved the node's value since we placed it into its
             // `Atomic` in the very beginning of the method, so the ref is still valid.
             // since the value is not currently marked as garbage, we know it will not
@@ -1981,7 +5161,56 @@ where
             // under a guard the pins the current epoch, the returned reference will remain
             // valid for the guard's lifetime.
             new: unsafe { value.deref() },
+        })
+    }
+}
+
+impl<K, V, S> HashMap<K, V, S>
+where
+    K: 'static + Sync + Send + Clone + Hash + TreeKey,
+    V: 'static + Sync + Send,
+    S: BuildHasher,
+{
+    /// Inserts every key-value pair from `iter` into the map.
+    ///
+    /// This is meant for loading many entries at once. The table is grown up front to fit the
+    /// iterator's [`size_hint`](Iterator::size_hint), rather than doubling over and over as the
+    /// entries come in, and all pairs are inserted under `guard`. This is what the `Extend`
+    /// implementation does as well, except that it has to pin a guard of its own.
+    ///
+    /// As with [`HashMap::insert`], pairs for keys that are already in the map replace the
+    /// existing values, and if `iter` yields a key several times, the last value wins.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// let guard = map.guard();
+    /// map.insert_all((0..10_000).map(|i| (i, i * 2)), &guard);
+    /// assert_eq!(map.len(), 10_000);
+    /// assert_eq!(map.get(&1234, &guard), Some(&2468));
+    /// ```
+    pub fn insert_all<I>(&self, iter: I, guard: &Guard)
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        self.check_guard(guard);
+        let iter = iter.into_iter();
+        // from `hashbrown::HashMap::extend`:
+        // Keys may be already present or show multiple times in the iterator.
+        // Reserve the entire hint lower bound if the map is empty.
+        // Otherwise reserve half the hint (rounded up), so the map
+        // will only resize twice in the worst case.
+        let hint = iter.size_hint().0;
+        if self.is_empty() {
+            self.reserve(hint, guard);
+        } else {
+            self.reserve(hint - hint / 2, guard);
         }
+
+        self.put_all(iter, guard);
     }
 
     fn put_all<I: Iterator<Item = (K, V)>>(&self, iter: I, guard: &Guard) {
@@ -1990,6 +5219,498 @@ where
         }
     }
 
+    /// Creates a map that uses `hash_builder`, and fills it with `entries`, which have `len`
+    /// distinct keys.
+    pub(crate) fn from_distinct<I>(hash_builder: S, len: usize, entries: I) -> Self
+    where
+        I: Iterator<Item = (K, V)>,
+    {
+        let mut map = Self::with_hasher(hash_builder);
+        if len == 0 {
+            return map;
+        }
+        map.presize(len);
+
+        // safety: we are creating this map, so no other thread can access it yet
+        let guard = unsafe { epoch::unprotected() };
+        let entries = entries.map(|(key, value)| (map.hash(&key), key, value));
+        map.fill(entries, guard);
+        map
+    }
+
+    /// Fills in the bins of a new map with `entries`, whose hashes have already been computed.
+    ///
+    /// No other thread can access the map yet, so this writes to the bins directly, without
+    /// taking any locks. The map should be presized to hold all the entries. If a key occurs more
+    /// than once, the last value for it wins, as with insert.
+    fn fill<I>(&self, entries: I, guard: &Guard)
+    where
+        I: Iterator<Item = (u64, K, V)>,
+    {
+        // safety: the table was just created under our guard, and only we can replace it
+        let t = unsafe { self.init_table(guard).deref() };
+        let mut bin_lengths = vec![0; t.len()];
+        let mut count = 0;
+        for (hash, key, value) in entries {
+            let bini = t.bini(hash);
+            let value = Owned::new(value);
+
+            let head = t.bin(bini, guard);
+            let mut p = head;
+            while !p.is_null() {
+                // safety: the nodes of the map are only ever dropped by the map, which we are
+                // still building
+                let n = unsafe { p.deref() }.as_node().unwrap();
+                if n.hash == hash && n.key == key {
+                    break;
+                }
+                p = n.next.load(Ordering::SeqCst, guard);
+            }
+            if !p.is_null() {
+                // safety: as above
+                let n = unsafe { p.deref() }.as_node().unwrap();
+                let old = n.value.swap(value, Ordering::SeqCst, guard);
+                // safety: the old value was never visible to any other thread
+                drop(unsafe { old.into_owned() });
+                continue;
+            }
+
+            let node = Node::with_next(hash, key, value, Atomic::from(head));
+            t.store_bin(bini, Owned::new(BinEntry::Node(node)));
+            bin_lengths[bini] += 1;
+            count += 1;
+        }
+
+        self.add_count(count, Some(0), guard);
+        let t = Shared::from(t as *const _);
+        for (bini, &len) in bin_lengths.iter().enumerate() {
+            if len < TREEIFY_THRESHOLD {
+                continue;
+            }
+            // treeifying a bin of a small table resizes it instead, at which point there is
+            // nothing left to do
+            if self.table.load(Ordering::SeqCst, guard) != t {
+                break;
+            }
+            // safety: as above
+            self.treeify_bin(unsafe { t.deref() }, bini, guard);
+        }
+    }
+
+    /// Inserts every key-value pair received from `receiver` into the map, until all senders
+    /// have disconnected.
+    ///
+    /// This blocks while waiting for pairs to arrive. Whenever it wakes up, it takes all the pairs
+    /// that are queued in the channel at that point (up to a limit), reserves room for them, and
+    /// inserts them under a single guard. The guard is dropped before waiting for more pairs, so
+    /// an idle channel does not hold up the reclamation of garbage.
+    ///
+    /// Pairs for keys that are already in the map replace the existing values. Returns the number
+    /// of pairs received.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    /// use std::sync::mpsc;
+    /// use std::thread;
+    ///
+    /// let (tx, rx) = mpsc::channel();
+    /// let producers: Vec<_> = (0..4)
+    ///     .map(|p| {
+    ///         let tx = tx.clone();
+    ///         thread::spawn(move || {
+    ///             for i in 0..100 {
+    ///                 tx.send((p * 100 + i, i)).unwrap();
+    ///             }
+    ///         })
+    ///     })
+    ///     .collect();
+    /// drop(tx);
+    ///
+    /// let map = HashMap::new();
+    /// assert_eq!(map.extend_from_channel(&rx), 400);
+    /// assert_eq!(map.len(), 400);
+    /// # for p in producers { p.join().unwrap(); }
+    /// ```
+    pub fn extend_from_channel(&self, receiver: &mpsc::Receiver<(K, V)>) -> usize {
+        let mut batch = Vec::new();
+        let mut received = 0;
+        while let Ok(first) = receiver.recv() {
+            batch.push(first);
+            while batch.len() < CHANNEL_BATCH {
+                match receiver.try_recv() {
+                    Ok(item) => batch.push(item),
+                    Err(_) => break,
+                }
+            }
+
+            let guard = self.guard();
+            self.reserve(batch.len(), &guard);
+            received += batch.len();
+            for (key, value) in batch.drain(..) {
+                self.put(key, value, false, &guard);
+            }
+        }
+        received
+    }
+
+    /// Inserts every key-value pair from `iter` into the map, using `resolve` to pick the value
+    /// for keys that are already present.
+    ///
+    /// For each pair whose key is not in the map, the pair is inserted as-is. If the key is
+    /// already present, `resolve` is called with the key, the current value, and the new value,
+    /// and the value it returns replaces the current one. Like [`HashMap::compute_if_present`],
+    /// `resolve` runs while holding the lock on the key's bin, so it should be short and simple,
+    /// and no other write to the key can slip in between reading the current value and
+    /// replacing it.
+    ///
+    /// Room for the pairs is reserved once, up front, based on the iterator's size hint, in the
+    /// same way as [`Extend`] does. Each pair is still merged on its own, so other threads may
+    /// observe some of the pairs before others.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// let guard = map.guard();
+    /// map.insert("apples", 3, &guard);
+    ///
+    /// let changes = vec![("apples", 2), ("pears", 5), ("pears", 1)];
+    /// map.merge_from_iter(changes, |_, old, new| old + new, &guard);
+    ///
+    /// assert_eq!(map.get("apples", &guard), Some(&5));
+    /// assert_eq!(map.get("pears", &guard), Some(&6));
+    /// ```
+    pub fn merge_from_iter<I, F>(&self, iter: I, mut resolve: F, guard: &Guard)
+    where
+        I: IntoIterator<Item = (K, V)>,
+        F: FnMut(&K, &V, V) -> V,
+    {
+        self.check_guard(guard);
+        // see `Extend` for why we only reserve half the hint if the map is not empty
+        let iter = iter.into_iter();
+        let hint = iter.size_hint().0;
+        let reserve = if self.is_empty() {
+            hint
+        } else {
+            hint - hint / 2
+        };
+        self.reserve(reserve, guard);
+
+        for (key, mut value) in iter {
+            loop {
+                match self.put(key.clone(), value, true, guard) {
+                    PutResult::Inserted { .. } => break,
+                    PutResult::Exists { not_inserted, .. } => {
+                        let mut new = Some(*not_inserted);
+                        self.compute_if_present(
+                            &key,
+                            |key, old| Some(resolve(key, old, new.take().expect("called once"))),
+                            guard,
+                        );
+                        match new {
+                            // the key was removed before we got to resolve the conflict, so try
+                            // inserting the new value again
+                            Some(v) => value = v,
+                            None => break,
+                        }
+                    }
+                    PutResult::Replaced { .. } => {
+                        unreachable!("no_replacement cannot result in PutResult::Replaced")
+                    }
+                }
+            }
+        }
+    }
+
+    /// Applies all the insertions and removals in `batch` to the map.
+    ///
+    /// This is equivalent to performing the batch's operations one after the other, except that
+    /// the only operation that takes effect for a key is the last one in the batch. However, the
+    /// operations are grouped by the bin they fall into, and each linear bin is locked only once
+    /// to apply all of its operations, which makes applying a large batch considerably cheaper
+    /// than making each change individually. Bins that have been converted into trees, and bins
+    /// that are being moved by a concurrent resize, are updated one operation at a time.
+    ///
+    /// The batch is not applied atomically: other threads may observe some of its changes before
+    /// others.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::{HashMap, WriteBatch};
+    ///
+    /// let map = HashMap::new();
+    /// let guard = map.guard();
+    /// map.insert("a", 1, &guard);
+    ///
+    /// let mut batch = WriteBatch::new();
+    /// batch.insert("a", 2);
+    /// batch.insert("b", 3);
+    /// batch.remove("b");
+    /// batch.insert("c", 4);
+    /// map.apply_batch(batch, &guard);
+    ///
+    /// assert_eq!(map.get("a", &guard), Some(&2));
+    /// assert_eq!(map.get("b", &guard), None);
+    /// assert_eq!(map.get("c", &guard), Some(&4));
+    /// ```
+    pub fn apply_batch(&self, batch: WriteBatch<K, V>, guard: &Guard) {
+        self.check_guard(guard);
+        #[cfg(feature = "shadow")]
+        let _shadow = self.enter_shadow();
+
+        let mut ops: Vec<_> = batch
+            .ops
+            .into_iter()
+            .map(|(key, value)| (self.hash(&key), key, value))
+            .collect();
+        if ops.is_empty() {
+            return;
+        }
+
+        // only the last operation for each key takes effect. the sort is stable, so operations on
+        // the same key stay in the order in which they were added to the batch. keys may not be
+        // ordered, so equal keys are found among the (few) operations with the same hash.
+        ops.sort_by_key(|op| op.0);
+        let mut last: Vec<(u64, K, Option<V>)> = Vec::with_capacity(ops.len());
+        for op in ops {
+            let earlier = last
+                .iter()
+                .rev()
+                .take_while(|prev| prev.0 == op.0)
+                .position(|prev| prev.1 == op.1);
+            match earlier {
+                Some(i) => {
+                    let i = last.len() - 1 - i;
+                    last[i] = op;
+                }
+                None => last.push(op),
+            }
+        }
+
+        let mut table = self.table.load(Ordering::SeqCst, guard);
+        // safety: the table was read while the epoch was pinned, so it cannot have been dropped
+        if table.is_null() || unsafe { table.deref() }.is_empty() {
+            table = self.init_table(guard);
+        }
+        // safety: as above, or the table was just returned by init_table under our guard
+        let t = unsafe { table.deref() };
+        last.sort_by_key(|op| t.bini(op.0));
+
+        let mut ops = last.into_iter().peekable();
+        while let Some(first) = ops.next() {
+            let bini = t.bini(first.0);
+            let mut group = vec![first];
+            while let Some(op) = ops.next_if(|op| t.bini(op.0) == bini) {
+                group.push(op);
+            }
+            self.apply_bin_batch(table, bini, group, guard);
+        }
+        self.garbage.flush(guard);
+    }
+
+    /// Applies `group`, which holds at most one operation per key, all of which fall into bin
+    /// `bini` of `table`.
+    fn apply_bin_batch<'g>(
+        &'g self,
+        table: Shared<'g, Table<K, V>>,
+        bini: usize,
+        mut group: Vec<(u64, K, Option<V>)>,
+        guard: &'g Guard,
+    ) {
+        loop {
+            if self.table.load(Ordering::SeqCst, guard) != table {
+                // the map was resized since we grouped the operations, so the grouping is stale
+                return self.apply_each(group, guard);
+            }
+            // safety: table is the map's current table, which we read under our guard
+            let t = unsafe { table.deref() };
+            let bin = t.bin(bini, guard);
+            if bin.is_null() {
+                // removals from an empty bin are no-ops, and the first insertion creates the bin
+                // just like a regular insert would
+                match group.iter().position(|op| op.2.is_some()) {
+                    Some(i) => {
+                        let (_, key, value) = group.swap_remove(i);
+                        self.put(key, value.unwrap(), false, guard);
+                        continue;
+                    }
+                    None => return,
+                }
+            }
+
+            // safety: bin is a valid pointer, for the same reasons as in put
+            let head = match *unsafe { bin.deref() } {
+                BinEntry::Node(ref head) => head,
+                BinEntry::Moved | BinEntry::Tree(_) | BinEntry::Reserved(_) => {
+                    return self.apply_each(group, guard)
+                }
+                BinEntry::TreeNode(_) => unreachable!(
+                    "The head of a bin cannot be a TreeNode directly without BinEntry::Tree"
+                ),
+            };
+            let head_lock = head.lock.lock();
+
+            // need to check that this is _still_ the head
+            if t.bin(bini, guard) != bin {
+                continue;
+            }
+
+            let _write = self.stamps.write();
+
+            let mut added = 0;
+            let mut removals = Vec::new();
+            for (hash, key, value) in group {
+                let value = match value {
+                    Some(value) => Owned::new(value).into_shared(guard),
+                    None => {
+                        removals.push((hash, key));
+                        continue;
+                    }
+                };
+                let mut p = bin;
+                loop {
+                    // safety: p is either bin, or was read from the next pointer of a node in the
+                    // bin while we held both the guard and the bin's lock. see also put.
+                    let n = unsafe { p.deref() }.as_node().unwrap();
+                    if n.hash == hash && n.key == key {
+                        let now_garbage = n.swap_value(value, self.versions.next(), guard);
+                        if self.observers.is_active() {
+                            // safety: now_garbage was in the map until just now, and value was
+                            // just stored in a node that is still in the map
+                            self.observers.notify(Change::Replaced {
+                                key: &n.key,
+                                old: unsafe { now_garbage.deref() },
+                                value: unsafe { value.deref() },
+                            });
+                        }
+                        // safety: now_garbage is no longer reachable, see put
+                        unsafe { self.retire_value(guard, now_garbage) };
+                        break;
+                    }
+                    let next = n.next.load(Ordering::SeqCst, guard);
+                    if next.is_null() {
+                        let node = Node::new(hash, key, value).versioned(self.versions.next());
+                        let node = Owned::new(BinEntry::Node(node)).into_shared(guard);
+                        n.next.store(node, Ordering::SeqCst);
+                        if self.observers.is_active() {
+                            self.observers.notify(Change::Inserted {
+                                // safety: we just inserted node while holding the guard
+                                key: &unsafe { node.deref() }.as_node().unwrap().key,
+                                value: unsafe { value.deref() },
+                            });
+                        }
+                        added += 1;
+                        break;
+                    }
+                    p = next;
+                }
+            }
+
+            // the head is removed last, so that the bin stays locked by the same node throughout
+            let mut removed = 0;
+            let mut remove_head = false;
+            for (hash, key) in removals {
+                // safety: as above
+                let mut pred = unsafe { bin.deref() }.as_node().unwrap();
+                if pred.hash == hash && pred.key == key {
+                    remove_head = true;
+                    continue;
+                }
+                let mut e = pred.next.load(Ordering::SeqCst, guard);
+                while !e.is_null() {
+                    // safety: as above
+                    let n = unsafe { e.deref() }.as_node().unwrap();
+                    let next = n.next.load(Ordering::SeqCst, guard);
+                    if n.hash == hash && n.key == key {
+                        pred.next.store(next, Ordering::SeqCst);
+                        let ev = n.value.load(Ordering::SeqCst, guard);
+                        if self.observers.is_active() {
+                            self.observers.notify(Change::Removed {
+                                key: &n.key,
+                                // safety: ev was loaded under our guard
+                                old: unsafe { ev.deref() },
+                            });
+                        }
+                        // safety: the node and its value are no longer reachable, see
+                        // replace_node
+                        unsafe {
+                            self.garbage.retire(guard, e);
+                            self.retire_value(guard, ev);
+                        }
+                        removed += 1;
+                        break;
+                    }
+                    pred = n;
+                    e = next;
+                }
+            }
+            // count the remaining nodes before the head is removed, since the bin is no longer
+            // guarded by our lock once it has a new head
+            let mut bin_count = 0;
+            let mut p = bin;
+            while !p.is_null() {
+                bin_count += 1;
+                // safety: as above
+                p = unsafe { p.deref() }
+                    .as_node()
+                    .unwrap()
+                    .next
+                    .load(Ordering::SeqCst, guard);
+            }
+            if remove_head {
+                bin_count -= 1;
+                let n = head;
+                let ev = n.value.load(Ordering::SeqCst, guard);
+                t.store_bin(bini, n.next.load(Ordering::SeqCst, guard));
+                if self.observers.is_active() {
+                    self.observers.notify(Change::Removed {
+                        key: &n.key,
+                        // safety: ev was loaded under our guard
+                        old: unsafe { ev.deref() },
+                    });
+                }
+                // safety: as above
+                unsafe {
+                    self.garbage.retire(guard, bin);
+                    self.retire_value(guard, ev);
+                }
+                removed += 1;
+            }
+
+            drop(head_lock);
+
+            if removed != 0 {
+                self.add_count(-removed, None, guard);
+            }
+            if added != 0 {
+                self.add_count(added, Some(bin_count), guard);
+            }
+            if bin_count >= TREEIFY_THRESHOLD {
+                self.treeify_bin(t, bini, guard);
+            }
+            return;
+        }
+    }
+
+    /// Applies each operation in `ops` on its own.
+    fn apply_each(&self, ops: Vec<(u64, K, Option<V>)>, guard: &Guard) {
+        for (_, key, value) in ops {
+            match value {
+                Some(value) => {
+                    self.put(key, value, false, guard);
+                }
+                None => {
+                    self.replace_node(&key, None, None, guard);
+                }
+            }
+        }
+    }
+
     /// If the value for the specified `key` is present, attempts to
     /// compute a new mapping given the key and its current mapped value.
     ///
@@ -2011,6 +5732,25 @@ where
     ///
     /// [`Ord`]: std::cmp::Ord
     /// [`Hash`]: std::hash::Hash
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// let guard = map.guard();
+    /// map.insert("stock", 2, &guard);
+    ///
+    /// // take one item out of stock, and remove the entry once it runs out
+    /// let take = |_: &&str, n: &i32| if *n > 1 { Some(n - 1) } else { None };
+    /// assert_eq!(map.compute_if_present("stock", take, &guard), Some(&1));
+    /// assert_eq!(map.compute_if_present("stock", take, &guard), None);
+    /// assert!(!map.contains_key("stock", &guard));
+    ///
+    /// // absent keys are left alone
+    /// assert_eq!(map.compute_if_present("stock", take, &guard), None);
+    /// ```
     pub fn compute_if_present<'g, Q, F>(
         &'g self,
         key: &Q,
@@ -2019,10 +5759,234 @@ where
     ) -> Option<&'g V>
     where
         K: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + TreeKey,
+        F: FnOnce(&K, &V) -> Option<V>,
+    {
+        self.check_guard(guard);
+        let remap = |key: &K, value: &V| match remapping_function(key, value) {
+            Some(value) => Decision::Replace(value),
+            None => Decision::Remove,
+        };
+        match self.compute_if_present_until(key, remap, None, guard) {
+            Ok(new_value) => new_value,
+            Err(_) => unreachable!("compute_if_present without a deadline cannot time out"),
+        }
+    }
+
+    /// Attempts to compute a new mapping for `key` like [`HashMap::compute_if_present`], unless
+    /// the lock on the key's bin cannot be acquired within `timeout`.
+    ///
+    /// If the lock is not acquired in time, `remapping_function` is not called, the map is left
+    /// unchanged, and the function is returned in the `Err`, so that the caller may retry later.
+    ///
+    /// As with [`HashMap::try_insert_for`], only waiting for the bin's lock is bounded by
+    /// `timeout`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    /// use std::time::Duration;
+    ///
+    /// let map = HashMap::new();
+    /// let guard = map.guard();
+    /// map.insert("counter", 0, &guard);
+    /// let timeout = Duration::from_millis(10);
+    /// let new = map.try_compute_for("counter", |_, v| Some(v + 1), timeout, &guard);
+    /// assert_eq!(new.ok(), Some(Some(&1)));
+    /// ```
+    pub fn try_compute_for<'g, Q, F>(
+        &'g self,
+        key: &Q,
+        remapping_function: F,
+        timeout: Duration,
+        guard: &'g Guard,
+    ) -> Result<Option<&'g V>, LockTimeoutError<F>>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
         F: FnOnce(&K, &V) -> Option<V>,
     {
         self.check_guard(guard);
+        let deadline = Some(Instant::now() + timeout);
+        // on a timeout, we need to hand back the caller's function rather than our wrapper
+        let mut remapping_function = Some(remapping_function);
+        let remap = |key: &K, value: &V| {
+            let remapping_function = remapping_function
+                .take()
+                .expect("the remapping function is only called once");
+            match remapping_function(key, value) {
+                Some(value) => Decision::Replace(value),
+                None => Decision::Remove,
+            }
+        };
+        match self.compute_if_present_until(key, remap, deadline, guard) {
+            Ok(new_value) => Ok(new_value),
+            Err(_) => Err(LockTimeoutError {
+                timeout,
+                not_applied: remapping_function
+                    .take()
+                    .expect("the remapping function was not called"),
+            }),
+        }
+    }
+
+    /// Computes a new mapping for `key` from its current value, if there is one.
+    ///
+    /// `remapping_function` is called with the key and its current value, or `None` if the key is
+    /// not in the map. If it returns `Some`, that value is inserted for `key`, replacing the
+    /// current one; if it returns `None`, the entry is removed, or the map is left unchanged if
+    /// there was no entry to begin with.
+    ///
+    /// The function is called exactly once, while the key's bin is locked (or reserved, if the bin
+    /// is empty), so no write from another thread can come between it reading the current value
+    /// and its result being stored. Other writes to the same bin have to wait for it, so it should
+    /// be short and simple, and it must not access the map.
+    ///
+    /// Returns the new value associated with `key`, or `None` if there is none.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// let guard = map.guard();
+    ///
+    /// // count words, and forget about the ones that have been taken back
+    /// let count = |_: &&str, n: Option<&i32>| Some(n.map_or(1, |n| n + 1));
+    /// let uncount = |_: &&str, n: Option<&i32>| n.filter(|&&n| n > 1).map(|n| n - 1);
+    /// assert_eq!(map.compute("flurry", count, &guard), Some(&1));
+    /// assert_eq!(map.compute("flurry", count, &guard), Some(&2));
+    /// assert_eq!(map.compute("flurry", uncount, &guard), Some(&1));
+    /// assert_eq!(map.compute("flurry", uncount, &guard), None);
+    /// assert!(!map.contains_key("flurry", &guard));
+    /// ```
+    pub fn compute<'g, F>(
+        &'g self,
+        key: K,
+        remapping_function: F,
+        guard: &'g Guard,
+    ) -> Option<&'g V>
+    where
+        F: FnOnce(&K, Option<&V>) -> Option<V>,
+    {
+        self.check_guard(guard);
+        let mut key = key;
+        let mut remapping_function = remapping_function;
+        loop {
+            // first try to update an existing entry, and keep hold of the function if there is none
+            let mut f = Some(remapping_function);
+            let remap = |key: &K, value: &V| {
+                let f = f
+                    .take()
+                    .expect("the remapping function is only called once");
+                match f(key, Some(value)) {
+                    Some(value) => Decision::Replace(value),
+                    None => Decision::Remove,
+                }
+            };
+            let new_value = match self.compute_if_present_until(&key, remap, None, guard) {
+                Ok(new_value) => new_value,
+                Err(_) => unreachable!("compute without a deadline cannot time out"),
+            };
+            remapping_function = match f {
+                Some(f) => f,
+                None => return new_value,
+            };
+
+            // the key was missing, so try to insert it instead
+            match self.put_with(key, remapping_function, guard) {
+                PutWith::Inserted(value) => return Some(value),
+                PutWith::Declined => return None,
+                PutWith::Present { key: k, f, .. } => {
+                    // another thread inserted the key in the meantime, so start over
+                    key = k;
+                    remapping_function = f;
+                }
+            }
+        }
+    }
+
+    /// Inserts `value` for `key`, or combines it with the current value if the key is already
+    /// present.
+    ///
+    /// If there is a current value, `merge_function` is called with it and `value`, and its result
+    /// replaces the current value. As with [`HashMap::compute`], this happens while the key's bin
+    /// is locked, so concurrent merges into the same key are never lost. This makes it the
+    /// natural building block for concurrent aggregations like counters and histograms.
+    ///
+    /// Returns the value that is now associated with `key`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// let guard = map.guard();
+    /// for word in "the cat saw the other cat".split(' ') {
+    ///     map.merge(word, 1, |old, new| old + new, &guard);
+    /// }
+    /// assert_eq!(map.get("the", &guard), Some(&2));
+    /// assert_eq!(map.get("saw", &guard), Some(&1));
+    /// assert_eq!(map.merge("cat", 3, |old, new| old + new, &guard), &5);
+    /// ```
+    pub fn merge<'g, F>(&'g self, key: K, value: V, merge_function: F, guard: &'g Guard) -> &'g V
+    where
+        F: FnOnce(&V, V) -> V,
+    {
+        let merge = move |_: &K, current: Option<&V>| match current {
+            Some(current) => Some(merge_function(current, value)),
+            None => Some(value),
+        };
+        self.compute(key, merge, guard)
+            .expect("merge always produces a value")
+    }
+
+    /// Adds `delta` to the value for `key`, or inserts `delta` if the key is not in the map, and
+    /// returns the new value.
+    ///
+    /// This is the counter case of [`HashMap::merge`]: the addition happens while the key's bin
+    /// is locked, so concurrent additions to the same key are never lost, and there is no retry
+    /// loop to write. Overflow behaves as it does for `+` on `V`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let requests = HashMap::<&str, u64>::new();
+    /// let guard = requests.guard();
+    /// requests.add("/index", 1, &guard);
+    /// requests.add("/index", 1, &guard);
+    /// assert_eq!(requests.add("/about", 1, &guard), &1);
+    /// assert_eq!(requests.get("/index", &guard), Some(&2));
+    /// ```
+    pub fn add<'g>(&'g self, key: K, delta: V, guard: &'g Guard) -> &'g V
+    where
+        V: Clone + Add<Output = V>,
+    {
+        self.merge(key, delta, |current, delta| current.clone() + delta, guard)
+    }
+
+    /// Like `compute_if_present`, but gives up and returns the remapping function if a bin lock
+    /// cannot be acquired before `deadline`.
+    pub(crate) fn compute_if_present_until<'g, Q, F>(
+        &'g self,
+        key: &Q,
+        remapping_function: F,
+        deadline: Option<Instant>,
+        guard: &'g Guard,
+    ) -> Result<Option<&'g V>, F>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
+        F: FnOnce(&K, &'g V) -> Decision<V>,
+    {
+        #[cfg(feature = "shadow")]
+        let _shadow = self.enter_shadow();
+
         let hash = self.hash(&key);
 
         let mut table = self.table.load(Ordering::SeqCst, guard);
@@ -2059,7 +6023,7 @@ where
             let bin = t.bin(bini, guard);
             if bin.is_null() {
                 // fast path -- bin is empty so key is not present
-                return None;
+                return Ok(None);
             }
 
             // slow path -- bin is non-empty
@@ -2085,9 +6049,15 @@ where
                     table = self.help_transfer(table, guard);
                     continue;
                 }
+                // the entry that reserved the bin has not been inserted yet, so the key is not
+                // present
+                BinEntry::Reserved(_) => return Ok(None),
                 BinEntry::Node(ref head) => {
                     // bin is non-empty, need to link into it, so we must take the lock
-                    let head_lock = head.lock.lock();
+                    let head_lock = match lock_bin(&head.lock, deadline) {
+                        Some(lock) => lock,
+                        None => return Err(remapping_function),
+                    };
 
                     // need to check that this is _still_ the head
                     let current_head = t.bin(bini, guard);
@@ -2096,6 +6066,8 @@ where
                         continue;
                     }
 
+                    let _write = self.stamps.write();
+
                     // yes, it is still the head, so we can now "own" the bin
                     // note that there can still be readers in the bin!
 
@@ -2119,77 +6091,99 @@ where
                             // safety: since the value is present now, and we've held a guard from
                             // the beginning of the search, the value cannot be dropped until the
                             // next epoch, which won't arrive until after we drop our guard.
-                            let new_value =
+                            let decision =
                                 remapping_function(&n.key, unsafe { current_value.deref() });
 
-                            if let Some(value) = new_value {
-                                let value = Owned::new(value).into_shared(guard);
-                                let now_garbage = n.value.swap(value, Ordering::SeqCst, guard);
-                                // NOTE: now_garbage == current_value
-
-                                // safety: need to guarantee that now_garbage is no longer
-                                // reachable. more specifically, no thread that executes _after_
-                                // this line can ever get a reference to now_garbage.
-                                //
-                                // here are the possible cases:
-                                //
-                                //  - another thread already has a reference to now_garbage.
-                                //    they must have read it before the call to swap.
-                                //    because of this, that thread must be pinned to an epoch <=
-                                //    the epoch of our guard. since the garbage is placed in our
-                                //    epoch, it won't be freed until the _next_ epoch, at which
-                                //    point, that thread must have dropped its guard, and with it,
-                                //    any reference to the value.
-                                //  - another thread is about to get a reference to this value.
-                                //    they execute _after_ the swap, and therefore do _not_ get a
-                                //    reference to now_garbage (they get value instead). there are
-                                //    no other ways to get to a value except through its Node's
-                                //    `value` field (which is what we swapped), so freeing
-                                //    now_garbage is fine.
-                                unsafe { guard.defer_destroy(now_garbage) };
+                            match decision {
+                                // safety: as above
+                                Decision::Keep => break Some(unsafe { current_value.deref() }),
+                                Decision::Replace(value) => {
+                                    let value = Owned::new(value).into_shared(guard);
+                                    let now_garbage =
+                                        n.swap_value(value, self.versions.next(), guard);
+                                    // NOTE: now_garbage == current_value
+                                    if self.observers.is_active() {
+                                        // safety: current_value and value are both protected by
+                                        // our guard, as argued for each of them above and below.
+                                        self.observers.notify(Change::Replaced {
+                                            key: &n.key,
+                                            old: unsafe { current_value.deref() },
+                                            value: unsafe { value.deref() },
+                                        });
+                                    }
 
-                                // safety: since the value is present now, and we've held a guard from
-                                // the beginning of the search, the value cannot be dropped until the
-                                // next epoch, which won't arrive until after we drop our guard.
-                                break Some(unsafe { value.deref() });
-                            } else {
-                                removed_node = true;
-                                // remove the BinEntry containing the removed key value pair from the bucket
-                                if !pred.is_null() {
-                                    // either by changing the pointer of the previous BinEntry, if present
-                                    // safety: see remove
-                                    unsafe { pred.deref() }
-                                        .as_node()
-                                        .unwrap()
-                                        .next
-                                        .store(next, Ordering::SeqCst);
-                                } else {
-                                    // or by setting the next node as the first BinEntry if there is no previous entry
-                                    t.store_bin(bini, next);
+                                    // safety: need to guarantee that now_garbage is no longer
+                                    // reachable. more specifically, no thread that executes _after_
+                                    // this line can ever get a reference to now_garbage.
+                                    //
+                                    // here are the possible cases:
+                                    //
+                                    //  - another thread already has a reference to now_garbage.
+                                    //    they must have read it before the call to swap.
+                                    //    because of this, that thread must be pinned to an epoch <=
+                                    //    the epoch of our guard. since the garbage is placed in our
+                                    //    epoch, it won't be freed until the _next_ epoch, at which
+                                    //    point, that thread must have dropped its guard, and with it,
+                                    //    any reference to the value.
+                                    //  - another thread is about to get a reference to this value.
+                                    //    they execute _after_ the swap, and therefore do _not_ get a
+                                    //    reference to now_garbage (they get value instead). there are
+                                    //    no other ways to get to a value except through its Node's
+                                    //    `value` field (which is what we swapped), so freeing
+                                    //    now_garbage is fine.
+                                    unsafe { self.retire_value(guard, now_garbage) };
+
+                                    // safety: since the value is present now, and we've held a guard from
+                                    // the beginning of the search, the value cannot be dropped until the
+                                    // next epoch, which won't arrive until after we drop our guard.
+                                    break Some(unsafe { value.deref() });
                                 }
+                                Decision::Remove => {
+                                    removed_node = true;
+                                    // remove the BinEntry containing the removed key value pair from the bucket
+                                    if !pred.is_null() {
+                                        // either by changing the pointer of the previous BinEntry, if present
+                                        // safety: see remove
+                                        unsafe { pred.deref() }
+                                            .as_node()
+                                            .unwrap()
+                                            .next
+                                            .store(next, Ordering::SeqCst);
+                                    } else {
+                                        // or by setting the next node as the first BinEntry if there is no previous entry
+                                        t.store_bin(bini, next);
+                                    }
+                                    if self.observers.is_active() {
+                                        self.observers.notify(Change::Removed {
+                                            key: &n.key,
+                                            // safety: as above
+                                            old: unsafe { current_value.deref() },
+                                        });
+                                    }
 
-                                // in either case, mark the BinEntry as garbage, since it was just removed
-                                // safety: need to guarantee that the old value is no longer
-                                // reachable. more specifically, no thread that executes _after_
-                                // this line can ever get a reference to val.
-                                //
-                                // here are the possible cases:
-                                //
-                                //  - another thread already has a reference to the old value.
-                                //    they must have read it before the call to store_bin.
-                                //    because of this, that thread must be pinned to an epoch <=
-                                //    the epoch of our guard. since the garbage is placed in our
-                                //    epoch, it won't be freed until the _next_ epoch, at which
-                                //    point, that thread must have dropped its guard, and with it,
-                                //    any reference to the value.
-                                //  - another thread is about to get a reference to this value.
-                                //    they execute _after_ the store_bin, and therefore do _not_ get a
-                                //    reference to the old value. there are no other ways to get to a
-                                //    value except through its Node's `value` field (which is now gone
-                                //    together with the node), so freeing the old value is fine.
-                                unsafe { guard.defer_destroy(p) };
-                                unsafe { guard.defer_destroy(current_value) };
-                                break None;
+                                    // in either case, mark the BinEntry as garbage, since it was just removed
+                                    // safety: need to guarantee that the old value is no longer
+                                    // reachable. more specifically, no thread that executes _after_
+                                    // this line can ever get a reference to val.
+                                    //
+                                    // here are the possible cases:
+                                    //
+                                    //  - another thread already has a reference to the old value.
+                                    //    they must have read it before the call to store_bin.
+                                    //    because of this, that thread must be pinned to an epoch <=
+                                    //    the epoch of our guard. since the garbage is placed in our
+                                    //    epoch, it won't be freed until the _next_ epoch, at which
+                                    //    point, that thread must have dropped its guard, and with it,
+                                    //    any reference to the value.
+                                    //  - another thread is about to get a reference to this value.
+                                    //    they execute _after_ the store_bin, and therefore do _not_ get a
+                                    //    reference to the old value. there are no other ways to get to a
+                                    //    value except through its Node's `value` field (which is now gone
+                                    //    together with the node), so freeing the old value is fine.
+                                    unsafe { self.garbage.retire(guard, p) };
+                                    unsafe { self.retire_value(guard, current_value) };
+                                    break None;
+                                }
                             }
                         }
 
@@ -2206,7 +6200,10 @@ where
                 }
                 BinEntry::Tree(ref tree_bin) => {
                     // bin is non-empty, need to link into it, so we must take the lock
-                    let bin_lock = tree_bin.lock.lock();
+                    let bin_lock = match lock_bin(&tree_bin.lock, deadline) {
+                        Some(lock) => lock,
+                        None => return Err(remapping_function),
+                    };
 
                     // need to check that this is _still_ the head
                     let current_head = t.bin(bini, guard);
@@ -2215,6 +6212,8 @@ where
                         continue;
                     }
 
+                    let _write = self.stamps.write();
+
                     // yes, it is still the head, so we can now "own" the bin
                     // note that there can still be readers in the bin!
                     bin_count = 2;
@@ -2247,73 +6246,102 @@ where
                             // safety: since the value is present now, and we've held a guard from
                             // the beginning of the search, the value cannot be dropped until the
                             // next epoch, which won't arrive until after we drop our guard.
-                            let new_value =
+                            let decision =
                                 remapping_function(&n.key, unsafe { current_value.deref() });
 
-                            if let Some(value) = new_value {
-                                let value = Owned::new(value).into_shared(guard);
-                                let now_garbage = n.value.swap(value, Ordering::SeqCst, guard);
-                                // NOTE: now_garbage == current_value
+                            match decision {
+                                // safety: as above
+                                Decision::Keep => Some(unsafe { current_value.deref() }),
+                                Decision::Replace(value) => {
+                                    let value = Owned::new(value).into_shared(guard);
+                                    let now_garbage =
+                                        n.swap_value(value, self.versions.next(), guard);
+                                    // NOTE: now_garbage == current_value
+                                    if self.observers.is_active() {
+                                        // safety: current_value and value are both protected by
+                                        // our guard, as argued for each of them above and below.
+                                        self.observers.notify(Change::Replaced {
+                                            key: &n.key,
+                                            old: unsafe { current_value.deref() },
+                                            value: unsafe { value.deref() },
+                                        });
+                                    }
 
-                                // safety: need to guarantee that now_garbage is no longer
-                                // reachable. more specifically, no thread that executes _after_
-                                // this line can ever get a reference to now_garbage.
-                                //
-                                // here are the possible cases:
-                                //
-                                //  - another thread already has a reference to now_garbage.
-                                //    they must have read it before the call to swap.
-                                //    because of this, that thread must be pinned to an epoch <=
-                                //    the epoch of our guard. since the garbage is placed in our
-                                //    epoch, it won't be freed until the _next_ epoch, at which
-                                //    point, that thread must have dropped its guard, and with it,
-                                //    any reference to the value.
-                                //  - another thread is about to get a reference to this value.
-                                //    they execute _after_ the swap, and therefore do _not_ get a
-                                //    reference to now_garbage (they get value instead). there are
-                                //    no other ways to get to a value except through its Node's
-                                //    `value` field (which is what we swapped), so freeing
-                                //    now_garbage is fine.
-                                unsafe { guard.defer_destroy(now_garbage) };
-                                // safety: since the value is present now, and we've held a guard from
-                                // the beginning of the search, the value cannot be dropped until the
-                                // next epoch, which won't arrive until after we drop our guard.
-                                Some(unsafe { value.deref() })
-                            } else {
-                                removed_node = true;
-                                // remove the BinEntry::TreeNode containing the removed key value pair from the bucket
-                                // also drop the old value stored in the tree node, as it was removed from the map
-                                // safety: `p` and its value are either marked for garbage collection in `remove_tree_node`
-                                // directly, or we will `need_to_untreeify`. In the latter case, we `defer_destroy`
-                                // both `p` and its value below, after storing the linear bin. Thus, everything is
-                                // always marked for garbage collection _after_ it becomes unaccessible by other threads.
-                                let need_to_untreeify =
-                                    unsafe { tree_bin.remove_tree_node(p, true, guard) };
-                                if need_to_untreeify {
-                                    let linear_bin = Self::untreeify(
-                                        tree_bin.first.load(Ordering::SeqCst, guard),
-                                        guard,
-                                    );
-                                    t.store_bin(bini, linear_bin);
-                                    // the old bin is now garbage, but its values are not,
-                                    // since they are re-used in the linear bin.
-                                    // safety: in the same way as for `now_garbage` above, any existing
-                                    // references to `bin` must have been obtained before storing the
-                                    // linear bin. These references were obtained while pinning an epoch
-                                    // <= our epoch and have to be dropped before the epoch can advance
-                                    // past the destruction of the old bin. After the store, threads will
-                                    // always see the linear bin, so the cannot obtain new references either.
+                                    // safety: need to guarantee that now_garbage is no longer
+                                    // reachable. more specifically, no thread that executes _after_
+                                    // this line can ever get a reference to now_garbage.
+                                    //
+                                    // here are the possible cases:
                                     //
-                                    // The same holds for `p` and its value, which does not get dropped together
-                                    // with `bin` here since `remove_tree_node` indicated that the bin needs to
-                                    // be untreeified.
-                                    unsafe {
-                                        TreeBin::defer_drop_without_values(bin, guard);
-                                        guard.defer_destroy(p);
-                                        guard.defer_destroy(current_value);
+                                    //  - another thread already has a reference to now_garbage.
+                                    //    they must have read it before the call to swap.
+                                    //    because of this, that thread must be pinned to an epoch <=
+                                    //    the epoch of our guard. since the garbage is placed in our
+                                    //    epoch, it won't be freed until the _next_ epoch, at which
+                                    //    point, that thread must have dropped its guard, and with it,
+                                    //    any reference to the value.
+                                    //  - another thread is about to get a reference to this value.
+                                    //    they execute _after_ the swap, and therefore do _not_ get a
+                                    //    reference to now_garbage (they get value instead). there are
+                                    //    no other ways to get to a value except through its Node's
+                                    //    `value` field (which is what we swapped), so freeing
+                                    //    now_garbage is fine.
+                                    unsafe { self.retire_value(guard, now_garbage) };
+                                    // safety: since the value is present now, and we've held a guard from
+                                    // the beginning of the search, the value cannot be dropped until the
+                                    // next epoch, which won't arrive until after we drop our guard.
+                                    Some(unsafe { value.deref() })
+                                }
+                                Decision::Remove => {
+                                    removed_node = true;
+                                    // remove the BinEntry::TreeNode containing the removed key value pair from the bucket
+                                    // also drop the old value stored in the tree node, as it was removed from the map
+                                    // safety: `p` and its value are either marked for garbage collection in `remove_tree_node`
+                                    // directly, or we will `need_to_untreeify`. In the latter case, we `defer_destroy`
+                                    // both `p` and its value below, after storing the linear bin. Thus, everything is
+                                    // always marked for garbage collection _after_ it becomes unaccessible by other threads.
+                                    if self.observers.is_active() {
+                                        self.observers.notify(Change::Removed {
+                                            key: &n.key,
+                                            // safety: as above
+                                            old: unsafe { current_value.deref() },
+                                        });
+                                    }
+                                    let need_to_untreeify = unsafe {
+                                        tree_bin.remove_tree_node(p, false, &self.garbage, guard)
+                                    };
+                                    if need_to_untreeify {
+                                        let linear_bin = Self::untreeify(
+                                            tree_bin.first.load(Ordering::SeqCst, guard),
+                                            guard,
+                                        );
+                                        t.store_bin(bini, linear_bin);
+                                        // the old bin is now garbage, but its values are not,
+                                        // since they are re-used in the linear bin.
+                                        // safety: in the same way as for `now_garbage` above, any existing
+                                        // references to `bin` must have been obtained before storing the
+                                        // linear bin. These references were obtained while pinning an epoch
+                                        // <= our epoch and have to be dropped before the epoch can advance
+                                        // past the destruction of the old bin. After the store, threads will
+                                        // always see the linear bin, so the cannot obtain new references either.
+                                        //
+                                        // The same holds for `p` and its value, which does not get dropped together
+                                        // with `bin` here since `remove_tree_node` indicated that the bin needs to
+                                        // be untreeified.
+                                        unsafe {
+                                            TreeBin::defer_drop_without_values(
+                                                bin,
+                                                &self.garbage,
+                                                guard,
+                                            );
+                                            self.garbage.retire(guard, p);
+                                        }
                                     }
+                                    // safety: the value is unreachable once `p` has been unlinked,
+                                    // or the linear bin has been stored, as argued above
+                                    unsafe { self.retire_value(guard, current_value) };
+                                    None
                                 }
-                                None
                             }
                         }
                     };
@@ -2323,30 +6351,342 @@ where
                     "The head of a bin cannot be a TreeNode directly without BinEntry::Tree"
                 ),
             }
-            // NOTE: the Java code checks `bin_count` here because they also
-            // reach this point if the bin changed while obtaining the lock.
-            // However, our code doesn't (it uses continue) and making this
-            // break conditional confuses the compiler about how many times we
-            // use the `remapping_function`.
-            debug_assert_ne!(bin_count, 0);
-            break;
+            // NOTE: the Java code checks `bin_count` here because they also
+            // reach this point if the bin changed while obtaining the lock.
+            // However, our code doesn't (it uses continue) and making this
+            // break conditional confuses the compiler about how many times we
+            // use the `remapping_function`.
+            debug_assert_ne!(bin_count, 0);
+            break;
+        }
+        if removed_node {
+            // decrement count
+            self.add_count(-1, Some(bin_count), guard);
+        }
+        self.garbage.flush(guard);
+        Ok(new_val)
+    }
+
+    /// Removes a key-value pair from the map, and returns the removed value (if any).
+    ///
+    /// The key may be any borrowed form of the map's key type, or any other type that is
+    /// [`Comparable`] to it, but [`Hash`] and [`Ord`] on that type *must* match those for the
+    /// key type.
+    ///
+    /// [`Comparable`]: crate::Comparable
+    /// [`Ord`]: std::cmp::Ord
+    /// [`Hash`]: std::hash::Hash
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// map.pin().insert(1, "a");
+    /// assert_eq!(map.pin().remove(&1), Some(&"a"));
+    /// assert_eq!(map.pin().remove(&1), None);
+    /// ```
+    pub fn remove<'g, Q>(&'g self, key: &Q, guard: &'g Guard) -> Option<&'g V>
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+    {
+        // NOTE: _technically_, this method shouldn't require the thread-safety bounds, but a) that
+        // would require special-casing replace_node for when new_value.is_none(), and b) it's sort
+        // of useless to call remove on a collection that you know you can never insert into.
+        self.check_guard(guard);
+        self.replace_node(key, None, None, guard).map(|(_, v)| v)
+    }
+
+    /// Removes a key from the map, and returns its value, if the key was in the map.
+    ///
+    /// Since the map is borrowed mutably, no other thread can be holding a reference to the
+    /// removed value, so this does not need a guard, and the value is moved out of the map
+    /// rather than dropped.
+    ///
+    /// The key may be any borrowed form of the map's key type, or any other type that is
+    /// [`Comparable`] to it, but [`Hash`] and [`Ord`] on that type *must* match those for the
+    /// key type.
+    ///
+    /// [`Comparable`]: crate::Comparable
+    /// [`Ord`]: std::cmp::Ord
+    /// [`Hash`]: std::hash::Hash
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// map.pin().insert(1, String::from("a"));
+    /// assert_eq!(map.remove_mut(&1), Some(String::from("a")));
+    /// assert_eq!(map.remove_mut(&1), None);
+    /// ```
+    pub fn remove_mut<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+    {
+        // safety: we have &mut self, and all references we have returned are bound to the
+        // lifetime of a borrow of self, so no other thread can be accessing the map. that also
+        // means that garbage can be dropped right away, including the removed node and its key.
+        let guard = unsafe { epoch::unprotected() };
+        let (_, old) = self.swap_node_if(self.hash(key), key, None, None, None, guard)?;
+        // safety: the old value is no longer in the map, and no-one else can be holding a
+        // reference to it (see above)
+        Some(*unsafe { old.into_owned() }.into_box())
+    }
+
+    /// Removes a key from the map, returning the stored key and value if the
+    /// key was previously in the map.
+    ///
+    /// The key may be any borrowed form of the map's key type, or any other type that is
+    /// [`Comparable`] to it, but [`Hash`] and [`Ord`] on that type *must* match those for the
+    /// key type.
+    ///
+    /// [`Comparable`]: crate::Comparable
+    /// [`Ord`]: std::cmp::Ord
+    /// [`Hash`]: std::hash::Hash
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// let guard = map.guard();
+    /// map.insert(1, "a", &guard);
+    /// assert_eq!(map.remove_entry(&1, &guard), Some((&1, &"a")));
+    /// assert_eq!(map.remove(&1, &guard), None);
+    /// ```
+    pub fn remove_entry<'g, Q>(&'g self, key: &Q, guard: &'g Guard) -> Option<(&'g K, &'g V)>
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+    {
+        self.check_guard(guard);
+        self.replace_node(key, None, None, guard)
+    }
+
+    /// Removes the entry for `key` if `predicate` returns `true` for its value, and returns the
+    /// removed value (if any).
+    ///
+    /// `predicate` is called while the key's bin is locked, so unlike a [`get`](HashMap::get)
+    /// followed by a [`remove`](HashMap::remove), this never removes a value that another thread
+    /// wrote after `predicate` looked at the old one. It is not called at all if there is no entry
+    /// for `key`.
+    ///
+    /// The key may be any borrowed form of the map's key type, but
+    /// [`Hash`] and [`Ord`] on the borrowed form *must* match those for
+    /// the key type.
+    ///
+    /// [`Ord`]: std::cmp::Ord
+    /// [`Hash`]: std::hash::Hash
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// let guard = map.guard();
+    /// map.insert("session", 3, &guard);
+    ///
+    /// // only expire the session if it has not been refreshed
+    /// assert_eq!(map.remove_if("session", |&v| v < 3, &guard), None);
+    /// assert_eq!(map.remove_if("session", |&v| v <= 3, &guard), Some(&3));
+    /// assert!(!map.contains_key("session", &guard));
+    /// ```
+    pub fn remove_if<'g, Q, F>(&'g self, key: &Q, predicate: F, guard: &'g Guard) -> Option<&'g V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
+        F: FnOnce(&V) -> bool,
+    {
+        self.check_guard(guard);
+        let mut removed = None;
+        let decide = |_: &K, value: &'g V| {
+            if predicate(value) {
+                removed = Some(value);
+                Decision::Remove
+            } else {
+                Decision::Keep
+            }
+        };
+        match self.compute_if_present_until(key, decide, None, guard) {
+            Ok(_) => removed,
+            Err(_) => unreachable!("remove_if without a deadline cannot time out"),
         }
-        if removed_node {
-            // decrement count
-            self.add_count(-1, Some(bin_count), guard);
+    }
+
+    /// Removes every key that is also in `other` from the map, and returns how many entries were
+    /// removed.
+    ///
+    /// This walks whichever of the two maps is smaller: if `other` is smaller, each of its keys is
+    /// removed from this map, and otherwise each key of this map is looked up in `other`. Only the
+    /// keys of `other` matter, so its values may be of any type, such as `()` for a set of
+    /// tombstones.
+    ///
+    /// `our_guard` must come from this map, and `their_guard` from `other`. Keys that are added to
+    /// `other` while this runs may or may not be removed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map: HashMap<_, _> = (0..10).map(|i| (i, i * 10)).collect();
+    /// let tombstones: HashMap<_, _> = vec![(2, ()), (3, ()), (42, ())].into_iter().collect();
+    ///
+    /// let removed = map.subtract_keys(&tombstones, &map.guard(), &tombstones.guard());
+    /// assert_eq!(removed, 2);
+    /// assert_eq!(map.len(), 8);
+    /// assert!(!map.pin().contains_key(&2));
+    /// ```
+    pub fn subtract_keys<V2, S2>(
+        &self,
+        other: &HashMap<K, V2, S2>,
+        our_guard: &Guard,
+        their_guard: &Guard,
+    ) -> usize
+    where
+        S2: BuildHasher,
+    {
+        self.check_guard(our_guard);
+        other.check_guard(their_guard);
+        if other.len() < self.len() {
+            other
+                .keys(their_guard)
+                .filter(|key| self.replace_node(*key, None, None, our_guard).is_some())
+                .count()
+        } else {
+            self.keys(our_guard)
+                .filter(|key| {
+                    other.contains_key(*key, their_guard)
+                        && self.replace_node(*key, None, None, our_guard).is_some()
+                })
+                .count()
         }
-        guard.flush();
-        new_val
     }
 
-    /// Removes a key-value pair from the map, and returns the removed value (if any).
+    /// Removes a key from the map if its entry is still at `version`, and returns the removed
+    /// value.
+    ///
+    /// Returns `None`, and leaves the map unchanged, if the key is not in the map, or if its entry
+    /// has been modified since it was at `version`. Versions are read with
+    /// [`get_versioned`](HashMap::get_versioned).
+    ///
+    /// The key may be any borrowed form of the map's key type, but
+    /// [`Hash`] and [`Ord`] on the borrowed form *must* match those for
+    /// the key type.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the map was not created with
+    /// [`with_entry_versions`](HashMap::with_entry_versions).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new().with_entry_versions();
+    /// let guard = map.guard();
+    /// map.insert(1, "a", &guard);
+    /// let (_, version) = map.get_versioned(&1, &guard).unwrap();
+    ///
+    /// // someone else modifies the entry in the meantime
+    /// map.insert(1, "b", &guard);
+    /// assert_eq!(map.remove_if_version(&1, version, &guard), None);
+    ///
+    /// let (_, version) = map.get_versioned(&1, &guard).unwrap();
+    /// assert_eq!(map.remove_if_version(&1, version, &guard), Some(&"b"));
+    /// ```
+    ///
+    /// [`Ord`]: std::cmp::Ord
+    /// [`Hash`]: std::hash::Hash
+    pub fn remove_if_version<'g, Q>(
+        &'g self,
+        key: &Q,
+        version: u64,
+        guard: &'g Guard,
+    ) -> Option<&'g V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
+    {
+        self.check_guard(guard);
+        self.assert_entry_versions();
+        self.replace_node_if(self.hash(key), key, None, None, Some(version), guard)
+            .map(|(_, v)| v)
+    }
+
+    /// Replaces the value for a key with `value` if its entry is still at `version`, and returns
+    /// the replaced value.
+    ///
+    /// Returns `None`, and leaves the map unchanged, if the key is not in the map, or if its entry
+    /// has been modified since it was at `version`. Versions are read with
+    /// [`get_versioned`](HashMap::get_versioned). If the value is replaced, the entry moves to a
+    /// new version.
     ///
     /// The key may be any borrowed form of the map's key type, but
     /// [`Hash`] and [`Ord`] on the borrowed form *must* match those for
     /// the key type.
     ///
+    /// # Panics
+    ///
+    /// Panics if the map was not created with
+    /// [`with_entry_versions`](HashMap::with_entry_versions).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new().with_entry_versions();
+    /// let guard = map.guard();
+    /// map.insert("hits", 0, &guard);
+    ///
+    /// // increment the counter without holding any lock while computing the new value
+    /// loop {
+    ///     let (&hits, version) = map.get_versioned("hits", &guard).unwrap();
+    ///     if map.replace_if_version("hits", hits + 1, version, &guard).is_some() {
+    ///         break;
+    ///     }
+    /// }
+    /// assert_eq!(map.get("hits", &guard), Some(&1));
+    /// ```
+    ///
     /// [`Ord`]: std::cmp::Ord
     /// [`Hash`]: std::hash::Hash
+    pub fn replace_if_version<'g, Q>(
+        &'g self,
+        key: &Q,
+        value: V,
+        version: u64,
+        guard: &'g Guard,
+    ) -> Option<&'g V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
+    {
+        self.check_guard(guard);
+        self.assert_entry_versions();
+        self.replace_node_if(self.hash(key), key, Some(value), None, Some(version), guard)
+            .map(|(_, v)| v)
+    }
+
+    /// Replaces the value for `key` with `new` if the current value is equal to `current`.
+    ///
+    /// The comparison happens while the key's bin is locked, so the value cannot change between it
+    /// and the replacement. A value is always considered equal to itself, so passing a reference
+    /// that was read from the map compares by identity first.
+    ///
+    /// Returns the replaced value if the exchange happened. Otherwise, the map is left unchanged,
+    /// and the [`CompareExchangeError`] holds the actual current value (or `None` if the key is not
+    /// in the map) and hands `new` back.
+    ///
+    /// The key may be any borrowed form of the map's key type, but
+    /// [`Hash`] and [`Ord`] on the borrowed form *must* match those for
+    /// the key type.
     ///
     /// # Examples
     ///
@@ -2354,31 +6694,141 @@ where
     /// use flurry::HashMap;
     ///
     /// let map = HashMap::new();
-    /// map.pin().insert(1, "a");
-    /// assert_eq!(map.pin().remove(&1), Some(&"a"));
-    /// assert_eq!(map.pin().remove(&1), None);
+    /// let guard = map.guard();
+    /// map.insert("state", "idle", &guard);
+    ///
+    /// assert_eq!(map.compare_exchange("state", &"idle", "busy", &guard), Ok(&"idle"));
+    /// let err = map.compare_exchange("state", &"idle", "busy", &guard).unwrap_err();
+    /// assert_eq!(err.current, Some(&"busy"));
+    /// assert_eq!(err.new, "busy");
     /// ```
-    pub fn remove<'g, Q>(&'g self, key: &Q, guard: &'g Guard) -> Option<&'g V>
+    ///
+    /// [`Ord`]: std::cmp::Ord
+    /// [`Hash`]: std::hash::Hash
+    pub fn compare_exchange<'g, Q>(
+        &'g self,
+        key: &Q,
+        current: &V,
+        new: V,
+        guard: &'g Guard,
+    ) -> Result<&'g V, CompareExchangeError<'g, V>>
     where
         K: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + TreeKey,
+        V: PartialEq,
     {
-        // NOTE: _technically_, this method shouldn't require the thread-safety bounds, but a) that
-        // would require special-casing replace_node for when new_value.is_none(), and b) it's sort
-        // of useless to call remove on a collection that you know you can never insert into.
         self.check_guard(guard);
-        self.replace_node(key, None, None, guard).map(|(_, v)| v)
+        let mut new = Some(new);
+        let mut found = None;
+        let decide = |_: &K, value: &'g V| {
+            let matches = std::ptr::eq(value, current) || value == current;
+            found = Some((value, matches));
+            if matches {
+                Decision::Replace(new.take().expect("the value is only stored once"))
+            } else {
+                Decision::Keep
+            }
+        };
+        if self
+            .compute_if_present_until(key, decide, None, guard)
+            .is_err()
+        {
+            unreachable!("compare_exchange without a deadline cannot time out");
+        }
+        match found {
+            Some((old, true)) => Ok(old),
+            Some((current, false)) => Err(CompareExchangeError {
+                current: Some(current),
+                new: new.expect("the value was not stored"),
+            }),
+            None => Err(CompareExchangeError {
+                current: None,
+                new: new.expect("the value was not stored"),
+            }),
+        }
     }
 
-    /// Removes a key from the map, returning the stored key and value if the
-    /// key was previously in the map.
+    /// Replaces the value for `key` with `new` if the current value is equal to `expected`, and
+    /// returns the value that won.
+    ///
+    /// This is [`compare_exchange`](HashMap::compare_exchange), except that it returns a reference
+    /// to `new` as it is now stored in the map if the exchange happened, rather than the value it
+    /// replaced. That is what an optimistic state machine keyed by ID usually needs: every thread
+    /// that tries the same transition learns which state the entry ended up in, whether it won or
+    /// lost the race.
+    ///
+    /// If the exchange did not happen, the map is left unchanged, and the
+    /// [`CompareExchangeError`] holds the current value (or `None` if the key is not in the map)
+    /// and hands `new` back.
     ///
     /// The key may be any borrowed form of the map's key type, but
     /// [`Hash`] and [`Ord`] on the borrowed form *must* match those for
     /// the key type.
     ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// let guard = map.guard();
+    /// map.insert(7, "pending", &guard);
+    ///
+    /// assert_eq!(map.replace_entry(&7, &"pending", "running", &guard), Ok(&"running"));
+    /// let lost = map.replace_entry(&7, &"pending", "failed", &guard).unwrap_err();
+    /// assert_eq!(lost.current, Some(&"running"));
+    /// assert_eq!(lost.new, "failed");
+    /// ```
+    ///
     /// [`Ord`]: std::cmp::Ord
     /// [`Hash`]: std::hash::Hash
+    pub fn replace_entry<'g, Q>(
+        &'g self,
+        key: &Q,
+        expected: &V,
+        new: V,
+        guard: &'g Guard,
+    ) -> Result<&'g V, CompareExchangeError<'g, V>>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
+        V: PartialEq,
+    {
+        self.check_guard(guard);
+        let mut new = Some(new);
+        let decide = |_: &K, value: &'g V| {
+            if std::ptr::eq(value, expected) || value == expected {
+                Decision::Replace(new.take().expect("the value is only stored once"))
+            } else {
+                Decision::Keep
+            }
+        };
+        let current = match self.compute_if_present_until(key, decide, None, guard) {
+            Ok(current) => current,
+            Err(_) => unreachable!("replace_entry without a deadline cannot time out"),
+        };
+        match new {
+            // the closure took the value, so the map now holds it
+            None => Ok(current.expect("the value was just stored")),
+            Some(new) => Err(CompareExchangeError { current, new }),
+        }
+    }
+
+    /// Replaces the value for `key` with the result of `f`, and returns the previous value.
+    ///
+    /// This mirrors `fetch_update` on the standard library's atomic integers: `f` is called with
+    /// the current value, and may return `None` to leave the entry unchanged. Unlike there, `f` is
+    /// called exactly once, while the key's bin is locked, so there is no retry loop and no
+    /// update from another thread can slip in between `f` reading the value and its result being
+    /// stored.
+    ///
+    /// Returns `Ok` with the previous value if the value was replaced, and `Err` with the current
+    /// value if `f` returned `None`, or `Err(None)` if the key is not in the map (in which case
+    /// `f` is not called).
+    ///
+    /// The key may be any borrowed form of the map's key type, but
+    /// [`Hash`] and [`Ord`] on the borrowed form *must* match those for
+    /// the key type.
     ///
     /// # Examples
     ///
@@ -2387,48 +6837,151 @@ where
     ///
     /// let map = HashMap::new();
     /// let guard = map.guard();
-    /// map.insert(1, "a", &guard);
-    /// assert_eq!(map.remove_entry(&1, &guard), Some((&1, &"a")));
-    /// assert_eq!(map.remove(&1, &guard), None);
+    /// map.insert("tickets", 2, &guard);
+    ///
+    /// // sell tickets until there are none left
+    /// let sell = |n: &u32| n.checked_sub(1);
+    /// assert_eq!(map.fetch_update("tickets", sell, &guard), Ok(&2));
+    /// assert_eq!(map.fetch_update("tickets", sell, &guard), Ok(&1));
+    /// assert_eq!(map.fetch_update("tickets", sell, &guard), Err(Some(&0)));
+    /// assert_eq!(map.fetch_update("refunds", sell, &guard), Err(None));
     /// ```
-    pub fn remove_entry<'g, Q>(&'g self, key: &Q, guard: &'g Guard) -> Option<(&'g K, &'g V)>
+    ///
+    /// [`Ord`]: std::cmp::Ord
+    /// [`Hash`]: std::hash::Hash
+    pub fn fetch_update<'g, Q, F>(
+        &'g self,
+        key: &Q,
+        f: F,
+        guard: &'g Guard,
+    ) -> Result<&'g V, Option<&'g V>>
     where
         K: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + TreeKey,
+        F: FnOnce(&V) -> Option<V>,
     {
         self.check_guard(guard);
-        self.replace_node(key, None, None, guard)
+        let mut previous = None;
+        let update = |_: &K, value: &'g V| match f(value) {
+            Some(new) => {
+                previous = Some(value);
+                Decision::Replace(new)
+            }
+            None => Decision::Keep,
+        };
+        match self.compute_if_present_until(key, update, None, guard) {
+            Ok(current) => previous.ok_or(current),
+            Err(_) => unreachable!("fetch_update without a deadline cannot time out"),
+        }
+    }
+}
+
+// see the comment on the impl block of `put`.
+impl<K, V, S> HashMap<K, V, S>
+where
+    K: Sync + Send + Clone + Hash + TreeKey,
+    V: Sync + Send,
+    S: BuildHasher,
+{
+    /// Replaces node value with `new_value`.
+    ///
+    /// If an `observed_value` is provided, the replacement only happens if `observed_value` equals
+    /// the value that is currently associated with the given key.
+    ///
+    /// If `new_value` is `None`, it removes the key (and its corresponding value) from this map.
+    ///
+    /// This method does nothing if the key is not in the map.
+    ///
+    /// Returns the previous key and value associated with the given key.
+    ///
+    /// The key may be any borrowed form of the map's key type, but
+    /// [`Hash`] and [`Ord`] on the borrowed form *must* match those for
+    /// the key type.
+    ///
+    /// [`Ord`]: std::cmp::Ord
+    /// [`Hash`]: std::hash::Hash
+    pub(crate) fn replace_node<'g, Q>(
+        &'g self,
+        key: &Q,
+        new_value: Option<V>,
+        observed_value: Option<Shared<'g, V>>,
+        guard: &'g Guard,
+    ) -> Option<(&'g K, &'g V)>
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+    {
+        self.replace_node_if(self.hash(key), key, new_value, observed_value, None, guard)
+    }
+
+    /// Like `replace_node`, but with a hash for `key` that the caller has already computed, and
+    /// if an `observed_version` is provided, the replacement also only happens if the entry for
+    /// the given key is still at that version.
+    pub(crate) fn replace_node_if<'g, Q>(
+        &'g self,
+        hash: u64,
+        key: &Q,
+        new_value: Option<V>,
+        observed_value: Option<Shared<'g, V>>,
+        observed_version: Option<u64>,
+        guard: &'g Guard,
+    ) -> Option<(&'g K, &'g V)>
+    where
+        Q: ?Sized + Comparable<K>,
+    {
+        let (key, val) = self.swap_node_if(
+            hash,
+            key,
+            new_value,
+            observed_value,
+            observed_version,
+            guard,
+        )?;
+
+        // safety: need to guarantee that the old value is no longer
+        // reachable. more specifically, no thread that executes _after_
+        // this line can ever get a reference to val.
+        //
+        // here are the possible cases:
+        //
+        //  - another thread already has a reference to the old value.
+        //    they must have read it before the call to store_bin.
+        //    because of this, that thread must be pinned to an epoch <=
+        //    the epoch of our guard. since the garbage is placed in our
+        //    epoch, it won't be freed until the _next_ epoch, at which
+        //    point, that thread must have dropped its guard, and with it,
+        //    any reference to the value.
+        //  - another thread is about to get a reference to this value.
+        //    they execute _after_ the store_bin, and therefore do _not_ get a
+        //    reference to the old value. there are no other ways to get to a
+        //    value except through its Node's `value` field (which is now gone
+        //    together with the node), so freeing the old value is fine.
+        unsafe { self.retire_value(guard, val) };
+
+        // safety: the lifetime of the reference is bound to the guard
+        // supplied which means that the memory will not be freed
+        // until at least after the guard goes out of scope
+        unsafe { val.as_ref() }.map(move |v| (key, v))
     }
 
-    /// Replaces node value with `new_value`.
-    ///
-    /// If an `observed_value` is provided, the replacement only happens if `observed_value` equals
-    /// the value that is currently associated with the given key.
-    ///
-    /// If `new_value` is `None`, it removes the key (and its corresponding value) from this map.
-    ///
-    /// This method does nothing if the key is not in the map.
+    /// Like `replace_node_if`, but returns the old value without retiring it, so that the caller
+    /// can take ownership of it if no other thread can be holding a reference to it.
     ///
-    /// Returns the previous key and value associated with the given key.
-    ///
-    /// The key may be any borrowed form of the map's key type, but
-    /// [`Hash`] and [`Ord`] on the borrowed form *must* match those for
-    /// the key type.
-    ///
-    /// [`Ord`]: std::cmp::Ord
-    /// [`Hash`]: std::hash::Hash
-    fn replace_node<'g, Q>(
+    /// If the node was removed, its key is retired along with it, so the returned key reference
+    /// is only valid if `guard` is not an unprotected one.
+    fn swap_node_if<'g, Q>(
         &'g self,
+        hash: u64,
         key: &Q,
         new_value: Option<V>,
         observed_value: Option<Shared<'g, V>>,
+        observed_version: Option<u64>,
         guard: &'g Guard,
-    ) -> Option<(&'g K, &'g V)>
+    ) -> Option<(&'g K, Shared<'g, V>)>
     where
-        K: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Comparable<K>,
     {
-        let hash = self.hash(key);
+        #[cfg(feature = "shadow")]
+        let _shadow = self.enter_shadow();
 
         let is_remove = new_value.is_none();
         let mut old_val = None;
@@ -2482,6 +7035,9 @@ where
                     table = self.help_transfer(table, guard);
                     continue;
                 }
+                // the entry that reserved the bin has not been inserted yet, so the key is not
+                // present
+                BinEntry::Reserved(_) => break,
                 BinEntry::Node(ref head) => {
                     let head_lock = head.lock.lock();
 
@@ -2490,8 +7046,11 @@ where
                         continue;
                     }
 
+                    let _write = self.stamps.write();
+
                     let mut e = bin;
                     let mut pred: Shared<'_, BinEntry<K, V>> = Shared::null();
+                    let mut removed = Shared::null();
                     loop {
                         // safety: either e is bin, in which case it is valid due to the above,
                         // or e was obtained from a next pointer. Any next pointer obtained from
@@ -2503,17 +7062,30 @@ where
                         // our guard, e is also valid if it was obtained from a next pointer.
                         let n = unsafe { e.deref() }.as_node().unwrap();
                         let next = n.next.load(Ordering::SeqCst, guard);
-                        if n.hash == hash && n.key.borrow() == key {
+                        if n.hash == hash && key.equivalent(&n.key) {
                             let ev = n.value.load(Ordering::SeqCst, guard);
 
-                            // only replace the node if the value is the one we expected at method call
-                            if observed_value.map(|ov| ov == ev).unwrap_or(true) {
+                            // only replace the node if the value (and version) is the one we
+                            // expected at method call
+                            if observed_value.map(|ov| ov == ev).unwrap_or(true)
+                                && n.has_version(observed_version)
+                            {
                                 // we remember the old value so that we can return it and mark it for deletion below
                                 old_val = Some((&n.key, ev));
 
                                 // found the node but we have a new value to replace the old one
                                 if let Some(nv) = new_value {
-                                    n.value.store(Owned::new(nv), Ordering::SeqCst);
+                                    let nv = Owned::new(nv).into_shared(guard);
+                                    n.swap_value(nv, self.versions.next(), guard);
+                                    if self.observers.is_active() {
+                                        // safety: ev was loaded under our guard, and nv was just
+                                        // stored in a node that is still in the map.
+                                        self.observers.notify(Change::Replaced {
+                                            key: &n.key,
+                                            old: unsafe { ev.deref() },
+                                            value: unsafe { nv.deref() },
+                                        });
+                                    }
                                     // we are just replacing entry value and we do not want to remove the node
                                     // so we stop iterating here
                                     break;
@@ -2531,10 +7103,14 @@ where
                                     // or by setting the next node as the first BinEntry if there is no previous entry
                                     t.store_bin(bini, next);
                                 }
-
-                                // in either case, mark the BinEntry as garbage, since it was just removed
-                                // safety: as for val below / in put
-                                unsafe { guard.defer_destroy(e) };
+                                if self.observers.is_active() {
+                                    self.observers.notify(Change::Removed {
+                                        key: &n.key,
+                                        // safety: ev was loaded under our guard
+                                        old: unsafe { ev.deref() },
+                                    });
+                                }
+                                removed = e;
                             }
                             // since the key was found and only one node exists per key, we can break here
                             break;
@@ -2547,6 +7123,12 @@ where
                         }
                     }
                     drop(head_lock);
+                    if !removed.is_null() {
+                        // in either case, mark the BinEntry as garbage, since it was just removed.
+                        // we only do so once we have released the lock, which may live in it.
+                        // safety: as in put
+                        unsafe { self.garbage.retire(guard, removed) };
+                    }
                 }
                 BinEntry::Tree(ref tree_bin) => {
                     let bin_lock = tree_bin.lock.lock();
@@ -2556,6 +7138,8 @@ where
                         continue;
                     }
 
+                    let _write = self.stamps.write();
+
                     let root = tree_bin.root.load(Ordering::SeqCst, guard);
                     if root.is_null() {
                         // we are in the correct bin for the given key's hash and the bin is not
@@ -2583,86 +7167,93 @@ where
                     // Structurally, TreeNodes always point to TreeNodes, so this is sound.
                     let n = &unsafe { TreeNode::get_tree_node(p) }.node;
                     let pv = n.value.load(Ordering::SeqCst, guard);
+                    let mut untreeified = None;
 
-                    // only replace the node if the value is the one we expected at method call
-                    if observed_value.map(|ov| ov == pv).unwrap_or(true) {
+                    // only replace the node if the value (and version) is the one we expected at
+                    // method call
+                    if observed_value.map(|ov| ov == pv).unwrap_or(true)
+                        && n.has_version(observed_version)
+                    {
                         // we remember the old value so that we can return it and mark it for deletion below
                         old_val = Some((&n.key, pv));
 
                         if let Some(nv) = new_value {
                             // found the node but we have a new value to replace the old one
-                            n.value.store(Owned::new(nv), Ordering::SeqCst);
+                            let nv = Owned::new(nv).into_shared(guard);
+                            n.swap_value(nv, self.versions.next(), guard);
+                            if self.observers.is_active() {
+                                // safety: pv was loaded under our guard, and nv was just stored in
+                                // a node that is still in the map.
+                                self.observers.notify(Change::Replaced {
+                                    key: &n.key,
+                                    old: unsafe { pv.deref() },
+                                    value: unsafe { nv.deref() },
+                                });
+                            }
                         } else {
-                            // drop `p` without its value, since the old value is dropped
-                            // in the check on `old_val` below
+                            if self.observers.is_active() {
+                                // safety: pv was loaded under our guard
+                                self.observers.notify(Change::Removed {
+                                    key: &n.key,
+                                    old: unsafe { pv.deref() },
+                                });
+                            }
+                            // drop `p` without its value, since the old value is returned to the
+                            // caller, which is responsible for dropping it
                             // safety: `p` is either marked for garbage collection in `remove_tree_node` directly,
                             // or we will `need_to_untreeify`. In the latter case, we `defer_destroy` `p` below,
-                            // after storing the linear bin. The value stored in `p` is `defer_destroy`ed from within
-                            // `old_val` at the end of the method. Thus, everything is always marked for garbage
+                            // after storing the linear bin. The value stored in `p` is `defer_destroy`ed by
+                            // `replace_node_if` once we return it. Thus, everything is always marked for garbage
                             // collection _after_ it becomes unaccessible by other threads.
-                            let need_to_untreeify =
-                                unsafe { tree_bin.remove_tree_node(p, false, guard) };
+                            let need_to_untreeify = unsafe {
+                                tree_bin.remove_tree_node(p, false, &self.garbage, guard)
+                            };
                             if need_to_untreeify {
                                 let linear_bin = Self::untreeify(
                                     tree_bin.first.load(Ordering::SeqCst, guard),
                                     guard,
                                 );
                                 t.store_bin(bini, linear_bin);
-                                // the old bin is now garbage, but its values are not,
-                                // since they get re-used in the linear bin
-                                // safety: same as in put
-                                unsafe {
-                                    TreeBin::defer_drop_without_values(bin, guard);
-                                    guard.defer_destroy(p);
-                                }
+                                untreeified = Some(p);
                             }
                         }
                     }
 
                     drop(bin_lock);
+                    if let Some(p) = untreeified {
+                        // the old bin is now garbage, but its values are not, since they get
+                        // re-used in the linear bin. we only retire it once we have released the
+                        // lock, which lives in it.
+                        // safety: same as in put
+                        unsafe {
+                            TreeBin::defer_drop_without_values(bin, &self.garbage, guard);
+                            self.garbage.retire(guard, p);
+                        }
+                    }
                 }
                 BinEntry::TreeNode(_) => unreachable!(
                     "The head of a bin cannot be a TreeNode directly without BinEntry::Tree"
                 ),
             }
-            if let Some((key, val)) = old_val {
-                if is_remove {
-                    self.add_count(-1, None, guard);
-                }
-
-                // safety: need to guarantee that the old value is no longer
-                // reachable. more specifically, no thread that executes _after_
-                // this line can ever get a reference to val.
-                //
-                // here are the possible cases:
-                //
-                //  - another thread already has a reference to the old value.
-                //    they must have read it before the call to store_bin.
-                //    because of this, that thread must be pinned to an epoch <=
-                //    the epoch of our guard. since the garbage is placed in our
-                //    epoch, it won't be freed until the _next_ epoch, at which
-                //    point, that thread must have dropped its guard, and with it,
-                //    any reference to the value.
-                //  - another thread is about to get a reference to this value.
-                //    they execute _after_ the store_bin, and therefore do _not_ get a
-                //    reference to the old value. there are no other ways to get to a
-                //    value except through its Node's `value` field (which is now gone
-                //    together with the node), so freeing the old value is fine.
-                unsafe { guard.defer_destroy(val) };
-
-                // safety: the lifetime of the reference is bound to the guard
-                // supplied which means that the memory will not be freed
-                // until at least after the guard goes out of scope
-                return unsafe { val.as_ref() }.map(move |v| (key, v));
+            if old_val.is_some() && is_remove {
+                self.add_count(-1, None, guard);
             }
-            break;
+            return old_val;
         }
         None
     }
+}
 
+impl<K, V, S> HashMap<K, V, S>
+where
+    K: 'static + Sync + Send + Clone + Hash + TreeKey,
+    V: 'static + Sync + Send,
+    S: BuildHasher,
+{
     /// Retains only the elements specified by the predicate.
     ///
-    /// In other words, remove all pairs `(k, v)` such that `f(&k,&v)` returns `false`.
+    /// In other words, remove all pairs `(k, v)` such that `f(&k,&v)` returns `false`. Returns
+    /// the number of entries that were removed.
     ///
     /// # Examples
     ///
@@ -2674,7 +7265,7 @@ where
     /// for i in 0..8 {
     ///     map.pin().insert(i, i*10);
     /// }
-    /// map.pin().retain(|&k, _| k % 2 == 0);
+    /// assert_eq!(map.pin().retain(|&k, _| k % 2 == 0), 4);
     /// assert_eq!(map.pin().len(), 4);
     /// ```
     ///
@@ -2683,18 +7274,113 @@ where
     /// If `f` returns `false` for a given key/value pair, but the value for that pair is concurrently
     /// modified before the removal takes place, the entry will not be removed.
     /// If you want the removal to happen even in the case of concurrent modification, use [`HashMap::retain_force`].
-    pub fn retain<F>(&self, mut f: F, guard: &Guard)
+    pub fn retain<F>(&self, mut f: F, guard: &Guard) -> usize
     where
         F: FnMut(&K, &V) -> bool,
     {
         self.check_guard(guard);
+        let mut removed = 0;
         // removed selected keys
         for (k, v) in self.iter(guard) {
             if !f(k, v) {
                 let old_value: Shared<'_, V> = Shared::from(v as *const V);
-                self.replace_node(k, None, Some(old_value), guard);
+                if self.replace_node(k, None, Some(old_value), guard).is_some() {
+                    removed += 1;
+                }
             }
         }
+        removed
+    }
+
+    /// Retains only the elements specified by the predicate, visiting the entries in parallel.
+    ///
+    /// This does the same as [`retain`](HashMap::retain), but the table is split up into regions
+    /// of bins, which [rayon] hands to its worker threads. The workers read the map under
+    /// `guard`, and remove entries under guards of their own, so `f` is called from several
+    /// threads at once. Returns the number of entries that were removed.
+    ///
+    /// This is only available with the `rayon` feature.
+    ///
+    /// [rayon]: https://docs.rs/rayon
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map: HashMap<_, _> = (0..10_000).map(|i| (i, i)).collect();
+    /// assert_eq!(map.par_retain(|_, &v| v % 3 == 0, &map.guard()), 6_666);
+    /// assert_eq!(map.len(), 3_334);
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn par_retain<F>(&self, f: F, guard: &Guard) -> usize
+    where
+        F: Fn(&K, &V) -> bool + Sync,
+        S: Sync,
+    {
+        use rayon::iter::ParallelIterator;
+
+        self.par_iter(guard)
+            .map_init(
+                || self.guard(),
+                |worker_guard, (k, v)| {
+                    if f(k, v) {
+                        return 0;
+                    }
+                    // the entry was read under `guard`, which outlives this call, so it is
+                    // still valid to compare against even though we remove it under another one
+                    let old_value: Shared<'_, V> = Shared::from(v as *const V);
+                    match self.replace_node(k, None, Some(old_value), worker_guard) {
+                        Some(_) => 1,
+                        None => 0,
+                    }
+                },
+            )
+            .sum()
+    }
+
+    /// Computes a new value for every entry of the map in parallel, or removes the entry if `f`
+    /// returns `None`.
+    ///
+    /// Each entry is updated like with [`compute_if_present`](HashMap::compute_if_present): `f`
+    /// is called with the current value while holding the lock of the entry's bin, so updates
+    /// from other threads are never lost, and the entries are rewritten in place rather than
+    /// being removed and inserted again. As for [`par_retain`](HashMap::par_retain), the table
+    /// is split up into regions of bins, which [rayon] hands to its worker threads. The workers
+    /// read the map under `guard`, and update it under guards of their own.
+    ///
+    /// Entries that are inserted while this runs may or may not be visited.
+    ///
+    /// This is only available with the `rayon` feature.
+    ///
+    /// [rayon]: https://docs.rs/rayon
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map: HashMap<_, _> = (0..1_000).map(|i| (i, i as f64)).collect();
+    /// let guard = map.guard();
+    /// // scale all values down, and drop the ones that end up too small
+    /// map.par_compute_all(|_, &v| Some(v / 999.0).filter(|&v| v >= 0.5), &guard);
+    /// assert_eq!(map.len(), 500);
+    /// assert_eq!(map.get(&999, &guard), Some(&1.0));
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn par_compute_all<F>(&self, f: F, guard: &Guard)
+    where
+        F: Fn(&K, &V) -> Option<V> + Sync,
+        S: Sync,
+    {
+        use rayon::iter::ParallelIterator;
+
+        self.par_keys(guard).for_each_init(
+            || self.guard(),
+            |worker_guard, k| {
+                self.compute_if_present(k, &f, worker_guard);
+            },
+        )
     }
 
     /// Retains only the elements specified by the predicate.
@@ -2703,6 +7389,7 @@ where
     ///
     /// This method always deletes any key/value pair that `f` returns `false` for, even if if the
     /// value is updated concurrently. If you do not want that behavior, use [`HashMap::retain`].
+    /// Returns the number of entries that were removed.
     ///
     /// # Examples
     ///
@@ -2714,26 +7401,254 @@ where
     /// for i in 0..8 {
     ///     map.pin().insert(i, i*10);
     /// }
-    /// map.pin().retain_force(|&k, _| k % 2 == 0);
+    /// assert_eq!(map.pin().retain_force(|&k, _| k % 2 == 0), 4);
     /// assert_eq!(map.pin().len(), 4);
     /// ```
-    pub fn retain_force<F>(&self, mut f: F, guard: &Guard)
+    pub fn retain_force<F>(&self, mut f: F, guard: &Guard) -> usize
     where
         F: FnMut(&K, &V) -> bool,
     {
         self.check_guard(guard);
+        let mut removed = 0;
         // removed selected keys
         for (k, v) in self.iter(guard) {
+            if !f(k, v) && self.replace_node(k, None, None, guard).is_some() {
+                removed += 1;
+            }
+        }
+        removed
+    }
+
+    /// Retains only the elements specified by the predicate like [`HashMap::retain`], starting
+    /// where the call that returned `from` left off, and stops early if `should_yield` returns
+    /// `true`.
+    ///
+    /// The entries are visited a bin at a time, with a [`Cursor`]. `should_yield` is called
+    /// before each bin after the first one, so that every call makes progress. If it returns
+    /// `true`, the entries visited so far have been handled, the rest of the map is left as it
+    /// is, and the returned token says where to continue. Passing it to the next call picks up
+    /// right there. Once every entry has been visited, the returned token
+    /// [is finished](ResumeToken::is_finished). Start with `ResumeToken::default()`.
+    ///
+    /// Just like a cursor, this may visit an entry more than once if the table resizes between
+    /// calls, which is fine for predicates that give the same answer when asked twice.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::cursor::ResumeToken;
+    /// use flurry::HashMap;
+    ///
+    /// let map: HashMap<_, _> = (0..100).map(|i| (i, i)).collect();
+    /// let guard = map.guard();
+    /// let mut token = ResumeToken::default();
+    /// let mut calls = 0;
+    /// while !token.is_finished() {
+    ///     // handle a few bins at a time
+    ///     let mut budget = 4;
+    ///     token = map.retain_or_yield(
+    ///         |&k, _| k % 2 == 0,
+    ///         token,
+    ///         || {
+    ///             budget -= 1;
+    ///             budget < 0
+    ///         },
+    ///         &guard,
+    ///     );
+    ///     calls += 1;
+    /// }
+    /// assert!(calls > 1);
+    /// assert_eq!(map.len(), 50);
+    /// ```
+    pub fn retain_or_yield<F, Y>(
+        &self,
+        mut f: F,
+        from: ResumeToken,
+        mut should_yield: Y,
+        guard: &Guard,
+    ) -> ResumeToken
+    where
+        F: FnMut(&K, &V) -> bool,
+        Y: FnMut() -> bool,
+    {
+        self.check_guard(guard);
+        let mut cursor = self.cursor_from(from, guard);
+        let mut visited = false;
+        loop {
+            // only yield between bins, so that the token does not have to tell apart entries
+            // within a bin, some of which we may have removed
+            if visited && cursor.pending.is_empty() && should_yield() {
+                break;
+            }
+            let (k, v) = match cursor.next() {
+                Some(entry) => entry,
+                None => break,
+            };
+            visited = true;
             if !f(k, v) {
-                self.replace_node(k, None, None, guard);
+                let old_value: Shared<'_, V> = Shared::from(v as *const V);
+                self.replace_node(k, None, Some(old_value), guard);
+            }
+        }
+        cursor.into_token()
+    }
+
+    /// Keeps, removes, or replaces every entry of the map, as decided by `f`.
+    ///
+    /// `f` is called with the bin of each entry locked, so the entry cannot change between `f`
+    /// looking at it and its decision being applied. This makes it possible to prune and normalize
+    /// the entries of a map in a single pass. Entries that are inserted while the map is
+    /// traversed may or may not be visited.
+    ///
+    /// Since `f` runs while a bin lock is held, it should be short and must not access the map.
+    ///
+    /// Returns the number of entries that were removed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::{Decision, HashMap};
+    ///
+    /// let map = HashMap::new();
+    /// for i in 0..8 {
+    ///     map.pin().insert(i, i * 10);
+    /// }
+    /// // drop odd keys, and cap the remaining values at 30
+    /// let removed = map.pin().retain_mut(|&k, &v| match (k % 2, v) {
+    ///     (1, _) => Decision::Remove,
+    ///     (_, v) if v > 30 => Decision::Replace(30),
+    ///     _ => Decision::Keep,
+    /// });
+    /// assert_eq!(removed, 4);
+    /// assert_eq!(map.pin().len(), 4);
+    /// assert_eq!(map.pin().get(&2), Some(&20));
+    /// assert_eq!(map.pin().get(&6), Some(&30));
+    /// ```
+    pub fn retain_mut<F>(&self, mut f: F, guard: &Guard) -> usize
+    where
+        F: FnMut(&K, &V) -> Decision<V>,
+    {
+        self.check_guard(guard);
+        let mut removed = 0;
+        for (k, _) in self.iter(guard) {
+            // the decision is applied under the same bin lock that `f` is called under, so an
+            // entry that `f` decides to remove is removed
+            let decide = |k: &K, v: &V| {
+                let decision = f(k, v);
+                if let Decision::Remove = decision {
+                    removed += 1;
+                }
+                decision
+            };
+            match self.compute_if_present_until(k, decide, None, guard) {
+                Ok(_) => {}
+                Err(_) => unreachable!("retain_mut without a deadline cannot time out"),
             }
         }
+        removed
+    }
+
+    /// Removes the map's entries one at a time, and yields each removed key-value pair.
+    ///
+    /// Each entry is removed just before the iterator yields it, so entries that the iterator has
+    /// not reached when it is dropped stay in the map. As with [`HashMap::iter`], entries that are
+    /// inserted while the map is drained may or may not be visited, so the map is only guaranteed
+    /// to be empty afterwards if no other thread inserted into it in the meantime.
+    ///
+    /// The removed keys and values remain valid for as long as `guard` is held, and are dropped
+    /// once it is, just like the value returned by [`HashMap::remove`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map: HashMap<_, _> = (0..4).map(|i| (i, i * 10)).collect();
+    /// let guard = map.guard();
+    /// let mut drained: Vec<_> = map.drain(&guard).map(|(&k, &v)| (k, v)).collect();
+    /// drained.sort();
+    /// assert_eq!(drained, [(0, 0), (1, 10), (2, 20), (3, 30)]);
+    /// assert!(map.is_empty());
+    /// ```
+    pub fn drain<'g>(&'g self, guard: &'g Guard) -> Drain<'g, K, V, S> {
+        self.check_guard(guard);
+        let table = self.table.load(Ordering::SeqCst, guard);
+        let node_iter = NodeIter::new(table, guard);
+        Drain {
+            map: self,
+            node_iter,
+            guard,
+        }
+    }
+
+    /// Removes some entry from the map, and returns it.
+    ///
+    /// Which entry is removed is unspecified. This makes the map usable as a concurrent pool of
+    /// work items, from which each thread takes whatever item is available. Returns `None` if the
+    /// map has no entries, or if other threads removed them all first.
+    ///
+    /// Every call searches the table from the start, so when many threads pop from the same map
+    /// at once, they will contend for the first non-empty bins.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// let guard = map.guard();
+    /// map.insert("job", 1, &guard);
+    /// assert_eq!(map.pop(&guard), Some((&"job", &1)));
+    /// assert_eq!(map.pop(&guard), None);
+    /// ```
+    pub fn pop<'g>(&'g self, guard: &'g Guard) -> Option<(&'g K, &'g V)> {
+        self.drain(guard).next()
+    }
+
+    /// Removes the entries for which `pred` returns `true`, and yields each removed key-value
+    /// pair.
+    ///
+    /// This is like [`HashMap::retain`] with the predicate inverted, except that the removed
+    /// entries are handed to the caller, for example to move them into another collection. `pred`
+    /// is called while the entry's bin is locked, as with [`HashMap::remove_if`], so an entry is
+    /// only removed if the value `pred` saw is still current. Like [`HashMap::drain`], entries are
+    /// removed as the iterator advances, and the ones it has not reached when it is dropped stay
+    /// in the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map: HashMap<_, _> = (0..8).map(|i| (i, i * 10)).collect();
+    /// let guard = map.guard();
+    /// let mut odd: Vec<_> = map
+    ///     .extract_if(|&k, _| k % 2 == 1, &guard)
+    ///     .map(|(&k, &v)| (k, v))
+    ///     .collect();
+    /// odd.sort();
+    /// assert_eq!(odd, [(1, 10), (3, 30), (5, 50), (7, 70)]);
+    /// assert_eq!(map.len(), 4);
+    /// ```
+    pub fn extract_if<'g, F>(&'g self, pred: F, guard: &'g Guard) -> ExtractIf<'g, K, V, S, F>
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        self.check_guard(guard);
+        let table = self.table.load(Ordering::SeqCst, guard);
+        let node_iter = NodeIter::new(table, guard);
+        ExtractIf {
+            map: self,
+            node_iter,
+            pred,
+            guard,
+        }
     }
 }
 
 impl<K, V, S> HashMap<K, V, S>
 where
-    K: Clone + Ord,
+    K: Clone + TreeKey,
 {
     /// Replaces all linked nodes in the bin at the given index unless the table
     /// is too small, in which case a resize is initiated instead.
@@ -2741,7 +7656,7 @@ where
         let n = tab.len();
         if n < MIN_TREEIFY_CAPACITY {
             self.try_presize(n << 1, guard);
-        } else {
+        } else if TREEIFY {
             let bin = tab.bin(index, guard);
             if bin.is_null() {
                 return;
@@ -2778,6 +7693,7 @@ where
                             Atomic::null(),
                             Atomic::null(),
                         );
+                        new_tree_node.node.copy_version(e_deref);
                         new_tree_node.prev.store(tail, Ordering::Relaxed);
                         let new_tree_node =
                             Owned::new(BinEntry::TreeNode(new_tree_node)).into_shared(guard);
@@ -2820,13 +7736,14 @@ where
                         //
                         // NOTE: we do not drop the value, since it gets moved to the new TreeNode
                         unsafe {
-                            guard.defer_destroy(e);
-                            e = e
+                            let next = e
                                 .deref()
                                 .as_node()
                                 .unwrap()
                                 .next
                                 .load(Ordering::SeqCst, guard);
+                            self.garbage.retire(guard, e);
+                            e = next;
                         }
                     }
                 }
@@ -2870,6 +7787,10 @@ where
                     // need to perform any action on the bin anymore, since either it has already
                     // been treeified or it was moved to a new table.
                 }
+                BinEntry::Reserved(_) => {
+                    // the bin was emptied and then reserved since we looked at it, so it is
+                    // no longer too long either
+                }
                 BinEntry::TreeNode(_) => unreachable!("TreeNode cannot be the head of a bin"),
             }
         }
@@ -2895,12 +7816,13 @@ where
             let q_deref = unsafe { q.deref() }.as_tree_node().unwrap();
             // NOTE: cloning the value uses a load with Ordering::Relaxed, but
             // write access is synchronized through the bin lock
-            let new_node = Owned::new(BinEntry::Node(Node::new(
+            let new_node = Node::new(
                 q_deref.node.hash,
                 q_deref.node.key.clone(),
                 q_deref.node.value.clone(),
-            )))
-            .into_shared(guard);
+            );
+            new_node.copy_version(&q_deref.node);
+            let new_node = Owned::new(BinEntry::Node(new_node)).into_shared(guard);
             if tail.is_null() {
                 head = new_node;
             } else {
@@ -2919,13 +7841,14 @@ where
         head
     }
 }
-impl<K, V, S> PartialEq for HashMap<K, V, S>
+impl<K, V, S, S2> PartialEq<HashMap<K, V, S2>> for HashMap<K, V, S>
 where
-    K: Ord + Hash,
+    K: TreeKey + Hash,
     V: PartialEq,
     S: BuildHasher,
+    S2: BuildHasher,
 {
-    fn eq(&self, other: &Self) -> bool {
+    fn eq(&self, other: &HashMap<K, V, S2>) -> bool {
         if self.len() != other.len() {
             return false;
         }
@@ -2935,7 +7858,7 @@ where
 
 impl<K, V, S> Eq for HashMap<K, V, S>
 where
-    K: Ord + Hash,
+    K: TreeKey + Hash,
     V: Eq,
     S: BuildHasher,
 {
@@ -2948,7 +7871,26 @@ where
 {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         let guard = self.collector.register().pin();
-        f.debug_map().entries(self.iter(&guard)).finish()
+        let limit = self.debug_limit.unwrap_or(usize::MAX);
+        f.debug_map()
+            .entries(self.iter(&guard).take(limit))
+            .finish()?;
+        fmt_remaining(f, self.len(), limit)
+    }
+}
+
+impl<K, V, S> IntoIterator for HashMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    /// Consumes the map, and returns an iterator over its entries in arbitrary order.
+    ///
+    /// Since the map is owned, this does not need a guard, and the keys and values are moved out
+    /// of the map rather than cloned.
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            entries: self.into_entries().into_iter(),
+        }
     }
 }
 
@@ -2965,45 +7907,33 @@ impl<K, V, S> Drop for HashMap<K, V, S> {
 
         assert!(self.next_table.load(Ordering::SeqCst, guard).is_null());
         let table = self.table.swap(Shared::null(), Ordering::SeqCst, guard);
-        if table.is_null() {
-            // table was never allocated!
-            return;
+        // table may never have been allocated
+        if !table.is_null() {
+            // safety: same as above + we own the table
+            let mut table = unsafe { table.into_owned() }.into_box();
+            table.drop_bins();
         }
 
-        // safety: same as above + we own the table
-        let mut table = unsafe { table.into_owned() }.into_box();
-        table.drop_bins();
+        #[cfg(feature = "leak-check")]
+        self.check_garbage();
     }
 }
 
 impl<K, V, S> Extend<(K, V)> for &HashMap<K, V, S>
 where
-    K: 'static + Sync + Send + Clone + Hash + Ord,
+    K: 'static + Sync + Send + Clone + Hash + TreeKey,
     V: 'static + Sync + Send,
     S: BuildHasher,
 {
     fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
-        // from `hashbrown::HashMap::extend`:
-        // Keys may be already present or show multiple times in the iterator.
-        // Reserve the entire hint lower bound if the map is empty.
-        // Otherwise reserve half the hint (rounded up), so the map
-        // will only resize twice in the worst case.
-        let iter = iter.into_iter();
-        let reserve = if self.is_empty() {
-            iter.size_hint().0
-        } else {
-            (iter.size_hint().0 + 1) / 2
-        };
-
         let guard = self.collector.register().pin();
-        self.reserve(reserve, &guard);
-        (*self).put_all(iter, &guard);
+        (*self).insert_all(iter, &guard);
     }
 }
 
 impl<'a, K, V, S> Extend<(&'a K, &'a V)> for &HashMap<K, V, S>
 where
-    K: 'static + Sync + Send + Copy + Hash + Ord,
+    K: 'static + Sync + Send + Copy + Hash + TreeKey,
     V: 'static + Sync + Send + Copy,
     S: BuildHasher,
 {
@@ -3014,7 +7944,7 @@ where
 
 impl<K, V, S> FromIterator<(K, V)> for HashMap<K, V, S>
 where
-    K: 'static + Sync + Send + Clone + Hash + Ord,
+    K: 'static + Sync + Send + Clone + Hash + TreeKey,
     V: 'static + Sync + Send,
     S: BuildHasher + Default,
 {
@@ -3038,9 +7968,40 @@ where
     }
 }
 
+impl<K, V, S> From<std::collections::HashMap<K, V, S>> for HashMap<K, V, S>
+where
+    K: 'static + Sync + Send + Clone + Hash + TreeKey,
+    V: 'static + Sync + Send,
+    S: BuildHasher + Clone,
+{
+    /// Moves the entries of a [`std::collections::HashMap`] into a new map that uses a clone of
+    /// its hasher.
+    ///
+    /// The new map is sized to hold all the entries up front, and since the keys are known to be
+    /// distinct, they are placed into their bins directly rather than inserted one by one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let mut counts = std::collections::HashMap::new();
+    /// for word in "a b a c b a".split(' ') {
+    ///     *counts.entry(word).or_insert(0) += 1;
+    /// }
+    ///
+    /// let counts = HashMap::from(counts);
+    /// assert_eq!(counts.pin().get("a"), Some(&3));
+    /// ```
+    fn from(map: std::collections::HashMap<K, V, S>) -> Self {
+        let len = map.len();
+        Self::from_distinct(map.hasher().clone(), len, map.into_iter())
+    }
+}
+
 impl<'a, K, V, S> FromIterator<(&'a K, &'a V)> for HashMap<K, V, S>
 where
-    K: 'static + Sync + Send + Copy + Hash + Ord,
+    K: 'static + Sync + Send + Copy + Hash + TreeKey,
     V: 'static + Sync + Send + Copy,
     S: BuildHasher + Default,
 {
@@ -3051,7 +8012,7 @@ where
 
 impl<'a, K, V, S> FromIterator<&'a (K, V)> for HashMap<K, V, S>
 where
-    K: 'static + Sync + Send + Copy + Hash + Ord,
+    K: 'static + Sync + Send + Copy + Hash + TreeKey,
     V: 'static + Sync + Send + Copy,
     S: BuildHasher + Default,
 {
@@ -3062,22 +8023,50 @@ where
 
 impl<K, V, S> Clone for HashMap<K, V, S>
 where
-    K: 'static + Sync + Send + Clone + Hash + Ord,
+    K: 'static + Sync + Send + Clone + Hash + TreeKey,
     V: 'static + Sync + Send + Clone,
     S: BuildHasher + Clone,
 {
     fn clone(&self) -> HashMap<K, V, S> {
-        let cloned_map = Self::with_capacity_and_hasher(self.len(), self.build_hasher.clone());
+        let mut cloned_map =
+            Self::with_hasher(self.build_hasher.clone()).with_collector(&self.collector());
+        cloned_map.load_factor = self.load_factor;
+        cloned_map.reclaim_hook = self.reclaim_hook.clone();
+        cloned_map.spreader = self.spreader.clone();
+        let len = self.len();
+        if len > 0 {
+            cloned_map.presize(len);
+        }
         {
             let guard = self.collector.register().pin();
-            for (k, v) in self.iter(&guard) {
-                cloned_map.insert(k.clone(), v.clone(), &guard);
+            let table = self.table.load(Ordering::SeqCst, &guard);
+            if table.is_null() {
+                return cloned_map;
             }
+
+            // we reuse the hashes of the entries rather than hashing the keys again. since the
+            // cloned map uses a clone of our hasher, the hashes are the same.
+            let entries = NodeIter::new(table, &guard).map(|node| {
+                let value = node.value.load(Ordering::SeqCst, &guard);
+                // safety: flurry does not drop or move until after guard drop
+                let value = unsafe { value.deref() }.clone();
+                (node.hash, node.key.clone(), value)
+            });
+            cloned_map.fill(entries, &guard);
         }
         cloned_map
     }
 }
 
+/// Allocates a table with `n` bins, with `Table::try_new` if `fallible` is set.
+fn new_table<K, V>(n: usize, fallible: bool) -> Result<Table<K, V>, TryReserveError> {
+    if fallible {
+        Table::try_new(n)
+    } else {
+        Ok(Table::new(n))
+    }
+}
+
 #[cfg(not(miri))]
 #[inline]
 /// Returns the number of physical CPUs in the machine (_O(1)_).
@@ -3092,24 +8081,108 @@ const fn num_cpus() -> usize {
     1
 }
 
+/// Returns the number of bins a thread claims at a time when it moves a table of size `n`.
+fn transfer_stride(n: usize) -> isize {
+    let ncpu = num_cpus();
+    let stride = if ncpu > 1 { (n >> 3) / ncpu } else { n };
+    std::cmp::max(stride as isize, MIN_TRANSFER_STRIDE)
+}
+
+/// Panics because a guard from `guard_collector` was passed to a map that reclaims through
+/// `map_collector`.
+///
+/// Kept out of line so that the check in `check_guard` stays cheap on the fast path.
+#[cold]
+#[inline(never)]
+fn guard_mismatch(guard_collector: &epoch::Collector, map_collector: &epoch::Collector) -> ! {
+    fn describe(collector: &epoch::Collector) -> &'static str {
+        if collector == epoch::default_collector() {
+            "the global collector"
+        } else {
+            "a separate flurry::epoch::Collector"
+        }
+    }
+
+    panic!(
+        "flurry: guard belongs to {} but this map reclaims through {}; \
+         guards must come from the same collector as the map they are used with \
+         (use `HashMap::guard` on this map, or share one collector via `with_collector`)",
+        describe(guard_collector),
+        describe(map_collector),
+    );
+}
+
+/// Writes how many entries were left out when a map or set was formatted with a `debug_limit`.
+pub(crate) fn fmt_remaining(f: &mut Formatter<'_>, len: usize, limit: usize) -> fmt::Result {
+    match len.saturating_sub(limit) {
+        0 => Ok(()),
+        remaining => write!(f, " and {} more", remaining),
+    }
+}
+
+/// Restores the min-heap property of `heap` after an element was pushed onto it.
+fn sift_up<T, F>(heap: &mut [T], cmp: &mut F)
+where
+    F: FnMut(&T, &T) -> std::cmp::Ordering,
+{
+    let mut i = heap.len() - 1;
+    while i > 0 {
+        let parent = (i - 1) / 2;
+        if cmp(&heap[i], &heap[parent]) != std::cmp::Ordering::Less {
+            break;
+        }
+        heap.swap(i, parent);
+        i = parent;
+    }
+}
+
+/// Restores the min-heap property of `heap` after its first element was replaced.
+fn sift_down<T, F>(heap: &mut [T], cmp: &mut F)
+where
+    F: FnMut(&T, &T) -> std::cmp::Ordering,
+{
+    let mut i = 0;
+    loop {
+        let left = 2 * i + 1;
+        if left >= heap.len() {
+            break;
+        }
+        let right = left + 1;
+        let smallest =
+            if right < heap.len() && cmp(&heap[right], &heap[left]) == std::cmp::Ordering::Less {
+                right
+            } else {
+                left
+            };
+        if cmp(&heap[smallest], &heap[i]) != std::cmp::Ordering::Less {
+            break;
+        }
+        heap.swap(i, smallest);
+        i = smallest;
+    }
+}
+
 #[test]
 fn capacity() {
     let map = HashMap::<usize, usize>::new();
     let guard = epoch::pin();
 
+    assert_eq!(map.bucket_count(&guard), 0);
     assert_eq!(map.capacity(&guard), 0);
     // The table has not yet been allocated
 
     map.insert(42, 0, &guard);
 
-    assert_eq!(map.capacity(&guard), 16);
+    assert_eq!(map.bucket_count(&guard), 16);
+    assert_eq!(map.capacity(&guard), 11);
     // The table has been allocated and has default capacity
 
     for i in 0..16 {
         map.insert(i, 42, &guard);
     }
 
-    assert_eq!(map.capacity(&guard), 32);
+    assert_eq!(map.bucket_count(&guard), 32);
+    assert_eq!(map.capacity(&guard), 23);
     // The table has been resized once (and it's capacity doubled),
     // since we inserted more elements than it can hold
 }
@@ -3141,6 +8214,24 @@ mod tests {
         assert!(capacity >= 32);
     }
 
+    #[test]
+    fn try_reserve() {
+        let map = HashMap::<usize, usize>::new();
+        let guard = epoch::pin();
+
+        map.try_reserve(32, &guard).unwrap();
+        assert!(map.capacity(&guard) >= 32);
+
+        for i in 0..32 {
+            map.insert(i, i, &guard);
+        }
+        map.try_reserve(1000, &guard).unwrap();
+        assert!(map.capacity(&guard) >= 32 + 1000);
+        for i in 0..32 {
+            assert_eq!(map.get(&i, &guard), Some(&i));
+        }
+    }
+
     #[test]
     fn resize_stamp_negative() {
         let resize_stamp = HashMap::<usize, usize>::resize_stamp(1);
@@ -3363,7 +8454,7 @@ fn replace_twice() {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, not(feature = "unordered-keys")))]
 mod tree_bins {
     use super::*;
 
@@ -3408,6 +8499,7 @@ mod tree_bins {
                 BinEntry::Moved => panic!("bin was not correctly treeified -- is Moved"),
                 BinEntry::Node(_) => panic!("bin was not correctly treeified -- is Node"),
                 BinEntry::TreeNode(_) => panic!("bin was not correctly treeified -- is TreeNode"),
+                BinEntry::Reserved(_) => panic!("bin was not correctly treeified -- is Reserved"),
             }
 
             guard.flush();
@@ -3506,6 +8598,7 @@ mod tree_bins {
                 BinEntry::Moved => panic!("bin was not correctly treeified -- is Moved"),
                 BinEntry::Node(_) => panic!("bin was not correctly treeified -- is Node"),
                 BinEntry::TreeNode(_) => panic!("bin was not correctly treeified -- is TreeNode"),
+                BinEntry::Reserved(_) => panic!("bin was not correctly treeified -- is Reserved"),
             }
 
             // Delete keys to force untreeifying the bin
@@ -3529,6 +8622,7 @@ mod tree_bins {
                 BinEntry::Moved => panic!("bin was not correctly untreeified -- is Moved"),
                 BinEntry::Node(_) => {} // pass
                 BinEntry::TreeNode(_) => panic!("bin was not correctly untreeified -- is TreeNode"),
+                BinEntry::Reserved(_) => panic!("bin was not correctly untreeified -- is Reserved"),
             }
         }
 
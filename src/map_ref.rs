@@ -1,15 +1,51 @@
+use crate::cursor::{Cursor, ResumeToken};
+use crate::diagnostics::{MemoryUsage, Summary, TableStats, TreeBins};
 use crate::iter::*;
-use crate::{GuardRef, HashMap, TryInsertError};
+use crate::raw_entry::{RawEntryBuilder, RawEntryBuilderMut};
+use crate::watch::Watch;
+use crate::{
+    Comparable, CompareExchangeError, Decision, GuardRef, HashMap, LockTimeoutError,
+    StaleStampError, Stamp, TrackedGuard, TreeKey, TryInsertError, TryReserveError, WriteBatch,
+};
 use crossbeam_epoch::Guard;
 use std::borrow::Borrow;
 use std::fmt::{self, Debug, Formatter};
 use std::hash::{BuildHasher, Hash};
-use std::ops::Index;
+use std::ops::{Add, Index};
+use std::sync::Arc;
+use std::time::Duration;
 
 /// A reference to a [`HashMap`], constructed with [`HashMap::pin`] or [`HashMap::with_guard`].
 ///
 /// The current thread will be pinned for the duration of this reference.
 /// Keep in mind that this prevents the collection of garbage generated by the map.
+///
+/// Whether the reference owns its guard or borrows one does not show up in its type, so it can
+/// be named in struct fields and function signatures with just the map's lifetime:
+///
+/// ```
+/// use flurry::{HashMap, HashMapRef};
+///
+/// struct Lookup<'map> {
+///     names: HashMapRef<'map, u32, String>,
+/// }
+///
+/// impl Lookup<'_> {
+///     fn name(&self, id: u32) -> Option<&str> {
+///         self.names.get(&id).map(String::as_str)
+///     }
+/// }
+///
+/// let names = HashMap::new();
+/// names.pin().insert(1, String::from("one"));
+///
+/// let owned = Lookup { names: names.pin() };
+/// assert_eq!(owned.name(1), Some("one"));
+///
+/// let guard = names.guard();
+/// let borrowed = Lookup { names: names.with_guard(&guard) };
+/// assert_eq!(borrowed.name(2), None);
+/// ```
 pub struct HashMapRef<'map, K, V, S = crate::DefaultHashBuilder> {
     pub(crate) map: &'map HashMap<K, V, S>,
     guard: GuardRef<'map>,
@@ -27,6 +63,29 @@ impl<K, V, S> HashMap<K, V, S> {
         }
     }
 
+    /// Get a reference to this map with the current thread pinned by a cached guard.
+    ///
+    /// This is like [`HashMap::pin`], but uses [`HashMap::guard_cached`] rather than
+    /// [`HashMap::guard`], which makes it worthwhile for a single lookup or insertion.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// for i in 0..16 {
+    ///     map.pin_cached().insert(i, i * 2);
+    /// }
+    /// assert_eq!(map.pin_cached().get(&3), Some(&6));
+    /// ```
+    pub fn pin_cached(&self) -> HashMapRef<'_, K, V, S> {
+        HashMapRef {
+            guard: GuardRef::Cached(self.guard_cached()),
+            map: self,
+        }
+    }
+
     /// Get a reference to this map with the given guard.
     pub fn with_guard<'g>(&'g self, guard: &'g Guard) -> HashMapRef<'g, K, V, S> {
         HashMapRef {
@@ -36,6 +95,323 @@ impl<K, V, S> HashMap<K, V, S> {
     }
 }
 
+/// A reference to a [`HashMap`] in an [`Arc`] that keeps the current thread pinned, constructed
+/// with [`HashMap::pin_owned`].
+///
+/// Unlike a [`HashMapRef`], this does not borrow the map, so it can be kept in struct fields or
+/// thread-locals that must be `'static`. Like any guard, it cannot be sent to another thread, since
+/// it pins the thread that created it. That also rules out moving it into a task that an async
+/// runtime may run on other threads; use [`UnpinnedMapRef`](crate::UnpinnedMapRef), which pins on
+/// every call, for that instead. Keep in mind that for as long as it is alive, it prevents the
+/// collection of garbage generated by the map.
+///
+/// [`map_ref`](OwnedMapRef::map_ref) gives access to all the methods of [`HashMapRef`] without
+/// pinning the thread again.
+pub struct OwnedMapRef<K, V, S = crate::DefaultHashBuilder> {
+    // declared first, so that the map is not dropped while the thread is still pinned
+    guard: TrackedGuard,
+    map: Arc<HashMap<K, V, S>>,
+}
+
+impl<K, V, S> HashMap<K, V, S> {
+    /// Get a reference to this map with the current thread pinned, which keeps the map alive
+    /// rather than borrowing it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::{HashMap, OwnedMapRef};
+    /// use std::sync::Arc;
+    ///
+    /// struct Session {
+    ///     names: OwnedMapRef<u32, &'static str>,
+    /// }
+    ///
+    /// fn session(names: &Arc<HashMap<u32, &'static str>>) -> Session {
+    ///     Session {
+    ///         names: names.pin_owned(),
+    ///     }
+    /// }
+    ///
+    /// let names = Arc::new(HashMap::new());
+    /// let session = session(&names);
+    /// drop(names);
+    /// session.names.insert(1, "one");
+    /// assert_eq!(session.names.get(&1), Some(&"one"));
+    /// ```
+    pub fn pin_owned(self: &Arc<Self>) -> OwnedMapRef<K, V, S> {
+        OwnedMapRef {
+            guard: self.guard(),
+            map: Arc::clone(self),
+        }
+    }
+}
+
+impl<K, V, S> OwnedMapRef<K, V, S> {
+    /// Returns the map this reference keeps alive.
+    pub fn map(&self) -> &Arc<HashMap<K, V, S>> {
+        &self.map
+    }
+
+    /// Returns a [`HashMapRef`] to the map that uses this reference's guard.
+    pub fn map_ref(&self) -> HashMapRef<'_, K, V, S> {
+        self.map.with_guard(&self.guard)
+    }
+
+    /// Returns the number of entries in the map.
+    ///
+    /// See also [`HashMap::len`].
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns `true` if the map is empty. Otherwise returns `false`.
+    ///
+    /// See also [`HashMap::is_empty`].
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+impl<K, V, S> OwnedMapRef<K, V, S>
+where
+    K: Hash + TreeKey,
+    S: BuildHasher,
+{
+    /// Tests if `key` is a key in this table.
+    ///
+    /// See also [`HashMap::contains_key`].
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+    {
+        self.map.contains_key(key, &self.guard)
+    }
+
+    /// Returns a reference to the value corresponding to the key.
+    ///
+    /// See also [`HashMap::get`].
+    pub fn get<'g, Q>(&'g self, key: &Q) -> Option<&'g V>
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+    {
+        self.map.get(key, &self.guard)
+    }
+}
+
+impl<K, V, S> OwnedMapRef<K, V, S>
+where
+    K: 'static + Sync + Send + Clone + Hash + TreeKey,
+    V: 'static + Sync + Send,
+    S: BuildHasher,
+{
+    /// Inserts a key-value pair into the map.
+    ///
+    /// See also [`HashMap::insert`].
+    pub fn insert(&self, key: K, value: V) -> Option<&'_ V> {
+        self.map.insert(key, value, &self.guard)
+    }
+
+    /// Removes a key-value pair from the map, and returns the removed value (if any).
+    ///
+    /// See also [`HashMap::remove`].
+    pub fn remove<'g, Q>(&'g self, key: &Q) -> Option<&'g V>
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+    {
+        self.map.remove(key, &self.guard)
+    }
+}
+
+impl<K, V, S> Debug for OwnedMapRef<K, V, S>
+where
+    K: Debug,
+    V: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.map_ref().fmt(f)
+    }
+}
+
+impl<K, V, S> Clone for OwnedMapRef<K, V, S> {
+    fn clone(&self) -> Self {
+        self.map.pin_owned()
+    }
+}
+
+impl<K, V, S> crate::guard::private::Sealed for OwnedMapRef<K, V, S> {}
+
+impl<K, V, S> crate::epoch::AsGuard for OwnedMapRef<K, V, S> {
+    #[inline]
+    fn as_guard(&self) -> &Guard {
+        &self.guard
+    }
+}
+
+/// A reference to a [`HashMap`] that does not keep the current thread pinned, constructed with
+/// [`HashMap::unpinned`].
+///
+/// A guard pins the thread that created it, so neither guards nor the [`HashMapRef`]s that hold
+/// them can be sent to another thread. That also means they cannot be held across an `.await`
+/// in a task that a multi-threaded executor may move between threads. An `UnpinnedMapRef` holds
+/// no guard, and so is [`Send`] whenever the map can be shared between threads. Instead, each of
+/// its methods pins the thread with a cached guard (see [`HashMap::pin_cached`]) for the duration
+/// of the call, and returns owned data rather than references into the map.
+///
+/// For a stretch of synchronous code that performs several operations, [`pin`](Self::pin) gives
+/// a [`HashMapRef`] that pins the thread once for all of them.
+///
+/// ```
+/// use flurry::HashMap;
+///
+/// fn assert_send<T: Send>(_: &T) {}
+///
+/// let map = HashMap::<i32, String>::new();
+/// let unpinned = map.unpinned();
+/// assert_send(&unpinned);
+///
+/// unpinned.insert(1, String::from("one"));
+/// // in an async task, an `.await` could go here
+/// assert_eq!(unpinned.get(&1), Some(String::from("one")));
+/// assert_eq!(unpinned.view(&1, |s| s.len()), Some(3));
+///
+/// let pinned = unpinned.pin();
+/// assert_eq!(pinned.get(&1).map(String::as_str), Some("one"));
+/// ```
+pub struct UnpinnedMapRef<'map, K, V, S = crate::DefaultHashBuilder> {
+    map: &'map HashMap<K, V, S>,
+}
+
+impl<K, V, S> HashMap<K, V, S> {
+    /// Get a reference to this map that does not keep the current thread pinned, and that can be
+    /// sent to other threads and held across `.await` points.
+    ///
+    /// See [`UnpinnedMapRef`] for details.
+    pub fn unpinned(&self) -> UnpinnedMapRef<'_, K, V, S> {
+        UnpinnedMapRef { map: self }
+    }
+}
+
+impl<'map, K, V, S> UnpinnedMapRef<'map, K, V, S> {
+    /// Returns the map this reference points to.
+    pub fn map(&self) -> &'map HashMap<K, V, S> {
+        self.map
+    }
+
+    /// Returns a [`HashMapRef`] to the map with the current thread pinned by a cached guard.
+    ///
+    /// See also [`HashMap::pin_cached`].
+    pub fn pin(&self) -> HashMapRef<'map, K, V, S> {
+        self.map.pin_cached()
+    }
+
+    /// Returns the number of entries in the map.
+    ///
+    /// See also [`HashMap::len`].
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns `true` if the map is empty. Otherwise returns `false`.
+    ///
+    /// See also [`HashMap::is_empty`].
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+impl<K, V, S> UnpinnedMapRef<'_, K, V, S>
+where
+    K: Hash + TreeKey,
+    S: BuildHasher,
+{
+    /// Tests if `key` is a key in this table.
+    ///
+    /// See also [`HashMap::contains_key`].
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+    {
+        self.map.contains_key(key, &self.map.guard_cached())
+    }
+
+    /// Returns a clone of the value corresponding to the key.
+    ///
+    /// See also [`HashMap::get_cloned`].
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+        V: Clone,
+    {
+        self.map.get(key, &self.map.guard_cached()).cloned()
+    }
+
+    /// Calls `f` with the value corresponding to the key, and returns its result.
+    ///
+    /// See also [`HashMap::view`].
+    pub fn view<Q, F, R>(&self, key: &Q, f: F) -> Option<R>
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+        F: FnOnce(&V) -> R,
+    {
+        self.map.get(key, &self.map.guard_cached()).map(f)
+    }
+}
+
+impl<K, V, S> UnpinnedMapRef<'_, K, V, S>
+where
+    K: 'static + Sync + Send + Clone + Hash + TreeKey,
+    V: 'static + Sync + Send + Clone,
+    S: BuildHasher,
+{
+    /// Inserts a key-value pair into the map, and returns a clone of the value it replaced, if
+    /// any.
+    ///
+    /// See also [`HashMap::insert`].
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        self.map
+            .insert(key, value, &self.map.guard_cached())
+            .cloned()
+    }
+
+    /// Removes a key-value pair from the map, and returns a clone of the removed value (if any).
+    ///
+    /// See also [`HashMap::remove`].
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+    {
+        self.map.remove(key, &self.map.guard_cached()).cloned()
+    }
+}
+
+impl<K, V, S> Debug for UnpinnedMapRef<'_, K, V, S>
+where
+    K: Debug,
+    V: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.pin().fmt(f)
+    }
+}
+
+impl<K, V, S> Clone for UnpinnedMapRef<'_, K, V, S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<K, V, S> Copy for UnpinnedMapRef<'_, K, V, S> {}
+
+impl<K, V, S> crate::guard::private::Sealed for HashMapRef<'_, K, V, S> {}
+
+impl<K, V, S> crate::epoch::AsGuard for HashMapRef<'_, K, V, S> {
+    #[inline]
+    fn as_guard(&self) -> &Guard {
+        &self.guard
+    }
+}
+
 impl<K, V, S> HashMapRef<'_, K, V, S> {
     /// Returns the number of entries in the map.
     ///
@@ -51,6 +427,81 @@ impl<K, V, S> HashMapRef<'_, K, V, S> {
         self.map.is_empty()
     }
 
+    /// Returns a [`Stamp`] for reading several entries of the map as of a single point in time.
+    ///
+    /// See also [`HashMap::read_stamp`].
+    pub fn read_stamp(&self) -> Stamp {
+        self.map.read_stamp()
+    }
+
+    /// Returns `true` if no change has been made to the map since `stamp` was taken.
+    ///
+    /// See also [`HashMap::validate_stamp`].
+    pub fn validate_stamp(&self, stamp: Stamp) -> bool {
+        self.map.validate_stamp(stamp)
+    }
+
+    /// Returns the number of buckets in the map's current table.
+    ///
+    /// See also [`HashMap::bucket_count`].
+    pub fn bucket_count(&self) -> usize {
+        self.map.bucket_count(&self.guard)
+    }
+
+    /// Returns the number of entries the map can hold before it resizes its table.
+    ///
+    /// See also [`HashMap::capacity`].
+    pub fn capacity(&self) -> usize {
+        self.map.capacity(&self.guard)
+    }
+
+    /// An iterator over the bins of the map that have been turned into trees.
+    ///
+    /// See also [`HashMap::tree_bins`].
+    pub fn tree_bins(&self) -> TreeBins<'_, K, V> {
+        self.map.tree_bins(&self.guard)
+    }
+
+    /// Returns an overview of the map's size and layout.
+    ///
+    /// See also [`HashMap::summary`].
+    pub fn summary(&self) -> Summary {
+        self.map.summary()
+    }
+
+    /// Returns statistics about how the map's entries are spread over its table.
+    ///
+    /// See also [`HashMap::table_stats`].
+    pub fn table_stats(&self) -> TableStats {
+        self.map.table_stats(&self.guard)
+    }
+
+    /// Returns how much garbage the map has retired, and how much of it has been reclaimed.
+    ///
+    /// See also [`HashMap::garbage_stats`].
+    #[cfg(feature = "garbage-stats")]
+    pub fn garbage_stats(&self) -> crate::diagnostics::GarbageStats {
+        self.map.garbage_stats()
+    }
+
+    /// Returns an estimate of how many bytes the map takes up.
+    ///
+    /// See also [`HashMap::memory_usage`].
+    pub fn memory_usage(&self) -> MemoryUsage {
+        self.map.memory_usage(&self.guard)
+    }
+
+    /// Returns an estimate of how many bytes the map takes up, including the bytes that `f`
+    /// reports the keys and values own outside of the map.
+    ///
+    /// See also [`HashMap::memory_usage_with`].
+    pub fn memory_usage_with<F>(&self, f: F) -> MemoryUsage
+    where
+        F: FnMut(&K, &V) -> usize,
+    {
+        self.map.memory_usage_with(&self.guard, f)
+    }
+
     /// An iterator visiting all key-value pairs in arbitrary order.
     ///
     /// The iterator element type is `(&'g K, &'g V)`.
@@ -77,11 +528,195 @@ impl<K, V, S> HashMapRef<'_, K, V, S> {
     pub fn values(&self) -> Values<'_, K, V> {
         self.map.values(&self.guard)
     }
+
+    /// A parallel iterator visiting all key-value pairs in arbitrary order.
+    ///
+    /// The iterator element type is `(&'g K, &'g V)`.
+    ///
+    /// See also [`HashMap::par_iter`].
+    #[cfg(feature = "rayon")]
+    pub fn par_iter(&self) -> ParIter<'_, K, V> {
+        self.map.par_iter(&self.guard)
+    }
+
+    /// A parallel iterator visiting all keys in arbitrary order.
+    ///
+    /// The iterator element type is `&'g K`.
+    ///
+    /// See also [`HashMap::par_keys`].
+    #[cfg(feature = "rayon")]
+    pub fn par_keys(&self) -> ParKeys<'_, K, V> {
+        self.map.par_keys(&self.guard)
+    }
+
+    /// A parallel iterator visiting all values in arbitrary order.
+    ///
+    /// The iterator element type is `&'g V`.
+    ///
+    /// See also [`HashMap::par_values`].
+    #[cfg(feature = "rayon")]
+    pub fn par_values(&self) -> ParValues<'_, K, V> {
+        self.map.par_values(&self.guard)
+    }
+
+    /// Calls `f` on every key-value pair of the map, in parallel.
+    ///
+    /// The workers read the entries under the guard of this reference.
+    ///
+    /// See also [`HashMap::par_for_each`].
+    #[cfg(feature = "rayon")]
+    pub fn par_for_each<F>(&self, f: F)
+    where
+        K: Sync,
+        V: Sync,
+        F: Fn(&K, &V) + Sync,
+    {
+        use rayon::iter::ParallelIterator;
+
+        self.par_iter().for_each(|(k, v)| f(k, v))
+    }
+
+    /// Returns the result of calling `f` on some key-value pair for which it returns `Some`, or
+    /// `None` if there is no such pair.
+    ///
+    /// See also [`HashMap::par_search`].
+    #[cfg(feature = "rayon")]
+    pub fn par_search<R, F>(&self, f: F) -> Option<R>
+    where
+        K: Sync,
+        V: Sync,
+        R: Send,
+        F: Fn(&K, &V) -> Option<R> + Sync,
+    {
+        self.map.par_search(f, &self.guard)
+    }
+
+    /// Returns the result of accumulating the given transformation of all key-value pairs using
+    /// `reducer` to combine values, or `None` if the map is empty.
+    ///
+    /// See also [`HashMap::reduce_entries`].
+    #[cfg(feature = "rayon")]
+    pub fn reduce_entries<R, T, F>(
+        &self,
+        parallelism_threshold: usize,
+        transformer: T,
+        reducer: F,
+    ) -> Option<R>
+    where
+        K: Sync,
+        V: Sync,
+        R: Send,
+        T: Fn(&K, &V) -> R + Sync,
+        F: Fn(R, R) -> R + Sync,
+    {
+        self.map
+            .reduce_entries(parallelism_threshold, transformer, reducer, &self.guard)
+    }
+
+    /// Returns the result of accumulating the given transformation of all keys using `reducer`
+    /// to combine values, or `None` if the map is empty.
+    ///
+    /// See also [`HashMap::reduce_keys`].
+    #[cfg(feature = "rayon")]
+    pub fn reduce_keys<R, T, F>(
+        &self,
+        parallelism_threshold: usize,
+        transformer: T,
+        reducer: F,
+    ) -> Option<R>
+    where
+        K: Sync,
+        V: Sync,
+        R: Send,
+        T: Fn(&K) -> R + Sync,
+        F: Fn(R, R) -> R + Sync,
+    {
+        self.map
+            .reduce_keys(parallelism_threshold, transformer, reducer, &self.guard)
+    }
+
+    /// Returns the result of accumulating the given transformation of all values using `reducer`
+    /// to combine values, or `None` if the map is empty.
+    ///
+    /// See also [`HashMap::reduce_values`].
+    #[cfg(feature = "rayon")]
+    pub fn reduce_values<R, T, F>(
+        &self,
+        parallelism_threshold: usize,
+        transformer: T,
+        reducer: F,
+    ) -> Option<R>
+    where
+        K: Sync,
+        V: Sync,
+        R: Send,
+        T: Fn(&V) -> R + Sync,
+        F: Fn(R, R) -> R + Sync,
+    {
+        self.map
+            .reduce_values(parallelism_threshold, transformer, reducer, &self.guard)
+    }
+
+    /// Returns a cursor that scans the map's entries one bin at a time.
+    ///
+    /// See also [`HashMap::cursor`].
+    pub fn cursor(&self) -> Cursor<'_, K, V> {
+        self.map.cursor(&self.guard)
+    }
+
+    /// Returns a cursor that picks up a scan of the map where the cursor that `token` was taken
+    /// from stopped.
+    ///
+    /// See also [`HashMap::cursor_from`].
+    pub fn cursor_from(&self, token: ResumeToken) -> Cursor<'_, K, V> {
+        self.map.cursor_from(token, &self.guard)
+    }
+
+    /// Returns up to `n` distinct entries of the map, chosen approximately uniformly at random.
+    ///
+    /// See also [`HashMap::sample`].
+    #[cfg(feature = "rand")]
+    pub fn sample<R>(&self, n: usize, rng: &mut R) -> Vec<(&'_ K, &'_ V)>
+    where
+        R: rand::Rng + ?Sized,
+    {
+        self.map.sample(n, rng, &self.guard)
+    }
+
+    /// Calls `f` on the entries of the map until it returns `Some`, and returns that result.
+    ///
+    /// See also [`HashMap::search`].
+    pub fn search<'g, R, F>(&'g self, f: F) -> Option<R>
+    where
+        F: FnMut(&'g K, &'g V) -> Option<R>,
+    {
+        self.map.search(f, &self.guard)
+    }
+
+    /// Returns `true` if the map contains an entry with the given value.
+    ///
+    /// See also [`HashMap::contains_value`].
+    pub fn contains_value(&self, value: &V) -> bool
+    where
+        V: PartialEq,
+    {
+        self.map.contains_value(value, &self.guard)
+    }
+
+    /// Returns the `k` largest entries of the map according to `cmp`, largest first.
+    ///
+    /// See also [`HashMap::top_k_by`].
+    pub fn top_k_by<F>(&self, k: usize, cmp: F) -> Vec<(&'_ K, &'_ V)>
+    where
+        F: FnMut(&(&K, &V), &(&K, &V)) -> std::cmp::Ordering,
+    {
+        self.map.top_k_by(k, cmp, &self.guard)
+    }
 }
 
 impl<K, V, S> HashMapRef<'_, K, V, S>
 where
-    K: Clone + Ord,
+    K: Clone + TreeKey,
 {
     /// Tries to reserve capacity for at least `additional` more elements to be inserted in the
     /// `HashMap`.
@@ -92,11 +727,34 @@ where
     pub fn reserve(&self, additional: usize) {
         self.map.reserve(additional, &self.guard)
     }
+
+    /// Tries to reserve capacity for at least `additional` more elements to be inserted in the
+    /// `HashMap`, and returns an error if the memory for it cannot be allocated.
+    ///
+    /// See also [`HashMap::try_reserve`].
+    pub fn try_reserve(&self, additional: usize) -> Result<(), TryReserveError> {
+        self.map.try_reserve(additional, &self.guard)
+    }
+
+    /// Shrinks the table of the map as much as possible, while keeping enough room for its
+    /// current entries.
+    ///
+    /// See also [`HashMap::shrink_to_fit`].
+    pub fn shrink_to_fit(&self) {
+        self.map.shrink_to_fit(&self.guard)
+    }
+
+    /// Shrinks the table of the map so that it can still hold at least `min_capacity` elements.
+    ///
+    /// See also [`HashMap::shrink_to`].
+    pub fn shrink_to(&self, min_capacity: usize) {
+        self.map.shrink_to(min_capacity, &self.guard)
+    }
 }
 
 impl<K, V, S> HashMapRef<'_, K, V, S>
 where
-    K: Hash + Ord,
+    K: Hash + TreeKey,
     S: BuildHasher,
 {
     /// Returns `true` if the map contains a value for the specified key.
@@ -104,8 +762,7 @@ where
     /// See also [`HashMap::contains_key`].
     pub fn contains_key<Q>(&self, key: &Q) -> bool
     where
-        K: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<K>,
     {
         self.map.contains_key(key, &self.guard)
     }
@@ -116,28 +773,110 @@ where
     #[inline]
     pub fn get<'g, Q>(&'g self, key: &Q) -> Option<&'g V>
     where
-        K: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<K>,
     {
         self.map.get(key, &self.guard)
     }
 
+    /// Returns references to the values for each of `keys`, in the same order.
+    ///
+    /// See also [`HashMap::get_many`].
+    pub fn get_many<'g, Q>(&'g self, keys: &[&Q]) -> Vec<Option<&'g V>>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
+    {
+        self.map.get_many(keys, &self.guard)
+    }
+
+    /// Returns the index of the bucket that `key` falls into in the map's current table.
+    ///
+    /// See also [`HashMap::bucket_index`].
+    pub fn bucket_index<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
+    {
+        self.map.bucket_index(key, &self.guard)
+    }
+
+    /// Calls `f` with the value corresponding to the key, and returns its result.
+    ///
+    /// See also [`HashMap::view`].
+    pub fn view<Q, F, R>(&self, key: &Q, f: F) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
+        F: FnOnce(&V) -> R,
+    {
+        self.map.get(key, &self.guard).map(f)
+    }
+
     /// Returns the key-value pair corresponding to `key`.
     ///
     /// See also [`HashMap::get_key_value`].
     #[inline]
     pub fn get_key_value<'g, Q>(&'g self, key: &Q) -> Option<(&'g K, &'g V)>
     where
-        K: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<K>,
     {
         self.map.get_key_value(key, &self.guard)
     }
+
+    /// Returns a builder for looking up entries by hashes that the caller has already computed.
+    ///
+    /// See also [`HashMap::raw_entry`].
+    pub fn raw_entry(&self) -> RawEntryBuilder<'_, K, V, S> {
+        self.map.raw_entry(&self.guard)
+    }
+
+    /// Returns a builder for inserting and removing entries by hashes that the caller has
+    /// already computed.
+    ///
+    /// See also [`HashMap::raw_entry_mut`].
+    pub fn raw_entry_mut(&self) -> RawEntryBuilderMut<'_, K, V, S> {
+        self.map.raw_entry_mut(&self.guard)
+    }
+
+    /// Returns a reference to the value corresponding to the key, along with the entry's version.
+    ///
+    /// See also [`HashMap::get_versioned`].
+    #[inline]
+    pub fn get_versioned<'g, Q>(&'g self, key: &Q) -> Option<(&'g V, u64)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
+    {
+        self.map.get_versioned(key, &self.guard)
+    }
+
+    /// Returns a [`Watch`] that resolves the next time `key` changes.
+    ///
+    /// See also [`HashMap::watch`].
+    pub fn watch(&self, key: K) -> Watch {
+        self.map.watch(key)
+    }
+
+    /// Returns a reference to the value corresponding to the key, provided that the map has not
+    /// changed since `stamp` was taken.
+    ///
+    /// See also [`HashMap::get_validated`].
+    pub fn get_validated<'g, Q>(
+        &'g self,
+        key: &Q,
+        stamp: Stamp,
+    ) -> Result<Option<&'g V>, StaleStampError>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
+    {
+        self.map.get_validated(key, stamp, &self.guard)
+    }
 }
 
 impl<K, V, S> HashMapRef<'_, K, V, S>
 where
-    K: Clone + Ord,
+    K: Clone + TreeKey,
 {
     /// Clears the map, removing all key-value pairs.
     ///
@@ -145,11 +884,22 @@ where
     pub fn clear(&self) {
         self.map.clear(&self.guard);
     }
+
+    /// Clears the map, starting where the call that returned `from` left off, but stops early if
+    /// `should_yield` returns `true`.
+    ///
+    /// See also [`HashMap::clear_or_yield`].
+    pub fn clear_or_yield<F>(&self, from: ResumeToken, should_yield: F) -> ResumeToken
+    where
+        F: FnMut() -> bool,
+    {
+        self.map.clear_or_yield(from, should_yield, &self.guard)
+    }
 }
 
 impl<K, V, S> HashMapRef<'_, K, V, S>
 where
-    K: 'static + Sync + Send + Clone + Hash + Ord,
+    K: 'static + Sync + Send + Clone + Hash + TreeKey,
     V: 'static + Sync + Send,
     S: BuildHasher,
 {
@@ -160,6 +910,14 @@ where
         self.map.insert(key, value, &self.guard)
     }
 
+    /// Inserts a key-value pair into the map, and returns a reference to the value that is now in
+    /// the map for `key`.
+    ///
+    /// See also [`HashMap::insert_and_get`].
+    pub fn insert_and_get(&self, key: K, value: V) -> &'_ V {
+        self.map.insert_and_get(key, value, &self.guard)
+    }
+
     /// Inserts a key-value pair into the map unless the key already exists.
     ///
     /// See also [`HashMap::try_insert`].
@@ -168,6 +926,52 @@ where
         self.map.try_insert(key, value, &self.guard)
     }
 
+    /// Returns a reference to the value for `key`, first inserting `V::default()` if the key is
+    /// not in the map.
+    ///
+    /// See also [`HashMap::get_or_default`].
+    pub fn get_or_default(&self, key: K) -> &'_ V
+    where
+        V: Default,
+    {
+        self.map.get_or_default(key, &self.guard)
+    }
+
+    /// Returns a reference to the value for `key`, first inserting the value produced by `f` if
+    /// the key is not in the map, or returns the error produced by `f`.
+    ///
+    /// See also [`HashMap::get_or_try_insert_with`].
+    pub fn get_or_try_insert_with<F, E>(&self, key: K, f: F) -> Result<&'_ V, E>
+    where
+        F: FnOnce() -> Result<V, E>,
+    {
+        self.map.get_or_try_insert_with(key, f, &self.guard)
+    }
+
+    /// Returns a reference to the value for `key`, first inserting the value produced by `f` if
+    /// the key is not in the map.
+    ///
+    /// See also [`HashMap::get_or_insert_with`].
+    pub fn get_or_insert_with<F>(&self, key: K, f: F) -> &'_ V
+    where
+        F: FnOnce() -> V,
+    {
+        self.map.get_or_insert_with(key, f, &self.guard)
+    }
+
+    /// Inserts a key-value pair into the map, unless the lock on the key's bin cannot be acquired
+    /// within `timeout`.
+    ///
+    /// See also [`HashMap::try_insert_for`].
+    pub fn try_insert_for(
+        &self,
+        key: K,
+        value: V,
+        timeout: Duration,
+    ) -> Result<Option<&'_ V>, LockTimeoutError<(K, V)>> {
+        self.map.try_insert_for(key, value, timeout, &self.guard)
+    }
+
     /// If the value for the specified `key` is present, attempts to
     /// compute a new mapping given the key and its current mapped value.
     ///
@@ -175,20 +979,70 @@ where
     pub fn compute_if_present<'g, Q, F>(&'g self, key: &Q, remapping_function: F) -> Option<&'g V>
     where
         K: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + TreeKey,
         F: FnOnce(&K, &V) -> Option<V>,
     {
         self.map
             .compute_if_present(key, remapping_function, &self.guard)
     }
 
+    /// Computes a new mapping for `key` from its current value, if there is one.
+    ///
+    /// See also [`HashMap::compute`].
+    pub fn compute<F>(&self, key: K, remapping_function: F) -> Option<&'_ V>
+    where
+        F: FnOnce(&K, Option<&V>) -> Option<V>,
+    {
+        self.map.compute(key, remapping_function, &self.guard)
+    }
+
+    /// Inserts `value` for `key`, or combines it with the current value if the key is already
+    /// present.
+    ///
+    /// See also [`HashMap::merge`].
+    pub fn merge<F>(&self, key: K, value: V, merge_function: F) -> &'_ V
+    where
+        F: FnOnce(&V, V) -> V,
+    {
+        self.map.merge(key, value, merge_function, &self.guard)
+    }
+
+    /// Adds `delta` to the value for `key`, or inserts `delta` if the key is not in the map, and
+    /// returns the new value.
+    ///
+    /// See also [`HashMap::add`].
+    pub fn add(&self, key: K, delta: V) -> &'_ V
+    where
+        V: Clone + Add<Output = V>,
+    {
+        self.map.add(key, delta, &self.guard)
+    }
+
+    /// Attempts to compute a new mapping for `key`, unless the lock on the key's bin cannot be
+    /// acquired within `timeout`.
+    ///
+    /// See also [`HashMap::try_compute_for`].
+    pub fn try_compute_for<'g, Q, F>(
+        &'g self,
+        key: &Q,
+        remapping_function: F,
+        timeout: Duration,
+    ) -> Result<Option<&'g V>, LockTimeoutError<F>>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
+        F: FnOnce(&K, &V) -> Option<V>,
+    {
+        self.map
+            .try_compute_for(key, remapping_function, timeout, &self.guard)
+    }
+
     /// Removes a key-value pair from the map, and returns the removed value (if any).
     ///
     /// See also [`HashMap::remove`].
     pub fn remove<'g, Q>(&'g self, key: &Q) -> Option<&'g V>
     where
-        K: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<K>,
     {
         self.map.remove(key, &self.guard)
     }
@@ -199,30 +1053,226 @@ where
     /// See also [`HashMap::remove_entry`].
     pub fn remove_entry<'g, Q>(&'g self, key: &Q) -> Option<(&'g K, &'g V)>
     where
-        K: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<K>,
     {
         self.map.remove_entry(key, &self.guard)
     }
 
+    /// Removes the entry for `key` if `predicate` returns `true` for its value, and returns the
+    /// removed value (if any).
+    ///
+    /// See also [`HashMap::remove_if`].
+    pub fn remove_if<'g, Q, F>(&'g self, key: &Q, predicate: F) -> Option<&'g V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
+        F: FnOnce(&V) -> bool,
+    {
+        self.map.remove_if(key, predicate, &self.guard)
+    }
+
+    /// Removes every key that is also in `other` from the map, and returns how many entries were
+    /// removed.
+    ///
+    /// See also [`HashMap::subtract_keys`].
+    pub fn subtract_keys<V2, S2>(&self, other: &HashMapRef<'_, K, V2, S2>) -> usize
+    where
+        S2: BuildHasher,
+    {
+        self.map.subtract_keys(other.map, &self.guard, &other.guard)
+    }
+
+    /// Removes a key from the map if its entry is still at `version`, and returns the removed
+    /// value.
+    ///
+    /// See also [`HashMap::remove_if_version`].
+    pub fn remove_if_version<'g, Q>(&'g self, key: &Q, version: u64) -> Option<&'g V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
+    {
+        self.map.remove_if_version(key, version, &self.guard)
+    }
+
+    /// Replaces the value for a key with `value` if its entry is still at `version`, and returns
+    /// the replaced value.
+    ///
+    /// See also [`HashMap::replace_if_version`].
+    pub fn replace_if_version<'g, Q>(&'g self, key: &Q, value: V, version: u64) -> Option<&'g V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
+    {
+        self.map
+            .replace_if_version(key, value, version, &self.guard)
+    }
+
+    /// Replaces the value for `key` with `new` if the current value is equal to `current`.
+    ///
+    /// See also [`HashMap::compare_exchange`].
+    pub fn compare_exchange<'g, Q>(
+        &'g self,
+        key: &Q,
+        current: &V,
+        new: V,
+    ) -> Result<&'g V, CompareExchangeError<'g, V>>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
+        V: PartialEq,
+    {
+        self.map.compare_exchange(key, current, new, &self.guard)
+    }
+
+    /// Replaces the value for `key` with `new` if the current value is equal to `expected`, and
+    /// returns the value that won.
+    ///
+    /// See also [`HashMap::replace_entry`].
+    pub fn replace_entry<'g, Q>(
+        &'g self,
+        key: &Q,
+        expected: &V,
+        new: V,
+    ) -> Result<&'g V, CompareExchangeError<'g, V>>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
+        V: PartialEq,
+    {
+        self.map.replace_entry(key, expected, new, &self.guard)
+    }
+
+    /// Replaces the value for `key` with the result of `f`, and returns the previous value.
+    ///
+    /// See also [`HashMap::fetch_update`].
+    pub fn fetch_update<'g, Q, F>(&'g self, key: &Q, f: F) -> Result<&'g V, Option<&'g V>>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
+        F: FnOnce(&V) -> Option<V>,
+    {
+        self.map.fetch_update(key, f, &self.guard)
+    }
+
     /// Retains only the elements specified by the predicate.
     ///
     /// See also [`HashMap::retain`].
-    pub fn retain<F>(&self, f: F)
+    pub fn retain<F>(&self, f: F) -> usize
     where
         F: FnMut(&K, &V) -> bool,
     {
-        self.map.retain(f, &self.guard);
+        self.map.retain(f, &self.guard)
+    }
+
+    /// Retains only the elements specified by the predicate, visiting the entries in parallel.
+    ///
+    /// See also [`HashMap::par_retain`].
+    #[cfg(feature = "rayon")]
+    pub fn par_retain<F>(&self, f: F) -> usize
+    where
+        F: Fn(&K, &V) -> bool + Sync,
+        S: Sync,
+    {
+        self.map.par_retain(f, &self.guard)
+    }
+
+    /// Computes a new value for every entry of the map in parallel, or removes the entry if `f`
+    /// returns `None`.
+    ///
+    /// See also [`HashMap::par_compute_all`].
+    #[cfg(feature = "rayon")]
+    pub fn par_compute_all<F>(&self, f: F)
+    where
+        F: Fn(&K, &V) -> Option<V> + Sync,
+        S: Sync,
+    {
+        self.map.par_compute_all(f, &self.guard)
     }
 
     /// Retains only the elements specified by the predicate.
     ///
     /// See also [`HashMap::retain_force`].
-    pub fn retain_force<F>(&self, f: F)
+    pub fn retain_force<F>(&self, f: F) -> usize
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        self.map.retain_force(f, &self.guard)
+    }
+
+    /// Retains only the elements specified by the predicate, starting where the call that
+    /// returned `from` left off, but stops early if `should_yield` returns `true`.
+    ///
+    /// See also [`HashMap::retain_or_yield`].
+    pub fn retain_or_yield<F, Y>(&self, f: F, from: ResumeToken, should_yield: Y) -> ResumeToken
+    where
+        F: FnMut(&K, &V) -> bool,
+        Y: FnMut() -> bool,
+    {
+        self.map.retain_or_yield(f, from, should_yield, &self.guard)
+    }
+
+    /// Keeps, removes, or replaces every entry of the map, as decided by `f`.
+    ///
+    /// See also [`HashMap::retain_mut`].
+    pub fn retain_mut<F>(&self, f: F) -> usize
+    where
+        F: FnMut(&K, &V) -> Decision<V>,
+    {
+        self.map.retain_mut(f, &self.guard)
+    }
+
+    /// Removes the map's entries one at a time, and yields each removed key-value pair.
+    ///
+    /// See also [`HashMap::drain`].
+    pub fn drain(&self) -> Drain<'_, K, V, S> {
+        self.map.drain(&self.guard)
+    }
+
+    /// Removes some entry from the map, and returns it.
+    ///
+    /// See also [`HashMap::pop`].
+    pub fn pop(&self) -> Option<(&'_ K, &'_ V)> {
+        self.map.pop(&self.guard)
+    }
+
+    /// Removes the entries for which `pred` returns `true`, and yields each removed key-value
+    /// pair.
+    ///
+    /// See also [`HashMap::extract_if`].
+    pub fn extract_if<F>(&self, pred: F) -> ExtractIf<'_, K, V, S, F>
     where
         F: FnMut(&K, &V) -> bool,
     {
-        self.map.retain_force(f, &self.guard);
+        self.map.extract_if(pred, &self.guard)
+    }
+
+    /// Inserts every key-value pair from `iter` into the map.
+    ///
+    /// See also [`HashMap::insert_all`].
+    pub fn insert_all<I>(&self, iter: I)
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        self.map.insert_all(iter, &self.guard);
+    }
+
+    /// Inserts every key-value pair from `iter` into the map, using `resolve` to pick the value
+    /// for keys that are already present.
+    ///
+    /// See also [`HashMap::merge_from_iter`].
+    pub fn merge_from_iter<I, F>(&self, iter: I, resolve: F)
+    where
+        I: IntoIterator<Item = (K, V)>,
+        F: FnMut(&K, &V, V) -> V,
+    {
+        self.map.merge_from_iter(iter, resolve, &self.guard);
+    }
+
+    /// Applies all the insertions and removals in `batch` to the map.
+    ///
+    /// See also [`HashMap::apply_batch`].
+    pub fn apply_batch(&self, batch: WriteBatch<K, V>) {
+        self.map.apply_batch(batch, &self.guard);
     }
 }
 
@@ -251,42 +1301,45 @@ impl<K, V, S> Clone for HashMapRef<'_, K, V, S> {
     }
 }
 
-impl<K, V, S> PartialEq for HashMapRef<'_, K, V, S>
+impl<K, V, S, S2> PartialEq<HashMapRef<'_, K, V, S2>> for HashMapRef<'_, K, V, S>
 where
-    K: Hash + Ord,
+    K: Hash + TreeKey,
     V: PartialEq,
     S: BuildHasher,
+    S2: BuildHasher,
 {
-    fn eq(&self, other: &Self) -> bool {
+    fn eq(&self, other: &HashMapRef<'_, K, V, S2>) -> bool {
         self.map.guarded_eq(&other.map, &self.guard, &other.guard)
     }
 }
 
-impl<K, V, S> PartialEq<HashMap<K, V, S>> for HashMapRef<'_, K, V, S>
+impl<K, V, S, S2> PartialEq<HashMap<K, V, S2>> for HashMapRef<'_, K, V, S>
 where
-    K: Hash + Ord,
+    K: Hash + TreeKey,
     V: PartialEq,
     S: BuildHasher,
+    S2: BuildHasher,
 {
-    fn eq(&self, other: &HashMap<K, V, S>) -> bool {
+    fn eq(&self, other: &HashMap<K, V, S2>) -> bool {
         self.map.guarded_eq(&other, &self.guard, &other.guard())
     }
 }
 
-impl<K, V, S> PartialEq<HashMapRef<'_, K, V, S>> for HashMap<K, V, S>
+impl<K, V, S, S2> PartialEq<HashMapRef<'_, K, V, S2>> for HashMap<K, V, S>
 where
-    K: Hash + Ord,
+    K: Hash + TreeKey,
     V: PartialEq,
     S: BuildHasher,
+    S2: BuildHasher,
 {
-    fn eq(&self, other: &HashMapRef<'_, K, V, S>) -> bool {
+    fn eq(&self, other: &HashMapRef<'_, K, V, S2>) -> bool {
         self.guarded_eq(&other.map, &self.guard(), &other.guard)
     }
 }
 
 impl<K, V, S> Eq for HashMapRef<'_, K, V, S>
 where
-    K: Hash + Ord,
+    K: Hash + TreeKey,
     V: Eq,
     S: BuildHasher,
 {
@@ -294,13 +1347,58 @@ where
 
 impl<K, Q, V, S> Index<&'_ Q> for HashMapRef<'_, K, V, S>
 where
-    K: Hash + Ord + Borrow<Q>,
-    Q: ?Sized + Hash + Ord,
+    K: Hash + TreeKey,
+    Q: ?Sized + Hash + Comparable<K>,
     S: BuildHasher,
 {
     type Output = V;
 
+    /// Returns a reference to the value for `key`, like [`HashMapRef::get`].
+    ///
+    /// The reference lives for as long as the `HashMapRef`, whose guard keeps the value from
+    /// being dropped even if it is removed from the map in the meantime.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the map does not contain `key`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// let map = map.pin();
+    /// map.insert("a", 1);
+    /// assert_eq!(map[&"a"], 1);
+    /// assert_eq!(map["a"], 1);
+    /// ```
     fn index(&self, key: &Q) -> &V {
         self.get(key).expect("no entry found for key")
     }
 }
+
+impl<K, V, S> Extend<(K, V)> for HashMapRef<'_, K, V, S>
+where
+    K: 'static + Sync + Send + Clone + Hash + TreeKey,
+    V: 'static + Sync + Send,
+    S: BuildHasher,
+{
+    /// Inserts every key-value pair from `iter` into the map, under the guard of this reference.
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        self.insert_all(iter);
+    }
+}
+
+impl<'a, K, V, S> Extend<(&'a K, &'a V)> for HashMapRef<'_, K, V, S>
+where
+    K: 'static + Sync + Send + Copy + Hash + TreeKey,
+    V: 'static + Sync + Send + Copy,
+    S: BuildHasher,
+{
+    /// Inserts a copy of every key-value pair from `iter` into the map, under the guard of this
+    /// reference.
+    fn extend<T: IntoIterator<Item = (&'a K, &'a V)>>(&mut self, iter: T) {
+        self.insert_all(iter.into_iter().map(|(&key, &value)| (key, value)));
+    }
+}
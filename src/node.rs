@@ -1,19 +1,26 @@
+use crate::equivalent::{Comparable, TreeKey};
+use crate::primitives::{current, park, spin_loop, AtomicI64, AtomicU64, Mutex, Ordering, Thread};
 use crate::raw::Table;
-use core::sync::atomic::{spin_loop_hint, AtomicBool, AtomicI64, Ordering};
+use crate::reclaim::{retire, Garbage};
+use core::sync::atomic::AtomicBool;
 use crossbeam_epoch::{Atomic, Guard, Owned, Shared};
-use parking_lot::Mutex;
-use std::borrow::Borrow;
-use std::thread::{current, park, Thread};
 
 /// Entry in a bin.
 ///
 /// Will _generally_ be `Node`. Any entry that is not first in the bin, will be a `Node`.
 #[derive(Debug)]
-pub(crate) enum BinEntry<K, V> {
+pub enum BinEntry<K, V> {
+    /// An entry in a bin that is a linked list.
     Node(Node<K, V>),
+    /// The head of a bin that has been turned into a tree.
     Tree(TreeBin<K, V>),
+    /// An entry in a bin that has been turned into a tree.
     TreeNode(TreeNode<K, V>),
+    /// The head of a bin whose entries have been moved to the next table during a resize.
     Moved,
+    /// The head of an empty bin that a thread has reserved while it computes the value of the
+    /// first entry it is about to insert there.
+    Reserved(Reservation),
 }
 
 unsafe impl<K, V> Send for BinEntry<K, V>
@@ -35,7 +42,8 @@ where
 }
 
 impl<K, V> BinEntry<K, V> {
-    pub(crate) fn as_node(&self) -> Option<&Node<K, V>> {
+    /// Returns the node, if this is a [`BinEntry::Node`].
+    pub fn as_node(&self) -> Option<&Node<K, V>> {
         if let BinEntry::Node(ref n) = *self {
             Some(n)
         } else {
@@ -43,40 +51,75 @@ impl<K, V> BinEntry<K, V> {
         }
     }
 
-    pub(crate) fn as_tree_node(&self) -> Option<&TreeNode<K, V>> {
+    /// Returns the tree node, if this is a [`BinEntry::TreeNode`].
+    pub fn as_tree_node(&self) -> Option<&TreeNode<K, V>> {
         if let BinEntry::TreeNode(ref n) = *self {
             Some(n)
         } else {
             None
         }
     }
-    pub(crate) fn as_tree_bin(&self) -> Option<&TreeBin<K, V>> {
+
+    /// Returns the tree bin, if this is a [`BinEntry::Tree`].
+    pub fn as_tree_bin(&self) -> Option<&TreeBin<K, V>> {
         if let BinEntry::Tree(ref bin) = *self {
             Some(bin)
         } else {
             None
         }
     }
+
+    /// Returns the reservation, if this is a [`BinEntry::Reserved`].
+    pub fn as_reservation(&self) -> Option<&Reservation> {
+        if let BinEntry::Reserved(ref reservation) = *self {
+            Some(reservation)
+        } else {
+            None
+        }
+    }
+}
+
+/// A placeholder for the first entry of a bin, whose value is still being computed.
+///
+/// A reservation is locked from before it is placed into an empty bin until after it has been
+/// replaced again. Writers that come across it wait for its lock, and then find the bin changed.
+/// Readers treat a reserved bin as empty.
+#[derive(Debug)]
+pub struct Reservation {
+    pub(crate) lock: Mutex<()>,
+}
+
+impl Reservation {
+    pub(crate) fn new() -> Self {
+        Reservation {
+            lock: Mutex::new(()),
+        }
+    }
 }
 
 /// Key-value entry.
 #[derive(Debug)]
-pub(crate) struct Node<K, V> {
+pub struct Node<K, V> {
     pub(crate) hash: u64,
     pub(crate) key: K,
     pub(crate) value: Atomic<V>,
     pub(crate) next: Atomic<BinEntry<K, V>>,
     pub(crate) lock: Mutex<()>,
+    /// The entry's version, if the map keeps entry versions. See `Versions`.
+    pub(crate) version: AtomicU64,
 }
 
 impl<K, V> Node<K, V> {
-    pub(crate) fn new<AV>(hash: u64, key: K, value: AV) -> Self
+    /// Creates a node that is not linked to a next node.
+    pub fn new<AV>(hash: u64, key: K, value: AV) -> Self
     where
         AV: Into<Atomic<V>>,
     {
         Node::with_next(hash, key, value, Atomic::null())
     }
-    pub(crate) fn with_next<AV>(hash: u64, key: K, value: AV, next: Atomic<BinEntry<K, V>>) -> Self
+
+    /// Creates a node that is linked to `next`.
+    pub fn with_next<AV>(hash: u64, key: K, value: AV, next: Atomic<BinEntry<K, V>>) -> Self
     where
         AV: Into<Atomic<V>>,
     {
@@ -86,6 +129,160 @@ impl<K, V> Node<K, V> {
             value: value.into(),
             next,
             lock: Mutex::new(()),
+            version: AtomicU64::new(0),
+        }
+    }
+
+    /// Sets the version of a new node before it is inserted, if the map keeps versions.
+    pub(crate) fn versioned(self, version: Option<u64>) -> Self {
+        if let Some(version) = version {
+            self.version.store(version, Ordering::SeqCst);
+        }
+        self
+    }
+
+    /// Gives this node the same version as `other`, which it is a copy of.
+    pub(crate) fn copy_version(&self, other: &Node<K, V>) {
+        self.version
+            .store(other.version.load(Ordering::SeqCst), Ordering::SeqCst);
+    }
+
+    /// Replaces the node's value, and moves the entry to `version` if the map keeps versions.
+    ///
+    /// Must be called with the node's bin locked.
+    pub(crate) fn swap_value<'g>(
+        &self,
+        value: Shared<'g, V>,
+        version: Option<u64>,
+        guard: &'g Guard,
+    ) -> Shared<'g, V> {
+        match version {
+            None => self.value.swap(value, Ordering::SeqCst, guard),
+            Some(version) => {
+                // let readers know that the value no longer matches the version until we are done
+                self.version
+                    .store(version | VERSION_PENDING, Ordering::SeqCst);
+                let old = self.value.swap(value, Ordering::SeqCst, guard);
+                self.version.store(version, Ordering::SeqCst);
+                old
+            }
+        }
+    }
+
+    /// Returns a mutable reference to the node's value, and moves the entry to `version` if the
+    /// map keeps versions, since the value may be changed through the reference.
+    ///
+    /// # Safety
+    ///
+    /// The caller must have exclusive access to the map that holds the node, and must not hand out
+    /// any other reference to the value while the returned one is alive.
+    #[allow(clippy::mut_from_ref)]
+    pub(crate) unsafe fn value_mut<'a>(
+        &'a self,
+        version: Option<u64>,
+        guard: &'a Guard,
+    ) -> &'a mut V {
+        if let Some(version) = version {
+            self.version.store(version, Ordering::SeqCst);
+        }
+        let value = self.value.load(Ordering::SeqCst, guard);
+        assert!(!value.is_null());
+        &mut *(value.as_raw() as *mut V)
+    }
+
+    /// Returns `true` if the entry is at `version`, or if no particular version is required.
+    ///
+    /// Must be called with the node's bin locked.
+    pub(crate) fn has_version(&self, version: Option<u64>) -> bool {
+        match version {
+            Some(version) => self.version.load(Ordering::SeqCst) == version,
+            None => true,
+        }
+    }
+
+    /// Reads the node's value together with the version of the entry that it belongs to.
+    pub(crate) fn load_versioned<'g>(&self, guard: &'g Guard) -> (Shared<'g, V>, u64) {
+        loop {
+            let version = self.version.load(Ordering::SeqCst);
+            if version & VERSION_PENDING == 0 {
+                let value = self.value.load(Ordering::SeqCst, guard);
+                // if the version is unchanged, so is the value, since swap_value marks the
+                // version before changing the value
+                if self.version.load(Ordering::SeqCst) == version {
+                    return (value, version);
+                }
+            }
+            spin_loop();
+        }
+    }
+}
+
+/// Set in a node's version while its value is being replaced.
+const VERSION_PENDING: u64 = 1 << 63;
+
+#[cfg(feature = "raw")]
+impl<K, V> Node<K, V> {
+    /// Returns the hash of the node's key.
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// Returns the node's key.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Returns the pointer to the node's value.
+    pub fn value(&self) -> &Atomic<V> {
+        &self.value
+    }
+
+    /// Returns the pointer to the next entry in the node's bin.
+    pub fn next(&self) -> &Atomic<BinEntry<K, V>> {
+        &self.next
+    }
+
+    /// Runs `f` while holding the node's lock.
+    ///
+    /// The lock of the first node in a bin guards changes to the whole bin.
+    pub fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+        let _lock = self.lock.lock();
+        f()
+    }
+}
+
+/// The source of entry versions for a map.
+///
+/// Versions are handed out from a single counter, so that they increase every time an entry
+/// changes, even across the removal and re-insertion of its key.
+pub(crate) struct Versions {
+    enabled: bool,
+    last: AtomicU64,
+}
+
+impl Versions {
+    pub(crate) fn new(enabled: bool) -> Self {
+        Versions {
+            enabled,
+            last: AtomicU64::new(0),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Returns the version for a change that is about to be made, if versions are enabled.
+    ///
+    /// Must be called with the bin of the changed entry locked, so that later changes to the same
+    /// entry get later versions.
+    #[inline]
+    pub(crate) fn next(&self) -> Option<u64> {
+        if self.enabled {
+            Some(self.last.fetch_add(1, Ordering::SeqCst) + 1)
+        } else {
+            None
         }
     }
 }
@@ -94,7 +291,7 @@ impl<K, V> Node<K, V> {
 
 /// Nodes for use in TreeBins.
 #[derive(Debug)]
-pub(crate) struct TreeNode<K, V> {
+pub struct TreeNode<K, V> {
     // Node properties
     pub(crate) node: Node<K, V>,
 
@@ -128,6 +325,14 @@ impl<K, V> TreeNode<K, V> {
         }
     }
 
+    /// Sets the version of a new node before it is inserted, see [`Node::versioned`].
+    pub(crate) fn versioned(self, version: Option<u64>) -> Self {
+        if let Some(version) = version {
+            self.node.version.store(version, Ordering::SeqCst);
+        }
+        self
+    }
+
     /// Returns the `TreeNode` (or `Shared::null()` if not found) for the given
     /// key, starting at the given node.
     pub(crate) fn find_tree_node<'g, Q>(
@@ -137,8 +342,7 @@ impl<K, V> TreeNode<K, V> {
         guard: &'g Guard,
     ) -> Shared<'g, BinEntry<K, V>>
     where
-        K: Borrow<Q>,
-        Q: ?Sized + Ord,
+        Q: ?Sized + Comparable<K>,
     {
         // NOTE: in the Java code, this method is implemented on the `TreeNode`
         // instance directly, as they don't need to worry about shared pointers.
@@ -174,7 +378,7 @@ impl<K, V> TreeNode<K, V> {
             // if the hash matches, check if the given key also matches. If so,
             // we have found the target node.
             let p_key = &p_deref.node.key;
-            if p_key.borrow() == key {
+            if key.equivalent(p_key) {
                 return p;
             }
 
@@ -191,7 +395,7 @@ impl<K, V> TreeNode<K, V> {
             }
 
             // Otherwise, we compare keys to find the next child to look at.
-            p = match p_key.borrow().cmp(&key) {
+            p = match key.compare(p_key).reverse() {
                 std::cmp::Ordering::Greater => p_left,
                 std::cmp::Ordering::Less => p_right,
                 std::cmp::Ordering::Equal => {
@@ -209,6 +413,14 @@ impl<K, V> TreeNode<K, V> {
     }
 }
 
+#[cfg(feature = "raw")]
+impl<K, V> TreeNode<K, V> {
+    /// Returns the node that holds this tree node's key and value.
+    pub fn node(&self) -> &Node<K, V> {
+        &self.node
+    }
+}
+
 const WRITER: i64 = 1; // set while holding write lock
 const WAITER: i64 = 2; // set when waiting for write lock
 const READER: i64 = 4; // increment value for setting read lock
@@ -219,23 +431,153 @@ enum Dir {
     Right,
 }
 
+/// The most nodes a tree bin keeps a [`FlatIndex`] for.
+///
+/// Every change to a bin copies its index, so a bin that grows past this goes back to searching
+/// its tree alone.
+const FLAT_INDEX_LIMIT: usize = 256;
+
+/// The nodes of a tree bin in tree order, with their hashes laid out next to each other.
+///
+/// Lookups binary-search the hashes, which fit in a few cache lines, instead of chasing child
+/// pointers through the tree, and only dereference the nodes whose hash matches. An index is
+/// never changed in place: a writer that adds or removes a node publishes a changed copy and
+/// retires the old one, so readers that still hold the old index find its nodes kept alive by
+/// their guard.
+#[derive(Debug)]
+pub(crate) struct FlatIndex<K, V> {
+    hashes: Box<[u64]>,
+    nodes: Box<[Atomic<BinEntry<K, V>>]>,
+}
+
+impl<K, V> FlatIndex<K, V> {
+    /// Indexes the given nodes, which must be sorted by hash and then by key.
+    ///
+    /// # Safety
+    /// The nodes must be valid TreeNodes.
+    unsafe fn new(nodes: &[Shared<'_, BinEntry<K, V>>]) -> Self {
+        FlatIndex {
+            hashes: nodes
+                .iter()
+                .map(|&node| TreeNode::get_tree_node(node).node.hash)
+                .collect(),
+            nodes: nodes.iter().map(|&node| Atomic::from(node)).collect(),
+        }
+    }
+
+    /// Returns the positions of the nodes with the given hash.
+    fn hash_range(&self, hash: u64) -> std::ops::Range<usize> {
+        let start = self
+            .hashes
+            .binary_search_by(|&h| h.cmp(&hash).then(std::cmp::Ordering::Greater))
+            .unwrap_err();
+        let end = self
+            .hashes
+            .binary_search_by(|&h| h.cmp(&hash).then(std::cmp::Ordering::Less))
+            .unwrap_err();
+        start..end
+    }
+
+    /// Returns the indexed node for the given key, or `Shared::null()` if there is none.
+    fn find<'g, Q>(&'g self, hash: u64, key: &Q, guard: &'g Guard) -> Shared<'g, BinEntry<K, V>>
+    where
+        Q: ?Sized + Comparable<K>,
+    {
+        let candidates = &self.nodes[self.hash_range(hash)];
+        // safety: the index was read under our guard, and the nodes it points to are only
+        // retired after the index that points to them is.
+        // The index was filled before it was published, so relaxed loads see its nodes.
+        candidates
+            .binary_search_by(|node| {
+                let node = unsafe { TreeNode::get_tree_node(node.load(Ordering::Relaxed, guard)) };
+                key.compare(&node.node.key).reverse()
+            })
+            .map_or(Shared::null(), |i| {
+                candidates[i].load(Ordering::Relaxed, guard)
+            })
+    }
+
+    /// Returns a copy of the index that also holds `x`, or `None` if that would grow the index
+    /// past [`FLAT_INDEX_LIMIT`].
+    ///
+    /// # Safety
+    /// `x` and the indexed nodes must be valid TreeNodes.
+    unsafe fn with<'g>(&self, x: Shared<'g, BinEntry<K, V>>, guard: &'g Guard) -> Option<Self>
+    where
+        K: TreeKey,
+    {
+        if self.hashes.len() >= FLAT_INDEX_LIMIT {
+            return None;
+        }
+        let x_deref = TreeNode::get_tree_node(x);
+        let range = self.hash_range(x_deref.node.hash);
+        let at = range.start
+            + self.nodes[range]
+                .binary_search_by(|node| {
+                    let node = TreeNode::get_tree_node(node.load(Ordering::Relaxed, guard));
+                    node.node.key.tree_cmp(&x_deref.node.key)
+                })
+                .unwrap_err();
+        let mut nodes = Vec::with_capacity(self.nodes.len() + 1);
+        nodes.extend(
+            self.nodes[..at]
+                .iter()
+                .map(|n| n.load(Ordering::Relaxed, guard)),
+        );
+        nodes.push(x);
+        nodes.extend(
+            self.nodes[at..]
+                .iter()
+                .map(|n| n.load(Ordering::Relaxed, guard)),
+        );
+        Some(Self::new(&nodes))
+    }
+
+    /// Returns a copy of the index without `p`, which must be indexed.
+    ///
+    /// # Safety
+    /// `p` must be a valid TreeNode.
+    unsafe fn without<'g>(&self, p: Shared<'g, BinEntry<K, V>>, guard: &'g Guard) -> Self {
+        let at = self
+            .hash_range(TreeNode::get_tree_node(p).node.hash)
+            .find(|&i| self.nodes[i].load(Ordering::Relaxed, guard) == p)
+            .expect("an indexed tree bin indexes all of its nodes");
+        let mut hashes = Vec::with_capacity(self.hashes.len() - 1);
+        hashes.extend_from_slice(&self.hashes[..at]);
+        hashes.extend_from_slice(&self.hashes[at + 1..]);
+        let nodes = self.nodes[..at]
+            .iter()
+            .chain(&self.nodes[at + 1..])
+            .map(|n| Atomic::from(n.load(Ordering::Relaxed, guard)))
+            .collect();
+        FlatIndex {
+            hashes: hashes.into_boxed_slice(),
+            nodes,
+        }
+    }
+}
+
 /// TreeNodes used at the heads of bins. TreeBins do not hold user keys or
 /// values, but instead point to a list of TreeNodes and their root. They also
 /// maintain a parasitic read-write lock forcing writers (who hold the bin lock)
 /// to wait for readers (who do not) to complete before tree restructuring
 /// operations.
+///
+/// Bins with at most [`FLAT_INDEX_LIMIT`] nodes also keep a [`FlatIndex`] of them, which readers
+/// search instead of the tree.
 #[derive(Debug)]
-pub(crate) struct TreeBin<K, V> {
+pub struct TreeBin<K, V> {
     pub(crate) root: Atomic<BinEntry<K, V>>,
     pub(crate) first: Atomic<BinEntry<K, V>>,
+    pub(crate) index: Atomic<FlatIndex<K, V>>,
     pub(crate) waiter: Atomic<Thread>,
-    pub(crate) lock: parking_lot::Mutex<()>,
+    pub(crate) lock: Mutex<()>,
     pub(crate) lock_state: AtomicI64,
 }
 
 impl<K, V> TreeBin<K, V>
 where
-    K: Ord,
+    K: TreeKey,
 {
     /// Constructs a new bin from the given nodes.
     ///
@@ -278,7 +620,7 @@ where
                 // to descend the tree through this successor.
                 let xp = p;
                 let dir;
-                p = match p_hash.cmp(&hash).then(p_key.cmp(&key)) {
+                p = match p_hash.cmp(&hash).then(p_key.tree_cmp(&key)) {
                     std::cmp::Ordering::Greater => {
                         dir = Dir::Left;
                         &p_deref.left
@@ -316,11 +658,37 @@ where
         if cfg!(debug_assertions) {
             TreeNode::check_invariants(root, guard);
         }
+
+        // index the nodes if there are few enough of them
+        let mut nodes = Vec::new();
+        let mut x = bin;
+        while !x.is_null() && nodes.len() <= FLAT_INDEX_LIMIT {
+            nodes.push(x);
+            x = unsafe { TreeNode::get_tree_node(x) }
+                .node
+                .next
+                .load(Ordering::Relaxed, guard);
+        }
+        let index = if nodes.len() <= FLAT_INDEX_LIMIT {
+            nodes.sort_by(|&a, &b| {
+                let a = unsafe { TreeNode::get_tree_node(a) };
+                let b = unsafe { TreeNode::get_tree_node(b) };
+                a.node
+                    .hash
+                    .cmp(&b.node.hash)
+                    .then(a.node.key.tree_cmp(&b.node.key))
+            });
+            Atomic::new(unsafe { FlatIndex::new(&nodes) })
+        } else {
+            Atomic::null()
+        };
+
         TreeBin {
             root: Atomic::from(root),
             first: Atomic::from(bin),
+            index,
             waiter: Atomic::null(),
-            lock: parking_lot::Mutex::new(()),
+            lock: Mutex::new(()),
             lock_state: AtomicI64::new(0),
         }
     }
@@ -379,7 +747,7 @@ impl<K, V> TreeBin<K, V> {
                         // the waiter, and then went directly into this branch. In that case, some
                         // other thread may simultaneously have noticed that we wanted to be woken
                         // up, and be trying to call `.unpark`. So, we `defer_destroy` instead.
-                        unsafe { guard.defer_destroy(waiter) };
+                        unsafe { retire(guard, waiter) };
                     }
                     return;
                 }
@@ -399,7 +767,7 @@ impl<K, V> TreeBin<K, V> {
             } else if waiting {
                 park();
             }
-            spin_loop_hint();
+            spin_loop();
         }
     }
 
@@ -413,8 +781,7 @@ impl<K, V> TreeBin<K, V> {
         guard: &'g Guard,
     ) -> Shared<'g, BinEntry<K, V>>
     where
-        K: Borrow<Q>,
-        Q: ?Sized + Ord,
+        Q: ?Sized + Comparable<K>,
     {
         // safety: bin is a valid pointer.
         //
@@ -434,6 +801,12 @@ impl<K, V> TreeBin<K, V> {
         // pinning the epoch, the drop must happen in the _next_ epoch (i.e., the one that we
         // are holding up by holding on to our guard).
         let bin_deref = unsafe { bin.deref() }.as_tree_bin().unwrap();
+        let index = bin_deref.index.load(Ordering::SeqCst, guard);
+        if !index.is_null() {
+            // safety: an index is only retired after a new one has replaced it in the bin,
+            // which cannot have happened before we read it under our guard.
+            return unsafe { index.deref() }.find(hash, key, guard);
+        }
         let mut element = bin_deref.first.load(Ordering::SeqCst, guard);
         while !element.is_null() {
             let s = bin_deref.lock_state.load(Ordering::SeqCst);
@@ -450,7 +823,7 @@ impl<K, V> TreeBin<K, V> {
                 // Structurally, TreeNodes always point to TreeNodes, so this is sound.
                 let element_deref = unsafe { TreeNode::get_tree_node(element) };
                 let element_key = &element_deref.node.key;
-                if element_deref.node.hash == hash && element_key.borrow() == key {
+                if element_deref.node.hash == hash && key.equivalent(element_key) {
                     return element;
                 }
                 element = element_deref.node.next.load(Ordering::SeqCst, guard);
@@ -467,19 +840,7 @@ impl<K, V> TreeBin<K, V> {
                 } else {
                     TreeNode::find_tree_node(root, hash, key, guard)
                 };
-                if bin_deref.lock_state.fetch_add(-READER, Ordering::SeqCst) == (READER | WAITER) {
-                    // we were the last reader holding up a waiting writer, so
-                    // we unpark the waiting writer by granting it a token
-                    let waiter = &bin_deref.waiter.load(Ordering::SeqCst, guard);
-                    if !waiter.is_null() {
-                        // safety: thread handles are only dropped by the thread
-                        // they represent _after_ it acquires the write lock.
-                        // Since the thread behind the `waiter` handle is
-                        // currently _waiting_ on said lock, the handle will not
-                        // yet be dropped.
-                        unsafe { waiter.deref() }.unpark();
-                    }
-                }
+                bin_deref.unlock_read(guard);
                 return p;
             }
         }
@@ -487,6 +848,86 @@ impl<K, V> TreeBin<K, V> {
         Shared::null()
     }
 
+    /// Returns the height of the tree, counting the root and the nodes below it.
+    ///
+    /// Unlike [`find`](TreeBin::find), this cannot fall back to the list of nodes while the tree
+    /// is being restructured, so it waits for any writer to finish first.
+    pub(crate) fn depth(&self, guard: &Guard) -> usize {
+        loop {
+            let s = self.lock_state.load(Ordering::SeqCst);
+            if s & (WAITER | WRITER) != 0 {
+                spin_loop();
+            } else if self
+                .lock_state
+                .compare_and_swap(s, s + READER, Ordering::SeqCst)
+                == s
+            {
+                break;
+            }
+        }
+        let depth = TreeNode::height(self.root.load(Ordering::SeqCst, guard), guard);
+        self.unlock_read(guard);
+        depth
+    }
+
+    /// Returns the number of bytes taken up by the bin and its nodes, not counting the values
+    /// of the nodes.
+    pub(crate) fn footprint(&self, guard: &Guard) -> usize {
+        // readers may always walk the nodes of a tree bin as a list.
+        let mut nodes = 0;
+        let mut p = self.first.load(Ordering::SeqCst, guard);
+        while !p.is_null() {
+            nodes += 1;
+            // safety: the nodes of a tree bin are only dropped once the bin is, which cannot
+            // happen until our guard is dropped, and they always point to TreeNodes.
+            p = unsafe { TreeNode::get_tree_node(p) }
+                .node
+                .next
+                .load(Ordering::SeqCst, guard);
+        }
+        let index = self.index.load(Ordering::SeqCst, guard);
+        let index = if index.is_null() {
+            0
+        } else {
+            std::mem::size_of::<FlatIndex<K, V>>()
+                + nodes
+                    * (std::mem::size_of::<u64>() + std::mem::size_of::<Atomic<BinEntry<K, V>>>())
+        };
+        (1 + nodes) * std::mem::size_of::<BinEntry<K, V>>() + index
+    }
+
+    /// Replaces the bin's index with `index`, and retires the old one.
+    ///
+    /// The caller must hold the bin lock.
+    fn replace_index(&self, index: Option<FlatIndex<K, V>>, guard: &Guard) {
+        let old = match index {
+            Some(index) => self.index.swap(Owned::new(index), Ordering::SeqCst, guard),
+            None => self.index.swap(Shared::null(), Ordering::SeqCst, guard),
+        };
+        if !old.is_null() {
+            // safety: the old index is no longer reachable from the bin, and readers that
+            // still hold it read it under a guard that keeps it alive.
+            unsafe { retire(guard, old) };
+        }
+    }
+
+    /// Releases a read lock, and wakes up a writer that waits for it if there is one.
+    fn unlock_read(&self, guard: &Guard) {
+        if self.lock_state.fetch_add(-READER, Ordering::SeqCst) == (READER | WAITER) {
+            // we were the last reader holding up a waiting writer, so
+            // we unpark the waiting writer by granting it a token
+            let waiter = &self.waiter.load(Ordering::SeqCst, guard);
+            if !waiter.is_null() {
+                // safety: thread handles are only dropped by the thread
+                // they represent _after_ it acquires the write lock.
+                // Since the thread behind the `waiter` handle is
+                // currently _waiting_ on said lock, the handle will not
+                // yet be dropped.
+                unsafe { waiter.deref() }.unpark();
+            }
+        }
+    }
+
     /// Unlinks the given node, which must be present before this call.
     ///
     /// This is messier than typical red-black deletion code because we cannot
@@ -513,6 +954,7 @@ impl<K, V> TreeBin<K, V> {
         &'g self,
         p: Shared<'g, BinEntry<K, V>>,
         drop_value: bool,
+        garbage: &Garbage,
         guard: &'g Guard,
     ) -> bool {
         // safety: we were read under our guard, at which point the tree
@@ -539,6 +981,10 @@ impl<K, V> TreeBin<K, V> {
                 .prev
                 .store(prev, Ordering::SeqCst);
         }
+        let index = self.index.load(Ordering::SeqCst, guard);
+        if !index.is_null() {
+            self.replace_index(Some(index.deref().without(p, guard)), guard);
+        }
 
         if self.first.load(Ordering::SeqCst, guard).is_null() {
             // since the bin was not empty previously (it contained p),
@@ -758,9 +1204,9 @@ impl<K, V> TreeBin<K, V> {
         #[allow(unused_unsafe)]
         unsafe {
             if drop_value {
-                guard.defer_destroy(p_deref.node.value.load(Ordering::Relaxed, guard));
+                garbage.retire(guard, p_deref.node.value.load(Ordering::Relaxed, guard));
             }
-            guard.defer_destroy(p);
+            garbage.retire(guard, p);
         }
 
         if cfg!(debug_assertions) {
@@ -772,7 +1218,7 @@ impl<K, V> TreeBin<K, V> {
 
 impl<K, V> TreeBin<K, V>
 where
-    K: Ord + Send + Sync,
+    K: TreeKey + Send + Sync,
 {
     /// Finds or adds a node to the tree.
     /// If a node for the given key already exists, it is returned. Otherwise,
@@ -782,22 +1228,25 @@ where
         hash: u64,
         key: K,
         value: Shared<'g, V>,
+        version: Option<u64>,
         guard: &'g Guard,
     ) -> Shared<'g, BinEntry<K, V>> {
         let mut p = self.root.load(Ordering::SeqCst, guard);
         if p.is_null() {
             // the current root is `null`, i.e. the tree is currently empty.
             // This, we simply insert the new entry as the root.
-            let tree_node = Owned::new(BinEntry::TreeNode(TreeNode::new(
+            let tree_node = TreeNode::new(
                 hash,
                 key,
                 Atomic::from(value),
                 Atomic::null(),
                 Atomic::null(),
-            )))
-            .into_shared(guard);
+            )
+            .versioned(version);
+            let tree_node = Owned::new(BinEntry::TreeNode(tree_node)).into_shared(guard);
             self.root.store(tree_node, Ordering::Release);
             self.first.store(tree_node, Ordering::Release);
+            self.index_insert(tree_node, guard);
             return Shared::null();
         }
         // safety: we were read under our guard, at which point the tree
@@ -825,7 +1274,7 @@ where
                         // a node with the given key already exists, so we return it
                         return p;
                     }
-                    match p_key.cmp(&key) {
+                    match p_key.tree_cmp(&key) {
                         std::cmp::Ordering::Greater => {
                             dir = Dir::Left;
                             &p_deref.left
@@ -854,14 +1303,15 @@ where
                 // position (which is here, since we arrived here by comparing
                 // hash and key of the new entry)
                 let first = self.first.load(Ordering::SeqCst, guard);
-                let x = Owned::new(BinEntry::TreeNode(TreeNode::new(
+                let x = TreeNode::new(
                     hash,
                     key,
                     Atomic::from(value),
                     Atomic::from(first),
                     Atomic::from(xp),
-                )))
-                .into_shared(guard);
+                )
+                .versioned(version);
+                let x = Owned::new(BinEntry::TreeNode(x)).into_shared(guard);
                 self.first.store(x, Ordering::SeqCst);
                 if !first.is_null() {
                     unsafe { TreeNode::get_tree_node(first) }
@@ -900,6 +1350,7 @@ where
                     );
                     self.unlock_root();
                 }
+                self.index_insert(x, guard);
                 break;
             }
         }
@@ -909,6 +1360,17 @@ where
         }
         Shared::null()
     }
+
+    /// Adds the new node `x` to the bin's index, or drops the index if the bin has outgrown it.
+    fn index_insert<'g>(&'g self, x: Shared<'g, BinEntry<K, V>>, guard: &'g Guard) {
+        let index = self.index.load(Ordering::SeqCst, guard);
+        if !index.is_null() {
+            // safety: we hold the bin lock, so the index and its nodes are valid, and so is the
+            // node we just inserted.
+            let index = unsafe { index.deref().with(x, guard) };
+            self.replace_index(index, guard);
+        }
+    }
 }
 
 impl<K, V> Drop for TreeBin<K, V> {
@@ -929,9 +1391,16 @@ impl<K, V> TreeBin<K, V> {
     /// method.
     pub(crate) unsafe fn defer_drop_without_values<'g>(
         bin: Shared<'g, BinEntry<K, V>>,
+        garbage: &Garbage,
         guard: &'g Guard,
     ) {
-        guard.defer_unchecked(move || {
+        // the values stay in the map, so only the bin and its nodes are garbage
+        let bytes = if cfg!(feature = "garbage-stats") {
+            bin.deref().as_tree_bin().unwrap().footprint(guard)
+        } else {
+            0
+        };
+        garbage.retire_with(guard, bytes, move || {
             if let BinEntry::Tree(mut tree_bin) = *bin.into_owned().into_box() {
                 tree_bin.drop_fields(false);
             } else {
@@ -954,6 +1423,10 @@ impl<K, V> TreeBin<K, V> {
         // swap out first pointer so nodes will not get dropped again when
         // `tree_bin` is dropped
         let guard = crossbeam_epoch::unprotected();
+        let index = self.index.swap(Shared::null(), Ordering::Relaxed, guard);
+        if !index.is_null() {
+            drop(index.into_owned());
+        }
         let p = self.first.swap(Shared::null(), Ordering::Relaxed, guard);
         Self::drop_tree_nodes(p, drop_values, guard);
     }
@@ -999,6 +1472,23 @@ impl<K, V> TreeNode<K, V> {
     pub(crate) unsafe fn get_tree_node<'g>(bin: Shared<'g, BinEntry<K, V>>) -> &'g TreeNode<K, V> {
         bin.deref().as_tree_node().unwrap()
     }
+
+    /// Returns the height of the subtree below `p`, which is 0 if `p` is null.
+    ///
+    /// The caller must hold the read lock of the tree bin, so that the tree is not restructured
+    /// while it walks it.
+    fn height<'g>(p: Shared<'g, BinEntry<K, V>>, guard: &'g Guard) -> usize {
+        if p.is_null() {
+            return 0;
+        }
+        // safety: the nodes of a tree bin are only dropped once they are no longer reachable from
+        // its root, which cannot happen until our guard is dropped, and they always point to
+        // TreeNodes.
+        let p_deref = unsafe { Self::get_tree_node(p) };
+        let left = Self::height(p_deref.left.load(Ordering::SeqCst, guard), guard);
+        let right = Self::height(p_deref.right.load(Ordering::SeqCst, guard), guard);
+        1 + std::cmp::max(left, right)
+    }
 }
 
 /* ----------------------------------------------------------------- */
@@ -1457,6 +1947,7 @@ mod tests {
             value: Atomic::new(value),
             next: Atomic::null(),
             lock: Mutex::new(()),
+            version: AtomicU64::new(0),
         }
     }
 
@@ -1578,4 +2069,138 @@ mod tests {
         unsafe { table2.deref_mut() }.drop_bins();
         unsafe { guard.defer_destroy(table2) };
     }
+
+    /// Builds a tree bin of the given keys, hashing each key to itself modulo `hashes`.
+    #[cfg(not(feature = "unordered-keys"))]
+    fn tree_bin<'g>(
+        keys: std::ops::Range<usize>,
+        hashes: usize,
+        guard: &'g Guard,
+    ) -> Shared<'g, BinEntry<usize, usize>> {
+        let mut head = Shared::null();
+        let mut tail = Shared::null();
+        for key in keys {
+            let node = TreeNode::new(
+                (key % hashes) as u64,
+                key,
+                Atomic::new(key),
+                Atomic::null(),
+                Atomic::null(),
+            );
+            node.prev.store(tail, Ordering::Relaxed);
+            let node = Owned::new(BinEntry::TreeNode(node)).into_shared(guard);
+            if tail.is_null() {
+                head = node;
+            } else {
+                // safety: we have just created `tail` in the last iteration
+                unsafe { TreeNode::get_tree_node(tail) }
+                    .node
+                    .next
+                    .store(node, Ordering::Relaxed);
+            }
+            tail = node;
+        }
+        // safety: we have just created the nodes and have never shared them
+        let bin = TreeBin::new(unsafe { head.into_owned() }, guard);
+        Owned::new(BinEntry::Tree(bin)).into_shared(guard)
+    }
+
+    #[cfg(not(feature = "unordered-keys"))]
+    fn get<'g>(
+        bin: Shared<'g, BinEntry<usize, usize>>,
+        key: usize,
+        hashes: usize,
+        guard: &'g Guard,
+    ) -> Option<usize> {
+        let p = TreeBin::find(bin, (key % hashes) as u64, &key, guard);
+        if p.is_null() {
+            None
+        } else {
+            // safety: the bin was not dropped, and neither were its nodes' values
+            let value = unsafe { TreeNode::get_tree_node(p) }
+                .node
+                .value
+                .load(Ordering::SeqCst, guard);
+            Some(*unsafe { value.deref() })
+        }
+    }
+
+    #[cfg(not(feature = "unordered-keys"))]
+    fn indexed(bin: &TreeBin<usize, usize>, guard: &Guard) -> Option<usize> {
+        let index = bin.index.load(Ordering::SeqCst, guard);
+        // safety: the bin was not dropped
+        unsafe { index.as_ref() }.map(|index| index.hashes.len())
+    }
+
+    #[test]
+    #[cfg(not(feature = "unordered-keys"))]
+    fn tree_bin_index() {
+        let guard = &crossbeam_epoch::pin();
+        // with few distinct hashes, lookups have to tell keys with the same hash apart
+        let bin = tree_bin(0..100, 7, guard);
+        // safety: the bin is valid until we drop it below
+        let tree = unsafe { bin.deref() }.as_tree_bin().unwrap();
+        assert_eq!(indexed(tree, guard), Some(100));
+        for key in 0..100 {
+            assert_eq!(get(bin, key, 7, guard), Some(key));
+        }
+        assert_eq!(get(bin, 100, 7, guard), None);
+
+        // the index follows insertions and removals
+        for key in 100..150 {
+            let value = Owned::new(key).into_shared(guard);
+            let p = tree.find_or_put_tree_val((key % 7) as u64, key, value, None, guard);
+            assert!(p.is_null());
+        }
+        let garbage = Garbage::new();
+        for key in (0..150).step_by(3) {
+            let p = TreeBin::find(bin, (key % 7) as u64, &key, guard);
+            // safety: `p` is in the bin, and the bin stays large enough to remain a tree
+            assert!(!unsafe { tree.remove_tree_node(p, true, &garbage, guard) });
+        }
+        assert_eq!(indexed(tree, guard), Some(100));
+        for key in 0..150 {
+            let expected = if key % 3 == 0 { None } else { Some(key) };
+            assert_eq!(get(bin, key, 7, guard), expected);
+        }
+
+        // safety: no other thread has seen the bin
+        drop(unsafe { bin.into_owned() });
+    }
+
+    #[test]
+    #[cfg(not(feature = "unordered-keys"))]
+    fn tree_bin_outgrows_index() {
+        let guard = &crossbeam_epoch::pin();
+        let bin = tree_bin(0..FLAT_INDEX_LIMIT, 7, guard);
+        // safety: the bin is valid until we drop it below
+        let tree = unsafe { bin.deref() }.as_tree_bin().unwrap();
+        assert_eq!(indexed(tree, guard), Some(FLAT_INDEX_LIMIT));
+
+        // one more node, and lookups go back to the tree
+        let key = FLAT_INDEX_LIMIT;
+        let value = Owned::new(key).into_shared(guard);
+        assert!(tree
+            .find_or_put_tree_val((key % 7) as u64, key, value, None, guard)
+            .is_null());
+        assert_eq!(indexed(tree, guard), None);
+        for key in 0..=FLAT_INDEX_LIMIT {
+            assert_eq!(get(bin, key, 7, guard), Some(key));
+        }
+        // safety: no other thread has seen the bin
+        drop(unsafe { bin.into_owned() });
+
+        // a bin that starts out too large is never indexed
+        let bin = tree_bin(0..FLAT_INDEX_LIMIT + 1, 7, guard);
+        // safety: the bin is valid until we drop it below
+        assert_eq!(
+            indexed(unsafe { bin.deref() }.as_tree_bin().unwrap(), guard),
+            None
+        );
+        for key in 0..=FLAT_INDEX_LIMIT {
+            assert_eq!(get(bin, key, 7, guard), Some(key));
+        }
+        // safety: no other thread has seen the bin
+        drop(unsafe { bin.into_owned() });
+    }
 }
@@ -0,0 +1,86 @@
+use crate::watch::{Watch, Watchers};
+use std::sync::Arc;
+
+/// A change to a single entry of a map.
+pub(crate) enum Change<'a, K, V> {
+    /// `key` was not in the map, and now maps to `value`.
+    Inserted { key: &'a K, value: &'a V },
+    /// `key` mapped to `old`, and now maps to `value`.
+    Replaced {
+        key: &'a K,
+        old: &'a V,
+        value: &'a V,
+    },
+    /// `key` mapped to `old`, and was removed from the map.
+    Removed { key: &'a K, old: &'a V },
+    /// `key` maps to `value`, and a mutable reference to `value` is being handed out, such as by
+    /// `HashMap::get_mut`.
+    ///
+    /// The value may be changed in place through that reference, and the map never learns what
+    /// it was changed to. Until the key is next replaced or removed, its value is unknown.
+    Lent { key: &'a K, value: &'a V },
+}
+
+impl<K, V> Clone for Change<'_, K, V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<K, V> Copy for Change<'_, K, V> {}
+
+/// Something that wants to learn about every change made to a map.
+///
+/// Observers are notified while the lock of the bin that contains the changed key is held. This
+/// means that all changes to the same key are observed in the order in which they took effect, but
+/// also that observers block other writers to that bin, and so should do as little as possible.
+/// Observers must not access the map they are observing.
+pub(crate) trait Observer<K, V>: Send + Sync {
+    fn observe(&self, change: Change<'_, K, V>);
+}
+
+/// The set of observers registered with a map, along with any pending [`Watch`]es.
+pub(crate) struct Observers<K, V> {
+    observers: Vec<Arc<dyn Observer<K, V>>>,
+    watchers: Watchers<K>,
+}
+
+impl<K, V> Observers<K, V> {
+    pub(crate) fn new() -> Self {
+        Observers {
+            observers: Vec::new(),
+            watchers: Watchers::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, observer: Arc<dyn Observer<K, V>>) {
+        self.observers.push(observer);
+    }
+
+    pub(crate) fn watch(&self, key: K) -> Watch {
+        self.watchers.watch(key)
+    }
+
+    /// Returns whether any observers need to be notified of changes.
+    #[inline]
+    pub(crate) fn is_active(&self) -> bool {
+        !self.observers.is_empty() || self.watchers.is_active()
+    }
+}
+
+impl<K: Eq, V> Observers<K, V> {
+    pub(crate) fn notify(&self, change: Change<'_, K, V>) {
+        for observer in &self.observers {
+            observer.observe(change);
+        }
+        if self.watchers.is_active() {
+            let key = match change {
+                Change::Inserted { key, .. }
+                | Change::Replaced { key, .. }
+                | Change::Removed { key, .. }
+                | Change::Lent { key, .. } => key,
+            };
+            self.watchers.notify(key);
+        }
+    }
+}
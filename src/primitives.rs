@@ -0,0 +1,314 @@
+//! Synchronization primitives used by the map internals.
+//!
+//! Every atomic counter, lock, spin, and park the map relies on is taken from this module rather
+//! than from `std` or `parking_lot` directly. This keeps the set of points at which threads
+//! synchronize in one place, so that the crate can be retargeted at a different implementation of
+//! these primitives (such as the ones provided by a deterministic scheduler) without touching the
+//! algorithm itself.
+//!
+//! Without the `schedule-hooks` feature, these are plain re-exports and compile down to exactly
+//! what the map used before. With the feature, the types are thin wrappers that first report the
+//! operation they are about to perform to [`crate::schedule`].
+//!
+//! The `Mutex` here is only used for bin locks. By default it is a `parking_lot::Mutex`, which
+//! spins for a short while before parking a waiting thread. With the `spin-bin-locks` feature, it
+//! is a lock that never parks, and instead keeps spinning (and eventually yielding) until the lock
+//! is released.
+
+use parking_lot::lock_api;
+pub(crate) use std::sync::atomic::Ordering;
+pub(crate) use std::thread::{current, Thread};
+
+/// The raw lock behind every bin lock.
+#[cfg(not(feature = "spin-bin-locks"))]
+type RawBinLock = parking_lot::RawMutex;
+#[cfg(feature = "spin-bin-locks")]
+type RawBinLock = spin::RawSpinLock;
+
+/// Holds a bin lock until it is dropped.
+pub(crate) type MutexGuard<'a, T> = lock_api::MutexGuard<'a, RawBinLock, T>;
+
+#[cfg(not(feature = "schedule-hooks"))]
+pub(crate) type Mutex<T> = lock_api::Mutex<RawBinLock, T>;
+#[cfg(not(feature = "schedule-hooks"))]
+pub(crate) use std::sync::atomic::{AtomicI64, AtomicIsize, AtomicU64};
+#[cfg(not(feature = "schedule-hooks"))]
+pub(crate) use std::thread::{park, park_timeout, yield_now};
+
+#[cfg(feature = "schedule-hooks")]
+pub(crate) use hooked::*;
+
+/// Reports that the current thread is about to perform the operation at the given
+/// [`crate::schedule::Point`]. Expands to nothing without the `schedule-hooks` feature.
+macro_rules! yield_point {
+    ($point:ident) => {
+        #[cfg(feature = "schedule-hooks")]
+        crate::schedule::call(crate::schedule::Point::$point);
+    };
+}
+
+/// Runs the action configured for the named fail point, if any. Expands to nothing without the
+/// `failpoints` feature.
+macro_rules! fail_point {
+    ($name:expr) => {
+        #[cfg(feature = "failpoints")]
+        crate::failpoints::eval($name);
+    };
+}
+
+/// Like `fail_point!`, but evaluates to `true` if the fail point is configured to make the
+/// operation fail with [`Action::Fail`](crate::failpoints::Action::Fail).
+macro_rules! fail_point_fails {
+    ($name:expr) => {{
+        #[cfg(feature = "failpoints")]
+        let fail = crate::failpoints::eval($name);
+        #[cfg(not(feature = "failpoints"))]
+        let fail = false;
+        fail
+    }};
+}
+
+/// Signals that the current thread is busy-waiting on another thread.
+#[inline]
+pub(crate) fn spin_loop() {
+    yield_point!(Spin);
+    #[allow(deprecated)]
+    std::sync::atomic::spin_loop_hint();
+}
+
+#[cfg(feature = "schedule-hooks")]
+mod hooked {
+    use std::sync::atomic::Ordering;
+
+    pub(crate) fn yield_now() {
+        yield_point!(Yield);
+        std::thread::yield_now()
+    }
+
+    /// Parks the current thread, unless a hook is installed, in which case this returns right
+    /// after reporting the park, like a spurious wakeup.
+    pub(crate) fn park() {
+        yield_point!(Park);
+        if !crate::schedule::installed() {
+            std::thread::park()
+        }
+    }
+
+    /// Like [`park`], but gives up waiting after `timeout`.
+    pub(crate) fn park_timeout(timeout: std::time::Duration) {
+        yield_point!(Park);
+        if !crate::schedule::installed() {
+            std::thread::park_timeout(timeout)
+        }
+    }
+
+    use super::{lock_api, MutexGuard, RawBinLock};
+
+    /// A bin lock that reports each acquisition.
+    ///
+    /// While a hook is installed, a thread that waits for the lock does not block, but retries
+    /// after yielding through the hook, since the scheduler behind the hook may have stopped the
+    /// thread that holds the lock.
+    #[derive(Debug, Default)]
+    pub(crate) struct Mutex<T>(lock_api::Mutex<RawBinLock, T>);
+
+    impl<T> Mutex<T> {
+        pub(crate) fn new(value: T) -> Self {
+            Self(lock_api::Mutex::new(value))
+        }
+
+        pub(crate) fn lock(&self) -> MutexGuard<'_, T> {
+            yield_point!(Lock);
+            if !crate::schedule::installed() {
+                return self.0.lock();
+            }
+            loop {
+                if let Some(guard) = self.0.try_lock() {
+                    return guard;
+                }
+                yield_now();
+            }
+        }
+
+        pub(crate) fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+            yield_point!(Lock);
+            self.0.try_lock()
+        }
+
+        pub(crate) fn try_lock_until(
+            &self,
+            deadline: std::time::Instant,
+        ) -> Option<MutexGuard<'_, T>> {
+            yield_point!(Lock);
+            if !crate::schedule::installed() {
+                return self.0.try_lock_until(deadline);
+            }
+            loop {
+                if let Some(guard) = self.0.try_lock() {
+                    return Some(guard);
+                }
+                if std::time::Instant::now() >= deadline {
+                    return None;
+                }
+                yield_now();
+            }
+        }
+
+        pub(crate) unsafe fn force_unlock(&self) {
+            self.0.force_unlock()
+        }
+    }
+
+    // `compare_and_swap` only accepts a single ordering; this picks the failure ordering the same
+    // way the standard library did before it was deprecated.
+    fn failure_ordering(order: Ordering) -> Ordering {
+        match order {
+            Ordering::Release | Ordering::Relaxed => Ordering::Relaxed,
+            Ordering::AcqRel | Ordering::Acquire => Ordering::Acquire,
+            _ => Ordering::SeqCst,
+        }
+    }
+
+    macro_rules! hooked_atomic {
+        ($name:ident, $int:ty) => {
+            /// An atomic integer that reports each access.
+            #[derive(Debug, Default)]
+            pub(crate) struct $name(std::sync::atomic::$name);
+
+            impl $name {
+                pub(crate) const fn new(v: $int) -> Self {
+                    Self(std::sync::atomic::$name::new(v))
+                }
+
+                pub(crate) fn load(&self, order: Ordering) -> $int {
+                    yield_point!(Atomic);
+                    self.0.load(order)
+                }
+
+                pub(crate) fn store(&self, v: $int, order: Ordering) {
+                    yield_point!(Atomic);
+                    self.0.store(v, order)
+                }
+
+                pub(crate) fn fetch_add(&self, v: $int, order: Ordering) -> $int {
+                    yield_point!(Atomic);
+                    self.0.fetch_add(v, order)
+                }
+
+                #[allow(dead_code)]
+                pub(crate) fn fetch_sub(&self, v: $int, order: Ordering) -> $int {
+                    yield_point!(Atomic);
+                    self.0.fetch_sub(v, order)
+                }
+
+                #[allow(dead_code)]
+                pub(crate) fn compare_and_swap(
+                    &self,
+                    current: $int,
+                    new: $int,
+                    order: Ordering,
+                ) -> $int {
+                    yield_point!(Atomic);
+                    match self
+                        .0
+                        .compare_exchange(current, new, order, failure_ordering(order))
+                    {
+                        Ok(v) | Err(v) => v,
+                    }
+                }
+            }
+        };
+    }
+
+    hooked_atomic!(AtomicIsize, isize);
+    hooked_atomic!(AtomicI64, i64);
+    hooked_atomic!(AtomicU64, u64);
+}
+
+#[cfg(feature = "spin-bin-locks")]
+mod spin {
+    use super::lock_api::{GuardNoSend, RawMutex, RawMutexTimed};
+    use super::{spin_loop, yield_now};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::{Duration, Instant};
+
+    /// After how many rounds of exponential backoff a waiting thread starts yielding its time
+    /// slice instead of spinning.
+    const SPIN_LIMIT: u32 = 6;
+
+    /// A lock that never parks the threads that are waiting for it.
+    ///
+    /// Waiting threads spin with exponential backoff, and once that has gone on for a while,
+    /// yield to the scheduler between attempts. This avoids the cost of parking and unparking
+    /// when critical sections are very short, at the cost of burning CPU time if they are not.
+    #[derive(Debug)]
+    pub(crate) struct RawSpinLock {
+        locked: AtomicBool,
+    }
+
+    impl RawSpinLock {
+        /// Waits for the lock until `done` returns `true`, which is checked between attempts.
+        /// Returns whether the lock was acquired.
+        fn lock_unless(&self, mut done: impl FnMut() -> bool) -> bool {
+            let mut step = 0;
+            loop {
+                if self.try_lock() {
+                    return true;
+                }
+                // wait for the lock to look free before trying to take it again, so that waiting
+                // threads do not keep stealing the cache line from the thread holding the lock
+                while self.locked.load(Ordering::Relaxed) {
+                    if done() {
+                        return false;
+                    }
+                    if step <= SPIN_LIMIT {
+                        for _ in 0..1 << step {
+                            spin_loop();
+                        }
+                        step += 1;
+                    } else {
+                        yield_now();
+                    }
+                }
+            }
+        }
+    }
+
+    // safety: the lock is only ever held by one thread at a time, since it is only taken by
+    // successfully swapping `locked` from `false` to `true`.
+    unsafe impl RawMutex for RawSpinLock {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const INIT: Self = RawSpinLock {
+            locked: AtomicBool::new(false),
+        };
+
+        type GuardMarker = GuardNoSend;
+
+        fn lock(&self) {
+            self.lock_unless(|| false);
+        }
+
+        fn try_lock(&self) -> bool {
+            self.locked
+                .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+        }
+
+        fn unlock(&self) {
+            self.locked.store(false, Ordering::Release);
+        }
+    }
+
+    unsafe impl RawMutexTimed for RawSpinLock {
+        type Duration = Duration;
+        type Instant = Instant;
+
+        fn try_lock_for(&self, timeout: Duration) -> bool {
+            self.try_lock_until(Instant::now() + timeout)
+        }
+
+        fn try_lock_until(&self, deadline: Instant) -> bool {
+            self.lock_unless(|| Instant::now() >= deadline)
+        }
+    }
+}
@@ -1,11 +1,69 @@
-use crate::node::*;
+//! The building blocks of the map, for embedding its table in other data structures.
+//!
+//! This module exposes the [`Table`] that backs a [`HashMap`](crate::HashMap), and the
+//! [`BinEntry`] and [`Node`] types that make up its bins, so that other concurrent data
+//! structures can reuse the map's layout and lock-free reads without going through the map
+//! itself. It is only available with the `raw` feature.
+//!
+//! A table on its own does none of the bookkeeping that the map does: it does not count its
+//! entries, resize, turn long bins into trees, or reclaim memory. Callers lock the first node of
+//! a bin with [`Node::with_lock`] before changing the bin, and retire the entries and values they
+//! unlink with [`Guard::defer_destroy`](crate::epoch::Guard::defer_destroy). Before a table that
+//! still has entries is dropped, they must be freed with [`Table::drop_entries`].
+//!
+//! This module is not covered by semantic versioning, and may change in any release.
+//!
+//! ```
+//! # #[cfg(feature = "raw")]
+//! # fn main() {
+//! use flurry::epoch::{self, Owned, Shared};
+//! use flurry::raw::{BinEntry, Node, Table};
+//!
+//! let mut table = Table::new(16);
+//! let guard = epoch::pin();
+//!
+//! let hash = 42;
+//! let node: Node<_, &str> = Node::new(hash, "key", Owned::new("value"));
+//! let i = table.bini(hash);
+//! assert!(table
+//!     .cas_bin(i, Shared::null(), Owned::new(BinEntry::Node(node)), &guard)
+//!     .is_ok());
+//!
+//! // safety: entries are only added through the table, and never removed
+//! let node = unsafe { table.get_node(hash, &"key", &guard) }.unwrap();
+//! let value = node.value().load(std::sync::atomic::Ordering::SeqCst, &guard);
+//! assert_eq!(unsafe { value.deref() }, &"value");
+//! drop(guard);
+//!
+//! // safety: we own the table, and all of its entries were allocated with `Owned`
+//! unsafe { table.drop_entries() };
+//! # }
+//! # #[cfg(not(feature = "raw"))]
+//! # fn main() {}
+//! ```
+
+use crate::equivalent::Comparable;
+#[cfg(feature = "raw")]
+use crate::equivalent::TreeKey;
+#[cfg(feature = "raw")]
+pub use crate::node::{BinEntry, Node, Reservation, TreeBin, TreeNode};
+#[cfg(not(feature = "raw"))]
+use crate::node::{BinEntry, TreeBin};
+use crate::TryReserveError;
 use crossbeam_epoch::{Atomic, Guard, Owned, Pointer, Shared};
+use std::alloc::{self, Layout};
+#[cfg(feature = "raw")]
 use std::borrow::Borrow;
 use std::fmt::Debug;
+use std::mem;
 use std::sync::atomic::Ordering;
 
+/// The array of bins that backs a map.
+///
+/// Each bin holds a pointer to its first [`BinEntry`], or null if it is empty. The position of an
+/// entry's bin is given by [`Table::bini`] for the entry's hash.
 #[derive(Debug)]
-pub(crate) struct Table<K, V> {
+pub struct Table<K, V> {
     bins: Box<[Atomic<BinEntry<K, V>>]>,
 
     // since a Moved does not contain associated information,
@@ -60,18 +118,65 @@ impl<K, V> From<Vec<Atomic<BinEntry<K, V>>>> for Table<K, V> {
 }
 
 impl<K, V> Table<K, V> {
-    pub(crate) fn new(bins: usize) -> Self {
+    /// Creates a table with `bins` empty bins.
+    ///
+    /// `bins` must be a power of two.
+    pub fn new(bins: usize) -> Self {
+        fail_point!("table-alloc");
         Self::from(vec![Atomic::null(); bins])
     }
 
-    pub(crate) fn is_empty(&self) -> bool {
+    /// Creates a table with `bins` empty bins, or returns an error if the memory for the bins
+    /// cannot be allocated.
+    ///
+    /// `bins` must be a power of two.
+    pub(crate) fn try_new(bins: usize) -> Result<Self, TryReserveError> {
+        let layout = mem::size_of::<Atomic<BinEntry<K, V>>>()
+            .checked_mul(bins)
+            .and_then(|size| {
+                Layout::from_size_align(size, mem::align_of::<Atomic<BinEntry<K, V>>>()).ok()
+            })
+            .ok_or(TryReserveError::CapacityOverflow)?;
+        if fail_point_fails!("table-alloc") {
+            return Err(TryReserveError::AllocError { layout });
+        }
+        if bins == 0 {
+            return Ok(Self::from(Vec::new()));
+        }
+
+        // safety: the layout is not zero-sized, since there is at least one bin, and a bin is a
+        // pointer.
+        let ptr = unsafe { alloc::alloc(layout) } as *mut Atomic<BinEntry<K, V>>;
+        if ptr.is_null() {
+            return Err(TryReserveError::AllocError { layout });
+        }
+        for i in 0..bins {
+            // safety: the allocation has room for `bins` bins
+            unsafe { ptr.add(i).write(Atomic::null()) };
+        }
+        // safety: the bins were allocated with the global allocator, with the same layout a
+        // `Vec` of `bins` bins uses, and they have all been initialized.
+        Ok(Self::from(unsafe { Vec::from_raw_parts(ptr, bins, bins) }))
+    }
+
+    /// Returns `true` if the table has no bins.
+    pub fn is_empty(&self) -> bool {
         self.bins.is_empty()
     }
 
-    pub(crate) fn len(&self) -> usize {
+    /// Returns the number of bins in the table.
+    pub fn len(&self) -> usize {
         self.bins.len()
     }
 
+    /// Returns the number of bytes taken up by the table and its bins, not counting the entries
+    /// in the bins.
+    pub(crate) fn footprint(&self) -> usize {
+        mem::size_of::<Self>()
+            + self.bins.len() * mem::size_of::<Atomic<BinEntry<K, V>>>()
+            + mem::size_of::<BinEntry<K, V>>()
+    }
+
     pub(crate) fn get_moved<'g>(
         &'g self,
         for_table: Shared<'g, Table<K, V>>,
@@ -110,8 +215,7 @@ impl<K, V> Table<K, V> {
         guard: &'g Guard,
     ) -> Shared<'g, BinEntry<K, V>>
     where
-        K: Borrow<Q>,
-        Q: ?Sized + Ord,
+        Q: ?Sized + Comparable<K>,
     {
         match *bin {
             BinEntry::Node(_) => {
@@ -123,7 +227,7 @@ impl<K, V> Table<K, V> {
                         unreachable!("BinEntry::Node only points to BinEntry::Node");
                     };
 
-                    if n.hash == hash && n.key.borrow() == key {
+                    if n.hash == hash && key.equivalent(&n.key) {
                         return Shared::from(node as *const _);
                     }
                     let next = n.next.load(Ordering::SeqCst, guard);
@@ -162,6 +266,7 @@ impl<K, V> Table<K, V> {
                             table = unsafe { table.next_table(guard).deref() };
                             continue;
                         }
+                        BinEntry::Reserved(_) => break Shared::null(),
                         BinEntry::TreeNode(_) => unreachable!("`find` was called on a Moved entry pointing to a TreeNode, which cannot be the first entry in a bin"),
                     }
                 }
@@ -172,9 +277,68 @@ impl<K, V> Table<K, V> {
                 );
             }
             BinEntry::Tree(_) => TreeBin::find(Shared::from(bin as *const _), hash, key, guard),
+            // a reserved bin has no entries yet
+            BinEntry::Reserved(_) => Shared::null(),
         }
     }
 
+    /// Like `find`, but looks for the first entry with the given hash whose key `is_match`
+    /// accepts, instead of comparing keys.
+    ///
+    /// Tree bins are ordered by key, which does not help when the comparison is done by a
+    /// closure, so they are searched linearly.
+    pub(crate) fn find_by<'g, F>(
+        &'g self,
+        bin: &BinEntry<K, V>,
+        hash: u64,
+        is_match: &mut F,
+        guard: &'g Guard,
+    ) -> Shared<'g, BinEntry<K, V>>
+    where
+        F: FnMut(&K) -> bool,
+    {
+        let mut node = match *bin {
+            BinEntry::Node(_) => Shared::from(bin as *const _),
+            BinEntry::Tree(ref tree_bin) => tree_bin.first.load(Ordering::SeqCst, guard),
+            BinEntry::Moved => {
+                // safety: see `find`
+                let table = unsafe { self.next_table(guard).deref() };
+                if table.is_empty() {
+                    return Shared::null();
+                }
+                let bin = table.bin(table.bini(hash), guard);
+                if bin.is_null() {
+                    return Shared::null();
+                }
+                // safety: the table is protected by the guard, and so is the bin.
+                return table.find_by(unsafe { bin.deref() }, hash, is_match, guard);
+            }
+            BinEntry::TreeNode(_) => {
+                unreachable!(
+                    "`find_by` was called on a TreeNode, which cannot be the first entry in a bin"
+                );
+            }
+            // a reserved bin has no entries yet
+            BinEntry::Reserved(_) => return Shared::null(),
+        };
+
+        while !node.is_null() {
+            // safety: the nodes of a bin, or of the tree in a bin, are only dropped after they
+            // have been unlinked from it, and an epoch has passed. we are holding up the epoch
+            // with our guard.
+            let n = match *unsafe { node.deref() } {
+                BinEntry::Node(ref n) => n,
+                BinEntry::TreeNode(ref tn) => &tn.node,
+                _ => unreachable!("bins only link to nodes"),
+            };
+            if n.hash == hash && is_match(&n.key) {
+                return node;
+            }
+            node = n.next.load(Ordering::SeqCst, guard);
+        }
+        Shared::null()
+    }
+
     pub(crate) fn drop_bins(&mut self) {
         // safety: we have &mut self _and_ all references we have returned are bound to the
         // lifetime of their borrow of self, so there cannot be any outstanding references to
@@ -230,6 +394,81 @@ impl<K, V> Table<K, V> {
                     // TreeBin::drop will take care of freeing the contained TreeNodes and their values
                     drop(bin);
                 }
+                BinEntry::Reserved(_) => {
+                    // safety: same as for BinEntry::Node
+                    drop(unsafe { bin.into_owned() });
+                }
+                BinEntry::TreeNode(_) => unreachable!(
+                    "The head of a bin cannot be a TreeNode directly without BinEntry::Tree"
+                ),
+            }
+        }
+    }
+
+    /// Moves the keys and values of all entries out of the table and into `entries`, like
+    /// `drop_bins` would drop them.
+    pub(crate) fn take_entries(&mut self, entries: &mut Vec<(K, V)>) {
+        // safety: as in drop_bins
+        let guard = unsafe { crossbeam_epoch::unprotected() };
+
+        for bin in Vec::from(std::mem::replace(&mut self.bins, vec![].into_boxed_slice())) {
+            if bin.load(Ordering::SeqCst, guard).is_null() {
+                // bin was never used
+                continue;
+            }
+
+            // safety: same as in drop_bins
+            let bin_entry = unsafe { bin.load(Ordering::SeqCst, guard).deref() };
+            match *bin_entry {
+                BinEntry::Moved => {}
+                BinEntry::Node(_) => {
+                    // safety: same as in drop_bins, we own all the nodes in the list
+                    let mut p = unsafe { bin.into_owned() };
+                    loop {
+                        let node = if let BinEntry::Node(node) = *p.into_box() {
+                            node
+                        } else {
+                            unreachable!();
+                        };
+                        let next = node.next;
+                        // safety: as above, we own the value, and no-one else can reach it
+                        let value = unsafe { node.value.into_owned() }.into_box();
+                        entries.push((node.key, *value));
+
+                        if next.load(Ordering::SeqCst, guard).is_null() {
+                            break;
+                        }
+                        // safety: as above
+                        p = unsafe { next.into_owned() };
+                    }
+                }
+                BinEntry::Tree(ref tree_bin) => {
+                    // take the nodes out of the tree bin, so that dropping it does not drop them.
+                    // it is sufficient to follow the `next` pointers of the nodes, since the tree
+                    // pointers point to the same nodes.
+                    let mut p = tree_bin
+                        .first
+                        .swap(Shared::null(), Ordering::Relaxed, guard);
+                    while !p.is_null() {
+                        // safety: as for BinEntry::Node
+                        if let BinEntry::TreeNode(tree_node) = *unsafe { p.into_owned() }.into_box()
+                        {
+                            let node = tree_node.node;
+                            p = node.next.load(Ordering::SeqCst, guard);
+                            // safety: as for BinEntry::Node
+                            let value = unsafe { node.value.into_owned() }.into_box();
+                            entries.push((node.key, *value));
+                        } else {
+                            unreachable!("Trees can only ever contain TreeNodes");
+                        }
+                    }
+                    // safety: as for BinEntry::Node. the bin no longer holds any nodes.
+                    drop(unsafe { bin.into_owned() });
+                }
+                BinEntry::Reserved(_) => {
+                    // safety: same as for BinEntry::Node
+                    drop(unsafe { bin.into_owned() });
+                }
                 BinEntry::TreeNode(_) => unreachable!(
                     "The head of a bin cannot be a TreeNode directly without BinEntry::Tree"
                 ),
@@ -238,6 +477,66 @@ impl<K, V> Table<K, V> {
     }
 }
 
+#[cfg(feature = "raw")]
+impl<K, V> Table<K, V> {
+    /// Returns the node with the given hash and key, if it is in the table.
+    ///
+    /// Lookups do not follow [`BinEntry::Moved`] entries, since tables built outside of a map do
+    /// not have a table that they forward to.
+    ///
+    /// # Safety
+    ///
+    /// Every entry reachable from the bin for `hash` must stay valid for as long as `guard` is
+    /// held. This is the case if entries are only ever added through this table, and are only
+    /// freed with [`Guard::defer_destroy`] after they have been unlinked from it.
+    pub unsafe fn get_node<'g, Q>(
+        &'g self,
+        hash: u64,
+        key: &Q,
+        guard: &'g Guard,
+    ) -> Option<&'g Node<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + TreeKey,
+    {
+        let bin = self.bin(self.bini(hash), guard);
+        if bin.is_null() {
+            return None;
+        }
+        // safety: guaranteed by the caller
+        let bin = bin.deref();
+        if let BinEntry::Moved = *bin {
+            return None;
+        }
+        let entry = self.find(bin, hash, key, guard);
+        if entry.is_null() {
+            return None;
+        }
+        // safety: entries reachable from the bin are valid for 'g, as guaranteed by the caller
+        match *entry.deref() {
+            BinEntry::Node(ref node) => Some(node),
+            BinEntry::TreeNode(ref tree_node) => Some(&tree_node.node),
+            BinEntry::Moved | BinEntry::Tree(_) | BinEntry::Reserved(_) => {
+                unreachable!("`find` only returns the node that matched the key")
+            }
+        }
+    }
+
+    /// Drops every entry in the table, along with their values.
+    ///
+    /// This must be called before a table that has non-empty bins is dropped. Afterwards, the
+    /// table has no bins left.
+    ///
+    /// # Safety
+    ///
+    /// Every non-null bin must point to a list of entries that is owned by this table, and whose
+    /// entries and values were allocated with [`Owned`]. No other references to any of them may
+    /// exist.
+    pub unsafe fn drop_entries(&mut self) {
+        self.drop_bins();
+    }
+}
+
 impl<K, V> Drop for Table<K, V> {
     fn drop(&mut self) {
         // safety: we have &mut self _and_ all references we have returned are bound to the
@@ -290,20 +589,24 @@ impl<K, V> Drop for Table<K, V> {
 }
 
 impl<K, V> Table<K, V> {
+    /// Returns the index of the bin that entries with the given hash belong in.
     #[inline]
-    pub(crate) fn bini(&self, hash: u64) -> usize {
+    pub fn bini(&self, hash: u64) -> usize {
         let mask = self.bins.len() as u64 - 1;
         (hash & mask) as usize
     }
 
+    /// Loads the head of bin `i`.
     #[inline]
-    pub(crate) fn bin<'g>(&'g self, i: usize, guard: &'g Guard) -> Shared<'g, BinEntry<K, V>> {
+    pub fn bin<'g>(&'g self, i: usize, guard: &'g Guard) -> Shared<'g, BinEntry<K, V>> {
+        yield_point!(BinLoad);
         self.bins[i].load(Ordering::Acquire, guard)
     }
 
+    /// Replaces the head of bin `i` with `new` if it is still `current`.
     #[inline]
     #[allow(clippy::type_complexity)]
-    pub(crate) fn cas_bin<'g, P>(
+    pub fn cas_bin<'g, P>(
         &'g self,
         i: usize,
         current: Shared<'_, BinEntry<K, V>>,
@@ -316,11 +619,21 @@ impl<K, V> Table<K, V> {
     where
         P: Pointer<BinEntry<K, V>>,
     {
-        self.bins[i].compare_and_set(current, new, Ordering::AcqRel, guard)
+        fail_point!("bin-cas-before");
+        yield_point!(BinCas);
+        let result = self.bins[i].compare_and_set(current, new, Ordering::AcqRel, guard);
+        if result.is_ok() {
+            fail_point!("bin-cas-after");
+        }
+        result
     }
 
+    /// Sets the head of bin `i` to `new`.
+    ///
+    /// This should only be done while holding the lock of the bin's current head, if it has one.
     #[inline]
-    pub(crate) fn store_bin<P: Pointer<BinEntry<K, V>>>(&self, i: usize, new: P) {
+    pub fn store_bin<P: Pointer<BinEntry<K, V>>>(&self, i: usize, new: P) {
+        yield_point!(BinStore);
         self.bins[i].store(new, Ordering::Release)
     }
 
@@ -0,0 +1,200 @@
+//! Access to a map's entries by precomputed hashes.
+//!
+//! Every lookup, insertion, and removal normally hashes its key with the map's hasher. Code that
+//! already knows the hash of a key, say because it hashed the key once to pick a shard or a
+//! worker, can skip that step with [`HashMap::raw_entry`] and [`HashMap::raw_entry_mut`], and
+//! pass the hash along instead. Lookups can also match keys with a closure, without having to
+//! implement [`Comparable`] for the type that the keys are compared to.
+//!
+//! The hash must be the one that the map's hasher computes for the key:
+//!
+//! ```
+//! use flurry::HashMap;
+//! use std::hash::{BuildHasher, Hash, Hasher};
+//!
+//! let map = HashMap::new();
+//! let hash = |key: &str| {
+//!     let mut hasher = map.hasher().build_hasher();
+//!     key.hash(&mut hasher);
+//!     hasher.finish()
+//! };
+//!
+//! let guard = map.guard();
+//! let h = hash("apples");
+//! map.raw_entry_mut(&guard)
+//!     .insert_hashed_nocheck(h, String::from("apples"), 3);
+//! assert_eq!(map.get("apples", &guard), Some(&3));
+//!
+//! let entry = map.raw_entry(&guard).from_hash(h, |key| key == "apples");
+//! assert_eq!(entry, Some((&String::from("apples"), &3)));
+//! ```
+//!
+//! Passing any other hash is a logic error: the map may then fail to find the entry, or store a
+//! second entry for the same key. It does not lead to undefined behavior.
+//!
+//! [`HashMap::raw_entry`]: crate::HashMap::raw_entry
+//! [`HashMap::raw_entry_mut`]: crate::HashMap::raw_entry_mut
+
+use crate::map::PutResult;
+use crate::{Comparable, HashMap, TreeKey, TryInsertError};
+use crossbeam_epoch::{Guard, Shared};
+use std::fmt::{self, Debug, Formatter};
+use std::hash::{BuildHasher, Hash};
+use std::sync::atomic::Ordering;
+
+/// A builder for lookups by precomputed hashes, as returned by
+/// [`HashMap::raw_entry`](crate::HashMap::raw_entry).
+///
+/// See the [module-level documentation](self) for details.
+pub struct RawEntryBuilder<'g, K, V, S> {
+    pub(crate) map: &'g HashMap<K, V, S>,
+    pub(crate) guard: &'g Guard,
+}
+
+impl<K, V, S> Debug for RawEntryBuilder<'_, K, V, S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RawEntryBuilder").finish()
+    }
+}
+
+impl<'g, K, V, S> RawEntryBuilder<'g, K, V, S>
+where
+    K: Hash + TreeKey,
+    S: BuildHasher,
+{
+    /// Returns the entry for `key`, if there is one.
+    ///
+    /// This is the same as [`HashMap::get_key_value`](crate::HashMap::get_key_value).
+    pub fn from_key<Q>(self, key: &Q) -> Option<(&'g K, &'g V)>
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+    {
+        self.map.get_key_value(key, self.guard)
+    }
+
+    /// Returns the entry for `key`, whose hash is `hash`, if there is one.
+    pub fn from_key_hashed_nocheck<Q>(self, hash: u64, key: &Q) -> Option<(&'g K, &'g V)>
+    where
+        Q: ?Sized + Comparable<K>,
+    {
+        let node = self
+            .map
+            .find_node_hashed(self.map.spread(hash), key, self.guard)?;
+        Some((
+            &node.key,
+            self.value(node.value.load(Ordering::SeqCst, self.guard)),
+        ))
+    }
+
+    /// Returns the first entry with hash `hash` whose key `is_match` returns `true` for, if there
+    /// is one.
+    ///
+    /// `is_match` is only called for keys with the given hash. Bins that have been turned into
+    /// trees are searched linearly, since the keys cannot be compared with their order.
+    pub fn from_hash<F>(self, hash: u64, is_match: F) -> Option<(&'g K, &'g V)>
+    where
+        F: FnMut(&K) -> bool,
+    {
+        let node = self
+            .map
+            .find_node_by(self.map.spread(hash), is_match, self.guard)?;
+        Some((
+            &node.key,
+            self.value(node.value.load(Ordering::SeqCst, self.guard)),
+        ))
+    }
+
+    fn value(&self, v: Shared<'g, V>) -> &'g V {
+        assert!(!v.is_null());
+        // safety: the value was loaded while the guard was held, and values are only dropped in
+        // an epoch after they are replaced or removed, so it lives for as long as the guard.
+        unsafe { v.deref() }
+    }
+}
+
+/// A builder for insertions and removals by precomputed hashes, as returned by
+/// [`HashMap::raw_entry_mut`](crate::HashMap::raw_entry_mut).
+///
+/// See the [module-level documentation](self) for details.
+pub struct RawEntryBuilderMut<'g, K, V, S> {
+    pub(crate) map: &'g HashMap<K, V, S>,
+    pub(crate) guard: &'g Guard,
+}
+
+impl<K, V, S> Debug for RawEntryBuilderMut<'_, K, V, S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RawEntryBuilderMut").finish()
+    }
+}
+
+impl<'g, K, V, S> RawEntryBuilderMut<'g, K, V, S>
+where
+    K: 'static + Sync + Send + Clone + Hash + TreeKey,
+    V: 'static + Sync + Send,
+    S: BuildHasher,
+{
+    /// Inserts `value` for `key`, whose hash is `hash`, and returns the value it replaced, if
+    /// any.
+    ///
+    /// See [`HashMap::insert`](crate::HashMap::insert).
+    pub fn insert_hashed_nocheck(self, hash: u64, key: K, value: V) -> Option<&'g V> {
+        self.map
+            .put_hashed(self.map.spread(hash), key, value, false, self.guard)
+            .before()
+    }
+
+    /// Inserts `value` for `key`, whose hash is `hash`, unless the map already has an entry for
+    /// `key`.
+    ///
+    /// See [`HashMap::try_insert`](crate::HashMap::try_insert).
+    pub fn try_insert_hashed_nocheck(
+        self,
+        hash: u64,
+        key: K,
+        value: V,
+    ) -> Result<&'g V, TryInsertError<'g, V>> {
+        match self
+            .map
+            .put_hashed(self.map.spread(hash), key, value, true, self.guard)
+        {
+            PutResult::Exists {
+                current,
+                not_inserted,
+            } => Err(TryInsertError {
+                current,
+                not_inserted: *not_inserted,
+            }),
+            PutResult::Inserted { new } => Ok(new),
+            PutResult::Replaced { .. } => {
+                unreachable!("no_replacement cannot result in PutResult::Replaced")
+            }
+        }
+    }
+
+    /// Removes the entry for `key`, whose hash is `hash`, and returns its key and value, if
+    /// there was one.
+    ///
+    /// See [`HashMap::remove_entry`](crate::HashMap::remove_entry).
+    pub fn remove_hashed_nocheck<Q>(self, hash: u64, key: &Q) -> Option<(&'g K, &'g V)>
+    where
+        Q: ?Sized + Comparable<K>,
+    {
+        self.map
+            .replace_node_if(self.map.spread(hash), key, None, None, None, self.guard)
+    }
+
+    /// Removes the first entry with hash `hash` whose key `is_match` returns `true` for, and
+    /// returns its key and value, if there was one.
+    ///
+    /// If the matching entry is replaced by another thread before it can be removed, the entry
+    /// that replaced it is removed instead, since it has the same key.
+    pub fn remove_from_hash<F>(self, hash: u64, is_match: F) -> Option<(&'g K, &'g V)>
+    where
+        F: FnMut(&K) -> bool,
+    {
+        let hash = self.map.spread(hash);
+        let node = self.map.find_node_by(hash, is_match, self.guard)?;
+        self.map
+            .replace_node_if(hash, &node.key, None, None, None, self.guard)
+    }
+}
@@ -1,10 +1,81 @@
-use crate::{HashMap, HashMapRef, HashSet, HashSetRef};
+use crate::iter::{Iter, Keys, Values};
+use crate::{HashMap, HashMapRef, HashSet, HashSetRef, TreeKey};
+use rayon::iter::plumbing::{bridge_unindexed, Folder, UnindexedConsumer, UnindexedProducer};
 use rayon::iter::{FromParallelIterator, IntoParallelIterator, ParallelExtend, ParallelIterator};
+use std::fmt::{self, Debug, Formatter};
 use std::hash::{BuildHasher, Hash};
 
+/// A parallel iterator over a map's entries.
+///
+/// See [`HashMap::par_iter`](crate::HashMap::par_iter) for details.
+pub struct ParIter<'g, K, V> {
+    pub(crate) iter: Iter<'g, K, V>,
+}
+
+/// A parallel iterator over a map's keys.
+///
+/// See [`HashMap::par_keys`](crate::HashMap::par_keys) for details.
+pub struct ParKeys<'g, K, V> {
+    pub(crate) iter: Keys<'g, K, V>,
+}
+
+/// A parallel iterator over a map's values.
+///
+/// See [`HashMap::par_values`](crate::HashMap::par_values) for details.
+pub struct ParValues<'g, K, V> {
+    pub(crate) iter: Values<'g, K, V>,
+}
+
+macro_rules! par_iter {
+    ($name:ident, $item:ty) => {
+        // safety: the iterator only uses its guard to load from the map. any thread may do that
+        // while the guard keeps the epoch pinned, and the guard outlives the iterator, which is
+        // only ever used while the thread that owns the guard waits for it to finish. what the
+        // iterator yields is `Send`, since the keys and values are `Sync`.
+        unsafe impl<K: Sync, V: Sync> Send for $name<'_, K, V> {}
+
+        impl<'g, K: Sync, V: Sync> ParallelIterator for $name<'g, K, V> {
+            type Item = $item;
+
+            fn drive_unindexed<C>(self, consumer: C) -> C::Result
+            where
+                C: UnindexedConsumer<Self::Item>,
+            {
+                bridge_unindexed(self, consumer)
+            }
+        }
+
+        impl<'g, K: Sync, V: Sync> UnindexedProducer for $name<'g, K, V> {
+            type Item = $item;
+
+            fn split(mut self) -> (Self, Option<Self>) {
+                let upper = self.iter.split().map(|iter| $name { iter });
+                (self, upper)
+            }
+
+            fn fold_with<F>(self, folder: F) -> F
+            where
+                F: Folder<Self::Item>,
+            {
+                folder.consume_iter(self.iter)
+            }
+        }
+
+        impl<K, V> Debug for $name<'_, K, V> {
+            fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                f.debug_struct(stringify!($name)).finish()
+            }
+        }
+    };
+}
+
+par_iter!(ParIter, (&'g K, &'g V));
+par_iter!(ParKeys, &'g K);
+par_iter!(ParValues, &'g V);
+
 impl<K, V, S> FromParallelIterator<(K, V)> for HashMap<K, V, S>
 where
-    K: Clone + Hash + Ord + Send + Sync + 'static,
+    K: Clone + Hash + TreeKey + Send + Sync + 'static,
     V: Send + Sync + 'static,
     S: BuildHasher + Default + Sync,
 {
@@ -20,7 +91,7 @@ where
 
 impl<K, V, S> ParallelExtend<(K, V)> for HashMap<K, V, S>
 where
-    K: Clone + Hash + Ord + Send + Sync + 'static,
+    K: Clone + Hash + TreeKey + Send + Sync + 'static,
     V: Send + Sync + 'static,
     S: BuildHasher + Sync,
 {
@@ -34,7 +105,7 @@ where
 
 impl<K, V, S> ParallelExtend<(K, V)> for &HashMap<K, V, S>
 where
-    K: Clone + Hash + Ord + Send + Sync + 'static,
+    K: Clone + Hash + TreeKey + Send + Sync + 'static,
     V: Send + Sync + 'static,
     S: BuildHasher + Sync,
 {
@@ -42,10 +113,23 @@ where
     where
         I: IntoParallelIterator<Item = (K, V)>,
     {
-        par_iter.into_par_iter().for_each_init(
+        let par_iter = par_iter.into_par_iter();
+        // grow the table once up front, rather than have the workers resize it over and over as
+        // they fill it. like `insert_all`, only reserve half of the length if the map already has
+        // entries, since keys may be present already.
+        if let Some(len) = par_iter.opt_len() {
+            let guard = self.guard();
+            if self.is_empty() {
+                self.reserve(len, &guard);
+            } else {
+                self.reserve(len - len / 2, &guard);
+            }
+        }
+
+        par_iter.for_each_init(
             || self.guard(),
             |guard, (k, v)| {
-                self.insert(k, v, &guard);
+                self.insert(k, v, guard);
             },
         );
     }
@@ -53,7 +137,7 @@ where
 
 impl<'map, K, V, S> ParallelExtend<(K, V)> for HashMapRef<'map, K, V, S>
 where
-    K: Clone + Hash + Ord + Send + Sync + 'static,
+    K: Clone + Hash + TreeKey + Send + Sync + 'static,
     V: Send + Sync + 'static,
     S: BuildHasher + Sync,
 {
@@ -67,7 +151,7 @@ where
 
 impl<K, S> FromParallelIterator<K> for HashSet<K, S>
 where
-    K: Clone + Hash + Ord + Send + Sync + 'static,
+    K: Clone + Hash + TreeKey + Send + Sync + 'static,
     S: BuildHasher + Default + Sync,
 {
     fn from_par_iter<I>(par_iter: I) -> Self
@@ -82,7 +166,7 @@ where
 
 impl<K, S> ParallelExtend<K> for HashSet<K, S>
 where
-    K: Clone + Hash + Ord + Send + Sync + 'static,
+    K: Clone + Hash + TreeKey + Send + Sync + 'static,
     S: BuildHasher + Sync,
 {
     fn par_extend<I>(&mut self, par_iter: I)
@@ -95,7 +179,7 @@ where
 
 impl<K, S> ParallelExtend<K> for &HashSet<K, S>
 where
-    K: Clone + Hash + Ord + Send + Sync + 'static,
+    K: Clone + Hash + TreeKey + Send + Sync + 'static,
     S: BuildHasher + Sync,
 {
     fn par_extend<I>(&mut self, par_iter: I)
@@ -109,7 +193,7 @@ where
 
 impl<'set, K, S> ParallelExtend<K> for HashSetRef<'set, K, S>
 where
-    K: Clone + Hash + Ord + Send + Sync + 'static,
+    K: Clone + Hash + TreeKey + Send + Sync + 'static,
     S: BuildHasher + Sync,
 {
     fn par_extend<I>(&mut self, par_iter: I)
@@ -123,7 +207,9 @@ where
 #[cfg(test)]
 mod test {
     use crate::{HashMap, HashSet};
-    use rayon::iter::{FromParallelIterator, IntoParallelIterator, ParallelExtend};
+    use rayon::iter::{
+        FromParallelIterator, IntoParallelIterator, ParallelExtend, ParallelIterator,
+    };
 
     #[test]
     fn hm_from_empty_parallel_iter() {
@@ -172,12 +258,12 @@ mod test {
     fn hm_parallel_extend_by_nothing() {
         let to_extend_with = Vec::new();
 
-        let mut map = HashMap::new();
+        let map = HashMap::new();
         let guard = map.guard();
         map.insert(1, 2, &guard);
         map.insert(3, 4, &guard);
 
-        map.par_extend(to_extend_with.into_par_iter());
+        (&map).par_extend(to_extend_with.into_par_iter());
 
         assert_eq!(map.len(), 2);
 
@@ -192,12 +278,12 @@ mod test {
             to_extend_with.push((i + 100, i * 10));
         }
 
-        let mut map = HashMap::new();
+        let map = HashMap::new();
         let guard = map.guard();
         map.insert(1, 2, &guard);
         map.insert(3, 4, &guard);
 
-        map.par_extend(to_extend_with.into_par_iter());
+        (&map).par_extend(to_extend_with.into_par_iter());
         assert_eq!(map.len(), 102);
 
         assert_eq!(map.get(&1, &guard), Some(&2));
@@ -317,4 +403,249 @@ mod test {
         assert!(set_ref.contains(&(199, 990)));
         assert!(!set_ref.contains(&(199, 167)));
     }
+
+    #[test]
+    fn hm_parallel_iter() {
+        let map: HashMap<usize, usize> = (0..10_000).map(|i| (i, i * 2)).collect();
+        let guard = map.guard();
+
+        let mut entries: Vec<_> = map.par_iter(&guard).map(|(&k, &v)| (k, v)).collect();
+        entries.sort_unstable();
+        assert_eq!(entries, (0..10_000).map(|i| (i, i * 2)).collect::<Vec<_>>());
+
+        let mut keys: Vec<_> = map.par_keys(&guard).copied().collect();
+        keys.sort_unstable();
+        assert_eq!(keys, (0..10_000).collect::<Vec<_>>());
+
+        let sum: usize = map.par_values(&guard).sum();
+        assert_eq!(sum, 9_999 * 10_000);
+    }
+
+    #[test]
+    fn hm_parallel_iter_empty() {
+        let map = HashMap::<usize, usize>::new();
+        assert_eq!(map.pin().par_iter().count(), 0);
+        let map = HashMap::<usize, usize>::with_capacity(1024);
+        assert_eq!(map.pin().par_keys().count(), 0);
+    }
+
+    #[test]
+    fn hm_parallel_iter_during_resize() {
+        let map = HashMap::new();
+        for i in 0..1_000 {
+            map.pin().insert(i, i);
+        }
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for i in 1_000..50_000 {
+                    map.pin().insert(i, i);
+                }
+            });
+            for _ in 0..20 {
+                let map = map.pin();
+                let keys: Vec<_> = map.par_keys().copied().collect();
+                // every key shows up once, whichever table it is read from
+                let mut sorted = keys.clone();
+                sorted.sort_unstable();
+                sorted.dedup();
+                assert_eq!(sorted.len(), keys.len());
+                assert!(sorted.starts_with(&(0..1_000).collect::<Vec<_>>()));
+            }
+        });
+    }
+
+    #[test]
+    fn hm_ref_parallel_iter() {
+        let map: HashMap<usize, usize> = (0..1_000).map(|i| (i, i)).collect();
+        let map = map.pin();
+        assert_eq!(map.par_iter().filter(|(&k, &v)| k == v).count(), 1_000);
+        assert_eq!(map.par_values().max(), Some(&999));
+    }
+
+    #[test]
+    fn hs_parallel_iter() {
+        let set: HashSet<usize> = (0..1_000).collect();
+        let guard = set.guard();
+        assert_eq!(set.par_iter(&guard).sum::<usize>(), 999 * 1_000 / 2);
+        assert_eq!(set.pin().par_iter().count(), 1_000);
+    }
+
+    #[test]
+    fn hm_parallel_extend_presizes() {
+        let map = HashMap::new();
+        (&map).par_extend((0..10_000).into_par_iter().map(|i| (i, i)));
+        let presized = HashMap::<i32, i32>::new();
+        presized.reserve(10_000, &presized.guard());
+        assert_eq!(
+            map.bucket_count(&map.guard()),
+            presized.bucket_count(&presized.guard())
+        );
+        assert_eq!(map.len(), 10_000);
+
+        // without a known length, the map grows as it goes
+        let map = HashMap::new();
+        (&map).par_extend(
+            (0..10_000)
+                .into_par_iter()
+                .filter(|i| i % 2 == 0)
+                .map(|i| (i, i)),
+        );
+        assert_eq!(map.len(), 5_000);
+    }
+
+    #[test]
+    fn hm_collect_from_parallel_iter() {
+        let map: HashMap<usize, usize> = (0..1_000).map(|i| (i, i)).collect();
+        let doubled: HashMap<usize, usize> =
+            map.pin().par_iter().map(|(&k, &v)| (k, v * 2)).collect();
+        assert_eq!(doubled.len(), 1_000);
+        let guard = doubled.guard();
+        assert!(doubled.iter(&guard).all(|(&k, &v)| v == k * 2));
+    }
+
+    #[test]
+    fn hm_parallel_retain() {
+        let map: HashMap<usize, usize> = (0..10_000).map(|i| (i, i)).collect();
+        let guard = map.guard();
+        assert_eq!(map.par_retain(|&k, _| k % 2 == 0, &guard), 5_000);
+        assert_eq!(map.len(), 5_000);
+        assert!(map.iter(&guard).all(|(&k, _)| k % 2 == 0));
+
+        let map = map.pin();
+        assert_eq!(map.par_retain(|_, _| true), 0);
+        assert_eq!(map.par_retain(|&k, _| k >= 5_000), 2_500);
+        assert_eq!(map.len(), 2_500);
+        assert_eq!(map.par_retain(|_, _| false), 2_500);
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn hm_parallel_retain_during_inserts() {
+        let map = HashMap::new();
+        for i in 0..1_000 {
+            map.pin().insert(i, i);
+        }
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for i in 1_000..20_000 {
+                    map.pin().insert(i, i);
+                }
+            });
+            map.pin().par_retain(|&k, _| k >= 500);
+        });
+        let map = map.pin();
+        assert!((0..500).all(|k| !map.contains_key(&k)));
+        assert!((500..20_000).all(|k| map.contains_key(&k)));
+    }
+
+    #[test]
+    fn hm_reduce() {
+        let map: HashMap<u64, u64> = (0..10_000).map(|i| (i, i * 2)).collect();
+        let guard = map.guard();
+        for &threshold in &[1, 10_000, usize::MAX] {
+            let keys = map.reduce_keys(threshold, |&k| k, |a, b| a + b, &guard);
+            assert_eq!(keys, Some(9_999 * 10_000 / 2));
+            let values = map.reduce_values(threshold, |&v| v, |a, b| a + b, &guard);
+            assert_eq!(values, Some(9_999 * 10_000));
+            let entries = map.reduce_entries(threshold, |&k, &v| v - k, |a, b| a + b, &guard);
+            assert_eq!(entries, keys);
+        }
+
+        let map = map.pin();
+        assert_eq!(map.reduce_values(1, |&v| v, std::cmp::max), Some(19_998));
+        assert_eq!(map.reduce_keys(1, |&k| k, std::cmp::min), Some(0));
+        let count = map.reduce_entries(1, |_, _| 1, |a, b| a + b);
+        assert_eq!(count, Some(10_000));
+    }
+
+    #[test]
+    fn hm_reduce_empty() {
+        let map = HashMap::<u64, u64>::new();
+        let guard = map.guard();
+        assert_eq!(map.reduce_values(1, |&v| v, |a, b| a + b, &guard), None);
+        assert_eq!(
+            map.reduce_keys(usize::MAX, |&k| k, |a, b| a + b, &guard),
+            None
+        );
+    }
+
+    #[test]
+    fn hm_parallel_search() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let map: HashMap<usize, usize> = (0..100_000).map(|i| (i, i)).collect();
+        let guard = map.guard();
+        assert_eq!(
+            map.par_search(|&k, _| Some(k).filter(|&k| k == 4_242), &guard),
+            Some(4_242)
+        );
+        assert_eq!(map.par_search(|_, _| None::<()>, &guard), None);
+
+        // every entry matches, so the search stops long before visiting all of them
+        let visited = AtomicUsize::new(0);
+        let found = map.pin().par_search(|&k, _| {
+            visited.fetch_add(1, Ordering::Relaxed);
+            Some(k)
+        });
+        assert!(found.is_some());
+        assert!(visited.load(Ordering::Relaxed) < 100_000);
+    }
+
+    #[test]
+    fn hm_parallel_for_each() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let map: HashMap<usize, usize> = (0..100_000).map(|i| (i, i)).collect();
+        let visited: Vec<_> = (0..100_000).map(|_| AtomicUsize::new(0)).collect();
+        map.par_for_each(|&k, &v| {
+            assert_eq!(k, v);
+            visited[k].fetch_add(1, Ordering::Relaxed);
+        });
+        assert!(visited.iter().all(|n| n.load(Ordering::Relaxed) == 1));
+
+        let count = AtomicUsize::new(0);
+        map.pin().par_for_each(|_, _| {
+            count.fetch_add(1, Ordering::Relaxed);
+        });
+        assert_eq!(count.into_inner(), 100_000);
+    }
+
+    #[test]
+    fn hm_parallel_compute_all() {
+        let map: HashMap<usize, usize> = (0..10_000).map(|i| (i, i)).collect();
+        let guard = map.guard();
+        map.par_compute_all(|&k, &v| if k % 2 == 0 { Some(v * 2) } else { None }, &guard);
+        assert_eq!(map.len(), 5_000);
+        assert!(map.iter(&guard).all(|(&k, &v)| k % 2 == 0 && v == k * 2));
+
+        let map = map.pin();
+        map.par_compute_all(|_, &v| Some(v + 1));
+        assert!(map.iter().all(|(&k, &v)| v == k * 2 + 1));
+        map.par_compute_all(|_, _| None);
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn hm_parallel_compute_all_during_updates() {
+        let map: HashMap<usize, usize> = (0..1_000).map(|i| (i, 0)).collect();
+
+        // concurrent increments are applied under the same bin locks, so none of them is lost
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let guard = map.guard();
+                for _ in 0..10 {
+                    for i in 0..1_000 {
+                        map.compute_if_present(&i, |_, &v| Some(v + 1), &guard);
+                    }
+                }
+            });
+            for _ in 0..10 {
+                map.par_compute_all(|_, &v| Some(v + 1), &map.guard());
+            }
+        });
+        let guard = map.guard();
+        assert!(map.iter(&guard).all(|(_, &v)| v == 20));
+    }
 }
@@ -0,0 +1,176 @@
+//! The map's interface to the epoch-based garbage collector.
+//!
+//! All garbage the map produces is retired through this module rather than directly through the
+//! [`Guard`], so that reclamation can be observed (see the `trace` module and the `garbage-stats`
+//! feature) and checked (see the `leak-check` feature).
+
+#[cfg(feature = "garbage-stats")]
+use crate::diagnostics::GarbageStats;
+#[cfg(feature = "reclaim-trace")]
+use crate::guard::{Collector, PinsRef};
+use crossbeam_epoch::{Guard, Shared};
+#[cfg(feature = "garbage-stats")]
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+/// The garbage retired by a single map.
+pub(crate) struct Garbage {
+    #[cfg(feature = "garbage-stats")]
+    counts: Arc<Counts>,
+    /// The registry of the map's collector, which holds the collector's `trace::Tracker`.
+    #[cfg(feature = "reclaim-trace")]
+    pins: PinsRef,
+}
+
+#[cfg(feature = "garbage-stats")]
+#[derive(Default)]
+struct Counts {
+    retired: AtomicUsize,
+    destroyed: AtomicUsize,
+    retired_bytes: AtomicUsize,
+    destroyed_bytes: AtomicUsize,
+}
+
+impl Garbage {
+    pub(crate) fn new() -> Self {
+        Garbage {
+            #[cfg(feature = "garbage-stats")]
+            counts: Arc::default(),
+            #[cfg(feature = "reclaim-trace")]
+            pins: Collector::global().pins().clone(),
+        }
+    }
+
+    /// Attributes the garbage retired from now on to the collector with the given registry.
+    #[cfg(feature = "reclaim-trace")]
+    pub(crate) fn set_collector(&mut self, pins: &PinsRef) {
+        self.pins = pins.clone();
+    }
+
+    /// Retires `ptr`, so that it is dropped once no thread can still be holding a reference to it.
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`Guard::defer_destroy`].
+    #[inline]
+    pub(crate) unsafe fn retire<T>(&self, guard: &Guard, ptr: Shared<'_, T>) {
+        self.retire_with(guard, std::mem::size_of::<T>(), move || {
+            drop(ptr.into_owned())
+        });
+    }
+
+    /// Runs `f` once no thread can still be holding a reference to the garbage it cleans up,
+    /// which takes up `bytes` bytes.
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`Guard::defer_unchecked`].
+    #[inline]
+    #[cfg_attr(not(feature = "garbage-stats"), allow(unused_variables))]
+    pub(crate) unsafe fn retire_with<F>(&self, guard: &Guard, bytes: usize, f: F)
+    where
+        F: FnOnce(),
+    {
+        #[cfg(feature = "reclaim-trace")]
+        let f = {
+            // an unprotected guard runs `f` right away, without going through the collector
+            let pins = guard.collector().map(|_| self.pins.clone());
+            if let Some(ref pins) = pins {
+                pins.tracker().retired();
+            }
+            move || {
+                f();
+                if let Some(pins) = pins {
+                    pins.tracker().destroyed();
+                }
+            }
+        };
+
+        #[cfg(feature = "garbage-stats")]
+        let f = {
+            let counts = Arc::clone(&self.counts);
+            counts.retired.fetch_add(1, Ordering::Relaxed);
+            counts.retired_bytes.fetch_add(bytes, Ordering::Relaxed);
+            move || {
+                f();
+                counts.destroyed_bytes.fetch_add(bytes, Ordering::Relaxed);
+                counts.destroyed.fetch_add(1, Ordering::Relaxed);
+            }
+        };
+
+        guard.defer_unchecked(f);
+    }
+
+    /// Hands the garbage retired through `guard` to the collector, and reclaims what it can.
+    #[inline]
+    pub(crate) fn flush(&self, guard: &Guard) {
+        #[cfg(feature = "reclaim-trace")]
+        {
+            if guard.collector().is_some() {
+                let tracker = self.pins.tracker();
+                if tracker.probe() {
+                    let pins = self.pins.clone();
+                    guard.defer(move || pins.tracker().advanced());
+                }
+                guard.flush();
+                tracker.flushed();
+                return;
+            }
+        }
+
+        guard.flush();
+    }
+
+    /// Returns how much garbage this map has retired and how much of it has been destroyed.
+    #[cfg(feature = "garbage-stats")]
+    pub(crate) fn stats(&self) -> GarbageStats {
+        // load the destructions first, so that we do not see more of them than retirements.
+        let reclaimed = self.counts.destroyed.load(Ordering::Relaxed);
+        let reclaimed_bytes = self.counts.destroyed_bytes.load(Ordering::Relaxed);
+        GarbageStats {
+            retired: self.counts.retired.load(Ordering::Relaxed),
+            reclaimed,
+            retired_bytes: self.counts.retired_bytes.load(Ordering::Relaxed),
+            reclaimed_bytes,
+        }
+    }
+
+    /// Returns the number of objects this map retired that have not been destroyed yet.
+    #[cfg(feature = "garbage-stats")]
+    pub(crate) fn outstanding(&self) -> usize {
+        // the counters are updated independently, so we may see a destruction before the
+        // corresponding retirement.
+        let destroyed = self.counts.destroyed.load(Ordering::Relaxed);
+        self.counts
+            .retired
+            .load(Ordering::Relaxed)
+            .saturating_sub(destroyed)
+    }
+
+    /// Returns the number of bytes taken up by the objects this map retired that have not been
+    /// destroyed yet.
+    #[cfg(feature = "garbage-stats")]
+    pub(crate) fn outstanding_bytes(&self) -> usize {
+        let destroyed = self.counts.destroyed_bytes.load(Ordering::Relaxed);
+        self.counts
+            .retired_bytes
+            .load(Ordering::Relaxed)
+            .saturating_sub(destroyed)
+    }
+}
+
+/// Retires `ptr` without attributing it to any map.
+///
+/// This is for garbage that does not belong to the map's contents (such as a parked thread's
+/// handle), and does not need to be reclaimed before the map is dropped. It is not reported by the
+/// `trace` module either.
+///
+/// # Safety
+///
+/// Same as for [`Guard::defer_destroy`].
+#[inline]
+pub(crate) unsafe fn retire<T>(guard: &Guard, ptr: Shared<'_, T>) {
+    guard.defer_destroy(ptr);
+}
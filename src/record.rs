@@ -0,0 +1,284 @@
+//! Recording and replaying the changes made to a map.
+//!
+//! A [`Recorder`] attached to a map with [`HashMap::with_recorder`] logs every insertion,
+//! replacement, and removal that the map performs, together with the thread that performed it and
+//! a logical timestamp that orders it relative to all other recorded changes. The log is kept in a
+//! ring buffer of bounded size, so the recorder can be left enabled in long-running processes and
+//! still tell you about the most recent history of the map. Changes to the same key are always
+//! recorded in the order in which they took effect.
+//!
+//! The recorded log can be inspected with [`Recorder::records`], dumped when the process panics
+//! with [`Recorder::dump_on_panic`], and re-applied to a fresh map with [`replay`].
+//!
+//! Recording clones every key and value that is changed, and serializes all changes to the map
+//! through the recorder's lock. It is meant as a debugging aid, not as a replication mechanism.
+//!
+//! ```
+//! use flurry::record::{self, Op, Recorder};
+//! use flurry::HashMap;
+//! use std::sync::Arc;
+//!
+//! let recorder = Arc::new(Recorder::new(128));
+//! let map = HashMap::new().with_recorder(Arc::clone(&recorder));
+//! map.pin().insert(1, "a");
+//! map.pin().insert(1, "b");
+//! map.pin().remove(&1);
+//!
+//! let records = recorder.records();
+//! assert_eq!(records.len(), 3);
+//! assert_eq!(records[2].op, Op::Remove { key: 1, old: "b" });
+//!
+//! // replaying the log reconstructs the map
+//! let copy = HashMap::new();
+//! record::replay(&records, &copy, &copy.guard());
+//! assert_eq!(copy, map);
+//! ```
+//!
+//! [`HashMap::with_recorder`]: crate::HashMap::with_recorder
+
+use crate::observe::{Change, Observer};
+use crate::{HashMap, TreeKey};
+use crossbeam_epoch::Guard;
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::fmt::{self, Debug, Formatter};
+use std::hash::{BuildHasher, Hash};
+use std::sync::Arc;
+use std::thread::{self, ThreadId};
+
+/// A change made to a map.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op<K, V> {
+    /// `key` was inserted with `value`.
+    Insert {
+        /// The inserted key.
+        key: K,
+        /// The inserted value.
+        value: V,
+    },
+    /// The value of `key` was replaced with `value`.
+    Replace {
+        /// The key whose value was replaced.
+        key: K,
+        /// The value that was replaced.
+        old: V,
+        /// The new value.
+        value: V,
+    },
+    /// `key` was removed.
+    Remove {
+        /// The removed key.
+        key: K,
+        /// The value `key` had when it was removed.
+        old: V,
+    },
+    /// A mutable reference to the value of `key` was handed out, such as by
+    /// [`HashMap::get_mut`], so the value may have been changed in place.
+    ///
+    /// The map does not see what the value is changed to, so neither does the recorder.
+    Lend {
+        /// The key whose value was lent out.
+        key: K,
+        /// The value `key` had when it was lent out.
+        old: V,
+    },
+}
+
+/// A change made to a map, as logged by a [`Recorder`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record<K, V> {
+    /// The position of this change among all the changes logged by the recorder.
+    ///
+    /// Timestamps start at 0 and increase by one with every recorded change.
+    pub timestamp: u64,
+    /// The thread that made the change.
+    pub thread: ThreadId,
+    /// The change itself.
+    pub op: Op<K, V>,
+}
+
+struct Log<K, V> {
+    records: VecDeque<Record<K, V>>,
+    capacity: usize,
+    next_timestamp: u64,
+}
+
+/// A bounded log of the changes made to one or more maps.
+///
+/// See the [module-level documentation](self) for details.
+pub struct Recorder<K, V> {
+    log: Mutex<Log<K, V>>,
+}
+
+impl<K, V> Debug for Recorder<K, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let log = self.log.lock();
+        f.debug_struct("Recorder")
+            .field("capacity", &log.capacity)
+            .field("len", &log.records.len())
+            .field("recorded", &log.next_timestamp)
+            .finish()
+    }
+}
+
+impl<K, V> Recorder<K, V> {
+    /// Creates a recorder that retains the `capacity` most recent changes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is 0.
+    pub fn new(capacity: usize) -> Self {
+        assert!(
+            capacity > 0,
+            "a recorder must be able to hold at least one record"
+        );
+        Recorder {
+            log: Mutex::new(Log {
+                records: VecDeque::with_capacity(capacity),
+                capacity,
+                next_timestamp: 0,
+            }),
+        }
+    }
+
+    /// Returns the total number of changes recorded so far, including ones that have since been
+    /// evicted from the log.
+    pub fn recorded(&self) -> u64 {
+        self.log.lock().next_timestamp
+    }
+
+    /// Returns the number of changes recorded so far that have since been evicted from the log to
+    /// make room for newer ones.
+    pub fn evicted(&self) -> u64 {
+        let log = self.log.lock();
+        log.next_timestamp - log.records.len() as u64
+    }
+
+    /// Discards all retained records.
+    ///
+    /// Timestamps keep increasing across calls to `clear`.
+    pub fn clear(&self) {
+        self.log.lock().records.clear();
+    }
+
+    fn push(&self, op: Op<K, V>) {
+        let thread = thread::current().id();
+        let mut log = self.log.lock();
+        if log.records.len() == log.capacity {
+            log.records.pop_front();
+        }
+        let timestamp = log.next_timestamp;
+        log.next_timestamp += 1;
+        log.records.push_back(Record {
+            timestamp,
+            thread,
+            op,
+        });
+    }
+}
+
+impl<K, V> Recorder<K, V>
+where
+    K: Clone,
+    V: Clone,
+{
+    /// Returns the retained records, oldest first.
+    pub fn records(&self) -> Vec<Record<K, V>> {
+        self.log.lock().records.iter().cloned().collect()
+    }
+}
+
+impl<K, V> Recorder<K, V>
+where
+    K: 'static + Debug + Send,
+    V: 'static + Debug + Send,
+{
+    /// Prints the retained records to standard error if the process panics.
+    ///
+    /// The records are printed before the previously installed panic hook runs. The hook only
+    /// holds a weak reference to the recorder, and does nothing once the recorder has been
+    /// dropped.
+    pub fn dump_on_panic(self: &Arc<Self>) {
+        let recorder = Arc::downgrade(self);
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if let Some(recorder) = recorder.upgrade() {
+                // the panic may have happened while the log was locked (e.g., in a `Clone` impl),
+                // in which case waiting for the lock would deadlock.
+                match recorder.log.try_lock() {
+                    Some(log) => {
+                        eprintln!(
+                            "flurry: last {} of {} recorded changes:",
+                            log.records.len(),
+                            log.next_timestamp
+                        );
+                        for record in &log.records {
+                            eprintln!(
+                                "  [{}] {:?}: {:?}",
+                                record.timestamp, record.thread, record.op
+                            );
+                        }
+                    }
+                    None => eprintln!("flurry: recorded changes are unavailable (log is locked)"),
+                }
+            }
+            previous(info);
+        }));
+    }
+}
+
+impl<K, V> Observer<K, V> for Recorder<K, V>
+where
+    K: Clone + Send,
+    V: Clone + Send,
+{
+    fn observe(&self, change: Change<'_, K, V>) {
+        self.push(match change {
+            Change::Inserted { key, value } => Op::Insert {
+                key: key.clone(),
+                value: value.clone(),
+            },
+            Change::Replaced { key, old, value } => Op::Replace {
+                key: key.clone(),
+                old: old.clone(),
+                value: value.clone(),
+            },
+            Change::Removed { key, old } => Op::Remove {
+                key: key.clone(),
+                old: old.clone(),
+            },
+            Change::Lent { key, value } => Op::Lend {
+                key: key.clone(),
+                old: value.clone(),
+            },
+        });
+    }
+}
+
+/// Applies the changes in `records` to `map`, in order.
+///
+/// Insertions and replacements insert the recorded value, and removals remove the recorded key.
+/// The recorded old values are not checked against the current contents of `map`. Since the
+/// changes made to a [lent out](Op::Lend) value were never recorded, those records are skipped,
+/// and the replayed map keeps the value from before the change.
+pub fn replay<'r, K, V, S>(
+    records: impl IntoIterator<Item = &'r Record<K, V>>,
+    map: &HashMap<K, V, S>,
+    guard: &Guard,
+) where
+    K: 'static + Sync + Send + Clone + Hash + TreeKey,
+    V: 'static + Sync + Send + Clone,
+    S: BuildHasher,
+{
+    for record in records {
+        match &record.op {
+            Op::Insert { key, value } | Op::Replace { key, value, .. } => {
+                map.insert(key.clone(), value.clone(), guard);
+            }
+            Op::Remove { key, .. } => {
+                map.remove(key, guard);
+            }
+            Op::Lend { .. } => {}
+        }
+    }
+}
@@ -0,0 +1,93 @@
+//! Hooks for driving the map's thread interleavings from a deterministic scheduler.
+//!
+//! Model checkers like `loom` explore interleavings exhaustively, which quickly becomes infeasible
+//! for a data structure of this size. Randomized schedulers (such as `shuttle`) instead pick a
+//! thread to run at every synchronization point, and can be seeded to replay a schedule that
+//! exposed a bug. To let such a scheduler control the map, this module lets you install a hook
+//! that is called right _before_ the map performs any operation through which it may observe or
+//! affect another thread: loads and updates of its internal counters, loads and updates of bin
+//! heads, acquiring a bin lock, spinning, and parking. The hook may block, yield, or switch to
+//! another thread as it sees fit.
+//!
+//! While a hook is installed, the map never blocks a thread outside of it. A thread that waits
+//! for a bin lock keeps trying to take it, and reports a [`Point::Yield`] after every failed
+//! attempt, and parking a thread only reports a [`Point::Park`] and then returns as if the thread
+//! had been woken up spuriously. That way, a scheduler that stopped the thread holding the lock,
+//! or the thread that would unpark the waiter, at one of its own hooks gets to run it again.
+//!
+//! The hook is process-global and installed with [`set_hook`]. It is only available with the
+//! `schedule-hooks` feature; without it, none of the calls to the hook are compiled in.
+//!
+//! ```
+//! use flurry::schedule::{self, Point};
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//!
+//! static LOCKS: AtomicUsize = AtomicUsize::new(0);
+//!
+//! fn hook(point: Point) {
+//!     if point == Point::Lock {
+//!         LOCKS.fetch_add(1, Ordering::Relaxed);
+//!     }
+//!     // a scheduler would pick the next thread to run here, e.g. `shuttle::thread::yield_now()`
+//! }
+//!
+//! schedule::set_hook(Some(hook));
+//! let map = flurry::HashMap::new();
+//! let guard = map.guard();
+//! map.insert(1, 1, &guard);
+//! // bin 1 is not empty any more, so this insert takes the bin's lock
+//! map.insert(1, 2, &guard);
+//! schedule::set_hook(None);
+//! assert!(LOCKS.load(Ordering::Relaxed) >= 1);
+//! ```
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A point at which the map synchronizes with other threads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Point {
+    /// A load, store, or read-modify-write of one of the map's internal counters (its size, its
+    /// resize control word, or a tree bin's lock state).
+    Atomic,
+    /// A load of the head of a bin.
+    BinLoad,
+    /// A compare-and-swap of the head of a bin.
+    BinCas,
+    /// An unconditional store to the head of a bin.
+    BinStore,
+    /// The acquisition of a bin lock.
+    Lock,
+    /// A busy-wait iteration while waiting for another thread.
+    Spin,
+    /// A voluntary yield of the current thread while waiting for another thread.
+    Yield,
+    /// Parking the current thread until another thread wakes it up.
+    Park,
+}
+
+// stores the installed `fn(Point)` as a `usize`, with 0 meaning "no hook"
+static HOOK: AtomicUsize = AtomicUsize::new(0);
+
+/// Installs `hook` to be called at every synchronization [`Point`], replacing any previous hook.
+///
+/// Passing `None` removes the current hook.
+pub fn set_hook(hook: Option<fn(Point)>) {
+    HOOK.store(hook.map_or(0, |hook| hook as usize), Ordering::SeqCst);
+}
+
+/// Returns `true` if a hook is installed.
+#[inline]
+pub(crate) fn installed() -> bool {
+    HOOK.load(Ordering::Relaxed) != 0
+}
+
+#[inline]
+pub(crate) fn call(point: Point) {
+    let hook = HOOK.load(Ordering::Relaxed);
+    if hook != 0 {
+        // safety: the only non-zero values ever stored in `HOOK` are `fn(Point)` pointers cast to
+        // `usize` by `set_hook`.
+        let hook: fn(Point) = unsafe { std::mem::transmute(hook) };
+        hook(point);
+    }
+}
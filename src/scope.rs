@@ -0,0 +1,296 @@
+//! Maps whose keys and values may borrow from the stack.
+//!
+//! A [`HashMap`] does not drop the values it removes right away, but hands them to a garbage
+//! collector, which drops them once no thread can be reading them anymore. That may be arbitrarily
+//! late, which is why inserting into a map requires `K: 'static` and `V: 'static`.
+//!
+//! [`scope`] lifts that restriction, much like [`std::thread::scope`] does for threads. The maps
+//! created through a [`Scope`] share a collector of their own, which is dropped before `scope`
+//! returns, and dropping it drops all the garbage it still holds. Their keys and values therefore
+//! only need to outlive the call to `scope`.
+//!
+//! To guarantee that nothing keeps the collector alive for longer, a [`ScopedHashMap`] does not
+//! give access to the underlying [`HashMap`], and its operations take a [`ScopedGuard`] rather
+//! than a [`Guard`](crate::epoch::Guard). Neither can leave the closure passed to `scope`. If one
+//! of them is leaked with [`std::mem::forget`], the garbage of the scope is leaked along with it.
+//!
+//! ```
+//! use flurry::scope;
+//!
+//! let words = String::from("the quick brown fox jumps over the lazy dog");
+//!
+//! let longest = scope(|s| {
+//!     let lengths = s.map();
+//!     let guard = s.guard();
+//!     for word in words.split(' ') {
+//!         lengths.insert(word, word.len(), &guard);
+//!     }
+//!     lengths.remove(&"the", &guard);
+//!     lengths.iter(&guard).map(|(_, &n)| n).max()
+//! });
+//! assert_eq!(longest, Some(5));
+//! ```
+//!
+//! The maps of a scope can be shared with the threads of a [`std::thread::scope`] started within
+//! it, each of which pins with a guard of its own:
+//!
+//! ```
+//! use flurry::scope;
+//!
+//! let names = vec![String::from("ada"), String::from("grace")];
+//!
+//! scope(|s| {
+//!     let index = s.map();
+//!     std::thread::scope(|t| {
+//!         for (i, name) in names.iter().enumerate() {
+//!             let index = &index;
+//!             t.spawn(move || index.insert(name.as_str(), i, &index.guard()).is_none());
+//!         }
+//!     });
+//!     assert_eq!(index.get("grace", &index.guard()), Some(&1));
+//! });
+//! ```
+
+use crate::epoch::Collector;
+use crate::iter::Iter;
+use crate::{Comparable, DefaultHashBuilder, HashMap, TreeKey};
+use crossbeam_epoch::Guard;
+use std::fmt::{self, Debug, Formatter};
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+
+/// Creates a scope for maps whose keys and values only need to outlive the call.
+///
+/// The closure is passed a [`Scope`], which creates [`ScopedHashMap`]s and the guards to use them
+/// with. Once the closure returns, all the maps and guards of the scope are gone, and all the keys
+/// and values they retired are dropped before `scope` returns.
+///
+/// See the [module-level documentation](self) for details.
+pub fn scope<'env, F, T>(f: F) -> T
+where
+    F: for<'scope> FnOnce(&'scope Scope<'scope, 'env>) -> T,
+{
+    let scope = Scope {
+        collector: Collector::new(),
+        scope: PhantomData,
+        env: PhantomData,
+    };
+    // the maps and guards of the scope cannot outlive the closure, so unless one of them was
+    // leaked, `scope` holds the last reference to the collector once it returns. dropping that
+    // when we return drops all the garbage the collector still holds.
+    f(&scope)
+}
+
+/// A scope for maps whose keys and values borrow from outside of it, created by [`scope`].
+pub struct Scope<'scope, 'env: 'scope> {
+    collector: Collector,
+    // invariant, like the lifetimes of `std::thread::Scope`, so that maps and guards of one scope
+    // cannot be passed off as those of another.
+    scope: PhantomData<&'scope mut &'scope ()>,
+    env: PhantomData<&'env mut &'env ()>,
+}
+
+impl<'scope, 'env> Scope<'scope, 'env> {
+    /// Creates an empty map that reclaims memory with the collector of this scope.
+    pub fn map<K, V>(&'scope self) -> ScopedHashMap<'scope, K, V>
+    where
+        K: 'env,
+        V: 'env,
+    {
+        self.map_with_hasher(DefaultHashBuilder::default())
+    }
+
+    /// Creates an empty map that reclaims memory with the collector of this scope, and uses
+    /// `hash_builder` to hash the keys.
+    pub fn map_with_hasher<K, V, S>(&'scope self, hash_builder: S) -> ScopedHashMap<'scope, K, V, S>
+    where
+        K: 'env,
+        V: 'env,
+    {
+        ScopedHashMap {
+            map: HashMap::with_hasher(hash_builder).with_collector(&self.collector),
+            scope: PhantomData,
+        }
+    }
+
+    /// Pin a guard for use with all the maps of this scope.
+    pub fn guard(&'scope self) -> ScopedGuard<'scope> {
+        ScopedGuard {
+            guard: self.collector.guard().into_inner(),
+            scope: PhantomData,
+        }
+    }
+}
+
+impl Debug for Scope<'_, '_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scope").finish()
+    }
+}
+
+/// A guard for use with the maps of a [`Scope`].
+///
+/// Unlike a [`Guard`](crate::epoch::Guard), it does not give access to its collector, and cannot
+/// outlive the scope.
+pub struct ScopedGuard<'scope> {
+    guard: Guard,
+    scope: PhantomData<&'scope mut &'scope ()>,
+}
+
+impl Debug for ScopedGuard<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScopedGuard").finish()
+    }
+}
+
+/// A [`HashMap`] whose keys and values only need to outlive a [`Scope`].
+///
+/// Created with [`Scope::map`]. See the [module-level documentation](self) for details.
+pub struct ScopedHashMap<'scope, K, V, S = DefaultHashBuilder> {
+    map: HashMap<K, V, S>,
+    scope: PhantomData<&'scope mut &'scope ()>,
+}
+
+impl<'scope, K, V, S> ScopedHashMap<'scope, K, V, S> {
+    /// Pin a guard for use with this map, and all the other maps of its scope.
+    pub fn guard(&self) -> ScopedGuard<'scope> {
+        ScopedGuard {
+            guard: self.map.guard().into_inner(),
+            scope: PhantomData,
+        }
+    }
+
+    /// Returns the number of entries in the map.
+    ///
+    /// See also [`HashMap::len`].
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns `true` if the map is empty. Otherwise returns `false`.
+    ///
+    /// See also [`HashMap::is_empty`].
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// An iterator visiting all key-value pairs in arbitrary order.
+    ///
+    /// See also [`HashMap::iter`].
+    pub fn iter<'g>(&'g self, guard: &'g ScopedGuard<'scope>) -> Iter<'g, K, V> {
+        self.map.iter(&guard.guard)
+    }
+}
+
+impl<'scope, K, V, S> ScopedHashMap<'scope, K, V, S>
+where
+    K: Clone + TreeKey,
+{
+    /// Clears the map, removing all key-value pairs.
+    ///
+    /// See also [`HashMap::clear`].
+    pub fn clear(&self, guard: &ScopedGuard<'scope>) {
+        self.map.clear(&guard.guard)
+    }
+}
+
+impl<'scope, K, V, S> ScopedHashMap<'scope, K, V, S>
+where
+    K: Hash + TreeKey,
+    S: BuildHasher,
+{
+    /// Tests if `key` is a key in this map.
+    ///
+    /// See also [`HashMap::contains_key`].
+    pub fn contains_key<Q>(&self, key: &Q, guard: &ScopedGuard<'scope>) -> bool
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+    {
+        self.map.contains_key(key, &guard.guard)
+    }
+
+    /// Returns a reference to the value corresponding to the key.
+    ///
+    /// See also [`HashMap::get`].
+    pub fn get<'g, Q>(&'g self, key: &Q, guard: &'g ScopedGuard<'scope>) -> Option<&'g V>
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+    {
+        self.map.get(key, &guard.guard)
+    }
+}
+
+impl<'scope, K, V, S> ScopedHashMap<'scope, K, V, S>
+where
+    K: Sync + Send + Clone + Hash + TreeKey,
+    V: Sync + Send,
+    S: BuildHasher,
+{
+    /// Inserts a key-value pair into the map.
+    ///
+    /// See also [`HashMap::insert`].
+    pub fn insert<'g>(&'g self, key: K, value: V, guard: &'g ScopedGuard<'scope>) -> Option<&'g V> {
+        self.map.check_guard(&guard.guard);
+        self.map.put(key, value, false, &guard.guard).before()
+    }
+
+    /// Removes a key-value pair from the map, and returns the removed value (if any).
+    ///
+    /// See also [`HashMap::remove`].
+    pub fn remove<'g, Q>(&'g self, key: &Q, guard: &'g ScopedGuard<'scope>) -> Option<&'g V>
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+    {
+        self.map.check_guard(&guard.guard);
+        self.map
+            .replace_node(key, None, None, &guard.guard)
+            .map(|(_, v)| v)
+    }
+}
+
+impl<K, V, S> Debug for ScopedHashMap<'_, K, V, S>
+where
+    K: Debug,
+    V: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.map.fmt(f)
+    }
+}
+
+/// Checks that nothing that keeps the collector of a scope alive can leave it.
+///
+/// # Maps and guards do not outlive the scope.
+///
+/// ```compile_fail
+/// let map = flurry::scope(|s| s.map::<(), ()>());
+/// ```
+/// ```compile_fail
+/// let guard = flurry::scope(|s| s.guard());
+/// ```
+/// ```compile_fail
+/// let guard = flurry::scope(|s| s.map::<(), ()>().guard());
+/// ```
+///
+/// # Keys and values outlive the scope.
+///
+/// ```compile_fail
+/// flurry::scope(|s| {
+///     let key = String::from("key");
+///     let map = s.map();
+///     map.insert(key.as_str(), (), &s.guard());
+/// });
+/// ```
+///
+/// # Guards of one scope cannot be used in another.
+///
+/// ```compile_fail
+/// flurry::scope(|outer| {
+///     let guard = outer.guard();
+///     flurry::scope(|inner| {
+///         inner.map::<(), ()>().insert((), (), &guard);
+///     });
+/// });
+/// ```
+#[allow(dead_code)]
+struct CompileFailTests;
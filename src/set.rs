@@ -2,10 +2,12 @@
 //!
 //! See `HashSet` for details.
 
+use crate::diagnostics::Summary;
 use crate::epoch::Guard;
-use crate::iter::Keys;
-use crate::HashMap;
-use std::borrow::Borrow;
+#[cfg(feature = "rayon")]
+use crate::iter::ParKeys;
+use crate::iter::{IntoKeys, Keys};
+use crate::{Comparable, HashMap, HashSpreader, TreeKey, TryReserveError};
 use std::fmt::{self, Debug, Formatter};
 use std::hash::{BuildHasher, Hash};
 use std::iter::FromIterator;
@@ -147,14 +149,104 @@ impl<T, S> HashSet<T, S> {
         }
     }
 
+    /// Resizes the table of this set once it holds `load_factor` times as many elements as it has
+    /// bins, rather than three quarters as many.
+    ///
+    /// See also [`HashMap::with_load_factor`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `load_factor` is not positive and finite.
+    pub fn with_load_factor(mut self, load_factor: f32) -> Self {
+        self.map = self.map.with_load_factor(load_factor);
+        self
+    }
+
+    /// Prints at most `limit` elements when this set is formatted with `Debug`.
+    ///
+    /// See also [`HashMap::with_debug_limit`].
+    pub fn with_debug_limit(mut self, limit: usize) -> Self {
+        self.map = self.map.with_debug_limit(limit);
+        self
+    }
+
+    /// Applies `spreader` to every hash that the set's hasher produces, before the set uses it to
+    /// pick a bin.
+    ///
+    /// See also [`HashMap::with_hash_spreader`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the set already holds elements.
+    pub fn with_hash_spreader<H>(mut self, spreader: H) -> Self
+    where
+        H: HashSpreader + 'static,
+    {
+        self.map = self.map.with_hash_spreader(spreader);
+        self
+    }
+
+    /// Makes this set use `collector` to reclaim the memory of removed elements, rather than the
+    /// global collector.
+    ///
+    /// See also [`HashMap::with_collector`].
+    pub fn with_collector(mut self, collector: &crate::epoch::Collector) -> Self {
+        self.map = self.map.with_collector(collector);
+        self
+    }
+
+    /// Returns how much garbage the set has retired, and how much of it has been reclaimed.
+    ///
+    /// See also [`HashMap::garbage_stats`].
+    #[cfg(feature = "garbage-stats")]
+    pub fn garbage_stats(&self) -> crate::diagnostics::GarbageStats {
+        self.map.garbage_stats()
+    }
+
+    /// Tries to reclaim the memory of removed elements and replaced tables right away.
+    ///
+    /// See also [`HashMap::flush_garbage`].
+    pub fn flush_garbage(&self) {
+        self.map.flush_garbage();
+    }
+
+    /// Returns the [`Collector`](crate::epoch::Collector) this set uses to reclaim memory.
+    ///
+    /// See also [`HashMap::collector`].
+    pub fn collector(&self) -> crate::epoch::Collector {
+        self.map.collector()
+    }
+
     /// Pin a `Guard` for use with this set.
     ///
     /// Keep in mind that for as long as you hold onto this `Guard`, you are preventing the
     /// collection of garbage generated by the set.
-    pub fn guard(&self) -> crate::epoch::Guard {
+    pub fn guard(&self) -> crate::TrackedGuard {
         self.map.guard()
     }
 
+    /// Pin a `Guard` for use with this set, reusing a handle to the set's collector that the
+    /// calling thread keeps around.
+    ///
+    /// See also [`HashMap::guard_cached`].
+    pub fn guard_cached(&self) -> Guard {
+        self.map.guard_cached()
+    }
+
+    /// Returns a reference to the set's [`BuildHasher`].
+    ///
+    /// [`BuildHasher`]: std::hash::BuildHasher
+    pub fn hasher(&self) -> &S {
+        self.map.hasher()
+    }
+
+    /// Returns an overview of the set's size and layout.
+    ///
+    /// See also [`HashMap::summary`].
+    pub fn summary(&self) -> Summary {
+        self.map.summary()
+    }
+
     /// Returns the number of elements in the set.
     ///
     /// # Examples
@@ -173,6 +265,22 @@ impl<T, S> HashSet<T, S> {
         self.map.len()
     }
 
+    /// Returns the number of elements the set can hold before it resizes its table.
+    ///
+    /// See also [`HashMap::capacity`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashSet;
+    ///
+    /// let set: HashSet<i32> = HashSet::with_capacity(100);
+    /// assert!(set.capacity(&set.guard()) >= 100);
+    /// ```
+    pub fn capacity(&self, guard: &Guard) -> usize {
+        self.map.capacity(guard)
+    }
+
     /// Returns `true` if the set is empty. Otherwise returns `false`.
     ///
     /// # Examples
@@ -212,19 +320,55 @@ impl<T, S> HashSet<T, S> {
     pub fn iter<'g>(&'g self, guard: &'g Guard) -> Keys<'g, T, ()> {
         self.map.keys(guard)
     }
+
+    /// A parallel iterator visiting all elements in arbitrary order.
+    ///
+    /// The iterator element type is `&'g T`.
+    ///
+    /// See [`HashMap::par_iter`] for details.
+    #[cfg(feature = "rayon")]
+    pub fn par_iter<'g>(&'g self, guard: &'g Guard) -> ParKeys<'g, T, ()> {
+        self.map.par_keys(guard)
+    }
+
+    /// Consumes the set, and moves its elements into a [`std::collections::HashSet`] that uses a
+    /// clone of the set's hasher.
+    ///
+    /// See [`HashMap::into_std`] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashSet;
+    ///
+    /// let set: HashSet<_> = (0..4).collect();
+    /// let set = set.into_std();
+    /// assert!(set.contains(&3));
+    /// ```
+    pub fn into_std(self) -> std::collections::HashSet<T, S>
+    where
+        T: Hash + Eq,
+        S: BuildHasher + Clone,
+    {
+        let mut set =
+            std::collections::HashSet::with_capacity_and_hasher(self.len(), self.hasher().clone());
+        set.extend(self);
+        set
+    }
 }
 
 impl<T, S> HashSet<T, S>
 where
-    T: Hash + Ord,
+    T: Hash + TreeKey,
     S: BuildHasher,
 {
     /// Returns `true` if the given value is an element of this set.
     ///
-    /// The value may be any borrowed form of the set's value type, but
-    /// [`Hash`] and [`Ord`] on the borrowed form *must* match those for
-    /// the value type.
+    /// The value may be any borrowed form of the set's value type, or any other type that is
+    /// [`Comparable`] to it, but [`Hash`] and [`Ord`] on that type *must* match those for the
+    /// value type.
     ///
+    /// [`Comparable`]: crate::Comparable
     /// [`Ord`]: std::cmp::Ord
     /// [`Hash`]: std::hash::Hash
     ///
@@ -243,18 +387,18 @@ where
     #[inline]
     pub fn contains<'g, Q>(&self, value: &Q, guard: &'g Guard) -> bool
     where
-        T: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<T>,
     {
         self.map.contains_key(value, guard)
     }
 
     /// Returns a reference to the element in the set, if any, that is equal to the given value.
     ///
-    /// The value may be any borrowed form of the set's value type, but
-    /// [`Hash`] and [`Ord`] on the borrowed form *must* match those for
-    /// the value type.
+    /// The value may be any borrowed form of the set's value type, or any other type that is
+    /// [`Comparable`] to it, but [`Hash`] and [`Ord`] on that type *must* match those for the
+    /// value type.
     ///
+    /// [`Comparable`]: crate::Comparable
     /// [`Ord`]: std::cmp::Ord
     /// [`Hash`]: std::hash::Hash
     ///
@@ -270,15 +414,15 @@ where
     /// ```
     pub fn get<'g, Q>(&'g self, value: &Q, guard: &'g Guard) -> Option<&'g T>
     where
-        T: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<T>,
     {
         self.map.get_key_value(value, guard).map(|(k, _)| k)
     }
 
     /// Returns `true` if `self` has no elements in common with `other`.
     ///
-    /// This is equivalent to checking for an empty intersection.
+    /// This is equivalent to checking for an empty intersection. Only the smaller of the two sets
+    /// is iterated over.
     ///
     /// # Examples
     ///
@@ -302,8 +446,12 @@ where
         our_guard: &Guard,
         their_guard: &Guard,
     ) -> bool {
+        // only the smaller set needs to be walked
+        if other.len() < self.len() {
+            return other.is_disjoint(self, their_guard, our_guard);
+        }
         for value in self.iter(our_guard) {
-            if other.contains(&value, their_guard) {
+            if other.contains(value, their_guard) {
                 return false;
             }
         }
@@ -313,6 +461,10 @@ where
 
     /// Returns `true` if the set is a subset of another, i.e., `other` contains at least all the values in `self`.
     ///
+    /// If `self` has more elements than `other`, this returns `false` without looking at any of
+    /// them. Since the sets may be modified concurrently, their lengths are only a snapshot, and
+    /// so is the result.
+    ///
     /// # Examples
     ///
     /// ```
@@ -329,8 +481,12 @@ where
     /// assert!(!set.pin().is_subset(&sup.pin()));
     /// ```
     pub fn is_subset(&self, other: &HashSet<T, S>, our_guard: &Guard, their_guard: &Guard) -> bool {
+        // a set cannot fit into a smaller one
+        if self.len() > other.len() {
+            return false;
+        }
         for value in self.iter(our_guard) {
-            if !other.contains(&value, their_guard) {
+            if !other.contains(value, their_guard) {
                 return false;
             }
         }
@@ -367,14 +523,131 @@ where
         other.is_subset(self, their_guard, our_guard)
     }
 
-    pub(crate) fn guarded_eq(&self, other: &Self, our_guard: &Guard, their_guard: &Guard) -> bool {
+    /// Adds clones of the values that are in `self` or in `other` to `into`.
+    ///
+    /// Values that are in both sets are only added once. Since the results go straight into
+    /// `into`, this avoids building an intermediate set when they are needed in another kind of
+    /// collection.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::iter::FromIterator;
+    /// use flurry::HashSet;
+    ///
+    /// let a = HashSet::<i32>::from_iter(&[1, 2, 3]);
+    /// let b = HashSet::<i32>::from_iter(&[3, 4]);
+    ///
+    /// let mut union = Vec::new();
+    /// a.union_into(&b, &mut union, &a.guard(), &b.guard());
+    /// union.sort();
+    /// assert_eq!(union, vec![1, 2, 3, 4]);
+    /// ```
+    pub fn union_into<E>(
+        &self,
+        other: &HashSet<T, S>,
+        into: &mut E,
+        our_guard: &Guard,
+        their_guard: &Guard,
+    ) where
+        T: Clone,
+        E: Extend<T>,
+    {
+        into.extend(self.iter(our_guard).cloned());
+        into.extend(
+            other
+                .iter(their_guard)
+                .filter(|value| !self.contains(*value, our_guard))
+                .cloned(),
+        );
+    }
+
+    /// Adds clones of the values that are in both `self` and `other` to `into`.
+    ///
+    /// This visits the elements of whichever set is smaller.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::iter::FromIterator;
+    /// use flurry::HashSet;
+    ///
+    /// let a = HashSet::<i32>::from_iter(&[1, 2, 3]);
+    /// let b = HashSet::<i32>::from_iter(&[2, 3, 4]);
+    ///
+    /// let mut intersection = std::collections::BTreeSet::new();
+    /// a.intersection_into(&b, &mut intersection, &a.guard(), &b.guard());
+    /// assert_eq!(intersection.into_iter().collect::<Vec<_>>(), vec![2, 3]);
+    /// ```
+    pub fn intersection_into<E>(
+        &self,
+        other: &HashSet<T, S>,
+        into: &mut E,
+        our_guard: &Guard,
+        their_guard: &Guard,
+    ) where
+        T: Clone,
+        E: Extend<T>,
+    {
+        if self.len() <= other.len() {
+            into.extend(
+                self.iter(our_guard)
+                    .filter(|value| other.contains(*value, their_guard))
+                    .cloned(),
+            );
+        } else {
+            other.intersection_into(self, into, their_guard, our_guard);
+        }
+    }
+
+    /// Adds clones of the values that are in `self` but not in `other` to `into`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::iter::FromIterator;
+    /// use flurry::HashSet;
+    ///
+    /// let a = HashSet::<i32>::from_iter(&[1, 2, 3]);
+    /// let b = HashSet::<i32>::from_iter(&[2, 3, 4]);
+    ///
+    /// let mut difference = Vec::new();
+    /// a.difference_into(&b, &mut difference, &a.guard(), &b.guard());
+    /// assert_eq!(difference, vec![1]);
+    /// ```
+    pub fn difference_into<E>(
+        &self,
+        other: &HashSet<T, S>,
+        into: &mut E,
+        our_guard: &Guard,
+        their_guard: &Guard,
+    ) where
+        T: Clone,
+        E: Extend<T>,
+    {
+        into.extend(
+            self.iter(our_guard)
+                .filter(|value| !other.contains(*value, their_guard))
+                .cloned(),
+        );
+    }
+
+    pub(crate) fn guarded_eq<S2>(
+        &self,
+        other: &HashSet<T, S2>,
+        our_guard: &Guard,
+        their_guard: &Guard,
+    ) -> bool
+    where
+        S2: BuildHasher,
+    {
         self.map.guarded_eq(&other.map, our_guard, their_guard)
     }
 }
 
 impl<T, S> HashSet<T, S>
 where
-    T: 'static + Sync + Send + Clone + Hash + Ord,
+    T: 'static + Sync + Send + Clone + Hash + TreeKey,
     S: BuildHasher,
 {
     /// Adds a value to the set.
@@ -428,8 +701,7 @@ where
     /// ```
     pub fn remove<Q>(&self, value: &Q, guard: &Guard) -> bool
     where
-        T: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<T>,
     {
         let removed = self.map.remove(value, guard);
         removed.is_some()
@@ -437,10 +709,11 @@ where
 
     /// Removes and returns the value in the set, if any, that is equal to the given one.
     ///
-    /// The value may be any borrowed form of the set's value type, but
-    /// [`Hash`] and [`Ord`] on the borrowed form *must* match those for
-    /// the value type.
+    /// The value may be any borrowed form of the set's value type, or any other type that is
+    /// [`Comparable`] to it, but [`Hash`] and [`Ord`] on that type *must* match those for the
+    /// value type.
     ///
+    /// [`Comparable`]: crate::Comparable
     /// [`Ord`]: std::cmp::Ord
     /// [`Hash`]: std::hash::Hash
     ///
@@ -456,15 +729,36 @@ where
     /// ```
     pub fn take<'g, Q>(&'g self, value: &Q, guard: &'g Guard) -> Option<&'g T>
     where
-        T: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<T>,
     {
         self.map.remove_entry(value, guard).map(|(k, _)| k)
     }
 
+    /// Removes some element from the set, and returns it.
+    ///
+    /// Which element is removed is unspecified. See [`HashMap::pop`](crate::HashMap::pop), which
+    /// this is based on, for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashSet;
+    ///
+    /// let set: HashSet<_> = [1, 2].iter().cloned().collect();
+    /// let guard = set.guard();
+    /// let mut taken = vec![*set.take_any(&guard).unwrap(), *set.take_any(&guard).unwrap()];
+    /// taken.sort();
+    /// assert_eq!(taken, [1, 2]);
+    /// assert_eq!(set.take_any(&guard), None);
+    /// ```
+    pub fn take_any<'g>(&'g self, guard: &'g Guard) -> Option<&'g T> {
+        self.map.pop(guard).map(|(value, _)| value)
+    }
+
     /// Retains only the elements specified by the predicate.
     ///
-    /// In other words, remove all elements `e` such that `f(&e)` returns `false`.
+    /// In other words, remove all elements `e` such that `f(&e)` returns `false`. Returns the
+    /// number of elements that were removed.
     ///
     /// # Examples
     ///
@@ -476,10 +770,10 @@ where
     /// for i in 0..8 {
     ///     set.pin().insert(i);
     /// }
-    /// set.pin().retain(|&e| e % 2 == 0);
+    /// assert_eq!(set.pin().retain(|&e| e % 2 == 0), 4);
     /// assert_eq!(set.pin().len(), 4);
     /// ```
-    pub fn retain<F>(&self, mut f: F, guard: &Guard)
+    pub fn retain<F>(&self, mut f: F, guard: &Guard) -> usize
     where
         F: FnMut(&T) -> bool,
     {
@@ -489,7 +783,7 @@ where
 
 impl<T, S> HashSet<T, S>
 where
-    T: Clone + Ord,
+    T: Clone + TreeKey,
 {
     /// Clears the set, removing all elements.
     ///
@@ -515,21 +809,47 @@ where
     pub fn reserve(&self, additional: usize, guard: &Guard) {
         self.map.reserve(additional, guard)
     }
+
+    /// Tries to reserve capacity for at least `additional` more elements to
+    /// be inserted in the `HashSet`, and returns an error if the memory for
+    /// it cannot be allocated.
+    ///
+    /// See [`HashMap::try_reserve`] for details.
+    pub fn try_reserve(&self, additional: usize, guard: &Guard) -> Result<(), TryReserveError> {
+        self.map.try_reserve(additional, guard)
+    }
+
+    /// Shrinks the table of the set as much as possible, while keeping
+    /// enough room for its current elements.
+    ///
+    /// See [`HashMap::shrink_to_fit`] for details.
+    pub fn shrink_to_fit(&self, guard: &Guard) {
+        self.map.shrink_to_fit(guard)
+    }
+
+    /// Shrinks the table of the set so that it can still hold at least
+    /// `min_capacity` elements.
+    ///
+    /// See [`HashMap::shrink_to`] for details.
+    pub fn shrink_to(&self, min_capacity: usize, guard: &Guard) {
+        self.map.shrink_to(min_capacity, guard)
+    }
 }
 
-impl<T, S> PartialEq for HashSet<T, S>
+impl<T, S, S2> PartialEq<HashSet<T, S2>> for HashSet<T, S>
 where
-    T: Ord + Hash,
+    T: TreeKey + Hash,
     S: BuildHasher,
+    S2: BuildHasher,
 {
-    fn eq(&self, other: &Self) -> bool {
+    fn eq(&self, other: &HashSet<T, S2>) -> bool {
         self.map == other.map
     }
 }
 
 impl<T, S> Eq for HashSet<T, S>
 where
-    T: Ord + Hash,
+    T: TreeKey + Hash,
     S: BuildHasher,
 {
 }
@@ -540,13 +860,17 @@ where
 {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         let guard = self.guard();
-        f.debug_set().entries(self.iter(&guard)).finish()
+        let limit = self.map.debug_limit.unwrap_or(usize::MAX);
+        f.debug_set()
+            .entries(self.iter(&guard).take(limit))
+            .finish()?;
+        crate::map::fmt_remaining(f, self.len(), limit)
     }
 }
 
 impl<T, S> Extend<T> for &HashSet<T, S>
 where
-    T: 'static + Sync + Send + Clone + Hash + Ord,
+    T: 'static + Sync + Send + Clone + Hash + TreeKey,
     S: BuildHasher,
 {
     fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
@@ -556,7 +880,7 @@ where
 
 impl<'a, T, S> Extend<&'a T> for &HashSet<T, S>
 where
-    T: 'static + Sync + Send + Copy + Hash + Ord,
+    T: 'static + Sync + Send + Copy + Hash + TreeKey,
     S: BuildHasher,
 {
     fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
@@ -566,7 +890,7 @@ where
 
 impl<T, S> FromIterator<T> for HashSet<T, S>
 where
-    T: 'static + Sync + Send + Clone + Hash + Ord,
+    T: 'static + Sync + Send + Clone + Hash + TreeKey,
     S: BuildHasher + Default,
 {
     fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
@@ -576,9 +900,30 @@ where
     }
 }
 
+impl<T, S> From<std::collections::HashSet<T, S>> for HashSet<T, S>
+where
+    T: 'static + Sync + Send + Clone + Hash + TreeKey,
+    S: BuildHasher + Clone,
+{
+    /// Moves the elements of a [`std::collections::HashSet`] into a new set that uses a clone of
+    /// its hasher.
+    ///
+    /// See the corresponding implementation for [`HashMap`] for details.
+    fn from(set: std::collections::HashSet<T, S>) -> Self {
+        let len = set.len();
+        Self {
+            map: HashMap::from_distinct(
+                set.hasher().clone(),
+                len,
+                set.into_iter().map(|v| (v, ())),
+            ),
+        }
+    }
+}
+
 impl<'a, T, S> FromIterator<&'a T> for HashSet<T, S>
 where
-    T: 'static + Sync + Send + Copy + Hash + Ord,
+    T: 'static + Sync + Send + Copy + Hash + TreeKey,
     S: BuildHasher + Default,
 {
     fn from_iter<I: IntoIterator<Item = &'a T>>(iter: I) -> Self {
@@ -588,9 +933,22 @@ where
     }
 }
 
+impl<T, S> IntoIterator for HashSet<T, S> {
+    type Item = T;
+    type IntoIter = IntoKeys<T, ()>;
+
+    /// Consumes the set, and returns an iterator over its elements in arbitrary order.
+    ///
+    /// Since the set is owned, this does not need a guard, and the elements are moved out of the
+    /// set rather than cloned.
+    fn into_iter(self) -> Self::IntoIter {
+        self.map.into_keys()
+    }
+}
+
 impl<T, S> Clone for HashSet<T, S>
 where
-    T: 'static + Sync + Send + Clone + Hash + Ord,
+    T: 'static + Sync + Send + Clone + Hash + TreeKey,
     S: BuildHasher + Clone,
 {
     fn clone(&self) -> HashSet<T, S> {
@@ -1,7 +1,6 @@
 use crate::iter::*;
-use crate::{GuardRef, HashSet};
+use crate::{Comparable, GuardRef, HashSet, TreeKey, TryReserveError};
 use crossbeam_epoch::Guard;
-use std::borrow::Borrow;
 use std::fmt::{self, Debug, Formatter};
 use std::hash::{BuildHasher, Hash};
 
@@ -9,6 +8,9 @@ use std::hash::{BuildHasher, Hash};
 ///
 /// The current thread will be pinned for the duration of this reference.
 /// Keep in mind that this prevents the collection of garbage generated by the set.
+///
+/// Like [`HashMapRef`](crate::HashMapRef), its type is the same whether it owns its guard or
+/// borrows one.
 pub struct HashSetRef<'set, T, S = crate::DefaultHashBuilder> {
     pub(crate) set: &'set HashSet<T, S>,
     guard: GuardRef<'set>,
@@ -26,6 +28,16 @@ impl<T, S> HashSet<T, S> {
         }
     }
 
+    /// Get a reference to this set with the current thread pinned by a cached guard.
+    ///
+    /// See also [`HashMap::pin_cached`](crate::HashMap::pin_cached).
+    pub fn pin_cached(&self) -> HashSetRef<'_, T, S> {
+        HashSetRef {
+            guard: GuardRef::Cached(self.guard_cached()),
+            set: self,
+        }
+    }
+
     /// Get a reference to this set with the given guard.
     pub fn with_guard<'g>(&'g self, guard: &'g Guard) -> HashSetRef<'g, T, S> {
         HashSetRef {
@@ -35,6 +47,15 @@ impl<T, S> HashSet<T, S> {
     }
 }
 
+impl<T, S> crate::guard::private::Sealed for HashSetRef<'_, T, S> {}
+
+impl<T, S> crate::epoch::AsGuard for HashSetRef<'_, T, S> {
+    #[inline]
+    fn as_guard(&self) -> &Guard {
+        &self.guard
+    }
+}
+
 impl<T, S> HashSetRef<'_, T, S> {
     /// Returns the number of elements in the set.
     ///
@@ -43,6 +64,13 @@ impl<T, S> HashSetRef<'_, T, S> {
         self.set.len()
     }
 
+    /// Returns the number of elements the set can hold before it resizes its table.
+    ///
+    /// See also [`HashSet::capacity`].
+    pub fn capacity(&self) -> usize {
+        self.set.capacity(&self.guard)
+    }
+
     /// Returns `true` if the set is empty. Otherwise returns `false`.
     ///
     /// See also [`HashSet::is_empty`].
@@ -58,11 +86,21 @@ impl<T, S> HashSetRef<'_, T, S> {
     pub fn iter(&self) -> Keys<'_, T, ()> {
         self.set.iter(&self.guard)
     }
+
+    /// A parallel iterator visiting all elements in arbitrary order.
+    ///
+    /// The iterator element type is `&'g T`.
+    ///
+    /// See also [`HashSet::par_iter`].
+    #[cfg(feature = "rayon")]
+    pub fn par_iter(&self) -> ParKeys<'_, T, ()> {
+        self.set.par_iter(&self.guard)
+    }
 }
 
 impl<T, S> HashSetRef<'_, T, S>
 where
-    T: Hash + Ord,
+    T: Hash + TreeKey,
     S: BuildHasher,
 {
     /// Returns `true` if the given value is an element of this set.
@@ -71,8 +109,7 @@ where
     #[inline]
     pub fn contains<Q>(&self, value: &Q) -> bool
     where
-        T: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<T>,
     {
         self.set.contains(value, &self.guard)
     }
@@ -82,8 +119,7 @@ where
     /// See also [`HashSet::get`].
     pub fn get<'g, Q>(&'g self, value: &Q) -> Option<&'g T>
     where
-        T: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<T>,
     {
         self.set.get(value, &self.guard)
     }
@@ -108,11 +144,47 @@ where
     pub fn is_superset<'other>(&self, other: &HashSetRef<'other, T, S>) -> bool {
         self.set.is_superset(other.set, &self.guard, &other.guard)
     }
+
+    /// Adds clones of the values that are in `self` or in `other` to `into`.
+    ///
+    /// See also [`HashSet::union_into`].
+    pub fn union_into<E>(&self, other: &HashSetRef<'_, T, S>, into: &mut E)
+    where
+        T: Clone,
+        E: Extend<T>,
+    {
+        self.set
+            .union_into(other.set, into, &self.guard, &other.guard)
+    }
+
+    /// Adds clones of the values that are in both `self` and `other` to `into`.
+    ///
+    /// See also [`HashSet::intersection_into`].
+    pub fn intersection_into<E>(&self, other: &HashSetRef<'_, T, S>, into: &mut E)
+    where
+        T: Clone,
+        E: Extend<T>,
+    {
+        self.set
+            .intersection_into(other.set, into, &self.guard, &other.guard)
+    }
+
+    /// Adds clones of the values that are in `self` but not in `other` to `into`.
+    ///
+    /// See also [`HashSet::difference_into`].
+    pub fn difference_into<E>(&self, other: &HashSetRef<'_, T, S>, into: &mut E)
+    where
+        T: Clone,
+        E: Extend<T>,
+    {
+        self.set
+            .difference_into(other.set, into, &self.guard, &other.guard)
+    }
 }
 
 impl<T, S> HashSetRef<'_, T, S>
 where
-    T: 'static + Sync + Send + Clone + Hash + Ord,
+    T: 'static + Sync + Send + Clone + Hash + TreeKey,
     S: BuildHasher,
 {
     /// Adds a value to the set.
@@ -127,8 +199,7 @@ where
     /// See also [`HashSet::remove`].
     pub fn remove<Q>(&self, value: &Q) -> bool
     where
-        T: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<T>,
     {
         self.set.remove(value, &self.guard)
     }
@@ -138,26 +209,32 @@ where
     /// See also [`HashSet::take`].
     pub fn take<'g, Q>(&'g self, value: &Q) -> Option<&'g T>
     where
-        T: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<T>,
     {
         self.set.take(value, &self.guard)
     }
 
+    /// Removes some element from the set, and returns it.
+    ///
+    /// See also [`HashSet::take_any`].
+    pub fn take_any(&self) -> Option<&'_ T> {
+        self.set.take_any(&self.guard)
+    }
+
     /// Retains only the elements specified by the predicate.
     ///
     /// See also [`HashSet::retain`].
-    pub fn retain<F>(&self, f: F)
+    pub fn retain<F>(&self, f: F) -> usize
     where
         F: FnMut(&T) -> bool,
     {
-        self.set.retain(f, &self.guard);
+        self.set.retain(f, &self.guard)
     }
 }
 
 impl<T, S> HashSetRef<'_, T, S>
 where
-    T: Clone + Ord,
+    T: Clone + TreeKey,
 {
     /// Clears the set, removing all elements.
     ///
@@ -173,6 +250,30 @@ where
     pub fn reserve(&self, additional: usize) {
         self.set.reserve(additional, &self.guard)
     }
+
+    /// Tries to reserve capacity for at least `additional` more elements to
+    /// be inserted into the underlying `HashSet`, and returns an error if the
+    /// memory for it cannot be allocated.
+    ///
+    /// See also [`HashSet::try_reserve`].
+    pub fn try_reserve(&self, additional: usize) -> Result<(), TryReserveError> {
+        self.set.try_reserve(additional, &self.guard)
+    }
+
+    /// Shrinks the table of the underlying `HashSet` as much as possible.
+    ///
+    /// See also [`HashSet::shrink_to_fit`].
+    pub fn shrink_to_fit(&self) {
+        self.set.shrink_to_fit(&self.guard)
+    }
+
+    /// Shrinks the table of the underlying `HashSet` so that it can still
+    /// hold at least `min_capacity` elements.
+    ///
+    /// See also [`HashSet::shrink_to`].
+    pub fn shrink_to(&self, min_capacity: usize) {
+        self.set.shrink_to(min_capacity, &self.guard)
+    }
 }
 
 impl<'g, T, S> IntoIterator for &'g HashSetRef<'_, T, S> {
@@ -199,39 +300,65 @@ impl<T, S> Clone for HashSetRef<'_, T, S> {
     }
 }
 
-impl<T, S> PartialEq for HashSetRef<'_, T, S>
+impl<T, S, S2> PartialEq<HashSetRef<'_, T, S2>> for HashSetRef<'_, T, S>
 where
-    T: Hash + Ord,
+    T: Hash + TreeKey,
     S: BuildHasher,
+    S2: BuildHasher,
 {
-    fn eq(&self, other: &Self) -> bool {
+    fn eq(&self, other: &HashSetRef<'_, T, S2>) -> bool {
         self.set == other.set
     }
 }
 
-impl<T, S> PartialEq<HashSet<T, S>> for HashSetRef<'_, T, S>
+impl<T, S, S2> PartialEq<HashSet<T, S2>> for HashSetRef<'_, T, S>
 where
-    T: Hash + Ord,
+    T: Hash + TreeKey,
     S: BuildHasher,
+    S2: BuildHasher,
 {
-    fn eq(&self, other: &HashSet<T, S>) -> bool {
+    fn eq(&self, other: &HashSet<T, S2>) -> bool {
         self.set.guarded_eq(&other, &self.guard, &other.guard())
     }
 }
 
-impl<T, S> PartialEq<HashSetRef<'_, T, S>> for HashSet<T, S>
+impl<T, S, S2> PartialEq<HashSetRef<'_, T, S2>> for HashSet<T, S>
 where
-    T: Hash + Ord,
+    T: Hash + TreeKey,
     S: BuildHasher,
+    S2: BuildHasher,
 {
-    fn eq(&self, other: &HashSetRef<'_, T, S>) -> bool {
+    fn eq(&self, other: &HashSetRef<'_, T, S2>) -> bool {
         self.guarded_eq(&other.set, &self.guard(), &other.guard)
     }
 }
 
 impl<T, S> Eq for HashSetRef<'_, T, S>
 where
-    T: Hash + Ord,
+    T: Hash + TreeKey,
+    S: BuildHasher,
+{
+}
+
+impl<T, S> Extend<T> for HashSetRef<'_, T, S>
+where
+    T: 'static + Sync + Send + Clone + Hash + TreeKey,
     S: BuildHasher,
 {
+    /// Adds every value from `iter` to the set, under the guard of this reference.
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter().map(|v| (v, ()));
+        self.set.map.insert_all(iter, &self.guard);
+    }
+}
+
+impl<'a, T, S> Extend<&'a T> for HashSetRef<'_, T, S>
+where
+    T: 'static + Sync + Send + Copy + Hash + TreeKey,
+    S: BuildHasher,
+{
+    /// Adds a copy of every value from `iter` to the set, under the guard of this reference.
+    fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().copied());
+    }
 }
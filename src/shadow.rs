@@ -0,0 +1,157 @@
+//! Cross-checking a map against a reference implementation.
+//!
+//! See [`HashMap::with_shadow_checking`](crate::HashMap::with_shadow_checking).
+
+use crate::equivalent::Equivalent;
+use crate::observe::{Change, Observer};
+use parking_lot::{Mutex, ReentrantMutex, ReentrantMutexGuard};
+use std::collections::HashMap as StdHashMap;
+use std::fmt::Debug;
+use std::hash::Hash;
+
+/// A `std::collections::HashMap` that mirrors the contents of a flurry map.
+pub(crate) struct Shadow<K, V> {
+    /// Held for the entire duration of every operation on the shadowed map.
+    ///
+    /// This serializes all operations, so that the contents of the shadow always match what the
+    /// shadowed map should contain at the time of an operation. It has to be reentrant since
+    /// some operations are implemented in terms of others (e.g., `retain` and `replace_node`).
+    lock: ReentrantMutex<()>,
+    /// The expected value of every key, or `None` if the value was lent out mutably, and so may
+    /// have been changed in place since.
+    entries: Mutex<StdHashMap<K, Option<V>>>,
+    eq: fn(&V, &V) -> bool,
+    show_key: fn(&K) -> String,
+    show_value: fn(&V) -> String,
+}
+
+impl<K, V> Shadow<K, V> {
+    pub(crate) fn new() -> Self
+    where
+        K: Debug + Hash + Eq,
+        V: Debug + PartialEq,
+    {
+        Shadow {
+            lock: ReentrantMutex::new(()),
+            entries: Mutex::new(StdHashMap::new()),
+            eq: |a, b| a == b,
+            show_key: |k| format!("{:?}", k),
+            show_value: |v| format!("{:?}", v),
+        }
+    }
+
+    /// Adds entries that are already in the shadowed map.
+    pub(crate) fn mirror<'a>(&self, entries: impl Iterator<Item = (&'a K, &'a V)>)
+    where
+        K: 'a + Clone + Hash + Eq,
+        V: 'a + Clone,
+    {
+        self.entries
+            .lock()
+            .extend(entries.map(|(k, v)| (k.clone(), Some(v.clone()))));
+    }
+
+    pub(crate) fn enter(&self) -> ReentrantMutexGuard<'_, ()> {
+        self.lock.lock()
+    }
+
+    fn diverged(&self, what: &str, key: &K, expected: Option<&V>, found: Option<&V>) -> ! {
+        let show = |v: Option<&V>| v.map_or_else(|| "nothing".to_string(), self.show_value);
+        panic!(
+            "flurry shadow check failed: {} for key {}: expected {}, but found {}",
+            what,
+            (self.show_key)(key),
+            show(expected),
+            show(found)
+        );
+    }
+
+    /// Checks that the result of looking up `key` in the shadowed map is what we expect.
+    pub(crate) fn check_lookup<Q>(&self, key: &Q, found: Option<(&K, &V)>)
+    where
+        K: Hash + Eq,
+        Q: ?Sized + Equivalent<K>,
+    {
+        let entries = self.entries.lock();
+        // `key` may not be a type that the shadow can look up by, so we look up the key that was
+        // found instead, and only search through all entries if nothing was found.
+        let expected = match found {
+            Some((k, v)) if !key.equivalent(k) => {
+                self.diverged("lookup found a different key", k, None, Some(v))
+            }
+            Some((k, _)) => entries.get_key_value(k),
+            None => entries.iter().find(|(k, _)| key.equivalent(k)),
+        };
+        match (expected, found) {
+            (None, None) => {}
+            // the value was lent out, so we do not know what it is now
+            (Some((_, None)), Some(_)) => {}
+            (Some((_, Some(expected))), Some((_, v))) if (self.eq)(expected, v) => {}
+            (Some((k, expected)), Some((_, v))) => self.diverged(
+                "lookup returned the wrong value",
+                k,
+                expected.as_ref(),
+                Some(v),
+            ),
+            (Some((k, expected)), None) => {
+                self.diverged("lookup missed a key", k, expected.as_ref(), None)
+            }
+            (None, Some((k, v))) => self.diverged("lookup found a removed key", k, None, Some(v)),
+        }
+    }
+}
+
+impl<K, V> Observer<K, V> for Shadow<K, V>
+where
+    K: Clone + Hash + Eq + Send,
+    V: Clone + Send,
+{
+    fn observe(&self, change: Change<'_, K, V>) {
+        let mut entries = self.entries.lock();
+        match change {
+            Change::Inserted { key, value } => {
+                if let Some(expected) = entries.insert(key.clone(), Some(value.clone())) {
+                    self.diverged("inserted a key that exists", key, expected.as_ref(), None);
+                }
+            }
+            Change::Replaced { key, old, value } => {
+                match entries.insert(key.clone(), Some(value.clone())) {
+                    Some(Some(ref expected)) if (self.eq)(expected, old) => {}
+                    Some(None) => {}
+                    expected => self.diverged(
+                        "replaced the wrong value",
+                        key,
+                        expected.as_ref().and_then(Option::as_ref),
+                        Some(old),
+                    ),
+                }
+            }
+            Change::Removed { key, old } => match entries.remove(key) {
+                Some(Some(ref expected)) if (self.eq)(expected, old) => {}
+                Some(None) => {}
+                expected => self.diverged(
+                    "removed the wrong value",
+                    key,
+                    expected.as_ref().and_then(Option::as_ref),
+                    Some(old),
+                ),
+            },
+            Change::Lent { key, value } => match entries.get_mut(key) {
+                Some(expected) => {
+                    if let Some(ref expected) = expected {
+                        if !(self.eq)(expected, value) {
+                            self.diverged(
+                                "lent out the wrong value",
+                                key,
+                                Some(expected),
+                                Some(value),
+                            );
+                        }
+                    }
+                    *expected = None;
+                }
+                None => self.diverged("lent out a removed key", key, None, Some(value)),
+            },
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use crate::primitives::{AtomicU64, Ordering};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+/// A token for optimistically reading several entries of a map, obtained from
+/// [`HashMap::read_stamp`](crate::HashMap::read_stamp).
+///
+/// A stamp stays valid for as long as the map is not changed. Lookups made with
+/// [`HashMap::get_validated`](crate::HashMap::get_validated) succeed only while the stamp they are
+/// given is valid, so if a sequence of them all succeed with the same stamp, the values they
+/// returned were all present in the map at the same time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stamp {
+    // the number of writes that had started when the stamp was taken
+    started: u64,
+    // whether all of those writes had also finished
+    settled: bool,
+}
+
+/// The error returned by [`HashMap::get_validated`](crate::HashMap::get_validated) when the map
+/// has changed since the given [`Stamp`] was taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StaleStampError;
+
+impl Display for StaleStampError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "The map has changed since the stamp was taken")
+    }
+}
+
+impl Error for StaleStampError {}
+
+/// Counts the writes made to a map, so that readers can tell whether it changed.
+///
+/// Every write increments `started` before it changes the map, and `finished` once it is done. A
+/// stamp taken while no write is in progress is valid until `started` changes again.
+pub(crate) struct Stamps {
+    enabled: bool,
+    started: AtomicU64,
+    finished: AtomicU64,
+}
+
+impl Stamps {
+    pub(crate) fn new(enabled: bool) -> Self {
+        Stamps {
+            enabled,
+            started: AtomicU64::new(0),
+            finished: AtomicU64::new(0),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Marks the start of a write that lasts until the returned guard is dropped.
+    #[inline]
+    pub(crate) fn write(&self) -> StampedWrite<'_> {
+        if self.enabled {
+            self.started.fetch_add(1, Ordering::SeqCst);
+        }
+        StampedWrite { stamps: self }
+    }
+
+    pub(crate) fn stamp(&self) -> Stamp {
+        // a write that starts after we read `started` will invalidate the stamp, so we only need
+        // to make sure that all the writes that started before it have also finished.
+        let started = self.started.load(Ordering::SeqCst);
+        let finished = self.finished.load(Ordering::SeqCst);
+        Stamp {
+            started,
+            settled: started == finished,
+        }
+    }
+
+    pub(crate) fn validate(&self, stamp: Stamp) -> bool {
+        stamp.settled && self.started.load(Ordering::SeqCst) == stamp.started
+    }
+}
+
+/// A write to a map that is in progress, see [`Stamps::write`].
+pub(crate) struct StampedWrite<'a> {
+    stamps: &'a Stamps,
+}
+
+impl Drop for StampedWrite<'_> {
+    #[inline]
+    fn drop(&mut self) {
+        if self.stamps.enabled {
+            self.stamps.finished.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
@@ -0,0 +1,313 @@
+//! Loading maps from asynchronous sources, and reading them from asynchronous code.
+//!
+//! [`HashMap::populate_from`] consumes a [`Stream`] of key-value pairs and inserts them into the
+//! map. Items are inserted in batches: every time the stream is polled, all the items it has
+//! ready are collected, room is reserved for them in one go, and they are inserted under a single
+//! guard. The guard is never held while the stream is pending, so waiting on a slow source does not
+//! hold up the reclamation of garbage.
+//!
+//! In the other direction, [`HashMap::stream`] returns a [`Stream`] of clones of the map's
+//! entries. It reads them in batches too, each under a guard that is dropped again before the
+//! batch is handed out, so the stream can be held across `.await` points, and consumers that are
+//! slow to poll it do not hold up the reclamation of garbage either.
+//!
+//! flurry does not depend on any async runtime, and by default not on the `futures` crate either.
+//! Instead, this module defines a minimal [`Stream`] trait with the same shape as
+//! `futures::Stream`, which other stream types can be adapted to with a few lines of code. With
+//! the `futures` feature, the stream returned by [`HashMap::stream`] also implements
+//! `futures_core::Stream`, so that it works with the combinators of `futures` and of async
+//! runtimes directly.
+//!
+//! ```
+//! use flurry::stream::Stream;
+//! use flurry::HashMap;
+//! use std::future::Future;
+//! use std::pin::Pin;
+//! use std::task::{Context, Poll};
+//!
+//! // a stream that has all of its items ready immediately
+//! struct Ready<I>(I);
+//!
+//! impl<I: Iterator + Unpin> Stream for Ready<I> {
+//!     type Item = I::Item;
+//!
+//!     fn poll_next(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<I::Item>> {
+//!         Poll::Ready(self.0.next())
+//!     }
+//! }
+//!
+//! # fn block_on<F: Future + Unpin>(mut future: F) -> F::Output {
+//! #     use std::task::{RawWaker, RawWakerVTable, Waker};
+//! #     fn raw() -> RawWaker {
+//! #         RawWaker::new(std::ptr::null(), &VTABLE)
+//! #     }
+//! #     static VTABLE: RawWakerVTable = RawWakerVTable::new(|_| raw(), |_| {}, |_| {}, |_| {});
+//! #     let waker = unsafe { Waker::from_raw(raw()) };
+//! #     loop {
+//! #         if let Poll::Ready(output) = Pin::new(&mut future).poll(&mut Context::from_waker(&waker)) {
+//! #             return output;
+//! #         }
+//! #     }
+//! # }
+//! let map = HashMap::new();
+//! let inserted = block_on(map.populate_from(Ready((0..100).map(|i| (i, i * 2)))));
+//! assert_eq!(inserted, 100);
+//! assert_eq!(map.pin().get(&42), Some(&84));
+//! ```
+//!
+//! [`HashMap::populate_from`]: crate::HashMap::populate_from
+//! [`HashMap::stream`]: crate::HashMap::stream
+
+use crate::cursor::{ResumeToken, START};
+use crate::{HashMap, TreeKey};
+use std::fmt::{self, Debug, Formatter};
+use std::future::Future;
+use std::hash::{BuildHasher, Hash};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// The most items inserted or read under a single guard.
+///
+/// This bounds both the memory used to buffer ready items and how long a single poll can hold up
+/// the reclamation of garbage.
+const MAX_BATCH: usize = 1024;
+
+/// An asynchronous sequence of values.
+///
+/// This has the same methods as `futures::Stream`.
+pub trait Stream {
+    /// The values produced by the stream.
+    type Item;
+
+    /// Attempts to produce the next value, returning `Poll::Ready(None)` once the stream is
+    /// exhausted.
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>>;
+
+    /// Returns bounds on the number of values the stream has yet to produce.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, None)
+    }
+}
+
+impl<St> Stream for &mut St
+where
+    St: Stream + Unpin + ?Sized,
+{
+    type Item = St::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut **self).poll_next(cx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (**self).size_hint()
+    }
+}
+
+impl<St> Stream for Box<St>
+where
+    St: Stream + Unpin + ?Sized,
+{
+    type Item = St::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut **self).poll_next(cx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (**self).size_hint()
+    }
+}
+
+/// The future returned by [`HashMap::populate_from`].
+///
+/// Resolves to the number of items taken from the stream once it is exhausted.
+#[must_use = "futures do nothing unless polled"]
+pub struct PopulateFrom<'map, K, V, S, St> {
+    map: &'map HashMap<K, V, S>,
+    stream: St,
+    batch: Vec<(K, V)>,
+    inserted: usize,
+    reserved: bool,
+}
+
+impl<K, V, S, St> Debug for PopulateFrom<'_, K, V, S, St> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PopulateFrom")
+            .field("inserted", &self.inserted)
+            .finish()
+    }
+}
+
+// the future never pins any of its fields, not even the stream (which is required to be `Unpin`)
+impl<K, V, S, St: Unpin> Unpin for PopulateFrom<'_, K, V, S, St> {}
+
+impl<K, V, S, St> Future for PopulateFrom<'_, K, V, S, St>
+where
+    K: 'static + Sync + Send + Clone + Hash + TreeKey,
+    V: 'static + Sync + Send,
+    S: BuildHasher,
+    St: Stream<Item = (K, V)> + Unpin,
+{
+    type Output = usize;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<usize> {
+        let this = &mut *self;
+        if !this.reserved {
+            this.reserved = true;
+            let (lower, _) = this.stream.size_hint();
+            this.map.reserve(lower, &this.map.guard());
+        }
+
+        loop {
+            let mut done = false;
+            let mut pending = false;
+            while this.batch.len() < MAX_BATCH {
+                match Pin::new(&mut this.stream).poll_next(cx) {
+                    Poll::Ready(Some(item)) => this.batch.push(item),
+                    Poll::Ready(None) => {
+                        done = true;
+                        break;
+                    }
+                    Poll::Pending => {
+                        pending = true;
+                        break;
+                    }
+                }
+            }
+
+            if !this.batch.is_empty() {
+                let guard = this.map.guard();
+                this.map.reserve(this.batch.len(), &guard);
+                this.inserted += this.batch.len();
+                for (key, value) in this.batch.drain(..) {
+                    this.map.insert(key, value, &guard);
+                }
+            }
+
+            if done {
+                return Poll::Ready(this.inserted);
+            }
+            if pending {
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+impl<K, V, S> HashMap<K, V, S>
+where
+    K: 'static + Sync + Send + Clone + Hash + TreeKey,
+    V: 'static + Sync + Send,
+    S: BuildHasher,
+{
+    /// Inserts all key-value pairs produced by `stream` into the map.
+    ///
+    /// The returned future resolves to the number of pairs inserted once the stream is exhausted.
+    /// Pairs for keys that are already in the map replace the existing values. See the
+    /// [`stream`](crate::stream) module for details.
+    pub fn populate_from<St>(&self, stream: St) -> PopulateFrom<'_, K, V, S, St>
+    where
+        St: Stream<Item = (K, V)> + Unpin,
+    {
+        PopulateFrom {
+            map: self,
+            stream,
+            batch: Vec::new(),
+            inserted: 0,
+            reserved: false,
+        }
+    }
+}
+
+/// The stream returned by [`HashMap::stream`].
+///
+/// Yields clones of the map's entries.
+#[must_use = "streams do nothing unless polled"]
+pub struct Entries<'map, K, V, S> {
+    map: &'map HashMap<K, V, S>,
+    batch: std::vec::IntoIter<(K, V)>,
+    token: ResumeToken,
+}
+
+impl<K, V, S> Debug for Entries<'_, K, V, S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Entries")
+            .field("buffered", &self.batch.len())
+            .field("token", &self.token)
+            .finish()
+    }
+}
+
+// the stream never pins any of its fields
+impl<K, V, S> Unpin for Entries<'_, K, V, S> {}
+
+impl<K, V, S> Stream for Entries<'_, K, V, S>
+where
+    K: Clone,
+    V: Clone,
+{
+    type Item = (K, V);
+
+    fn poll_next(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<(K, V)>> {
+        let this = &mut *self;
+        if this.batch.len() == 0 && !this.token.is_finished() {
+            let guard = this.map.guard();
+            let mut cursor = this.map.cursor_from(this.token, &guard);
+            let batch: Vec<_> = cursor
+                .by_ref()
+                .take(MAX_BATCH)
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect();
+            this.token = cursor.into_token();
+            this.batch = batch.into_iter();
+        }
+        Poll::Ready(this.batch.next())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.batch.len(), None)
+    }
+}
+
+#[cfg(feature = "futures")]
+impl<K, V, S> futures_core::Stream for Entries<'_, K, V, S>
+where
+    K: Clone,
+    V: Clone,
+{
+    type Item = (K, V);
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<(K, V)>> {
+        Stream::poll_next(self, cx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        Stream::size_hint(self)
+    }
+}
+
+impl<K, V, S> HashMap<K, V, S>
+where
+    K: Clone,
+    V: Clone,
+{
+    /// Returns a stream of clones of the map's entries.
+    ///
+    /// The entries are read in batches, each under a guard of its own that is dropped before any
+    /// of them are yielded, so the stream never holds a guard between polls. Like with a
+    /// [`Cursor`](crate::cursor::Cursor), every entry that is in the map for as long as the stream
+    /// is being polled is yielded, but an entry may be yielded more than once if the table resizes
+    /// in the meantime. Entries that are inserted or removed while the stream is being polled may
+    /// or may not be yielded. See the [`stream`](crate::stream) module for details.
+    ///
+    /// The stream implements this crate's [`Stream`] trait, and with the `futures` feature also
+    /// `futures_core::Stream`.
+    pub fn stream(&self) -> Entries<'_, K, V, S> {
+        Entries {
+            map: self,
+            batch: Vec::new().into_iter(),
+            token: START,
+        }
+    }
+}
@@ -0,0 +1,436 @@
+//! Maps and sets that never hand out guards or references.
+//!
+//! [`SyncHashMap`] and [`SyncHashSet`] wrap a [`HashMap`] and a [`HashSet`], and pin the epoch
+//! inside each method call. Nothing they return borrows from the collection: lookups return
+//! clones of the keys and values, and no signature mentions a guard or a lifetime. That makes
+//! them easy to use from code that does not want to reason about epochs, and to put behind a
+//! foreign function interface.
+//!
+//! Iterating over a [`SyncHashMap`] yields owned pairs as well. The iterator pins the epoch for as
+//! long as it is alive, and clones each entry as it goes.
+//!
+//! The price is a clone for every value that is read, and a guard for every call. Code that reads
+//! large values, or performs many operations in a row, is better off with the underlying
+//! collection, which [`SyncHashMap::as_flurry`] and [`SyncHashSet::as_flurry`] give access to.
+//!
+//! ```
+//! use flurry::sync::SyncHashMap;
+//!
+//! let map = SyncHashMap::new();
+//! assert_eq!(map.insert("apples", 3), None);
+//! assert_eq!(map.insert("apples", 4), Some(3));
+//! assert_eq!(map.get("apples"), Some(4));
+//! assert_eq!(map.compute_if_present("apples", |_, n| Some(n + 1)), Some(5));
+//! assert_eq!(map.remove("apples"), Some(5));
+//! assert!(map.is_empty());
+//!
+//! map.insert("pears", 2);
+//! for (fruit, n) in &map {
+//!     assert_eq!((fruit, n), ("pears", 2));
+//! }
+//! ```
+
+use crate::iter::{IntoIter, OwnedIter};
+use crate::{DefaultHashBuilder, HashMap, HashSet, TreeKey};
+use std::borrow::Borrow;
+use std::fmt::{self, Debug, Formatter};
+use std::hash::{BuildHasher, Hash};
+use std::iter::FromIterator;
+
+/// A concurrent map whose methods manage guards internally and return owned data.
+///
+/// See the [module-level documentation](self) for details.
+pub struct SyncHashMap<K, V, S = DefaultHashBuilder> {
+    map: HashMap<K, V, S>,
+}
+
+impl<K, V, S> Debug for SyncHashMap<K, V, S>
+where
+    K: Debug,
+    V: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.map.fmt(f)
+    }
+}
+
+impl<K, V> SyncHashMap<K, V, DefaultHashBuilder> {
+    /// Creates an empty map.
+    pub fn new() -> Self {
+        Self::from(HashMap::new())
+    }
+
+    /// Creates an empty map that can hold at least `capacity` elements without resizing.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::from(HashMap::with_capacity(capacity))
+    }
+}
+
+impl<K, V, S> Default for SyncHashMap<K, V, S>
+where
+    S: Default,
+{
+    fn default() -> Self {
+        Self::from(HashMap::default())
+    }
+}
+
+impl<K, V, S> From<HashMap<K, V, S>> for SyncHashMap<K, V, S> {
+    fn from(map: HashMap<K, V, S>) -> Self {
+        SyncHashMap { map }
+    }
+}
+
+impl<K, V, S> SyncHashMap<K, V, S> {
+    /// Creates an empty map that uses `hasher` to hash keys.
+    pub fn with_hasher(hasher: S) -> Self {
+        Self::from(HashMap::with_hasher(hasher))
+    }
+
+    /// Creates an empty map that uses `hasher` to hash keys, and can hold at least `capacity`
+    /// elements without resizing.
+    pub fn with_capacity_and_hasher(capacity: usize, hasher: S) -> Self {
+        Self::from(HashMap::with_capacity_and_hasher(capacity, hasher))
+    }
+
+    /// Returns the underlying [`HashMap`].
+    pub fn as_flurry(&self) -> &HashMap<K, V, S> {
+        &self.map
+    }
+
+    /// Consumes the wrapper and returns the underlying [`HashMap`].
+    pub fn into_flurry(self) -> HashMap<K, V, S> {
+        self.map
+    }
+
+    /// Returns the number of entries in the map.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns `true` if the map contains no entries.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Returns clones of all the keys in the map, in arbitrary order.
+    pub fn keys(&self) -> Vec<K>
+    where
+        K: Clone,
+    {
+        self.map.keys(&self.map.guard()).cloned().collect()
+    }
+
+    /// Returns clones of all the values in the map, in arbitrary order.
+    pub fn values(&self) -> Vec<V>
+    where
+        V: Clone,
+    {
+        self.map.values(&self.map.guard()).cloned().collect()
+    }
+
+    /// An iterator visiting clones of all the entries in the map, in arbitrary order.
+    ///
+    /// The iterator pins the epoch for as long as it is alive. See [`HashMap::owned_iter`].
+    pub fn iter(&self) -> OwnedIter<'_, K, V>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        self.map.owned_iter()
+    }
+
+    /// Returns clones of all the entries in the map, in arbitrary order.
+    pub fn to_vec(&self) -> Vec<(K, V)>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let guard = self.map.guard();
+        self.map
+            .iter(&guard)
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect()
+    }
+}
+
+impl<K, V, S> SyncHashMap<K, V, S>
+where
+    K: Hash + TreeKey,
+    S: BuildHasher,
+{
+    /// Returns a clone of the value for `key`, if there is one.
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
+        V: Clone,
+    {
+        self.map.get(key, &self.map.guard()).cloned()
+    }
+
+    /// Returns `true` if the map contains an entry for `key`.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
+    {
+        self.map.contains_key(key, &self.map.guard())
+    }
+}
+
+impl<K, V, S> SyncHashMap<K, V, S>
+where
+    K: Clone + TreeKey,
+{
+    /// Removes all entries from the map.
+    pub fn clear(&self) {
+        self.map.clear(&self.map.guard());
+    }
+}
+
+impl<K, V, S> SyncHashMap<K, V, S>
+where
+    K: 'static + Sync + Send + Clone + Hash + TreeKey,
+    V: 'static + Sync + Send + Clone,
+    S: BuildHasher,
+{
+    /// Inserts `value` for `key`, and returns (a clone of) the value it replaced, if any.
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        self.map.insert(key, value, &self.map.guard()).cloned()
+    }
+
+    /// Inserts `value` for `key` unless the map already has an entry for it.
+    ///
+    /// Returns `Ok(())` if the value was inserted, and otherwise hands `value` back together
+    /// with a clone of the current value.
+    pub fn try_insert(&self, key: K, value: V) -> Result<(), (V, V)> {
+        match self.map.try_insert(key, value, &self.map.guard()) {
+            Ok(_) => Ok(()),
+            Err(e) => Err((e.current.clone(), e.not_inserted)),
+        }
+    }
+
+    /// Removes the entry for `key`, and returns (a clone of) its value, if there was one.
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
+    {
+        self.map.remove(key, &self.map.guard()).cloned()
+    }
+
+    /// Replaces the value for `key` with the result of `f`, or removes the entry if `f` returns
+    /// `None`, and returns (a clone of) the new value.
+    ///
+    /// Does nothing if there is no entry for `key`. See [`HashMap::compute_if_present`].
+    pub fn compute_if_present<Q, F>(&self, key: &Q, f: F) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
+        F: FnOnce(&K, &V) -> Option<V>,
+    {
+        self.map
+            .compute_if_present(key, f, &self.map.guard())
+            .cloned()
+    }
+
+    /// Removes every entry for which `f` returns `false`, and returns how many were removed.
+    pub fn retain<F>(&self, f: F) -> usize
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        self.map.retain(f, &self.map.guard())
+    }
+}
+
+impl<K, V, S> Clone for SyncHashMap<K, V, S>
+where
+    K: 'static + Sync + Send + Clone + Hash + TreeKey,
+    V: 'static + Sync + Send + Clone,
+    S: BuildHasher + Clone,
+{
+    fn clone(&self) -> Self {
+        Self::from(self.map.clone())
+    }
+}
+
+impl<'m, K, V, S> IntoIterator for &'m SyncHashMap<K, V, S>
+where
+    K: Clone,
+    V: Clone,
+{
+    type Item = (K, V);
+    type IntoIter = OwnedIter<'m, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K, V, S> IntoIterator for SyncHashMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.map.into_iter()
+    }
+}
+
+impl<K, V, S> FromIterator<(K, V)> for SyncHashMap<K, V, S>
+where
+    K: 'static + Sync + Send + Clone + Hash + TreeKey,
+    V: 'static + Sync + Send,
+    S: BuildHasher + Default,
+{
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        Self::from(HashMap::from_iter(iter))
+    }
+}
+
+/// A concurrent set whose methods manage guards internally and return owned data.
+///
+/// See the [module-level documentation](self) for details.
+pub struct SyncHashSet<T, S = DefaultHashBuilder> {
+    set: HashSet<T, S>,
+}
+
+impl<T, S> Debug for SyncHashSet<T, S>
+where
+    T: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.set.fmt(f)
+    }
+}
+
+impl<T> SyncHashSet<T, DefaultHashBuilder> {
+    /// Creates an empty set.
+    pub fn new() -> Self {
+        Self::from(HashSet::new())
+    }
+
+    /// Creates an empty set that can hold at least `capacity` elements without resizing.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::from(HashSet::with_capacity(capacity))
+    }
+}
+
+impl<T, S> Default for SyncHashSet<T, S>
+where
+    S: Default,
+{
+    fn default() -> Self {
+        Self::from(HashSet::default())
+    }
+}
+
+impl<T, S> From<HashSet<T, S>> for SyncHashSet<T, S> {
+    fn from(set: HashSet<T, S>) -> Self {
+        SyncHashSet { set }
+    }
+}
+
+impl<T, S> SyncHashSet<T, S> {
+    /// Creates an empty set that uses `hasher` to hash values.
+    pub fn with_hasher(hasher: S) -> Self {
+        Self::from(HashSet::with_hasher(hasher))
+    }
+
+    /// Creates an empty set that uses `hasher` to hash values, and can hold at least `capacity`
+    /// elements without resizing.
+    pub fn with_capacity_and_hasher(capacity: usize, hasher: S) -> Self {
+        Self::from(HashSet::with_capacity_and_hasher(capacity, hasher))
+    }
+
+    /// Returns the underlying [`HashSet`].
+    pub fn as_flurry(&self) -> &HashSet<T, S> {
+        &self.set
+    }
+
+    /// Consumes the wrapper and returns the underlying [`HashSet`].
+    pub fn into_flurry(self) -> HashSet<T, S> {
+        self.set
+    }
+
+    /// Returns the number of elements in the set.
+    pub fn len(&self) -> usize {
+        self.set.len()
+    }
+
+    /// Returns `true` if the set contains no elements.
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty()
+    }
+
+    /// Returns clones of all the elements in the set, in arbitrary order.
+    pub fn to_vec(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        self.set.iter(&self.set.guard()).cloned().collect()
+    }
+}
+
+impl<T, S> SyncHashSet<T, S>
+where
+    T: Hash + TreeKey,
+    S: BuildHasher,
+{
+    /// Returns `true` if `value` is an element of the set.
+    pub fn contains<Q>(&self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
+    {
+        self.set.contains(value, &self.set.guard())
+    }
+}
+
+impl<T, S> SyncHashSet<T, S>
+where
+    T: Clone + TreeKey,
+{
+    /// Removes all elements from the set.
+    pub fn clear(&self) {
+        self.set.clear(&self.set.guard());
+    }
+}
+
+impl<T, S> SyncHashSet<T, S>
+where
+    T: 'static + Sync + Send + Clone + Hash + TreeKey,
+    S: BuildHasher,
+{
+    /// Adds `value` to the set, and returns `true` if it was not in the set already.
+    pub fn insert(&self, value: T) -> bool {
+        self.set.insert(value, &self.set.guard())
+    }
+
+    /// Removes `value` from the set, and returns `true` if it was in the set.
+    pub fn remove<Q>(&self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: ?Sized + Hash + TreeKey,
+    {
+        self.set.remove(value, &self.set.guard())
+    }
+
+    /// Removes every element for which `f` returns `false`, and returns how many were removed.
+    pub fn retain<F>(&self, f: F) -> usize
+    where
+        F: FnMut(&T) -> bool,
+    {
+        self.set.retain(f, &self.set.guard())
+    }
+}
+
+impl<T, S> Clone for SyncHashSet<T, S>
+where
+    T: 'static + Sync + Send + Clone + Hash + TreeKey,
+    S: BuildHasher + Clone,
+{
+    fn clone(&self) -> Self {
+        Self::from(self.set.clone())
+    }
+}
@@ -0,0 +1,200 @@
+//! Tracing of memory reclamation.
+//!
+//! The map never frees memory directly. Removed entries, replaced values, and old tables are
+//! _retired_ instead, and only destroyed once the epoch-based garbage collector has determined
+//! that no thread can still be accessing them. If some thread stays pinned for a long time, that
+//! can take arbitrarily long, and memory use grows even though the map itself does not. This
+//! module lets you install a hook that is told about the progress of reclamation, so that such
+//! growth can be told apart from genuine growth of the map.
+//!
+//! Every [`Event`] is tagged with the [`CollectorId`] of the collector it happened in, since
+//! garbage of all the maps (and other data structures) that share a collector is reclaimed
+//! together. Maps use the global collector with id [`CollectorId::DEFAULT`] unless they are
+//! created [`with_collector`](crate::HashMap::with_collector) or in a [`scope`](crate::scope),
+//! whose collectors each have an id of their own (see [`Collector::trace_id`](crate::epoch::Collector::trace_id)). Events are emitted whenever a map explicitly hands garbage to the collector, which
+//! it does at the end of some operations (such as inserting a new key) but not others.
+//!
+//! The hook is process-global and installed with [`set_hook`]. It is only available with the
+//! `reclaim-trace` feature; without it, no reclamation is tracked.
+//!
+//! ```
+//! use flurry::trace::{self, Event};
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//!
+//! static DESTROYED: AtomicUsize = AtomicUsize::new(0);
+//!
+//! fn hook(event: Event) {
+//!     if let Event::Destroyed { count, .. } = event {
+//!         DESTROYED.fetch_add(count, Ordering::Relaxed);
+//!     }
+//! }
+//!
+//! trace::set_hook(Some(hook));
+//! let map = flurry::HashMap::new();
+//! map.pin().insert(0, 0);
+//! for _ in 0..1024 {
+//!     let guard = map.guard();
+//!     map.compute_if_present(&0, |_, v| Some(v + 1), &guard);
+//! }
+//! trace::set_hook(None);
+//! // a bunch of the replaced values have been destroyed by now
+//! assert!(DESTROYED.load(Ordering::Relaxed) > 0);
+//! ```
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Identifies a garbage collector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CollectorId(usize);
+
+impl CollectorId {
+    /// The process-wide default collector, which maps use unless told otherwise.
+    pub const DEFAULT: CollectorId = CollectorId(0);
+
+    /// Returns an id that no other collector has.
+    pub(crate) fn next() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(1);
+        CollectorId(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// Something that happened during reclamation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Event {
+    /// A thread handed the garbage it retired to the collector.
+    Flushed {
+        /// The collector the garbage was handed to.
+        collector: CollectorId,
+        /// The number of objects that have been retired in this collector but not yet destroyed.
+        pending: usize,
+    },
+    /// Retired objects were destroyed.
+    ///
+    /// Destruction happens in batches, whenever a thread pins, unpins, or hands garbage to the
+    /// collector. This event reports all objects destroyed since the previous such event, and is
+    /// emitted when garbage is next handed to the collector.
+    Destroyed {
+        /// The collector the objects were retired in.
+        collector: CollectorId,
+        /// The number of objects that were destroyed.
+        count: usize,
+        /// The number of objects that remain to be destroyed.
+        pending: usize,
+    },
+    /// The collector's epoch has advanced.
+    ///
+    /// This is emitted once the epoch has advanced far enough that everything retired at the
+    /// time of the previous such event can be destroyed. If this event stops appearing while
+    /// `pending` keeps growing, some thread is holding up reclamation by staying pinned.
+    EpochAdvanced {
+        /// The collector whose epoch advanced.
+        collector: CollectorId,
+    },
+}
+
+// stores the installed `fn(Event)` as a `usize`, with 0 meaning "no hook"
+static HOOK: AtomicUsize = AtomicUsize::new(0);
+
+/// Installs `hook` to be called for every reclamation [`Event`], replacing any previous hook.
+///
+/// Passing `None` removes the current hook. The hook may be called from within the collector
+/// while garbage is being destroyed, and so must not use any map that shares that collector.
+pub fn set_hook(hook: Option<fn(Event)>) {
+    HOOK.store(hook.map_or(0, |hook| hook as usize), Ordering::SeqCst);
+}
+
+fn emit(event: Event) {
+    let hook = HOOK.load(Ordering::Relaxed);
+    if hook != 0 {
+        // safety: the only non-zero values ever stored in `HOOK` are `fn(Event)` pointers cast to
+        // `usize` by `set_hook`.
+        let hook: fn(Event) = unsafe { std::mem::transmute(hook) };
+        hook(event);
+    }
+}
+
+/// Reclamation bookkeeping for a single collector.
+pub(crate) struct Tracker {
+    id: CollectorId,
+    retired: AtomicUsize,
+    destroyed: AtomicUsize,
+    /// The value of `destroyed` at the time of the last `Destroyed` event.
+    reported: AtomicUsize,
+    /// Whether we are waiting for a probe to be destroyed to learn that the epoch advanced.
+    probing: AtomicBool,
+}
+
+impl Tracker {
+    pub(crate) const fn new(id: CollectorId) -> Self {
+        Tracker {
+            id,
+            retired: AtomicUsize::new(0),
+            destroyed: AtomicUsize::new(0),
+            reported: AtomicUsize::new(0),
+            probing: AtomicBool::new(false),
+        }
+    }
+
+    pub(crate) fn retired(&self) {
+        self.retired.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn destroyed(&self) {
+        self.destroyed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn pending(&self, destroyed: usize) -> usize {
+        // the counters are updated independently, so we may see a destruction before the
+        // corresponding retirement.
+        self.retired
+            .load(Ordering::Relaxed)
+            .saturating_sub(destroyed)
+    }
+
+    pub(crate) fn id(&self) -> CollectorId {
+        self.id
+    }
+
+    /// Returns whether the caller should retire a probe that calls [`Tracker::advanced`] once it
+    /// is destroyed, which happens once the collector's epoch has advanced past the current one.
+    pub(crate) fn probe(&self) -> bool {
+        !self.probing.swap(true, Ordering::Relaxed)
+    }
+
+    pub(crate) fn advanced(&self) {
+        self.probing.store(false, Ordering::Relaxed);
+        emit(Event::EpochAdvanced { collector: self.id });
+    }
+
+    /// Reports that a thread handed its garbage to the collector.
+    pub(crate) fn flushed(&self) {
+        let destroyed = self.destroyed.load(Ordering::Relaxed);
+        let pending = self.pending(destroyed);
+
+        emit(Event::Flushed {
+            collector: self.id,
+            pending,
+        });
+
+        // report every destruction exactly once, even if multiple threads flush concurrently
+        let mut reported = self.reported.load(Ordering::Relaxed);
+        while destroyed > reported {
+            match self.reported.compare_exchange(
+                reported,
+                destroyed,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => {
+                    emit(Event::Destroyed {
+                        collector: self.id,
+                        count: destroyed - reported,
+                        pending,
+                    });
+                    break;
+                }
+                Err(r) => reported = r,
+            }
+        }
+    }
+}
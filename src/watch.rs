@@ -0,0 +1,213 @@
+//! Waiting for changes to individual keys.
+//!
+//! [`HashMap::watch`] returns a [`Watch`] that resolves the next time its key is inserted into the
+//! map, has its value replaced, or is removed from the map. A `Watch` can be waited on by blocking
+//! the current thread with [`Watch::wait`], or awaited from any async runtime, since it implements
+//! [`Future`]. Other ways of being notified can be plugged in through the [`Wake`] trait.
+//!
+//! ```
+//! use flurry::HashMap;
+//! use std::sync::Arc;
+//! use std::thread;
+//!
+//! let map = Arc::new(HashMap::new());
+//! let watch = map.watch("ready");
+//!
+//! let writer = {
+//!     let map = Arc::clone(&map);
+//!     thread::spawn(move || {
+//!         map.pin().insert("ready", true);
+//!     })
+//! };
+//!
+//! // no need to poll `get` in a loop
+//! watch.wait();
+//! assert_eq!(map.pin().get("ready"), Some(&true));
+//! # writer.join().unwrap();
+//! ```
+//!
+//! A `Watch` only learns _that_ its key changed, not what it changed to, and fires at most once.
+//! Changes made before the `Watch` was created are not reported.
+//!
+//! [`HashMap::watch`]: crate::HashMap::watch
+
+use crate::primitives::{park, park_timeout};
+use parking_lot::Mutex;
+use std::fmt::{self, Debug, Formatter};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
+use std::task::{Context, Poll};
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+
+/// Something that can be woken up when a [`Watch`] fires.
+///
+/// This is implemented for [`Waker`](std::task::Waker), which is how a `Watch` integrates with
+/// async runtimes, and for [`Thread`], which is how [`Watch::wait`] blocks.
+pub trait Wake: Send + Sync + 'static {
+    /// Signals that the watched key has changed.
+    ///
+    /// This is called while the lock of the bin that contains the key is held, and so should
+    /// return quickly.
+    fn wake(&self);
+}
+
+impl Wake for std::task::Waker {
+    fn wake(&self) {
+        self.wake_by_ref();
+    }
+}
+
+impl Wake for Thread {
+    fn wake(&self) {
+        self.unpark();
+    }
+}
+
+/// The state shared between a [`Watch`] and the map it watches.
+struct Signal {
+    fired: AtomicBool,
+    waker: Mutex<Option<Box<dyn Wake>>>,
+}
+
+impl Signal {
+    fn fire(&self) {
+        self.fired.store(true, Ordering::SeqCst);
+        if let Some(waker) = self.waker.lock().take() {
+            waker.wake();
+        }
+    }
+}
+
+/// A pending notification of a change to a single key of a [`HashMap`](crate::HashMap).
+///
+/// See the [module-level documentation](self) for details.
+#[must_use = "a `Watch` does nothing unless it is waited on"]
+pub struct Watch {
+    signal: Arc<Signal>,
+}
+
+impl Watch {
+    /// Returns `true` if the key has changed since this `Watch` was created.
+    pub fn is_changed(&self) -> bool {
+        self.signal.fired.load(Ordering::SeqCst)
+    }
+
+    /// Arranges for `waker` to be woken up once the key changes.
+    ///
+    /// If the key has already changed, `waker` is woken up immediately. Only the most recently
+    /// registered waker is woken up.
+    pub fn on_change<W: Wake>(&self, waker: W) {
+        *self.signal.waker.lock() = Some(Box::new(waker));
+        // the key may have changed before the waker was in place
+        if self.is_changed() {
+            if let Some(waker) = self.signal.waker.lock().take() {
+                waker.wake();
+            }
+        }
+    }
+
+    /// Blocks the current thread until the key changes.
+    pub fn wait(self) {
+        self.on_change(thread::current());
+        while !self.is_changed() {
+            park();
+        }
+    }
+
+    /// Blocks the current thread until the key changes, or until `timeout` has passed.
+    ///
+    /// Returns `true` if the key changed.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        self.on_change(thread::current());
+        while !self.is_changed() {
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            park_timeout(deadline - now);
+        }
+        true
+    }
+}
+
+impl Future for Watch {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.is_changed() {
+            return Poll::Ready(());
+        }
+        self.on_change(cx.waker().clone());
+        if self.is_changed() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl Debug for Watch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watch")
+            .field("changed", &self.is_changed())
+            .finish()
+    }
+}
+
+/// The watches registered with a single map.
+///
+/// Each change to the map is checked against every registered watch, so this is meant for
+/// a moderate number of outstanding watches. Watches that are dropped before they fire are
+/// cleaned up the next time the map changes.
+pub(crate) struct Watchers<K> {
+    // the number of registered watches, so that maps without any do not have to take the lock
+    live: AtomicUsize,
+    watches: Mutex<Vec<(K, Weak<Signal>)>>,
+}
+
+impl<K> Watchers<K> {
+    pub(crate) fn new() -> Self {
+        Watchers {
+            live: AtomicUsize::new(0),
+            watches: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Returns whether any watches need to be notified of changes.
+    #[inline]
+    pub(crate) fn is_active(&self) -> bool {
+        self.live.load(Ordering::SeqCst) != 0
+    }
+
+    pub(crate) fn watch(&self, key: K) -> Watch {
+        let signal = Arc::new(Signal {
+            fired: AtomicBool::new(false),
+            waker: Mutex::new(None),
+        });
+        let mut watches = self.watches.lock();
+        watches.retain(|(_, signal)| signal.strong_count() != 0);
+        watches.push((key, Arc::downgrade(&signal)));
+        self.live.store(watches.len(), Ordering::SeqCst);
+        Watch { signal }
+    }
+}
+
+impl<K: Eq> Watchers<K> {
+    /// Fires all watches for `changed`.
+    pub(crate) fn notify(&self, changed: &K) {
+        let mut watches = self.watches.lock();
+        watches.retain(|(key, signal)| match signal.upgrade() {
+            Some(signal) if key == changed => {
+                signal.fire();
+                false
+            }
+            Some(_) => true,
+            None => false,
+        });
+        self.live.store(watches.len(), Ordering::SeqCst);
+    }
+}
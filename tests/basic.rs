@@ -75,6 +75,120 @@ fn insert_and_remove() {
     }
 }
 
+#[test]
+fn remove_if() {
+    let map = HashMap::<usize, usize>::new();
+    let guard = map.guard();
+
+    assert_eq!(
+        map.remove_if(&42, |_| panic!("no entry to test"), &guard),
+        None
+    );
+    map.insert(42, 1, &guard);
+    assert_eq!(map.remove_if(&42, |&v| v == 0, &guard), None);
+    assert_eq!(map.get(&42, &guard), Some(&1));
+    assert_eq!(map.remove_if(&42, |&v| v == 1, &guard), Some(&1));
+    assert!(map.get(&42, &guard).is_none());
+}
+
+#[test]
+fn compare_exchange() {
+    let map = HashMap::<usize, String>::new();
+    let guard = map.guard();
+
+    let err = map
+        .compare_exchange(&42, &String::new(), String::from("a"), &guard)
+        .unwrap_err();
+    assert_eq!(err.current, None);
+    assert_eq!(err.new, "a");
+    assert!(map.is_empty());
+
+    map.insert(42, String::from("a"), &guard);
+    let err = map
+        .compare_exchange(&42, &String::from("b"), String::from("c"), &guard)
+        .unwrap_err();
+    assert_eq!(err.current, Some(&String::from("a")));
+    assert_eq!(err.new, "c");
+
+    // equal values are exchanged, as are the map's own values
+    let old = map
+        .compare_exchange(&42, &String::from("a"), String::from("b"), &guard)
+        .unwrap();
+    assert_eq!(old, "a");
+    let current = map.get(&42, &guard).unwrap();
+    assert_eq!(
+        map.compare_exchange(&42, current, String::from("c"), &guard),
+        Ok(current)
+    );
+    assert_eq!(map.get(&42, &guard).unwrap(), "c");
+}
+
+#[test]
+fn replace_entry() {
+    let map = HashMap::<usize, String>::new();
+    let guard = map.guard();
+
+    let err = map
+        .replace_entry(&42, &String::new(), String::from("a"), &guard)
+        .unwrap_err();
+    assert_eq!(err.current, None);
+    assert_eq!(err.new, "a");
+    assert!(map.is_empty());
+
+    map.insert(42, String::from("a"), &guard);
+    let err = map
+        .replace_entry(&42, &String::from("b"), String::from("c"), &guard)
+        .unwrap_err();
+    assert_eq!(err.current, Some(&String::from("a")));
+    assert_eq!(err.new, "c");
+
+    // the winner is the value that is now in the map
+    let winner = map
+        .replace_entry(&42, &String::from("a"), String::from("b"), &guard)
+        .unwrap();
+    assert_eq!(winner, "b");
+    assert!(std::ptr::eq(winner, map.get(&42, &guard).unwrap()));
+    assert_eq!(
+        map.replace_entry(&42, winner, String::from("c"), &guard)
+            .unwrap(),
+        "c"
+    );
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn concurrent_replace_entry() {
+    let map = Arc::new(HashMap::<usize, usize>::new());
+    map.insert(0, 0, &map.guard());
+
+    // every thread tries to advance the state, and each step is won by exactly one of them
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let map = Arc::clone(&map);
+            std::thread::spawn(move || {
+                let guard = map.guard();
+                let mut won = 0;
+                loop {
+                    let state = *map.get(&0, &guard).unwrap();
+                    if state == 1000 {
+                        return won;
+                    }
+                    match map.replace_entry(&0, &state, state + 1, &guard) {
+                        Ok(&winner) => {
+                            assert_eq!(winner, state + 1);
+                            won += 1;
+                        }
+                        Err(e) => assert!(*e.current.unwrap() > state),
+                    }
+                }
+            })
+        })
+        .collect();
+    let won: usize = threads.into_iter().map(|t| t.join().unwrap()).sum();
+    assert_eq!(won, 1000);
+    assert_eq!(map.get(&0, &map.guard()), Some(&1000));
+}
+
 #[test]
 fn insert_and_get() {
     let map = HashMap::<usize, usize>::new();
@@ -87,6 +201,395 @@ fn insert_and_get() {
     }
 }
 
+#[test]
+fn insert_and_get_returns_value() {
+    let map = HashMap::<usize, usize>::new();
+    let guard = map.guard();
+    assert_eq!(map.insert_and_get(42, 0, &guard), &0);
+    // the value is replaced, and the new one returned
+    let value = map.insert_and_get(42, 1, &guard);
+    assert_eq!(value, &1);
+    assert_eq!(map.get(&42, &guard), Some(value));
+    assert_eq!(map.len(), 1);
+}
+
+#[test]
+fn get_or_default() {
+    let map = HashMap::<usize, usize>::new();
+    let guard = map.guard();
+
+    assert_eq!(map.get_or_default(42, &guard), &0);
+    assert_eq!(map.len(), 1);
+    map.insert(42, 1, &guard);
+    assert_eq!(map.get_or_default(42, &guard), &1);
+    assert_eq!(map.len(), 1);
+}
+
+#[test]
+fn get_or_try_insert_with() {
+    let map = HashMap::<usize, usize>::new();
+    let guard = map.guard();
+
+    assert_eq!(
+        map.get_or_try_insert_with(42, || Err("io"), &guard),
+        Err("io")
+    );
+    assert!(map.is_empty());
+    assert_eq!(
+        map.get_or_try_insert_with(42, || Ok::<_, ()>(1), &guard),
+        Ok(&1)
+    );
+    // present keys do not call the closure
+    assert_eq!(
+        map.get_or_try_insert_with(42, || -> Result<_, ()> { panic!() }, &guard),
+        Ok(&1)
+    );
+    assert_eq!(map.len(), 1);
+}
+
+#[test]
+fn get_or_insert_with() {
+    let map = HashMap::<usize, usize>::new();
+    let guard = map.guard();
+
+    // a panicking closure leaves the bin empty again
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        map.get_or_insert_with(42, || panic!("boom"), &guard);
+    }));
+    assert!(result.is_err());
+    assert!(map.is_empty());
+
+    assert_eq!(map.get_or_insert_with(42, || 1, &guard), &1);
+    // present keys do not call the closure
+    assert_eq!(map.get_or_insert_with(42, || panic!(), &guard), &1);
+    for i in 100..164 {
+        assert_eq!(map.get_or_insert_with(i, || i * 2, &guard), &(i * 2));
+    }
+    assert_eq!(map.len(), 65);
+    assert_eq!(map.get(&42, &guard), Some(&1));
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn concurrent_get_or_insert_with() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // a small map, so that the threads also race with resizes
+    let map = Arc::new(HashMap::<usize, usize>::with_capacity(1));
+    let calls = Arc::new(AtomicUsize::new(0));
+    let threads: Vec<_> = (0..8)
+        .map(|_| {
+            let map = Arc::clone(&map);
+            let calls = Arc::clone(&calls);
+            std::thread::spawn(move || {
+                let guard = map.guard();
+                for i in 0..256 {
+                    let value = map.get_or_insert_with(
+                        i,
+                        || {
+                            calls.fetch_add(1, Ordering::SeqCst);
+                            i + 1
+                        },
+                        &guard,
+                    );
+                    assert_eq!(*value, i + 1);
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 256);
+    assert_eq!(map.len(), 256);
+}
+
+#[test]
+fn compute() {
+    let map = HashMap::<usize, usize>::new();
+    let guard = map.guard();
+
+    // declining to insert leaves the map alone
+    assert_eq!(map.compute(42, |_, v| v.copied(), &guard), None);
+    assert!(map.is_empty());
+
+    assert_eq!(
+        map.compute(42, |_, v| Some(v.map_or(1, |v| v + 1)), &guard),
+        Some(&1)
+    );
+    assert_eq!(
+        map.compute(42, |_, v| Some(v.map_or(1, |v| v + 1)), &guard),
+        Some(&2)
+    );
+    assert_eq!(map.compute(42, |_, _| None, &guard), None);
+    assert!(map.is_empty());
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn concurrent_compute() {
+    // a small map, so that the threads also race with resizes
+    let map = Arc::new(HashMap::<usize, usize>::with_capacity(1));
+    let threads: Vec<_> = (0..8)
+        .map(|_| {
+            let map = Arc::clone(&map);
+            std::thread::spawn(move || {
+                let guard = map.guard();
+                for i in 0..64 {
+                    for _ in 0..16 {
+                        map.compute(i, |_, v| Some(v.map_or(1, |v| v + 1)), &guard);
+                    }
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    let guard = map.guard();
+    assert_eq!(map.len(), 64);
+    for i in 0..64 {
+        assert_eq!(map.get(&i, &guard), Some(&128));
+    }
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn concurrent_merge() {
+    let map = Arc::new(HashMap::<usize, usize>::with_capacity(1));
+    let threads: Vec<_> = (0..8)
+        .map(|t| {
+            let map = Arc::clone(&map);
+            std::thread::spawn(move || {
+                let guard = map.guard();
+                for i in 0..256 {
+                    map.merge(i % 32, t, |old, new| old + new, &guard);
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    let guard = map.guard();
+    assert_eq!(map.len(), 32);
+    // each thread merged its index into every key 8 times
+    for i in 0..32 {
+        assert_eq!(map.get(&i, &guard), Some(&(8 * (0..8).sum::<usize>())));
+    }
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn concurrent_add() {
+    let map = Arc::new(HashMap::<usize, u64>::new());
+    let threads: Vec<_> = (0..8)
+        .map(|_| {
+            let map = Arc::clone(&map);
+            std::thread::spawn(move || {
+                let guard = map.guard();
+                for i in 0..1024 {
+                    map.add(i % 16, 1, &guard);
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    {
+        let guard = map.guard();
+        assert_eq!(map.len(), 16);
+        for i in 0..16 {
+            assert_eq!(map.get(&i, &guard), Some(&(8 * 64)));
+        }
+    }
+
+    // negative deltas work for signed values
+    let map = HashMap::<&str, i64>::new();
+    let guard = map.guard();
+    assert_eq!(map.add("balance", -5, &guard), &-5);
+    assert_eq!(map.pin().add("balance", 7), &2);
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn concurrent_fetch_update() {
+    let map = Arc::new(HashMap::<usize, usize>::new());
+    map.insert(0, 0, &map.guard());
+    let threads: Vec<_> = (0..8)
+        .map(|_| {
+            let map = Arc::clone(&map);
+            std::thread::spawn(move || {
+                let guard = map.guard();
+                let mut seen = Vec::new();
+                for _ in 0..128 {
+                    let previous = map.fetch_update(&0, |v| Some(v + 1), &guard).unwrap();
+                    seen.push(*previous);
+                }
+                seen
+            })
+        })
+        .collect();
+    let mut seen: Vec<_> = threads
+        .into_iter()
+        .flat_map(|thread| thread.join().unwrap())
+        .collect();
+    // every update saw a different previous value
+    seen.sort_unstable();
+    assert_eq!(seen, (0..1024).collect::<Vec<_>>());
+    assert_eq!(map.get(&0, &map.guard()), Some(&1024));
+}
+
+#[test]
+fn view() {
+    let map = HashMap::<usize, String>::new();
+    assert_eq!(map.view(&42, |v| v.len()), None);
+
+    map.insert(42, String::from("answer"), &map.guard());
+    assert_eq!(map.view(&42, |v| v.len()), Some(6));
+    assert_eq!(map.view(&42, String::clone), Some(String::from("answer")));
+}
+
+#[test]
+fn get_cloned() {
+    let map = HashMap::<usize, String>::new();
+    assert_eq!(map.get_cloned(&42), None);
+
+    map.insert(42, String::from("answer"), &map.guard());
+    let value = map.get_cloned(&42);
+    map.remove(&42, &map.guard());
+    assert_eq!(value, Some(String::from("answer")));
+    assert_eq!(map.get_cloned(&42), None);
+}
+
+#[test]
+fn bucket_index() {
+    let map = HashMap::<usize, usize>::new();
+    let guard = map.guard();
+    assert_eq!(map.bucket_count(&guard), 0);
+    assert_eq!(map.bucket_index(&0, &guard), None);
+
+    map.insert(0, 0, &guard);
+    let initial = map.bucket_count(&guard);
+    let before: Vec<_> = (0..100)
+        .map(|i| map.bucket_index(&i, &guard).unwrap())
+        .collect();
+    assert!(before.iter().all(|&i| i < initial));
+
+    for i in 0..1000 {
+        map.insert(i, i, &guard);
+    }
+    let grown = map.bucket_count(&guard);
+    assert!(grown > initial);
+    for (key, &index) in before.iter().enumerate() {
+        let now = map.bucket_index(&key, &guard).unwrap();
+        assert!(now < grown);
+        assert_eq!(now % initial, index);
+    }
+}
+
+#[test]
+fn bucket_index_after_shrink() {
+    let map = HashMap::<usize, usize>::new();
+    let guard = map.guard();
+    for i in 0..1000 {
+        map.insert(i, i, &guard);
+    }
+    let grown = map.bucket_count(&guard);
+    let before: Vec<_> = (0..10)
+        .map(|i| map.bucket_index(&i, &guard).unwrap())
+        .collect();
+
+    for i in 10..1000 {
+        map.remove(&i, &guard);
+    }
+    map.shrink_to_fit(&guard);
+    let shrunk = map.bucket_count(&guard);
+    assert!(shrunk < grown);
+    for (key, &index) in before.iter().enumerate() {
+        // the indices are only stable modulo the smaller number of buckets
+        let now = map.bucket_index(&key, &guard).unwrap();
+        assert!(now < shrunk);
+        assert_eq!(now, index % shrunk);
+    }
+    // keys that were in different buckets before may now share one
+    let mut indices: Vec<_> = (0..1000)
+        .map(|i| map.bucket_index(&i, &guard).unwrap())
+        .collect();
+    indices.sort_unstable();
+    indices.dedup();
+    assert_eq!(indices.len(), shrunk);
+}
+
+#[test]
+fn top_k_by() {
+    let map = HashMap::<usize, usize>::new();
+    let guard = map.guard();
+    assert!(map.top_k_by(3, |a, b| a.1.cmp(b.1), &guard).is_empty());
+
+    for i in 0..1000 {
+        map.insert(i, (i * 7919) % 1000, &guard);
+    }
+    let top = map.top_k_by(5, |a, b| a.1.cmp(b.1), &guard);
+    let values: Vec<_> = top.iter().map(|(_, &v)| v).collect();
+    assert_eq!(values, vec![999, 998, 997, 996, 995]);
+    for (&k, &v) in top {
+        assert_eq!((k * 7919) % 1000, v);
+    }
+
+    assert!(map.top_k_by(0, |a, b| a.1.cmp(b.1), &guard).is_empty());
+    assert_eq!(map.top_k_by(2000, |a, b| a.0.cmp(b.0), &guard).len(), 1000);
+}
+
+#[test]
+fn debug_limit() {
+    #[derive(Debug)]
+    struct Registry {
+        names: HashMap<usize, &'static str>,
+    }
+
+    let registry = Registry {
+        names: HashMap::new().with_debug_limit(2),
+    };
+    assert_eq!(format!("{:?}", registry), "Registry { names: {} }");
+
+    registry.names.pin().insert(1, "a");
+    registry.names.pin().insert(2, "b");
+    let debug = format!("{:?}", registry);
+    assert!(
+        debug == r#"Registry { names: {1: "a", 2: "b"} }"#
+            || debug == r#"Registry { names: {2: "b", 1: "a"} }"#
+    );
+
+    for i in 3..10 {
+        registry.names.pin().insert(i, "c");
+    }
+    let debug = format!("{:?}", registry.names);
+    assert!(debug.ends_with("} and 7 more"), "{}", debug);
+    assert_eq!(debug.matches(':').count(), 2);
+}
+
+#[test]
+fn eq_across_hashers() {
+    let map = HashMap::<usize, usize>::new();
+    let other = HashMap::with_hasher(std::collections::hash_map::RandomState::new());
+    assert_eq!(map, other);
+
+    for i in 0..16 {
+        map.pin().insert(i, i);
+        other.pin().insert(i, i);
+    }
+    assert_eq!(map, other);
+    assert_eq!(map.pin(), other.pin());
+    assert_eq!(map.pin(), other);
+    assert_eq!(map, other.pin());
+
+    other.pin().insert(0, 1);
+    assert_ne!(map, other);
+    assert_ne!(map.pin(), other.pin());
+}
+
 #[test]
 fn insert_and_get_key_value() {
     let map = HashMap::<usize, usize>::new();
@@ -479,6 +982,219 @@ fn clone_map_filled() {
     assert_ne!(&map, &cloned_map);
 }
 
+#[test]
+fn clone_map_large() {
+    let map: HashMap<u32, String> = (0..1000).map(|x| (x, x.to_string())).collect();
+    let cloned_map = map.clone();
+    assert_eq!(cloned_map.len(), 1000);
+    assert_eq!(&map, &cloned_map);
+    // the clone can grow like any other map
+    let guard = cloned_map.guard();
+    for i in 1000..3000 {
+        cloned_map.insert(i, i.to_string(), &guard);
+    }
+    assert_eq!(cloned_map.len(), 3000);
+    assert_eq!(map.len(), 1000);
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn clone_map_concurrent() {
+    let map: Arc<HashMap<u32, u32>> = Arc::new((0..1000).map(|x| (x, x)).collect());
+    let writer = {
+        let map = Arc::clone(&map);
+        std::thread::spawn(move || {
+            let guard = map.guard();
+            for i in 0..10_000 {
+                map.remove(&(i % 1000), &guard);
+                map.insert(i % 1000, i, &guard);
+                map.insert(1000 + i, i, &guard);
+            }
+        })
+    };
+    for _ in 0..20 {
+        let cloned_map = HashMap::clone(&map);
+        let guard = cloned_map.guard();
+        // every key is in the clone at most once
+        let mut keys: Vec<_> = cloned_map.keys(&guard).copied().collect();
+        let len = keys.len();
+        keys.sort_unstable();
+        keys.dedup();
+        assert_eq!(keys.len(), len);
+        assert_eq!(cloned_map.len(), len);
+        for key in keys {
+            assert!(cloned_map.contains_key(&key, &guard));
+        }
+    }
+    writer.join().unwrap();
+}
+
+#[test]
+fn shrink_to_fit() {
+    let map = HashMap::<usize, usize>::new();
+    let guard = map.guard();
+
+    // an empty map has no table to shrink
+    map.shrink_to_fit(&guard);
+    assert_eq!(map.bucket_count(&guard), 0);
+
+    for i in 0..4096 {
+        map.insert(i, i, &guard);
+    }
+    for i in 100..4096 {
+        map.remove(&i, &guard);
+    }
+    map.shrink_to(1000, &guard);
+    assert_eq!(map.bucket_count(&guard), 2048);
+    map.shrink_to_fit(&guard);
+    assert_eq!(map.bucket_count(&guard), 256);
+
+    let mut keys: Vec<_> = map.keys(&guard).copied().collect();
+    keys.sort_unstable();
+    assert_eq!(keys, (0..100).collect::<Vec<_>>());
+    for i in 0..100 {
+        assert_eq!(map.get(&i, &guard), Some(&i));
+    }
+
+    // the map grows again as usual
+    for i in 100..1000 {
+        map.insert(i, i, &guard);
+    }
+    assert_eq!(map.len(), 1000);
+    assert!(map.bucket_count(&guard) >= 1024);
+    for i in 0..1000 {
+        assert_eq!(map.get(&i, &guard), Some(&i));
+    }
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn concurrent_shrink() {
+    let map = Arc::new(HashMap::<usize, usize>::new());
+    {
+        let guard = map.guard();
+        for i in 0..64 {
+            map.insert(i, i, &guard);
+        }
+    }
+
+    let shrinker = {
+        let map = Arc::clone(&map);
+        std::thread::spawn(move || {
+            for _ in 0..20 {
+                let guard = map.guard();
+                for i in 64..2048 {
+                    map.insert(i, i, &guard);
+                }
+                for i in 64..2048 {
+                    map.remove(&i, &guard);
+                }
+                map.shrink_to_fit(&guard);
+            }
+        })
+    };
+    let readers: Vec<_> = (0..2)
+        .map(|_| {
+            let map = Arc::clone(&map);
+            std::thread::spawn(move || {
+                for _ in 0..50 {
+                    let guard = map.guard();
+                    for i in 0..64 {
+                        assert_eq!(map.get(&i, &guard), Some(&i));
+                    }
+                    // iterating never skips or repeats an entry, even while bins are moved
+                    let mut keys: Vec<_> = map.keys(&guard).copied().filter(|&k| k < 64).collect();
+                    keys.sort_unstable();
+                    assert_eq!(keys, (0..64).collect::<Vec<_>>());
+                }
+            })
+        })
+        .collect();
+    let writer = {
+        let map = Arc::clone(&map);
+        std::thread::spawn(move || {
+            let guard = map.guard();
+            for i in 0..2000 {
+                map.insert(i % 64, i % 64, &guard);
+            }
+        })
+    };
+
+    shrinker.join().unwrap();
+    for reader in readers {
+        reader.join().unwrap();
+    }
+    writer.join().unwrap();
+
+    let guard = map.guard();
+    assert_eq!(map.len(), 64);
+    map.shrink_to_fit(&guard);
+    assert_eq!(map.bucket_count(&guard), 128);
+    assert_eq!(map.keys(&guard).count(), 64);
+}
+
+#[test]
+fn load_factor() {
+    {
+        let map = HashMap::<usize, usize>::new().with_load_factor(1.0);
+        let guard = map.guard();
+        for i in 0..15 {
+            map.insert(i, i, &guard);
+        }
+        assert_eq!(map.bucket_count(&guard), 16);
+        map.insert(15, 15, &guard);
+        assert_eq!(map.bucket_count(&guard), 32);
+    }
+
+    // tables are sized for the load factor
+    {
+        let map = HashMap::<usize, usize>::new().with_load_factor(2.0);
+        let guard = map.guard();
+        map.reserve(100, &guard);
+        assert_eq!(map.bucket_count(&guard), 64);
+    }
+    let map = HashMap::<usize, usize>::new().with_load_factor(0.5);
+    {
+        let guard = map.guard();
+        for i in 0..20 {
+            map.insert(i, i, &guard);
+        }
+        map.shrink_to_fit(&guard);
+        assert_eq!(map.bucket_count(&guard), 64);
+        for i in 20..100 {
+            map.insert(i, i, &guard);
+        }
+    }
+
+    // clones keep the load factor
+    {
+        let cloned_map = map.clone();
+        let guard = cloned_map.guard();
+        assert_eq!(cloned_map.bucket_count(&guard), 256);
+        for i in 100..128 {
+            cloned_map.insert(i, i, &guard);
+        }
+        assert_eq!(cloned_map.bucket_count(&guard), 512);
+    }
+
+    // a table that already exists keeps its size, but is resized at the new load factor
+    let map = HashMap::<usize, usize>::with_capacity(100).with_load_factor(0.25);
+    let guard = map.guard();
+    assert_eq!(map.bucket_count(&guard), 256);
+    for i in 0..63 {
+        map.insert(i, i, &guard);
+    }
+    assert_eq!(map.bucket_count(&guard), 256);
+    map.insert(63, 63, &guard);
+    assert_eq!(map.bucket_count(&guard), 512);
+}
+
+#[test]
+#[should_panic(expected = "load factor must be positive")]
+fn load_factor_not_positive() {
+    let _ = HashMap::<usize, usize>::new().with_load_factor(0.0);
+}
+
 #[test]
 fn default() {
     let map: HashMap<usize, usize> = Default::default();
@@ -538,6 +1254,52 @@ fn extend_ref() {
     assert_eq!(entries, collected);
 }
 
+#[test]
+#[cfg_attr(miri, ignore)]
+fn extend_from_threads() {
+    let map = Arc::new(HashMap::<usize, usize>::new());
+    let threads: Vec<_> = (0..4)
+        .map(|t| {
+            let map = Arc::clone(&map);
+            std::thread::spawn(move || {
+                (&*map).extend((t * 1000..(t + 1) * 1000).map(|i| (i, t)));
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    let guard = map.guard();
+    assert_eq!(map.len(), 4000);
+    for i in 0..4000 {
+        assert_eq!(map.get(&i, &guard), Some(&(i / 1000)));
+    }
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn extend_from_channel() {
+    let map: HashMap<usize, usize> = HashMap::new();
+    let (tx, rx) = std::sync::mpsc::sync_channel(4);
+    let producer = std::thread::spawn(move || {
+        for i in 0..2000 {
+            tx.send((i % 1500, i)).unwrap();
+        }
+    });
+
+    assert_eq!(map.extend_from_channel(&rx), 2000);
+    producer.join().unwrap();
+
+    let guard = map.guard();
+    assert_eq!(map.len(), 1500);
+    assert_eq!(map.get(&0, &guard), Some(&1500));
+    assert_eq!(map.get(&1499, &guard), Some(&1499));
+
+    // a channel without senders is drained immediately
+    assert_eq!(map.extend_from_channel(&rx), 0);
+}
+
 #[test]
 fn from_iter_ref() {
     use std::iter::FromIterator;
@@ -594,7 +1356,7 @@ fn retain_some() {
     let map: HashMap<u32, u32> = (0..10).map(|x| (x, x)).collect();
     let guard = map.guard();
     let expected_map: HashMap<u32, u32> = (5..10).map(|x| (x, x)).collect();
-    map.retain(|_, v| *v >= 5, &guard);
+    assert_eq!(map.retain(|_, v| *v >= 5, &guard), 5);
     assert_eq!(map.len(), 5);
     assert_eq!(map, expected_map);
 }
@@ -612,7 +1374,367 @@ fn retain_force_some() {
     let map: HashMap<u32, u32> = (0..10).map(|x| (x, x)).collect();
     let guard = map.guard();
     let expected_map: HashMap<u32, u32> = (5..10).map(|x| (x, x)).collect();
-    map.retain_force(|_, v| *v >= 5, &guard);
+    assert_eq!(map.retain_force(|_, v| *v >= 5, &guard), 5);
+    assert_eq!(map.len(), 5);
+    assert_eq!(map, expected_map);
+}
+
+#[test]
+fn retain_mut_some() {
+    let map: HashMap<u32, u32> = (0..10).map(|x| (x, x)).collect();
+    let guard = map.guard();
+    let expected_map: HashMap<u32, u32> = (5..10).map(|x| (x, x.min(7))).collect();
+    let removed = map.retain_mut(
+        |_, &v| match v {
+            0..=4 => Decision::Remove,
+            8..=9 => Decision::Replace(7),
+            _ => Decision::Keep,
+        },
+        &guard,
+    );
+    assert_eq!(removed, 5);
     assert_eq!(map.len(), 5);
     assert_eq!(map, expected_map);
 }
+
+#[test]
+fn merge_from_iter() {
+    let map: HashMap<u32, Vec<u32>> = (0..5).map(|x| (x, vec![x])).collect();
+    let guard = map.guard();
+    map.merge_from_iter(
+        (3..8).map(|x| (x, vec![x * 10])),
+        |_, old, mut new| {
+            let mut merged = old.clone();
+            merged.append(&mut new);
+            merged
+        },
+        &guard,
+    );
+    assert_eq!(map.len(), 8);
+    assert_eq!(map.get(&0, &guard), Some(&vec![0]));
+    assert_eq!(map.get(&3, &guard), Some(&vec![3, 30]));
+    assert_eq!(map.get(&4, &guard), Some(&vec![4, 40]));
+    assert_eq!(map.get(&7, &guard), Some(&vec![70]));
+}
+
+#[test]
+fn subtract_keys() {
+    let map: HashMap<u32, u32> = (0..100).map(|x| (x, x)).collect();
+    let small: HashMap<u32, ()> = (90..110).map(|x| (x, ())).collect();
+    let large: HashMap<u32, ()> = (0..1000).filter(|x| x % 2 == 0).map(|x| (x, ())).collect();
+
+    let guard = map.guard();
+    // iterates `small`
+    assert_eq!(map.subtract_keys(&small, &guard, &small.guard()), 10);
+    assert_eq!(map.len(), 90);
+    // iterates `map`
+    assert_eq!(map.subtract_keys(&large, &guard, &large.guard()), 45);
+    assert_eq!(map.len(), 45);
+    assert!(map.iter(&guard).all(|(k, _)| k % 2 == 1 && *k < 90));
+}
+
+#[test]
+fn clear_or_yield() {
+    let map: HashMap<u32, u32> = (0..1000).map(|x| (x, x)).collect();
+    let guard = map.guard();
+    let mut calls = 0;
+    let token = map.clear_or_yield(
+        cursor::ResumeToken::default(),
+        || {
+            calls += 1;
+            calls > 10
+        },
+        &guard,
+    );
+    assert!(!token.is_finished());
+    assert!(!map.is_empty());
+    assert!(map.len() < 1000);
+    assert!(map.clear_or_yield(token, || false, &guard).is_finished());
+    assert!(map.is_empty());
+}
+
+#[test]
+fn clear_or_yield_always_progresses() {
+    let map: HashMap<u32, u32> = (0..1000).map(|x| (x, x)).collect();
+    let guard = map.guard();
+    // even if every call yields as early as it can, each one clears a bin, and resumes where the
+    // last one stopped
+    let mut token = cursor::ResumeToken::default();
+    let mut calls = 0;
+    while !token.is_finished() {
+        let before = map.len();
+        token = map.clear_or_yield(token, || true, &guard);
+        assert!(token.is_finished() || map.len() < before);
+        calls += 1;
+    }
+    assert!(map.is_empty());
+    assert!(calls <= 1000);
+
+    // a finished token clears nothing
+    map.insert(0, 0, &guard);
+    assert!(map.clear_or_yield(token, || true, &guard).is_finished());
+    assert_eq!(map.len(), 1);
+}
+
+#[test]
+fn clear_or_yield_across_resize() {
+    let map: HashMap<u32, u32> = (0..1000).map(|x| (x, x)).collect();
+    let guard = map.guard();
+    let mut token = cursor::ResumeToken::default();
+    let mut calls = 0;
+    while !token.is_finished() {
+        let mut budget = 10;
+        token = map.clear_or_yield(
+            token,
+            || {
+                budget -= 1;
+                budget < 0
+            },
+            &guard,
+        );
+        if calls == 0 {
+            // grow the table while the clear is paused
+            for i in 1000..5000 {
+                map.insert(i, i, &guard);
+            }
+            for i in 1000..5000 {
+                map.remove(&i, &guard);
+            }
+        }
+        calls += 1;
+    }
+    assert!(calls > 1);
+    assert!(map.is_empty());
+}
+
+#[test]
+fn retain_or_yield() {
+    let map: HashMap<u32, u32> = (0..1000).map(|x| (x, x)).collect();
+    let guard = map.guard();
+    let mut calls = 0;
+    let token = map.retain_or_yield(
+        |&k, _| k % 2 == 0,
+        cursor::ResumeToken::default(),
+        || {
+            calls += 1;
+            calls > 100
+        },
+        &guard,
+    );
+    assert!(!token.is_finished());
+    assert!(map.len() > 500);
+    let token = map.retain_or_yield(|&k, _| k % 2 == 0, token, || false, &guard);
+    assert!(token.is_finished());
+    assert_eq!(map.len(), 500);
+}
+
+#[test]
+fn retain_or_yield_always_progresses() {
+    let map: HashMap<u32, u32> = (0..1000).map(|x| (x, x)).collect();
+    let guard = map.guard();
+    let mut visited = std::collections::HashMap::new();
+    let mut token = cursor::ResumeToken::default();
+    while !token.is_finished() {
+        let before = visited.len();
+        token = map.retain_or_yield(
+            |&k, _| {
+                *visited.entry(k).or_insert(0) += 1;
+                k % 3 == 0
+            },
+            token,
+            || true,
+            &guard,
+        );
+        assert!(token.is_finished() || visited.len() > before);
+    }
+    // every entry was visited exactly once, since the table did not resize in between
+    assert_eq!(visited.len(), 1000);
+    assert!(visited.values().all(|&n| n == 1));
+    assert_eq!(map.len(), 334);
+}
+
+#[test]
+fn drain() {
+    let map: HashMap<u32, u32> = (0..64).map(|x| (x, x * 2)).collect();
+    let guard = map.guard();
+
+    // dropping the iterator early leaves the rest of the map alone
+    assert_eq!(map.drain(&guard).take(10).count(), 10);
+    assert_eq!(map.len(), 54);
+
+    let mut drained: Vec<_> = map.drain(&guard).map(|(&k, &v)| (k, v)).collect();
+    drained.sort_unstable();
+    assert_eq!(drained.len(), 54);
+    assert!(drained.iter().all(|&(k, v)| v == k * 2));
+    assert!(map.is_empty());
+    assert_eq!(map.drain(&guard).count(), 0);
+}
+
+#[test]
+fn extract_if() {
+    let map: HashMap<u32, u32> = (0..64).map(|x| (x, x * 2)).collect();
+    let guard = map.guard();
+
+    let mut extracted: Vec<_> = map
+        .extract_if(|&k, _| k % 4 == 0, &guard)
+        .map(|(&k, &v)| (k, v))
+        .collect();
+    extracted.sort_unstable();
+    assert_eq!(
+        extracted,
+        (0..16).map(|x| (x * 4, x * 8)).collect::<Vec<_>>()
+    );
+    assert_eq!(map.len(), 48);
+    assert!(map.keys(&guard).all(|k| k % 4 != 0));
+
+    // entries that do not match are left alone
+    assert_eq!(map.extract_if(|_, &v| v > 1000, &guard).count(), 0);
+    assert_eq!(map.len(), 48);
+}
+
+#[test]
+fn pop() {
+    let map: HashMap<u32, u32> = (0..16).map(|x| (x, x * 2)).collect();
+    let guard = map.guard();
+    let mut popped = Vec::new();
+    while let Some((&k, &v)) = map.pop(&guard) {
+        popped.push((k, v));
+    }
+    popped.sort_unstable();
+    assert_eq!(popped, (0..16).map(|x| (x, x * 2)).collect::<Vec<_>>());
+    assert!(map.is_empty());
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn concurrent_pop() {
+    let map: Arc<HashMap<u32, u32>> = Arc::new((0..1024).map(|x| (x, x)).collect());
+    let threads: Vec<_> = (0..8)
+        .map(|_| {
+            let map = Arc::clone(&map);
+            std::thread::spawn(move || {
+                let guard = map.guard();
+                let mut taken = Vec::new();
+                while let Some((&k, _)) = map.pop(&guard) {
+                    taken.push(k);
+                }
+                taken
+            })
+        })
+        .collect();
+    // every entry is handed to exactly one thread
+    let mut taken: Vec<_> = threads
+        .into_iter()
+        .flat_map(|thread| thread.join().unwrap())
+        .collect();
+    taken.sort_unstable();
+    assert_eq!(taken, (0..1024).collect::<Vec<_>>());
+    assert!(map.is_empty());
+}
+
+#[test]
+fn get_many() {
+    let map: HashMap<String, u32> = (0..8).map(|x| (x.to_string(), x)).collect();
+    let guard = map.guard();
+    assert_eq!(map.get_many::<str>(&[], &guard), []);
+    assert_eq!(
+        map.get_many(&["3", "9", "0", "3"], &guard),
+        [Some(&3), None, Some(&0), Some(&3)]
+    );
+}
+
+#[test]
+fn insert_all() {
+    // the table is sized for all entries at once, like with an explicit reserve
+    let reserved = {
+        let map = HashMap::<u32, u32>::new();
+        let guard = map.guard();
+        map.reserve(1000, &guard);
+        map.bucket_count(&guard)
+    };
+
+    let map = HashMap::<u32, u32>::new();
+    let guard = map.guard();
+    map.insert_all((0..1000).map(|x| (x, x)), &guard);
+    assert_eq!(map.len(), 1000);
+    assert_eq!(map.bucket_count(&guard), reserved);
+
+    // existing keys are replaced, and a repeated key keeps its last value
+    map.insert_all(vec![(1, 10), (2000, 1), (2000, 2)], &guard);
+    assert_eq!(map.len(), 1001);
+    assert_eq!(map.get(&1, &guard), Some(&10));
+    assert_eq!(map.get(&2000, &guard), Some(&2));
+}
+
+#[test]
+fn search() {
+    let map: HashMap<u32, u32> = (0..100).map(|x| (x, x * 3)).collect();
+    let guard = map.guard();
+
+    let mut visited = 0;
+    let found = map.search(
+        |&k, &v| {
+            visited += 1;
+            if v == 150 {
+                Some(k)
+            } else {
+                None
+            }
+        },
+        &guard,
+    );
+    assert_eq!(found, Some(50));
+    assert!(visited <= 100);
+    assert_eq!(
+        map.search(|_, &v| if v == 1 { Some(v) } else { None }, &guard),
+        None
+    );
+
+    assert!(map.contains_value(&297, &guard));
+    assert!(!map.contains_value(&298, &guard));
+    assert!(!HashMap::<u32, u32>::new().contains_value(&0, &guard));
+}
+
+#[test]
+fn into_keys_and_values() {
+    let values: Vec<_> = (0..64).map(Arc::new).collect();
+    let map = HashMap::<u32, Arc<u32>>::new();
+    {
+        let guard = map.guard();
+        for (i, value) in values.iter().enumerate() {
+            map.insert(i as u32, Arc::clone(value), &guard);
+        }
+    }
+
+    let moved: Vec<_> = map.into_values().collect();
+    assert_eq!(moved.len(), 64);
+    // the values were moved out rather than cloned or dropped
+    assert!(values.iter().all(|v| Arc::strong_count(v) == 2));
+    drop(moved);
+    assert!(values.iter().all(|v| Arc::strong_count(v) == 1));
+
+    let map: HashMap<u32, u32> = (0..64).map(|x| (x, x)).collect();
+    let mut keys: Vec<_> = map.into_keys().collect();
+    keys.sort_unstable();
+    assert_eq!(keys, (0..64).collect::<Vec<_>>());
+
+    assert_eq!(HashMap::<u32, u32>::new().into_keys().count(), 0);
+}
+
+#[test]
+fn into_iter() {
+    let map: HashMap<u32, String> = (0..64).map(|x| (x, x.to_string())).collect();
+    let mut entries: Vec<(u32, String)> = map.into_iter().collect();
+    entries.sort();
+    assert_eq!(
+        entries,
+        (0..64).map(|x| (x, x.to_string())).collect::<Vec<_>>()
+    );
+
+    let map: HashMap<u32, u32> = (0..8).map(|x| (x, x)).collect();
+    let mut sum = 0;
+    for (k, v) in map {
+        sum += k + v;
+    }
+    assert_eq!(sum, 56);
+}
@@ -81,6 +81,15 @@ fn insert_and_get() {
     }
 }
 
+#[test]
+fn insert_and_get_returns_value() {
+    let map = HashMap::<usize, usize>::new();
+    let map = map.pin();
+    assert_eq!(map.insert_and_get(42, 0), &0);
+    assert_eq!(map.insert_and_get(42, 1), &1);
+    assert_eq!(map.get(&42), Some(&1));
+}
+
 #[test]
 fn insert_and_get_key_value() {
     let map = HashMap::<usize, usize>::new();
@@ -522,3 +531,141 @@ fn concurrent_compute_if_present() {
         assert!(map.get(&i).is_none());
     }
 }
+
+#[test]
+fn index() {
+    let map = HashMap::<usize, usize>::new();
+    let map = map.pin();
+    map.insert(42, 0);
+    assert_eq!(map[&42], 0);
+
+    // the reference stays valid after the entry is removed, since the ref holds the guard
+    let value = &map[&42];
+    map.remove(&42);
+    assert_eq!(*value, 0);
+}
+
+#[test]
+#[should_panic(expected = "no entry found for key")]
+fn index_missing() {
+    let map = HashMap::<usize, usize>::new();
+    let map = map.pin();
+    let _ = map[&42];
+}
+
+#[test]
+fn extend() {
+    let map = HashMap::<usize, usize>::new();
+    let mut map = map.pin();
+    map.extend(vec![(42, 0), (16, 6)]);
+    map.extend(vec![(&38, &42), (&16, &7)]);
+
+    let mut collected: Vec<(usize, usize)> = map.iter().map(|(&k, &v)| (k, v)).collect();
+    collected.sort();
+    assert_eq!(collected, vec![(16, 7), (38, 42), (42, 0)]);
+}
+
+struct Holder<'map> {
+    map: HashMapRef<'map, usize, usize>,
+    set: HashSetRef<'map, usize>,
+}
+
+fn holder<'map>(
+    map: &'map HashMap<usize, usize>,
+    set: &'map HashSet<usize>,
+    guard: Option<&'map epoch::Guard>,
+) -> Holder<'map> {
+    match guard {
+        Some(guard) => Holder {
+            map: map.with_guard(guard),
+            set: set.with_guard(guard),
+        },
+        None => Holder {
+            map: map.pin(),
+            set: set.pin(),
+        },
+    }
+}
+
+#[test]
+fn stored_in_struct() {
+    let map = HashMap::new();
+    let set = HashSet::new();
+    let guard = epoch::pin();
+    for &guard in &[None, Some(&guard)] {
+        let holder = holder(&map, &set, guard);
+        holder.map.insert(holder.map.len(), 0);
+        holder.set.insert(holder.set.len());
+    }
+    assert_eq!(map.pin().len(), 2);
+    assert_eq!(set.pin().len(), 2);
+}
+
+#[test]
+fn as_guard() {
+    use flurry::epoch::AsGuard;
+
+    let map = HashMap::<usize, usize>::new();
+    let set = HashSet::<usize>::new();
+    let mref = map.pin();
+    mref.insert(1, 1);
+    // a reference's pin can be used for another collection
+    set.insert(1, mref.as_guard());
+    let sref = set.pin();
+    assert_eq!(map.get(&1, sref.as_guard()), Some(&1));
+    assert!(sref.contains(&1));
+}
+
+thread_local! {
+    static NAMES: std::cell::RefCell<Option<OwnedMapRef<usize, String>>> = Default::default();
+}
+
+#[test]
+fn pin_owned() {
+    let map = Arc::new(HashMap::<usize, String>::new());
+    let owned = map.pin_owned();
+    assert!(owned.is_empty());
+    assert_eq!(owned.insert(1, String::from("one")), None);
+    assert!(owned.contains_key(&1));
+    assert_eq!(owned.map_ref().get(&1).map(String::as_str), Some("one"));
+    assert!(Arc::ptr_eq(owned.map(), &map));
+
+    // the reference keeps the map alive, so it can outlive the caller's handle to it
+    NAMES.with(|names| *names.borrow_mut() = Some(owned.clone()));
+    drop(map);
+    let removed = owned.remove(&1).cloned();
+    assert_eq!(removed.as_deref(), Some("one"));
+    NAMES.with(|names| {
+        let names = names.borrow_mut().take().unwrap();
+        assert_eq!(names.len(), 0);
+        assert_eq!(format!("{:?}", names), "{}");
+    });
+}
+
+#[test]
+fn unpinned() {
+    let map = HashMap::<usize, String>::new();
+    let unpinned = map.unpinned();
+    assert!(unpinned.is_empty());
+    assert_eq!(unpinned.insert(1, String::from("one")), None);
+
+    // the reference can be sent to another thread, which pins for each call
+    std::thread::scope(|s| {
+        s.spawn(move || {
+            assert_eq!(unpinned.get(&1).as_deref(), Some("one"));
+            assert_eq!(
+                unpinned.insert(1, String::from("uno")).as_deref(),
+                Some("one")
+            );
+            assert_eq!(unpinned.insert(2, String::from("two")), None);
+        });
+    });
+
+    assert_eq!(unpinned.len(), 2);
+    assert!(unpinned.contains_key(&2));
+    assert_eq!(unpinned.view(&1, String::len), Some(3));
+    assert_eq!(unpinned.remove(&2).as_deref(), Some("two"));
+    assert_eq!(unpinned.pin().get(&1).map(String::as_str), Some("uno"));
+    assert!(std::ptr::eq(unpinned.map(), &map));
+    assert_eq!(format!("{:?}", unpinned), r#"{1: "uno"}"#);
+}
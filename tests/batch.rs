@@ -0,0 +1,193 @@
+use flurry::events::{Event, EventStream};
+use flurry::*;
+use std::hash::BuildHasherDefault;
+use std::sync::Arc;
+use std::thread;
+
+#[derive(Default)]
+struct ZeroHasher;
+
+impl std::hash::Hasher for ZeroHasher {
+    fn finish(&self) -> u64 {
+        0
+    }
+    fn write(&mut self, _: &[u8]) {}
+}
+
+#[test]
+fn apply_empty() {
+    let map = HashMap::<usize, usize>::new();
+    map.apply_batch(WriteBatch::new(), &map.guard());
+    assert!(map.is_empty());
+}
+
+#[test]
+fn apply_mixed() {
+    let map = HashMap::new();
+    let guard = map.guard();
+    for i in 0..10 {
+        map.insert(i, i, &guard);
+    }
+
+    let mut batch = WriteBatch::new();
+    for i in 0..10 {
+        if i % 2 == 0 {
+            batch.remove(i);
+        } else {
+            batch.insert(i, i * 10);
+        }
+    }
+    batch.extend((10..20).map(|i| (i, i * 10)));
+    batch.remove(100);
+    assert_eq!(batch.len(), 21);
+    map.apply_batch(batch, &guard);
+
+    assert_eq!(map.len(), 15);
+    for i in 0..20 {
+        if i < 10 && i % 2 == 0 {
+            assert_eq!(map.get(&i, &guard), None);
+        } else {
+            assert_eq!(map.get(&i, &guard), Some(&(i * 10)));
+        }
+    }
+}
+
+#[test]
+fn last_operation_wins() {
+    let map = HashMap::new();
+    let guard = map.guard();
+    map.insert(1, 0, &guard);
+
+    let mut batch = WriteBatch::new();
+    batch.insert(1, 1);
+    batch.remove(1);
+    batch.remove(2);
+    batch.insert(2, 2);
+    batch.insert(3, 3);
+    batch.insert(3, 4);
+    map.apply_batch(batch, &guard);
+
+    assert_eq!(map.get(&1, &guard), None);
+    assert_eq!(map.get(&2, &guard), Some(&2));
+    assert_eq!(map.get(&3, &guard), Some(&4));
+    assert_eq!(map.len(), 2);
+}
+
+#[test]
+fn apply_large() {
+    let map = HashMap::new();
+    let guard = map.guard();
+    let mut batch = WriteBatch::with_capacity(1000);
+    batch.extend((0..1000).map(|i| (i, i)));
+    map.apply_batch(batch, &guard);
+    assert_eq!(map.len(), 1000);
+
+    let mut batch = WriteBatch::new();
+    for i in (0..1000).step_by(3) {
+        batch.remove(i);
+    }
+    map.pin().apply_batch(batch);
+    assert_eq!(map.len(), 666);
+    for i in 0..1000 {
+        let expected = if i % 3 == 0 { None } else { Some(&i) };
+        assert_eq!(map.get(&i, &guard), expected);
+    }
+}
+
+#[test]
+fn apply_to_single_bin() {
+    // every key collides, so the bin eventually turns into a tree
+    let map = HashMap::<usize, usize, _>::with_hasher(BuildHasherDefault::<ZeroHasher>::default());
+    let guard = map.guard();
+    for round in 0..3 {
+        let mut batch = WriteBatch::new();
+        batch.extend((0..6).map(|i| (round * 6 + i, i)));
+        // remove the current head of the bin, and something in its middle
+        if round > 0 {
+            batch.remove(0);
+            batch.remove(round * 6 - 3);
+        }
+        map.apply_batch(batch, &guard);
+    }
+
+    assert_eq!(map.len(), 15);
+    for i in 0..18 {
+        let removed = i == 0 || i == 3 || i == 9;
+        assert_eq!(
+            map.get(&i, &guard).copied(),
+            if removed { None } else { Some(i % 6) }
+        );
+    }
+}
+
+#[test]
+fn notifies_observers() {
+    let events = Arc::new(EventStream::with_values(16));
+    let map = HashMap::new().with_events(Arc::clone(&events));
+    let guard = map.guard();
+    map.insert(1, "a", &guard);
+    map.insert(2, "b", &guard);
+    events.drain();
+
+    let mut batch = WriteBatch::new();
+    batch.insert(1, "c");
+    batch.remove(2);
+    batch.insert(3, "d");
+    map.apply_batch(batch, &guard);
+
+    let mut changes = events.drain();
+    changes.sort_by_key(|event| *event.key());
+    assert_eq!(
+        changes,
+        vec![
+            Event::Updated {
+                key: 1,
+                value: Some("c")
+            },
+            Event::Removed { key: 2 },
+            Event::Inserted {
+                key: 3,
+                value: Some("d")
+            },
+        ]
+    );
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn concurrent_batches() {
+    const THREADS: usize = 4;
+    const KEYS: usize = 500;
+
+    let map = Arc::new(HashMap::new());
+    let writers: Vec<_> = (0..THREADS)
+        .map(|t| {
+            let map = Arc::clone(&map);
+            thread::spawn(move || {
+                let mut batch = WriteBatch::new();
+                batch.extend((0..KEYS).map(|i| (i * THREADS + t, t)));
+                map.apply_batch(batch, &map.guard());
+
+                let mut batch = WriteBatch::new();
+                for i in (0..KEYS).filter(|i| i % 2 == 0) {
+                    batch.remove(i * THREADS + t);
+                }
+                map.apply_batch(batch, &map.guard());
+            })
+        })
+        .collect();
+    for writer in writers {
+        writer.join().unwrap();
+    }
+
+    let guard = map.guard();
+    assert_eq!(map.len(), THREADS * KEYS / 2);
+    for key in 0..THREADS * KEYS {
+        // every other key of each thread was removed again
+        let expected = match (key / THREADS) % 2 {
+            0 => None,
+            _ => Some(key % THREADS),
+        };
+        assert_eq!(map.get(&key, &guard).copied(), expected);
+    }
+}
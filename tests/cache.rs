@@ -0,0 +1,114 @@
+use flurry::cache::{Cache, CacheLoader, CacheWriter};
+use flurry::*;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+#[derive(Default)]
+struct Store {
+    entries: Mutex<BTreeMap<usize, usize>>,
+    loads: AtomicUsize,
+    log: Mutex<Vec<String>>,
+}
+
+impl CacheLoader<usize, usize> for Store {
+    fn load(&self, key: &usize) -> Option<usize> {
+        self.loads.fetch_add(1, Ordering::SeqCst);
+        self.entries.lock().unwrap().get(key).copied()
+    }
+}
+
+impl CacheWriter<usize, usize> for Store {
+    fn write(&self, key: &usize, value: &usize) {
+        self.log
+            .lock()
+            .unwrap()
+            .push(format!("write {} {}", key, value));
+        self.entries.lock().unwrap().insert(*key, *value);
+    }
+
+    fn delete(&self, key: &usize) {
+        self.log.lock().unwrap().push(format!("delete {}", key));
+        self.entries.lock().unwrap().remove(key);
+    }
+}
+
+#[test]
+fn read_through() {
+    let store = Arc::new(Store::default());
+    store.entries.lock().unwrap().insert(1, 10);
+    let cache = Cache::new(HashMap::new()).with_loader(Arc::clone(&store));
+    let guard = cache.guard();
+
+    assert_eq!(cache.get(&1, &guard), Some(&10));
+    assert_eq!(cache.get(&1, &guard), Some(&10));
+    assert_eq!(store.loads.load(Ordering::SeqCst), 1);
+
+    // misses in the store are not cached
+    assert_eq!(cache.get(&2, &guard), None);
+    assert_eq!(cache.get(&2, &guard), None);
+    assert_eq!(store.loads.load(Ordering::SeqCst), 3);
+
+    // invalidated keys are loaded again
+    store.entries.lock().unwrap().insert(1, 11);
+    assert_eq!(cache.invalidate(&1, &guard), Some(&10));
+    assert_eq!(cache.get(&1, &guard), Some(&11));
+
+    // nothing is written without a writer
+    cache.insert(3, 30, &guard);
+    assert_eq!(store.entries.lock().unwrap().get(&3), None);
+}
+
+#[test]
+fn write_through() {
+    let store = Arc::new(Store::default());
+    let cache = Cache::new(HashMap::new()).with_writer(Arc::clone(&store));
+    let guard = cache.guard();
+
+    assert_eq!(cache.insert(1, 10, &guard), None);
+    assert_eq!(cache.insert(1, 11, &guard), Some(&10));
+    assert_eq!(cache.remove(&1, &guard), Some(&11));
+    assert_eq!(cache.remove(&2, &guard), None);
+    assert_eq!(
+        *store.log.lock().unwrap(),
+        ["write 1 10", "write 1 11", "delete 1", "delete 2"]
+    );
+    // without a loader, misses stay misses
+    assert_eq!(cache.get(&1, &guard), None);
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn write_behind() {
+    let store = Arc::new(Store::default());
+    let cache = Arc::new(Cache::new(HashMap::new()).with_write_behind(Arc::clone(&store)));
+    let writers: Vec<_> = (0..4)
+        .map(|t| {
+            let cache = Arc::clone(&cache);
+            thread::spawn(move || {
+                let guard = cache.guard();
+                for i in 0..64 {
+                    cache.insert(t * 64 + i, i, &guard);
+                }
+                for i in 0..32 {
+                    cache.remove(&(t * 64 + i), &guard);
+                }
+            })
+        })
+        .collect();
+    for w in writers {
+        w.join().unwrap();
+    }
+
+    cache.flush();
+    assert_eq!(store.entries.lock().unwrap().len(), 128);
+    assert_eq!(store.log.lock().unwrap().len(), 4 * 96);
+
+    // dropping the cache drains the queue
+    let guard = cache.guard();
+    cache.insert(1000, 1, &guard);
+    drop(guard);
+    drop(Arc::try_unwrap(cache).unwrap());
+    assert_eq!(store.entries.lock().unwrap().get(&1000), Some(&1));
+}
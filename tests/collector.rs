@@ -0,0 +1,212 @@
+use flurry::epoch::{self, Collector};
+use flurry::*;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+struct Tracked(Arc<AtomicUsize>);
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Hashes integers so that they all end up in the first bin of tables with up to 1024 bins.
+#[derive(Default)]
+struct FirstBinHasher(u64);
+
+impl Hasher for FirstBinHasher {
+    fn finish(&self) -> u64 {
+        self.0 << 10
+    }
+
+    fn write(&mut self, _: &[u8]) {
+        unimplemented!()
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.0 = i;
+    }
+}
+
+#[test]
+fn shared_guard() {
+    let collector = Collector::new();
+    let map = HashMap::new().with_collector(&collector);
+    let set = HashSet::new().with_collector(&collector);
+    let other = HashMap::<usize, usize>::new().with_collector(&map.collector());
+    assert_eq!(map.collector(), collector);
+    assert_eq!(set.collector(), collector);
+    assert_ne!(HashMap::<usize, usize>::new().collector(), collector);
+
+    // a guard from any of them works with all of them
+    let guard = set.guard();
+    map.insert(1, 1, &guard);
+    set.insert(1, &guard);
+    other.insert(1, 2, &guard);
+    let guard = collector.guard();
+    assert_eq!(map.get(&1, &guard), Some(&1));
+    assert!(set.contains(&1, &guard));
+    assert_eq!(other.get(&1, &map.guard()), Some(&2));
+}
+
+#[test]
+fn active_guards() {
+    let collector = Collector::new();
+    let map = HashMap::<usize, usize>::new().with_collector(&collector);
+    let _global = epoch::pin();
+    let _unrelated = HashMap::<usize, usize>::new().guard();
+    assert_eq!(map.active_guards(), 0);
+    let guard = collector.guard();
+    let _pinned = map.pin();
+    assert_eq!(map.active_guards(), 2);
+    drop(guard);
+    assert_eq!(map.active_guards(), 1);
+}
+
+#[test]
+#[should_panic(
+    expected = "guard belongs to the global collector but this map reclaims through a separate flurry::epoch::Collector"
+)]
+fn foreign_guard() {
+    let map = HashMap::new().with_collector(&Collector::new());
+    map.insert(1, 1, &epoch::pin());
+}
+
+#[test]
+#[should_panic(expected = "guard belongs to a separate flurry::epoch::Collector")]
+fn foreign_guard_clear() {
+    let map = HashMap::<usize, usize>::new();
+    let other = HashMap::<usize, usize>::new().with_collector(&Collector::new());
+    map.insert(1, 1, &map.guard());
+    map.clear(&other.guard());
+}
+
+#[test]
+fn clone_shares_collector() {
+    let collector = Collector::new();
+    let map = HashMap::new().with_collector(&collector);
+    map.insert(1, 1, &collector.guard());
+    let clone = map.clone();
+    assert_eq!(clone.collector(), collector);
+    assert_eq!(clone.get(&1, &collector.guard()), Some(&1));
+}
+
+#[test]
+fn flush_garbage() {
+    // the map has a collector of its own, so that guards of other tests do not hold it up
+    let collector = Collector::new();
+    let dropped = Arc::new(AtomicUsize::new(0));
+    let map = HashMap::new().with_collector(&collector);
+    {
+        let guard = map.guard();
+        for i in 0..64 {
+            map.insert(i, Tracked(Arc::clone(&dropped)), &guard);
+        }
+        for i in 0..16 {
+            map.remove(&i, &guard);
+        }
+    }
+    map.flush_garbage();
+    assert_eq!(dropped.load(Ordering::SeqCst), 16);
+
+    // garbage retired while a guard is alive is held up until it is dropped
+    let guard = collector.guard();
+    map.clear(&guard);
+    collector.flush();
+    assert_eq!(dropped.load(Ordering::SeqCst), 16);
+    drop(guard);
+    collector.flush();
+    assert_eq!(dropped.load(Ordering::SeqCst), 64);
+}
+
+#[test]
+fn reclaim_hook() {
+    let collector = Collector::new();
+    let reclaimed = Arc::new(Mutex::new(Vec::new()));
+    let map =
+        HashMap::with_capacity_and_hasher(128, BuildHasherDefault::<FirstBinHasher>::default())
+            .with_collector(&collector)
+            .with_reclaim_hook({
+                let reclaimed = Arc::clone(&reclaimed);
+                move |value: u64| reclaimed.lock().unwrap().push(value)
+            });
+    let guard = collector.guard();
+    // all keys share a bin, which becomes a tree bin (unless the `unordered-keys` feature turns
+    // tree bins off)
+    for i in 0..32 {
+        map.insert(i, i, &guard);
+    }
+    let trees = if cfg!(feature = "unordered-keys") {
+        0
+    } else {
+        1
+    };
+    assert_eq!(map.table_stats(&guard).tree_bins(), trees);
+    map.insert(0, 100, &guard);
+    map.remove(&1, &guard);
+    map.compute_if_present(&2, |_, _| None, &guard);
+    map.retain(|&k, _| k != 3, &guard);
+
+    // nothing is reclaimed while the guard may still read the values
+    collector.flush();
+    assert!(reclaimed.lock().unwrap().is_empty());
+    drop(guard);
+    map.flush_garbage();
+    let mut values = reclaimed.lock().unwrap().clone();
+    values.sort();
+    assert_eq!(values, [0, 1, 2, 3]);
+
+    // values in a cleared tree bin are passed to the hook as well
+    reclaimed.lock().unwrap().clear();
+    map.pin().clear();
+    map.flush_garbage();
+    let mut values = reclaimed.lock().unwrap().clone();
+    values.sort();
+    assert_eq!(values, (4..32).chain(Some(100)).collect::<Vec<_>>());
+
+    // values that are still in the map when it is dropped are not
+    reclaimed.lock().unwrap().clear();
+    map.pin().insert(0, 0);
+    drop(map);
+    collector.flush();
+    assert!(reclaimed.lock().unwrap().is_empty());
+}
+
+#[test]
+fn guard_cached() {
+    let first = Collector::new();
+    let second = Collector::new();
+    let a = HashMap::new().with_collector(&first);
+    let b = HashMap::new().with_collector(&second);
+    let global = HashMap::new();
+
+    // switching between collectors replaces the cached handle
+    for i in 0..4 {
+        a.pin_cached().insert(i, i);
+        b.insert(i, i, &b.guard_cached());
+        global.pin_cached().insert(i, i);
+        assert_eq!(a.get(&i, &first.guard_cached()), Some(&i));
+        assert_eq!(b.pin_cached().get(&i), Some(&i));
+        assert_eq!(global.get(&i, &epoch::pin()), Some(&i));
+    }
+
+    // cached guards are not tracked
+    let _guard = a.guard_cached();
+    let _pinned = a.pin_cached();
+    assert_eq!(a.active_guards(), 0);
+
+    // a cached guard works with everything that shares its collector
+    let set = HashSet::new().with_collector(&second);
+    set.insert(1, &b.guard_cached());
+    assert!(set.pin_cached().contains(&1));
+}
+
+#[test]
+#[should_panic(expected = "guard belongs to a separate flurry::epoch::Collector")]
+fn foreign_guard_cached() {
+    let a = HashMap::new().with_collector(&Collector::new());
+    let b = HashMap::<usize, usize>::new().with_collector(&Collector::new());
+    a.insert(1, 1, &b.guard_cached());
+}
@@ -0,0 +1,151 @@
+use flurry::compat::dashmap::{DashMap, Entry};
+use std::sync::Arc;
+use std::thread;
+
+#[test]
+fn get_insert_remove() {
+    let map = DashMap::new();
+    assert!(map.is_empty());
+    assert_eq!(map.insert(1, "one"), None);
+    assert_eq!(map.insert(1, "uno"), Some("one"));
+    assert_eq!(map.len(), 1);
+
+    {
+        let r = map.get(&1).unwrap();
+        assert_eq!(r.key(), &1);
+        assert_eq!(*r, "uno");
+        // the reference outlives the removal of the entry
+        assert_eq!(map.remove(&1), Some((1, "uno")));
+        assert_eq!(r.pair(), (&1, &"uno"));
+    }
+
+    assert!(map.get(&1).is_none());
+    assert!(!map.contains_key(&1));
+    assert_eq!(map.remove(&1), None);
+}
+
+#[test]
+fn remove_if() {
+    let map = DashMap::new();
+    map.insert(1, 10);
+    assert_eq!(map.remove_if(&1, |_, v| *v > 10), None);
+    assert_eq!(map.remove_if(&1, |_, v| *v == 10), Some((1, 10)));
+    assert!(map.is_empty());
+}
+
+#[test]
+fn remove_if_false_leaves_entry_untouched() {
+    let map = DashMap::new();
+    map.insert(1, 10);
+    // the reference keeps the value alive, so a replaced value could not reuse its memory
+    let before = map.get(&1).unwrap();
+    assert_eq!(map.remove_if(&1, |_, _| false), None);
+    let after = map.get(&1).unwrap();
+    assert!(std::ptr::eq(before.value(), after.value()));
+}
+
+#[test]
+fn alter() {
+    let map = DashMap::new();
+    map.insert("a", 1);
+    map.insert("b", 2);
+    map.alter("a", |_, v| v * 10);
+    map.alter("missing", |_, v| v * 10);
+    assert_eq!(*map.get("a").unwrap(), 10);
+    assert!(map.get("missing").is_none());
+
+    map.alter_all(|_, v| v + 1);
+    assert_eq!(*map.get("a").unwrap(), 11);
+    assert_eq!(*map.get("b").unwrap(), 3);
+}
+
+#[test]
+fn retain_and_clear() {
+    let map = DashMap::new();
+    for i in 0..10 {
+        map.insert(i, i);
+    }
+    map.retain(|_, v| v % 2 == 0);
+    assert_eq!(map.len(), 5);
+    map.clear();
+    assert!(map.is_empty());
+}
+
+#[test]
+fn iter() {
+    let map = DashMap::new();
+    for i in 0..64 {
+        map.insert(i, i * 2);
+    }
+    let mut seen: Vec<_> = map.iter().map(|r| (*r.key(), *r.value())).collect();
+    seen.sort();
+    assert_eq!(seen, (0..64).map(|i| (i, i * 2)).collect::<Vec<_>>());
+
+    // items stay valid after the iterator is gone
+    let refs: Vec<_> = map.iter().collect();
+    map.clear();
+    assert_eq!(refs.len(), 64);
+    assert!(refs.iter().all(|r| *r.value() == r.key() * 2));
+}
+
+#[test]
+fn entry() {
+    let map = DashMap::new();
+    assert_eq!(*map.entry("a").or_insert(1), 1);
+    assert_eq!(*map.entry("a").or_insert(2), 1);
+    assert_eq!(*map.entry("a").and_modify(|v| *v += 1).or_insert(0), 2);
+    assert_eq!(*map.entry("b").and_modify(|v| *v += 1).or_default(), 0);
+    assert_eq!(*map.entry("c").or_insert_with(|| 3), 3);
+    assert_eq!(map.entry("d").key(), &"d");
+
+    match map.entry("a") {
+        Entry::Occupied(e) => {
+            assert_eq!(e.key(), &"a");
+            assert_eq!(e.get(), &2);
+            assert_eq!(e.insert(5), 2);
+        }
+        Entry::Vacant(_) => panic!("entry should be occupied"),
+    }
+    match map.entry("a") {
+        Entry::Occupied(e) => assert_eq!(e.remove(), 5),
+        Entry::Vacant(_) => panic!("entry should be occupied"),
+    }
+    match map.entry("a") {
+        Entry::Occupied(_) => panic!("entry should be vacant"),
+        Entry::Vacant(e) => assert_eq!(*e.insert(7), 7),
+    }
+    assert_eq!(*map.get("a").unwrap(), 7);
+}
+
+#[test]
+fn into_flurry() {
+    let map = DashMap::new();
+    map.insert(1, 1);
+    assert_eq!(map.as_flurry().pin().get(&1), Some(&1));
+    let map = map.into_flurry();
+    assert_eq!(map.pin().get(&1), Some(&1));
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn concurrent_entry() {
+    let map = Arc::new(DashMap::new());
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let map = Arc::clone(&map);
+            thread::spawn(move || {
+                for i in 0..256 {
+                    map.entry(i).or_insert(i);
+                    map.alter(&i, |_, v| v + 1);
+                }
+            })
+        })
+        .collect();
+    for t in threads {
+        t.join().unwrap();
+    }
+    assert_eq!(map.len(), 256);
+    for r in map.iter() {
+        assert_eq!(*r.value(), r.key() + 4);
+    }
+}
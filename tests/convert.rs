@@ -0,0 +1,84 @@
+use flurry::*;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasherDefault, Hasher};
+
+#[derive(Default)]
+struct ZeroHasher;
+
+impl Hasher for ZeroHasher {
+    fn finish(&self) -> u64 {
+        0
+    }
+    fn write(&mut self, _: &[u8]) {}
+}
+
+#[test]
+fn map_round_trip() {
+    let std: std::collections::HashMap<_, _> = (0..1024).map(|i| (i, i * 2)).collect();
+    let map = HashMap::from(std.clone());
+    assert_eq!(map.len(), 1024);
+    {
+        let guard = map.guard();
+        for i in 0..1024 {
+            assert_eq!(map.get(&i, &guard), Some(&(i * 2)));
+        }
+        // the map keeps working as usual
+        map.insert(1024, 2048, &guard);
+        assert_eq!(map.remove(&0, &guard), Some(&0));
+    }
+
+    let back = map.into_std();
+    assert_eq!(back.len(), 1024);
+    assert_eq!(back.get(&0), None);
+    assert_eq!(back[&1024], 2048);
+    assert!(std.iter().all(|(k, v)| *k == 0 || back[k] == *v));
+}
+
+#[test]
+fn empty() {
+    let std = std::collections::HashMap::<usize, usize>::new();
+    let map = HashMap::from(std);
+    assert!(map.is_empty());
+    map.pin().insert(1, 1);
+    assert_eq!(map.into_std().len(), 1);
+
+    let set = HashSet::<usize, RandomState>::from(std::collections::HashSet::default());
+    assert!(set.into_std().is_empty());
+}
+
+#[test]
+fn colliding_keys() {
+    let hasher = BuildHasherDefault::<ZeroHasher>::default();
+    let mut std = std::collections::HashMap::with_hasher(hasher);
+    for i in 0..64 {
+        std.insert(i, i);
+    }
+    let map = HashMap::from(std);
+    let guard = map.guard();
+    let trees = if cfg!(feature = "unordered-keys") {
+        0
+    } else {
+        1
+    };
+    assert_eq!(map.tree_bins(&guard).count(), trees);
+    for i in 0..64 {
+        assert_eq!(map.get(&i, &guard), Some(&i));
+    }
+    drop(guard);
+
+    let back = map.into_std();
+    assert_eq!(back.len(), 64);
+    assert!((0..64).all(|i| back[&i] == i));
+}
+
+#[test]
+fn set_round_trip() {
+    let std: std::collections::HashSet<_> = (0..256).map(|i| i.to_string()).collect();
+    let set = HashSet::from(std.clone());
+    assert_eq!(set.len(), 256);
+    {
+        let guard = set.guard();
+        assert!(std.iter().all(|v| set.contains(v, &guard)));
+    }
+    assert_eq!(set.into_std(), std);
+}
@@ -0,0 +1,165 @@
+use flurry::cursor::ResumeToken;
+use flurry::*;
+use std::collections::HashSet as StdSet;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::sync::Arc;
+use std::thread;
+
+#[derive(Default)]
+struct ZeroHasher;
+
+impl Hasher for ZeroHasher {
+    fn finish(&self) -> u64 {
+        0
+    }
+    fn write(&mut self, _: &[u8]) {}
+}
+
+/// Scans `map` in pages of `page` entries, calling `between` whenever the scan is paused.
+fn scan_in_pages<S>(
+    map: &HashMap<usize, usize, S>,
+    page: usize,
+    mut between: impl FnMut(usize),
+) -> Vec<usize> {
+    let mut seen = Vec::new();
+    let mut token: Option<ResumeToken> = None;
+    for pause in 0.. {
+        let guard = map.guard();
+        let mut cursor = match token {
+            Some(token) => map.cursor_from(token, &guard),
+            None => map.cursor(&guard),
+        };
+        seen.extend(cursor.by_ref().take(page).map(|(k, _)| *k));
+        let next = cursor.into_token();
+        if next.is_finished() {
+            break;
+        }
+        token = Some(next);
+        drop(guard);
+        between(pause);
+    }
+    seen
+}
+
+#[test]
+fn full_scan() {
+    let map: HashMap<_, _> = (0..1024).map(|i| (i, i)).collect();
+    let guard = map.guard();
+    let mut seen: Vec<_> = map.cursor(&guard).map(|(k, _)| *k).collect();
+    seen.sort();
+    assert_eq!(seen, (0..1024).collect::<Vec<_>>());
+
+    let empty = HashMap::<usize, usize>::new();
+    let mut cursor = empty.cursor(&guard);
+    assert_eq!(cursor.by_ref().count(), 0);
+    assert!(cursor.into_token().is_finished());
+}
+
+#[test]
+fn paused_scan_sees_everything_once() {
+    let map: HashMap<_, _> = (0..1024).map(|i| (i, i)).collect();
+    let mut seen = scan_in_pages(&map, 10, |_| {});
+    // without a resize, nothing repeats
+    seen.sort();
+    assert_eq!(seen, (0..1024).collect::<Vec<_>>());
+}
+
+#[test]
+fn resumes_across_growth() {
+    let map: HashMap<_, _> = (0..64).map(|i| (i, i)).collect();
+    let seen = scan_in_pages(&map, 8, |pause| {
+        if pause >= 4 {
+            return;
+        }
+        let guard = map.guard();
+        // grow the table several times over while the scan is paused
+        for i in 0..256 {
+            let key = 1000 + pause * 256 + i;
+            map.insert(key, key, &guard);
+        }
+    });
+    let seen: StdSet<_> = seen.into_iter().collect();
+    assert!((0..64).all(|i| seen.contains(&i)));
+}
+
+#[test]
+fn resumes_across_shrinking() {
+    let map: HashMap<_, _> = (0..4096).map(|i| (i, i)).collect();
+    let mut removed = false;
+    let seen = scan_in_pages(&map, 64, |_| {
+        if !removed {
+            removed = true;
+            let guard = map.guard();
+            for i in 64..4096 {
+                map.remove(&i, &guard);
+            }
+            map.shrink_to_fit(&guard);
+        }
+    });
+    let seen: StdSet<_> = seen.into_iter().collect();
+    assert!((0..64).all(|i| seen.contains(&i)));
+}
+
+#[test]
+fn tree_bins() {
+    let map = HashMap::<usize, usize, _>::with_hasher(BuildHasherDefault::<ZeroHasher>::default());
+    {
+        let guard = map.guard();
+        for i in 0..64 {
+            map.insert(i, i, &guard);
+        }
+    }
+    // pages end halfway through the bin, and pick up where they stopped
+    let mut seen = scan_in_pages(&map, 5, |_| {});
+    seen.sort();
+    assert_eq!(seen, (0..64).collect::<Vec<_>>());
+}
+
+#[test]
+fn finished_token() {
+    let map: HashMap<_, _> = (0..16).map(|i| (i, i)).collect();
+    let guard = map.guard();
+    let mut cursor = map.cursor(&guard);
+    assert!(!cursor.token().is_finished());
+    assert_eq!(cursor.by_ref().count(), 16);
+    let token = cursor.into_token();
+    assert!(token.is_finished());
+    assert_eq!(map.cursor_from(token, &guard).count(), 0);
+}
+
+#[test]
+fn map_ref() {
+    let map: HashMap<_, _> = (0..16).map(|i| (i, i)).collect();
+    let map = map.pin();
+    let mut cursor = map.cursor();
+    assert_eq!(cursor.by_ref().take(3).count(), 3);
+    let token = cursor.into_token();
+    let rest: StdSet<_> = map.cursor_from(token).map(|(k, _)| *k).collect();
+    assert!(rest.len() >= 13);
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn concurrent_resizes() {
+    let map = Arc::new(HashMap::<usize, usize>::new());
+    {
+        let guard = map.guard();
+        for i in 0..128 {
+            map.insert(i, i, &guard);
+        }
+    }
+    let writer = {
+        let map = Arc::clone(&map);
+        thread::spawn(move || {
+            let guard = map.guard();
+            for i in 128..16384 {
+                map.insert(i, i, &guard);
+            }
+        })
+    };
+    let seen: StdSet<_> = scan_in_pages(&map, 4, |_| thread::yield_now())
+        .into_iter()
+        .collect();
+    writer.join().unwrap();
+    assert!((0..128).all(|i| seen.contains(&i)));
+}
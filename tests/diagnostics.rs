@@ -0,0 +1,228 @@
+use flurry::*;
+use std::hash::{BuildHasherDefault, Hasher};
+
+/// Hashes integers to their value modulo 2^32, so that keys can be chosen to collide.
+#[derive(Default)]
+struct LowBitsHasher(u64);
+
+impl Hasher for LowBitsHasher {
+    fn finish(&self) -> u64 {
+        self.0 & 0xffff_ffff
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 = (self.0 << 8) | u64::from(b);
+        }
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.0 = i;
+    }
+}
+
+type LowBits = BuildHasherDefault<LowBitsHasher>;
+
+#[test]
+fn no_tree_bins() {
+    let map = HashMap::new();
+    let guard = map.guard();
+    for i in 0..1024 {
+        map.insert(i, i, &guard);
+    }
+    assert_eq!(map.tree_bins(&guard).count(), 0);
+    assert_eq!(HashMap::<u64, u64>::new().tree_bins(&guard).count(), 0);
+}
+
+#[test]
+#[cfg(not(feature = "unordered-keys"))]
+fn reports_colliding_hashes() {
+    let map = HashMap::<u64, u64, _>::with_capacity_and_hasher(1 << 8, LowBits::default());
+    let guard = map.guard();
+    // ten keys with the same hash, and four more that only share the bin
+    for i in 0..10 {
+        map.insert(3 + (i << 32), i, &guard);
+    }
+    for i in 1..5 {
+        map.insert(3 + (i << 16), i, &guard);
+    }
+    // and some well-behaved keys
+    for i in 100..150 {
+        map.insert(i, i, &guard);
+    }
+
+    let bins: Vec<_> = map.pin().tree_bins().collect();
+    assert_eq!(bins.len(), 1);
+    let bin = &bins[0];
+    assert_eq!(bin.index(), 3);
+    assert_eq!(bin.entries(), 14);
+    assert_eq!(
+        bin.hashes(),
+        &[
+            (3, 10),
+            (3 + (1 << 16), 1),
+            (3 + (2 << 16), 1),
+            (3 + (3 << 16), 1),
+            (3 + (4 << 16), 1)
+        ]
+    );
+
+    // removing keys until the bin is small again turns it back into a list
+    for i in 0..10 {
+        map.remove(&(3 + (i << 32)), &guard);
+    }
+    assert_eq!(map.tree_bins(&guard).count(), 0);
+}
+
+#[test]
+fn summary() {
+    let summary = HashMap::<u64, u64>::new().summary();
+    assert_eq!(summary.entries(), 0);
+    assert_eq!(summary.bucket_count(), 0);
+    assert_eq!(summary.capacity(), 0);
+    assert_eq!(summary.occupied_bins(), 0);
+    assert_eq!(summary.load(), 0.0);
+
+    let map = HashMap::<u64, u64, _>::with_capacity_and_hasher(1 << 8, LowBits::default());
+
+    let guard = map.guard();
+    for i in 0..10 {
+        map.insert(i << 32, i, &guard);
+    }
+    for i in 1..=54 {
+        map.insert(i, i, &guard);
+    }
+    let summary = map.summary();
+    assert_eq!(summary.entries(), 64);
+    assert_eq!(summary.bucket_count(), map.bucket_count(&guard));
+    assert_eq!(summary.load(), 64.0 / map.bucket_count(&guard) as f64);
+    assert_eq!(summary.capacity(), map.capacity(&guard));
+    assert_eq!(summary.capacity(), 383);
+    // the ten keys that share a hash are all in one bucket
+    assert_eq!(summary.occupied_bins(), 55);
+    let trees = if cfg!(feature = "unordered-keys") {
+        0
+    } else {
+        1
+    };
+    assert_eq!(summary.tree_bins(), trees);
+    assert_eq!(summary.garbage().is_some(), cfg!(feature = "garbage-stats"));
+    assert!(summary
+        .to_string()
+        .starts_with(&format!("64 entries in {} buckets", summary.bucket_count())));
+    assert_eq!(map.pin().summary(), summary);
+}
+
+#[test]
+fn table_stats() {
+    let map = HashMap::<u64, u64>::new();
+    let guard = map.guard();
+    let stats = map.table_stats(&guard);
+    assert_eq!(stats.bin_count(), 0);
+    assert_eq!(stats.entries(), 0);
+    assert_eq!(stats.resize_stride(), 0);
+    assert!(stats.chain_lengths().is_empty());
+    assert!(stats.stride_entries().is_empty());
+
+    let map = HashMap::<u64, u64, _>::with_capacity_and_hasher(1 << 8, LowBits::default());
+    let guard = map.guard();
+    // three keys in bin 1, two in bin 2, and one in each of bins 3 through 7
+    for i in 0..3 {
+        map.insert(1 + (i << 32), i, &guard);
+    }
+    for i in 0..2 {
+        map.insert(2 + (i << 32), i, &guard);
+    }
+    for i in 3..8 {
+        map.insert(i, i, &guard);
+    }
+
+    let stats = map.table_stats(&guard);
+    let bins = map.bucket_count(&guard);
+    assert_eq!(stats.bin_count(), bins);
+    assert_eq!(stats.entries(), 10);
+    assert_eq!(stats.chain_lengths(), &[bins - 7, 5, 1, 1]);
+    assert_eq!(stats.max_chain_length(), 3);
+    assert_eq!(stats.tree_bins(), 0);
+    assert_eq!(stats.moved_bins(), 0);
+    assert!(stats.resize_stride() >= 16);
+    assert_eq!(stats.stride_entries().iter().sum::<usize>(), 10);
+    assert_eq!(
+        stats.stride_entries().len(),
+        (bins - 1) / stats.resize_stride() + 1
+    );
+    // all the keys are in the first bins, which the last stride of a resize covers
+    assert_eq!(stats.stride_entries()[0], 10);
+    assert_eq!(map.pin().table_stats(), stats);
+}
+
+#[test]
+#[cfg(not(feature = "unordered-keys"))]
+fn table_stats_tree_depths() {
+    let map = HashMap::<u64, u64, _>::with_capacity_and_hasher(1 << 8, LowBits::default());
+    let guard = map.guard();
+    for i in 0..64 {
+        map.insert(5 + (i << 32), i, &guard);
+    }
+    map.insert(6, 6, &guard);
+
+    let stats = map.table_stats(&guard);
+    assert_eq!(stats.entries(), 65);
+    assert_eq!(stats.tree_bins(), 1);
+    assert_eq!(stats.chain_lengths()[1], 1);
+    assert_eq!(stats.max_chain_length(), 1);
+    // a red-black tree of 64 nodes is at least 7 and at most 12 levels deep
+    let depth = stats.tree_depths().len() - 1;
+    assert!((7..=12).contains(&depth), "depth {}", depth);
+    assert_eq!(stats.tree_depths()[depth], 1);
+}
+
+#[test]
+fn memory_usage() {
+    let map = HashMap::<u64, String>::new();
+    let guard = map.guard();
+    let empty = map.memory_usage(&guard);
+    assert_eq!(empty.table(), 0);
+    assert_eq!(empty.nodes(), 0);
+    assert_eq!(empty.garbage().is_some(), cfg!(feature = "garbage-stats"));
+
+    let map = HashMap::<u64, String>::with_capacity(100);
+    let guard = map.guard();
+    for i in 0..50 {
+        map.insert(i, i.to_string(), &guard);
+    }
+    let half = map.memory_usage(&guard);
+    for i in 50..100 {
+        map.insert(i, i.to_string(), &guard);
+    }
+    let full = map.memory_usage(&guard);
+
+    // the table has not grown, and every entry takes up the same space
+    assert_eq!(full.table(), half.table());
+    assert!(full.table() >= map.bucket_count(&guard) * std::mem::size_of::<usize>());
+    assert_eq!(full.nodes(), 2 * half.nodes());
+    assert!(full.nodes() >= 100 * std::mem::size_of::<(u64, String)>());
+    assert_eq!(full.contents(), 0);
+
+    let with_strings = map.memory_usage_with(&guard, |_, v| v.len());
+    let digits: usize = (0..100).map(|i: u64| i.to_string().len()).sum();
+    assert_eq!(with_strings.contents(), digits);
+    assert_eq!(with_strings.total(), full.total() + digits);
+    assert_eq!(map.pin().memory_usage(), full);
+}
+
+#[test]
+#[cfg(feature = "garbage-stats")]
+fn memory_usage_garbage() {
+    let map = HashMap::<u64, u64>::new();
+    {
+        let guard = map.guard();
+        map.insert(1, 1, &guard);
+        let before = map.memory_usage(&guard).garbage().unwrap();
+        // replacing the value retires the old one, which our guard keeps from being reclaimed
+        map.insert(1, 2, &guard);
+        let after = map.memory_usage(&guard).garbage().unwrap();
+        assert!(after >= before + std::mem::size_of::<u64>());
+    }
+    map.flush_garbage();
+}
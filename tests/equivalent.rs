@@ -0,0 +1,125 @@
+use flurry::*;
+use std::cmp::Ordering;
+use std::hash::{BuildHasherDefault, Hasher};
+
+// These tests all use `K = (String, String)` and `Q = Pair`, which `K` cannot be borrowed as
+
+#[derive(Hash)]
+struct Pair<'a>(&'a str, &'a str);
+
+impl Equivalent<(String, String)> for Pair<'_> {
+    fn equivalent(&self, key: &(String, String)) -> bool {
+        self.0 == key.0 && self.1 == key.1
+    }
+}
+
+impl Comparable<(String, String)> for Pair<'_> {
+    fn compare(&self, key: &(String, String)) -> Ordering {
+        (self.0, self.1).cmp(&(key.0.as_str(), key.1.as_str()))
+    }
+}
+
+fn key(a: &str, b: &str) -> (String, String) {
+    (a.to_string(), b.to_string())
+}
+
+#[derive(Default)]
+struct ZeroHasher;
+
+impl Hasher for ZeroHasher {
+    fn finish(&self) -> u64 {
+        0
+    }
+    fn write(&mut self, _: &[u8]) {}
+}
+
+#[test]
+fn get_and_remove() {
+    let map = HashMap::new();
+    let guard = map.guard();
+    map.insert(key("grace", "hopper"), 1906, &guard);
+    map.insert(key("ada", "lovelace"), 1815, &guard);
+
+    assert_eq!(map.get(&Pair("grace", "hopper"), &guard), Some(&1906));
+    assert!(map.contains_key(&Pair("ada", "lovelace"), &guard));
+    assert!(!map.contains_key(&Pair("ada", "hopper"), &guard));
+    assert_eq!(
+        map.get_key_value(&Pair("ada", "lovelace"), &guard),
+        Some((&key("ada", "lovelace"), &1815))
+    );
+
+    assert_eq!(map.remove(&Pair("grace", "hopper"), &guard), Some(&1906));
+    assert_eq!(map.remove(&Pair("grace", "hopper"), &guard), None);
+    assert_eq!(
+        map.remove_entry(&Pair("ada", "lovelace"), &guard),
+        Some((&key("ada", "lovelace"), &1815))
+    );
+    assert!(map.is_empty());
+
+    // borrowed forms of the key still work as before
+    map.insert(key("alan", "turing"), 1912, &guard);
+    assert_eq!(map.get(&key("alan", "turing"), &guard), Some(&1912));
+}
+
+#[test]
+fn tree_bins() {
+    let map = HashMap::with_hasher(BuildHasherDefault::<ZeroHasher>::default());
+    let guard = map.guard();
+    let names: Vec<_> = (0..32).map(|i| i.to_string()).collect();
+    for (i, name) in names.iter().enumerate() {
+        map.insert(key("n", name), i, &guard);
+    }
+    let trees = if cfg!(feature = "unordered-keys") {
+        0
+    } else {
+        1
+    };
+    assert_eq!(map.tree_bins(&guard).count(), trees);
+
+    for (i, name) in names.iter().enumerate() {
+        assert_eq!(map.get(&Pair("n", name), &guard), Some(&i));
+        assert!(!map.contains_key(&Pair("m", name), &guard));
+    }
+    for (i, name) in names.iter().enumerate().step_by(2) {
+        assert_eq!(map.remove(&Pair("n", name), &guard), Some(&i));
+    }
+    assert_eq!(map.len(), 16);
+    for (i, name) in names.iter().enumerate() {
+        let expected = if i % 2 == 0 { None } else { Some(&i) };
+        assert_eq!(map.get(&Pair("n", name), &guard), expected);
+    }
+}
+
+#[test]
+fn map_ref() {
+    let map = HashMap::new();
+    let map = map.pin();
+    map.insert(key("grace", "hopper"), 1906);
+    assert_eq!(map.get(&Pair("grace", "hopper")), Some(&1906));
+    assert!(map.contains_key(&Pair("grace", "hopper")));
+    assert_eq!(map.remove(&Pair("grace", "hopper")), Some(&1906));
+    assert!(map.is_empty());
+}
+
+#[test]
+fn set() {
+    let set = HashSet::new();
+    let guard = set.guard();
+    set.insert(key("grace", "hopper"), &guard);
+    assert!(set.contains(&Pair("grace", "hopper"), &guard));
+    assert_eq!(
+        set.get(&Pair("grace", "hopper"), &guard),
+        Some(&key("grace", "hopper"))
+    );
+    assert_eq!(
+        set.take(&Pair("grace", "hopper"), &guard),
+        Some(&key("grace", "hopper"))
+    );
+    assert!(!set.remove(&Pair("grace", "hopper"), &guard));
+    assert!(set.is_empty());
+
+    let set = set.pin();
+    set.insert(key("ada", "lovelace"));
+    assert!(set.contains(&Pair("ada", "lovelace")));
+    assert!(set.remove(&Pair("ada", "lovelace")));
+}
@@ -0,0 +1,121 @@
+use flurry::events::{Event, EventStream};
+use flurry::*;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn keys_only() {
+    let events = Arc::new(EventStream::new(16));
+    let map = HashMap::new().with_events(Arc::clone(&events));
+    let guard = map.guard();
+    map.insert(1, String::from("a"), &guard);
+    map.compute_if_present(&1, |_, v| Some(v.clone() + "b"), &guard);
+    map.insert(2, String::from("c"), &guard);
+    map.clear(&guard);
+
+    let mut received = events.drain();
+    assert_eq!(
+        received[..2],
+        [
+            Event::Inserted {
+                key: 1,
+                value: None
+            },
+            Event::Updated {
+                key: 1,
+                value: None
+            },
+        ]
+    );
+    assert_eq!(
+        received[2],
+        Event::Inserted {
+            key: 2,
+            value: None
+        }
+    );
+    // clear removes bins in no particular order
+    let mut removed: Vec<_> = received.drain(3..).map(|e| *e.key()).collect();
+    removed.sort();
+    assert_eq!(removed, [1, 2]);
+    assert!(events.is_empty());
+}
+
+#[test]
+fn lent_values() {
+    let events = Arc::new(EventStream::with_values(16));
+    let mut map = HashMap::new().with_events(Arc::clone(&events));
+    map.insert(1, 1, &map.guard());
+    *map.get_mut(&1).unwrap() += 1;
+    assert_eq!(
+        events.drain(),
+        vec![
+            Event::Inserted {
+                key: 1,
+                value: Some(1)
+            },
+            Event::Updated {
+                key: 1,
+                value: None
+            },
+        ]
+    );
+}
+
+#[test]
+fn bounded() {
+    let events = Arc::new(EventStream::with_values(4));
+    let map = HashMap::new().with_events(Arc::clone(&events));
+    let guard = map.guard();
+    for i in 0..10 {
+        map.insert(i, i, &guard);
+    }
+    assert_eq!(events.len(), 4);
+    assert_eq!(events.dropped(), 6);
+    // the oldest events are kept
+    assert_eq!(
+        events.try_recv(),
+        Some(Event::Inserted {
+            key: 0,
+            value: Some(0)
+        })
+    );
+    map.insert(10, 10, &guard);
+    assert_eq!(events.len(), 4);
+    assert_eq!(events.dropped(), 6);
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn concurrent_consumer() {
+    const N: usize = 256;
+    let events = Arc::new(EventStream::with_values(2 * N));
+    let map = Arc::new(HashMap::new().with_events(Arc::clone(&events)));
+    let writer = {
+        let map = Arc::clone(&map);
+        thread::spawn(move || {
+            for i in 0..N {
+                map.pin().insert(i, i);
+                map.pin().remove(&i);
+            }
+        })
+    };
+
+    let mut inserted = 0;
+    let mut removed = 0;
+    while inserted + removed < 2 * N {
+        match events.recv() {
+            Event::Inserted { key, value } => {
+                assert_eq!(value, Some(key));
+                inserted += 1;
+            }
+            Event::Removed { .. } => removed += 1,
+            Event::Updated { .. } => panic!("nothing was updated"),
+        }
+    }
+    writer.join().unwrap();
+    assert_eq!((inserted, removed), (N, N));
+    assert_eq!(events.recv_timeout(Duration::from_millis(10)), None);
+    assert_eq!(events.dropped(), 0);
+}
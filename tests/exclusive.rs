@@ -0,0 +1,124 @@
+use flurry::*;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::sync::Arc;
+
+#[derive(Default)]
+struct ZeroHasher;
+
+impl Hasher for ZeroHasher {
+    fn finish(&self) -> u64 {
+        0
+    }
+    fn write(&mut self, _: &[u8]) {}
+}
+
+#[test]
+fn get_mut() {
+    let mut map = HashMap::new();
+    map.pin().insert(1, vec![1]);
+    map.get_mut(&1).unwrap().push(2);
+    assert_eq!(map.get_mut(&2), None);
+    assert_eq!(map.pin().get(&1), Some(&vec![1, 2]));
+}
+
+#[test]
+fn iter_mut_after_resize() {
+    let mut map = HashMap::new();
+    for i in 0..1024 {
+        map.insert_mut(i, i);
+    }
+    for (k, v) in map.iter_mut() {
+        *v += *k;
+    }
+    for v in map.values_mut() {
+        *v += 1;
+    }
+    assert_eq!(map.iter_mut().count(), 1024);
+    let guard = map.guard();
+    for i in 0..1024 {
+        assert_eq!(map.get(&i, &guard), Some(&(2 * i + 1)));
+    }
+}
+
+#[test]
+fn insert_and_remove_mut() {
+    let mut map = HashMap::new();
+    assert_eq!(map.insert_mut(1, String::from("a")), None);
+    assert_eq!(
+        map.insert_mut(1, String::from("b")),
+        Some(String::from("a"))
+    );
+    assert_eq!(map.insert_mut(2, String::from("c")), None);
+    assert_eq!(map.len(), 2);
+
+    assert_eq!(map.remove_mut(&1), Some(String::from("b")));
+    assert_eq!(map.remove_mut(&1), None);
+    assert_eq!(map.len(), 1);
+    assert_eq!(map.pin().get(&2).map(String::as_str), Some("c"));
+}
+
+#[test]
+fn tree_bins() {
+    let mut map =
+        HashMap::<usize, usize, _>::with_hasher(BuildHasherDefault::<ZeroHasher>::default());
+    for i in 0..64 {
+        assert_eq!(map.insert_mut(i, i), None);
+    }
+    for i in 0..64 {
+        assert_eq!(map.insert_mut(i, i * 2), Some(i));
+        *map.get_mut(&i).unwrap() += 1;
+    }
+    for v in map.values_mut() {
+        *v += 1;
+    }
+    for i in (0..64).step_by(2) {
+        assert_eq!(map.remove_mut(&i), Some(i * 2 + 2));
+    }
+    assert_eq!(map.len(), 32);
+    let guard = map.guard();
+    for i in 0..64 {
+        let expected = if i % 2 == 0 { None } else { Some(&(i * 2 + 2)) };
+        assert_eq!(map.get(&i, &guard), expected);
+    }
+    drop(guard);
+
+    // removing the rest turns the bin back into a list, and then empties it from the front
+    for i in (1..64).step_by(2) {
+        assert_eq!(map.remove_mut(&i), Some(i * 2 + 2));
+    }
+    assert!(map.is_empty());
+}
+
+#[test]
+fn values_are_moved_out() {
+    let value = Arc::new(());
+    let mut map = HashMap::new();
+    map.insert_mut(1, Arc::clone(&value));
+    let replaced = map.insert_mut(1, Arc::clone(&value)).unwrap();
+    let removed = map.remove_mut(&1).unwrap();
+    assert!(map.is_empty());
+    drop(map);
+
+    // neither value was dropped along with the map's garbage
+    assert_eq!(Arc::strong_count(&value), 3);
+    drop((replaced, removed));
+    assert_eq!(Arc::strong_count(&value), 1);
+}
+
+#[test]
+fn mutations_move_versions() {
+    let mut map = HashMap::new().with_entry_versions();
+    map.insert_mut(1, 1);
+    let (_, before) = map.pin().get_versioned(&1).unwrap();
+    *map.get_mut(&1).unwrap() += 1;
+    let (_, after) = map.pin().get_versioned(&1).unwrap();
+    assert!(after > before);
+
+    for v in map.values_mut() {
+        *v += 1;
+    }
+    let map = map.pin();
+    let (value, last) = map.get_versioned(&1).unwrap();
+    assert_eq!(*value, 3);
+    assert!(last > after);
+}
@@ -0,0 +1,100 @@
+#![cfg(feature = "failpoints")]
+
+use flurry::failpoints::{self, Action};
+use flurry::*;
+use std::panic;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+// fail points are process-global, so all scenarios live in one test to keep them from interfering
+
+#[test]
+fn fail_points() {
+    // a panicking allocation surfaces to the caller
+    failpoints::set("table-alloc", Action::Panic);
+    let map = HashMap::<usize, usize>::new();
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        map.insert(1, 1, &map.guard());
+    }));
+    assert!(result.is_err());
+    assert_eq!(failpoints::hits("table-alloc"), 1);
+    failpoints::remove("table-alloc");
+
+    // once removed, the same map works as usual
+    map.insert(1, 1, &map.guard());
+    assert_eq!(map.get(&1, &map.guard()), Some(&1));
+
+    // the same goes for a panicking allocation during a resize
+    let resized = HashMap::<usize, usize>::with_capacity(8);
+    let bins = resized.bucket_count(&resized.guard());
+    failpoints::set("table-alloc", Action::Panic);
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        let guard = resized.guard();
+        for i in 0..bins {
+            resized.insert(i, i, &guard);
+        }
+    }));
+    assert!(result.is_err());
+    failpoints::remove("table-alloc");
+    {
+        let guard = resized.guard();
+        for i in 0..4 * bins {
+            resized.insert(i, i, &guard);
+        }
+        assert!(resized.bucket_count(&guard) > bins);
+        assert_eq!(resized.len(), 4 * bins);
+        assert_eq!(resized.get(&(bins / 2), &guard), Some(&(bins / 2)));
+    }
+
+    // callbacks run at every hit
+    let counted = Arc::new(AtomicUsize::new(0));
+    let c = Arc::clone(&counted);
+    failpoints::set(
+        "count-before",
+        Action::Callback(Arc::new(move || {
+            c.fetch_add(1, Ordering::SeqCst);
+        })),
+    );
+    {
+        let guard = map.guard();
+        map.insert(2, 2, &guard);
+        map.remove(&1, &guard);
+    }
+    assert_eq!(counted.load(Ordering::SeqCst), 2);
+    assert_eq!(failpoints::hits("count-before"), 2);
+
+    // clearing resets everything
+    failpoints::clear();
+    map.insert(3, 3, &map.guard());
+    assert_eq!(counted.load(Ordering::SeqCst), 2);
+    assert_eq!(failpoints::hits("count-before"), 0);
+
+    // a failed allocation makes try_reserve back out, and leaves the map usable
+    failpoints::set("table-alloc", Action::Fail);
+    let map = HashMap::<usize, usize>::new();
+    {
+        let guard = map.guard();
+        match map.try_reserve(64, &guard) {
+            Err(TryReserveError::AllocError { .. }) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+        assert_eq!(map.bucket_count(&guard), 0);
+        // other operations ignore the action, so this initializes the table as usual
+        map.insert(1, 1, &guard);
+        let buckets = map.bucket_count(&guard);
+        match map.try_reserve(1000, &guard) {
+            Err(TryReserveError::AllocError { .. }) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+        assert_eq!(map.bucket_count(&guard), buckets);
+        for i in 2..100 {
+            map.insert(i, i, &guard);
+        }
+        assert_eq!(map.len(), 99);
+        assert!(map.bucket_count(&guard) > buckets);
+    }
+    failpoints::remove("table-alloc");
+    let guard = map.guard();
+    assert_eq!(map.try_reserve(1000, &guard), Ok(()));
+    assert!(map.bucket_count(&guard) >= 1099);
+}
@@ -0,0 +1,37 @@
+use flurry::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+// flushing relies on no other thread holding up the global epoch, so this file has only one test
+
+struct Tracked(Arc<AtomicUsize>);
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn flush_garbage_frees_removed_values() {
+    let dropped = Arc::new(AtomicUsize::new(0));
+    let map = HashMap::new();
+    {
+        let guard = map.guard();
+        for i in 0..64 {
+            map.insert(i, Tracked(Arc::clone(&dropped)), &guard);
+        }
+        for i in 0..32 {
+            map.remove(&i, &guard);
+        }
+        map.insert(32, Tracked(Arc::clone(&dropped)), &guard);
+    }
+
+    map.flush_garbage();
+    assert_eq!(dropped.load(Ordering::SeqCst), 33);
+
+    // the remaining values are still there
+    assert_eq!(map.len(), 32);
+    drop(map);
+    assert_eq!(dropped.load(Ordering::SeqCst), 65);
+}
@@ -0,0 +1,41 @@
+#![cfg(feature = "garbage-stats")]
+
+use flurry::epoch::Collector;
+use flurry::*;
+
+#[test]
+fn counts_retired_and_reclaimed() {
+    // the map has a collector of its own, so that guards of other tests do not hold it up
+    let collector = Collector::new();
+    let map = HashMap::<u64, u64>::new().with_collector(&collector);
+    assert_eq!(map.garbage_stats(), Default::default());
+
+    let guard = collector.guard();
+    for i in 0..32 {
+        map.insert(i, i, &guard);
+    }
+    let inserted = map.garbage_stats();
+    for i in 0..32 {
+        map.remove(&i, &guard);
+    }
+
+    // each removal retires a node and its value, which our guard holds up
+    let removed = map.garbage_stats().since(&inserted);
+    assert_eq!(removed.retired(), 64);
+    assert_eq!(removed.reclaimed(), 0);
+    assert!(removed.retired_bytes() >= 32 * 2 * std::mem::size_of::<u64>());
+    let stats = map.garbage_stats();
+    assert!(stats.queued() >= 64);
+    assert_eq!(
+        stats.queued_bytes(),
+        map.memory_usage(&guard).garbage().unwrap()
+    );
+
+    drop(guard);
+    map.flush_garbage();
+    let stats = map.garbage_stats();
+    assert_eq!(stats.queued(), 0);
+    assert_eq!(stats.queued_bytes(), 0);
+    assert_eq!(stats.reclaimed(), stats.retired());
+    assert_eq!(map.pin().garbage_stats(), stats);
+}
@@ -0,0 +1,41 @@
+use flurry::*;
+use std::thread;
+#[cfg(feature = "guard-ages")]
+use std::time::Duration;
+
+// guards are counted across all maps that share the default collector, so this file has only one
+// test
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn active_guards() {
+    let map = HashMap::<usize, usize>::new();
+    let set = HashSet::<usize>::new();
+    assert_eq!(map.active_guards(), 0);
+    #[cfg(feature = "guard-ages")]
+    assert_eq!(map.oldest_guard_age(), None);
+
+    let guard = map.guard();
+    let set_guard = set.pin();
+    let other = thread::spawn(|| {
+        let map = HashMap::<usize, usize>::new();
+        let _guard = map.guard();
+        map.active_guards()
+    });
+    assert_eq!(other.join().unwrap(), 3);
+    assert_eq!(map.active_guards(), 2);
+
+    #[cfg(feature = "guard-ages")]
+    {
+        thread::sleep(Duration::from_millis(20));
+        assert!(map.oldest_guard_age().unwrap() >= Duration::from_millis(20));
+    }
+
+    drop(set_guard);
+    assert_eq!(map.active_guards(), 1);
+    let inner = guard.into_inner();
+    assert_eq!(map.active_guards(), 0);
+    #[cfg(feature = "guard-ages")]
+    assert_eq!(map.oldest_guard_age(), None);
+    map.insert(1, 1, &inner);
+}
@@ -1,5 +1,5 @@
 use crossbeam_epoch as epoch;
-use flurry::{DefaultHashBuilder, HashMap};
+use flurry::{DefaultHashBuilder, Fmix64, HashMap, HashSet};
 use std::hash::{BuildHasher, BuildHasherDefault, Hasher};
 
 #[derive(Default)]
@@ -49,6 +49,102 @@ fn test_zero_hasher() {
     check::<BuildHasherDefault<ZeroHasher>>();
 }
 
+#[test]
+fn test_zero_hasher_get_or_insert_with() {
+    // every key ends up in the same bin, which is turned into a tree
+    let guard = epoch::pin();
+    let map = HashMap::<i32, i32, _>::with_hasher(BuildHasherDefault::<ZeroHasher>::default());
+    for i in 0..32 {
+        assert_eq!(map.get_or_insert_with(i, || i * 10, &guard), &(i * 10));
+    }
+    for i in 0..32 {
+        assert_eq!(
+            map.get_or_insert_with(i, || unreachable!(), &guard),
+            &(i * 10)
+        );
+    }
+    assert_eq!(map.len(), 32);
+}
+
+#[test]
+fn test_zero_hasher_remove_if() {
+    let guard = epoch::pin();
+    let map = HashMap::<i32, i32, _>::with_hasher(BuildHasherDefault::<ZeroHasher>::default());
+    for i in 0..16 {
+        map.insert(i, i, &guard);
+    }
+    assert_eq!(map.remove_if(&7, |&v| v == 0, &guard), None);
+    assert_eq!(map.remove_if(&7, |&v| v == 7, &guard), Some(&7));
+    assert_eq!(map.remove_if(&7, |_| unreachable!(), &guard), None);
+    assert_eq!(map.len(), 15);
+}
+
+#[test]
+fn test_zero_hasher_into_values() {
+    let map = HashMap::<i32, String, _>::with_hasher(BuildHasherDefault::<ZeroHasher>::default());
+    {
+        let guard = epoch::pin();
+        for i in 0..32 {
+            map.insert(i, i.to_string(), &guard);
+        }
+    }
+    let mut values: Vec<_> = map.into_values().collect();
+    values.sort_by_key(|v| v.parse::<i32>().unwrap());
+    assert_eq!(values, (0..32).map(|i| i.to_string()).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_zero_hasher_clone() {
+    let guard = epoch::pin();
+    let map = HashMap::<i32, i32, _>::with_capacity_and_hasher(
+        256,
+        BuildHasherDefault::<ZeroHasher>::default(),
+    );
+    for i in 0..32 {
+        map.insert(i, i * 10, &guard);
+    }
+    let cloned_map = map.clone();
+    assert_eq!(cloned_map, map);
+    // the clone turns the colliding bin into a tree as well, unless keys are unordered
+    let trees = if cfg!(feature = "unordered-keys") {
+        0
+    } else {
+        1
+    };
+    assert_eq!(cloned_map.tree_bins(&guard).count(), trees);
+    for i in 0..32 {
+        assert_eq!(cloned_map.get(&i, &guard), Some(&(i * 10)));
+    }
+}
+
+#[test]
+fn test_zero_hasher_shrink() {
+    let guard = epoch::pin();
+    let map = HashMap::<i32, i32, _>::with_capacity_and_hasher(
+        4096,
+        BuildHasherDefault::<ZeroHasher>::default(),
+    );
+    for i in 0..256 {
+        map.insert(i, i, &guard);
+    }
+    for i in 64..256 {
+        map.remove(&i, &guard);
+    }
+    map.shrink_to_fit(&guard);
+    assert_eq!(map.bucket_count(&guard), 128);
+    // the bin with all the entries is still a tree, unless keys are unordered
+    let trees = if cfg!(feature = "unordered-keys") {
+        0
+    } else {
+        1
+    };
+    assert_eq!(map.tree_bins(&guard).count(), trees);
+    for i in 0..64 {
+        assert_eq!(map.get(&i, &guard), Some(&i));
+    }
+    assert_eq!(map.iter(&guard).count(), 64);
+}
+
 #[test]
 fn test_max_hasher() {
     #[derive(Default)]
@@ -63,3 +159,119 @@ fn test_max_hasher() {
 
     check::<BuildHasherDefault<MaxHasher>>();
 }
+
+#[test]
+fn test_seeded_default_hasher() {
+    let hash = |s: &DefaultHashBuilder| {
+        let mut h = s.build_hasher();
+        h.write_u64(42);
+        h.finish()
+    };
+
+    let a = DefaultHashBuilder::with_seeds(1, 2);
+    let b = DefaultHashBuilder::with_seeds(1, 2);
+    assert_eq!(a.seeds(), (1, 2));
+    assert_eq!(hash(&a), hash(&b));
+    assert_ne!(hash(&a), hash(&DefaultHashBuilder::with_seeds(2, 1)));
+
+    // random seeds differ between builders, but can be used to recreate a builder
+    let random = DefaultHashBuilder::new();
+    assert_ne!(random.seeds(), DefaultHashBuilder::new().seeds());
+    let (k0, k1) = random.seeds();
+    assert_eq!(hash(&random), hash(&DefaultHashBuilder::with_seeds(k0, k1)));
+
+    let map = HashMap::<i32, i32>::with_hasher(DefaultHashBuilder::with_seeds(3, 4));
+    assert_eq!(map.hasher().seeds(), (3, 4));
+}
+
+/// Passes integer keys through as their hash.
+#[derive(Default)]
+struct IdentityHasher(u64);
+
+impl Hasher for IdentityHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+    fn write(&mut self, _: &[u8]) {
+        unimplemented!("only used with u64 keys")
+    }
+    fn write_u64(&mut self, n: u64) {
+        self.0 = n;
+    }
+}
+
+type IdentityMap = HashMap<u64, u64, BuildHasherDefault<IdentityHasher>>;
+
+#[test]
+fn hash_spreader_spreads_high_bits() {
+    // without a spreader, keys that only differ in their high bits all share the first bin
+    {
+        let map = IdentityMap::default();
+        let guard = map.guard();
+        for i in 0..64 {
+            map.insert(i << 32, i, &guard);
+        }
+        assert_eq!(map.bucket_index(&(5 << 32), &guard), Some(0));
+        let stats = map.table_stats(&guard);
+        assert_eq!(stats.max_chain_length() + 64 * stats.tree_bins(), 64);
+    }
+
+    let map = IdentityMap::default().with_hash_spreader(Fmix64);
+    let guard = map.guard();
+    for i in 0..64 {
+        map.insert(i << 32, i, &guard);
+    }
+    let stats = map.table_stats(&guard);
+    assert_eq!(stats.tree_bins(), 0);
+    assert!(stats.max_chain_length() < 8);
+    for i in 0..64 {
+        assert_eq!(map.get(&(i << 32), &guard), Some(&i));
+    }
+    assert_eq!(map.remove(&(3 << 32), &guard), Some(&3));
+    assert!(!map.contains_key(&(3 << 32), &guard));
+
+    // clones keep the spreader, so their entries are found just the same
+    let clone = map.clone();
+    let clone_guard = clone.guard();
+    assert_eq!(clone.get(&(5 << 32), &clone_guard), Some(&5));
+    assert_eq!(
+        clone.bucket_index(&(5 << 32), &clone_guard),
+        map.bucket_index(&(5 << 32), &guard)
+    );
+}
+
+#[test]
+fn hash_spreader_closure_and_raw_entry() {
+    // a spreader that sends every key to the last bin
+    let map = IdentityMap::default().with_hash_spreader(|_: u64| u64::MAX);
+    let guard = map.guard();
+    map.insert(1, 1, &guard);
+    let bins = map.bucket_count(&guard);
+    assert_eq!(map.bucket_index(&1, &guard), Some(bins - 1));
+
+    // the raw entry API takes the hasher's hashes, and spreads them like any other
+    map.raw_entry_mut(&guard).insert_hashed_nocheck(2, 2, 20);
+    assert_eq!(map.get(&2, &guard), Some(&20));
+    assert_eq!(
+        map.raw_entry(&guard).from_key_hashed_nocheck(1, &1),
+        Some((&1, &1))
+    );
+    assert_eq!(
+        map.raw_entry_mut(&guard).remove_hashed_nocheck(2, &2),
+        Some((&2, &20))
+    );
+    assert!(!map.contains_key(&2, &guard));
+
+    let set =
+        HashSet::<u64, BuildHasherDefault<IdentityHasher>>::default().with_hash_spreader(Fmix64);
+    assert!(set.pin().insert(1 << 40));
+    assert!(set.pin().contains(&(1 << 40)));
+}
+
+#[test]
+#[should_panic(expected = "before any entries are inserted")]
+fn hash_spreader_on_non_empty_map() {
+    let map = IdentityMap::default();
+    map.pin().insert(1, 1);
+    let _ = map.with_hash_spreader(Fmix64);
+}
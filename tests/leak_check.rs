@@ -0,0 +1,57 @@
+#![cfg(feature = "leak-check")]
+
+use flurry::*;
+use std::panic;
+use std::sync::mpsc;
+use std::thread;
+
+// reclamation depends on no other thread holding up the global epoch, so this file has only one
+// test
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn leak_check() {
+    // garbage is reclaimed when the map is dropped
+    let map = HashMap::new();
+    {
+        let guard = map.guard();
+        for i in 0..64 {
+            map.insert(i, i, &guard);
+        }
+        for i in 0..64 {
+            map.remove(&i, &guard);
+        }
+    }
+    drop(map);
+
+    // but not if some thread stays pinned
+    let (pinned_tx, pinned_rx) = mpsc::channel();
+    let (release_tx, release_rx) = mpsc::channel::<()>();
+    let pinned = thread::spawn(move || {
+        let other = HashMap::<usize, usize>::new();
+        let guard = other.guard();
+        pinned_tx.send(()).unwrap();
+        let _ = release_rx.recv();
+        drop(guard);
+    });
+    pinned_rx.recv().unwrap();
+
+    let map = HashMap::new();
+    {
+        let guard = map.guard();
+        map.insert(1, 1, &guard);
+        map.insert(1, 2, &guard);
+    }
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(move || drop(map)));
+    let message = result.unwrap_err();
+    let message = message.downcast_ref::<String>().unwrap();
+    assert!(message.contains("1 retired objects"), "{}", message);
+    assert!(
+        message.contains("1 guards obtained from flurry"),
+        "{}",
+        message
+    );
+
+    release_tx.send(()).unwrap();
+    pinned.join().unwrap();
+}
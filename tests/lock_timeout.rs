@@ -0,0 +1,158 @@
+use flurry::*;
+use std::hash::BuildHasherDefault;
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+#[derive(Default)]
+struct ZeroHasher;
+
+impl std::hash::Hasher for ZeroHasher {
+    fn finish(&self) -> u64 {
+        0
+    }
+    fn write(&mut self, _: &[u8]) {}
+}
+
+type Map = HashMap<usize, usize, BuildHasherDefault<ZeroHasher>>;
+
+const TIMEOUT: Duration = Duration::from_millis(20);
+
+/// Holds the lock of the bin containing `key` until the returned sender is used (or dropped).
+fn hold_bin(map: &Arc<Map>, key: usize) -> (mpsc::Sender<()>, thread::JoinHandle<()>) {
+    let (locked_tx, locked_rx) = mpsc::channel();
+    let (release_tx, release_rx) = mpsc::channel::<()>();
+    let map = Arc::clone(map);
+    let holder = thread::spawn(move || {
+        let guard = map.guard();
+        map.compute_if_present(
+            &key,
+            |_, v| {
+                locked_tx.send(()).unwrap();
+                let _ = release_rx.recv();
+                Some(*v)
+            },
+            &guard,
+        );
+    });
+    locked_rx.recv().unwrap();
+    (release_tx, holder)
+}
+
+fn times_out_while_bin_is_held(map: Arc<Map>) {
+    let (release, holder) = hold_bin(&map, 0);
+    {
+        let guard = map.guard();
+        let err = map.try_insert_for(1, 10, TIMEOUT, &guard).unwrap_err();
+        assert_eq!(err.timeout, TIMEOUT);
+        assert_eq!(err.into_inner(), (1, 10));
+
+        let err = map
+            .try_compute_for(
+                &1,
+                |_, _| panic!("called without the lock"),
+                TIMEOUT,
+                &guard,
+            )
+            .unwrap_err();
+        assert_eq!(err.timeout, TIMEOUT);
+        assert_eq!(map.get(&1, &guard), Some(&1));
+    }
+    release.send(()).unwrap();
+    holder.join().unwrap();
+
+    let guard = map.guard();
+    assert_eq!(
+        map.try_insert_for(1, 10, TIMEOUT, &guard).unwrap(),
+        Some(&1)
+    );
+    assert_eq!(
+        map.try_compute_for(&1, |_, v| Some(v + 1), TIMEOUT, &guard)
+            .unwrap(),
+        Some(&11)
+    );
+    assert_eq!(map.pin().try_insert_for(100, 100, TIMEOUT).unwrap(), None);
+    assert_eq!(
+        map.pin()
+            .try_compute_for(&100, |_, _| None, TIMEOUT)
+            .unwrap(),
+        None
+    );
+    assert_eq!(map.get(&100, &guard), None);
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn linear_bin() {
+    let map = Arc::new(Map::default());
+    map.pin().insert(0, 0);
+    map.pin().insert(1, 1);
+    times_out_while_bin_is_held(map);
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn tree_bin() {
+    let map = Arc::new(Map::with_capacity_and_hasher(128, Default::default()));
+    {
+        let guard = map.guard();
+        for i in 0..16 {
+            map.insert(i, i, &guard);
+        }
+    }
+    times_out_while_bin_is_held(map);
+}
+
+#[test]
+fn uncontended() {
+    let map = HashMap::new();
+    let guard = map.guard();
+    assert_eq!(map.try_insert_for(1, 1, TIMEOUT, &guard).unwrap(), None);
+    assert_eq!(
+        map.try_compute_for(&1, |_, v| Some(v * 2), TIMEOUT, &guard)
+            .unwrap(),
+        Some(&2)
+    );
+    assert_eq!(
+        map.try_compute_for(&3, |_, v| Some(v * 2), TIMEOUT, &guard)
+            .unwrap(),
+        None
+    );
+}
+
+#[test]
+fn error_display() {
+    let err = LockTimeoutError {
+        timeout: TIMEOUT,
+        not_applied: (1, 1),
+    };
+    assert!(err.to_string().contains("20ms"));
+    assert!(format!("{:?}", err).contains("LockTimeoutError"));
+}
+
+#[test]
+fn contended_bin_loses_no_updates() {
+    // all keys share one bin, so every update below contends for the same lock
+    let map = Arc::new(Map::default());
+    for key in 0..4 {
+        map.pin().insert(key, 0);
+    }
+    let threads: Vec<_> = (0..8)
+        .map(|t| {
+            let map = Arc::clone(&map);
+            thread::spawn(move || {
+                let guard = map.guard();
+                for _ in 0..1000 {
+                    map.compute_if_present(&(t % 4), |_, v| Some(v + 1), &guard);
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    let guard = map.guard();
+    for key in 0..4 {
+        assert_eq!(map.get(&key, &guard), Some(&2000));
+    }
+}
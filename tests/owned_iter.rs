@@ -0,0 +1,103 @@
+use flurry::iter::OwnedIter;
+use flurry::*;
+use std::sync::Arc;
+use std::thread;
+
+fn entries(map: &HashMap<usize, String>) -> OwnedIter<'_, usize, String> {
+    map.owned_iter()
+}
+
+struct Cursor<'m> {
+    entries: OwnedIter<'m, usize, String>,
+    seen: usize,
+}
+
+#[test]
+fn returned_from_function() {
+    let map = HashMap::new();
+    for i in 0..64 {
+        map.pin().insert(i, i.to_string());
+    }
+    let mut all: Vec<_> = entries(&map).collect();
+    all.sort();
+    assert_eq!(all.len(), 64);
+    assert!(all.iter().all(|(k, v)| k.to_string() == *v));
+}
+
+#[test]
+fn stored_in_struct() {
+    let map: HashMap<_, _> = (0..16).map(|i| (i, i.to_string())).collect();
+    let mut cursor = Cursor {
+        entries: map.owned_iter(),
+        seen: 0,
+    };
+    for (k, v) in cursor.entries.by_ref() {
+        assert_eq!(k.to_string(), v);
+        cursor.seen += 1;
+    }
+    assert_eq!(cursor.seen, 16);
+}
+
+#[test]
+fn entries_outlive_removal() {
+    let map: HashMap<_, _> = (0..16).map(|i| (i, i.to_string())).collect();
+    let mut iter = map.owned_iter();
+    let first = iter.next().unwrap();
+    {
+        let guard = map.guard();
+        for i in 0..16 {
+            map.remove(&i, &guard);
+        }
+    }
+    // the iterator's own guard keeps the table and nodes it is walking alive
+    assert!(iter.all(|(k, v)| k.to_string() == v));
+    assert_eq!(first.0.to_string(), first.1);
+    assert!(map.is_empty());
+}
+
+#[test]
+fn empty() {
+    let map = HashMap::<usize, String>::new();
+    assert_eq!(map.owned_iter().count(), 0);
+    drop(map.owned_iter());
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn concurrent_inserts() {
+    let map = Arc::new(HashMap::<usize, usize>::new());
+    let writer = {
+        let map = Arc::clone(&map);
+        thread::spawn(move || {
+            let guard = map.guard();
+            for i in 0..4096 {
+                map.insert(i, i, &guard);
+            }
+        })
+    };
+    for _ in 0..16 {
+        assert!(map.owned_iter().all(|(k, v)| k == v));
+    }
+    writer.join().unwrap();
+    assert_eq!(map.owned_iter().count(), 4096);
+}
+
+#[test]
+fn values_cloned() {
+    let map: HashMap<_, _> = (0..16).map(|i| (i, i.to_string())).collect();
+    let mut all: Vec<_> = map.values_cloned().collect();
+    all.sort_by_key(|v| v.parse::<usize>().unwrap());
+    assert_eq!(all, (0..16).map(|i| i.to_string()).collect::<Vec<_>>());
+
+    let mut values = map.values_cloned();
+    let first = values.next().unwrap();
+    {
+        let guard = map.guard();
+        for i in 0..16 {
+            map.remove(&i, &guard);
+        }
+    }
+    assert!(first.parse::<usize>().unwrap() < 16);
+    assert!(values.all(|v| v.parse::<usize>().unwrap() < 16));
+    assert_eq!(map.values_cloned().count(), 0);
+}
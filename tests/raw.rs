@@ -0,0 +1,116 @@
+#![cfg(feature = "raw")]
+
+use flurry::epoch::{self, Atomic, Owned, Shared};
+use flurry::raw::{BinEntry, Node, Table};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread;
+
+/// Inserts `key` at the head of its bin, or replaces its value if it is already there.
+fn insert(table: &Table<usize, usize>, key: usize, value: usize, guard: &epoch::Guard) {
+    let hash = key as u64;
+    let i = table.bini(hash);
+    loop {
+        let head = table.bin(i, guard);
+        if head.is_null() {
+            let node = Owned::new(BinEntry::Node(Node::new(hash, key, Owned::new(value))));
+            match table.cas_bin(i, head, node, guard) {
+                Ok(_) => return,
+                Err(e) => {
+                    // the node was never shared, so we still own its value
+                    let value = e.new.as_node().unwrap().value();
+                    drop(unsafe {
+                        value
+                            .swap(Shared::null(), Ordering::SeqCst, guard)
+                            .into_owned()
+                    });
+                    continue;
+                }
+            }
+        }
+
+        // safety: entries are never removed from the table
+        let head_node = unsafe { head.deref() }.as_node().unwrap();
+        let done = head_node.with_lock(|| {
+            if table.bin(i, guard) != head {
+                // another thread changed the head before we got the lock
+                return false;
+            }
+            // safety: entries are never removed from the table
+            match unsafe { table.get_node(hash, &key, guard) } {
+                Some(node) => {
+                    let old = node
+                        .value()
+                        .swap(Owned::new(value), Ordering::SeqCst, guard);
+                    // safety: the old value is no longer reachable for new readers
+                    unsafe { guard.defer_destroy(old) };
+                }
+                None => {
+                    let node = Node::with_next(hash, key, Owned::new(value), Atomic::from(head));
+                    table.store_bin(i, Owned::new(BinEntry::Node(node)));
+                }
+            }
+            true
+        });
+        if done {
+            return;
+        }
+    }
+}
+
+#[test]
+fn insert_and_find() {
+    let mut table = Table::new(4);
+    assert_eq!(table.len(), 4);
+    {
+        let guard = epoch::pin();
+        for key in 0..32 {
+            insert(&table, key, key * 10, &guard);
+        }
+        insert(&table, 3, 0, &guard);
+
+        for key in 0..32 {
+            // safety: entries are never removed from the table
+            let node = unsafe { table.get_node(key as u64, &key, &guard) }.unwrap();
+            assert_eq!(*node.key(), key);
+            assert_eq!(node.hash(), key as u64);
+            let value = node.value().load(Ordering::SeqCst, &guard);
+            let expected = if key == 3 { 0 } else { key * 10 };
+            assert_eq!(unsafe { value.deref() }, &expected);
+        }
+        assert!(unsafe { table.get_node(32, &32, &guard) }.is_none());
+    }
+    // safety: we own the table, and all entries and values were allocated with `Owned`
+    unsafe { table.drop_entries() };
+    assert!(table.is_empty());
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn concurrent_inserts() {
+    let table = Arc::new(Table::new(8));
+    let threads: Vec<_> = (0..4)
+        .map(|t| {
+            let table = Arc::clone(&table);
+            thread::spawn(move || {
+                let guard = epoch::pin();
+                for key in 0..256 {
+                    insert(&table, key, t, &guard);
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    let guard = epoch::pin();
+    for key in 0..256 {
+        // safety: entries are never removed from the table
+        assert!(unsafe { table.get_node(key as u64, &key, &guard) }.is_some());
+    }
+    drop(guard);
+    let mut table = Arc::try_unwrap(table).unwrap();
+    // safety: we own the table, and all entries and values were allocated with `Owned`
+    unsafe { table.drop_entries() };
+}
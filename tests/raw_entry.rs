@@ -0,0 +1,207 @@
+use flurry::*;
+use std::hash::{BuildHasher, BuildHasherDefault, Hash, Hasher};
+use std::sync::Arc;
+use std::thread;
+
+#[derive(Default)]
+struct ZeroHasher;
+
+impl Hasher for ZeroHasher {
+    fn finish(&self) -> u64 {
+        0
+    }
+    fn write(&mut self, _: &[u8]) {}
+}
+
+// `BuildHasher::hash_one` is newer than the oldest Rust version we support
+#[allow(clippy::manual_hash_one)]
+fn hash<K: Hash + ?Sized, S: BuildHasher>(hasher: &S, key: &K) -> u64 {
+    let mut h = hasher.build_hasher();
+    key.hash(&mut h);
+    h.finish()
+}
+
+#[test]
+fn lookups_match_get() {
+    let map = HashMap::new();
+    let guard = map.guard();
+    for i in 0..256 {
+        map.insert(i, i * 2, &guard);
+    }
+
+    for i in 0..300 {
+        let h = hash(map.hasher(), &i);
+        let expected = map.get_key_value(&i, &guard);
+        assert_eq!(map.raw_entry(&guard).from_key(&i), expected);
+        assert_eq!(
+            map.raw_entry(&guard).from_key_hashed_nocheck(h, &i),
+            expected
+        );
+        assert_eq!(map.raw_entry(&guard).from_hash(h, |k| *k == i), expected);
+    }
+}
+
+#[test]
+fn lookup_by_proxy() {
+    // a key type that cannot be borrowed as the type we look it up by
+    #[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+    struct Name {
+        first: String,
+        last: String,
+    }
+
+    let map = HashMap::new();
+    let guard = map.guard();
+    let name = Name {
+        first: String::from("Grace"),
+        last: String::from("Hopper"),
+    };
+    let h = hash(map.hasher(), &name);
+    map.insert(name, 1906, &guard);
+
+    let (first, last) = ("Grace", "Hopper");
+    let found = map
+        .raw_entry(&guard)
+        .from_hash(h, |k| k.first == first && k.last == last);
+    assert_eq!(found.map(|(_, v)| v), Some(&1906));
+    assert_eq!(
+        map.raw_entry(&guard).from_hash(h, |k| k.first == "Ada"),
+        None
+    );
+
+    let removed = map
+        .raw_entry_mut(&guard)
+        .remove_from_hash(h, |k| k.first == first && k.last == last);
+    assert_eq!(
+        removed.map(|(k, v)| (k.last.as_str(), *v)),
+        Some(("Hopper", 1906))
+    );
+    assert!(map.is_empty());
+}
+
+#[test]
+fn insert_and_remove_hashed() {
+    let map = HashMap::new();
+    let guard = map.guard();
+    for i in 0..128 {
+        let h = hash(map.hasher(), &i);
+        assert_eq!(
+            map.raw_entry_mut(&guard).insert_hashed_nocheck(h, i, i),
+            None
+        );
+    }
+    assert_eq!(map.len(), 128);
+    for i in 0..128 {
+        assert_eq!(map.get(&i, &guard), Some(&i));
+    }
+
+    let h = hash(map.hasher(), &7);
+    assert_eq!(
+        map.raw_entry_mut(&guard).insert_hashed_nocheck(h, 7, 70),
+        Some(&7)
+    );
+    let err = map
+        .raw_entry_mut(&guard)
+        .try_insert_hashed_nocheck(h, 7, 700)
+        .unwrap_err();
+    assert_eq!(err.current, &70);
+    assert_eq!(err.not_inserted, 700);
+
+    assert_eq!(
+        map.raw_entry_mut(&guard).remove_hashed_nocheck(h, &7),
+        Some((&7, &70))
+    );
+    assert_eq!(map.raw_entry_mut(&guard).remove_hashed_nocheck(h, &7), None);
+    assert_eq!(
+        map.raw_entry_mut(&guard)
+            .try_insert_hashed_nocheck(h, 7, 700),
+        Ok(&700)
+    );
+    assert_eq!(map.len(), 128);
+}
+
+#[test]
+fn tree_bins() {
+    let map = HashMap::<usize, usize, _>::with_hasher(BuildHasherDefault::<ZeroHasher>::default());
+    let guard = map.guard();
+    for i in 0..64 {
+        map.raw_entry_mut(&guard).insert_hashed_nocheck(0, i, i);
+    }
+    let trees = if cfg!(feature = "unordered-keys") {
+        0
+    } else {
+        1
+    };
+    assert_eq!(map.tree_bins(&guard).count(), trees);
+
+    for i in 0..64 {
+        assert_eq!(
+            map.raw_entry(&guard).from_key_hashed_nocheck(0, &i),
+            Some((&i, &i))
+        );
+        assert_eq!(
+            map.raw_entry(&guard).from_hash(0, |k| *k == i),
+            Some((&i, &i))
+        );
+    }
+    // a wrong hash does not find anything
+    assert_eq!(map.raw_entry(&guard).from_hash(1, |k| *k == 0), None);
+
+    for i in (0..64).step_by(2) {
+        assert_eq!(
+            map.raw_entry_mut(&guard).remove_from_hash(0, |k| *k == i),
+            Some((&i, &i))
+        );
+    }
+    assert_eq!(map.len(), 32);
+    assert!(map.iter(&guard).all(|(k, _)| k % 2 == 1));
+}
+
+#[test]
+fn raw_entry_ref() {
+    let map = HashMap::new();
+    let h = hash(map.hasher(), &1);
+    let map = map.pin();
+    assert_eq!(map.raw_entry_mut().insert_hashed_nocheck(h, 1, "one"), None);
+    assert_eq!(
+        map.raw_entry().from_hash(h, |k| *k == 1),
+        Some((&1, &"one"))
+    );
+    assert_eq!(
+        map.raw_entry_mut().remove_hashed_nocheck(h, &1),
+        Some((&1, &"one"))
+    );
+    assert!(map.is_empty());
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn concurrent_insert_hashed() {
+    let map = Arc::new(HashMap::<usize, usize>::new());
+    let threads: Vec<_> = (0..4)
+        .map(|t| {
+            let map = Arc::clone(&map);
+            thread::spawn(move || {
+                let guard = map.guard();
+                for i in (t * 1024)..((t + 1) * 1024) {
+                    let h = hash(map.hasher(), &i);
+                    map.raw_entry_mut(&guard).insert_hashed_nocheck(h, i, i);
+                    // the entry can be found while other threads resize the table
+                    assert_eq!(
+                        map.raw_entry(&guard).from_hash(h, |k| *k == i),
+                        Some((&i, &i))
+                    );
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    let guard = map.guard();
+    assert_eq!(map.len(), 4096);
+    for i in 0..4096 {
+        assert_eq!(map.get(&i, &guard), Some(&i));
+    }
+}
@@ -0,0 +1,168 @@
+use flurry::record::{self, Op, Recorder};
+use flurry::*;
+use std::hash::BuildHasherDefault;
+use std::sync::Arc;
+
+#[derive(Default)]
+struct ZeroHasher;
+
+impl std::hash::Hasher for ZeroHasher {
+    fn finish(&self) -> u64 {
+        0
+    }
+    fn write(&mut self, _: &[u8]) {}
+}
+
+#[test]
+fn records_all_changes() {
+    let recorder = Arc::new(Recorder::new(16));
+    let map = HashMap::<usize, usize>::new().with_recorder(Arc::clone(&recorder));
+    let guard = map.guard();
+    map.insert(1, 1, &guard);
+    map.insert(2, 2, &guard);
+    map.insert(1, 10, &guard);
+    assert!(map.try_insert(2, 20, &guard).is_err());
+    map.compute_if_present(&2, |_, v| Some(v + 1), &guard);
+    map.compute_if_present(&2, |_, _| None, &guard);
+    map.remove(&1, &guard);
+    map.remove(&1, &guard);
+
+    let ops: Vec<_> = recorder.records().into_iter().map(|r| r.op).collect();
+    assert_eq!(
+        ops,
+        vec![
+            Op::Insert { key: 1, value: 1 },
+            Op::Insert { key: 2, value: 2 },
+            Op::Replace {
+                key: 1,
+                old: 1,
+                value: 10
+            },
+            Op::Replace {
+                key: 2,
+                old: 2,
+                value: 3
+            },
+            Op::Remove { key: 2, old: 3 },
+            Op::Remove { key: 1, old: 10 },
+        ]
+    );
+}
+
+#[test]
+fn records_lent_values() {
+    let recorder = Arc::new(Recorder::new(16));
+    let mut map = HashMap::<usize, usize>::new().with_recorder(Arc::clone(&recorder));
+    map.insert(1, 1, &map.guard());
+    *map.get_mut(&1).unwrap() += 1;
+    for (_, v) in map.iter_mut() {
+        *v += 1;
+    }
+
+    let ops: Vec<_> = recorder.records().into_iter().map(|r| r.op).collect();
+    assert_eq!(
+        ops,
+        vec![
+            Op::Insert { key: 1, value: 1 },
+            Op::Lend { key: 1, old: 1 },
+            Op::Lend { key: 1, old: 2 },
+        ]
+    );
+}
+
+#[test]
+fn records_clear_and_retain() {
+    let recorder = Arc::new(Recorder::new(64));
+    let map = HashMap::<usize, usize>::new().with_recorder(Arc::clone(&recorder));
+    let guard = map.guard();
+    for i in 0..8 {
+        map.insert(i, i, &guard);
+    }
+    map.retain(|&k, _| k % 2 == 0, &guard);
+    assert_eq!(recorder.recorded(), 12);
+    map.clear(&guard);
+    assert_eq!(recorder.recorded(), 16);
+
+    let removed = recorder
+        .records()
+        .into_iter()
+        .filter(|r| matches!(r.op, Op::Remove { .. }))
+        .count();
+    assert_eq!(removed, 8);
+}
+
+#[test]
+fn records_tree_bins() {
+    let recorder = Arc::new(Recorder::new(128));
+    let map = HashMap::<usize, usize, BuildHasherDefault<ZeroHasher>>::default()
+        .with_recorder(Arc::clone(&recorder));
+    let guard = map.guard();
+    // all keys land in the same bin, which is converted into a tree bin
+    for i in 0..20 {
+        map.insert(i, i, &guard);
+    }
+    map.insert(3, 30, &guard);
+    map.remove(&4, &guard);
+    map.clear(&guard);
+
+    let records = recorder.records();
+    assert_eq!(records.len(), 20 + 1 + 1 + 19);
+    assert_eq!(records[19].op, Op::Insert { key: 19, value: 19 });
+    assert_eq!(
+        records[20].op,
+        Op::Replace {
+            key: 3,
+            old: 3,
+            value: 30
+        }
+    );
+    assert_eq!(records[21].op, Op::Remove { key: 4, old: 4 });
+}
+
+#[test]
+fn evicts_oldest() {
+    let recorder = Arc::new(Recorder::new(4));
+    let map = HashMap::<usize, usize>::new().with_recorder(Arc::clone(&recorder));
+    let guard = map.guard();
+    for i in 0..10 {
+        map.insert(i, i, &guard);
+    }
+    let records = recorder.records();
+    assert_eq!(records.len(), 4);
+    assert_eq!(recorder.recorded(), 10);
+    assert_eq!(recorder.evicted(), 6);
+    let timestamps: Vec<_> = records.iter().map(|r| r.timestamp).collect();
+    assert_eq!(timestamps, vec![6, 7, 8, 9]);
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn replay_concurrent() {
+    const NUM_THREADS: usize = 4;
+    const NUM_KEYS: usize = 64;
+    let recorder = Arc::new(Recorder::new(NUM_THREADS * NUM_KEYS * 4));
+    let map = Arc::new(HashMap::<usize, usize>::new().with_recorder(Arc::clone(&recorder)));
+
+    let handles: Vec<_> = (0..NUM_THREADS)
+        .map(|t| {
+            let map = Arc::clone(&map);
+            std::thread::spawn(move || {
+                let guard = map.guard();
+                for i in 0..NUM_KEYS {
+                    map.insert(i, t, &guard);
+                    if i % 3 == t % 3 {
+                        map.remove(&i, &guard);
+                    }
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    assert_eq!(recorder.evicted(), 0);
+    let copy = HashMap::new();
+    record::replay(&recorder.records(), &copy, &copy.guard());
+    assert_eq!(&copy, &*map);
+}
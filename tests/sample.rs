@@ -0,0 +1,76 @@
+#![cfg(feature = "rand")]
+
+use flurry::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::collections::HashSet as StdSet;
+use std::hash::{BuildHasherDefault, Hasher};
+
+#[derive(Default)]
+struct ZeroHasher;
+
+impl Hasher for ZeroHasher {
+    fn finish(&self) -> u64 {
+        0
+    }
+    fn write(&mut self, _: &[u8]) {}
+}
+
+#[test]
+fn distinct_entries() {
+    let map: HashMap<_, _> = (0..1024).map(|i| (i, i * 2)).collect();
+    let guard = map.guard();
+    let mut rng = StdRng::seed_from_u64(42);
+    let sample = map.sample(32, &mut rng, &guard);
+    assert_eq!(sample.len(), 32);
+    assert!(sample.iter().all(|(k, v)| **v == **k * 2));
+    let keys: StdSet<_> = sample.iter().map(|(k, _)| **k).collect();
+    assert_eq!(keys.len(), 32);
+}
+
+#[test]
+fn small_and_empty_maps() {
+    let mut rng = StdRng::seed_from_u64(42);
+    // fixed seeds keep the keys in the same bins on every run, so that the probes find them
+    let map = HashMap::<usize, usize, _>::with_hasher(DefaultHashBuilder::with_seeds(1, 2));
+    let guard = map.guard();
+    assert!(map.sample(8, &mut rng, &guard).is_empty());
+
+    map.insert(1, 1, &guard);
+    map.insert(2, 2, &guard);
+    assert_eq!(map.sample(0, &mut rng, &guard), []);
+    let mut sample = map.sample(8, &mut rng, &guard);
+    sample.sort();
+    assert_eq!(sample, [(&1, &1), (&2, &2)]);
+}
+
+#[test]
+fn roughly_uniform() {
+    let map: HashMap<_, _> = (0..64).map(|i| (i, ())).collect();
+    let guard = map.guard();
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut hits = vec![0; 64];
+    for _ in 0..6400 {
+        for (k, _) in map.sample(1, &mut rng, &guard) {
+            hits[*k] += 1;
+        }
+    }
+    // every entry comes up, and none comes up much more often than the others
+    assert!(hits.iter().all(|&h| h > 20 && h < 400), "{:?}", hits);
+}
+
+#[test]
+fn tree_bins() {
+    let map = HashMap::<usize, usize, _>::with_hasher(BuildHasherDefault::<ZeroHasher>::default());
+    let map = map.pin();
+    for i in 0..64 {
+        map.insert(i, i);
+    }
+    let mut rng = StdRng::seed_from_u64(42);
+    // all entries are in one of 64 bins, so most probes find nothing
+    let sample = map.sample(16, &mut rng);
+    assert!(!sample.is_empty() && sample.len() <= 16);
+    assert!(sample.iter().all(|(k, v)| k == v));
+    let keys: StdSet<_> = sample.iter().map(|(k, _)| **k).collect();
+    assert_eq!(keys.len(), sample.len());
+}
@@ -0,0 +1,64 @@
+#![cfg(feature = "schedule-hooks")]
+
+use flurry::schedule::{self, Point};
+use flurry::HashMap;
+use std::cell::RefCell;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+
+// A cooperative scheduler: only the thread that holds `BATON` runs, and it only hands the baton
+// on at a synchronization point. A thread that blocked for real while holding the baton would
+// stop every other thread, including the one it waits for.
+static BATON: Mutex<()> = Mutex::new(());
+
+thread_local! {
+    static HELD: RefCell<Option<MutexGuard<'static, ()>>> = const { RefCell::new(None) };
+}
+
+fn take_baton() {
+    let baton = BATON.lock().unwrap_or_else(|e| e.into_inner());
+    HELD.with(|held| *held.borrow_mut() = Some(baton));
+}
+
+fn drop_baton() {
+    HELD.with(|held| held.borrow_mut().take());
+}
+
+fn hand_on(_: Point) {
+    drop_baton();
+    thread::yield_now();
+    take_baton();
+}
+
+#[test]
+fn contended_bins_under_a_cooperative_scheduler() {
+    const THREADS: u64 = 4;
+    const ROUNDS: u64 = 200;
+
+    let map = Arc::new(HashMap::<u64, u64>::new());
+    schedule::set_hook(Some(hand_on));
+    let threads: Vec<_> = (0..THREADS)
+        .map(|_| {
+            let map = Arc::clone(&map);
+            thread::spawn(move || {
+                take_baton();
+                let guard = map.guard();
+                // every thread updates the same few keys, so that they wait for each other's
+                // bin locks while the holder of the lock is stopped at a hook
+                for i in 0..ROUNDS {
+                    map.merge(i % 4, 1, |v, one| v + one, &guard);
+                }
+                drop(guard);
+                drop_baton();
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    schedule::set_hook(None);
+
+    let guard = map.guard();
+    let total: u64 = (0..4).map(|k| *map.get(&k, &guard).unwrap()).sum();
+    assert_eq!(total, THREADS * ROUNDS);
+}
@@ -0,0 +1,95 @@
+use flurry::scope;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct Tracked<'a>(&'a AtomicUsize);
+
+impl Drop for Tracked<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn borrowed_keys_and_values() {
+    let keys: Vec<String> = (0..64).map(|i| i.to_string()).collect();
+    let values: Vec<usize> = (0..64).collect();
+
+    let sum = scope(|s| {
+        let map = s.map();
+        let guard = map.guard();
+        for (k, v) in keys.iter().zip(&values) {
+            assert_eq!(map.insert(k.as_str(), v, &guard), None);
+        }
+        assert_eq!(map.len(), 64);
+        assert!(map.contains_key("42", &guard));
+        assert_eq!(map.get("42", &guard), Some(&&42));
+        assert_eq!(map.insert("42", &values[0], &guard), Some(&&42));
+        assert_eq!(map.remove("42", &guard), Some(&&0));
+        assert!(!map.contains_key("42", &guard));
+        map.iter(&guard).map(|(_, &&v)| v).sum::<usize>()
+    });
+    assert_eq!(sum, (0..64).sum::<usize>() - 42);
+}
+
+#[test]
+fn garbage_is_dropped_before_scope_returns() {
+    let dropped = AtomicUsize::new(0);
+
+    scope(|s| {
+        let map = s.map();
+        let guard = s.guard();
+        for i in 0..128 {
+            map.insert(i, Tracked(&dropped), &guard);
+        }
+        for i in 0..64 {
+            map.remove(&i, &guard);
+        }
+        map.clear(&guard);
+        assert!(map.is_empty());
+    });
+    assert_eq!(dropped.load(Ordering::SeqCst), 128);
+}
+
+#[test]
+fn maps_share_the_guards_of_their_scope() {
+    let names = ["ada", "grace", "barbara"];
+
+    scope(|s| {
+        let ids = s.map();
+        let lengths = s.map();
+        let guard = ids.guard();
+        for (i, name) in names.iter().enumerate() {
+            ids.insert(*name, i, &guard);
+            lengths.insert(i, name.len(), &guard);
+        }
+        let id = ids.get("grace", &guard).unwrap();
+        assert_eq!(lengths.get(id, &guard), Some(&5));
+        ids.remove("ada", &guard);
+        ids.remove("barbara", &guard);
+        assert_eq!(format!("{:?}", ids), r#"{"grace": 1}"#);
+    });
+}
+
+#[test]
+fn scoped_threads() {
+    let words: Vec<String> = (0..1000).map(|i| format!("word{}", i % 100)).collect();
+    let dropped = AtomicUsize::new(0);
+
+    scope(|s| {
+        let counts = s.map();
+        std::thread::scope(|t| {
+            for chunk in words.chunks(250) {
+                let counts = &counts;
+                let dropped = &dropped;
+                t.spawn(move || {
+                    let guard = counts.guard();
+                    for word in chunk {
+                        counts.insert(word.as_str(), Tracked(dropped), &guard);
+                    }
+                });
+            }
+        });
+        assert_eq!(counts.len(), 100);
+    });
+    assert_eq!(dropped.load(Ordering::SeqCst), 1000);
+}
@@ -320,3 +320,110 @@ fn from_iter_empty() {
 
     assert_eq!(set.len(), 0)
 }
+
+#[test]
+fn debug_limit() {
+    let set = HashSet::new().with_debug_limit(1);
+    set.pin().insert(1);
+    assert_eq!(format!("{:?}", set), "{1}");
+
+    set.pin().insert(2);
+    let debug = format!("{:?}", set);
+    assert!(
+        debug == "{1} and 1 more" || debug == "{2} and 1 more",
+        "{}",
+        debug
+    );
+}
+
+#[test]
+fn eq_across_hashers() {
+    let set: HashSet<_> = (0..16).collect();
+    let other = HashSet::with_hasher(std::collections::hash_map::RandomState::new());
+    assert_ne!(set, other);
+
+    for i in 0..16 {
+        other.pin().insert(i);
+    }
+    assert_eq!(set, other);
+    assert_eq!(set.pin(), other.pin());
+    assert_eq!(set, other.pin());
+}
+
+#[test]
+fn set_operations_into() {
+    let a: HashSet<_> = (0..10).collect();
+    let b: HashSet<_> = (5..20).collect();
+    let (a, b) = (a.pin(), b.pin());
+
+    let mut union = Vec::new();
+    a.union_into(&b, &mut union);
+    union.sort();
+    assert_eq!(union, (0..20).collect::<Vec<_>>());
+
+    let mut intersection = std::collections::HashSet::new();
+    a.intersection_into(&b, &mut intersection);
+    assert_eq!(intersection, (5..10).collect());
+    intersection.clear();
+    b.intersection_into(&a, &mut intersection);
+    assert_eq!(intersection, (5..10).collect());
+
+    let mut difference = Vec::new();
+    a.difference_into(&b, &mut difference);
+    b.difference_into(&a, &mut difference);
+    difference.sort();
+    assert_eq!(difference, (0..5).chain(10..20).collect::<Vec<_>>());
+}
+
+#[test]
+fn subset_superset_disjoint() {
+    let small: HashSet<i32> = (0..10).collect();
+    let large: HashSet<i32> = (0..1000).collect();
+    let odd: HashSet<i32> = (0..1000).filter(|x| x % 2 == 1).collect();
+    let (small, large, odd) = (small.pin(), large.pin(), odd.pin());
+
+    assert!(small.is_subset(&large));
+    assert!(!large.is_subset(&small));
+    assert!(large.is_superset(&small));
+    assert!(!small.is_superset(&large));
+    assert!(!odd.is_subset(&small));
+
+    assert!(!small.is_disjoint(&odd));
+    assert!(!odd.is_disjoint(&small));
+    small.retain(|x| x % 2 == 0);
+    assert!(small.is_disjoint(&odd));
+    assert!(odd.is_disjoint(&small));
+}
+
+#[test]
+fn take_any() {
+    let set: HashSet<i32> = (0..10).collect();
+    let set = set.pin();
+    let mut taken = Vec::new();
+    while let Some(&value) = set.take_any() {
+        taken.push(value);
+    }
+    taken.sort_unstable();
+    assert_eq!(taken, (0..10).collect::<Vec<_>>());
+    assert!(set.is_empty());
+}
+
+#[test]
+fn into_iter() {
+    let set: HashSet<String> = (0..10).map(|x| x.to_string()).collect();
+    let mut values: Vec<String> = set.into_iter().collect();
+    values.sort_by_key(|v| v.parse::<i32>().unwrap());
+    assert_eq!(values, (0..10).map(|x| x.to_string()).collect::<Vec<_>>());
+}
+
+#[test]
+fn extend_pinned() {
+    let set = HashSet::<usize>::new();
+    let mut set = set.pin();
+    set.extend(vec![42, 16]);
+    set.extend(vec![&38, &16]);
+
+    let mut collected: Vec<_> = set.iter().copied().collect();
+    collected.sort();
+    assert_eq!(collected, vec![16, 38, 42]);
+}
@@ -0,0 +1,142 @@
+#![cfg(feature = "shadow")]
+
+use flurry::*;
+use std::hash::BuildHasherDefault;
+use std::sync::Arc;
+
+#[derive(Default)]
+struct ZeroHasher;
+
+impl std::hash::Hasher for ZeroHasher {
+    fn finish(&self) -> u64 {
+        0
+    }
+    fn write(&mut self, _: &[u8]) {}
+}
+
+#[test]
+fn consistent_operations() {
+    let map = HashMap::<usize, usize>::new();
+    map.insert(100, 100, &map.guard());
+    let map = map.with_shadow_checking();
+    let guard = map.guard();
+    assert_eq!(map.get(&100, &guard), Some(&100));
+    for i in 0..64 {
+        map.insert(i, i, &guard);
+    }
+    for i in 0..64 {
+        assert_eq!(map.get(&i, &guard), Some(&i));
+    }
+    assert!(map.try_insert(1, 10, &guard).is_err());
+    map.compute_if_present(&2, |_, v| Some(v * 2), &guard);
+    map.compute_if_present(&3, |_, _| None, &guard);
+    assert_eq!(map.get(&2, &guard), Some(&4));
+    assert!(!map.contains_key(&3, &guard));
+    map.retain(|&k, _| k % 2 == 0, &guard);
+    assert!(!map.contains_key(&5, &guard));
+    map.clear(&guard);
+    assert_eq!(map.get(&100, &guard), None);
+}
+
+#[test]
+fn consistent_after_get_mut() {
+    let mut map = HashMap::<usize, usize>::new().with_shadow_checking();
+    map.insert(1, 1, &map.guard());
+    *map.get_mut(&1).unwrap() = 10;
+    assert_eq!(map.get(&1, &map.guard()), Some(&10));
+    // once the key is changed again, its value is checked as usual
+    assert_eq!(map.insert(1, 2, &map.guard()), Some(&10));
+    assert_eq!(map.get(&1, &map.guard()), Some(&2));
+    for v in map.values_mut() {
+        *v += 1;
+    }
+    assert_eq!(map.get(&1, &map.guard()), Some(&3));
+    assert_eq!(map.remove(&1, &map.guard()), Some(&3));
+    assert_eq!(map.get(&1, &map.guard()), None);
+}
+
+#[test]
+fn consistent_tree_bins() {
+    let map =
+        HashMap::<usize, usize, BuildHasherDefault<ZeroHasher>>::default().with_shadow_checking();
+    let guard = map.guard();
+    for i in 0..20 {
+        map.insert(i, i, &guard);
+    }
+    map.insert(3, 30, &guard);
+    map.remove(&4, &guard);
+    assert_eq!(map.get(&3, &guard), Some(&30));
+    assert_eq!(map.get(&4, &guard), None);
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn consistent_concurrent() {
+    let map = Arc::new(HashMap::<usize, usize>::new().with_shadow_checking());
+    let handles: Vec<_> = (0..4)
+        .map(|t| {
+            let map = Arc::clone(&map);
+            std::thread::spawn(move || {
+                let guard = map.guard();
+                for i in 0..256 {
+                    map.insert(i, t, &guard);
+                    map.get(&(i / 2), &guard);
+                    if i % 4 == t {
+                        map.remove(&i, &guard);
+                    }
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+// these rely on tree bins, which the `unordered-keys` feature turns off
+#[cfg(not(feature = "unordered-keys"))]
+mod trees {
+    use super::*;
+    use std::cmp::Ordering;
+    use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
+
+    /// When set, `Flippable` keys are ordered in reverse.
+    static FLIPPED: AtomicBool = AtomicBool::new(false);
+
+    /// A key whose ordering changes while it is in the map.
+    #[derive(Debug, Clone, Hash, PartialEq, Eq)]
+    struct Flippable(usize);
+
+    impl PartialOrd for Flippable {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Flippable {
+        fn cmp(&self, other: &Self) -> Ordering {
+            if FLIPPED.load(SeqCst) {
+                other.0.cmp(&self.0)
+            } else {
+                self.0.cmp(&other.0)
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "shadow check failed")]
+    fn detects_divergence() {
+        let map = HashMap::<Flippable, usize, BuildHasherDefault<ZeroHasher>>::default()
+            .with_shadow_checking();
+        let guard = map.guard();
+        // all keys end up in a single tree bin, which is ordered by `Ord`
+        for i in 0..20 {
+            map.insert(Flippable(i), i, &guard);
+        }
+        // after the order changes, lookups take wrong turns in the tree and miss keys
+        FLIPPED.store(true, SeqCst);
+        for i in 0..20 {
+            map.get(&Flippable(i), &guard);
+        }
+    }
+}
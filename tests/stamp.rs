@@ -0,0 +1,121 @@
+use flurry::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+#[test]
+fn valid_until_changed() {
+    let map = HashMap::new().with_read_stamps();
+    let guard = map.guard();
+    map.insert(1, 1, &guard);
+
+    let stamp = map.read_stamp();
+    assert!(map.validate_stamp(stamp));
+    assert_eq!(map.get_validated(&1, stamp, &guard), Ok(Some(&1)));
+    assert_eq!(map.get_validated(&2, stamp, &guard), Ok(None));
+
+    // lookups do not invalidate the stamp
+    assert_eq!(map.get(&1, &guard), Some(&1));
+    assert!(map.validate_stamp(stamp));
+
+    map.insert(2, 2, &guard);
+    assert!(!map.validate_stamp(stamp));
+    assert_eq!(map.get_validated(&1, stamp, &guard), Err(StaleStampError));
+    assert_eq!(
+        map.get_validated(&2, map.read_stamp(), &guard),
+        Ok(Some(&2))
+    );
+}
+
+#[test]
+fn invalidated_by_every_change() {
+    let map = HashMap::new().with_read_stamps();
+    let guard = map.guard();
+    map.insert(1, 1, &guard);
+
+    type Change = Box<dyn Fn(&HashMap<i32, i32>)>;
+    let changes: Vec<Change> = vec![
+        Box::new(|map| {
+            map.pin().insert(1, 2);
+        }),
+        Box::new(|map| {
+            map.pin().compute_if_present(&1, |_, v| Some(v + 1));
+        }),
+        Box::new(|map| {
+            map.pin().remove(&1);
+        }),
+        Box::new(|map| {
+            map.pin().insert(1, 1);
+        }),
+        Box::new(|map| {
+            let mut batch = WriteBatch::new();
+            batch.insert(2, 2);
+            map.pin().apply_batch(batch);
+        }),
+        Box::new(|map| map.pin().clear()),
+        // resizes invalidate stamps as well, even though they do not change any entries
+        Box::new(|map| map.pin().reserve(1000)),
+    ];
+    for change in changes {
+        let stamp = map.read_stamp();
+        change(&map);
+        assert!(!map.validate_stamp(stamp));
+    }
+}
+
+#[test]
+fn map_ref() {
+    let map = HashMap::new().with_read_stamps();
+    let map = map.pin();
+    map.insert(1, 1);
+    let stamp = map.read_stamp();
+    assert_eq!(map.get_validated(&1, stamp), Ok(Some(&1)));
+    map.remove(&1);
+    assert!(!map.validate_stamp(stamp));
+}
+
+#[test]
+#[should_panic(expected = "with_read_stamps")]
+fn requires_read_stamps() {
+    let map = HashMap::<usize, usize>::new();
+    let _ = map.read_stamp();
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn consistent_reads() {
+    // the writer always increments `a` before `b`, so every state of the map has b <= a <= b + 1
+    let map = Arc::new(HashMap::new().with_read_stamps());
+    map.pin().insert("a", 0);
+    map.pin().insert("b", 0);
+    let done = Arc::new(AtomicBool::new(false));
+
+    let writer = {
+        let map = Arc::clone(&map);
+        let done = Arc::clone(&done);
+        thread::spawn(move || {
+            for i in 1..10_000 {
+                map.pin().insert("a", i);
+                map.pin().insert("b", i);
+            }
+            done.store(true, Ordering::SeqCst);
+        })
+    };
+
+    while !done.load(Ordering::SeqCst) {
+        let guard = map.guard();
+        let stamp = map.read_stamp();
+        // read in the opposite order of the writer, which would see an inconsistent state
+        // without the stamp
+        let b = map.get_validated("b", stamp, &guard);
+        let a = map.get_validated("a", stamp, &guard);
+        if let (Ok(Some(&a)), Ok(Some(&b))) = (a, b) {
+            assert!(b <= a && a <= b + 1, "read a = {} and b = {}", a, b);
+        }
+    }
+    writer.join().unwrap();
+
+    let guard = map.guard();
+    let stamp = map.read_stamp();
+    assert_eq!(map.get_validated("a", stamp, &guard), Ok(Some(&9_999)));
+}
@@ -0,0 +1,175 @@
+#![cfg(feature = "async")]
+
+use flurry::stream::Stream;
+use flurry::*;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+fn noop_waker() -> Waker {
+    fn raw() -> RawWaker {
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+    static VTABLE: RawWakerVTable = RawWakerVTable::new(|_| raw(), |_| {}, |_| {}, |_| {});
+    unsafe { Waker::from_raw(raw()) }
+}
+
+/// Produces `n` items, in bursts of `burst` separated by `Poll::Pending`.
+struct Bursty {
+    next: usize,
+    n: usize,
+    burst: usize,
+    paused_at: Option<usize>,
+}
+
+impl Stream for Bursty {
+    type Item = (usize, usize);
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.next == self.n {
+            return Poll::Ready(None);
+        }
+        let burst_start = self.next / self.burst * self.burst;
+        if self.next == burst_start && self.paused_at != Some(self.next) {
+            self.paused_at = Some(self.next);
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        let i = self.next;
+        self.next += 1;
+        Poll::Ready(Some((i, i * 2)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = self.n - self.next;
+        (left, Some(left))
+    }
+}
+
+fn run<F: Future + Unpin>(mut future: F) -> (F::Output, usize) {
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    let mut pending = 0;
+    loop {
+        match Pin::new(&mut future).poll(&mut cx) {
+            Poll::Ready(output) => return (output, pending),
+            Poll::Pending => pending += 1,
+        }
+    }
+}
+
+#[test]
+fn populate_in_batches() {
+    let map = HashMap::new();
+    map.pin().insert(0, 1);
+    let stream = Bursty {
+        next: 0,
+        n: 100,
+        burst: 10,
+        paused_at: None,
+    };
+    let (inserted, pending) = run(map.populate_from(stream));
+    assert_eq!(inserted, 100);
+    assert_eq!(pending, 10);
+    assert_eq!(map.len(), 100);
+    let guard = map.guard();
+    for i in 0..100 {
+        assert_eq!(map.get(&i, &guard), Some(&(i * 2)));
+    }
+}
+
+#[test]
+fn populate_large_and_empty() {
+    let map = HashMap::new();
+    let mut stream = Bursty {
+        next: 0,
+        n: 5000,
+        burst: 5000,
+        paused_at: None,
+    };
+    let (inserted, _) = run(map.populate_from(&mut stream));
+    assert_eq!(inserted, 5000);
+    assert_eq!(map.len(), 5000);
+
+    // the stream is exhausted now
+    let (inserted, pending) = run(map.populate_from(Box::new(stream)));
+    assert_eq!((inserted, pending), (0, 0));
+}
+
+/// Drains `stream`, polling it with a waker that does nothing.
+fn drain<St: Stream + Unpin>(mut stream: St) -> Vec<St::Item> {
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    let mut items = Vec::new();
+    loop {
+        match Pin::new(&mut stream).poll_next(&mut cx) {
+            Poll::Ready(Some(item)) => items.push(item),
+            Poll::Ready(None) => return items,
+            Poll::Pending => {}
+        }
+    }
+}
+
+#[test]
+fn stream_entries() {
+    let map: HashMap<usize, String> = (0..5000).map(|i| (i, i.to_string())).collect();
+    let mut entries = drain(map.stream());
+    entries.sort();
+    entries.dedup();
+    assert_eq!(entries.len(), 5000);
+    for (i, (k, v)) in entries.into_iter().enumerate() {
+        assert_eq!((k, v), (i, i.to_string()));
+    }
+
+    let empty = HashMap::<usize, usize>::new();
+    assert!(drain(empty.stream()).is_empty());
+}
+
+#[test]
+fn stream_across_modifications() {
+    let map: HashMap<usize, usize> = (0..5000).map(|i| (i, i)).collect();
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    let mut stream = map.stream();
+    let mut seen = std::collections::HashSet::new();
+    let mut polls = 0;
+    while let Poll::Ready(Some((k, v))) = Pin::new(&mut stream).poll_next(&mut cx) {
+        assert_eq!(k, v);
+        seen.insert(k);
+        polls += 1;
+        // the stream holds no guard between polls, so the map can grow and shrink underneath it
+        if polls == 100 {
+            let guard = map.guard();
+            for i in 5000..20_000 {
+                map.insert(i, i, &guard);
+            }
+            for i in 10_000..20_000 {
+                map.remove(&i, &guard);
+            }
+        }
+    }
+    // every entry that was in the map all along is yielded
+    assert!((0..5000).all(|i| seen.contains(&i)));
+}
+
+#[test]
+#[cfg(feature = "futures")]
+fn stream_entries_as_futures_stream() {
+    fn drain_futures<St: futures_core::Stream + Unpin>(mut stream: St) -> Vec<St::Item> {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut items = Vec::new();
+        while let Poll::Ready(Some(item)) =
+            futures_core::Stream::poll_next(Pin::new(&mut stream), &mut cx)
+        {
+            items.push(item);
+        }
+        items
+    }
+
+    let map: HashMap<usize, usize> = (0..2000).map(|i| (i, i * 2)).collect();
+    let mut entries = drain_futures(map.stream());
+    entries.sort();
+    entries.dedup();
+    assert_eq!(entries, (0..2000).map(|i| (i, i * 2)).collect::<Vec<_>>());
+}
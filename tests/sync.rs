@@ -0,0 +1,92 @@
+use flurry::sync::{SyncHashMap, SyncHashSet};
+use std::sync::Arc;
+use std::thread;
+
+#[test]
+fn map() {
+    let map = SyncHashMap::new();
+    assert!(map.is_empty());
+    assert_eq!(map.insert(1, String::from("one")), None);
+    assert_eq!(
+        map.try_insert(1, String::from("uno")),
+        Err(("one".into(), "uno".into()))
+    );
+    assert_eq!(map.try_insert(2, String::from("two")), Ok(()));
+    assert_eq!(map.len(), 2);
+    assert_eq!(map.get(&1).as_deref(), Some("one"));
+    assert!(map.contains_key(&2));
+
+    assert_eq!(
+        map.compute_if_present(&2, |_, v| Some(v.to_uppercase()))
+            .as_deref(),
+        Some("TWO")
+    );
+    let mut entries = map.to_vec();
+    entries.sort();
+    assert_eq!(entries, vec![(1, "one".into()), (2, "TWO".into())]);
+
+    assert_eq!(map.retain(|&k, _| k == 1), 1);
+    assert_eq!(map.keys(), vec![1]);
+    assert_eq!(map.remove(&1).as_deref(), Some("one"));
+    assert_eq!(map.remove(&1), None);
+    assert!(map.values().is_empty());
+}
+
+#[test]
+fn set() {
+    let set = SyncHashSet::new();
+    assert!(set.insert(1));
+    assert!(!set.insert(1));
+    assert!(set.insert(2));
+    assert!(set.contains(&2));
+    assert_eq!(set.retain(|&v| v > 1), 1);
+    assert_eq!(set.to_vec(), vec![2]);
+    assert!(set.remove(&2));
+    assert!(!set.remove(&2));
+    set.insert(3);
+    set.clear();
+    assert!(set.is_empty());
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn concurrent() {
+    let map = Arc::new(SyncHashMap::new());
+    let threads: Vec<_> = (0..4)
+        .map(|t| {
+            let map = Arc::clone(&map);
+            thread::spawn(move || {
+                for i in 0..64 {
+                    map.insert(t * 64 + i, i);
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(map.len(), 256);
+    assert_eq!(map.get(&(3 * 64 + 5)), Some(5));
+}
+
+#[test]
+fn map_iter() {
+    let map: SyncHashMap<_, _> = (0..8).map(|i| (i, i.to_string())).collect();
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort();
+    assert_eq!(
+        entries,
+        (0..8).map(|i| (i, i.to_string())).collect::<Vec<_>>()
+    );
+
+    let mut n = 0;
+    for (k, v) in &map {
+        assert_eq!(k.to_string(), v);
+        n += 1;
+    }
+    assert_eq!(n, 8);
+
+    let mut owned: Vec<(usize, String)> = map.into_iter().collect();
+    owned.sort();
+    assert_eq!(owned, entries);
+}
@@ -0,0 +1,123 @@
+#![cfg(feature = "reclaim-trace")]
+
+use flurry::trace::{self, CollectorId, Event};
+use flurry::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+// the hook is process-global, so all scenarios live in one test to keep them from interfering
+
+static FLUSHED: AtomicUsize = AtomicUsize::new(0);
+static DESTROYED: AtomicUsize = AtomicUsize::new(0);
+static ADVANCED: AtomicUsize = AtomicUsize::new(0);
+static PENDING: AtomicUsize = AtomicUsize::new(0);
+
+// the collector of the maps created with a collector of their own
+static SEPARATE: Mutex<Option<CollectorId>> = Mutex::new(None);
+static SEPARATE_FLUSHED: AtomicUsize = AtomicUsize::new(0);
+static SEPARATE_DESTROYED: AtomicUsize = AtomicUsize::new(0);
+// flushes of any other collector, such as the one of a scope
+static OTHER_FLUSHED: AtomicUsize = AtomicUsize::new(0);
+
+fn hook(event: Event) {
+    let collector = match event {
+        Event::Flushed { collector, .. }
+        | Event::Destroyed { collector, .. }
+        | Event::EpochAdvanced { collector } => collector,
+    };
+    if collector == CollectorId::DEFAULT {
+        match event {
+            Event::Flushed { pending, .. } => {
+                FLUSHED.fetch_add(1, Ordering::SeqCst);
+                PENDING.store(pending, Ordering::SeqCst);
+            }
+            Event::Destroyed { count, .. } => {
+                DESTROYED.fetch_add(count, Ordering::SeqCst);
+            }
+            Event::EpochAdvanced { .. } => {
+                ADVANCED.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    } else if Some(collector) == *SEPARATE.lock().unwrap() {
+        match event {
+            Event::Flushed { .. } => {
+                SEPARATE_FLUSHED.fetch_add(1, Ordering::SeqCst);
+            }
+            Event::Destroyed { count, .. } => {
+                SEPARATE_DESTROYED.fetch_add(count, Ordering::SeqCst);
+            }
+            Event::EpochAdvanced { .. } => {}
+        }
+    } else if let Event::Flushed { .. } = event {
+        OTHER_FLUSHED.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn trace_reclamation() {
+    trace::set_hook(Some(hook));
+    let map = HashMap::<usize, usize>::new();
+    map.pin().insert(0, 0);
+    let flushed = FLUSHED.load(Ordering::SeqCst);
+    let bump = || {
+        let guard = map.guard();
+        map.compute_if_present(&0, |_, v| Some(v + 1), &guard);
+    };
+
+    // garbage is reclaimed as the epoch advances
+    for _ in 0..1024 {
+        bump();
+    }
+    assert_eq!(FLUSHED.load(Ordering::SeqCst) - flushed, 1024);
+    assert!(DESTROYED.load(Ordering::SeqCst) > 0);
+    let advanced = ADVANCED.load(Ordering::SeqCst);
+    assert!(advanced > 0);
+
+    // a long-lived guard stalls reclamation, and garbage piles up
+    let stalled = map.guard();
+    let destroyed = DESTROYED.load(Ordering::SeqCst);
+    for _ in 0..1024 {
+        bump();
+    }
+    assert!(PENDING.load(Ordering::SeqCst) >= 1024);
+    // nothing retired while the guard was held can have been destroyed
+    assert!(DESTROYED.load(Ordering::SeqCst) - destroyed < 1024);
+    assert!(ADVANCED.load(Ordering::SeqCst) - advanced <= 1);
+    drop(stalled);
+
+    // once it is gone, reclamation resumes
+    for _ in 0..1024 {
+        bump();
+    }
+    assert!(ADVANCED.load(Ordering::SeqCst) - advanced > 1);
+    assert!(PENDING.load(Ordering::SeqCst) < 1024);
+
+    // a map with a collector of its own is traced under that collector's id
+    let collector = epoch::Collector::new();
+    assert_ne!(collector.trace_id(), CollectorId::DEFAULT);
+    *SEPARATE.lock().unwrap() = Some(collector.trace_id());
+    let separate = HashMap::<usize, usize>::new().with_collector(&collector);
+    separate.pin().insert(0, 0);
+    let flushed = FLUSHED.load(Ordering::SeqCst);
+    let separate_flushed = SEPARATE_FLUSHED.load(Ordering::SeqCst);
+    for _ in 0..1024 {
+        let guard = separate.guard();
+        separate.compute_if_present(&0, |_, v| Some(v + 1), &guard);
+    }
+    assert_eq!(
+        SEPARATE_FLUSHED.load(Ordering::SeqCst) - separate_flushed,
+        1024
+    );
+    assert!(SEPARATE_DESTROYED.load(Ordering::SeqCst) > 0);
+    assert_eq!(FLUSHED.load(Ordering::SeqCst), flushed);
+
+    // and so are the maps of a scope
+    scope(|s| {
+        let scoped = s.map::<usize, usize>();
+        scoped.insert(0, 0, &s.guard());
+    });
+    assert!(OTHER_FLUSHED.load(Ordering::SeqCst) > 0);
+    assert_eq!(FLUSHED.load(Ordering::SeqCst), flushed);
+
+    trace::set_hook(None);
+}
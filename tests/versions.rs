@@ -0,0 +1,233 @@
+use flurry::*;
+use std::hash::BuildHasherDefault;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+#[derive(Default)]
+struct ZeroHasher;
+
+impl std::hash::Hasher for ZeroHasher {
+    fn finish(&self) -> u64 {
+        0
+    }
+    fn write(&mut self, _: &[u8]) {}
+}
+
+fn version<S: std::hash::BuildHasher>(map: &HashMap<usize, usize, S>, key: usize) -> u64 {
+    map.pin().get_versioned(&key).unwrap().1
+}
+
+#[test]
+fn changes_bump_version() {
+    let map = HashMap::new().with_entry_versions();
+    let guard = map.guard();
+    assert_eq!(map.get_versioned(&1, &guard), None);
+
+    map.insert(1, 1, &guard);
+    let inserted = version(&map, 1);
+    assert!(inserted > 0);
+
+    // changes to other entries leave the version alone
+    map.insert(2, 2, &guard);
+    map.remove(&2, &guard);
+    assert_eq!(version(&map, 1), inserted);
+
+    map.insert(1, 1, &guard);
+    let replaced = version(&map, 1);
+    assert!(replaced > inserted);
+
+    map.compute_if_present(&1, |_, v| Some(v + 1), &guard);
+    let computed = version(&map, 1);
+    assert!(computed > replaced);
+    assert_eq!(map.get_versioned(&1, &guard), Some((&2, computed)));
+
+    let mut batch = WriteBatch::new();
+    batch.insert(1, 3);
+    map.apply_batch(batch, &guard);
+    let batched = version(&map, 1);
+    assert!(batched > computed);
+
+    // a re-inserted key does not start over
+    map.remove(&1, &guard);
+    map.insert(1, 1, &guard);
+    assert!(version(&map, 1) > batched);
+}
+
+#[test]
+fn preserved_by_resize() {
+    let map = HashMap::new().with_entry_versions();
+    map.pin().insert(0, 0);
+    let before = version(&map, 0);
+    for i in 1..1000 {
+        map.pin().insert(i, i);
+    }
+    assert_eq!(version(&map, 0), before);
+}
+
+#[test]
+fn tree_bins() {
+    let map = HashMap::<usize, usize, _>::with_hasher(BuildHasherDefault::<ZeroHasher>::default())
+        .with_entry_versions();
+    let guard = map.guard();
+    map.insert(0, 0, &guard);
+    let before = version(&map, 0);
+
+    // turn the bin into a tree, and then back into a linear bin
+    for i in 1..20 {
+        map.insert(i, i, &guard);
+    }
+    assert_eq!(version(&map, 0), before);
+    map.insert(0, 1, &guard);
+    let replaced = version(&map, 0);
+    assert!(replaced > before);
+    assert!(version(&map, 19) > version(&map, 1));
+    for i in 1..20 {
+        map.remove(&i, &guard);
+    }
+    assert_eq!(map.get_versioned(&0, &guard), Some((&1, replaced)));
+}
+
+#[test]
+fn map_ref() {
+    let map = HashMap::new().with_entry_versions();
+    let map = map.pin();
+    map.insert("a", 1);
+    let (value, first) = map.get_versioned("a").unwrap();
+    assert_eq!(value, &1);
+    map.insert("a", 2);
+    assert!(map.get_versioned("a").unwrap().1 > first);
+}
+
+#[test]
+#[should_panic(expected = "with_entry_versions")]
+fn requires_entry_versions() {
+    let map = HashMap::<usize, usize>::new();
+    let _ = map.pin().get_versioned(&0);
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn consistent_with_values() {
+    let map = Arc::new(HashMap::new().with_entry_versions());
+    map.pin().insert(0, 0);
+    let done = Arc::new(AtomicBool::new(false));
+
+    let writer = {
+        let map = Arc::clone(&map);
+        let done = Arc::clone(&done);
+        thread::spawn(move || {
+            for i in 1..10_000 {
+                map.pin().insert(0, i);
+            }
+            done.store(true, Ordering::SeqCst);
+        })
+    };
+
+    let mut last = (0, 0);
+    while !done.load(Ordering::SeqCst) {
+        let (&value, version) = map.pin().get_versioned(&0).unwrap();
+        // versions and values only ever move forward together
+        if version == last.1 {
+            assert_eq!(value, last.0);
+        } else {
+            assert!(version > last.1);
+            assert!(value > last.0 || last.1 == 0);
+        }
+        last = (value, version);
+    }
+    writer.join().unwrap();
+}
+
+#[test]
+fn conditional_remove() {
+    let map = HashMap::new().with_entry_versions();
+    let guard = map.guard();
+    map.insert(1, 1, &guard);
+    let (_, version) = map.get_versioned(&1, &guard).unwrap();
+
+    assert_eq!(map.remove_if_version(&2, version, &guard), None);
+    assert_eq!(map.remove_if_version(&1, version + 1, &guard), None);
+    assert_eq!(map.len(), 1);
+    assert_eq!(map.remove_if_version(&1, version, &guard), Some(&1));
+    assert!(map.is_empty());
+    // the entry is gone, so even the right version no longer matches
+    assert_eq!(map.remove_if_version(&1, version, &guard), None);
+}
+
+#[test]
+fn conditional_replace() {
+    let map = HashMap::new().with_entry_versions();
+    let guard = map.guard();
+    assert_eq!(map.replace_if_version(&1, 1, 0, &guard), None);
+    assert!(map.is_empty());
+
+    map.insert(1, 1, &guard);
+    let (_, version) = map.get_versioned(&1, &guard).unwrap();
+    assert_eq!(map.replace_if_version(&1, 2, version, &guard), Some(&1));
+    let (value, replaced) = map.get_versioned(&1, &guard).unwrap();
+    assert_eq!(value, &2);
+    assert!(replaced > version);
+
+    // the old version is stale now
+    assert_eq!(map.replace_if_version(&1, 3, version, &guard), None);
+    assert_eq!(map.get(&1, &guard), Some(&2));
+}
+
+#[test]
+fn conditional_tree_bins() {
+    let map = HashMap::<usize, usize, _>::with_hasher(BuildHasherDefault::<ZeroHasher>::default())
+        .with_entry_versions();
+    let guard = map.guard();
+    for i in 0..20 {
+        map.insert(i, i, &guard);
+    }
+    let (_, version) = map.get_versioned(&5, &guard).unwrap();
+    assert_eq!(map.replace_if_version(&5, 50, version + 1, &guard), None);
+    assert_eq!(map.replace_if_version(&5, 50, version, &guard), Some(&5));
+    assert_eq!(map.remove_if_version(&5, version, &guard), None);
+    let (_, version) = map.get_versioned(&5, &guard).unwrap();
+    assert_eq!(map.remove_if_version(&5, version, &guard), Some(&50));
+    assert_eq!(map.len(), 19);
+}
+
+#[test]
+fn conditional_map_ref() {
+    let map = HashMap::new().with_entry_versions();
+    let map = map.pin();
+    map.insert("a", 1);
+    let (_, version) = map.get_versioned("a").unwrap();
+    assert_eq!(map.replace_if_version("a", 2, version), Some(&1));
+    let (_, version) = map.get_versioned("a").unwrap();
+    assert_eq!(map.remove_if_version("a", version), Some(&2));
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn optimistic_increments() {
+    const THREADS: usize = 4;
+    const INCREMENTS: usize = 1000;
+
+    let map = Arc::new(HashMap::new().with_entry_versions());
+    map.pin().insert("hits", 0);
+    let threads: Vec<_> = (0..THREADS)
+        .map(|_| {
+            let map = Arc::clone(&map);
+            thread::spawn(move || {
+                for _ in 0..INCREMENTS {
+                    let map = map.pin();
+                    loop {
+                        let (&hits, version) = map.get_versioned("hits").unwrap();
+                        if map.replace_if_version("hits", hits + 1, version).is_some() {
+                            break;
+                        }
+                    }
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(map.pin().get("hits"), Some(&(THREADS * INCREMENTS)));
+}
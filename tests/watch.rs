@@ -0,0 +1,139 @@
+use flurry::watch::Wake;
+use flurry::*;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn fires_on_insert_replace_remove() {
+    let map = HashMap::new();
+    let guard = map.guard();
+
+    let inserted = map.watch(1);
+    map.insert(1, 1, &guard);
+    assert!(inserted.is_changed());
+
+    let replaced = map.watch(1);
+    assert!(!replaced.is_changed());
+    map.insert(1, 2, &guard);
+    assert!(replaced.is_changed());
+
+    let computed = map.watch(1);
+    map.compute_if_present(&1, |_, v| Some(v + 1), &guard);
+    assert!(computed.is_changed());
+
+    let removed = map.watch(1);
+    map.remove(&1, &guard);
+    assert!(removed.is_changed());
+
+    let cleared = map.watch(2);
+    map.insert(2, 2, &guard);
+    let cleared_after = map.pin().watch(2);
+    map.clear(&guard);
+    assert!(cleared.is_changed());
+    assert!(cleared_after.is_changed());
+}
+
+#[test]
+fn ignores_other_keys() {
+    let map = HashMap::new();
+    let guard = map.guard();
+    let watch = map.watch(1);
+    map.insert(2, 2, &guard);
+    map.remove(&3, &guard);
+    // a no-op compute does not count as a change
+    map.compute_if_present(&1, |_, v| Some(v + 1), &guard);
+    assert!(!watch.is_changed());
+    assert!(!watch.wait_timeout(Duration::from_millis(10)));
+}
+
+#[test]
+fn dropped_watches_are_cleaned_up() {
+    let map = HashMap::new();
+    let guard = map.guard();
+    for i in 0..16 {
+        drop(map.watch(i));
+    }
+    map.insert(0, 0, &guard);
+    let watch = map.watch(1);
+    map.insert(1, 1, &guard);
+    assert!(watch.is_changed());
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn wait_for_other_thread() {
+    let map = Arc::new(HashMap::new());
+    let watch = map.watch("ready");
+    let writer = {
+        let map = Arc::clone(&map);
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            map.pin().insert("ready", ());
+        })
+    };
+    watch.wait();
+    assert!(map.pin().contains_key("ready"));
+    writer.join().unwrap();
+}
+
+struct Counter(Arc<AtomicUsize>);
+
+impl Wake for Counter {
+    fn wake(&self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn custom_wake() {
+    let map = HashMap::new();
+    let woken = Arc::new(AtomicUsize::new(0));
+    let watch = map.watch(1);
+    watch.on_change(Counter(Arc::clone(&woken)));
+    map.pin().insert(1, 1);
+    assert_eq!(woken.load(Ordering::SeqCst), 1);
+    // a changed watch wakes new wakers right away
+    watch.on_change(Counter(Arc::clone(&woken)));
+    assert_eq!(woken.load(Ordering::SeqCst), 2);
+}
+
+fn counting_waker(counter: &Arc<AtomicUsize>) -> Waker {
+    unsafe fn clone(data: *const ()) -> RawWaker {
+        Arc::increment_strong_count(data as *const AtomicUsize);
+        RawWaker::new(data, &VTABLE)
+    }
+    unsafe fn wake(data: *const ()) {
+        wake_by_ref(data);
+        drop_waker(data);
+    }
+    unsafe fn wake_by_ref(data: *const ()) {
+        (*(data as *const AtomicUsize)).fetch_add(1, Ordering::SeqCst);
+    }
+    unsafe fn drop_waker(data: *const ()) {
+        drop(Arc::from_raw(data as *const AtomicUsize));
+    }
+    static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake_by_ref, drop_waker);
+
+    let data = Arc::into_raw(Arc::clone(counter)) as *const ();
+    unsafe { Waker::from_raw(RawWaker::new(data, &VTABLE)) }
+}
+
+#[test]
+fn future() {
+    let map = HashMap::new();
+    let woken = Arc::new(AtomicUsize::new(0));
+    let waker = counting_waker(&woken);
+    let mut cx = Context::from_waker(&waker);
+
+    let mut watch = map.watch(1);
+    assert_eq!(Pin::new(&mut watch).poll(&mut cx), Poll::Pending);
+    assert_eq!(woken.load(Ordering::SeqCst), 0);
+    map.pin().insert(1, 1);
+    assert_eq!(woken.load(Ordering::SeqCst), 1);
+    assert_eq!(Pin::new(&mut watch).poll(&mut cx), Poll::Ready(()));
+}